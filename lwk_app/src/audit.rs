@@ -0,0 +1,147 @@
+//! Append-only audit log of the sensitive operations performed by the server
+//!
+//! Every entry commits to the previous one with its hash, so that removing or altering an entry
+//! breaks the chain and it's detected by [`verify()`].
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lwk_rpc_model::response::{AuditEntry, AuditOperation};
+use lwk_wollet::hashes::{sha256, Hash};
+
+use crate::Error;
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+pub struct AuditLog {
+    path: PathBuf,
+    next_index: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    /// Open the audit log at the given path, creating it at the first append if missing
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        let entries = read(&path)?;
+        let (next_index, last_hash) = match entries.last() {
+            Some(last) => (last.index + 1, last.hash.clone()),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        Ok(Self {
+            path,
+            next_index,
+            last_hash,
+        })
+    }
+
+    /// Append an entry to the log, syncing it to disk
+    pub fn append(
+        &mut self,
+        operation: AuditOperation,
+        actor: &str,
+        details: String,
+    ) -> Result<(), Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Clock may have gone backwards")
+            .as_secs();
+        let mut entry = AuditEntry {
+            index: self.next_index,
+            timestamp,
+            operation,
+            actor: actor.to_string(),
+            details,
+            prev_hash: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry_hash(&entry)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.sync_all()?;
+
+        self.next_index += 1;
+        self.last_hash = entry.hash;
+        Ok(())
+    }
+}
+
+/// Read all the entries of the audit log at the given path
+pub fn read(path: &Path) -> Result<Vec<AuditEntry>, Error> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let file = File::open(path)?;
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        entries.push(serde_json::from_str(&line?)?);
+    }
+    Ok(entries)
+}
+
+/// Check that the entries form an intact hash chain starting from the first entry
+pub fn verify(entries: &[AuditEntry]) -> bool {
+    let mut prev_hash = GENESIS_HASH;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.index != i as u64 || entry.prev_hash != prev_hash {
+            return false;
+        }
+        match entry_hash(entry) {
+            Ok(hash) if hash == entry.hash => (),
+            _ => return false,
+        }
+        prev_hash = &entry.hash;
+    }
+    true
+}
+
+/// The hash of the entry serialized without its own hash
+fn entry_hash(entry: &AuditEntry) -> Result<String, Error> {
+    let mut entry = entry.clone();
+    entry.hash = String::new();
+    let bytes = serde_json::to_vec(&entry)?;
+    Ok(sha256::Hash::hash(&bytes).to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn audit_log_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let mut log = AuditLog::open(path.clone()).unwrap();
+        log.append(AuditOperation::PsetCreated, "127.0.0.1", "wallet: w".into())
+            .unwrap();
+        log.append(AuditOperation::PsetSigned, "127.0.0.1", "signer: s".into())
+            .unwrap();
+
+        // Reopening continues the chain
+        let mut log = AuditLog::open(path.clone()).unwrap();
+        log.append(AuditOperation::Broadcast, "10.0.0.1", "txid: t".into())
+            .unwrap();
+
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[2].prev_hash, entries[1].hash);
+        assert!(verify(&entries));
+
+        let mut tampered = entries.clone();
+        tampered[1].actor = "10.0.0.2".into();
+        assert!(!verify(&tampered));
+
+        let mut removed = entries.clone();
+        removed.remove(1);
+        assert!(!verify(&removed));
+
+        assert!(!verify(&entries[1..]));
+    }
+}
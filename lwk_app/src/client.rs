@@ -467,6 +467,10 @@ impl Client {
         self.make_request(Method::Scan, None::<Box<RawValue>>)
     }
 
+    pub fn audit_log(&self) -> Result<response::AuditLog, Error> {
+        self.make_request(Method::AuditLog, None::<Box<RawValue>>)
+    }
+
     pub fn stop(&self) -> Result<Value, Error> {
         // TODO discriminate only stop error
         let _: Result<Value, Error> = self.make_request(Method::Stop, None::<Box<RawValue>>);
@@ -22,6 +22,13 @@ pub struct Config {
     pub tls: bool,
     pub validate_domain: bool,
 
+    /// If not empty, the certificate of the Electrum server must match one of these pins
+    pub electrum_tls_pins: Vec<lwk_wollet::TlsPin>,
+
+    /// Other Electrum servers to fail over to if `electrum_url` fails, using the same `tls` and
+    /// `validate_domain` settings
+    pub electrum_failover_urls: Vec<String>,
+
     pub explorer_url: String,
 
     // Unfortunately we cannot always derive the "api" url from "explorer_url", thus we need two separate values
@@ -41,6 +48,8 @@ impl Config {
             network: ElementsNetwork::LiquidTestnet,
             tls: true,
             validate_domain: true,
+            electrum_tls_pins: vec![],
+            electrum_failover_urls: vec![],
             explorer_url: "https://blockstream.info/liquidtestnet/".into(),
             esplora_api_url: "https://blockstream.info/liquidtestnet/api/".into(),
             registry_url: "https://assets-testnet.blockstream.info/".into(),
@@ -57,6 +66,8 @@ impl Config {
             network: ElementsNetwork::Liquid,
             tls: true,
             validate_domain: true,
+            electrum_tls_pins: vec![],
+            electrum_failover_urls: vec![],
             explorer_url: "https://blockstream.info/liquid/".into(),
             esplora_api_url: "https://blockstream.info/liquid/api/".into(),
             registry_url: "https://assets.blockstream.info/".into(),
@@ -77,6 +88,8 @@ impl Config {
             network: ElementsNetwork::ElementsRegtest { policy_asset },
             tls: false,
             validate_domain: false,
+            electrum_tls_pins: vec![],
+            electrum_failover_urls: vec![],
             explorer_url: "".into(),
             esplora_api_url: "".into(),
             registry_url: "".into(),
@@ -101,10 +114,10 @@ impl Config {
         Ok(path)
     }
 
-    /// Appends the network to the given datadir
+    /// Appends the network namespace to the given datadir
     pub fn datadir(&self) -> Result<PathBuf, Error> {
         let mut path: PathBuf = self.datadir.clone();
-        path.push(self.network.as_str());
+        path.push(self.network.namespace());
         fs::create_dir_all(&path)?;
         Ok(path)
     }
@@ -116,30 +129,48 @@ impl Config {
         Ok(path)
     }
 
+    /// Returns the path of the audit log file under datadir
+    pub fn audit_path(&self) -> Result<PathBuf, Error> {
+        let mut path = self.datadir()?;
+        path.push("audit.jsonl");
+        Ok(path)
+    }
+
     /// True if Liquid mainnet
     pub fn is_mainnet(&self) -> bool {
         matches!(self.network, ElementsNetwork::Liquid)
     }
 
-    fn electrum_url(&self) -> Result<lwk_wollet::ElectrumUrl, Error> {
-        Ok(
-            lwk_wollet::ElectrumUrl::new(&self.electrum_url, self.tls, self.validate_domain)
-                .map_err(lwk_wollet::Error::Url)?,
-        )
+    fn electrum_urls(&self) -> Result<Vec<lwk_wollet::ElectrumUrl>, Error> {
+        std::iter::once(&self.electrum_url)
+            .chain(&self.electrum_failover_urls)
+            .map(|url| {
+                lwk_wollet::ElectrumUrl::new(url, self.tls, self.validate_domain)
+                    .map_err(|e| lwk_wollet::Error::Url(e).into())
+            })
+            .collect()
     }
 
+    /// A new connection to the Electrum servers, the app keeps one alive in
+    /// [`crate::state::SharedElectrumClient`]
     pub fn electrum_client(&self) -> Result<lwk_wollet::ElectrumClient, Error> {
-        // TODO cache it instead of recreating every time
-        Ok(lwk_wollet::ElectrumClient::new(&self.electrum_url()?)?)
+        let options = lwk_wollet::ElectrumOptions {
+            tls_pins: self.electrum_tls_pins.clone(),
+            ..Default::default()
+        };
+        Ok(lwk_wollet::ElectrumClient::with_failover(
+            &self.electrum_urls()?,
+            options,
+        )?)
     }
 
+    /// A new Esplora client, used only by occasional requests such as the asset registry lookups
     pub fn esplora_client(&self) -> lwk_wollet::asyncr::EsploraClient {
-        // TODO cache it instead of recreating every time
         lwk_wollet::asyncr::EsploraClient::new(self.network, &self.esplora_api_url)
     }
 
+    /// A new blocking Esplora client, used only by occasional requests such as the explorer lookups
     pub fn esplora_blocking_client(&self) -> Result<lwk_wollet::blocking::EsploraClient, Error> {
-        // TODO cache it instead of recreating every time
         Ok(lwk_wollet::blocking::EsploraClient::new(
             self.esplora_api_url.trim_end_matches('/'),
             self.network,
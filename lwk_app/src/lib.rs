@@ -46,8 +46,10 @@ use lwk_wollet::Wollet;
 use lwk_wollet::WolletDescriptor;
 use serde_json::Value;
 
+use crate::audit::AuditLog;
 use crate::method::Method;
 use crate::state::{AppAsset, AppSigner, State};
+use lwk_rpc_model::response::AuditOperation;
 use lwk_rpc_model::{request, response};
 
 pub use client::Client;
@@ -55,6 +57,7 @@ pub use config::Config;
 pub use error::Error;
 pub use lwk_tiny_jrpc::RpcError;
 
+mod audit;
 mod client;
 mod config;
 pub mod consts;
@@ -105,6 +108,8 @@ impl App {
             tx_memos: Default::default(),
             addr_memos: Default::default(),
             do_persist: false,
+            audit: None,
+            electrum_client: Default::default(),
             scan_loops_started: 0,
             scan_loops_completed: 0,
             interrupt_wait: false,
@@ -141,7 +146,11 @@ impl App {
                 log::info!("There is no previous state at {path:?}");
             }
         }
-        state.lock().map_err(|e| e.to_string())?.do_persist = true;
+        {
+            let mut s = state.lock().map_err(|e| e.to_string())?;
+            s.do_persist = true;
+            s.audit = Some(AuditLog::open(self.config.audit_path()?)?);
+        }
 
         self.rpc = Some(rpc);
 
@@ -152,6 +161,11 @@ impl App {
         let scanning_interval = self.config.scanning_interval;
         let stop_interval = Duration::from_millis(100);
         let mut interval = Duration::ZERO; // Do not wait in the first scan loop
+        let electrum_client = state
+            .lock()
+            .map_err(|e| e.to_string())?
+            .electrum_client
+            .clone();
         let scanning_handle = std::thread::spawn(move || 'scan: loop {
             // Sleep for scanning_interval, but check stop signal every stop_interval
             'stop: loop {
@@ -180,8 +194,9 @@ impl App {
                 (wollets_names, config)
             };
 
-            match config.electrum_client() {
-                Ok(mut electrum_client) => {
+            // Keep the connection across scans, reconnecting if it was dropped
+            match electrum_client.keepalive(&config) {
+                Ok(()) => {
                     for name in wollets_names {
                         let state = match state_scanning
                             .lock()
@@ -193,7 +208,10 @@ impl App {
                             Err(_) => continue,
                         };
 
-                        match electrum_client.full_scan(&state) {
+                        // the connection is locked for every wallet, so requests can use it
+                        // between the scans
+                        let scan = electrum_client.with(&config, |c| Ok(c.full_scan(&state)?));
+                        match scan {
                             Ok(Some(update)) => {
                                 let mut s = state_scanning.lock().expect("state lock poison");
                                 let _ = match s.wollets.get_mut(&name) {
@@ -260,6 +278,13 @@ impl App {
     }
 }
 
+/// The unique id of the PSET, used to identify it in the audit log
+fn pset_id(pset: &PartiallySignedTransaction) -> String {
+    pset.unique_id()
+        .map(|id| id.to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
 fn method_handler(
     request: Request,
     state: Arc<Mutex<State>>,
@@ -370,6 +395,11 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             if r.persist {
                 s.persist(&request)?;
             }
+            let details = format!(
+                "signer: {} kind: software fingerprint: {}",
+                r.name, resp.fingerprint
+            );
+            s.audit(AuditOperation::SignerRegistered, &request, details)?;
             Response::result(request.id, serde_json::to_value(resp)?)
         }
         Method::SignerLoadJade => {
@@ -380,6 +410,11 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let resp: response::Signer = signer_response_from(&r.name, &signer)?;
             s.signers.insert(&r.name, signer)?;
             s.persist(&request)?;
+            let details = format!(
+                "signer: {} kind: jade fingerprint: {}",
+                r.name, resp.fingerprint
+            );
+            s.audit(AuditOperation::SignerRegistered, &request, details)?;
             Response::result(request.id, serde_json::to_value(resp)?)
         }
         Method::SignerLoadExternal => {
@@ -391,6 +426,11 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let resp: response::Signer = signer_response_from(&r.name, &signer)?;
             s.signers.insert(&r.name, signer)?;
             s.persist(&request)?;
+            let details = format!(
+                "signer: {} kind: external fingerprint: {}",
+                r.name, resp.fingerprint
+            );
+            s.audit(AuditOperation::SignerRegistered, &request, details)?;
             Response::result(request.id, serde_json::to_value(resp)?)
         }
         Method::SignerUnload => {
@@ -552,6 +592,8 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let mut tx = builder.finish()?;
 
             add_contracts(&mut tx, s.assets.iter());
+            let details = format!("wallet: {} pset: {}", r.name, pset_id(&tx));
+            s.audit(AuditOperation::PsetCreated, &request, details)?;
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
@@ -573,6 +615,8 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 .finish()?;
 
             add_contracts(&mut tx, s.assets.iter());
+            let details = format!("wallet: {} pset: {}", r.name, pset_id(&tx));
+            s.audit(AuditOperation::PsetCreated, &request, details)?;
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
@@ -646,10 +690,12 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 let descriptor: JadeDescriptor = (&descriptor).try_into()?;
                 jade.register_multisig(RegisterMultisigParams {
                     network,
-                    multisig_name: r.wallet,
+                    multisig_name: r.wallet.clone(),
                     descriptor,
                 })?;
             }
+            let details = format!("signer: {} wallet: {}", r.name, r.wallet);
+            s.audit(AuditOperation::MultisigRegistered, &request, details)?;
             Response::result(request.id, serde_json::to_value(response::Empty {})?)
         }
         Method::SignerXpub => {
@@ -679,10 +725,17 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let mut pset =
                 PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
 
-            signer.sign(&mut pset)?;
+            let signatures_added = signer.sign(&mut pset)?;
 
             // TODO we may want to return other details such as if signatures have been added
 
+            let details = format!(
+                "signer: {} pset: {} signatures_added: {signatures_added}",
+                r.name,
+                pset_id(&pset)
+            );
+            s.audit(AuditOperation::PsetSigned, &request, details)?;
+
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
@@ -692,16 +745,28 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
         }
         Method::WalletBroadcast => {
             let r: request::WalletBroadcast = serde_json::from_value(params)?;
-            let mut s = state.lock()?;
-
-            let wollet = s.wollets.get_mut(&r.name)?;
-            let mut pset =
-                PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
-            let tx = wollet.finalize(&mut pset)?;
-            let electrum_client = s.config.electrum_client()?;
+            let (tx, electrum_client, config) = {
+                let mut s = state.lock()?;
+
+                let wollet = s.wollets.get_mut(&r.name)?;
+                let mut pset =
+                    PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+                let tx = wollet.finalize(&mut pset)?;
+
+                // recorded before broadcasting, so that a transaction is never sent unaudited
+                let details = format!(
+                    "wallet: {} txid: {} dry_run: {}",
+                    r.name,
+                    tx.txid(),
+                    r.dry_run
+                );
+                s.audit(AuditOperation::Broadcast, &request, details)?;
+                (tx, s.electrum_client.clone(), s.config.clone())
+            };
 
+            // the state is unlocked, since the scanning thread locks it while using the connection
             if !r.dry_run {
-                electrum_client.broadcast(&tx)?;
+                electrum_client.with(&config, |c| Ok(c.broadcast(&tx)?))?;
             }
 
             Response::result(
@@ -946,6 +1011,8 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 )?
                 .fee_rate(r.fee_rate)
                 .finish()?;
+            let details = format!("wallet: {} pset: {}", r.name, pset_id(&tx));
+            s.audit(AuditOperation::PsetCreated, &request, details)?;
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
@@ -972,6 +1039,8 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 .finish()?;
 
             add_contracts(&mut pset, s.assets.iter());
+            let details = format!("wallet: {} pset: {}", r.name, pset_id(&pset));
+            s.audit(AuditOperation::PsetCreated, &request, details)?;
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
@@ -992,6 +1061,8 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 .finish()?;
 
             add_contracts(&mut pset, s.assets.iter());
+            let details = format!("wallet: {} pset: {}", r.name, pset_id(&pset));
+            s.audit(AuditOperation::PsetCreated, &request, details)?;
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
@@ -1122,6 +1193,15 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
         Method::Stop => {
             return Err(Error::Stop);
         }
+        Method::AuditLog => {
+            let path = state.lock()?.config.audit_path()?;
+            let entries = audit::read(&path)?;
+            let valid = audit::verify(&entries);
+            Response::result(
+                request.id,
+                serde_json::to_value(response::AuditLog { entries, valid })?,
+            )
+        }
         Method::AssetPublish => {
             let r: request::AssetPublish = serde_json::from_value(params)?;
             let asset_id =
@@ -20,6 +20,7 @@ pub enum Method {
     Version,
     Scan,
     Stop,
+    AuditLog,
     WalletLoad,
     WalletUnload,
     WalletList,
@@ -71,6 +72,7 @@ impl Method {
                 Method::Version => schema_for!(request::Empty),
                 Method::Scan => schema_for!(request::Empty),
                 Method::Stop => schema_for!(request::Empty),
+                Method::AuditLog => schema_for!(request::Empty),
                 Method::WalletLoad => schema_for!(request::WalletLoad),
                 Method::WalletUnload => schema_for!(request::WalletUnload),
                 Method::WalletList => schema_for!(request::Empty),
@@ -121,6 +123,7 @@ impl Method {
                 Method::Version => schema_for!(response::Version),
                 Method::Scan => schema_for!(response::Empty),
                 Method::Stop => schema_for!(request::Empty),
+                Method::AuditLog => schema_for!(response::AuditLog),
                 Method::WalletLoad => schema_for!(response::Wallet),
                 Method::WalletUnload => schema_for!(response::WalletUnload),
                 Method::WalletList => schema_for!(response::WalletList),
@@ -179,6 +182,7 @@ impl FromStr for Method {
             "version" => Method::Version,
             "scan" => Method::Scan,
             "stop" => Method::Stop,
+            "audit_log" => Method::AuditLog,
             "wallet_load" => Method::WalletLoad,
             "wallet_unload" => Method::WalletUnload,
             "wallet_list" => Method::WalletList,
@@ -237,6 +241,7 @@ impl std::fmt::Display for Method {
             Method::Version => "version",
             Method::Scan => "scan",
             Method::Stop => "stop",
+            Method::AuditLog => "audit_log",
             Method::WalletLoad => "wallet_load",
             Method::WalletUnload => "wallet_unload",
             Method::WalletList => "wallet_list",
@@ -3,11 +3,13 @@ use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use lwk_common::Signer;
 use lwk_jade::Jade;
 use lwk_rpc_model::request;
+use lwk_rpc_model::response::AuditOperation;
 use lwk_signer::AnySigner;
 use lwk_signer::SwSigner;
 use lwk_tiny_jrpc::Request;
@@ -22,6 +24,7 @@ use lwk_wollet::Contract;
 use lwk_wollet::Wollet;
 use serde::Serialize;
 
+use crate::audit::AuditLog;
 use crate::config::Config;
 use crate::method::Method;
 use crate::Error;
@@ -242,6 +245,7 @@ impl AppAsset {
                     id: None,
                     method: Method::AssetInsert.to_string(),
                     params: Some(serde_json::to_value(params).expect("derived")),
+                    remote_addr: None,
                 })
             }
             _ => None,
@@ -264,6 +268,46 @@ pub struct TxMemos(HashMap<String, HashMap<Txid, String>>);
 #[derive(Default)]
 pub struct AddrMemos(HashMap<String, HashMap<Address, String>>);
 
+/// The Electrum connection kept alive by the scanning thread and shared with the requests, so
+/// that they don't open a new connection every time
+#[derive(Default, Clone)]
+pub struct SharedElectrumClient(Arc<Mutex<Option<lwk_wollet::ElectrumClient>>>);
+
+impl SharedElectrumClient {
+    /// Ping the server to keep the connection alive, connecting if needed or if it was dropped
+    pub fn keepalive(&self, config: &Config) -> Result<(), Error> {
+        let mut client = self.lock();
+        if let Some(c) = client.as_mut() {
+            if c.keepalive().is_ok() {
+                return Ok(());
+            }
+        }
+        *client = Some(config.electrum_client()?);
+        Ok(())
+    }
+
+    /// Call `f` with the connection, connecting if needed
+    ///
+    /// The connection fails over to the other servers by itself if a request fails.
+    pub fn with<T>(
+        &self,
+        config: &Config,
+        f: impl FnOnce(&mut lwk_wollet::ElectrumClient) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut client = self.lock();
+        let client = match client.as_mut() {
+            Some(client) => client,
+            None => client.insert(config.electrum_client()?),
+        };
+        f(client)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<lwk_wollet::ElectrumClient>> {
+        // a panic while using the connection doesn't leave it inconsistent
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 pub struct State {
     // TODO: config is read-only, so it's not useful to wrap it in a mutex.
     // Ideally it should be in _another_ struct accessible by method_handler.
@@ -275,6 +319,12 @@ pub struct State {
     pub addr_memos: AddrMemos,
     pub do_persist: bool,
 
+    /// The audit log of sensitive operations, set when the server is started
+    pub audit: Option<AuditLog>,
+
+    /// The Electrum connection, lock it without holding the state lock
+    pub electrum_client: SharedElectrumClient,
+
     /// Number of scan loops started
     pub scan_loops_started: u32,
 
@@ -600,6 +650,30 @@ impl State {
         Ok(())
     }
 
+    /// Record a sensitive operation in the audit log
+    ///
+    /// The server doesn't authenticate its clients, the network address of the client which sent
+    /// the request is recorded as the actor of the operation.
+    /// Operations replayed while loading the previous state are not recorded again.
+    pub fn audit(
+        &mut self,
+        operation: AuditOperation,
+        request: &Request,
+        details: String,
+    ) -> Result<(), Error> {
+        if !self.do_persist {
+            return Ok(());
+        }
+        if let Some(audit) = self.audit.as_mut() {
+            let actor = match request.remote_addr {
+                Some(addr) => addr.ip().to_string(),
+                None => "local".to_string(),
+            };
+            audit.append(operation, &actor, details)?;
+        }
+        Ok(())
+    }
+
     pub fn persist_all(&mut self) -> Result<(), Error> {
         let path = self.config.state_path()?;
         let mut temp = path.clone();
@@ -633,6 +707,7 @@ impl State {
                 id: None,
                 method: Method::WalletLoad.to_string(),
                 params: Some(serde_json::to_value(params)?),
+                remote_addr: None,
             };
             requests.push(r);
         }
@@ -650,6 +725,7 @@ impl State {
                     id: None,
                     method: Method::WalletSetTxMemo.to_string(),
                     params: Some(serde_json::to_value(params)?),
+                    remote_addr: None,
                 };
                 requests.push(r);
             }
@@ -668,6 +744,7 @@ impl State {
                     id: None,
                     method: Method::WalletSetAddrMemo.to_string(),
                     params: Some(serde_json::to_value(params)?),
+                    remote_addr: None,
                 };
                 requests.push(r);
             }
@@ -721,6 +798,7 @@ impl State {
                 id: None,
                 method: method.to_string(),
                 params: Some(params),
+                remote_addr: None,
             };
             requests.push(r);
         }
@@ -27,6 +27,59 @@ impl ElectrumClient {
         }))
     }
 
+    /// Construct an Electrum client accepting only TLS server certificates matching at least
+    /// one of the given pins, formatted as `cert-sha256:<hex>` or `spki-sha256:<hex>`
+    ///
+    /// Useful for self-hosted servers with self-signed certificates, in this case use
+    /// `validate_domain` false.
+    #[uniffi::constructor]
+    pub fn new_with_tls_pins(
+        electrum_url: &str,
+        validate_domain: bool,
+        tls_pins: Vec<String>,
+    ) -> Result<Arc<Self>, LwkError> {
+        let url = lwk_wollet::ElectrumUrl::new(electrum_url, true, validate_domain)
+            .map_err(lwk_wollet::Error::Url)?;
+        let tls_pins = tls_pins
+            .iter()
+            .map(|pin| pin.parse())
+            .collect::<Result<_, lwk_wollet::Error>>()?;
+        let options = lwk_wollet::ElectrumOptions {
+            tls_pins,
+            ..Default::default()
+        };
+        let client = lwk_wollet::ElectrumClient::with_options(&url, options)?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(client),
+        }))
+    }
+
+    /// Construct an Electrum client failing over between the given servers, in order
+    ///
+    /// When a request to the current server fails, the client reconnects to the next available
+    /// one and sends the request again.
+    #[uniffi::constructor]
+    pub fn new_with_failover(
+        electrum_urls: Vec<String>,
+        tls: bool,
+        validate_domain: bool,
+    ) -> Result<Arc<Self>, LwkError> {
+        let urls = electrum_urls
+            .iter()
+            .map(|url| lwk_wollet::ElectrumUrl::new(url, tls, validate_domain))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(lwk_wollet::Error::Url)?;
+        let client = lwk_wollet::ElectrumClient::with_failover(&urls, Default::default())?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(client),
+        }))
+    }
+
+    /// The url of the server the client is connected to
+    pub fn current_url(&self) -> Result<String, LwkError> {
+        Ok(self.inner.lock()?.current_url().to_string())
+    }
+
     pub fn ping(&self) -> Result<(), LwkError> {
         Ok(self.inner.lock()?.ping()?)
     }
@@ -16,6 +16,7 @@ mod persister;
 mod precision;
 mod pset;
 mod pset_details;
+mod registry;
 mod signer;
 mod test_env;
 mod tx_builder;
@@ -49,6 +50,7 @@ pub use persister::{ForeignPersister, ForeignPersisterLink};
 pub use precision::Precision;
 pub use pset::{Pset, PsetInput};
 pub use pset_details::{Issuance, PsetDetails};
+pub use registry::Registry;
 pub use test_env::TestEnv;
 pub use tx_builder::TxBuilder;
 pub use update::Update;
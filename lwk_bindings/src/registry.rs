@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use crate::{types::AssetId, LwkError, Network};
+
+/// Wrapper over [`lwk_wollet::registry::blocking::Registry`]
+#[derive(uniffi::Object)]
+pub struct Registry {
+    inner: lwk_wollet::registry::blocking::Registry,
+}
+
+#[uniffi::export]
+impl Registry {
+    /// Construct a client of the asset registry at the given url
+    #[uniffi::constructor]
+    pub fn new(url: &str) -> Result<Arc<Self>, LwkError> {
+        let inner = lwk_wollet::registry::blocking::Registry::new(url)?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    /// Construct a client of the default asset registry of the network
+    #[uniffi::constructor]
+    pub fn default_for_network(network: &Network) -> Result<Arc<Self>, LwkError> {
+        let inner = lwk_wollet::registry::blocking::Registry::default_for_network(network.into())?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    /// Fetch the icon of the asset, returning the bytes of the PNG image
+    pub fn fetch_icon(&self, asset_id: AssetId) -> Result<Vec<u8>, LwkError> {
+        let icon = self.inner.fetch_icon(asset_id.into())?;
+        Ok(icon.bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registry;
+    use crate::Network;
+
+    #[test]
+    fn registry_fetch_icon() {
+        // Nothing is listening here
+        let registry = Registry::new("http://127.0.0.1:1").unwrap();
+        let asset_id = Network::testnet().policy_asset();
+        assert!(registry.fetch_icon(asset_id).is_err());
+    }
+}
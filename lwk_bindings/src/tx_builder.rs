@@ -62,6 +62,14 @@ impl TxBuilder {
         Ok(())
     }
 
+    /// Set the asset used to pay the fees, wrapper of [`lwk_wollet::TxBuilder::fee_asset()`]
+    pub fn fee_asset(&self, asset: AssetId) -> Result<(), LwkError> {
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.fee_asset(asset.into()));
+        Ok(())
+    }
+
     /// Select all available L-BTC inputs
     pub fn drain_lbtc_wallet(&self) -> Result<(), LwkError> {
         let mut lock = self.inner.lock()?;
@@ -17,8 +17,22 @@ use std::{
 #[derive(uniffi::Object)]
 pub struct Wollet {
     inner: Mutex<lwk_wollet::Wollet>, // every exposed method must take `&self` (no &mut) so that we need to encapsulate into Mutex
+    events: Arc<lwk_wollet::EventQueue>,
 }
+
+/// The maximum number of events kept until [`Wollet::drain_events()`] is called
+const MAX_QUEUED_EVENTS: usize = 1000;
+
 impl Wollet {
+    fn from_inner(mut inner: lwk_wollet::Wollet) -> Arc<Self> {
+        let events = Arc::new(lwk_wollet::EventQueue::new(MAX_QUEUED_EVENTS));
+        inner.add_listener(events.clone());
+        Arc::new(Self {
+            inner: Mutex::new(inner),
+            events,
+        })
+    }
+
     pub fn inner_wollet(
         &self,
     ) -> Result<MutexGuard<'_, lwk_wollet::Wollet>, PoisonError<MutexGuard<'_, lwk_wollet::Wollet>>>
@@ -38,9 +52,7 @@ impl Wollet {
     ) -> Result<Arc<Self>, LwkError> {
         let inner = lwk_wollet::Wollet::new(network.into(), persister, descriptor.into())?;
 
-        Ok(Arc::new(Self {
-            inner: Mutex::new(inner),
-        }))
+        Ok(Self::from_inner(inner))
     }
 
     /// Construct a Watch-Only wallet object
@@ -57,9 +69,7 @@ impl Wollet {
             None => lwk_wollet::Wollet::new(network.into(), NoPersist::new(), descriptor.into())?,
         };
 
-        Ok(Arc::new(Self {
-            inner: Mutex::new(inner),
-        }))
+        Ok(Self::from_inner(inner))
     }
 
     pub fn descriptor(&self) -> Result<Arc<WolletDescriptor>, LwkError> {
@@ -73,12 +83,45 @@ impl Wollet {
         Ok(Arc::new(address.into()))
     }
 
+    /// Get the address at the given `index`, without modifying the wallet state
+    pub fn address_at(&self, index: u32) -> Result<Arc<AddressResult>, LwkError> {
+        let wollet = self.inner.lock()?;
+        let address = wollet.address_at(index)?;
+        Ok(Arc::new(address.into()))
+    }
+
+    /// Get the next unused address, without modifying the wallet state
+    pub fn peek_next_address(&self) -> Result<Arc<AddressResult>, LwkError> {
+        let wollet = self.inner.lock()?;
+        let address = wollet.peek_next_address()?;
+        Ok(Arc::new(address.into()))
+    }
+
+    /// Mark the external address at the given `index` (and all the previous ones) as used
+    pub fn mark_used(&self, index: u32) -> Result<(), LwkError> {
+        let mut wollet = self.inner.lock()?;
+        wollet.mark_used(index)?;
+        Ok(())
+    }
+
     pub fn apply_update(&self, update: &Update) -> Result<(), LwkError> {
         let mut wollet = self.inner.lock()?;
         wollet.apply_update(update.clone().into())?;
         Ok(())
     }
 
+    /// Remove and return the events caused by the updates applied since the last call, in the
+    /// order they happened
+    ///
+    /// Every event is a JSON object with a `type` field, see [`lwk_wollet::WolletEvent`].
+    pub fn drain_events(&self) -> Vec<String> {
+        self.events
+            .drain_events()
+            .iter()
+            .map(|e| serde_json::to_string(e).expect("contain simple types"))
+            .collect()
+    }
+
     pub fn balance(&self) -> Result<HashMap<AssetId, u64>, LwkError> {
         let m: HashMap<_, _> = self
             .inner
@@ -90,6 +133,21 @@ impl Wollet {
         Ok(m)
     }
 
+    /// Get the wallet balance considering only UTXOs with at least `min_conf` confirmations
+    pub fn balance_with_confirmations(
+        &self,
+        min_conf: u32,
+    ) -> Result<HashMap<AssetId, u64>, LwkError> {
+        let m: HashMap<_, _> = self
+            .inner
+            .lock()?
+            .balance_with_confirmations(min_conf)?
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect();
+        Ok(m)
+    }
+
     pub fn transactions(&self) -> Result<Vec<Arc<WalletTx>>, LwkError> {
         Ok(self
             .inner
@@ -101,6 +159,26 @@ impl Wollet {
             .collect())
     }
 
+    /// Get a page of `limit` wallet transactions starting at `offset`, in the same order of
+    /// [`Wollet::transactions()`]
+    pub fn transactions_paginated(
+        &self,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Arc<WalletTx>>, LwkError> {
+        let filter = lwk_wollet::TxFilter::default()
+            .offset(offset as usize)
+            .limit(limit as usize);
+        Ok(self
+            .inner
+            .lock()?
+            .transactions_with(&filter)?
+            .into_iter()
+            .map(Into::into)
+            .map(Arc::new)
+            .collect())
+    }
+
     /// Get the unspent transaction outputs of the wallet
     pub fn utxos(&self) -> Result<Vec<Arc<WalletTxOut>>, LwkError> {
         Ok(self
@@ -113,6 +191,26 @@ impl Wollet {
             .collect())
     }
 
+    /// Get a page of `limit` unspent transaction outputs starting at `offset`, in the same order
+    /// of [`Wollet::utxos()`]
+    pub fn utxos_paginated(
+        &self,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Arc<WalletTxOut>>, LwkError> {
+        let filter = lwk_wollet::UtxoFilter::default()
+            .offset(offset as usize)
+            .limit(limit as usize);
+        Ok(self
+            .inner
+            .lock()?
+            .utxos_with(&filter)?
+            .into_iter()
+            .map(Into::into)
+            .map(Arc::new)
+            .collect())
+    }
+
     /// Get all the transaction outputs of the wallet, both spent and unspent
     pub fn txos(&self) -> Result<Vec<Arc<WalletTxOut>>, LwkError> {
         Ok(self
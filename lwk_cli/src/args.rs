@@ -143,6 +143,7 @@ pub enum ServerSubCommandsEnum {
     // Start is a special command
     Scan,
     Stop,
+    AuditLog,
 }
 
 #[derive(Debug, Args)]
@@ -867,6 +868,17 @@ pub enum ServerCommand {
         #[arg(short, long)]
         electrum_url: Option<String>,
 
+        /// Accept only Electrum server certificates matching this pin, can be repeated
+        ///
+        /// The pin is `cert-sha256:<hex>` for the hash of the certificate or `spki-sha256:<hex>`
+        /// for the hash of its public key, which survives certificate renewals with the same key.
+        #[arg(long = "electrum-tls-pin")]
+        electrum_tls_pins: Vec<String>,
+
+        /// Electrum URL of a server to fail over to if the previous ones fail, can be repeated
+        #[arg(long = "electrum-failover-url")]
+        electrum_failover_urls: Vec<String>,
+
         #[arg(long)]
         #[cfg(feature = "registry")]
         /// Needed only in regtest because public network have their official defaults
@@ -894,6 +906,11 @@ pub enum ServerCommand {
     /// Wait until an entire blockchain scan has been completed
     Scan,
 
+    /// Show the audit log of the sensitive operations performed by the server
+    ///
+    /// Entries are hash-chained, the log is reported as valid if the chain is intact
+    AuditLog,
+
     /// Stop the server
     ///
     /// Alternatively the server can be stopped also with SIGINT (ctrl-c)
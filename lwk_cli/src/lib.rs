@@ -74,6 +74,8 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
             match a.command {
                 ServerCommand::Start {
                     electrum_url,
+                    electrum_tls_pins,
+                    electrum_failover_urls,
                     #[cfg(feature = "registry")]
                     registry_url,
                     esplora_api_url,
@@ -106,6 +108,11 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                     } else if let Network::Regtest = args.network {
                         anyhow::bail!("on regtest you have to specify --electrum-url");
                     };
+                    config.electrum_tls_pins = electrum_tls_pins
+                        .iter()
+                        .map(|pin| pin.parse())
+                        .collect::<Result<_, _>>()?;
+                    config.electrum_failover_urls = electrum_failover_urls;
                     if let Some(url) = esplora_api_url {
                         config.esplora_api_url = url;
                     };
@@ -150,6 +157,10 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 ServerCommand::Stop => {
                     client.stop()?;
                 }
+                ServerCommand::AuditLog => {
+                    let r = client.audit_log()?;
+                    return Ok(serde_json::to_value(r)?);
+                }
             }
 
             Value::Null
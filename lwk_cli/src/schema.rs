@@ -33,6 +33,7 @@ impl From<ServerSubCommandsEnum> for Method {
         match value {
             ServerSubCommandsEnum::Scan => Method::Scan,
             ServerSubCommandsEnum::Stop => Method::Stop,
+            ServerSubCommandsEnum::AuditLog => Method::AuditLog,
         }
     }
 }
@@ -775,6 +775,30 @@ fn test_broadcast() {
     send(&cli, "w1", &addr, policy_asset, 1000, &["s1"]);
     assert!(1_000_000 > get_balance(&cli, "w1", policy_asset));
 
+    let r = sh(&format!("{cli} server audit-log"));
+    assert!(r.get("valid").unwrap().as_bool().unwrap());
+    let operations: Vec<_> = r
+        .get("entries")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| get_str(e, "operation"))
+        .collect();
+    assert_eq!(
+        operations,
+        [
+            "signer_registered",
+            "pset_created",
+            "pset_signed",
+            "pset_signed",
+            "broadcast",
+            "broadcast"
+        ]
+    );
+    let entries = r.get("entries").unwrap().as_array().unwrap();
+    assert!(entries.iter().all(|e| get_str(e, "actor") == "127.0.0.1"));
+
     sh(&format!("{cli} server stop"));
     t.join().unwrap();
 }
@@ -48,6 +48,53 @@ pub fn singlesig_desc<S: Signer>(
     Ok(format!("{desc}#{checksum}"))
 }
 
+/// Build a singlesig descriptor from an xpub, with its key origin if known
+///
+/// Differently from [`singlesig_desc()`] the signer is not needed, thus the deterministic
+/// SLIP77 blinding key is not supported.
+pub fn singlesig_desc_from_xpub(
+    xpub: (Option<KeySource>, Xpub),
+    script_variant: Singlesig,
+    blinding_variant: DescriptorBlindingKey,
+) -> Result<String, String> {
+    let (prefix, suffix) = match script_variant {
+        Singlesig::Wpkh => ("elwpkh", ""),
+        Singlesig::ShWpkh => ("elsh(wpkh", ")"),
+    };
+
+    let blinding_key = match blinding_variant {
+        DescriptorBlindingKey::Slip77 => {
+            return Err(
+                "Deterministic slip77 key not supported in descriptor generation from xpub".into(),
+            )
+        }
+        DescriptorBlindingKey::Slip77Rand => random_slip77(),
+        DescriptorBlindingKey::Elip151 => "elip151".to_string(),
+    };
+
+    let (keyorigin, xpub) = xpub;
+    let key = fmt_key(&keyorigin, &xpub);
+    let desc = format!("ct({blinding_key},{prefix}({key}){suffix})");
+    let checksum = desc_checksum(&desc).map_err(|e| format!("{:?}", e))?;
+    Ok(format!("{desc}#{checksum}"))
+}
+
+fn random_slip77() -> String {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill(&mut bytes);
+    format!("slip77({})", bytes.to_hex())
+}
+
+/// Format the key with its origin, if any, and the multipath for the external and internal chains
+fn fmt_key(keyorigin: &Option<KeySource>, xpub: &Xpub) -> String {
+    let prefix = if let Some((fingerprint, path)) = keyorigin {
+        format!("[{fingerprint}/{}]", fmt_path(path))
+    } else {
+        "".to_string()
+    };
+    format!("{prefix}{xpub}/<0;1>/*")
+}
+
 fn fmt_path(path: &DerivationPath) -> String {
     path.to_string().replace("m/", "").replace('\'', "h")
 }
@@ -75,24 +122,13 @@ pub fn multisig_desc(
                 "Deterministic slip77 key not supported in multisig descriptor generation".into(),
             )
         }
-        DescriptorBlindingKey::Slip77Rand => {
-            let mut bytes = [0u8; 32];
-            thread_rng().fill(&mut bytes);
-            format!("slip77({})", bytes.to_hex())
-        }
+        DescriptorBlindingKey::Slip77Rand => random_slip77(),
         DescriptorBlindingKey::Elip151 => "elip151".to_string(),
     };
 
     let xpubs = xpubs
         .iter()
-        .map(|(keyorigin, xpub)| {
-            let prefix = if let Some((fingerprint, path)) = keyorigin {
-                format!("[{fingerprint}/{}]", fmt_path(path))
-            } else {
-                "".to_string()
-            };
-            format!("{prefix}{xpub}/<0;1>/*")
-        })
+        .map(|(keyorigin, xpub)| fmt_key(keyorigin, xpub))
         .collect::<Vec<_>>()
         .join(",");
     let desc = format!("ct({blinding_key},{prefix}({threshold},{xpubs}){suffix})");
@@ -240,4 +276,46 @@ mod test {
         }
         Bip::from_str("vattelapesca").unwrap_err();
     }
+
+    #[test]
+    fn test_singlesig_desc_from_xpub() {
+        use super::{singlesig_desc_from_xpub, DescriptorBlindingKey, Singlesig};
+        use elements::bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+        use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
+
+        let xpub = Xpub::from_str("tpubDC2Q4xK4XH72GM7MowNuajyWVbigRLBWKswyP5T88hpPwu5nGqJWnda8zhJEFt71av73Hm8mUMMFSz9acNVzz8b1UbdSHCDXKTbSv5eEytu").unwrap();
+        let fingerprint = Fingerprint::from_str("28b3f14e").unwrap();
+        let path = DerivationPath::from_str("m/84'/1'/0'").unwrap();
+        let keyorigin = Some((fingerprint, path));
+
+        let desc = singlesig_desc_from_xpub(
+            (keyorigin.clone(), xpub),
+            Singlesig::Wpkh,
+            DescriptorBlindingKey::Elip151,
+        )
+        .unwrap();
+        let (desc_str, _checksum) = desc.split_once('#').unwrap();
+        assert_eq!(
+            desc_str,
+            format!("ct(elip151,elwpkh([28b3f14e/84h/1h/0h]{xpub}/<0;1>/*))")
+        );
+        ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&desc).unwrap();
+
+        let desc = singlesig_desc_from_xpub(
+            (None, xpub),
+            Singlesig::ShWpkh,
+            DescriptorBlindingKey::Slip77Rand,
+        )
+        .unwrap();
+        assert!(desc.starts_with("ct(slip77("));
+        assert!(desc.contains(&format!(",elsh(wpkh({xpub}/<0;1>/*)))#")));
+        ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&desc).unwrap();
+
+        let err = singlesig_desc_from_xpub(
+            (keyorigin, xpub),
+            Singlesig::Wpkh,
+            DescriptorBlindingKey::Slip77,
+        );
+        assert!(err.is_err());
+    }
 }
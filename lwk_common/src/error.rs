@@ -68,4 +68,7 @@ pub enum Error {
 
     #[error(transparent)]
     Miniscript(#[from] elements_miniscript::Error),
+
+    #[error(transparent)]
+    Pset(#[from] elements::pset::Error),
 }
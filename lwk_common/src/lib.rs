@@ -16,13 +16,15 @@ mod model;
 mod network;
 pub mod precision;
 mod qr;
+mod secp;
 mod segwit;
+mod sign_request;
 mod signer;
 
 pub use crate::descriptor::{
-    multisig_desc, singlesig_desc, Bip, DescriptorBlindingKey, InvalidBipVariant,
-    InvalidBlindingKeyVariant, InvalidMultisigVariant, InvalidSinglesigVariant, Multisig,
-    Singlesig,
+    multisig_desc, singlesig_desc, singlesig_desc_from_xpub, Bip, DescriptorBlindingKey,
+    InvalidBipVariant, InvalidBlindingKeyVariant, InvalidMultisigVariant, InvalidSinglesigVariant,
+    Multisig, Singlesig,
 };
 pub use crate::error::Error;
 pub use crate::keyorigin_xpub::{keyorigin_xpub_from_str, InvalidKeyOriginXpub};
@@ -30,8 +32,10 @@ pub use crate::model::*;
 pub use crate::network::Network;
 pub use crate::precision::Precision;
 pub use crate::qr::*;
+pub use crate::secp::{Ec, EC};
 pub use crate::segwit::is_provably_segwit;
-pub use crate::signer::Signer;
+pub use crate::sign_request::{SignRequest, SignResponse};
+pub use crate::signer::{SignProgress, Signer};
 
 use elements::confidential::{Asset, Value};
 use elements_miniscript::confidential::bare::tweak_private_key;
@@ -68,15 +72,15 @@ pub fn derive_blinding_key(
     descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
     script_pubkey: &Script,
 ) -> Option<SecretKey> {
-    let secp = Secp256k1::new();
     match &descriptor.key {
         Key::Slip77(k) => Some(k.blinding_private_key(script_pubkey)),
         Key::View(DescriptorSecretKey::XPrv(dxk)) => {
-            let k = dxk.xkey.to_priv();
-            Some(tweak_private_key(&secp, script_pubkey, &k.inner))
+            // ELIP150: the view key is the extended key derived with the given steps, if any
+            let k = dxk.xkey.derive_priv(&EC, &dxk.derivation_path).ok()?;
+            Some(tweak_private_key(&EC, script_pubkey, &k.private_key))
         }
         Key::View(DescriptorSecretKey::Single(k)) => {
-            Some(tweak_private_key(&secp, script_pubkey, &k.key.inner))
+            Some(tweak_private_key(&EC, script_pubkey, &k.key.inner))
         }
         _ => None,
     }
@@ -130,7 +134,6 @@ pub fn pset_balance(
     descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
     params: &'static elements::AddressParams,
 ) -> Result<PsetBalance, Error> {
-    let secp = Secp256k1::new();
     let mut balances: BTreeMap<AssetId, i64> = BTreeMap::new();
     let mut fee: Option<u64> = None;
     for (idx, input) in pset.inputs().iter().enumerate() {
@@ -183,9 +186,9 @@ pub fn pset_balance(
                     .rangeproof
                     .clone_from(&input.in_utxo_rangeproof);
                 let txout_secrets = txout_with_rangeproof
-                    .unblind(&secp, private_blinding_key)
+                    .unblind(&EC, private_blinding_key)
                     .map_err(|_| Error::InputMineNotUnblindable { idx })?;
-                if (asset_comm, amount_comm) != commitments(&secp, &txout_secrets) {
+                if (asset_comm, amount_comm) != commitments(&EC, &txout_secrets) {
                     return Err(Error::InputCommitmentsMismatch { idx });
                 }
 
@@ -250,15 +253,11 @@ pub fn pset_balance(
                 Some(amount_comm),
                 Some(blind_value_proof),
             ) => {
-                if !blind_asset_proof.blind_asset_proof_verify(&secp, asset, asset_comm) {
+                if !blind_asset_proof.blind_asset_proof_verify(&EC, asset, asset_comm) {
                     return Err(Error::InvalidAssetBlindProof { idx });
                 }
-                if !blind_value_proof.blind_value_proof_verify(
-                    &secp,
-                    amount,
-                    asset_comm,
-                    amount_comm,
-                ) {
+                if !blind_value_proof.blind_value_proof_verify(&EC, amount, asset_comm, amount_comm)
+                {
                     return Err(Error::InvalidValueBlindProof { idx });
                 }
 
@@ -267,9 +266,9 @@ pub fn pset_balance(
                     .ok_or(Error::MissingPrivateBlindingKey)?;
                 let txout_secrets = output
                     .to_txout()
-                    .unblind(&secp, private_blinding_key)
+                    .unblind(&EC, private_blinding_key)
                     .map_err(|_| Error::OutputMineNotUnblindable { idx })?;
-                if (asset_comm, amount_comm) != commitments(&secp, &txout_secrets) {
+                if (asset_comm, amount_comm) != commitments(&EC, &txout_secrets) {
                     return Err(Error::OutputCommitmentsMismatch { idx });
                 }
 
@@ -298,6 +297,7 @@ pub fn pset_signatures(pset: &PartiallySignedTransaction) -> Vec<PsetSignatures>
             let mut has_signature = vec![];
             let mut missing_signature = vec![];
             for (pk, ks) in input.bip32_derivation.clone() {
+                let ks = resolve_key_source(pset, &pk, ks);
                 if input.partial_sigs.contains_key(&pk) {
                     has_signature.push((pk, ks));
                 } else {
@@ -312,6 +312,32 @@ pub fn pset_signatures(pset: &PartiallySignedTransaction) -> Vec<PsetSignatures>
         .collect()
 }
 
+/// Express `key_source` relative to the master key of the cosigner, using the PSET global xpubs
+///
+/// Some software sets the key sources of the inputs and outputs relative to the account xpub,
+/// with the fingerprint of the xpub itself, and puts the xpub with its origin in the global
+/// xpubs. In that case the key source is mapped to the origin of the xpub, if deriving the xpub
+/// gives `pk`. Otherwise `key_source` is returned unchanged.
+pub fn resolve_key_source(
+    pset: &PartiallySignedTransaction,
+    pk: &PublicKey,
+    key_source: KeySource,
+) -> KeySource {
+    let (fingerprint, path) = &key_source;
+    for (xpub, (origin_fingerprint, origin_path)) in &pset.global.xpub {
+        if xpub.fingerprint() != *fingerprint || origin_fingerprint == fingerprint {
+            continue;
+        }
+        match xpub.derive_pub(&EC, path) {
+            Ok(derived) if derived.public_key == pk.inner => {
+                return (*origin_fingerprint, origin_path.extend(path));
+            }
+            _ => continue,
+        }
+    }
+    key_source
+}
+
 pub fn pset_issuances(pset: &PartiallySignedTransaction) -> Vec<Issuance> {
     pset.inputs().iter().map(Issuance::new).collect()
 }
@@ -386,4 +412,45 @@ mod test {
         assert_eq!(recipient.value.unwrap(), expected_value);
         assert_eq!(recipient.vout, 0);
     }
+
+    #[test]
+    fn test_derive_blinding_key_elip150() {
+        use elements::bitcoin::bip32::Xpriv;
+        use elements::bitcoin::Network;
+        use elements_miniscript::confidential::slip77::MasterBlindingKey;
+
+        use crate::{derive_blinding_key, EC};
+
+        let xprv = Xpriv::new_master(Network::Testnet, &[1u8; 16]).unwrap();
+        let slip77 = MasterBlindingKey::from_seed(&[2u8; 16]);
+        let xpub = "tpubD6NzVbkrYhZ4Was8nwnZi7eiWUNJq2LFpPSCMQLioUfUtT1e72GkRbmVeRAZc26j5MRUz2hRLsaVHJfs6L7ppNfLUrm9btQTuaEsLrT7D87";
+        let ordinary = format!("elwpkh({xpub}/<0;1>/*)");
+
+        for blinding_key in [
+            format!("slip77({slip77})"),
+            xprv.private_key.display_secret().to_string(),
+            xprv.to_string(),
+            format!("{xprv}/0h/1"),
+            "elip151".to_string(),
+        ] {
+            let desc = format!("ct({blinding_key},{ordinary})");
+            let desc: ConfidentialDescriptor<DescriptorPublicKey> = desc.parse().expect(&desc);
+            for descriptor in desc.descriptor.clone().into_single_descriptors().unwrap() {
+                let desc = ConfidentialDescriptor {
+                    key: desc.key.clone(),
+                    descriptor,
+                };
+                let definite = desc.at_derivation_index(7).unwrap();
+                let address = definite
+                    .address(&EC, &elements::AddressParams::LIQUID_TESTNET)
+                    .unwrap();
+                let blinding_key = derive_blinding_key(&desc, &address.script_pubkey()).unwrap();
+                assert_eq!(
+                    address.blinding_pubkey.unwrap(),
+                    blinding_key.public_key(&EC),
+                    "{desc}"
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,41 @@
+use std::ops::Deref;
+
+use elements::secp256k1_zkp::{All, Secp256k1};
+
+/// Type of the shared secp256k1 context [`EC`], dereferencing to [`Secp256k1<All>`]
+pub struct Ec(());
+
+/// The secp256k1 context, created once and reused across the workspace since creating it is
+/// expensive.
+///
+/// With the `global-context` feature the global context of rust-secp256k1 is used instead.
+pub static EC: Ec = Ec(());
+
+impl Deref for Ec {
+    type Target = Secp256k1<All>;
+
+    #[cfg(feature = "global-context")]
+    fn deref(&self) -> &Self::Target {
+        secp256k1::SECP256K1
+    }
+
+    #[cfg(not(feature = "global-context"))]
+    fn deref(&self) -> &Self::Target {
+        static CONTEXT: std::sync::OnceLock<Secp256k1<All>> = std::sync::OnceLock::new();
+        CONTEXT.get_or_init(|| {
+            let mut ctx = Secp256k1::new();
+            ctx.randomize(&mut rand::thread_rng());
+            ctx
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EC;
+
+    #[test]
+    fn test_shared_context() {
+        assert!(std::ptr::eq(&*EC, &*EC));
+    }
+}
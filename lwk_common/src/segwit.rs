@@ -24,7 +24,6 @@ mod test {
     use super::*;
     use elements_miniscript::elements::bitcoin::PublicKey;
     use elements_miniscript::elements::schnorr::UntweakedPublicKey;
-    use elements_miniscript::elements::secp256k1_zkp::Secp256k1;
     use elements_miniscript::elements::{PubkeyHash, WPubkeyHash};
     use std::str::FromStr;
 
@@ -59,8 +58,7 @@ mod test {
 
         let s = "0202020202020202020202020202020202020202020202020202020202020202";
         let upk = UntweakedPublicKey::from_str(s).unwrap();
-        let secp = Secp256k1::new();
-        let p2tr = Script::new_v1_p2tr(&secp, upk, None);
+        let p2tr = Script::new_v1_p2tr(&crate::EC, upk, None);
         assert!(is_provably_segwit(&p2tr, &None));
 
         let p2sh_p2wpkh = p2wpkh.to_p2sh();
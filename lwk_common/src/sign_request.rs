@@ -0,0 +1,131 @@
+use elements::pset::PartiallySignedTransaction;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Network};
+
+/// A request to sign a PSET, serializable so that it can be sent to a remote signer
+///
+/// Remote signers, like signing enclaves or threshold/MPC backends, may not keep any state,
+/// thus the request carries the wallet context along with the PSET.
+/// The signer answers with a [`SignResponse`], see [`crate::Signer::sign_request()`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignRequest {
+    /// Identifier chosen by the requester, repeated in the response to match them
+    pub id: String,
+
+    /// The PSET to sign, serialized in base64
+    #[serde(with = "pset_base64")]
+    pub pset: PartiallySignedTransaction,
+
+    /// The confidential descriptor of the wallet spending the PSET inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descriptor: Option<String>,
+
+    /// The network of the PSET
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<Network>,
+}
+
+/// The response of a signer to a [`SignRequest`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignResponse {
+    /// The identifier of the request
+    pub id: String,
+
+    /// The PSET of the request with the signatures added, serialized in base64
+    #[serde(with = "pset_base64")]
+    pub pset: PartiallySignedTransaction,
+
+    /// How many signatures were added or overwritten
+    pub signatures_added: u32,
+}
+
+impl SignRequest {
+    /// Create a request to sign the given PSET, without wallet context
+    pub fn new(id: &str, pset: PartiallySignedTransaction) -> Self {
+        Self {
+            id: id.to_string(),
+            pset,
+            descriptor: None,
+            network: None,
+        }
+    }
+
+    /// Add the confidential descriptor of the wallet to the request
+    pub fn with_descriptor(mut self, descriptor: &str) -> Self {
+        self.descriptor = Some(descriptor.to_string());
+        self
+    }
+
+    /// Add the network to the request
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+}
+
+impl SignResponse {
+    /// Merge the signatures of the response in the given PSET
+    ///
+    /// The PSET must be the one of the request, possibly with other signatures added in the
+    /// meantime, for instance by other cosigners.
+    pub fn apply(self, pset: &mut PartiallySignedTransaction) -> Result<(), Error> {
+        pset.merge(self.pset)?;
+        Ok(())
+    }
+}
+
+mod pset_base64 {
+    use elements::pset::PartiallySignedTransaction;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        pset: &PartiallySignedTransaction,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_str(pset)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<PartiallySignedTransaction, D::Error> {
+        let string = String::deserialize(d)?;
+        string.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_request_roundtrip() {
+        let pset_str = include_str!("../test_data/pset_details/pset.base64");
+        let pset: PartiallySignedTransaction = pset_str.trim().parse().unwrap();
+        let desc = include_str!("../test_data/pset_details/descriptor");
+
+        let request = SignRequest::new("1", pset.clone())
+            .with_descriptor(desc.trim())
+            .with_network(Network::TestnetLiquid);
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(json.contains("\"network\":\"testnet-liquid\""));
+        let back: SignRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(request, back);
+
+        let json = serde_json::to_string(&SignRequest::new("2", pset.clone())).unwrap();
+        assert!(!json.contains("descriptor"));
+
+        let response = SignResponse {
+            id: "1".to_string(),
+            pset: pset.clone(),
+            signatures_added: 0,
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        let back: SignResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(response, back);
+
+        let mut local = pset.clone();
+        back.apply(&mut local).unwrap();
+        assert_eq!(local.inputs(), pset.inputs());
+    }
+}
@@ -11,6 +11,21 @@ use elements::{
 use elements_miniscript::slip77::MasterBlindingKey;
 
 use crate::descriptor::Bip;
+use crate::{SignRequest, SignResponse};
+
+/// Progress of a signing session, reported by signers that can take a long time to sign, like
+/// hardware wallets, so that applications can show feedback to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignProgress {
+    /// The index of the PSET input just signed
+    pub input_index: usize,
+
+    /// How many signatures have been produced so far, including the last one
+    pub signed: usize,
+
+    /// How many signatures are expected to be produced in this session
+    pub total: usize,
+}
 
 /// A trait defining methods of signers, providing blanket implementations for some methods.
 pub trait Signer {
@@ -20,6 +35,20 @@ pub trait Signer {
     /// returns how many signatures were added or overwritten
     fn sign(&self, pset: &mut PartiallySignedTransaction) -> Result<u32, Self::Error>;
 
+    /// Sign the PSET of a [`SignRequest`], returning the signed PSET in a [`SignResponse`]
+    ///
+    /// The PSET of the request is not mutated, use [`SignResponse::apply()`] to merge the
+    /// signatures in the requester PSET.
+    fn sign_request(&self, request: &SignRequest) -> Result<SignResponse, Self::Error> {
+        let mut pset = request.pset.clone();
+        let signatures_added = self.sign(&mut pset)?;
+        Ok(SignResponse {
+            id: request.id.clone(),
+            pset,
+            signatures_added,
+        })
+    }
+
     /// Derive an xpub from the master, path can contains hardened derivations
     fn derive_xpub(&self, path: &DerivationPath) -> Result<Xpub, Self::Error>;
 
@@ -1,21 +1,37 @@
 use std::collections::BTreeMap;
 use std::{collections::HashMap, io::ErrorKind};
 
+use crate::attestation::{Attestation, SignAttestationParams};
+use crate::bip85::{check_num_words, EncryptedEntropy, GetBip85Bip39EntropyParams};
+use crate::blinding::{
+    asset_and_value_blinding_factors, BlindingFactorType, Commitments, GetBlindingFactorParams,
+    GetCommitmentsParams, GetSharedNonceParams,
+};
+use crate::capabilities::{Capabilities, JadeFeature};
 use crate::get_receive_address::{GetReceiveAddressParams, SingleOrMulti, Variant};
+use crate::pinserver::CustomPinServer;
 use crate::protocol::{
     AuthUserParams, DebugSetMnemonicParams, EntropyParams, EpochParams, GenericMethod,
     GetMasterBlindingKeyParams, GetSignatureParams, GetXpubParams, IsAuthResult, Request,
     SignMessageParams, UpdatePinserverParams, VersionInfoResult,
 };
 use crate::register_multisig::{
-    GetRegisteredMultisigParams, RegisterMultisigParams, RegisteredMultisig,
-    RegisteredMultisigDetails,
+    registered_multisig_name, GetRegisteredMultisigParams, RegisterMultisigParams,
+    RegisteredMultisig, RegisteredMultisigDetails,
 };
 use crate::sign_liquid_tx::{SignLiquidTxParams, TxInputParams};
-use crate::{json_to_cbor, try_parse_response, vec_to_derivation_path, Error, Result};
-use elements::bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
-use elements_miniscript::slip77;
-use lwk_common::Network;
+use crate::{
+    derivation_path_to_vec, json_to_cbor, try_parse_response, vec_to_derivation_path, Error, Result,
+};
+use bip39::Mnemonic;
+use elements::bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub};
+use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+use elements::hashes::{sha256d, Hash};
+use elements::pset::PartiallySignedTransaction;
+use elements::secp256k1_zkp::{PublicKey, SecretKey};
+use elements_miniscript::slip77::MasterBlindingKey;
+use elements_miniscript::{slip77, ConfidentialDescriptor, DescriptorPublicKey};
+use lwk_common::{DescriptorBlindingKey, Network, Signer, Singlesig, EC};
 use serde::de::DeserializeOwned;
 use serde_bytes::ByteBuf;
 use tokio::sync::Mutex;
@@ -35,6 +51,9 @@ pub struct Jade<S: Stream> {
 
     /// Cached multisigs details
     multisigs_details: Mutex<Option<Vec<RegisteredMultisigDetails>>>,
+
+    /// Cached features supported by the firmware
+    capabilities: Mutex<Option<Capabilities>>,
 }
 
 pub trait Stream {
@@ -73,6 +92,7 @@ impl<S: Stream> Jade<S> {
             network,
             cached_xpubs: Mutex::new(HashMap::new()),
             multisigs_details: Mutex::new(None),
+            capabilities: Mutex::new(None),
         }
     }
 
@@ -97,6 +117,17 @@ impl<S: Stream> Jade<S> {
         self.send(Request::GetVersionInfo).await
     }
 
+    /// The features supported by the firmware of the device, see [`crate::capabilities`]
+    pub async fn capabilities(&self) -> Result<Capabilities> {
+        let mut guard = self.capabilities.lock().await;
+        if let Some(capabilities) = *guard {
+            return Ok(capabilities);
+        }
+        let capabilities = Capabilities::try_from(&self.version_info().await?)?;
+        *guard = Some(capabilities);
+        Ok(capabilities)
+    }
+
     pub async fn set_epoch(&self, epoch: u64) -> Result<bool> {
         self.send(Request::SetEpoch(EpochParams { epoch })).await
     }
@@ -114,6 +145,18 @@ impl<S: Stream> Jade<S> {
         self.send(Request::UpdatePinserver(params)).await
     }
 
+    /// Use a self-hosted PIN server instead of the default Blockstream one
+    ///
+    /// The user must confirm the change on the device.
+    pub async fn set_custom_pinserver(&self, pin_server: &CustomPinServer) -> Result<bool> {
+        self.update_pinserver(pin_server.into()).await
+    }
+
+    /// Go back to the default Blockstream PIN server
+    pub async fn reset_pinserver(&self) -> Result<bool> {
+        self.update_pinserver(UpdatePinserverParams::reset()).await
+    }
+
     async fn get_xpub(&self, params: GetXpubParams) -> Result<Xpub> {
         self.send(Request::GetXpub(params)).await
     }
@@ -154,6 +197,9 @@ impl<S: Stream> Jade<S> {
     }
 
     pub async fn register_multisig(&self, params: RegisterMultisigParams) -> Result<bool> {
+        self.capabilities()
+            .await?
+            .check(JadeFeature::RegisterMultisig)?;
         self.invalidate_registered_multisigs().await;
         self.send(Request::RegisterMultisig(params)).await
     }
@@ -169,6 +215,62 @@ impl<S: Stream> Jade<S> {
         self.send(Request::GetRegisteredMultisig(params)).await
     }
 
+    pub async fn get_blinding_factor(&self, params: GetBlindingFactorParams) -> Result<ByteBuf> {
+        self.send(Request::GetBlindingFactor(params)).await
+    }
+
+    pub async fn get_commitments(&self, params: GetCommitmentsParams) -> Result<Commitments> {
+        self.send(Request::GetCommitments(params)).await
+    }
+
+    pub async fn get_shared_nonce(&self, params: GetSharedNonceParams) -> Result<ByteBuf> {
+        self.send(Request::GetSharedNonce(params)).await
+    }
+
+    pub async fn get_bip85_bip39_entropy(
+        &self,
+        params: GetBip85Bip39EntropyParams,
+    ) -> Result<EncryptedEntropy> {
+        self.send(Request::GetBip85Bip39Entropy(params)).await
+    }
+
+    pub async fn sign_attestation(&self, params: SignAttestationParams) -> Result<Attestation> {
+        self.send(Request::SignAttestation(params)).await
+    }
+
+    /// Verify the Jade is genuine, signing a random challenge with its attestation key, which must
+    /// be signed by one of the `verifying_keys`, PEM RSA public keys of the attestation
+    /// authorities, see [`Attestation::verify()`]
+    ///
+    /// Returns [`Error::AttestationFailed`] if the device may be counterfeit, users should be
+    /// warned before trusting it with funds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn verify_genuine_with_keys(&self, verifying_keys: &[&str]) -> Result<()> {
+        let mut challenge = vec![0u8; crate::attestation::CHALLENGE_LEN];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut challenge);
+        let params = SignAttestationParams {
+            challenge: challenge.clone(),
+        };
+        let attestation = self.sign_attestation(params).await?;
+        attestation.verify(&challenge, verifying_keys)
+    }
+
+    /// The asset and value blinding factors Jade derives for the output at `output_index` of the
+    /// transaction spending the outpoints hashed in `hash_prevouts`
+    pub async fn blinding_factors(
+        &self,
+        hash_prevouts: sha256d::Hash,
+        output_index: u32,
+    ) -> Result<(AssetBlindingFactor, ValueBlindingFactor)> {
+        let params = GetBlindingFactorParams {
+            hash_prevouts: hash_prevouts.to_byte_array().to_vec(),
+            output_index,
+            factor_type: BlindingFactorType::AssetAndValue,
+        };
+        let bytes = self.get_blinding_factor(params).await?;
+        asset_and_value_blinding_factors(&bytes)
+    }
+
     pub async fn get_cached_xpub(&self, params: GetXpubParams) -> Result<Xpub> {
         let mut guard = self.cached_xpubs.lock().await;
         let der_path = vec_to_derivation_path(&params.path);
@@ -181,6 +283,31 @@ impl<S: Stream> Jade<S> {
         }
     }
 
+    /// The multisigs registered on the Jade, by name, as descriptors usable with lwk
+    pub async fn registered_multisig_descriptors(
+        &self,
+    ) -> Result<BTreeMap<String, ConfidentialDescriptor<DescriptorPublicKey>>> {
+        self.get_cached_registered_multisigs()
+            .await?
+            .iter()
+            .map(|details| {
+                let desc = details.confidential_descriptor()?;
+                Ok((details.multisig_name.clone(), desc))
+            })
+            .collect()
+    }
+
+    /// The name of the multisig registered on the Jade matching `desc`, if any
+    ///
+    /// Use it to avoid asking the user to register the same descriptor twice.
+    pub async fn registered_multisig_name(
+        &self,
+        desc: &ConfidentialDescriptor<DescriptorPublicKey>,
+    ) -> Result<Option<String>> {
+        let registered = self.get_cached_registered_multisigs().await?;
+        Ok(registered_multisig_name(&registered, desc)?.map(ToString::to_string))
+    }
+
     async fn get_cached_registered_multisigs(&self) -> Result<Vec<RegisteredMultisigDetails>> {
         let mut guard = self.multisigs_details.lock().await;
         if let Some(multisigs_details) = guard.as_ref() {
@@ -324,6 +451,52 @@ impl<S: Stream> Jade<S> {
         Ok(slip77::MasterBlindingKey::from(array))
     }
 
+    /// The BIP85 child mnemonic of `num_words` words, 12 or 24, at `index`
+    ///
+    /// The user must confirm the export on the device, which is unlocked if needed. The child
+    /// mnemonic gives full control of its funds, handle it like any other secret.
+    pub async fn bip85_mnemonic(&self, num_words: u32, index: u32) -> Result<Mnemonic> {
+        check_num_words(num_words)?;
+        self.unlock().await?;
+        let secret_key = SecretKey::new(&mut rand::thread_rng());
+        let params = GetBip85Bip39EntropyParams {
+            num_words,
+            index,
+            pubkey: PublicKey::from_secret_key(&EC, &secret_key)
+                .serialize()
+                .to_vec(),
+        };
+        let entropy = self
+            .get_bip85_bip39_entropy(params)
+            .await?
+            .decrypt(&secret_key)?;
+        Ok(Mnemonic::from_entropy(&entropy)?)
+    }
+
+    /// The descriptor of the first native segwit singlesig account of the Jade, blinded with its
+    /// SLIP77 master blinding key
+    ///
+    /// Use it to create a watch-only wallet of the Jade, the device is unlocked if needed.
+    pub async fn wpkh_slip77_descriptor(&self) -> Result<String> {
+        self.unlock().await?;
+        let coin_type = if self.network.is_mainnet() { 1776 } else { 1 };
+        let account: DerivationPath = [84, coin_type, 0]
+            .into_iter()
+            .map(|i| ChildNumber::from_hardened_idx(i).expect("less than 2^31"))
+            .collect();
+        let params = GetXpubParams {
+            network: self.network,
+            path: derivation_path_to_vec(&account),
+        };
+        let keys = FetchedKeys {
+            master_xpub: self.get_master_xpub().await?,
+            account_xpub: (account, self.get_cached_xpub(params).await?),
+            slip77: self.slip77_master_blinding_key().await?,
+        };
+        lwk_common::singlesig_desc(&keys, Singlesig::Wpkh, DescriptorBlindingKey::Slip77)
+            .map_err(Error::Generic)
+    }
+
     pub(crate) async fn send<T>(&self, request: Request) -> Result<T>
     where
         T: std::fmt::Debug + DeserializeOwned,
@@ -357,3 +530,32 @@ impl<S: Stream> Jade<S> {
         }
     }
 }
+
+/// Keys fetched from the async Jade, to build descriptors with the [`Signer`] helpers
+struct FetchedKeys {
+    master_xpub: Xpub,
+    account_xpub: (DerivationPath, Xpub),
+    slip77: MasterBlindingKey,
+}
+
+impl Signer for FetchedKeys {
+    type Error = Error;
+
+    fn sign(&self, _pset: &mut PartiallySignedTransaction) -> Result<u32> {
+        Err(Error::Generic("Fetched keys cannot sign".to_string()))
+    }
+
+    fn derive_xpub(&self, path: &DerivationPath) -> Result<Xpub> {
+        if path.is_master() {
+            Ok(self.master_xpub)
+        } else if *path == self.account_xpub.0 {
+            Ok(self.account_xpub.1)
+        } else {
+            Err(Error::Generic(format!("Xpub at {path} not fetched")))
+        }
+    }
+
+    fn slip77_master_blinding_key(&self) -> Result<MasterBlindingKey> {
+        Ok(self.slip77)
+    }
+}
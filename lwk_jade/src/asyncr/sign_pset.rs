@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
 use elements::pset::PartiallySignedTransaction;
+use lwk_common::SignProgress;
 
 use crate::{
-    create_jade_sign_req, derivation_path_to_vec, protocol::GetSignatureParams, script_code_wpkh,
-    sign_liquid_tx::TxInputParams, Error,
+    capabilities::JadeFeature, count_to_sign, create_jade_sign_req, derivation_path_to_vec,
+    protocol::GetSignatureParams, script_code_wpkh, sign_liquid_tx::TxInputParams, Error,
 };
 
 use super::{Jade, Stream};
@@ -12,6 +13,19 @@ use super::{Jade, Stream};
 impl<S: Stream> Jade<S> {
     /// Sign a pset from a Jade
     pub async fn sign(&self, pset: &mut PartiallySignedTransaction) -> Result<u32, Error> {
+        self.sign_with_progress(pset, |_| ()).await
+    }
+
+    /// Sign a pset from a Jade, calling `progress` every time a signature is received
+    ///
+    /// Signing a pset with many inputs could take minutes, use this to give feedback to the user.
+    pub async fn sign_with_progress(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        mut progress: impl FnMut(SignProgress),
+    ) -> Result<u32, Error> {
+        let capabilities = self.capabilities().await?;
+        capabilities.check(JadeFeature::AntiExfil)?;
         let my_fingerprint = self.fingerprint().await?;
 
         // Singlesig signing don't need this, however, it is simpler to always ask for it and once cached is a
@@ -19,7 +33,10 @@ impl<S: Stream> Jade<S> {
         let multisigs_details = self.get_cached_registered_multisigs().await?;
         let network = self.network;
 
-        let params = create_jade_sign_req(pset, my_fingerprint, multisigs_details, network)?;
+        let mut params = create_jade_sign_req(pset, my_fingerprint, multisigs_details, network)?;
+        if !capabilities.supports(JadeFeature::Elip100AssetInfo) {
+            params.asset_info.clear();
+        }
 
         let mut sigs_added_or_overwritten = 0;
         let sign_response = self.sign_liquid_tx(params).await?;
@@ -87,7 +104,8 @@ impl<S: Stream> Jade<S> {
             }
         }
 
-        for input in pset.inputs_mut().iter_mut() {
+        let total = count_to_sign(pset, &signers_commitment);
+        for (input_index, input) in pset.inputs_mut().iter_mut().enumerate() {
             for (public_key, (_, _)) in input.bip32_derivation.iter() {
                 if let Some(_signer_commitment) = signers_commitment.get(public_key) {
                     let params = GetSignatureParams {
@@ -97,6 +115,11 @@ impl<S: Stream> Jade<S> {
 
                     input.partial_sigs.insert(*public_key, sig);
                     sigs_added_or_overwritten += 1;
+                    progress(SignProgress {
+                        input_index,
+                        signed: sigs_added_or_overwritten as usize,
+                        total,
+                    });
                 }
             }
         }
@@ -0,0 +1,178 @@
+//! Verification that a Jade is a genuine device
+//!
+//! Genuine Jades have an RSA key generated during manufacturing, whose public key is signed by an
+//! attestation authority. With `sign_attestation` Jade signs a random challenge of the host and
+//! returns its public key with the authority signature, proving it holds a key signed by the
+//! authority.
+//!
+//! The device key is verified with the PEM RSA public keys of the authorities passed by the
+//! caller, for Blockstream devices the ones published in the Jade repository. Verification is not
+//! available on wasm.
+
+use elements::hex::ToHex;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{der_read, pem_decode, Error, Result};
+
+/// The length of the challenges created by [`crate::Jade::verify_genuine_with_keys()`]
+pub const CHALLENGE_LEN: usize = 32;
+
+#[derive(Serialize)]
+pub struct SignAttestationParams {
+    #[serde(with = "serde_bytes")]
+    pub challenge: Vec<u8>,
+}
+
+impl std::fmt::Debug for SignAttestationParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignAttestationParams")
+            .field("challenge", &self.challenge.to_hex())
+            .finish()
+    }
+}
+
+/// The response of `sign_attestation`
+#[derive(Deserialize, Serialize)]
+pub struct Attestation {
+    /// The RSA public key of the device, in PEM format
+    pub pubkey_pem: String,
+
+    /// The signature of `pubkey_pem` by the attestation authority
+    pub ext_signature: ByteBuf,
+
+    /// The signature of the challenge by the device key
+    pub signature: ByteBuf,
+}
+
+impl std::fmt::Debug for Attestation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Attestation")
+            .field("pubkey_pem", &self.pubkey_pem)
+            .field("ext_signature", &self.ext_signature.to_hex())
+            .field("signature", &self.signature.to_hex())
+            .finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Attestation {
+    /// Verify the device signed `challenge` with a key signed by one of the `verifying_keys`,
+    /// PEM RSA public keys of the attestation authorities
+    ///
+    /// Both signatures are RSA PKCS#1 v1.5 with SHA-256.
+    pub fn verify(&self, challenge: &[u8], verifying_keys: &[&str]) -> Result<()> {
+        if verifying_keys.is_empty() {
+            return Err(Error::AttestationFailed(
+                "no attestation verifying keys".to_string(),
+            ));
+        }
+        let device_key = rsa_public_key(&self.pubkey_pem)?;
+        verify_rsa(&device_key, challenge, &self.signature)
+            .map_err(|_| Error::AttestationFailed("invalid challenge signature".to_string()))?;
+
+        for verifying_key in verifying_keys {
+            let verifying_key = rsa_public_key(verifying_key)?;
+            if verify_rsa(
+                &verifying_key,
+                self.pubkey_pem.as_bytes(),
+                &self.ext_signature,
+            )
+            .is_ok()
+            {
+                return Ok(());
+            }
+        }
+        Err(Error::AttestationFailed(
+            "device key not signed by a verifying key".to_string(),
+        ))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn verify_rsa(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> std::result::Result<(), ring::error::Unspecified> {
+    use ring::signature::{UnparsedPublicKey, RSA_PKCS1_2048_8192_SHA256};
+    UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, public_key).verify(message, signature)
+}
+
+/// The DER `RSAPublicKey` (PKCS#1) from a PEM `PUBLIC KEY` (SPKI) or `RSA PUBLIC KEY`
+#[cfg(not(target_arch = "wasm32"))]
+fn rsa_public_key(pem: &str) -> Result<Vec<u8>> {
+    let err = || Error::AttestationFailed(format!("invalid RSA public key: {pem}"));
+    if let Some(der) = pem_decode(pem, "RSA PUBLIC KEY") {
+        return Ok(der);
+    }
+    let spki = pem_decode(pem, "PUBLIC KEY").ok_or_else(err)?;
+
+    // SubjectPublicKeyInfo ::= SEQUENCE { algorithm AlgorithmIdentifier, subjectPublicKey BIT STRING }
+    let (spki, _) = der_read(&spki, 0x30).ok_or_else(err)?;
+    let (algorithm, rest) = der_read(spki, 0x30).ok_or_else(err)?;
+    let (oid, _) = der_read(algorithm, 0x06).ok_or_else(err)?;
+    if oid != RSA_ENCRYPTION_OID {
+        return Err(err());
+    }
+    let (bits, _) = der_read(rest, 0x03).ok_or_else(err)?;
+    match bits.split_first() {
+        Some((0, key)) => Ok(key.to_vec()),
+        _ => Err(err()),
+    }
+}
+
+/// 1.2.840.113549.1.1.1
+#[cfg(not(target_arch = "wasm32"))]
+const RSA_ENCRYPTION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod test {
+    use serde_bytes::ByteBuf;
+
+    use super::{rsa_public_key, Attestation};
+
+    #[test]
+    fn test_verify_attestation() {
+        let data: serde_json::Value =
+            serde_json::from_str(include_str!("../test_data/attestation.json")).unwrap();
+        let get = |key: &str| data[key].as_str().unwrap().to_string();
+        let challenge = hex::decode(get("challenge")).unwrap();
+        let verifying_key = get("verifying_key_pem");
+        let attestation = Attestation {
+            pubkey_pem: get("pubkey_pem"),
+            ext_signature: ByteBuf::from(hex::decode(get("ext_signature")).unwrap()),
+            signature: ByteBuf::from(hex::decode(get("signature")).unwrap()),
+        };
+
+        attestation.verify(&challenge, &[&verifying_key]).unwrap();
+
+        // the device key itself is not an attestation authority
+        let device_key = attestation.pubkey_pem.clone();
+        assert!(attestation.verify(&challenge, &[&device_key]).is_err());
+        attestation
+            .verify(&challenge, &[&device_key, &verifying_key])
+            .unwrap();
+        let err = attestation.verify(&challenge, &[]).unwrap_err();
+        assert!(err.to_string().contains("no attestation verifying keys"));
+
+        // a replayed attestation doesn't sign a new challenge
+        assert!(attestation
+            .verify(b"other challenge", &[&verifying_key])
+            .is_err());
+
+        let mut tampered = Attestation {
+            pubkey_pem: attestation.pubkey_pem.clone(),
+            ext_signature: attestation.ext_signature.clone(),
+            signature: attestation.signature.clone(),
+        };
+        tampered.ext_signature[0] ^= 1;
+        assert!(tampered.verify(&challenge, &[&verifying_key]).is_err());
+
+        assert!(rsa_public_key("not a key").is_err());
+        rsa_public_key(&verifying_key).unwrap();
+        assert!(attestation.verify(&challenge, &["not a key"]).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Export of BIP85 child mnemonics, to be used in software signers
+//!
+//! Jade derives the BIP39 entropy at `m/83696968'/39'/0'/<num_words>'/<index>'` and, after the
+//! user confirms on the device, returns it encrypted to an ephemeral key of the host.
+//!
+//! The encryption is the one of libwally `aes_cbc_with_ecdh_key`: the ECDH shared secret keys an
+//! HMAC-SHA512 of the label, whose halves are the AES-256-CBC key and the HMAC-SHA256 key
+//! authenticating `iv || ciphertext`.
+
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, KeyInit};
+use aes::Aes256;
+use elements::hashes::hmac::{Hmac, HmacEngine};
+use elements::hashes::{sha256, sha512, Hash, HashEngine};
+use elements::hex::ToHex;
+use elements::secp256k1_zkp::ecdh::SharedSecret;
+use elements::secp256k1_zkp::{PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+use crate::{Error, Result};
+
+/// The label used by Jade to derive the encryption keys
+const LABEL: &[u8] = b"bip85_bip39_entropy";
+
+const IV_LEN: usize = 16;
+const HMAC_LEN: usize = 32;
+
+#[derive(Serialize)]
+pub struct GetBip85Bip39EntropyParams {
+    /// The number of words of the child mnemonic, 12 or 24
+    pub num_words: u32,
+
+    /// The BIP85 index of the child mnemonic
+    pub index: u32,
+
+    /// The ephemeral public key of the host, the entropy is encrypted to it
+    #[serde(with = "serde_bytes")]
+    pub pubkey: Vec<u8>,
+}
+
+impl std::fmt::Debug for GetBip85Bip39EntropyParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetBip85Bip39EntropyParams")
+            .field("num_words", &self.num_words)
+            .field("index", &self.index)
+            .field("pubkey", &self.pubkey.to_hex())
+            .finish()
+    }
+}
+
+/// The BIP85 entropy encrypted by Jade, as returned by `get_bip85_bip39_entropy`
+#[derive(Deserialize, Serialize)]
+pub struct EncryptedEntropy {
+    /// The ephemeral public key of Jade
+    pub pubkey: ByteBuf,
+
+    /// `iv || ciphertext || hmac`
+    pub encrypted: ByteBuf,
+}
+
+impl std::fmt::Debug for EncryptedEntropy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedEntropy")
+            .field("pubkey", &self.pubkey.to_hex())
+            .field("encrypted_bytes", &self.encrypted.len())
+            .finish()
+    }
+}
+
+impl EncryptedEntropy {
+    /// Decrypt the entropy with the secret key of the host ephemeral public key
+    pub fn decrypt(&self, secret_key: &SecretKey) -> Result<Vec<u8>> {
+        let jade_pubkey =
+            PublicKey::from_slice(&self.pubkey).map_err(|_| Error::Bip85EntropyDecryptionFailed)?;
+        let (aes_key, hmac_key) = derive_keys(secret_key, &jade_pubkey);
+
+        let bytes = &self.encrypted[..];
+        if bytes.len() < IV_LEN + HMAC_LEN || (bytes.len() - IV_LEN - HMAC_LEN) % 16 != 0 {
+            return Err(Error::Bip85EntropyDecryptionFailed);
+        }
+        let (authenticated, hmac) = bytes.split_at(bytes.len() - HMAC_LEN);
+        if hmac_sha256(&hmac_key, authenticated)[..] != hmac[..] {
+            return Err(Error::Bip85EntropyDecryptionFailed);
+        }
+        let (iv, ciphertext) = authenticated.split_at(IV_LEN);
+        aes_cbc_decrypt(&aes_key, iv, ciphertext)
+    }
+}
+
+/// Check the number of words of a child mnemonic supported by Jade
+pub(crate) fn check_num_words(num_words: u32) -> Result<()> {
+    match num_words {
+        12 | 24 => Ok(()),
+        _ => Err(Error::UnsupportedBip85WordCount(num_words)),
+    }
+}
+
+fn derive_keys(secret_key: &SecretKey, pubkey: &PublicKey) -> ([u8; 32], [u8; 32]) {
+    let shared_secret = SharedSecret::new(pubkey, secret_key);
+    let mut engine = HmacEngine::<sha512::Hash>::new(&shared_secret.secret_bytes());
+    engine.input(LABEL);
+    let keys = Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+    let mut aes_key = [0u8; 32];
+    let mut hmac_key = [0u8; 32];
+    aes_key.copy_from_slice(&keys[..32]);
+    hmac_key.copy_from_slice(&keys[32..]);
+    (aes_key, hmac_key)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(key);
+    engine.input(data);
+    Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+}
+
+/// AES-256-CBC with PKCS#7 padding
+fn aes_cbc_decrypt(key: &[u8; 32], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = Aes256::new(GenericArray::from_slice(key));
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    let mut prev = iv;
+    for chunk in ciphertext.chunks(16) {
+        let mut block = GenericArray::clone_from_slice(chunk);
+        cipher.decrypt_block(&mut block);
+        plaintext.extend(block.iter().zip(prev).map(|(b, p)| b ^ p));
+        prev = chunk;
+    }
+    let pad = *plaintext
+        .last()
+        .ok_or(Error::Bip85EntropyDecryptionFailed)? as usize;
+    if pad == 0
+        || pad > 16
+        || plaintext[plaintext.len() - pad..]
+            .iter()
+            .any(|b| *b as usize != pad)
+    {
+        return Err(Error::Bip85EntropyDecryptionFailed);
+    }
+    plaintext.truncate(plaintext.len() - pad);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod test {
+    use aes::cipher::generic_array::GenericArray;
+    use aes::cipher::{BlockEncrypt, KeyInit};
+    use aes::Aes256;
+    use elements::secp256k1_zkp::{PublicKey, SecretKey};
+    use lwk_common::EC;
+    use serde_bytes::ByteBuf;
+
+    use super::{check_num_words, derive_keys, hmac_sha256, EncryptedEntropy};
+
+    /// What Jade does to encrypt the entropy
+    fn encrypt(secret_key: &SecretKey, pubkey: &PublicKey, iv: [u8; 16], data: &[u8]) -> Vec<u8> {
+        let (aes_key, hmac_key) = derive_keys(secret_key, pubkey);
+        let cipher = Aes256::new(GenericArray::from_slice(&aes_key));
+        let pad = 16 - data.len() % 16;
+        let mut padded = data.to_vec();
+        padded.extend(std::iter::repeat(pad as u8).take(pad));
+
+        let mut result = iv.to_vec();
+        let mut prev = iv.to_vec();
+        for chunk in padded.chunks(16) {
+            let xored: Vec<u8> = chunk.iter().zip(&prev).map(|(a, b)| a ^ b).collect();
+            let mut block = GenericArray::clone_from_slice(&xored);
+            cipher.encrypt_block(&mut block);
+            prev = block.to_vec();
+            result.extend(block);
+        }
+        let hmac = hmac_sha256(&hmac_key, &result);
+        result.extend(hmac);
+        result
+    }
+
+    #[test]
+    fn test_decrypt_entropy() {
+        let host_secret = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let host_pubkey = PublicKey::from_secret_key(&EC, &host_secret);
+        let jade_secret = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let jade_pubkey = PublicKey::from_secret_key(&EC, &jade_secret);
+
+        for entropy in [[7u8; 16].to_vec(), [9u8; 32].to_vec()] {
+            let encrypted = EncryptedEntropy {
+                pubkey: ByteBuf::from(jade_pubkey.serialize().to_vec()),
+                encrypted: ByteBuf::from(encrypt(&jade_secret, &host_pubkey, [3u8; 16], &entropy)),
+            };
+            assert_eq!(encrypted.encrypted.len(), 16 + entropy.len() + 16 + 32);
+            assert_eq!(encrypted.decrypt(&host_secret).unwrap(), entropy);
+
+            let other_secret = SecretKey::from_slice(&[4u8; 32]).unwrap();
+            assert!(encrypted.decrypt(&other_secret).is_err());
+
+            let mut tampered = encrypted.encrypted.to_vec();
+            tampered[20] ^= 1;
+            let tampered = EncryptedEntropy {
+                pubkey: encrypted.pubkey.clone(),
+                encrypted: ByteBuf::from(tampered),
+            };
+            assert!(tampered.decrypt(&host_secret).is_err());
+        }
+
+        assert!(check_num_words(12).is_ok());
+        assert!(check_num_words(24).is_ok());
+        assert!(check_num_words(18).is_err());
+    }
+}
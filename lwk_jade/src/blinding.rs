@@ -0,0 +1,258 @@
+//! Blinding primitives, to let the host build externally blinded transactions
+//!
+//! In transactions built with other parties, like swaps or coinjoins, Jade can contribute the
+//! blinding factors and the commitments of its outputs and the nonces to unblind its inputs,
+//! without the host knowing the master blinding key.
+//!
+//! Blinding factors are derived from the `hash_prevouts` of the transaction, see
+//! [`hash_prevouts()`], so they are deterministic and Jade can recompute them when signing.
+
+use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+use elements::encode::serialize;
+use elements::hashes::{sha256d, Hash, HashEngine};
+use elements::hex::ToHex;
+use elements::secp256k1_zkp::{Generator, PedersenCommitment, PublicKey};
+use elements::{AssetId, OutPoint, Script};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+use crate::{Error, Result};
+
+/// The blinding factors requested with [`GetBlindingFactorParams`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BlindingFactorType {
+    /// The asset blinding factor, 32 bytes
+    Asset,
+
+    /// The value blinding factor, 32 bytes
+    Value,
+
+    /// The asset blinding factor followed by the value blinding factor, 64 bytes
+    AssetAndValue,
+}
+
+#[derive(Serialize)]
+pub struct GetBlindingFactorParams {
+    /// See [`hash_prevouts()`]
+    #[serde(with = "serde_bytes")]
+    pub hash_prevouts: Vec<u8>,
+
+    pub output_index: u32,
+
+    #[serde(rename = "type")]
+    pub factor_type: BlindingFactorType,
+}
+
+impl std::fmt::Debug for GetBlindingFactorParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetBlindingFactorParams")
+            .field("hash_prevouts", &self.hash_prevouts.to_hex())
+            .field("output_index", &self.output_index)
+            .field("factor_type", &self.factor_type)
+            .finish()
+    }
+}
+
+#[derive(Serialize)]
+pub struct GetCommitmentsParams {
+    /// The asset id, reversed as Jade wants it
+    #[serde(with = "serde_bytes")]
+    pub asset_id: Vec<u8>,
+
+    pub value: u64,
+
+    /// See [`hash_prevouts()`]
+    #[serde(with = "serde_bytes")]
+    pub hash_prevouts: Vec<u8>,
+
+    pub output_index: u32,
+
+    /// The value blinding factor to use instead of the derived one, needed for the last blinded
+    /// output to balance the transaction
+    #[serde(with = "serde_bytes", skip_serializing_if = "Option::is_none")]
+    pub vbf: Option<Vec<u8>>,
+}
+
+impl GetCommitmentsParams {
+    pub fn new(
+        asset: AssetId,
+        value: u64,
+        hash_prevouts: sha256d::Hash,
+        output_index: u32,
+        vbf: Option<ValueBlindingFactor>,
+    ) -> Self {
+        let mut asset_id = serialize(&asset);
+        asset_id.reverse(); // Jade want it reversed
+        Self {
+            asset_id,
+            value,
+            hash_prevouts: hash_prevouts.to_byte_array().to_vec(),
+            output_index,
+            vbf: vbf.map(|vbf| vbf.into_inner().as_ref().to_vec()),
+        }
+    }
+}
+
+impl std::fmt::Debug for GetCommitmentsParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetCommitmentsParams")
+            .field("asset_id", &self.asset_id.to_hex())
+            .field("value", &self.value)
+            .field("hash_prevouts", &self.hash_prevouts.to_hex())
+            .field("output_index", &self.output_index)
+            .field("vbf", &self.vbf.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// The blinding factors and the commitments of an output, as returned by Jade
+#[derive(Deserialize, Serialize)]
+pub struct Commitments {
+    pub abf: ByteBuf,
+    pub vbf: ByteBuf,
+    pub asset_generator: ByteBuf,
+    pub value_commitment: ByteBuf,
+}
+
+impl Commitments {
+    pub fn asset_blinding_factor(&self) -> Result<AssetBlindingFactor> {
+        AssetBlindingFactor::from_slice(&self.abf).map_err(|e| Error::Generic(e.to_string()))
+    }
+
+    pub fn value_blinding_factor(&self) -> Result<ValueBlindingFactor> {
+        ValueBlindingFactor::from_slice(&self.vbf).map_err(|e| Error::Generic(e.to_string()))
+    }
+
+    pub fn asset_generator(&self) -> Result<Generator> {
+        Generator::from_slice(&self.asset_generator).map_err(|e| Error::Generic(e.to_string()))
+    }
+
+    pub fn value_commitment(&self) -> Result<PedersenCommitment> {
+        PedersenCommitment::from_slice(&self.value_commitment)
+            .map_err(|e| Error::Generic(e.to_string()))
+    }
+}
+
+impl std::fmt::Debug for Commitments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Commitments")
+            .field("asset_generator", &self.asset_generator.to_hex())
+            .field("value_commitment", &self.value_commitment.to_hex())
+            .finish()
+    }
+}
+
+#[derive(Serialize)]
+pub struct GetSharedNonceParams {
+    /// The script pubkey whose blinding key is used
+    #[serde(with = "serde_bytes")]
+    pub script: Vec<u8>,
+
+    /// The ephemeral public key of the output, 33 bytes
+    #[serde(with = "serde_bytes")]
+    pub their_pubkey: Vec<u8>,
+}
+
+impl GetSharedNonceParams {
+    pub fn new(script_pubkey: &Script, their_pubkey: &PublicKey) -> Self {
+        Self {
+            script: script_pubkey.to_bytes(),
+            their_pubkey: their_pubkey.serialize().to_vec(),
+        }
+    }
+}
+
+impl std::fmt::Debug for GetSharedNonceParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetSharedNonceParams")
+            .field("script", &self.script.to_hex())
+            .field("their_pubkey", &self.their_pubkey.to_hex())
+            .finish()
+    }
+}
+
+/// The hash of the outpoints spent by a transaction, from which Jade derives the blinding factors
+/// of its outputs
+///
+/// It's the double sha256 of the concatenated outpoints, like the `hashPrevouts` of BIP143.
+pub fn hash_prevouts<'a>(outpoints: impl IntoIterator<Item = &'a OutPoint>) -> sha256d::Hash {
+    let mut engine = sha256d::Hash::engine();
+    for outpoint in outpoints {
+        engine.input(&serialize(outpoint));
+    }
+    sha256d::Hash::from_engine(engine)
+}
+
+/// Split the result of a [`BlindingFactorType::AssetAndValue`] request
+pub(crate) fn asset_and_value_blinding_factors(
+    bytes: &[u8],
+) -> Result<(AssetBlindingFactor, ValueBlindingFactor)> {
+    if bytes.len() != 64 {
+        return Err(Error::Generic(format!(
+            "Expected 64 bytes of blinding factors, got {}",
+            bytes.len()
+        )));
+    }
+    let (abf, vbf) = bytes.split_at(32);
+    let abf = AssetBlindingFactor::from_slice(abf).map_err(|e| Error::Generic(e.to_string()))?;
+    let vbf = ValueBlindingFactor::from_slice(vbf).map_err(|e| Error::Generic(e.to_string()))?;
+    Ok((abf, vbf))
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use elements::hashes::Hash;
+    use elements::{AssetId, OutPoint, Txid};
+
+    use super::{
+        asset_and_value_blinding_factors, hash_prevouts, BlindingFactorType,
+        GetBlindingFactorParams, GetCommitmentsParams,
+    };
+
+    #[test]
+    fn test_blinding_params() {
+        let txid =
+            Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001")
+                .unwrap();
+        let outpoints = [OutPoint::new(txid, 0), OutPoint::new(txid, 1)];
+        let hash = hash_prevouts(&outpoints);
+        assert_ne!(hash, hash_prevouts(&outpoints[..1]));
+
+        let mut expected = vec![];
+        for o in outpoints.iter() {
+            expected.extend(o.txid.to_byte_array());
+            expected.extend(o.vout.to_le_bytes());
+        }
+        assert_eq!(hash, elements::hashes::sha256d::Hash::hash(&expected));
+
+        let params = GetBlindingFactorParams {
+            hash_prevouts: hash.to_byte_array().to_vec(),
+            output_index: 1,
+            factor_type: BlindingFactorType::AssetAndValue,
+        };
+        let value = serde_cbor::value::to_value(&params).unwrap();
+        let json = serde_json::to_value(value).unwrap();
+        assert_eq!(json["type"], "ASSET_AND_VALUE");
+        assert_eq!(json["output_index"], 1);
+
+        let asset =
+            AssetId::from_str("6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d")
+                .unwrap();
+        let params = GetCommitmentsParams::new(asset, 1000, hash, 0, None);
+        assert_eq!(
+            params.asset_id[0], 0x6f,
+            "asset id must be in display order"
+        );
+        let value = serde_cbor::value::to_value(&params).unwrap();
+        let json = serde_json::to_value(value).unwrap();
+        assert!(json.get("vbf").is_none());
+
+        assert!(asset_and_value_blinding_factors(&[1u8; 32]).is_err());
+        let (abf, vbf) = asset_and_value_blinding_factors(&[1u8; 64]).unwrap();
+        assert_eq!(abf.into_inner().as_ref(), &[1u8; 32]);
+        assert_eq!(vbf.into_inner().as_ref(), &[1u8; 32]);
+    }
+}
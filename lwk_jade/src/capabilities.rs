@@ -0,0 +1,177 @@
+//! Protocol features supported by the Jade firmware versions
+//!
+//! Older firmwares answer requests they don't know with generic errors, checking the
+//! [`Capabilities`] of the device before sending a request gives an actionable error instead.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::protocol::VersionInfoResult;
+use crate::Error;
+
+/// A Jade firmware version, such as `1.0.31`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl FirmwareVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = Error;
+
+    /// Parse a version as returned in [`VersionInfoResult::jade_version`], ignoring suffixes
+    /// like in `1.0.31-dirty`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::InvalidFirmwareVersion(s.to_string());
+        let version = s.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = version
+            .split('.')
+            .map(|p| p.parse::<u32>().map_err(|_| err()));
+        let mut next = || parts.next().unwrap_or_else(|| Err(err()));
+        let version = Self::new(next()?, next()?, next()?);
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A protocol feature not supported by every firmware version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JadeFeature {
+    /// Anti-exfil signatures, always used by [`crate::Jade::sign()`]
+    AntiExfil,
+
+    /// Registration of Liquid multisig wallets with `register_multisig`
+    RegisterMultisig,
+
+    /// Asset metadata from the PSET (ELIP-100) shown while signing
+    Elip100AssetInfo,
+}
+
+impl JadeFeature {
+    /// All the features, from the oldest supported
+    pub const ALL: [JadeFeature; 3] = [
+        JadeFeature::AntiExfil,
+        JadeFeature::RegisterMultisig,
+        JadeFeature::Elip100AssetInfo,
+    ];
+
+    /// The first firmware version supporting the feature
+    pub fn min_version(&self) -> FirmwareVersion {
+        match self {
+            JadeFeature::AntiExfil => FirmwareVersion::new(0, 1, 27),
+            JadeFeature::RegisterMultisig => FirmwareVersion::new(0, 1, 33),
+            JadeFeature::Elip100AssetInfo => FirmwareVersion::new(1, 0, 30),
+        }
+    }
+}
+
+impl fmt::Display for JadeFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            JadeFeature::AntiExfil => "anti-exfil signatures",
+            JadeFeature::RegisterMultisig => "multisig registration",
+            JadeFeature::Elip100AssetInfo => "ELIP-100 asset metadata",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// The features supported by a Jade, according to its firmware version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    version: FirmwareVersion,
+}
+
+impl Capabilities {
+    pub fn new(version: FirmwareVersion) -> Self {
+        Self { version }
+    }
+
+    /// The firmware version of the device
+    pub fn version(&self) -> FirmwareVersion {
+        self.version
+    }
+
+    /// Whether the firmware supports `feature`
+    pub fn supports(&self, feature: JadeFeature) -> bool {
+        self.version >= feature.min_version()
+    }
+
+    /// Return [`Error::FirmwareTooOld`] if the firmware doesn't support `feature`
+    pub fn check(&self, feature: JadeFeature) -> Result<(), Error> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(Error::FirmwareTooOld {
+                feature,
+                current: self.version,
+                required: feature.min_version(),
+            })
+        }
+    }
+}
+
+impl TryFrom<&VersionInfoResult> for Capabilities {
+    type Error = Error;
+
+    fn try_from(info: &VersionInfoResult) -> Result<Self, Self::Error> {
+        Ok(Self::new(info.jade_version.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Capabilities, FirmwareVersion, JadeFeature};
+    use crate::Error;
+
+    #[test]
+    fn test_firmware_version() {
+        let v = |s: &str| s.parse::<FirmwareVersion>();
+        assert_eq!(v("1.0.31").unwrap(), FirmwareVersion::new(1, 0, 31));
+        assert_eq!(v("0.1.48-dirty").unwrap(), FirmwareVersion::new(0, 1, 48));
+        assert_eq!(v("1.0.31").unwrap().to_string(), "1.0.31");
+        assert!(v("1.0").is_err());
+        assert!(v("1.0.31.1").is_err());
+        assert!(v("one.0.31").is_err());
+        assert!(v("").is_err());
+        assert!(v("0.1.48").unwrap() < v("1.0.0").unwrap());
+        assert!(v("0.1.9").unwrap() < v("0.1.10").unwrap());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let caps = Capabilities::new(FirmwareVersion::new(0, 1, 40));
+        assert!(caps.supports(JadeFeature::AntiExfil));
+        assert!(caps.supports(JadeFeature::RegisterMultisig));
+        assert!(!caps.supports(JadeFeature::Elip100AssetInfo));
+        caps.check(JadeFeature::RegisterMultisig).unwrap();
+        let err = caps.check(JadeFeature::Elip100AssetInfo).unwrap_err();
+        assert!(matches!(err, Error::FirmwareTooOld { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Jade firmware 0.1.40 does not support ELIP-100 asset metadata, update it to 1.0.30 or later"
+        );
+
+        let caps = Capabilities::new(FirmwareVersion::new(1, 0, 30));
+        assert!(JadeFeature::ALL.iter().all(|f| caps.supports(*f)));
+    }
+}
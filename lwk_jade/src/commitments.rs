@@ -0,0 +1,147 @@
+//! Compute the trusted commitments of the outputs Jade is asked to sign
+//!
+//! For every blinded output Jade must receive the unblinded asset and value, together with the
+//! proofs that they are committed in the asset generator and value commitment of the
+//! transaction. Inconsistent values make Jade fail with a generic error, so they are checked
+//! here before sending the request.
+
+use elements::encode::serialize;
+use elements::pset::{Output, PartiallySignedTransaction};
+use elements::{BlindAssetProofs, BlindValueProofs, Script};
+use lwk_common::{burn_script, EC};
+
+use crate::sign_liquid_tx::Commitment;
+use crate::{Error, Result};
+
+/// The trusted commitments of the PSET outputs, as expected in `SignLiquidTxParams`
+///
+/// Fee and burn outputs, which are not blinded, have no commitment.
+pub fn trusted_commitments(pset: &PartiallySignedTransaction) -> Result<Vec<Option<Commitment>>> {
+    let burn_script = burn_script();
+    pset.outputs()
+        .iter()
+        .enumerate()
+        .map(|(i, output)| trusted_commitment(i, output, &burn_script))
+        .collect()
+}
+
+fn trusted_commitment(
+    i: usize,
+    output: &Output,
+    burn_script: &Script,
+) -> Result<Option<Commitment>> {
+    let asset = output.asset.ok_or(Error::MissingAssetIdInOutput(i))?;
+    if output.script_pubkey.is_empty() || &output.script_pubkey == burn_script {
+        return Ok(None);
+    }
+    let value = output.amount.ok_or(Error::MissingAmountInOutput(i))?;
+    let asset_generator = output
+        .asset_comm
+        .ok_or(Error::MissingAssetCommInOutput(i))?;
+    let value_commitment = output
+        .amount_comm
+        .ok_or(Error::MissingAmountCommInOutput(i))?;
+    let asset_blind_proof = output
+        .blind_asset_proof
+        .as_ref()
+        .ok_or(Error::MissingBlindAssetProofInOutput(i))?;
+    let value_blind_proof = output
+        .blind_value_proof
+        .as_ref()
+        .ok_or(Error::MissingBlindValueProofInOutput(i))?;
+    let blinding_key = output
+        .blinding_key
+        .ok_or(Error::MissingBlindingKeyInOutput(i))?;
+
+    if !asset_blind_proof.blind_asset_proof_verify(&EC, asset, asset_generator) {
+        return Err(Error::InvalidBlindAssetProofInOutput(i));
+    }
+    if !value_blind_proof.blind_value_proof_verify(&EC, value, asset_generator, value_commitment) {
+        return Err(Error::InvalidBlindValueProofInOutput(i));
+    }
+
+    let mut asset_id = serialize(&asset);
+    asset_id.reverse(); // Jade want it reversed
+    Ok(Some(Commitment {
+        asset_generator: asset_generator.serialize().to_vec(),
+        asset_id,
+        blinding_key: blinding_key.to_bytes(),
+        value,
+        value_commitment: value_commitment.serialize().to_vec(),
+        value_blind_proof: value_blind_proof.serialize(),
+        asset_blind_proof: asset_blind_proof.serialize(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::hex::ToHex;
+    use elements::pset::PartiallySignedTransaction;
+    use serde_json::{json, Value};
+
+    use super::trusted_commitments;
+    use crate::sign_liquid_tx::Commitment;
+    use crate::Error;
+
+    fn pset() -> PartiallySignedTransaction {
+        let b64 = include_str!("../test_data/pset_to_be_signed.base64");
+        PartiallySignedTransaction::from_str(b64.trim()).unwrap()
+    }
+
+    fn to_json(commitment: &Option<Commitment>) -> Value {
+        match commitment {
+            None => Value::Null,
+            Some(c) => json!({
+                "asset_generator": c.asset_generator.to_hex(),
+                "asset_id": c.asset_id.to_hex(),
+                "blinding_key": c.blinding_key.to_hex(),
+                "value": c.value,
+                "value_commitment": c.value_commitment.to_hex(),
+                "value_blind_proof": c.value_blind_proof.to_hex(),
+                "asset_blind_proof": c.asset_blind_proof.to_hex(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_trusted_commitments_vectors() {
+        let expected: Value =
+            serde_json::from_str(include_str!("../test_data/trusted_commitments.json")).unwrap();
+        let commitments = trusted_commitments(&pset()).unwrap();
+        let commitments: Vec<_> = commitments.iter().map(to_json).collect();
+        assert_eq!(Value::Array(commitments), expected);
+    }
+
+    #[test]
+    fn test_trusted_commitments_invalid() {
+        let pset = pset();
+        let i = pset
+            .outputs()
+            .iter()
+            .position(|o| o.blind_value_proof.is_some())
+            .unwrap();
+
+        let mut wrong_value = pset.clone();
+        *wrong_value.outputs_mut()[i].amount.as_mut().unwrap() += 1;
+        assert!(matches!(
+            trusted_commitments(&wrong_value),
+            Err(Error::InvalidBlindValueProofInOutput(n)) if n == i
+        ));
+
+        let mut wrong_asset = pset.clone();
+        wrong_asset.outputs_mut()[i].asset = Some(elements::AssetId::default());
+        assert!(matches!(
+            trusted_commitments(&wrong_asset),
+            Err(Error::InvalidBlindAssetProofInOutput(n)) if n == i
+        ));
+
+        let mut missing = pset;
+        missing.outputs_mut()[i].blind_value_proof = None;
+        assert!(matches!(
+            trusted_commitments(&missing),
+            Err(Error::MissingBlindValueProofInOutput(n)) if n == i
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+//! Discovery of the Jade devices connected to the host
+//!
+//! Apps can show the [`JadeDevice`]s returned by `list_devices()` in a device picker and then
+//! connect to the chosen one with `Jade::from_device()`, both require the `serial` feature.
+//!
+//! Only USB serial devices are listed. Jades advertising via Bluetooth Low Energy are not
+//! listed, since this crate has no Bluetooth transport to connect to them: the identifiers
+//! returned would not be usable to open a connection.
+
+use std::fmt;
+
+use crate::JADE_DEVICE_IDS;
+
+/// A device connected to the host which may be a Jade
+///
+/// The USB serial chips used by Jades are used by other devices too, so a device may not be a
+/// Jade until it answers to `version_info()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JadeDevice {
+    /// The serial port name, such as `/dev/ttyUSB0` or `COM3`, identifying the device
+    pub port_name: String,
+
+    /// The USB vendor ID
+    pub vid: u16,
+
+    /// The USB product ID
+    pub pid: u16,
+
+    /// The serial number of the USB device, if available
+    pub serial_number: Option<String>,
+
+    /// The manufacturer of the USB device, if available
+    pub manufacturer: Option<String>,
+
+    /// The product name of the USB device, if available
+    pub product: Option<String>,
+}
+
+impl JadeDevice {
+    /// The device on `port`, if it's a USB serial port with the IDs of a Jade
+    #[cfg(feature = "serial")]
+    pub fn from_port(port: &serialport::SerialPortInfo) -> Option<Self> {
+        match &port.port_type {
+            serialport::SerialPortType::UsbPort(usb) if is_jade_usb_id(usb.vid, usb.pid) => {
+                Some(Self {
+                    port_name: port.port_name.clone(),
+                    vid: usb.vid,
+                    pid: usb.pid,
+                    serial_number: usb.serial_number.clone(),
+                    manufacturer: usb.manufacturer.clone(),
+                    product: usb.product.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for JadeDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let product = self.product.as_deref().unwrap_or("Jade");
+        write!(f, "{product} ({})", self.port_name)?;
+        if let Some(serial_number) = self.serial_number.as_deref() {
+            write!(f, " {serial_number}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the USB vendor and product IDs are the ones of a Jade, see [`JADE_DEVICE_IDS`]
+pub fn is_jade_usb_id(vid: u16, pid: u16) -> bool {
+    JADE_DEVICE_IDS.contains(&(vid, pid))
+}
+
+/// The devices connected via USB serial which may be Jades
+#[cfg(feature = "serial")]
+pub fn list_devices() -> crate::Result<Vec<JadeDevice>> {
+    let ports = serialport::available_ports()?;
+    log::debug!("available serial ports: {}", ports.len());
+    Ok(ports.iter().filter_map(JadeDevice::from_port).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_jade_usb_id, JadeDevice};
+
+    #[test]
+    fn test_jade_device() {
+        assert!(is_jade_usb_id(0x10c4, 0xea60));
+        assert!(is_jade_usb_id(0x303a, 0x4001));
+        assert!(!is_jade_usb_id(0x10c4, 0x4001));
+
+        let mut device = JadeDevice {
+            port_name: "/dev/ttyACM0".to_string(),
+            vid: 0x303a,
+            pid: 0x4001,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+        };
+        assert_eq!(device.to_string(), "Jade (/dev/ttyACM0)");
+        device.product = Some("Jade Plus".to_string());
+        device.serial_number = Some("ABC123".to_string());
+        assert_eq!(device.to_string(), "Jade Plus (/dev/ttyACM0) ABC123");
+    }
+}
@@ -7,6 +7,8 @@ use lwk_common::Network;
 use serde::{Deserialize, Serialize};
 use serde_cbor::Value;
 
+use crate::capabilities::{FirmwareVersion, JadeFeature};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Jade Error: {0}")]
@@ -81,6 +83,12 @@ pub enum Error {
     #[error("Missing blind value proof in output {0}")]
     MissingBlindValueProofInOutput(usize),
 
+    #[error("Blind asset proof doesn't match the asset commitment in output {0}")]
+    InvalidBlindAssetProofInOutput(usize),
+
+    #[error("Blind value proof doesn't match the amount commitment in output {0}")]
+    InvalidBlindValueProofInOutput(usize),
+
     #[error("Missing witness utxo in input {0}")]
     MissingWitnessUtxoInInput(usize),
 
@@ -116,6 +124,33 @@ pub enum Error {
 
     #[error("{0}")]
     Generic(String),
+
+    #[error("Jade derives BIP85 mnemonics of 12 or 24 words, not {0}")]
+    UnsupportedBip85WordCount(u32),
+
+    #[error("Cannot decrypt the BIP85 entropy returned by Jade")]
+    Bip85EntropyDecryptionFailed,
+
+    #[error(transparent)]
+    Bip39(#[from] bip39::Error),
+
+    #[error("Invalid PIN server: {0}")]
+    InvalidPinServer(String),
+
+    #[error("Jade attestation failed, the device may not be genuine: {0}")]
+    AttestationFailed(String),
+
+    #[error("Invalid Jade firmware version: {0}")]
+    InvalidFirmwareVersion(String),
+
+    #[error(
+        "Jade firmware {current} does not support {feature}, update it to {required} or later"
+    )]
+    FirmwareTooOld {
+        feature: JadeFeature,
+        current: FirmwareVersion,
+        required: FirmwareVersion,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
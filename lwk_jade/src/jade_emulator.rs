@@ -1,9 +1,6 @@
 #![allow(clippy::unwrap_used)]
 
-use crate::{
-    protocol::{DebugSetMnemonicParams, UpdatePinserverParams},
-    Jade, Network,
-};
+use crate::{pinserver::CustomPinServer, protocol::DebugSetMnemonicParams, Jade, Network};
 use lwk_containers::testcontainers::{clients::Cli, Container};
 use lwk_containers::{JadeEmulator, PinServer, EMULATOR_PORT, PIN_SERVER_PORT};
 use tempfile::TempDir;
@@ -56,16 +53,12 @@ impl<'a> TestJadeEmulator<'a> {
         let port = pin_container.get_host_port_ipv4(PIN_SERVER_PORT);
         let pin_server_url = format!("http://127.0.0.1:{}", port);
 
-        let params = UpdatePinserverParams {
-            reset_details: false,
-            reset_certificate: false,
-            url_a: pin_server_url.clone(),
-            url_b: "".to_string(),
-            pubkey: pin_server_pub_key.to_bytes(),
-            certificate: "".into(),
-        };
+        let pin_server = CustomPinServer::new(&pin_server_url, pin_server_pub_key.inner).unwrap();
 
-        let result = test_jade_emul.jade.update_pinserver(params).unwrap();
+        let result = test_jade_emul
+            .jade
+            .set_custom_pinserver(&pin_server)
+            .unwrap();
         assert!(result);
 
         test_jade_emul.jade.unlock().unwrap();
@@ -5,9 +5,16 @@
 #[cfg(feature = "asyncr")]
 pub mod asyncr;
 
+pub mod attestation;
+pub mod bip85;
+pub mod blinding;
+pub mod capabilities;
+pub mod commitments;
 pub mod consts;
+pub mod devices;
 pub mod error;
 pub mod get_receive_address;
+pub mod pinserver;
 pub mod protocol;
 pub mod register_multisig;
 pub mod sign_liquid_tx;
@@ -21,7 +28,7 @@ pub use jade_emulator::TestJadeEmulator;
 #[cfg(feature = "sync")]
 mod sync;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub use consts::{BAUD_RATE, TIMEOUT};
 use elements::{
@@ -38,10 +45,10 @@ use elements::{
 };
 pub use error::Error;
 use get_receive_address::{SingleOrMulti, Variant};
-use lwk_common::{burn_script, Network};
+use lwk_common::Network;
 
 use register_multisig::RegisteredMultisigDetails;
-use sign_liquid_tx::{AssetInfo, Change, Commitment, Contract, Prevout, SignLiquidTxParams};
+use sign_liquid_tx::{AssetInfo, Change, Contract, Prevout, SignLiquidTxParams};
 #[cfg(feature = "sync")]
 pub use sync::Jade;
 
@@ -109,6 +116,43 @@ pub(crate) fn vec_to_derivation_path(path: &[u32]) -> DerivationPath {
     DerivationPath::from_iter(path.iter().cloned().map(Into::into))
 }
 
+/// The DER bytes of a PEM block with the given `label`, such as `CERTIFICATE`
+pub(crate) fn pem_decode(pem: &str, label: &str) -> Option<Vec<u8>> {
+    use elements::bitcoin::base64::{engine::general_purpose::STANDARD, Engine};
+    let body = pem
+        .trim()
+        .strip_prefix(&format!("-----BEGIN {label}-----"))?
+        .strip_suffix(&format!("-----END {label}-----"))?;
+    let body: String = body.split_whitespace().collect();
+    STANDARD.decode(body).ok().filter(|der| !der.is_empty())
+}
+
+/// Read a DER element with the given `tag`, returning its content and the following bytes
+pub(crate) fn der_read(bytes: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&first, rest) = bytes.split_first()?;
+    if first != tag {
+        return None;
+    }
+    let (&len, mut rest) = rest.split_first()?;
+    let len = if len < 0x80 {
+        len as usize
+    } else {
+        let num_bytes = (len & 0x7f) as usize;
+        if num_bytes == 0 || num_bytes > 4 || rest.len() < num_bytes {
+            return None;
+        }
+        let (len_bytes, r) = rest.split_at(num_bytes);
+        rest = r;
+        len_bytes
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize)
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some(rest.split_at(len))
+}
+
 pub(crate) fn json_to_cbor(value: &serde_json::Value) -> Result<serde_cbor::Value> {
     // serde_cbor::to_value doesn't exist
     Ok(serde_cbor::from_slice(&serde_cbor::to_vec(&value)?)?)
@@ -122,50 +166,12 @@ fn create_jade_sign_req(
 ) -> Result<SignLiquidTxParams> {
     let tx = pset.extract_tx()?;
     let txn = serialize(&tx);
-    let burn_script = burn_script();
+    let trusted_commitments = commitments::trusted_commitments(pset)?;
     let mut asset_ids_in_tx = HashSet::new();
-    let mut trusted_commitments = vec![];
     let mut changes = vec![];
     for (i, output) in pset.outputs().iter().enumerate() {
         let asset_id = output.asset.ok_or(Error::MissingAssetIdInOutput(i))?;
         asset_ids_in_tx.insert(asset_id);
-        let mut asset_id = serialize(&asset_id);
-        asset_id.reverse(); // Jade want it reversed
-        let unblinded = output.script_pubkey.is_empty() || output.script_pubkey == burn_script;
-        let trusted_commitment = if unblinded {
-            // fee output or burn output
-            None
-        } else {
-            Some(Commitment {
-                asset_blind_proof: output
-                    .blind_asset_proof
-                    .as_ref()
-                    .ok_or(Error::MissingBlindAssetProofInOutput(i))?
-                    .serialize(),
-                asset_generator: output
-                    .asset_comm
-                    .ok_or(Error::MissingAssetCommInOutput(i))?
-                    .serialize()
-                    .to_vec(),
-                asset_id,
-                blinding_key: output
-                    .blinding_key
-                    .ok_or(Error::MissingBlindingKeyInOutput(i))?
-                    .to_bytes(),
-                value: output.amount.ok_or(Error::MissingAmountInOutput(i))?,
-                value_commitment: output
-                    .amount_comm
-                    .ok_or(Error::MissingAmountCommInOutput(i))?
-                    .serialize()
-                    .to_vec(),
-                value_blind_proof: output
-                    .blind_value_proof
-                    .as_ref()
-                    .ok_or(Error::MissingBlindValueProofInOutput(i))?
-                    .serialize(),
-            })
-        };
-        trusted_commitments.push(trusted_commitment);
 
         let mut change = None;
         for (fingerprint, path) in output.bip32_derivation.values() {
@@ -278,6 +284,23 @@ fn script_code_wpkh(script: &Script) -> Script {
     Script::from(script_code)
 }
 
+// Number of signatures the Jade is going to produce, used to report the signing progress
+fn count_to_sign(
+    pset: &PartiallySignedTransaction,
+    signers_commitment: &HashMap<elements::bitcoin::PublicKey, Vec<u8>>,
+) -> usize {
+    pset.inputs()
+        .iter()
+        .map(|input| {
+            input
+                .bip32_derivation
+                .keys()
+                .filter(|k| signers_commitment.contains_key(k))
+                .count()
+        })
+        .sum()
+}
+
 // taken and adapted from:
 // https://github.com/rust-bitcoin/rust-bitcoin/blob/37daf4620c71dc9332c3e08885cf9de696204bca/bitcoin/src/blockdata/script/borrowed.rs#L266
 // TODO remove once it's released
@@ -0,0 +1,197 @@
+//! Configuration of the PIN server used by Jade
+//!
+//! Jade uses the Blockstream PIN server by default. Users running their own PIN server set it
+//! with a [`CustomPinServer`], which is validated before being sent to the device, and can go
+//! back to the default with [`UpdatePinserverParams::reset()`].
+//!
+//! Note the PIN server pubkey is needed to unlock the wallet, after changing it a Jade already
+//! set up must be set up again.
+
+use elements::secp256k1_zkp::PublicKey;
+use reqwest::Url;
+
+use crate::protocol::UpdatePinserverParams;
+use crate::{der_read, pem_decode, Error, Result};
+
+/// A self-hosted PIN server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomPinServer {
+    url_a: String,
+    url_b: Option<String>,
+    pubkey: PublicKey,
+    certificate: Option<String>,
+}
+
+impl CustomPinServer {
+    /// The PIN server reachable at the http(s) `url` and authenticating with `pubkey`
+    pub fn new(url: &str, pubkey: PublicKey) -> Result<Self> {
+        Ok(Self {
+            url_a: check_url(url)?,
+            url_b: None,
+            pubkey,
+            certificate: None,
+        })
+    }
+
+    /// A second url of the same PIN server, usually an onion one
+    pub fn with_url_b(mut self, url: &str) -> Result<Self> {
+        self.url_b = Some(check_url(url)?);
+        Ok(self)
+    }
+
+    /// The PEM root certificate of the PIN server, needed if its https certificate is not signed
+    /// by a well known authority
+    pub fn with_certificate(mut self, pem: &str) -> Result<Self> {
+        self.certificate = Some(check_certificate(pem)?);
+        Ok(self)
+    }
+
+    pub fn url_a(&self) -> &str {
+        &self.url_a
+    }
+
+    pub fn url_b(&self) -> Option<&str> {
+        self.url_b.as_deref()
+    }
+
+    pub fn pubkey(&self) -> &PublicKey {
+        &self.pubkey
+    }
+
+    pub fn certificate(&self) -> Option<&str> {
+        self.certificate.as_deref()
+    }
+}
+
+impl From<&CustomPinServer> for UpdatePinserverParams {
+    fn from(pin_server: &CustomPinServer) -> Self {
+        UpdatePinserverParams {
+            reset_details: false,
+            // a previous custom certificate must not be used with the new urls
+            reset_certificate: pin_server.certificate.is_none(),
+            url_a: pin_server.url_a.clone(),
+            url_b: pin_server.url_b.clone().unwrap_or_default(),
+            pubkey: pin_server.pubkey.serialize().to_vec(),
+            certificate: pin_server.certificate.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl UpdatePinserverParams {
+    /// Revert to the default Blockstream PIN server, removing any custom certificate
+    pub fn reset() -> Self {
+        UpdatePinserverParams {
+            reset_details: true,
+            reset_certificate: true,
+            url_a: String::new(),
+            url_b: String::new(),
+            pubkey: vec![],
+            certificate: String::new(),
+        }
+    }
+}
+
+fn check_url(url: &str) -> Result<String> {
+    let err = |reason: &str| Error::InvalidPinServer(format!("{url}: {reason}"));
+    let parsed = Url::parse(url).map_err(|e| err(&e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(err("only http and https urls are supported"));
+    }
+    if parsed.host_str().is_none() {
+        return Err(err("missing host"));
+    }
+    Ok(url.to_string())
+}
+
+fn check_certificate(pem: &str) -> Result<String> {
+    match pem_decode(pem, "CERTIFICATE") {
+        Some(der) if is_x509_certificate(&der) => Ok(pem.trim().to_string()),
+        _ => Err(Error::InvalidPinServer(
+            "certificate is not a valid PEM certificate".to_string(),
+        )),
+    }
+}
+
+/// Whether `der` has the structure of an X.509 certificate, the content is not validated
+///
+/// Certificate ::= SEQUENCE { tbsCertificate SEQUENCE, signatureAlgorithm SEQUENCE,
+/// signature BIT STRING }
+fn is_x509_certificate(der: &[u8]) -> bool {
+    let parse = || {
+        let (certificate, rest) = der_read(der, 0x30)?;
+        let (_tbs_certificate, certificate) = der_read(certificate, 0x30)?;
+        let (_signature_algorithm, certificate) = der_read(certificate, 0x30)?;
+        let (_signature, certificate) = der_read(certificate, 0x03)?;
+        Some(rest.is_empty() && certificate.is_empty())
+    };
+    parse().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use elements::secp256k1_zkp::{PublicKey, SecretKey};
+    use lwk_common::EC;
+
+    use super::CustomPinServer;
+    use crate::protocol::UpdatePinserverParams;
+
+    /// A self-signed certificate generated with `openssl req -x509`
+    const CERT: &str = include_str!("../test_data/pin_server_cert.pem");
+
+    #[test]
+    fn test_custom_pin_server() {
+        let pubkey = PublicKey::from_secret_key(&EC, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+
+        let pin_server = CustomPinServer::new("https://pin.example.com", pubkey).unwrap();
+        let params = UpdatePinserverParams::from(&pin_server);
+        assert!(!params.reset_details);
+        assert!(params.reset_certificate);
+        assert_eq!(params.url_a, "https://pin.example.com");
+        assert_eq!(params.url_b, "");
+        assert_eq!(params.pubkey, pubkey.serialize().to_vec());
+
+        let onion = "http://mrrxtq6tjpbnbm7vh5jt6mpjctn7ggyfy5wegvbeff3x7jrznqawlmid.onion";
+        let pin_server = pin_server
+            .with_url_b(onion)
+            .unwrap()
+            .with_certificate(CERT)
+            .unwrap();
+        let params = UpdatePinserverParams::from(&pin_server);
+        assert!(!params.reset_certificate);
+        assert_eq!(params.url_b, onion);
+        assert_eq!(params.certificate, CERT.trim());
+
+        assert!(CustomPinServer::new("pin.example.com", pubkey).is_err());
+        assert!(CustomPinServer::new("ftp://pin.example.com", pubkey).is_err());
+        let pin_server = CustomPinServer::new("http://127.0.0.1:8096", pubkey).unwrap();
+        assert!(pin_server.clone().with_certificate("not a cert").is_err());
+        let no_base64 = CERT.replace("MIIB", "!!!!");
+        assert!(pin_server.clone().with_certificate(&no_base64).is_err());
+        // valid base64 but not a certificate
+        let not_der = "-----BEGIN CERTIFICATE-----\nMIIBszCCAVmg\n-----END CERTIFICATE-----";
+        assert!(pin_server.clone().with_certificate(not_der).is_err());
+        let truncated: String = CERT.lines().take(4).chain(CERT.lines().last()).collect();
+        assert!(pin_server.clone().with_certificate(&truncated).is_err());
+        let empty = "-----BEGIN CERTIFICATE-----\n-----END CERTIFICATE-----";
+        assert!(pin_server.with_certificate(empty).is_err());
+
+        let to_json = |params: &UpdatePinserverParams| {
+            let value = serde_cbor::value::to_value(params).unwrap();
+            serde_json::to_value(value).unwrap()
+        };
+        // the details are always sent when set, also if empty
+        let pin_server = CustomPinServer::new("https://pin.example.com", pubkey).unwrap();
+        let json = to_json(&UpdatePinserverParams::from(&pin_server));
+        assert_eq!(json["urlB"], "");
+        assert_eq!(json["certificate"], "");
+        assert_eq!(json.as_object().unwrap().len(), 6);
+
+        // Jade refuses to reset and set the details at the same time
+        let params = UpdatePinserverParams::reset();
+        assert!(params.reset_details && params.reset_certificate);
+        assert_eq!(
+            to_json(&params),
+            serde_json::json!({"reset_details": true, "reset_certificate": true})
+        );
+    }
+}
@@ -3,10 +3,13 @@ use std::fmt::Debug;
 use elements::hex::ToHex;
 use lwk_common::Network;
 use rand::RngCore;
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
 use serde_cbor::Value;
 
 use crate::{
+    attestation::SignAttestationParams,
+    bip85::GetBip85Bip39EntropyParams,
+    blinding::{GetBlindingFactorParams, GetCommitmentsParams, GetSharedNonceParams},
     error::ErrorDetails,
     get_receive_address::GetReceiveAddressParams,
     register_multisig::{GetRegisteredMultisigParams, RegisterMultisigParams},
@@ -41,6 +44,11 @@ pub enum Request {
     RegisterMultisig(RegisterMultisigParams),
     GetRegisteredMultisigs,
     GetRegisteredMultisig(GetRegisteredMultisigParams),
+    GetBlindingFactor(GetBlindingFactorParams),
+    GetCommitments(GetCommitmentsParams),
+    GetSharedNonce(GetSharedNonceParams),
+    GetBip85Bip39Entropy(GetBip85Bip39EntropyParams),
+    SignAttestation(SignAttestationParams),
     Generic(GenericMethod),
 }
 
@@ -73,6 +81,11 @@ impl std::fmt::Display for Request {
             Request::RegisterMultisig(_) => write!(f, "register_multisig"),
             Request::GetRegisteredMultisigs => write!(f, "get_registered_multisigs"),
             Request::GetRegisteredMultisig(_) => write!(f, "get_registered_multisig"),
+            Request::GetBlindingFactor(_) => write!(f, "get_blinding_factor"),
+            Request::GetCommitments(_) => write!(f, "get_commitments"),
+            Request::GetSharedNonce(_) => write!(f, "get_shared_nonce"),
+            Request::GetBip85Bip39Entropy(_) => write!(f, "get_bip85_bip39_entropy"),
+            Request::SignAttestation(_) => write!(f, "sign_attestation"),
             Request::Generic(g) => write!(f, "{0}", g.method),
         }
     }
@@ -302,7 +315,7 @@ pub struct HttpParams {
     data: Value,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize)]
 pub struct UpdatePinserverParams {
     pub reset_details: bool,
     pub reset_certificate: bool,
@@ -318,6 +331,27 @@ pub struct UpdatePinserverParams {
     pub certificate: String,
 }
 
+impl Serialize for UpdatePinserverParams {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // Jade refuses to reset and set the details at the same time, so the details are sent
+        // only when not resetting them
+        let len = if self.reset_details { 2 } else { 6 };
+        let mut state = serializer.serialize_struct("UpdatePinserverParams", len)?;
+        state.serialize_field("reset_details", &self.reset_details)?;
+        state.serialize_field("reset_certificate", &self.reset_certificate)?;
+        if !self.reset_details {
+            state.serialize_field("urlA", &self.url_a)?;
+            state.serialize_field("urlB", &self.url_b)?;
+            state.serialize_field("pubkey", serde_bytes::Bytes::new(&self.pubkey))?;
+            state.serialize_field("certificate", &self.certificate)?;
+        }
+        state.end()
+    }
+}
+
 impl Debug for UpdatePinserverParams {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UpdatePinserverParams")
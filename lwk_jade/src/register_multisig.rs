@@ -206,13 +206,13 @@ impl MultisigSigner {
 
 #[derive(Deserialize, Serialize)]
 pub struct RegisteredMultisig {
-    variant: String,
-    sorted: bool,
-    threshold: u32,
+    pub variant: String,
+    pub sorted: bool,
+    pub threshold: u32,
     pub num_signers: u32,
 
     #[serde(with = "serde_bytes")]
-    master_blinding_key: Vec<u8>,
+    pub master_blinding_key: Vec<u8>,
 }
 
 impl std::fmt::Debug for RegisteredMultisig {
@@ -233,6 +233,30 @@ pub struct RegisteredMultisigDetails {
     pub descriptor: JadeDescriptor,
 }
 
+impl RegisteredMultisigDetails {
+    /// The registered multisig as a descriptor usable with lwk, with the `<0;1>/*` multipath
+    pub fn confidential_descriptor(
+        &self,
+    ) -> Result<ConfidentialDescriptor<DescriptorPublicKey>, Error> {
+        (&self.descriptor).try_into()
+    }
+}
+
+/// The name of the multisig in `registered` with the same threshold, signers and master blinding
+/// key of `desc`
+///
+/// Jade doesn't store the derivation after the signers xpubs, thus it's not compared.
+pub fn registered_multisig_name<'a>(
+    registered: &'a [RegisteredMultisigDetails],
+    desc: &ConfidentialDescriptor<DescriptorPublicKey>,
+) -> Result<Option<&'a str>, Error> {
+    let jade_desc: JadeDescriptor = desc.try_into()?;
+    Ok(registered
+        .iter()
+        .find(|details| details.descriptor == jade_desc)
+        .map(|details| details.multisig_name.as_str()))
+}
+
 #[cfg(test)]
 mod test {
     use elements::bitcoin::bip32::Fingerprint;
@@ -240,7 +264,9 @@ mod test {
 
     use crate::register_multisig::MultisigSigner;
 
-    use super::{JadeDescriptor, RegisterMultisigParams};
+    use super::{
+        registered_multisig_name, JadeDescriptor, RegisterMultisigParams, RegisteredMultisigDetails,
+    };
 
     #[test]
     fn parse_register_multisig() {
@@ -293,4 +319,50 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_registered_multisig_name() {
+        let a = "tpubDDCNstnPhbdd4vwbw5UWK3vRQSF1WXQkvBHpNXpKJAkwFYjwu735EH3GVf53qwbWimzewDUv68MUmRDgYtQ1AU8FRCPkazfuaBp7LaEaohG";
+        let b = "tpubDDExQpZg2tziZ7ACSBCYsY3rYxAZtTRBgWwioRLYqgNBguH6rMHN1D8epTxUQUB5kM5nxkEtr2SNic6PJLPubcGMR6S2fmDZTzL9dHpU7ka";
+        let slip77_key = "9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023";
+        let parse = |d: String| {
+            d.parse::<ConfidentialDescriptor<DescriptorPublicKey>>()
+                .unwrap()
+        };
+        let desc = parse(format!(
+            "ct(slip77({slip77_key}),elwsh(multi(2,[00000000/48h/1h/0h/2h]{a}/<0;1>/*,{b}/<0;1>/*)))"
+        ));
+        let details = RegisteredMultisigDetails {
+            multisig_name: "you_and_me".to_string(),
+            descriptor: (&desc).try_into().unwrap(),
+        };
+        assert_eq!(details.confidential_descriptor().unwrap(), desc);
+
+        let registered = [details];
+        assert_eq!(
+            registered_multisig_name(&registered, &desc).unwrap(),
+            Some("you_and_me")
+        );
+        // Jade doesn't know the derivation after the xpubs
+        let same = parse(format!(
+            "ct(slip77({slip77_key}),elwsh(multi(2,[00000000/48h/1h/0h/2h]{a}/*,{b}/*)))"
+        ));
+        assert_eq!(
+            registered_multisig_name(&registered, &same).unwrap(),
+            Some("you_and_me")
+        );
+
+        let other = parse(format!(
+            "ct(slip77({slip77_key}),elwsh(sortedmulti(2,[00000000/48h/1h/0h/2h]{a}/<0;1>/*,{b}/<0;1>/*)))"
+        ));
+        assert_eq!(registered_multisig_name(&registered, &other).unwrap(), None);
+        let other = parse(format!(
+            "ct(slip77({slip77_key}),elwsh(multi(1,[00000000/48h/1h/0h/2h]{a}/<0;1>/*,{b}/<0;1>/*)))"
+        ));
+        assert_eq!(registered_multisig_name(&registered, &other).unwrap(), None);
+        let other = parse(format!(
+            "ct(elip151,elwsh(multi(2,[00000000/48h/1h/0h/2h]{a}/<0;1>/*,{b}/<0;1>/*)))"
+        ));
+        assert!(registered_multisig_name(&registered, &other).is_err());
+    }
 }
@@ -3,25 +3,38 @@ use std::net::SocketAddr;
 use std::sync::Mutex;
 use std::{collections::HashMap, io::ErrorKind};
 
+use crate::attestation::{Attestation, SignAttestationParams};
+use crate::bip85::{check_num_words, EncryptedEntropy, GetBip85Bip39EntropyParams};
+use crate::blinding::{
+    asset_and_value_blinding_factors, BlindingFactorType, Commitments, GetBlindingFactorParams,
+    GetCommitmentsParams, GetSharedNonceParams,
+};
+use crate::capabilities::{Capabilities, JadeFeature};
 use crate::get_receive_address::{GetReceiveAddressParams, SingleOrMulti, Variant};
+use crate::pinserver::CustomPinServer;
 use crate::protocol::{
     AuthUserParams, DebugSetMnemonicParams, EntropyParams, EpochParams, GenericMethod,
     GetMasterBlindingKeyParams, GetSignatureParams, GetXpubParams, IsAuthResult, Request,
     SignMessageParams, UpdatePinserverParams, VersionInfoResult,
 };
 use crate::register_multisig::{
-    GetRegisteredMultisigParams, RegisterMultisigParams, RegisteredMultisig,
-    RegisteredMultisigDetails,
+    registered_multisig_name, GetRegisteredMultisigParams, RegisterMultisigParams,
+    RegisteredMultisig, RegisteredMultisigDetails,
 };
 use crate::sign_liquid_tx::{SignLiquidTxParams, TxInputParams};
 use crate::{
     derivation_path_to_vec, json_to_cbor, try_parse_response, vec_to_derivation_path, Error, Result,
 };
+use bip39::Mnemonic;
 use connection::Connection;
 use elements::bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+use elements::hashes::{sha256d, Hash};
 use elements::pset::PartiallySignedTransaction;
+use elements::secp256k1_zkp::{PublicKey, SecretKey};
 use elements_miniscript::slip77::{self, MasterBlindingKey};
-use lwk_common::{Network, Signer};
+use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
+use lwk_common::{DescriptorBlindingKey, Network, Signer, Singlesig, EC};
 use serde::de::DeserializeOwned;
 use serde_bytes::ByteBuf;
 
@@ -30,6 +43,8 @@ mod sign_pset;
 
 #[cfg(feature = "serial")]
 use crate::consts::{BAUD_RATE, TIMEOUT};
+#[cfg(feature = "serial")]
+use crate::devices::JadeDevice;
 
 #[derive(Debug)]
 pub struct Jade {
@@ -44,6 +59,9 @@ pub struct Jade {
 
     /// Cached multisigs details
     multisigs_details: Mutex<Option<Vec<RegisteredMultisigDetails>>>,
+
+    /// Cached features supported by the firmware
+    capabilities: Mutex<Option<Capabilities>>,
 }
 
 impl Jade {
@@ -53,6 +71,7 @@ impl Jade {
             network,
             cached_xpubs: Mutex::new(HashMap::new()),
             multisigs_details: Mutex::new(None),
+            capabilities: Mutex::new(None),
         }
     }
 
@@ -72,6 +91,17 @@ impl Jade {
         self.send(Request::GetVersionInfo)
     }
 
+    /// The features supported by the firmware of the device, see [`crate::capabilities`]
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        let mut guard = self.capabilities.lock()?;
+        if let Some(capabilities) = *guard {
+            return Ok(capabilities);
+        }
+        let capabilities = Capabilities::try_from(&self.version_info()?)?;
+        *guard = Some(capabilities);
+        Ok(capabilities)
+    }
+
     pub fn set_epoch(&self, epoch: u64) -> Result<bool> {
         self.send(Request::SetEpoch(EpochParams { epoch }))
     }
@@ -88,6 +118,18 @@ impl Jade {
         self.send(Request::UpdatePinserver(params))
     }
 
+    /// Use a self-hosted PIN server instead of the default Blockstream one
+    ///
+    /// The user must confirm the change on the device.
+    pub fn set_custom_pinserver(&self, pin_server: &CustomPinServer) -> Result<bool> {
+        self.update_pinserver(pin_server.into())
+    }
+
+    /// Go back to the default Blockstream PIN server
+    pub fn reset_pinserver(&self) -> Result<bool> {
+        self.update_pinserver(UpdatePinserverParams::reset())
+    }
+
     fn get_xpub(&self, params: GetXpubParams) -> Result<Xpub> {
         self.send(Request::GetXpub(params))
     }
@@ -125,6 +167,7 @@ impl Jade {
     }
 
     pub fn register_multisig(&self, params: RegisterMultisigParams) -> Result<bool> {
+        self.capabilities()?.check(JadeFeature::RegisterMultisig)?;
         self.invalidate_registered_multisigs()?;
         self.send(Request::RegisterMultisig(params))
     }
@@ -140,6 +183,62 @@ impl Jade {
         self.send(Request::GetRegisteredMultisig(params))
     }
 
+    pub fn get_blinding_factor(&self, params: GetBlindingFactorParams) -> Result<ByteBuf> {
+        self.send(Request::GetBlindingFactor(params))
+    }
+
+    pub fn get_commitments(&self, params: GetCommitmentsParams) -> Result<Commitments> {
+        self.send(Request::GetCommitments(params))
+    }
+
+    pub fn get_shared_nonce(&self, params: GetSharedNonceParams) -> Result<ByteBuf> {
+        self.send(Request::GetSharedNonce(params))
+    }
+
+    pub fn get_bip85_bip39_entropy(
+        &self,
+        params: GetBip85Bip39EntropyParams,
+    ) -> Result<EncryptedEntropy> {
+        self.send(Request::GetBip85Bip39Entropy(params))
+    }
+
+    pub fn sign_attestation(&self, params: SignAttestationParams) -> Result<Attestation> {
+        self.send(Request::SignAttestation(params))
+    }
+
+    /// Verify the Jade is genuine, signing a random challenge with its attestation key, which must
+    /// be signed by one of the `verifying_keys`, PEM RSA public keys of the attestation
+    /// authorities, see [`Attestation::verify()`]
+    ///
+    /// Returns [`Error::AttestationFailed`] if the device may be counterfeit, users should be
+    /// warned before trusting it with funds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn verify_genuine_with_keys(&self, verifying_keys: &[&str]) -> Result<()> {
+        let mut challenge = vec![0u8; crate::attestation::CHALLENGE_LEN];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut challenge);
+        let params = SignAttestationParams {
+            challenge: challenge.clone(),
+        };
+        let attestation = self.sign_attestation(params)?;
+        attestation.verify(&challenge, verifying_keys)
+    }
+
+    /// The asset and value blinding factors Jade derives for the output at `output_index` of the
+    /// transaction spending the outpoints hashed in `hash_prevouts`
+    pub fn blinding_factors(
+        &self,
+        hash_prevouts: sha256d::Hash,
+        output_index: u32,
+    ) -> Result<(AssetBlindingFactor, ValueBlindingFactor)> {
+        let params = GetBlindingFactorParams {
+            hash_prevouts: hash_prevouts.to_byte_array().to_vec(),
+            output_index,
+            factor_type: BlindingFactorType::AssetAndValue,
+        };
+        let bytes = self.get_blinding_factor(params)?;
+        asset_and_value_blinding_factors(&bytes)
+    }
+
     pub fn get_cached_xpub(&self, params: GetXpubParams) -> Result<Xpub> {
         let mut guard = self.cached_xpubs.lock()?;
         let der_path = vec_to_derivation_path(&params.path);
@@ -152,6 +251,30 @@ impl Jade {
         }
     }
 
+    /// The multisigs registered on the Jade, by name, as descriptors usable with lwk
+    pub fn registered_multisig_descriptors(
+        &self,
+    ) -> Result<BTreeMap<String, ConfidentialDescriptor<DescriptorPublicKey>>> {
+        self.get_cached_registered_multisigs()?
+            .iter()
+            .map(|details| {
+                let desc = details.confidential_descriptor()?;
+                Ok((details.multisig_name.clone(), desc))
+            })
+            .collect()
+    }
+
+    /// The name of the multisig registered on the Jade matching `desc`, if any
+    ///
+    /// Use it to avoid asking the user to register the same descriptor twice.
+    pub fn registered_multisig_name(
+        &self,
+        desc: &ConfidentialDescriptor<DescriptorPublicKey>,
+    ) -> Result<Option<String>> {
+        let registered = self.get_cached_registered_multisigs()?;
+        Ok(registered_multisig_name(&registered, desc)?.map(ToString::to_string))
+    }
+
     fn get_cached_registered_multisigs(&self) -> Result<Vec<RegisteredMultisigDetails>> {
         let mut guard = self.multisigs_details.lock()?;
         if let Some(multisigs_details) = guard.as_ref() {
@@ -201,6 +324,35 @@ impl Jade {
         self.get_cached_xpub(params)
     }
 
+    /// The BIP85 child mnemonic of `num_words` words, 12 or 24, at `index`
+    ///
+    /// The user must confirm the export on the device, which is unlocked if needed. The child
+    /// mnemonic gives full control of its funds, handle it like any other secret.
+    pub fn bip85_mnemonic(&self, num_words: u32, index: u32) -> Result<Mnemonic> {
+        check_num_words(num_words)?;
+        self.unlock()?;
+        let secret_key = SecretKey::new(&mut rand::thread_rng());
+        let params = GetBip85Bip39EntropyParams {
+            num_words,
+            index,
+            pubkey: PublicKey::from_secret_key(&EC, &secret_key)
+                .serialize()
+                .to_vec(),
+        };
+        let entropy = self.get_bip85_bip39_entropy(params)?.decrypt(&secret_key)?;
+        Ok(Mnemonic::from_entropy(&entropy)?)
+    }
+
+    /// The descriptor of the first native segwit singlesig account of the Jade, blinded with its
+    /// SLIP77 master blinding key
+    ///
+    /// Use it to create a watch-only wallet of the Jade, the device is unlocked if needed.
+    pub fn wpkh_slip77_descriptor(&self) -> Result<String> {
+        self.unlock()?;
+        lwk_common::singlesig_desc(self, Singlesig::Wpkh, DescriptorBlindingKey::Slip77)
+            .map_err(Error::Generic)
+    }
+
     /// Unlock an already initialized Jade.
     ///
     /// The device asks for the pin,
@@ -290,16 +442,20 @@ impl Jade {
 
         ports
             .into_iter()
-            .filter(|e| {
-                if let serialport::SerialPortType::UsbPort(val) = &e.port_type {
-                    crate::JADE_DEVICE_IDS.contains(&(val.vid, val.pid))
-                } else {
-                    false
-                }
-            })
+            .filter(|e| JadeDevice::from_port(e).is_some())
             .collect()
     }
 
+    /// Connect to a device returned by [`crate::devices::list_devices()`]
+    #[cfg(feature = "serial")]
+    pub fn from_device(
+        network: Network,
+        device: &JadeDevice,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<Self> {
+        Self::from_serial(network, &device.port_name, timeout)
+    }
+
     #[cfg(feature = "serial")]
     pub fn from_serial_matching_id(
         network: Network,
@@ -1,14 +1,28 @@
 use elements::pset::PartiallySignedTransaction;
+use lwk_common::SignProgress;
 use std::collections::HashMap;
 
 use crate::{
-    create_jade_sign_req, derivation_path_to_vec, protocol::GetSignatureParams, script_code_wpkh,
-    sign_liquid_tx::TxInputParams, Error, Jade,
+    capabilities::JadeFeature, count_to_sign, create_jade_sign_req, derivation_path_to_vec,
+    protocol::GetSignatureParams, script_code_wpkh, sign_liquid_tx::TxInputParams, Error, Jade,
 };
 
 impl Jade {
     /// Sign a pset from a Jade
     pub fn sign(&self, pset: &mut PartiallySignedTransaction) -> Result<u32, Error> {
+        self.sign_with_progress(pset, |_| ())
+    }
+
+    /// Sign a pset from a Jade, calling `progress` every time a signature is received
+    ///
+    /// Signing a pset with many inputs could take minutes, use this to give feedback to the user.
+    pub fn sign_with_progress(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        mut progress: impl FnMut(SignProgress),
+    ) -> Result<u32, Error> {
+        let capabilities = self.capabilities()?;
+        capabilities.check(JadeFeature::AntiExfil)?;
         let my_fingerprint = self.fingerprint()?;
 
         // Singlesig signing don't need this, however, it is simpler to always ask for it and once cached is a
@@ -16,7 +30,10 @@ impl Jade {
         let multisigs_details = self.get_cached_registered_multisigs()?;
         let network = self.network;
 
-        let params = create_jade_sign_req(pset, my_fingerprint, multisigs_details, network)?;
+        let mut params = create_jade_sign_req(pset, my_fingerprint, multisigs_details, network)?;
+        if !capabilities.supports(JadeFeature::Elip100AssetInfo) {
+            params.asset_info.clear();
+        }
 
         let mut sigs_added_or_overwritten = 0;
         let sign_response = self.sign_liquid_tx(params)?;
@@ -84,7 +101,8 @@ impl Jade {
             }
         }
 
-        for input in pset.inputs_mut().iter_mut() {
+        let total = count_to_sign(pset, &signers_commitment);
+        for (input_index, input) in pset.inputs_mut().iter_mut().enumerate() {
             for (public_key, (_, _)) in input.bip32_derivation.iter() {
                 if let Some(_signer_commitment) = signers_commitment.get(public_key) {
                     let params = GetSignatureParams {
@@ -94,6 +112,11 @@ impl Jade {
 
                     input.partial_sigs.insert(*public_key, sig);
                     sigs_added_or_overwritten += 1;
+                    progress(SignProgress {
+                        input_index,
+                        signed: sigs_added_or_overwritten as usize,
+                        total,
+                    });
                 }
             }
         }
@@ -1,20 +1,26 @@
 use base64::Engine;
 use elements::{
     bitcoin::{self, bip32::Fingerprint, bip32::Xpub, sign_message::signed_msg_hash},
-    hashes::Hash,
+    hashes::{
+        hmac::{Hmac, HmacEngine},
+        sha512, Hash, HashEngine,
+    },
     pset::PartiallySignedTransaction,
-    secp256k1_zkp::{ecdsa::Signature, Message, Secp256k1},
+    secp256k1_zkp::{ecdsa::Signature, Generator, Message, PedersenCommitment, Secp256k1},
     Address, AddressParams,
 };
 use elements_miniscript::{
     confidential::Key, ConfidentialDescriptor, DefiniteDescriptorKey, DescriptorPublicKey,
 };
+use lwk_common::EC;
 use lwk_containers::{
     testcontainers::clients::{self},
     PinServer, PIN_SERVER_PORT,
 };
 use lwk_jade::{
+    blinding::{hash_prevouts, GetCommitmentsParams, GetSharedNonceParams},
     get_receive_address::{GetReceiveAddressParams, SingleOrMulti, Variant},
+    pinserver::CustomPinServer,
     protocol::{
         GetMasterBlindingKeyParams, GetSignatureParams, GetXpubParams, JadeState,
         SignMessageParams, UpdatePinserverParams, VersionInfoResult,
@@ -86,6 +92,7 @@ fn update_pinserver() {
 
     let tempdir = tempfile::tempdir().unwrap();
     let pin_server = PinServer::new(&tempdir).unwrap();
+    let pin_server_pub_key = pin_server.pub_key().inner;
     let pub_key: Vec<u8> = pin_server.pub_key().to_bytes();
     let container = docker.run(pin_server);
     let port = container.get_host_port_ipv4(PIN_SERVER_PORT);
@@ -94,13 +101,20 @@ fn update_pinserver() {
     let params = UpdatePinserverParams {
         reset_details: false,
         reset_certificate: false,
-        url_a,
+        url_a: url_a.clone(),
         url_b: "".to_string(),
         pubkey: pub_key,
         certificate: "".into(),
     };
     let result = jade.jade.update_pinserver(params).unwrap();
     assert!(result);
+
+    let result = jade.jade.reset_pinserver().unwrap();
+    assert!(result);
+
+    let pin_server = CustomPinServer::new(&url_a, pin_server_pub_key).unwrap();
+    let result = jade.jade.set_custom_pinserver(&pin_server).unwrap();
+    assert!(result);
 }
 
 #[test]
@@ -289,6 +303,7 @@ fn jade_register_multisig_check_address() {
     let desc: ConfidentialDescriptor<DescriptorPublicKey> = desc.parse().unwrap();
     let jade_desc: JadeDescriptor = (&desc).try_into().unwrap();
     let network = lwk_common::Network::LocaltestLiquid;
+    assert_eq!(jade.jade.registered_multisig_name(&desc).unwrap(), None);
     jade.jade
         .register_multisig(RegisterMultisigParams {
             network,
@@ -300,6 +315,14 @@ fn jade_register_multisig_check_address() {
     let result = jade.jade.get_registered_multisigs().unwrap();
     assert_eq!(result.len(), 1);
     result.get(&multisig_name).unwrap();
+    assert_eq!(
+        jade.jade.registered_multisig_name(&desc).unwrap(),
+        Some(multisig_name.clone())
+    );
+    let descriptors = jade.jade.registered_multisig_descriptors().unwrap();
+    assert_eq!(descriptors.len(), 1);
+    let registered_desc = &descriptors[&multisig_name];
+    assert_eq!(registered_desc.key, desc.key);
 
     let result = jade
         .jade
@@ -408,6 +431,88 @@ fn jade_get_master_blinding_key() {
     assert_eq!(hex::encode(result), lwk_test_util::TEST_MNEMONIC_SLIP77);
 }
 
+#[test]
+fn jade_wpkh_slip77_descriptor() {
+    let docker = clients::Cli::default();
+    let mut jade = TestJadeEmulator::new(&docker);
+    jade.set_debug_mnemonic(TEST_MNEMONIC);
+
+    let desc = jade.jade.wpkh_slip77_descriptor().unwrap();
+    assert!(desc.contains(lwk_test_util::TEST_MNEMONIC_SLIP77));
+    let expected = lwk_common::singlesig_desc(
+        &jade.jade,
+        lwk_common::Singlesig::Wpkh,
+        lwk_common::DescriptorBlindingKey::Slip77,
+    )
+    .unwrap();
+    let parse = |d: &str| ConfidentialDescriptor::<DescriptorPublicKey>::from_str(d).unwrap();
+    assert_eq!(parse(&desc), parse(&expected));
+}
+
+#[test]
+fn jade_blinding_primitives() {
+    let docker = clients::Cli::default();
+    let mut jade = TestJadeEmulator::new(&docker);
+    jade.set_debug_mnemonic(TEST_MNEMONIC);
+
+    let txid = elements::Txid::from_str(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+    )
+    .unwrap();
+    let hash_prevouts = hash_prevouts(&[elements::OutPoint::new(txid, 0)]);
+    let (abf, vbf) = jade.jade.blinding_factors(hash_prevouts, 0).unwrap();
+
+    let asset = elements::AssetId::LIQUID_BTC;
+    let params = GetCommitmentsParams::new(asset, 1000, hash_prevouts, 0, None);
+    let commitments = jade.jade.get_commitments(params).unwrap();
+    assert_eq!(commitments.asset_blinding_factor().unwrap(), abf);
+    assert_eq!(commitments.value_blinding_factor().unwrap(), vbf);
+    let asset_generator = commitments.asset_generator().unwrap();
+    let expected = Generator::new_blinded(&EC, asset.into_tag(), abf.into_inner());
+    assert_eq!(asset_generator, expected);
+    let expected = PedersenCommitment::new(&EC, 1000, vbf.into_inner(), asset_generator);
+    assert_eq!(commitments.value_commitment().unwrap(), expected);
+
+    let script = lwk_common::burn_script();
+    let their_pubkey = elements::secp256k1_zkp::PublicKey::from_secret_key(
+        &EC,
+        &elements::secp256k1_zkp::SecretKey::from_slice(&[1u8; 32]).unwrap(),
+    );
+    let params = GetSharedNonceParams::new(&script, &their_pubkey);
+    let nonce = jade.jade.get_shared_nonce(params).unwrap();
+    assert_eq!(nonce.len(), 32);
+}
+
+#[test]
+fn jade_bip85_mnemonic() {
+    let docker = clients::Cli::default();
+    let mut jade = TestJadeEmulator::new(&docker);
+    jade.set_debug_mnemonic(TEST_MNEMONIC);
+
+    // BIP85 derivation of a BIP39 mnemonic from the test mnemonic
+    let seed = bip39::Mnemonic::from_str(TEST_MNEMONIC)
+        .unwrap()
+        .to_seed("");
+    let master = bitcoin::bip32::Xpriv::new_master(bitcoin::NetworkKind::Test, &seed).unwrap();
+    let expected = |num_words: u32, index: u32| {
+        let path = format!("m/83696968h/39h/0h/{num_words}h/{index}h");
+        let path = bitcoin::bip32::DerivationPath::from_str(&path).unwrap();
+        let key = master.derive_priv(&Secp256k1::new(), &path).unwrap();
+        let mut engine = HmacEngine::<sha512::Hash>::new(b"bip-entropy-from-k");
+        engine.input(&key.private_key.secret_bytes());
+        let entropy = Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+        let len = if num_words == 12 { 16 } else { 32 };
+        bip39::Mnemonic::from_entropy(&entropy[..len]).unwrap()
+    };
+
+    for (num_words, index) in [(12, 0), (24, 0), (12, 1)] {
+        let mnemonic = jade.jade.bip85_mnemonic(num_words, index).unwrap();
+        assert_eq!(mnemonic.word_count(), num_words as usize);
+        assert_eq!(mnemonic, expected(num_words, index));
+    }
+    assert!(jade.jade.bip85_mnemonic(18, 0).is_err());
+}
+
 #[cfg(feature = "asyncr")]
 #[tokio::test]
 async fn async_ping() {
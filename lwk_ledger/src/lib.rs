@@ -50,7 +50,7 @@ use elements_miniscript::elements::{
     Script,
 };
 
-use lwk_common::Signer;
+use lwk_common::{SignProgress, Signer};
 
 #[derive(Debug)]
 pub struct Ledger<T: Transport> {
@@ -77,10 +77,16 @@ impl Ledger<transport_hid::TransportHID> {
 
 pub type Error = error::LiquidClientError<TransportTcp>;
 
-impl<T: Transport> Signer for &Ledger<T> {
-    type Error = crate::Error;
-
-    fn sign(&self, pset: &mut PartiallySignedTransaction) -> std::result::Result<u32, Self::Error> {
+impl<T: Transport> Ledger<T> {
+    /// Sign the pset, calling `progress` every time a signature is added
+    ///
+    /// The Ledger returns the signatures of all the inputs of a wallet at the end of the
+    /// signing command, thus progress is reported in batches, once per signing wallet.
+    pub fn sign_with_progress(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        mut progress: impl FnMut(SignProgress),
+    ) -> std::result::Result<u32, Error> {
         // Set the default values some fields that Ledger requires
         if pset.global.tx_data.fallback_locktime.is_none() {
             pset.global.tx_data.fallback_locktime =
@@ -95,7 +101,7 @@ impl<T: Transport> Signer for &Ledger<T> {
         // Use a map to avoid inserting a wallet twice
         let mut wallets = std::collections::HashMap::<String, WalletPolicy>::new();
         let mut n_sigs = 0;
-        let master_fp = self.fingerprint()?;
+        let master_fp = self.client.get_master_fingerprint().map_err(to_dbg)?;
 
         // Figure out which wallets are signing
         'outer: for input in pset.inputs() {
@@ -209,6 +215,13 @@ impl<T: Transport> Signer for &Ledger<T> {
             }
         }
 
+        // Each input is signed once, by the wallet owning it
+        let total = pset
+            .inputs()
+            .iter()
+            .filter(|i| i.bip32_derivation.values().any(|(fp, _)| fp == &master_fp))
+            .count();
+
         // For each wallet, sign
         for wallet_policy in wallets.values() {
             let hmac = if wallet_policy.threshold.is_some() {
@@ -222,7 +235,6 @@ impl<T: Transport> Signer for &Ledger<T> {
                 .client
                 .sign_psbt(pset, wallet_policy, hmac.as_ref())
                 .map_err(to_dbg)?;
-            n_sigs += partial_sigs.len();
 
             // Add sigs to pset
             for (input_idx, sig) in partial_sigs {
@@ -239,11 +251,25 @@ impl<T: Transport> Signer for &Ledger<T> {
                         break;
                     }
                 }
+                n_sigs += 1;
+                progress(SignProgress {
+                    input_index: input_idx,
+                    signed: n_sigs,
+                    total,
+                });
             }
         }
 
         Ok(n_sigs as u32)
     }
+}
+
+impl<T: Transport> Signer for &Ledger<T> {
+    type Error = crate::Error;
+
+    fn sign(&self, pset: &mut PartiallySignedTransaction) -> std::result::Result<u32, Self::Error> {
+        self.sign_with_progress(pset, |_| ())
+    }
 
     fn derive_xpub(&self, path: &DerivationPath) -> std::result::Result<Xpub, Self::Error> {
         self.client.get_extended_pubkey(path, false).map_err(to_dbg)
@@ -460,6 +460,61 @@ pub struct Amp2Cosign {
     pub pset: String,
 }
 
+/// A sensitive operation recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// A PSET has been created by a wallet
+    PsetCreated,
+
+    /// A PSET has been signed by a signer
+    PsetSigned,
+
+    /// A transaction has been broadcast, or tested for broadcast with a dry run
+    Broadcast,
+
+    /// A signer has been loaded in the server
+    SignerRegistered,
+
+    /// A multisig wallet has been registered on a signer
+    MultisigRegistered,
+}
+
+/// An entry of the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEntry {
+    /// Position of the entry in the log, starting from 0
+    pub index: u64,
+
+    /// Unix timestamp in seconds of the operation
+    pub timestamp: u64,
+
+    /// The operation performed
+    pub operation: AuditOperation,
+
+    /// Who requested the operation, the IP address of the client
+    pub actor: String,
+
+    /// Details of the operation, such as the wallet or signer involved and the PSET id or txid
+    pub details: String,
+
+    /// The hash of the previous entry, all zeros for the first entry
+    pub prev_hash: String,
+
+    /// The hash of this entry, committing to all the other fields
+    pub hash: String,
+}
+
+/// The audit log of the server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AuditLog {
+    /// The entries of the log, oldest first
+    pub entries: Vec<AuditEntry>,
+
+    /// Whether the hash chain of the entries is intact
+    pub valid: bool,
+}
+
 impl std::fmt::Display for WalletType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
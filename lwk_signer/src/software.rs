@@ -14,15 +14,17 @@ use elements_miniscript::{
         },
         hashes::Hash,
         pset::PartiallySignedTransaction,
-        secp256k1_zkp::{All, Secp256k1},
+        schnorr::{SchnorrSig, TapTweak},
+        secp256k1_zkp::{All, Keypair, Secp256k1, XOnlyPublicKey},
         sighash::SighashCache,
-        EcdsaSighashType,
+        taproot::TapLeafHash,
+        BlockHash, EcdsaSighashType, SchnorrSighashType,
     },
     elementssig_to_rawsig,
     psbt::PsbtExt,
     slip77::MasterBlindingKey,
 };
-use lwk_common::Signer;
+use lwk_common::{Signer, EC};
 
 /// Possible errors when signing with the software signer [`SwSigner`]
 #[derive(thiserror::Error, Debug)]
@@ -44,6 +46,11 @@ pub enum SignError {
 
     #[error("Cannot derive slip77 key (mnemonic/seed not available)")]
     DeterministicSlip77NotAvailable,
+
+    #[error(
+        "Cannot sign taproot inputs without the genesis hash, see `SwSigner::set_genesis_hash`"
+    )]
+    MissingGenesisHash,
 }
 
 /// Possible errors when creating a new software signer [`SwSigner`]
@@ -71,9 +78,9 @@ enum EcdsaSignOpt {
 #[derive(Clone)]
 pub struct SwSigner {
     pub(crate) xprv: Xpriv,
-    pub(crate) secp: Secp256k1<All>, // could be sign only, but it is likely the caller already has the All context.
     pub(crate) mnemonic: Option<Mnemonic>,
     ecdsa_sign_opt: EcdsaSignOpt,
+    genesis_hash: Option<BlockHash>,
 }
 
 impl core::fmt::Debug for SwSigner {
@@ -88,7 +95,6 @@ impl SwSigner {
     /// Takes also a flag if the network is mainnet so that generated extended keys are in the
     /// correct form xpub/tpub (there is no need to discriminate between regtest and testnet)
     pub fn new(mnemonic: &str, is_mainnet: bool) -> Result<Self, NewError> {
-        let secp = Secp256k1::new();
         let mnemonic: Mnemonic = mnemonic.parse()?;
         let seed = mnemonic.to_seed("");
 
@@ -102,9 +108,9 @@ impl SwSigner {
 
         Ok(Self {
             xprv,
-            secp,
             mnemonic: Some(mnemonic),
             ecdsa_sign_opt: EcdsaSignOpt::default(),
+            genesis_hash: None,
         })
     }
 
@@ -120,9 +126,9 @@ impl SwSigner {
     pub fn from_xprv(xprv: Xpriv) -> Self {
         Self {
             xprv,
-            secp: Secp256k1::new(),
             mnemonic: None,
             ecdsa_sign_opt: EcdsaSignOpt::default(),
+            genesis_hash: None,
         }
     }
 
@@ -136,8 +142,13 @@ impl SwSigner {
         self.ecdsa_sign_opt = EcdsaSignOpt::NoGrind;
     }
 
+    /// Set the genesis hash of the network, needed to sign taproot inputs (BIP341 sighash)
+    pub fn set_genesis_hash(&mut self, genesis_hash: BlockHash) {
+        self.genesis_hash = Some(genesis_hash);
+    }
+
     pub fn xpub(&self) -> Xpub {
-        Xpub::from_priv(&self.secp, &self.xprv)
+        Xpub::from_priv(&EC, &self.xprv)
     }
 
     pub fn seed(&self) -> Option<[u8; 64]> {
@@ -149,11 +160,11 @@ impl SwSigner {
     }
 
     pub fn fingerprint(&self) -> Fingerprint {
-        self.xprv.fingerprint(&self.secp)
+        self.xprv.fingerprint(&EC)
     }
 
     pub fn derive_xprv(&self, path: &DerivationPath) -> Result<Xpriv, SignError> {
-        Ok(self.xprv.derive_priv(&self.secp, path)?)
+        Ok(self.xprv.derive_priv(&EC, path)?)
     }
 
     // TODO: move in trait Signer
@@ -164,10 +175,8 @@ impl SwSigner {
     ) -> Result<MessageSignature, SignError> {
         let digest = bitcoin::sign_message::signed_msg_hash(message);
         let message = Message::from_digest_slice(digest.as_ref()).expect("digest is 32");
-        let derived = self.xprv.derive_priv(&self.secp, path)?;
-        let signature = self
-            .secp
-            .sign_ecdsa_recoverable(&message, &derived.private_key);
+        let derived = self.xprv.derive_priv(&EC, path)?;
+        let signature = EC.sign_ecdsa_recoverable(&message, &derived.private_key);
         let signature = MessageSignature {
             signature,
             compressed: true,
@@ -176,6 +185,54 @@ impl SwSigner {
     }
 }
 
+impl SwSigner {
+    /// Compute the Schnorr signatures for the taproot inputs having keys of this signer,
+    /// both for the key path (leaf is `None`) and the script paths
+    #[allow(clippy::type_complexity)]
+    fn taproot_signatures(
+        &self,
+        pset: &PartiallySignedTransaction,
+        sighash_cache: &mut SighashCache<&elements_miniscript::elements::Transaction>,
+        signer_fingerprint: Fingerprint,
+    ) -> Result<Vec<(usize, Option<(XOnlyPublicKey, TapLeafHash)>, SchnorrSig)>, SignError> {
+        let mut result = vec![];
+        for (index, input) in pset.inputs().iter().enumerate() {
+            let hash_ty = input
+                .sighash_type
+                .and_then(|h| h.schnorr_hash_ty())
+                .unwrap_or(SchnorrSighashType::Default);
+            for (xonly, (leaf_hashes, (fingerprint, path))) in input.tap_key_origins.iter() {
+                if &signer_fingerprint != fingerprint {
+                    continue;
+                }
+                let derived = self.xprv.derive_priv(&EC, path)?;
+                let keypair = Keypair::from_secret_key(&EC, &derived.private_key);
+                if &keypair.x_only_public_key().0 != xonly {
+                    continue;
+                }
+                let genesis_hash = self.genesis_hash.ok_or(SignError::MissingGenesisHash)?;
+                if input.tap_internal_key.as_ref() == Some(xonly) {
+                    let msg = pset
+                        .sighash_msg(index, sighash_cache, None, genesis_hash)?
+                        .to_secp_msg();
+                    let tweaked = keypair.tap_tweak(&EC, input.tap_merkle_root);
+                    let sig = EC.sign_schnorr_no_aux_rand(&msg, &tweaked.to_inner());
+                    result.push((index, None, SchnorrSig { sig, hash_ty }));
+                }
+                for leaf_hash in leaf_hashes {
+                    let msg = pset
+                        .sighash_msg(index, sighash_cache, Some(*leaf_hash), genesis_hash)?
+                        .to_secp_msg();
+                    let sig = EC.sign_schnorr_no_aux_rand(&msg, &keypair);
+                    let key_leaf = Some((*xonly, *leaf_hash));
+                    result.push((index, key_leaf, SchnorrSig { sig, hash_ty }));
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
 #[allow(dead_code)]
 fn verify(
     secp: &Secp256k1<All>,
@@ -201,8 +258,8 @@ impl Signer for SwSigner {
         let mut sighash_cache = SighashCache::new(&tx);
         let mut signature_added = 0;
 
-        // genesis hash is not used at all for sighash calculation
-        let genesis_hash = elements_miniscript::elements::BlockHash::all_zeros();
+        // genesis hash is used only for taproot sighash calculation
+        let genesis_hash = self.genesis_hash.unwrap_or_else(BlockHash::all_zeros);
         let mut messages = vec![];
         for i in 0..pset.inputs().len() {
             // computing all the messages to sign, it is not necessary if we are not going to sign
@@ -214,6 +271,9 @@ impl Signer for SwSigner {
         }
 
         let signer_fingerprint = self.fingerprint();
+        let taproot_signatures =
+            self.taproot_signatures(pset, &mut sighash_cache, signer_fingerprint)?;
+
         for (input, msg) in pset.inputs_mut().iter_mut().zip(messages) {
             let hash_ty = input
                 .sighash_type
@@ -221,16 +281,13 @@ impl Signer for SwSigner {
                 .unwrap_or(EcdsaSighashType::All);
             for (want_public_key, (fingerprint, derivation_path)) in input.bip32_derivation.iter() {
                 if &signer_fingerprint == fingerprint {
-                    let ext_derived = self.xprv.derive_priv(&self.secp, derivation_path)?;
+                    let ext_derived = self.xprv.derive_priv(&EC, derivation_path)?;
                     let private_key = PrivateKey::new(ext_derived.private_key, Network::Bitcoin);
-                    let public_key = private_key.public_key(&self.secp);
+                    let public_key = private_key.public_key(&EC);
                     if want_public_key == &public_key {
-                        // fixme: for taproot use schnorr
                         let sig = match self.ecdsa_sign_opt {
-                            EcdsaSignOpt::LowR => {
-                                self.secp.sign_ecdsa_low_r(&msg, &private_key.inner)
-                            }
-                            EcdsaSignOpt::NoGrind => self.secp.sign_ecdsa(&msg, &private_key.inner),
+                            EcdsaSignOpt::LowR => EC.sign_ecdsa_low_r(&msg, &private_key.inner),
+                            EcdsaSignOpt::NoGrind => EC.sign_ecdsa(&msg, &private_key.inner),
                         };
                         let sig = elementssig_to_rawsig(&(sig, hash_ty));
 
@@ -243,12 +300,23 @@ impl Signer for SwSigner {
             }
         }
 
+        for (index, leaf, sig) in taproot_signatures {
+            let input = &mut pset.inputs_mut()[index];
+            let previous = match leaf {
+                None => input.tap_key_sig.replace(sig),
+                Some(key_leaf) => input.tap_script_sigs.insert(key_leaf, sig),
+            };
+            if previous.is_none() {
+                signature_added += 1;
+            }
+        }
+
         Ok(signature_added)
     }
 
     fn derive_xpub(&self, path: &DerivationPath) -> Result<Xpub, Self::Error> {
-        let derived = self.xprv.derive_priv(&self.secp, path)?;
-        Ok(Xpub::from_priv(&self.secp, &derived))
+        let derived = self.xprv.derive_priv(&EC, path)?;
+        Ok(Xpub::from_priv(&EC, &derived))
     }
 
     fn slip77_master_blinding_key(&self) -> Result<MasterBlindingKey, Self::Error> {
@@ -288,8 +356,7 @@ mod tests {
         let path: DerivationPath = "m/0'".parse().unwrap();
         let xprv = signer.derive_xprv(&path).unwrap();
         let xpub = signer.derive_xpub(&path).unwrap();
-        let secp = Secp256k1::new();
-        assert_eq!(xpub, Xpub::from_priv(&secp, &xprv));
+        assert_eq!(xpub, Xpub::from_priv(&EC, &xprv));
     }
 
     #[test]
@@ -330,6 +397,27 @@ mod tests {
         assert!(sig_low_r.len() < sig_no_grind.len());
     }
 
+    #[test]
+    fn signer_sign_request() {
+        use lwk_common::SignRequest;
+
+        let signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        let b64 = include_str!("../../lwk_jade/test_data/pset_to_be_signed.base64");
+        let pset: PartiallySignedTransaction = b64.parse().unwrap();
+
+        let request = SignRequest::new("id", pset.clone());
+        let response = signer.sign_request(&request).unwrap();
+        assert_eq!(response.id, "id");
+        assert_eq!(response.signatures_added, 1);
+        assert_eq!(request.pset, pset);
+
+        let mut local = pset.clone();
+        response.apply(&mut local).unwrap();
+        let mut signed = pset;
+        signer.sign(&mut signed).unwrap();
+        assert_eq!(local.inputs(), signed.inputs());
+    }
+
     #[test]
     fn test_sign_verify() {
         let signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, true).unwrap();
@@ -340,10 +428,72 @@ mod tests {
         let address = p2pkh(&xpub);
         assert_eq!(address.to_string(), "1BZ9j3F7m4H1RPyeDp5iFwpR31SB6zrs19");
         assert_eq!(signature.to_string(), "Hwlg40qLYZXEj9AoA3oZpfJMJPxaXzBL0+siHAJRhTIvSFiwSdtCsqxqB7TxgWfhqIr/YnGE4nagWzPchFJElTo=");
-        let verified = verify(&signer.secp, &address, message, &signature).unwrap();
+        let verified = verify(&EC, &address, message, &signature).unwrap();
         assert!(verified);
 
         // result checked also with bitcoin-cli
         // bitcoin-cli verifymessage "1BZ9j3F7m4H1RPyeDp5iFwpR31SB6zrs19" "Hwlg40qLYZXEj9AoA3oZpfJMJPxaXzBL0+siHAJRhTIvSFiwSdtCsqxqB7TxgWfhqIr/YnGE4nagWzPchFJElTo=" 'Hello, world!'
     }
+
+    #[test]
+    fn signer_taproot() {
+        use elements_miniscript::elements::{
+            confidential, AssetId, OutPoint, Transaction, TxIn, TxOut,
+        };
+        use elements_miniscript::psbt::PsbtExt;
+        use elements_miniscript::{DefiniteDescriptorKey, Descriptor, DescriptorPublicKey};
+        use std::str::FromStr;
+
+        let mut signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        let other = SwSigner::from_xprv(signer.derive_xprv(&"m/1'".parse().unwrap()).unwrap());
+        let fingerprint = signer.fingerprint();
+        let xpub = signer.xpub();
+        let genesis_hash = BlockHash::from_byte_array([1; 32]);
+
+        for desc in [
+            format!("eltr([{fingerprint}]{xpub}/0/*)"),
+            format!("eltr({}/0/*,pk([{fingerprint}]{xpub}/0/*))", other.xpub()),
+        ] {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(&desc).unwrap();
+            let desc: Descriptor<DefiniteDescriptorKey> = desc.at_derivation_index(0).unwrap();
+            let asset = AssetId::from_slice(&[2; 32]).unwrap();
+            let tx = Transaction {
+                version: 2,
+                lock_time: elements_miniscript::elements::LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::default(),
+                    ..Default::default()
+                }],
+                output: vec![TxOut::new_fee(1_000, asset)],
+            };
+            let mut pset = PartiallySignedTransaction::from_tx(tx);
+            pset.inputs_mut()[0].witness_utxo = Some(TxOut {
+                asset: confidential::Asset::Explicit(asset),
+                value: confidential::Value::Explicit(1_000),
+                nonce: confidential::Nonce::Null,
+                script_pubkey: desc.script_pubkey(),
+                witness: Default::default(),
+            });
+            pset.update_input_with_descriptor(0, &desc).unwrap();
+
+            let err = signer.sign(&mut pset).unwrap_err();
+            assert!(matches!(err, SignError::MissingGenesisHash));
+
+            signer.set_genesis_hash(genesis_hash);
+            assert_eq!(signer.sign(&mut pset).unwrap(), 1);
+            let input = &pset.inputs()[0];
+            assert_eq!(
+                input.tap_key_sig.is_some(),
+                input.tap_script_sigs.is_empty()
+            );
+
+            // Finalization checks the signatures, thus it fails with a different genesis hash
+            let mut wrong_genesis = pset.clone();
+            let wrong = BlockHash::all_zeros();
+            assert!(wrong_genesis.finalize_mut(&EC, wrong).is_err());
+            pset.finalize_mut(&EC, genesis_hash).unwrap();
+
+            signer.genesis_hash = None;
+        }
+    }
 }
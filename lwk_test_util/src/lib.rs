@@ -10,7 +10,7 @@ use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
 use elements::encode::Decodable;
 use elements::hex::{FromHex, ToHex};
 use elements::pset::PartiallySignedTransaction;
-use elements::{Address, AssetId, TxOutWitness, Txid};
+use elements::{Address, AssetId, BlockHash, TxOutWitness, Txid};
 use elements::{Block, TxOutSecrets};
 use elements_miniscript::descriptor::checksum::desc_checksum;
 use pulldown_cmark::{CodeBlockKind, Event, Tag};
@@ -261,6 +261,69 @@ impl TestElectrumServer {
         elementsd_generate(&self.elementsd.client, blocks);
     }
 
+    /// Generate blocks until the chain reaches the given `height`
+    ///
+    /// Does nothing if the chain is already at `height` or higher.
+    pub fn elementsd_generate_to_height(&self, height: u64) {
+        let current = self.elementsd_height();
+        if height > current {
+            self.elementsd_generate((height - current) as u32);
+        }
+    }
+
+    /// Set the node time to `timestamp` (seconds since the epoch), 0 restores the system time
+    ///
+    /// Blocks generated afterwards have this time, allowing to test time based timelocks.
+    pub fn elementsd_setmocktime(&self, timestamp: u64) {
+        self.elementsd
+            .client
+            .call::<Value>("setmocktime", &[timestamp.into()])
+            .unwrap();
+    }
+
+    /// Time of the tip block
+    pub fn elementsd_block_time(&self) -> u64 {
+        self.elementsd_header_field("time")
+    }
+
+    /// Median time past of the tip block, the time used to evaluate time based timelocks
+    pub fn elementsd_median_time(&self) -> u64 {
+        self.elementsd_header_field("mediantime")
+    }
+
+    fn elementsd_header_field(&self, field: &str) -> u64 {
+        let hash: Value = self.elementsd.client.call("getbestblockhash", &[]).unwrap();
+        let header: Value = self
+            .elementsd
+            .client
+            .call("getblockheader", &[hash])
+            .unwrap();
+        header.get(field).unwrap().as_u64().unwrap()
+    }
+
+    /// Generate `blocks` blocks, each one `seconds` after the previous one
+    ///
+    /// The node time is mocked and it's left at the time of the last generated block,
+    /// call [`TestElectrumServer::elementsd_setmocktime()`] with 0 to restore it.
+    pub fn elementsd_generate_with_interval(&self, blocks: u32, seconds: u64) {
+        let mut time = self.elementsd_block_time();
+        for _ in 0..blocks {
+            time += seconds;
+            self.elementsd_setmocktime(time);
+            self.elementsd_generate(1);
+        }
+    }
+
+    /// Advance the median time past of at least `seconds`, generating blocks 10 minutes apart
+    ///
+    /// Useful to make relative or absolute time based timelocks mature.
+    pub fn elementsd_advance_median_time(&self, seconds: u64) {
+        let target = self.elementsd_median_time() + seconds;
+        while self.elementsd_median_time() < target {
+            self.elementsd_generate_with_interval(1, 600);
+        }
+    }
+
     pub fn elementsd_sendtoaddress(
         &self,
         address: &Address,
@@ -298,6 +361,29 @@ impl TestElectrumServer {
         Txid::from_str(r.as_str().unwrap()).unwrap()
     }
 
+    /// Send L-BTC to `address` in a transaction with also an `OP_RETURN` output pushing `data`
+    pub fn elementsd_sendtoaddress_with_data(
+        &self,
+        address: &Address,
+        satoshi: u64,
+        data: &[u8],
+    ) -> Txid {
+        let btc = Amount::from_sat(satoshi).to_string_in(Denomination::Bitcoin);
+        let outputs = serde_json::json!([
+            { address.to_string(): btc },
+            { "data": data.to_hex() },
+        ]);
+        let r: Value = self
+            .elementsd
+            .client
+            .call("walletcreatefundedpsbt", &[serde_json::json!([]), outputs])
+            .unwrap();
+        let psbt = r.get("psbt").unwrap().as_str().unwrap();
+        let psbt = self.elementsd_walletprocesspsbt(psbt);
+        let tx = self.elementsd_finalizepsbt(&psbt);
+        Txid::from_str(&self.elementsd_sendrawtransaction(&tx)).unwrap()
+    }
+
     pub fn elementsd_issueasset(&self, satoshi: u64) -> AssetId {
         let amount = Amount::from_sat(satoshi);
         let btc = amount.to_string_in(Denomination::Bitcoin);
@@ -323,6 +409,15 @@ impl TestElectrumServer {
         raw.get("blocks").unwrap().as_u64().unwrap()
     }
 
+    pub fn elementsd_genesis_hash(&self) -> BlockHash {
+        let hash: String = self
+            .elementsd
+            .client
+            .call("getblockhash", &[0.into()])
+            .unwrap();
+        BlockHash::from_str(&hash).unwrap()
+    }
+
     pub fn elementsd_getpeginaddress(&self) -> (bitcoin::Address, String) {
         let value: serde_json::Value = self.elementsd.client.call("getpeginaddress", &[]).unwrap();
 
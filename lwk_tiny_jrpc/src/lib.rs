@@ -5,6 +5,7 @@ use std::{
     fmt::Display,
     fs::File,
     io::{ErrorKind, Read},
+    net::SocketAddr,
     str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -286,7 +287,8 @@ fn validate_jsonrpc_request(http_request: &mut tiny_http::Request) -> Result<Req
     let mut s = String::new(); // todo: performance
     http_request.as_reader().read_to_string(&mut s)?;
 
-    let request: Request = serde_json::from_str(&s)?;
+    let mut request: Request = serde_json::from_str(&s)?;
+    request.remote_addr = http_request.remote_addr().copied();
 
     Ok(request)
 }
@@ -344,6 +346,10 @@ pub struct Request {
     pub id: Option<Id>,
     pub method: String,
     pub params: Option<Value>,
+
+    /// The address of the client which sent the request, `None` if not received by the server
+    #[serde(skip)]
+    pub remote_addr: Option<SocketAddr>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
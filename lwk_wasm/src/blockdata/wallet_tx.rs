@@ -66,6 +66,10 @@ impl WalletTx {
             .collect()
     }
 
+    pub fn verified(&self) -> Option<bool> {
+        self.inner.verified
+    }
+
     #[wasm_bindgen(js_name = unblindedUrl)]
     pub fn unblinded_url(&self, explorer_url: &str) -> String {
         self.inner.unblinded_url(explorer_url)
@@ -118,6 +122,8 @@ mod tests {
             timestamp: Some(124),
             inputs: vec![Some(tx_out.clone())],
             outputs: vec![None, Some(tx_out.clone())],
+            verified: None,
+            zero_conf_risk: None,
         };
 
         let wallet_tx: WalletTx = el.clone().into();
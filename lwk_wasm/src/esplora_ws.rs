@@ -0,0 +1,83 @@
+use crate::{Error, Txid, Wollet};
+use lwk_wollet::clients::asyncr;
+use wasm_bindgen::prelude::*;
+
+/// Wrapper of [`asyncr::EsploraWsClient`]
+#[wasm_bindgen]
+pub struct EsploraWsClient {
+    inner: asyncr::EsploraWsClient,
+}
+
+#[wasm_bindgen]
+impl EsploraWsClient {
+    /// Connect to the WebSocket of an esplora server, for instance `wss://liquid.network/api/v1/ws`
+    #[wasm_bindgen(constructor)]
+    pub async fn connect(url: &str) -> Result<EsploraWsClient, Error> {
+        let inner = asyncr::EsploraWsClient::connect(url).await?;
+        Ok(Self { inner })
+    }
+
+    /// Ask to be notified of the new blocks
+    #[wasm_bindgen(js_name = subscribeBlocks)]
+    pub fn subscribe_blocks(&self) -> Result<(), Error> {
+        Ok(self.inner.subscribe_blocks()?)
+    }
+
+    /// Ask to be notified of the transactions of the wallet, call it again after a scan finding
+    /// new transactions
+    #[wasm_bindgen(js_name = trackWollet)]
+    pub fn track_wollet(&self, wollet: &Wollet) -> Result<(), Error> {
+        Ok(self.inner.track_wollet(wollet.as_ref())?)
+    }
+
+    /// Wait for the next notification, fails if the connection was closed
+    #[wasm_bindgen(js_name = nextNotification)]
+    pub async fn next_notification(&mut self) -> Result<EsploraWsNotification, Error> {
+        let inner = self.inner.next_notification().await?;
+        Ok(EsploraWsNotification { inner })
+    }
+}
+
+/// Wrapper of [`asyncr::EsploraWsNotification`], a new block or the activity of a tracked address
+#[wasm_bindgen]
+pub struct EsploraWsNotification {
+    inner: asyncr::EsploraWsNotification,
+}
+
+#[wasm_bindgen]
+impl EsploraWsNotification {
+    /// The height of the new block, undefined for address activity
+    pub fn height(&self) -> Option<u32> {
+        match &self.inner {
+            asyncr::EsploraWsNotification::Block { height, .. } => Some(*height),
+            asyncr::EsploraWsNotification::AddressActivity { .. } => None,
+        }
+    }
+
+    /// The hash of the new block, undefined for address activity
+    #[wasm_bindgen(js_name = blockHash)]
+    pub fn block_hash(&self) -> Option<String> {
+        match &self.inner {
+            asyncr::EsploraWsNotification::Block { block_hash, .. } => Some(block_hash.to_string()),
+            asyncr::EsploraWsNotification::AddressActivity { .. } => None,
+        }
+    }
+
+    /// The tracked address with new transactions, undefined for new blocks
+    pub fn address(&self) -> Option<String> {
+        match &self.inner {
+            asyncr::EsploraWsNotification::Block { .. } => None,
+            asyncr::EsploraWsNotification::AddressActivity { address, .. } => Some(address.clone()),
+        }
+    }
+
+    /// The transactions of the tracked address, empty for new blocks
+    pub fn txids(&self) -> Vec<Txid> {
+        match &self.inner {
+            asyncr::EsploraWsNotification::Block { .. } => vec![],
+            asyncr::EsploraWsNotification::AddressActivity { txids, .. } => {
+                txids.iter().map(|txid| (*txid).into()).collect()
+            }
+        }
+    }
+}
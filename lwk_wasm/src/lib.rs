@@ -8,12 +8,16 @@ mod contract;
 mod descriptor;
 mod error;
 mod esplora;
+#[cfg(target_arch = "wasm32")]
+mod esplora_ws;
 #[cfg(all(feature = "serial", target_arch = "wasm32"))]
 mod jade;
 #[cfg(all(feature = "serial", target_arch = "wasm32"))]
 mod ledger;
 mod mnemonic;
 mod network;
+#[cfg(all(feature = "persist", target_arch = "wasm32"))]
+mod persister;
 mod precision;
 mod pset;
 mod pset_details;
@@ -40,6 +44,8 @@ pub use contract::Contract;
 pub use descriptor::WolletDescriptor;
 pub(crate) use error::Error;
 pub use esplora::EsploraClient;
+#[cfg(target_arch = "wasm32")]
+pub use esplora_ws::{EsploraWsClient, EsploraWsNotification};
 #[cfg(all(feature = "serial", target_arch = "wasm32"))]
 pub use jade::{Jade, Singlesig};
 pub use mnemonic::Mnemonic;
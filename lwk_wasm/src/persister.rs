@@ -0,0 +1,145 @@
+//! A [`Persister`] storing the wallet state in the browser `localStorage`
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use lwk_wollet::elements::hashes::{sha256, Hash};
+use lwk_wollet::{ElementsNetwork, PersistError, Persister, Update};
+
+/// Store the wallet updates and values in the `localStorage` of the browser, so that the wallet
+/// survives page reloads
+///
+/// Entries are encrypted with the descriptor and namespaced by network and by a hash of the
+/// descriptor, so more wallets can share the same origin. Updates are stored under
+/// `{prefix}/update/{index}`, their number under `{prefix}/len` and values under
+/// `{prefix}/value/{key}`.
+///
+/// IndexedDB would allow storing more data, but its API is asynchronous while [`Persister`] is
+/// not.
+pub(crate) struct LocalStoragePersister {
+    prefix: String,
+    desc: lwk_wollet::WolletDescriptor,
+}
+
+impl LocalStoragePersister {
+    pub(crate) fn new(network: ElementsNetwork, desc: &lwk_wollet::WolletDescriptor) -> Self {
+        let id = sha256::Hash::hash(desc.to_string().as_bytes());
+        Self {
+            prefix: format!("lwk/{}/{id}", network.namespace()),
+            desc: desc.clone(),
+        }
+    }
+
+    fn update_key(&self, index: usize) -> String {
+        format!("{}/update/{index}", self.prefix)
+    }
+
+    fn value_key(&self, key: &str) -> String {
+        format!("{}/value/{key}", self.prefix)
+    }
+
+    fn len_key(&self) -> String {
+        format!("{}/len", self.prefix)
+    }
+
+    /// The number of stored updates
+    fn len(&self, storage: &web_sys::Storage) -> Result<usize, PersistError> {
+        if let Some(len) = storage.get_item(&self.len_key()).map_err(to_other)? {
+            return len
+                .parse()
+                .map_err(|_| PersistError::Corrupted(format!("invalid updates count {len}")));
+        }
+        // stores written before the count was kept, probe them once
+        let mut len = 0;
+        while storage
+            .get_item(&self.update_key(len))
+            .map_err(to_other)?
+            .is_some()
+        {
+            len += 1;
+        }
+        self.set_len(storage, len)?;
+        Ok(len)
+    }
+
+    fn set_len(&self, storage: &web_sys::Storage, len: usize) -> Result<(), PersistError> {
+        storage
+            .set_item(&self.len_key(), &len.to_string())
+            .map_err(to_other)
+    }
+}
+
+fn storage() -> Result<web_sys::Storage, PersistError> {
+    web_sys::window()
+        .ok_or_else(|| PersistError::Other("window not available".to_string()))?
+        .local_storage()
+        .map_err(to_other)?
+        .ok_or_else(|| PersistError::Other("localStorage not available".to_string()))
+}
+
+fn to_other<D: std::fmt::Debug>(d: D) -> PersistError {
+    PersistError::Other(format!("{d:?}"))
+}
+
+impl Persister for LocalStoragePersister {
+    fn get(&self, index: usize) -> Result<Option<Update>, PersistError> {
+        let storage = storage()?;
+        match storage
+            .get_item(&self.update_key(index))
+            .map_err(to_other)?
+        {
+            Some(base64) => Ok(Some(
+                Update::deserialize_decrypted_base64(&base64, &self.desc)
+                    .map_err(|e| PersistError::Corrupted(e.to_string()))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn push(&self, mut update: Update) -> Result<(), PersistError> {
+        let storage = storage()?;
+        let mut next = self.len(&storage)?;
+        if update.only_tip() && next > 0 {
+            if let Ok(Some(prev_update)) = self.get(next - 1) {
+                if prev_update.only_tip() {
+                    // overwrite the last update as FsPersister does, keeping its wallet status
+                    update.wollet_status = prev_update.wollet_status;
+                    next -= 1;
+                }
+            }
+        }
+        let base64 = update
+            .serialize_encrypted_base64(&self.desc)
+            .map_err(to_other)?;
+        storage
+            .set_item(&self.update_key(next), &base64)
+            .map_err(to_other)?;
+        self.set_len(&storage, next + 1)
+    }
+
+    fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>, PersistError> {
+        let storage = storage()?;
+        match storage.get_item(&self.value_key(key)).map_err(to_other)? {
+            Some(base64) => {
+                let bytes = BASE64_STANDARD.decode(base64).map_err(to_other)?;
+                Ok(Some(self.desc.decrypt(&bytes).map_err(to_other)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn put_value(&self, key: &str, value: &[u8]) -> Result<(), PersistError> {
+        let ciphertext = self.desc.encrypt(value).map_err(to_other)?;
+        storage()?
+            .set_item(&self.value_key(key), &BASE64_STANDARD.encode(ciphertext))
+            .map_err(to_other)
+    }
+
+    fn clear(&self) -> Result<(), PersistError> {
+        let storage = storage()?;
+        for index in 0..self.len(&storage)? {
+            storage
+                .remove_item(&self.update_key(index))
+                .map_err(to_other)?;
+        }
+        self.set_len(&storage, 0)
+    }
+}
@@ -95,6 +95,13 @@ impl Registry {
         })
     }
 
+    /// Fetch the icon of the asset, returning the bytes of the PNG image
+    #[wasm_bindgen(js_name = fetchIcon)]
+    pub async fn fetch_icon(&self, asset_id: &AssetId) -> Result<Vec<u8>, Error> {
+        let icon = self.inner.fetch_icon((*asset_id).into()).await?;
+        Ok(icon.bytes().to_vec())
+    }
+
     pub async fn post(&self, data: &RegistryPost) -> Result<(), Error> {
         let data: lwk_wollet::registry::RegistryPost = data.clone().into();
         Ok(self.inner.post(&data).await?)
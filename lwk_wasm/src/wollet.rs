@@ -13,6 +13,18 @@ use wasm_bindgen::prelude::*;
 #[wasm_bindgen]
 pub struct Wollet {
     inner: lwk_wollet::Wollet,
+    events: std::sync::Arc<lwk_wollet::EventQueue>,
+}
+
+/// The maximum number of events kept until [`Wollet::drain_events()`] is called
+const MAX_QUEUED_EVENTS: usize = 1000;
+
+impl From<lwk_wollet::Wollet> for Wollet {
+    fn from(mut inner: lwk_wollet::Wollet) -> Self {
+        let events = std::sync::Arc::new(lwk_wollet::EventQueue::new(MAX_QUEUED_EVENTS));
+        inner.add_listener(events.clone());
+        Self { inner, events }
+    }
 }
 
 impl AsRef<lwk_wollet::Wollet> for Wollet {
@@ -33,7 +45,24 @@ impl Wollet {
     #[wasm_bindgen(constructor)]
     pub fn new(network: &Network, descriptor: &WolletDescriptor) -> Result<Wollet, Error> {
         let inner = lwk_wollet::Wollet::without_persist(network.into(), descriptor.into())?;
-        Ok(Self { inner })
+        Ok(inner.into())
+    }
+
+    /// Create a `Wollet` persisting its state in the browser `localStorage`
+    ///
+    /// The wallet state is restored from previous sessions on the same origin, so that only the
+    /// changes since the last page load must be downloaded.
+    #[cfg(all(feature = "persist", target_arch = "wasm32"))]
+    #[wasm_bindgen(js_name = withLocalStorage)]
+    pub fn with_local_storage(
+        network: &Network,
+        descriptor: &WolletDescriptor,
+    ) -> Result<Wollet, Error> {
+        let network = network.into();
+        let descriptor: lwk_wollet::WolletDescriptor = descriptor.into();
+        let persister = crate::persister::LocalStoragePersister::new(network, &descriptor);
+        let inner = lwk_wollet::Wollet::new(network, std::sync::Arc::new(persister), descriptor)?;
+        Ok(inner.into())
     }
 
     /// Get a wallet address with the correspondong derivation index
@@ -70,6 +99,16 @@ impl Wollet {
         Ok(self.inner.apply_update(update.into())?)
     }
 
+    /// Remove and return the events caused by the updates applied since the last call, in the
+    /// order they happened
+    ///
+    /// Every event is an object with a `type` field, see [`lwk_wollet::WolletEvent`].
+    #[wasm_bindgen(js_name = drainEvents)]
+    pub fn drain_events(&self) -> Result<JsValue, Error> {
+        let events = self.events.drain_events();
+        Ok(serde_wasm_bindgen::to_value(&events)?)
+    }
+
     pub fn balance(&self) -> Result<JsValue, Error> {
         let balance = self.inner.balance()?;
         let serializer = Serializer::new().serialize_large_number_types_as_bigints(true);
@@ -85,6 +124,21 @@ impl Wollet {
             .collect())
     }
 
+    /// Get a page of `limit` wallet transactions starting at `offset`, in the same order of
+    /// `transactions()`
+    #[wasm_bindgen(js_name = transactionsPaginated)]
+    pub fn transactions_paginated(&self, offset: u32, limit: u32) -> Result<Vec<WalletTx>, Error> {
+        let filter = lwk_wollet::TxFilter::default()
+            .offset(offset as usize)
+            .limit(limit as usize);
+        Ok(self
+            .inner
+            .transactions_with(&filter)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
     /// Get the unspent transaction outputs of the wallet
     pub fn utxos(&self) -> Result<Vec<WalletTxOut>, Error> {
         Ok(self.inner.utxos()?.into_iter().map(Into::into).collect())
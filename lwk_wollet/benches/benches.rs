@@ -85,6 +85,18 @@ pub fn address(c: &mut Criterion) {
                 );
                 black_box(address);
             });
+        })
+        .bench_function("derive blinding key", |b: &mut criterion::Bencher<'_>| {
+            let desc_str = include_str!("../../lwk_common/test_data/pset_details/descriptor");
+            let d: WolletDescriptor = desc_str.parse().unwrap();
+            let script_pubkey = d
+                .address(0, ElementsNetwork::LiquidTestnet.address_params())
+                .unwrap()
+                .script_pubkey();
+            b.iter(|| {
+                let key = lwk_common::derive_blinding_key(d.as_ref(), &script_pubkey);
+                black_box(key);
+            });
         });
 }
 
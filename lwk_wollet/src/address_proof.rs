@@ -0,0 +1,236 @@
+//! Prove that an address belongs to a wallet, without revealing the wallet descriptor
+//!
+//! An [`AddressProof`] contains the descriptor with the keys replaced by placeholders, and for
+//! every key the public key of the descriptor xpub followed by the tweaks of the BIP32 derivation
+//! up to the address index. A third party knowing the xpubs public keys, or their fingerprints,
+//! can check that the address derives from them, but it cannot derive the other addresses since
+//! the chain codes are not revealed.
+//!
+//! The blinding public key of the address is not proven, funds are received on the script
+//! regardless of the blinding key used.
+
+use std::str::FromStr;
+
+use elements::bitcoin::bip32::{ChildNumber, Fingerprint};
+use elements::bitcoin::hashes::{hash160, Hash};
+use elements::bitcoin::secp256k1::{PublicKey, Scalar, SecretKey};
+use elements::{Address, AddressParams};
+use elements_miniscript::descriptor::{DescriptorPublicKey, SinglePubKey, Wildcard};
+use elements_miniscript::{Descriptor, ForEachKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{Chain, Error, WolletDescriptor, EC};
+
+/// A non-hardened BIP32 derivation step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationStep {
+    /// The child number
+    pub child: ChildNumber,
+
+    /// The tweak added to the parent public key to obtain the child public key
+    pub tweak: SecretKey,
+}
+
+/// The derivation of a key of the descriptor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyDerivation {
+    /// The public key of the xpub in the descriptor, or the key itself if it's not an xpub
+    pub base: PublicKey,
+
+    /// The derivation from `base` to the key in the address script
+    pub steps: Vec<DerivationStep>,
+}
+
+impl KeyDerivation {
+    /// The fingerprint of the xpub in the descriptor
+    pub fn fingerprint(&self) -> Fingerprint {
+        let hash = hash160::Hash::hash(&self.base.serialize());
+        let bytes: [u8; 4] = hash[0..4].try_into().expect("4 bytes");
+        Fingerprint::from(bytes)
+    }
+
+    /// The key in the address script
+    pub fn derived(&self) -> Result<PublicKey, Error> {
+        self.steps.iter().try_fold(self.base, |key, step| {
+            key.add_exp_tweak(&EC, &Scalar::from(step.tweak))
+                .map_err(|e| Error::InvalidAddressProof(e.to_string()))
+        })
+    }
+}
+
+/// A proof that an address derives from a wallet descriptor, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressProof {
+    /// The descriptor without the blinding key, with `@i` in place of the i-th key
+    pub template: String,
+
+    /// The chain of the address
+    pub chain: Chain,
+
+    /// The derivation index of the address
+    pub index: u32,
+
+    /// The derivation of the keys of the template
+    pub keys: Vec<KeyDerivation>,
+
+    /// The proven address
+    pub address: Address,
+}
+
+impl AddressProof {
+    pub(crate) fn new(
+        descriptor: &WolletDescriptor,
+        chain: Chain,
+        index: u32,
+        params: &'static AddressParams,
+    ) -> Result<Self, Error> {
+        let desc = descriptor.descriptor();
+        let mut pks: Vec<DescriptorPublicKey> = vec![];
+        desc.for_each_key(|pk| {
+            if !pks.contains(pk) {
+                pks.push(pk.clone());
+            }
+            true
+        });
+
+        let mut template = desc.to_string();
+        if let Some((without_checksum, _)) = template.split_once('#') {
+            template = without_checksum.to_string();
+        }
+        let mut keys = vec![];
+        for (i, pk) in pks.iter().enumerate() {
+            template = template.replace(&pk.to_string(), &format!("@{i}"));
+            keys.push(key_derivation(pk, chain, index)?);
+        }
+
+        let address = match chain {
+            Chain::External => descriptor.address(index, params)?,
+            Chain::Internal => descriptor.change(index, params)?,
+        };
+        Ok(Self {
+            template,
+            chain,
+            index,
+            keys,
+            address,
+        })
+    }
+
+    /// Check that the address script is the template with the derived keys
+    ///
+    /// The verifier must also check that the [`KeyDerivation::base`] keys are the ones of the
+    /// wallet, for instance comparing their [`KeyDerivation::fingerprint()`].
+    pub fn verify(&self) -> Result<(), Error> {
+        let mut desc = self.template.clone();
+        // replace from the last key, so that "@1" doesn't match "@10"
+        for (i, key) in self.keys.iter().enumerate().rev() {
+            desc = desc.replace(&format!("@{i}"), &key.derived()?.to_string());
+        }
+        if desc.contains('@') {
+            return Err(Error::InvalidAddressProof("unknown key in template".into()));
+        }
+        let desc = Descriptor::<elements::bitcoin::PublicKey>::from_str(&desc)
+            .map_err(|e| Error::InvalidAddressProof(e.to_string()))?;
+        if desc.script_pubkey() != self.address.script_pubkey() {
+            return Err(Error::InvalidAddressProof(
+                "address script doesn't match".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn key_derivation(
+    pk: &DescriptorPublicKey,
+    chain: Chain,
+    index: u32,
+) -> Result<KeyDerivation, Error> {
+    let (xpub, path, wildcard) = match pk {
+        DescriptorPublicKey::Single(single) => {
+            let base = match single.key {
+                SinglePubKey::FullKey(key) => key.inner,
+                SinglePubKey::XOnly(_) => {
+                    return Err(Error::InvalidAddressProof("x-only keys".into()))
+                }
+            };
+            return Ok(KeyDerivation {
+                base,
+                steps: vec![],
+            });
+        }
+        DescriptorPublicKey::XPub(xkey) => (xkey.xkey, xkey.derivation_path.clone(), xkey.wildcard),
+        DescriptorPublicKey::MultiXPub(xkey) => {
+            let paths = xkey.derivation_paths.paths();
+            let path = match chain {
+                Chain::External => paths.first(),
+                Chain::Internal => paths.last(),
+            };
+            let path = path.expect("never empty").clone();
+            (xkey.xkey, path, xkey.wildcard)
+        }
+    };
+    let mut children: Vec<ChildNumber> = path.into_iter().cloned().collect();
+    match wildcard {
+        Wildcard::None => {}
+        Wildcard::Unhardened => children.push(ChildNumber::from_normal_idx(index)?),
+        Wildcard::Hardened => return Err(Error::InvalidAddressProof("hardened derivation".into())),
+    }
+
+    let base = xpub.public_key;
+    let mut steps = vec![];
+    let mut current = xpub;
+    for child in children {
+        let (tweak, _) = current.ckd_pub_tweak(child)?;
+        current = current.ckd_pub(&EC, child)?;
+        steps.push(DerivationStep { child, tweak });
+    }
+    Ok(KeyDerivation { base, steps })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::bitcoin::bip32::Xpub;
+    use elements::AddressParams;
+
+    use crate::{Chain, Error, WolletDescriptor};
+
+    const VIEW_KEY: &str = "a45210d9afc904e522bd17a433518d75c6a00cc09ced714b7ec211abdebcb783";
+    const A: &str = "[e6b7814d/87'/1'/0']tpubDDmvBugC5YMK3UDKjcym7ED8Vfv8aLiX83Tcbecc783VFPEDqBigmzF52uFMyh89bXaf7jAporM1LcoaMcLdKeV4m7ixNAchpMQCL569Ldv/<0;1>/*";
+    const B: &str = "[a5a0841e/87'/1'/0']tpubDDZCCwQJyHksYEfUHb59Mr4ZCo1ndMt4Ys8rXF7RLhmfttU9AYybscFyCmWRVQUxffjGYQe8dtmGchA91PhLUCkH3H7D7Nx1CJLrv5W9tTs/<0;1>/*";
+
+    #[test]
+    fn test_address_proof() {
+        let params = &AddressParams::LIQUID_TESTNET;
+        let xpub_a = Xpub::from_str(&A[20..A.len() - 8]).unwrap();
+        for desc in [
+            format!("ct({VIEW_KEY},elwpkh({A}))"),
+            format!("ct(slip77({VIEW_KEY}),elsh(wpkh({A})))"),
+            format!("ct({VIEW_KEY},elwsh(multi(2,{A},{B})))"),
+        ] {
+            let desc = WolletDescriptor::from_str(&desc).unwrap();
+            for chain in [Chain::External, Chain::Internal] {
+                let proof = desc.address_proof(chain, 7, params).unwrap();
+                proof.verify().unwrap();
+                assert!(!proof.template.contains("tpub"));
+                assert_eq!(proof.keys[0].steps.len(), 2);
+                assert_eq!(proof.keys[0].fingerprint(), xpub_a.fingerprint());
+
+                let json = serde_json::to_string(&proof).unwrap();
+                let proof: super::AddressProof = serde_json::from_str(&json).unwrap();
+                proof.verify().unwrap();
+
+                // A proof for a different address
+                let mut wrong = proof.clone();
+                wrong.address = desc.address(8, params).unwrap();
+                assert!(matches!(wrong.verify(), Err(Error::InvalidAddressProof(_))));
+
+                // A wrong tweak
+                let mut wrong = proof;
+                wrong.keys[0].steps[1] = wrong.keys[0].steps[0].clone();
+                assert!(matches!(wrong.verify(), Err(Error::InvalidAddressProof(_))));
+            }
+        }
+    }
+}
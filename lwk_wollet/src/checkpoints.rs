@@ -0,0 +1,467 @@
+//! Known blocks used to validate the headers returned by the servers
+//!
+//! A server could feed the wallet a fake chain, with a tip behind the real one or headers not
+//! belonging to the network. The headers downloaded during a scan must match the embedded
+//! checkpoints and the block hashes returned with the history and connect to each other. If
+//! enabled with [`crate::Config::with_block_signers()`], they must also be signed by one of the
+//! given scripts, such as the Liquid federation one.
+//!
+//! Liquid has a single checkpoint besides the genesis block, Liquid testnet only the genesis
+//! block, regtest networks none. When the tip is close enough to the previous wallet tip or to a
+//! checkpoint, the headers in between are downloaded and must connect them, see
+//! [`MAX_CHAIN_HEADERS`]. Otherwise the tip is verified only with the checkpoints and, if enabled,
+//! its signature.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use elements::hashes::{sha256, Hash};
+use elements::opcodes::all::OP_CHECKMULTISIG;
+use elements::script::Instruction;
+use elements::secp256k1_zkp::{ecdsa::Signature, Message, PublicKey};
+use elements::{BlockExtData, BlockHash, BlockHeader, Script};
+
+use crate::store::Height;
+use crate::{ElementsNetwork, Error, EC};
+
+const LIQUID_CHECKPOINTS: [(Height, &str); 1] = [(
+    2_963_520,
+    "eb25e1d7415eafcd2867148058347ab8910000a22d0abdca04625705c0ac893d",
+)];
+
+/// The scripts of the federation signing the Liquid blocks since the last checkpoint
+const LIQUID_SIGNBLOCKSCRIPTS: [&str; 1] =
+    ["002080d1b9d14b8e1b47b70f07e33a43e1efe7a985c683f1f19ddf4951b1d7c6d04c"];
+
+/// The maximum number of headers downloaded to connect the tip to the previous wallet tip or to a
+/// checkpoint, about two hours and a half of Liquid blocks
+pub const MAX_CHAIN_HEADERS: Height = 144;
+
+/// The only sighash type of block signatures
+const SIGHASH_ALL: u8 = 0x01;
+
+impl ElementsNetwork {
+    /// Known blocks of this network, sorted by height, including the genesis block
+    ///
+    /// Returns an empty list for regtest networks.
+    pub fn checkpoints(&self) -> Vec<(Height, BlockHash)> {
+        let mut checkpoints: Vec<_> = self.genesis_hash().map(|h| (0, h)).into_iter().collect();
+        if let ElementsNetwork::Liquid = self {
+            checkpoints.extend(LIQUID_CHECKPOINTS.iter().map(|(height, hash)| {
+                (
+                    *height,
+                    BlockHash::from_str(hash).expect("can't fail on const"),
+                )
+            }));
+        }
+        checkpoints
+    }
+
+    /// The known scripts signing the blocks after the last checkpoint, to verify the block
+    /// signatures with [`crate::Config::with_block_signers()`]
+    ///
+    /// Returns an empty list if not known.
+    pub fn signblockscripts(&self) -> Vec<Script> {
+        match self {
+            ElementsNetwork::Liquid => LIQUID_SIGNBLOCKSCRIPTS
+                .iter()
+                .map(|s| Script::from_str(s).expect("can't fail on const"))
+                .collect(),
+            ElementsNetwork::LiquidTestnet | ElementsNetwork::ElementsRegtest { .. } => vec![],
+        }
+    }
+}
+
+/// Check that `tip` is not behind the last checkpoint, matches the checkpoint at its height and
+/// is signed by one of the `signblockscripts`
+pub(crate) fn verify_tip(
+    checkpoints: &[(Height, BlockHash)],
+    signblockscripts: &[Script],
+    tip: &BlockHeader,
+) -> Result<(), Error> {
+    if let Some((height, _)) = checkpoints.last() {
+        if tip.height < *height {
+            return Err(Error::TipBeforeCheckpoint {
+                tip: tip.height,
+                checkpoint: *height,
+            });
+        }
+    }
+    verify_headers(
+        checkpoints,
+        signblockscripts,
+        std::slice::from_ref(tip),
+        &HashMap::new(),
+    )
+}
+
+/// Check that `headers` match the checkpoints and the known block hashes, that the headers at
+/// consecutive heights connect to each other and that they are signed by one of the
+/// `signblockscripts`
+///
+/// The headers before the last checkpoint may be signed by previous federations, their signature
+/// is verified only if their script is known.
+pub(crate) fn verify_headers(
+    checkpoints: &[(Height, BlockHash)],
+    signblockscripts: &[Script],
+    headers: &[BlockHeader],
+    height_blockhash: &HashMap<Height, BlockHash>,
+) -> Result<(), Error> {
+    let last_checkpoint = checkpoints.last().map(|(height, _)| *height);
+    let checkpoints: HashMap<Height, BlockHash> = checkpoints.iter().cloned().collect();
+    for header in headers {
+        let found = header.block_hash();
+        if let Some(expected) = checkpoints.get(&header.height) {
+            if *expected != found {
+                return Err(Error::CheckpointMismatch {
+                    height: header.height,
+                    expected: *expected,
+                    found,
+                });
+            }
+        }
+        if let Some(expected) = height_blockhash.get(&header.height) {
+            if *expected != found {
+                return Err(Error::BlockHashMismatch {
+                    height: header.height,
+                    expected: *expected,
+                    found,
+                });
+            }
+        }
+        if !signblockscripts.is_empty() {
+            let after_checkpoint = last_checkpoint.map_or(true, |h| header.height >= h);
+            let signed = match signblockscript(header) {
+                Some(script) if signblockscripts.contains(script) => {
+                    verify_signblock_witness(header, script)
+                }
+                _ => !after_checkpoint,
+            };
+            if !signed {
+                return Err(Error::InvalidBlockSignature(header.height));
+            }
+        }
+    }
+
+    let mut sorted: Vec<&BlockHeader> = headers.iter().collect();
+    sorted.sort_by_key(|h| h.height);
+    for pair in sorted.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        let connected = match next.height - prev.height {
+            0 => prev.block_hash() == next.block_hash(),
+            1 => next.prev_blockhash == prev.block_hash(),
+            _ => true,
+        };
+        if !connected {
+            return Err(Error::HeadersNotConnected(next.height));
+        }
+    }
+    Ok(())
+}
+
+/// The block to connect `tip` to, the last checkpoint or the `previous_tip` of the wallet, if
+/// there are at most [`MAX_CHAIN_HEADERS`] blocks in between
+///
+/// Returns `None` for networks without checkpoints.
+pub(crate) fn chain_anchor(
+    checkpoints: &[(Height, BlockHash)],
+    previous_tip: (Height, BlockHash),
+    tip: &BlockHeader,
+) -> Option<(Height, BlockHash)> {
+    if checkpoints.is_empty() {
+        return None;
+    }
+    let previous_tip = Some(previous_tip).filter(|(h, _)| *h > 0);
+    let anchor = checkpoints
+        .iter()
+        .cloned()
+        .chain(previous_tip)
+        .filter(|(h, _)| *h < tip.height)
+        .max_by_key(|(h, _)| *h)?;
+    (tip.height - anchor.0 <= MAX_CHAIN_HEADERS).then_some(anchor)
+}
+
+/// Check that `headers`, the blocks between `anchor` and `tip`, connect them
+pub(crate) fn verify_chain(
+    anchor: (Height, BlockHash),
+    headers: &[BlockHeader],
+    tip: &BlockHeader,
+) -> Result<(), Error> {
+    let mut sorted: Vec<&BlockHeader> = headers.iter().collect();
+    sorted.sort_by_key(|h| h.height);
+    sorted.push(tip);
+    let (mut height, mut hash) = anchor;
+    for header in sorted {
+        if header.height != height + 1 || header.prev_blockhash != hash {
+            return Err(Error::HeadersNotConnected(header.height));
+        }
+        height = header.height;
+        hash = header.block_hash();
+    }
+    Ok(())
+}
+
+/// The script signing the block, the challenge of the current dynamic federation parameters
+fn signblockscript(header: &BlockHeader) -> Option<&Script> {
+    match &header.ext {
+        BlockExtData::Dynafed { current, .. } => current.signblockscript(),
+        BlockExtData::Proof { .. } => None,
+    }
+}
+
+/// Whether the signblock witness of `header` satisfies `script`, a P2WSH of a multisig
+///
+/// As in `OP_CHECKMULTISIG`, the signatures must be in the same order of the keys. They sign the
+/// block hash and have a trailing [`SIGHASH_ALL`] byte.
+fn verify_signblock_witness(header: &BlockHeader, script: &Script) -> bool {
+    let BlockExtData::Dynafed {
+        signblock_witness, ..
+    } = &header.ext
+    else {
+        return false;
+    };
+    let Some((witness_script, stack)) = signblock_witness.split_last() else {
+        return false;
+    };
+    if !script.is_v0_p2wsh()
+        || script.as_bytes()[2..] != sha256::Hash::hash(witness_script).as_byte_array()[..]
+    {
+        return false;
+    }
+    let Some((threshold, keys)) = parse_multisig(&Script::from(witness_script.clone())) else {
+        return false;
+    };
+    // the first element is the dummy consumed by OP_CHECKMULTISIG
+    let Some((dummy, signatures)) = stack.split_first() else {
+        return false;
+    };
+    if !dummy.is_empty() || signatures.len() != threshold {
+        return false;
+    }
+
+    let message = Message::from_digest(header.block_hash().to_byte_array());
+    let mut keys = keys.iter();
+    signatures.iter().all(|signature| {
+        let Some((&SIGHASH_ALL, der)) = signature.split_last() else {
+            return false;
+        };
+        let Ok(mut signature) = Signature::from_der_lax(der) else {
+            return false;
+        };
+        signature.normalize_s();
+        keys.any(|key| EC.verify_ecdsa(&message, &signature, key).is_ok())
+    })
+}
+
+/// The threshold and the keys of a `<k> <key>... <n> OP_CHECKMULTISIG` script
+fn parse_multisig(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (Instruction::Op(k), rest) = instructions.split_first()? else {
+        return None;
+    };
+    let (Instruction::Op(OP_CHECKMULTISIG), rest) = rest.split_last()? else {
+        return None;
+    };
+    let (Instruction::Op(n), keys) = rest.split_last()? else {
+        return None;
+    };
+    let small_int = |op: &elements::opcodes::All| match op.into_u8() {
+        op @ 0x51..=0x60 => Some(op as usize - 0x50),
+        _ => None,
+    };
+    let (k, n) = (small_int(k)?, small_int(n)?);
+    let keys = keys
+        .iter()
+        .map(|i| match i {
+            Instruction::PushBytes(bytes) => PublicKey::from_slice(bytes).ok(),
+            Instruction::Op(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (keys.len() == n && k <= n).then_some((k, keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use elements::hashes::Hash;
+    use elements::{BlockExtData, BlockHash};
+
+    use super::{chain_anchor, verify_chain, verify_headers, verify_tip, MAX_CHAIN_HEADERS};
+    use crate::{ElementsNetwork, Error};
+
+    #[test]
+    fn test_checkpoints() {
+        let liquid = ElementsNetwork::Liquid.checkpoints();
+        assert_eq!(
+            liquid[0],
+            (0, ElementsNetwork::Liquid.genesis_hash().unwrap())
+        );
+        assert!(liquid.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(ElementsNetwork::LiquidTestnet.checkpoints().len(), 1);
+        assert!(ElementsNetwork::default_regtest().checkpoints().is_empty());
+
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        assert!(liquid.contains(&(header.height, header.block_hash())));
+    }
+
+    #[test]
+    fn test_verify_headers() {
+        let checkpoints = ElementsNetwork::Liquid.checkpoints();
+        let signers = ElementsNetwork::Liquid.signblockscripts();
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        verify_tip(&checkpoints, &signers, &header).unwrap();
+        verify_headers(&checkpoints, &signers, &[header.clone()], &HashMap::new()).unwrap();
+
+        // a tip behind the last checkpoint
+        let mut fake = header.clone();
+        fake.height -= 1;
+        let err = verify_tip(&checkpoints, &signers, &fake).unwrap_err();
+        assert!(matches!(err, Error::TipBeforeCheckpoint { .. }));
+
+        // a different block at the checkpoint height
+        let mut fake = header.clone();
+        fake.time += 1;
+        let err = verify_tip(&checkpoints, &signers, &fake).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::CheckpointMismatch {
+                height: 2_963_520,
+                ..
+            }
+        ));
+
+        // the next block must build on the previous one
+        let mut next = header.clone();
+        next.height += 1;
+        next.prev_blockhash = header.block_hash();
+        verify_headers(
+            &checkpoints,
+            &[],
+            &[next.clone(), header.clone()],
+            &HashMap::new(),
+        )
+        .unwrap();
+        next.prev_blockhash = BlockHash::all_zeros();
+        let err = verify_headers(
+            &checkpoints,
+            &[],
+            &[header.clone(), next.clone()],
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::HeadersNotConnected(2_963_521)));
+
+        // the headers must match the block hashes returned with the history
+        let height_blockhash = [(next.height, BlockHash::all_zeros())]
+            .into_iter()
+            .collect();
+        let err = verify_headers(&[], &[], &[next.clone()], &height_blockhash).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BlockHashMismatch {
+                height: 2_963_521,
+                ..
+            }
+        ));
+        let height_blockhash = [(next.height, next.block_hash())].into_iter().collect();
+        verify_headers(&[], &[], &[next], &height_blockhash).unwrap();
+    }
+
+    #[test]
+    fn test_verify_block_signature() {
+        let checkpoints = ElementsNetwork::Liquid.checkpoints();
+        let signers = ElementsNetwork::Liquid.signblockscripts();
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        let verify = |header| verify_headers(&checkpoints, &signers, &[header], &HashMap::new());
+
+        // a block after the checkpoint not signed by the federation
+        let mut next = header.clone();
+        next.height += 1;
+        next.prev_blockhash = header.block_hash();
+        let err = verify(next.clone()).unwrap_err();
+        assert!(matches!(err, Error::InvalidBlockSignature(2_963_521)));
+        // with no known signers the signature is not verified
+        verify_headers(&checkpoints, &[], &[next.clone()], &HashMap::new()).unwrap();
+
+        // before the checkpoint blocks may be signed by previous federations
+        let mut previous = header.clone();
+        previous.height -= 1;
+        let BlockExtData::Dynafed { current, .. } = &mut previous.ext else {
+            panic!("dynafed header")
+        };
+        *current = elements::dynafed::Params::Null;
+        verify(previous.clone()).unwrap();
+        next.ext = previous.ext.clone();
+        let err = verify(next).unwrap_err();
+        assert!(matches!(err, Error::InvalidBlockSignature(2_963_521)));
+
+        let tamper = |f: fn(&mut Vec<Vec<u8>>)| {
+            let mut header = header.clone();
+            let BlockExtData::Dynafed {
+                signblock_witness, ..
+            } = &mut header.ext
+            else {
+                panic!("dynafed header")
+            };
+            f(signblock_witness);
+            verify(header)
+        };
+        tamper(|_| ()).unwrap();
+        // 11 of 15 signatures are needed
+        let err = tamper(|w| {
+            w.remove(1);
+        })
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidBlockSignature(2_963_520)));
+        // in the order of the keys
+        assert!(tamper(|w| w.swap(1, 2)).is_err());
+        // with the sighash byte
+        assert!(tamper(|w| {
+            w[1].pop();
+        })
+        .is_err());
+        // the witness script must match the signblockscript
+        assert!(tamper(|w| {
+            w.last_mut().unwrap()[0] = 0x5c;
+        })
+        .is_err());
+        assert!(tamper(|w| w[0].push(0)).is_err());
+        assert!(tamper(|w| w.clear()).is_err());
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let checkpoints = ElementsNetwork::Liquid.checkpoints();
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        let checkpoint = (header.height, header.block_hash());
+        let next = |prev: &elements::BlockHeader| {
+            let mut next = prev.clone();
+            next.height += 1;
+            next.prev_blockhash = prev.block_hash();
+            next
+        };
+        let h1 = next(&header);
+        let h2 = next(&h1);
+        let tip = next(&h2);
+
+        let none = (0, BlockHash::all_zeros());
+        assert_eq!(chain_anchor(&checkpoints, none, &tip), Some(checkpoint));
+        // the genesis block is too far
+        assert_eq!(chain_anchor(&checkpoints, none, &header), None);
+        let previous = (h1.height, h1.block_hash());
+        assert_eq!(chain_anchor(&checkpoints, previous, &tip), Some(previous));
+        assert_eq!(chain_anchor(&[], previous, &tip), None);
+        let mut far = tip.clone();
+        far.height = header.height + MAX_CHAIN_HEADERS + 1;
+        assert_eq!(chain_anchor(&checkpoints, none, &far), None);
+
+        verify_chain(checkpoint, &[h2.clone(), h1.clone()], &tip).unwrap();
+        verify_chain(previous, &[h2.clone()], &tip).unwrap();
+        verify_chain((h2.height, h2.block_hash()), &[], &tip).unwrap();
+        let err = verify_chain(checkpoint, &[h2.clone()], &tip).unwrap_err();
+        assert!(matches!(err, Error::HeadersNotConnected(2_963_522)));
+        let err = verify_chain(checkpoint, &[h1, h2], &next(&tip)).unwrap_err();
+        assert!(matches!(err, Error::HeadersNotConnected(2_963_524)));
+    }
+}
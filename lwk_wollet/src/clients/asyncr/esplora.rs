@@ -1,8 +1,13 @@
 //! NOTE This module is temporary, as soon we make the other clients async this will be merged in
 //! the standard esplora client of which contain a lot of duplicated code.
 
+use super::esplora_cache::EsploraCache;
+use crate::checkpoints::{chain_anchor, verify_chain, verify_headers, verify_tip};
 use crate::clients::{check_witnesses_non_empty, LastUnused};
-use crate::clients::{try_unblind, Capability, History};
+use crate::clients::{
+    gap_reached, missing_prevouts, try_unblind, wallet_output_index, Capability, History,
+    RetryPolicy, TxBudget, TxStatus,
+};
 use crate::BlindingPublicKey;
 use crate::{
     clients::Data,
@@ -13,12 +18,17 @@ use crate::{
 };
 use age::x25519::Recipient;
 use base64::Engine;
-use elements::{bitcoin::bip32::ChildNumber, Address, OutPoint};
+use elements::{bitcoin::bip32::ChildNumber, OutPoint};
 use elements::{
-    encode::Decodable, hashes::hex::FromHex, hex::ToHex, pset::serialize::Serialize, BlockHash,
-    Script, Txid,
+    encode::Decodable,
+    hashes::{hex::FromHex, sha256, Hash},
+    hex::ToHex,
+    pset::serialize::Serialize,
+    BlockHash, Script, Txid,
 };
 use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Response;
 use serde::Deserialize;
 use std::{
@@ -39,10 +49,25 @@ pub struct EsploraClient {
     waterfalls: bool,
     waterfalls_server_recipient: Option<Recipient>,
 
+    /// The descriptors encrypted for `waterfalls_server_recipient`, reusing the same ciphertext
+    /// allows the responses to be cached
+    waterfalls_encrypted_descriptors: HashMap<String, String>,
+
     /// Avoid encrypting the descriptor field
     pub(crate) waterfalls_avoid_encryption: bool,
 
     network: ElementsNetwork,
+
+    /// The maximum number of requests in flight
+    concurrency: usize,
+
+    retry: RetryPolicy,
+
+    /// Headers added to every request, for instance to authenticate
+    headers: HeaderMap,
+
+    /// The cache of the immutable responses, if any
+    cache: Option<EsploraCache>,
 }
 
 impl EsploraClient {
@@ -54,7 +79,8 @@ impl EsploraClient {
     }
 
     pub(crate) async fn last_block_hash(&mut self) -> Result<elements::BlockHash, crate::Error> {
-        let response = get_with_retry(&self.client, &self.tip_hash_url).await?;
+        let response =
+            get_with_retry(&self.client, &self.headers, &self.retry, &self.tip_hash_url).await?;
         Ok(BlockHash::from_str(&response.text().await?)?)
     }
 
@@ -65,15 +91,60 @@ impl EsploraClient {
         self.header(last_block_hash).await
     }
 
-    async fn header(&mut self, last_block_hash: BlockHash) -> Result<elements::BlockHeader, Error> {
-        let header_url = format!("{}/block/{}/header", self.base_url, last_block_hash);
-        let response = get_with_retry(&self.client, &header_url).await?;
+    async fn header(&self, block_hash: BlockHash) -> Result<elements::BlockHeader, Error> {
+        let key = format!("block/{block_hash}/header");
+        if let Some(header) = self.cached::<elements::BlockHeader>(&key) {
+            if header.block_hash() == block_hash {
+                return Ok(header);
+            }
+        }
+        let header_url = format!("{}/{key}", self.base_url);
+        let response =
+            get_with_retry(&self.client, &self.headers, &self.retry, &header_url).await?;
         let header_bytes = Vec::<u8>::from_hex(&response.text().await?)?;
 
         let header = elements::BlockHeader::consensus_decode(&header_bytes[..])?;
+        if header.block_hash() == block_hash {
+            self.cache(&key, &header);
+        }
         Ok(header)
     }
 
+    fn cached<T: Decodable>(&self, key: &str) -> Option<T> {
+        self.cache.as_ref()?.get(key)
+    }
+
+    fn cache<T: elements::encode::Encodable>(&self, key: &str, value: &T) {
+        if let Some(cache) = self.cache.as_ref() {
+            cache.insert(key, value);
+        }
+    }
+
+    /// The fee rates in sats/kvb for each confirmation target in blocks
+    pub async fn fee_estimates(&self) -> Result<HashMap<u16, f32>, Error> {
+        let url = format!("{}/fee-estimates", self.base_url);
+        let response = get_with_retry(&self.client, &self.headers, &self.retry, &url).await?;
+        let estimates: HashMap<u16, f32> = response.json().await?;
+        // esplora returns sats/vb
+        Ok(estimates
+            .into_iter()
+            .map(|(k, v)| (k, v * 1000.0))
+            .collect())
+    }
+
+    /// Async version of [`crate::blocking::BlockchainBackend::estimate_fee()`]
+    ///
+    /// Uses the estimate of the greatest target not above the given one, if any.
+    pub async fn estimate_fee(&self, target: u16) -> Result<f32, Error> {
+        let estimates = self.fee_estimates().await?;
+        estimates
+            .iter()
+            .filter(|(t, _)| **t <= target)
+            .max_by_key(|(t, _)| **t)
+            .map(|(_, fee_rate)| *fee_rate)
+            .ok_or(Error::FeeEstimateUnavailable(target))
+    }
+
     /// Async version of [`crate::blocking::BlockchainBackend::broadcast()`]
     pub async fn broadcast(
         &self,
@@ -85,6 +156,7 @@ impl EsploraClient {
         let response = self
             .client
             .post(&self.broadcast_url)
+            .headers(self.headers.clone())
             .body(tx_hex)
             .send()
             .await?;
@@ -93,22 +165,48 @@ impl EsploraClient {
     }
 
     pub(crate) async fn get_transaction(&self, txid: Txid) -> Result<elements::Transaction, Error> {
-        let tx_url = format!("{}/tx/{}/raw", self.base_url, txid);
-        let response = get_with_retry(&self.client, &tx_url).await?;
+        let key = format!("tx/{txid}/raw");
+        if let Some(tx) = self.cached::<elements::Transaction>(&key) {
+            if tx.txid() == txid {
+                return Ok(tx);
+            }
+        }
+        let tx_url = format!("{}/{key}", self.base_url);
+        let response = get_with_retry(&self.client, &self.headers, &self.retry, &tx_url).await?;
         let tx = elements::Transaction::consensus_decode(&response.bytes().await?[..])?;
+        if tx.txid() == txid {
+            self.cache(&key, &tx);
+        }
 
         Ok(tx)
     }
 
+    /// Async version of [`crate::blocking::BlockchainBackend::tx_status()`]
+    pub async fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
+        let url = format!("{}/tx/{}/status", self.base_url, txid);
+        let response = get_with_retry(&self.client, &self.headers, &self.retry, &url).await?;
+        if response.status() == 404 {
+            return Ok(TxStatus::NotFound);
+        }
+        let status: Status = response.error_for_status()?.json().await?;
+        Ok(match status.block_height {
+            Some(height) => TxStatus::Confirmed {
+                height: height as Height,
+            },
+            None => TxStatus::Unconfirmed,
+        })
+    }
+
+    /// Download the transactions, with at most [`EsploraClientBuilder::concurrency()`] requests
+    /// in flight
     pub(crate) async fn get_transactions(
         &self,
         txids: &[Txid],
     ) -> Result<Vec<elements::Transaction>, Error> {
-        let mut result = vec![];
-        for txid in txids.iter().cloned() {
-            result.push(self.get_transaction(txid).await?);
-        }
-        Ok(result)
+        stream::iter(txids.iter().map(|txid| self.get_transaction(*txid)))
+            .buffered(self.concurrency)
+            .try_collect()
+            .await
     }
 
     pub(crate) async fn get_headers(
@@ -116,58 +214,139 @@ impl EsploraClient {
         heights: &[Height],
         height_blockhash: &HashMap<Height, BlockHash>,
     ) -> Result<Vec<elements::BlockHeader>, Error> {
-        let mut result = vec![];
-        for height in heights.iter() {
-            let block_hash = match height_blockhash.get(height) {
-                Some(block_hash) => *block_hash,
-                None => {
-                    let block_height = format!("{}/block-height/{}", self.base_url, height);
-                    let response = get_with_retry(&self.client, &block_height).await?;
-                    BlockHash::from_str(&response.text().await?)?
-                }
-            };
-
-            let block_header = format!("{}/block/{}/header", self.base_url, block_hash);
-            let response = get_with_retry(&self.client, &block_header).await?;
-            let header_bytes = Vec::<u8>::from_hex(&response.text().await?)?;
+        stream::iter(
+            heights
+                .iter()
+                .map(|h| self.get_header(*h, height_blockhash)),
+        )
+        .buffered(self.concurrency)
+        .try_collect()
+        .await
+    }
 
-            let header = elements::BlockHeader::consensus_decode(&header_bytes[..])?;
+    async fn get_header(
+        &self,
+        height: Height,
+        height_blockhash: &HashMap<Height, BlockHash>,
+    ) -> Result<elements::BlockHeader, Error> {
+        let block_hash = match height_blockhash.get(&height) {
+            Some(block_hash) => *block_hash,
+            None => {
+                let block_height = format!("{}/block-height/{}", self.base_url, height);
+                let response =
+                    get_with_retry(&self.client, &self.headers, &self.retry, &block_height).await?;
+                BlockHash::from_str(&response.text().await?)?
+            }
+        };
 
-            result.push(header);
-        }
-        Ok(result)
+        self.header(block_hash).await
     }
 
-    // examples:
-    // https://blockstream.info/liquidtestnet/api/address/tex1qntw9m0j2e93n84x975t47ddhgkzx3x8lhfv2nj/txs
-    // https://blockstream.info/liquidtestnet/api/scripthash/b50a2a798d876db54acfa0d8dfdc49154ea8defed37b225ec4c9ec7415358ba3/txs
+    /// Download the history of the scripts, with at most [`EsploraClientBuilder::concurrency()`]
+    /// requests in flight
     pub async fn get_scripts_history(
         &self,
         scripts: &[&Script],
     ) -> Result<Vec<Vec<History>>, Error> {
-        let mut result: Vec<_> = vec![];
-        for script in scripts.iter() {
-            let address = Address::from_script(script, None, self.network.address_params()).ok_or(
-                Error::Generic("script generated is not a known template".to_owned()),
-            )?;
-            let url = format!("{}/address/{}/txs", self.base_url, address);
-            // TODO must handle paging -> https://github.com/blockstream/esplora/blob/master/API.md#addresses
-            let response = get_with_retry(&self.client, &url).await?;
-
-            // TODO going through string and then json is not as efficient as it could be but we prioritize debugging for now
-            let text = response.text().await?;
-            let json: Vec<EsploraTx> = match serde_json::from_str(&text) {
-                Ok(e) => e,
-                Err(e) => {
-                    log::warn!("error {e:?} in converting following text:\n{text}");
-                    return Err(e.into());
-                }
+        self.get_scripts_history_known(scripts, &HashMap::new())
+            .await
+    }
+
+    /// Download the history of the scripts, without requesting the confirmed transactions
+    /// older than the ones in `known`
+    async fn get_scripts_history_known(
+        &self,
+        scripts: &[&Script],
+        known: &HashMap<Script, HashMap<Txid, Height>>,
+    ) -> Result<Vec<Vec<History>>, Error> {
+        let empty = HashMap::new();
+        stream::iter(
+            scripts.iter().map(|script| {
+                self.get_script_history(script, known.get(*script).unwrap_or(&empty))
+            }),
+        )
+        .buffered(self.concurrency)
+        .try_collect()
+        .await
+    }
+
+    // examples:
+    // https://blockstream.info/liquidtestnet/api/scripthash/b50a2a798d876db54acfa0d8dfdc49154ea8defed37b225ec4c9ec7415358ba3/txs
+    // https://blockstream.info/liquidtestnet/api/scripthash/b50a2a798d876db54acfa0d8dfdc49154ea8defed37b225ec4c9ec7415358ba3/txs/chain/<last_seen_txid>
+    async fn get_script_history(
+        &self,
+        script: &Script,
+        known: &HashMap<Txid, Height>,
+    ) -> Result<Vec<History>, Error> {
+        let url = format!("{}/scripthash/{}/txs", self.base_url, script_hash(script));
+        let mut txs = self.get_esplora_txs(&url).await?;
+
+        // the first page contains the mempool transactions and up to 25 confirmed ones, the
+        // following confirmed ones are requested after the last seen
+        let mut page_start = 0;
+        let mut confirmed = txs.iter().filter(|tx| tx.status.confirmed()).count();
+        while confirmed == ESPLORA_CHAIN_PAGE_SIZE {
+            // once a page reaches a transaction already known at the same height, the older
+            // history is taken from the known transactions instead of being requested
+            if let Some(height) = oldest_known_height(&txs[page_start..], known) {
+                let seen: HashSet<Txid> = txs.iter().map(|tx| tx.txid).collect();
+                let mut history: Vec<History> = txs.into_iter().map(Into::into).collect();
+                history.extend(
+                    known
+                        .iter()
+                        .filter(|(txid, h)| **h <= height && !seen.contains(*txid))
+                        .map(|(txid, h)| History {
+                            txid: *txid,
+                            height: *h as i32,
+                            block_hash: None,
+                            block_timestamp: None,
+                        }),
+                );
+                return Ok(history);
+            }
+            let last_seen = match txs.last() {
+                Some(tx) => tx.txid,
+                None => break,
             };
+            let url = format!(
+                "{}/scripthash/{}/txs/chain/{}",
+                self.base_url,
+                script_hash(script),
+                last_seen
+            );
+            let page = self.get_esplora_txs(&url).await?;
+            confirmed = page.len();
+            page_start = txs.len();
+            txs.extend(page);
+        }
 
-            let history: Vec<History> = json.into_iter().map(Into::into).collect();
-            result.push(history)
+        Ok(txs.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_esplora_txs(&self, url: &str) -> Result<Vec<EsploraTx>, Error> {
+        let response = get_with_retry(&self.client, &self.headers, &self.retry, url).await?;
+
+        // TODO going through string and then json is not as efficient as it could be but we prioritize debugging for now
+        let text = response.text().await?;
+        match serde_json::from_str(&text) {
+            Ok(e) => Ok(e),
+            Err(e) => {
+                log::warn!("error {e:?} in converting following text:\n{text}");
+                Err(e.into())
+            }
         }
-        Ok(result)
+    }
+
+    /// Async version of [`crate::blocking::BlockchainBackend::scan_txid()`]
+    pub async fn scan_txid(
+        &mut self,
+        wollet: &Wollet,
+        txid: Txid,
+        max_index: u32,
+    ) -> Result<Option<Update>, Error> {
+        let tx = self.get_transaction(txid).await?;
+        let index = wallet_output_index(&tx, wollet, max_index)?;
+        self.full_scan_to_index(wollet, index + 1).await
     }
 
     /// Async version of [`crate::blocking::BlockchainBackend::full_scan()`]
@@ -182,51 +361,70 @@ impl EsploraClient {
     ) -> Result<Option<Update>, Error> {
         let descriptor = wollet.wollet_descriptor();
         let store = &wollet.store;
+        let gap_limit = wollet.config().gap_limit();
 
-        let Data {
-            txid_height,
-            scripts,
-            last_unused,
-            height_blockhash,
-            height_timestamp,
-            tip,
-        } = if self.waterfalls {
+        let mut data = if self.waterfalls {
             if index != 0 {
                 return Err(Error::UsingWaterfallsWithNonZeroIndex);
             }
             match self.get_history_waterfalls(&descriptor, wollet).await {
                 Ok(d) => d,
-                Err(Error::UsingWaterfallsWithElip151) => {
-                    self.get_history(&descriptor, store, index, wollet.last_unused())
+                Err(Error::UsingWaterfallsWithElip151 | Error::WaterfallsUnsupported(_)) => {
+                    self.get_history(&descriptor, store, index, wollet.last_unused(), gap_limit)
                         .await?
                 }
                 Err(e) => return Err(e),
             }
         } else {
-            self.get_history(&descriptor, store, index, wollet.last_unused())
+            self.get_history(&descriptor, store, index, wollet.last_unused(), gap_limit)
                 .await?
         };
+        if let Some(birthday) = wollet.config().birthday() {
+            data.skip_before(birthday);
+        }
+        let Data {
+            txid_height,
+            scripts,
+            last_unused,
+            height_blockhash,
+            height_timestamp,
+            tip,
+            checkpoints: _,
+        } = data;
 
         let tip = if let Some(tip) = tip {
             self.header(tip).await?
         } else {
             self.tip().await?
         };
+        let checkpoints = self.network.checkpoints();
+        let block_signers = wollet.config().block_signers();
+        verify_tip(&checkpoints, block_signers, &tip)?;
+        if let Some(anchor) = chain_anchor(&checkpoints, store.cache.tip, &tip) {
+            let heights: Vec<Height> = (anchor.0 + 1..tip.height).collect();
+            let headers = self.get_headers(&heights, &HashMap::new()).await?;
+            verify_chain(anchor, &headers, &tip)?;
+        }
 
         let history_txs_id: HashSet<Txid> = txid_height.keys().cloned().collect();
         let new_txs = self
-            .download_txs(&history_txs_id, &scripts, store, &descriptor)
+            .download_txs(&history_txs_id, &scripts, wollet, &descriptor)
             .await?;
+        let downloaded: HashSet<Txid> = new_txs.txs.iter().map(|(txid, _)| *txid).collect();
         let history_txs_heights_plus_tip: HashSet<Height> = txid_height
             .values()
             .filter_map(|e| *e)
             .chain(std::iter::once(tip.height))
             .collect();
+        // the header downloaded at the tip height must be the tip
+        let mut height_blockhash = height_blockhash;
+        height_blockhash.insert(tip.height, tip.block_hash());
         let timestamps = self
             .download_headers(
                 &history_txs_heights_plus_tip,
                 &height_blockhash,
                 &height_timestamp,
+                block_signers,
                 store,
             )
             .await?;
@@ -252,8 +450,10 @@ impl EsploraClient {
         if changed {
             log::debug!("something changed: !new_txs.txs.is_empty():{} last_unused_changed:{} !scripts.is_empty():{} !timestamps.is_empty():{}", !new_txs.txs.is_empty(), last_unused_changed, !scripts.is_empty(), !timestamps.is_empty() );
 
+            // transactions deferred by the memory budget are added by the next scans
             let txid_height_new: Vec<_> = txid_height
                 .iter()
+                .filter(|(k, _)| store.cache.all_txs.contains_key(*k) || downloaded.contains(*k))
                 .filter(|(k, v)| match store.cache.heights.get(*k) {
                     Some(e) => e != *v,
                     None => true,
@@ -285,6 +485,7 @@ impl EsploraClient {
                 timestamps,
                 scripts_with_blinding_pubkey,
                 tip,
+                checkpoints: vec![],
             };
             Ok(Some(update))
         } else {
@@ -299,18 +500,21 @@ impl EsploraClient {
         store: &Store,
         index: u32,
         last_unused: LastUnused,
+        gap_limit: u32,
     ) -> Result<Data, Error> {
         let mut data = Data::default();
+        let known = known_script_txs(store);
 
         for descriptor in descriptor.as_single_descriptors()? {
             let mut batch_count = 0;
+            let mut last_used = None;
             let chain: Chain = (&descriptor).try_into().unwrap_or(Chain::External);
             let index = index.max(last_unused[chain]);
             loop {
                 let batch = store.get_script_batch(batch_count, &descriptor)?;
 
                 let s: Vec<_> = batch.value.iter().map(|e| &e.0).collect();
-                let result: Vec<Vec<History>> = self.get_scripts_history(&s).await?;
+                let result: Vec<Vec<History>> = self.get_scripts_history_known(&s, &known).await?;
                 if !batch.cached {
                     data.scripts.extend(batch.value);
                 }
@@ -321,6 +525,7 @@ impl EsploraClient {
                     .map(|(i, _)| i as u32)
                     .max();
                 if let Some(max) = max {
+                    last_used = Some(max + batch_count * BATCH_SIZE);
                     match chain {
                         Chain::External => {
                             data.last_unused.external = 1 + max + batch_count * BATCH_SIZE
@@ -333,10 +538,6 @@ impl EsploraClient {
 
                 let flattened: Vec<History> = result.into_iter().flatten().collect();
 
-                if flattened.is_empty() && index <= 1 + batch_count * BATCH_SIZE {
-                    break;
-                }
-
                 for el in flattened {
                     // el.height = -1 means unconfirmed with unconfirmed parents
                     // el.height =  0 means unconfirmed with confirmed parents
@@ -354,6 +555,9 @@ impl EsploraClient {
                 }
 
                 batch_count += 1;
+                if gap_reached(batch_count * BATCH_SIZE, last_used, gap_limit, index) {
+                    break;
+                }
             }
         }
         Ok(data)
@@ -364,110 +568,247 @@ impl EsploraClient {
         match self.waterfalls_server_recipient.as_ref() {
             Some(r) => Ok(r.clone()),
             None => {
-                let url = format!("{}/v1/server_recipient", self.base_url);
-                let response = self.client.get(&url).send().await?;
-                let status = response.status().as_u16();
-                let body = response.text().await?;
-                if status != 200 {
-                    return Err(Error::Generic(body));
-                }
-                let rec = Recipient::from_str(&body).map_err(|_| Error::CannotParseRecipientKey)?;
-                self.waterfalls_server_recipient = Some(rec.clone());
+                let rec = self.fetch_waterfalls_server_recipient().await?;
+                self.set_waterfalls_server_recipient(rec.clone());
                 Ok(rec)
             }
         }
     }
 
+    async fn fetch_waterfalls_server_recipient(&self) -> Result<Recipient, Error> {
+        let url = format!("{}/v1/server_recipient", self.base_url);
+        let response = get_with_retry(&self.client, &self.headers, &self.retry, &url).await?;
+        let status = response.status().as_u16();
+        let body = response.text().await?;
+        if waterfalls_unsupported(status) {
+            return Err(Error::WaterfallsUnsupported(format!("status {status}")));
+        }
+        if status != 200 {
+            return Err(Error::Generic(body));
+        }
+        Recipient::from_str(body.trim()).map_err(|_| Error::CannotParseRecipientKey)
+    }
+
+    /// Ask the server its recipient key again, returns true if it changed from the cached one
+    async fn refresh_waterfalls_server_recipient(&mut self) -> Result<bool, Error> {
+        let rec = self.fetch_waterfalls_server_recipient().await?;
+        let changed = self
+            .waterfalls_server_recipient
+            .as_ref()
+            .map_or(true, |r| r.to_string() != rec.to_string());
+        self.set_waterfalls_server_recipient(rec);
+        Ok(changed)
+    }
+
+    /// Returns the descriptor encrypted for the server recipient key, encrypting it only the
+    /// first time
+    async fn encrypted_descriptor(&mut self, desc: &str) -> Result<String, Error> {
+        let recipient = self.waterfalls_server_recipient().await?;
+        if let Some(encrypted) = self.waterfalls_encrypted_descriptors.get(desc) {
+            return Ok(encrypted.clone());
+        }
+        let encrypted = encrypt(desc, recipient)?;
+        self.waterfalls_encrypted_descriptors
+            .insert(desc.to_string(), encrypted.clone());
+        Ok(encrypted)
+    }
+
+    /// Get the history of the descriptor with the "waterfalls" endpoint
+    ///
+    /// If the server doesn't support it, [`Error::WaterfallsUnsupported`] is returned and the
+    /// client stops using it.
     pub(crate) async fn get_history_waterfalls<S: WolletState>(
         &mut self,
         descriptor: &WolletDescriptor,
         store: &S,
+    ) -> Result<Data, Error> {
+        let result = self.get_history_waterfalls_pages(descriptor, store).await;
+        if let Err(Error::WaterfallsUnsupported(reason)) = &result {
+            log::warn!("waterfalls not supported ({reason}), using the esplora scan");
+            self.waterfalls = false;
+        }
+        result
+    }
+
+    async fn get_history_waterfalls_pages<S: WolletState>(
+        &mut self,
+        descriptor: &WolletDescriptor,
+        store: &S,
     ) -> Result<Data, Error> {
         let descriptor_url = format!("{}/v2/waterfalls", self.base_url);
         if descriptor.is_elip151() {
             return Err(Error::UsingWaterfallsWithElip151);
         }
         let desc = descriptor.bitcoin_descriptor_without_key_origin();
-        let desc = if self.waterfalls_avoid_encryption {
-            desc
-        } else {
-            let recipient = self.waterfalls_server_recipient().await?;
-
-            // TODO ideally the encrypted descriptor should be cached and reused, so that caching can be leveraged
-            encrypt(&desc, recipient)?
-        };
-
-        let response = self
-            .client
-            .get(&descriptor_url)
-            .query(&[("descriptor", desc)])
-            .send()
-            .await?;
-        let status = response.status().as_u16();
-        let body = response.text().await?;
+        if self.waterfalls_avoid_encryption {
+            return self
+                .get_waterfalls_data(&descriptor_url, descriptor, store, &desc)
+                .await;
+        }
 
-        if status != 200 {
-            return Err(Error::Generic(body));
+        let encrypted = self.encrypted_descriptor(&desc).await?;
+        match self
+            .get_waterfalls_data(&descriptor_url, descriptor, store, &encrypted)
+            .await
+        {
+            // A server which rotated its key can't decrypt the descriptor, then it tries to parse
+            // the ciphertext as a descriptor and rejects the request.
+            Err(Error::WaterfallsRequestRejected(reason)) => {
+                if !self.refresh_waterfalls_server_recipient().await? {
+                    return Err(Error::WaterfallsRequestRejected(reason));
+                }
+                log::info!("waterfalls server recipient changed, encrypting the descriptor again");
+                let encrypted = self.encrypted_descriptor(&desc).await?;
+                self.get_waterfalls_data(&descriptor_url, descriptor, store, &encrypted)
+                    .await
+            }
+            result => result,
         }
+    }
 
-        let waterfalls_result: WaterfallsResult = serde_json::from_str(&body)?;
+    /// Request the waterfalls pages of the (possibly encrypted) descriptor `desc` until the gap
+    /// limit is reached on both chains
+    async fn get_waterfalls_data<S: WolletState>(
+        &self,
+        descriptor_url: &str,
+        descriptor: &WolletDescriptor,
+        store: &S,
+        desc: &str,
+    ) -> Result<Data, Error> {
         let mut data = Data::default();
-
-        for (desc, chain_history) in waterfalls_result.txs_seen.iter() {
-            let desc: elements_miniscript::Descriptor<DescriptorPublicKey> = desc.parse()?;
-            let chain: Chain = (&desc)
-                .try_into()
-                .map_err(|_| Error::Generic("Cannot determine chain from desc".into()))?;
-            let max = chain_history
-                .iter()
-                .enumerate()
-                .filter(|(_, v)| !v.is_empty())
-                .map(|(i, _)| i as u32)
-                .max();
-            if let Some(max) = max {
-                data.last_unused[chain] = max + 1;
+        // the chains that may have used scripts in the following pages
+        let mut open_chains: HashSet<Chain> = [Chain::External, Chain::Internal].into();
+        let mut page: u16 = 0;
+        loop {
+            let waterfalls_result = self.get_waterfalls_page(descriptor_url, desc, page).await?;
+            if waterfalls_result.page != page {
+                return Err(Error::Generic(format!(
+                    "Waterfalls returned page {} instead of {page}",
+                    waterfalls_result.page
+                )));
             }
-            for (i, script_history) in chain_history.iter().enumerate() {
-                // TODO handle paging by asking following pages if there are more than 1000 results
-                let child = ChildNumber::from(waterfalls_result.page as u32 * 1000 + i as u32);
-                let ct_desc = ConfidentialDescriptor {
-                    key: descriptor.0.key.clone(),
-                    descriptor: desc.clone(),
-                };
-                let (script, blinding_pubkey, cached) =
-                    store.get_or_derive(chain, child, &ct_desc)?;
-                if !cached {
-                    data.scripts.insert(script, (chain, child, blinding_pubkey));
+            let first_index = page as u32 * WATERFALLS_PAGE_SIZE;
+            let mut next_open_chains = HashSet::new();
+
+            for (desc, chain_history) in waterfalls_result.txs_seen.iter() {
+                let desc: elements_miniscript::Descriptor<DescriptorPublicKey> = desc.parse()?;
+                let chain: Chain = (&desc)
+                    .try_into()
+                    .map_err(|_| Error::Generic("Cannot determine chain from desc".into()))?;
+                if !open_chains.contains(&chain) {
+                    continue;
                 }
-                for tx_seen in script_history {
-                    let height = if tx_seen.height > 0 {
-                        Some(tx_seen.height as u32)
-                    } else {
-                        None
+                // the server stops at the gap limit, a full page means there may be more
+                if chain_history.len() as u32 >= WATERFALLS_PAGE_SIZE {
+                    next_open_chains.insert(chain);
+                }
+                let max = chain_history
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| !v.is_empty())
+                    .map(|(i, _)| i as u32)
+                    .max();
+                if let Some(max) = max {
+                    data.last_unused[chain] = first_index + max + 1;
+                }
+                for (i, script_history) in chain_history.iter().enumerate() {
+                    let child = ChildNumber::from(first_index + i as u32);
+                    let ct_desc = ConfidentialDescriptor {
+                        key: descriptor.0.key.clone(),
+                        descriptor: desc.clone(),
                     };
-                    if let Some(height) = height.as_ref() {
-                        if let Some(block_hash) = tx_seen.block_hash.as_ref() {
-                            data.height_blockhash.insert(*height, *block_hash);
-                        }
-                        if let Some(ts) = tx_seen.block_timestamp.as_ref() {
-                            data.height_timestamp.insert(*height, *ts);
-                        }
+                    let (script, blinding_pubkey, cached) =
+                        store.get_or_derive(chain, child, &ct_desc)?;
+                    if !cached {
+                        data.scripts.insert(script, (chain, child, blinding_pubkey));
                     }
+                    for tx_seen in script_history {
+                        let height = if tx_seen.height > 0 {
+                            Some(tx_seen.height as u32)
+                        } else {
+                            None
+                        };
+                        if let Some(height) = height.as_ref() {
+                            if let Some(block_hash) = tx_seen.block_hash.as_ref() {
+                                data.height_blockhash.insert(*height, *block_hash);
+                            }
+                            if let Some(ts) = tx_seen.block_timestamp.as_ref() {
+                                data.height_timestamp.insert(*height, *ts);
+                            }
+                        }
 
-                    data.txid_height.insert(tx_seen.txid, height);
+                        data.txid_height.insert(tx_seen.txid, height);
+                    }
                 }
             }
+            // the tip of the last page, which may include transactions confirmed after the
+            // previous pages
+            data.tip = waterfalls_result.tip;
+
+            if next_open_chains.is_empty() {
+                break;
+            }
+            log::debug!("waterfalls page {page} is full, requesting the next one");
+            open_chains = next_open_chains;
+            page = page
+                .checked_add(1)
+                .ok_or_else(|| Error::Generic("Too many waterfalls pages".into()))?;
         }
-        data.tip = waterfalls_result.tip;
 
         Ok(data)
     }
 
+    /// Request a page of the waterfalls history of the (possibly encrypted) descriptor `desc`
+    async fn get_waterfalls_page(
+        &self,
+        descriptor_url: &str,
+        desc: &str,
+        page: u16,
+    ) -> Result<WaterfallsResult, Error> {
+        let response = send_with_retry(&self.retry, descriptor_url, || {
+            let request = self
+                .client
+                .get(descriptor_url)
+                .headers(self.headers.clone())
+                .query(&[("descriptor", desc)]);
+            if page > 0 {
+                request.query(&[("page", page)])
+            } else {
+                request
+            }
+        })
+        .await?;
+        let status = response.status().as_u16();
+        let body = response.text().await?;
+
+        if waterfalls_unsupported(status) {
+            return Err(Error::WaterfallsUnsupported(format!("status {status}")));
+        }
+        if (400..500).contains(&status) {
+            return Err(Error::WaterfallsRequestRejected(body));
+        }
+        if status != 200 {
+            return Err(Error::Generic(body));
+        }
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
     pub fn avoid_encryption(&mut self) {
         self.waterfalls_avoid_encryption = true;
     }
 
+    /// Set the recipient key of the waterfalls server, instead of asking it to the server
+    ///
+    /// If the server rotates its key, the new one is fetched automatically.
     pub fn set_waterfalls_server_recipient(&mut self, recipient: Recipient) {
+        let changed = self
+            .waterfalls_server_recipient
+            .as_ref()
+            .map_or(true, |r| r.to_string() != recipient.to_string());
+        if changed {
+            self.waterfalls_encrypted_descriptors.clear();
+        }
         self.waterfalls_server_recipient = Some(recipient);
     }
 
@@ -475,18 +816,36 @@ impl EsploraClient {
         &self,
         history_txs_id: &HashSet<Txid>,
         scripts: &HashMap<Script, (Chain, ChildNumber, BlindingPublicKey)>,
-        store: &Store,
+        wollet: &Wollet,
         descriptor: &WolletDescriptor,
     ) -> Result<DownloadTxResult, Error> {
         let mut txs = vec![];
         let mut unblinds = vec![];
+        let store = &wollet.store;
+        let verify_proofs = wollet.config().verify_proofs();
+        let memory_budget = wollet.config().memory_budget();
 
-        let mut txs_in_db = store.cache.all_txs.keys().cloned().collect();
+        let mut txs_in_db = WolletState::txs(wollet);
         let txs_to_download: Vec<Txid> = history_txs_id.difference(&txs_in_db).cloned().collect();
 
-        for txid in txs_to_download {
-            let tx = self.get_transaction(txid).await?;
+        let mut budget = TxBudget::new(memory_budget);
+        let mut txs_downloaded = vec![];
+        for chunk in txs_to_download.chunks(budget.batch_size()) {
+            if budget.exhausted() {
+                log::info!(
+                    "memory budget reached, {} transactions deferred to the next scan",
+                    txs_to_download.len() - txs_downloaded.len()
+                );
+                break;
+            }
+            for tx in self.get_transactions(chunk).await? {
+                budget.consume(&tx);
+                txs_downloaded.push(tx);
+            }
+        }
 
+        for tx in txs_downloaded {
+            let txid = tx.txid();
             txs_in_db.insert(txid);
 
             for (i, output) in tx.output.iter().enumerate() {
@@ -507,6 +866,13 @@ impl EsploraClient {
             txs.push((txid, tx));
         }
 
+        if verify_proofs {
+            let prevouts = missing_prevouts(txs.iter().map(|(_, tx)| tx), &txs_in_db);
+            for tx in self.get_transactions(&prevouts).await? {
+                txs.push((tx.txid(), tx));
+            }
+        }
+
         Ok(DownloadTxResult { txs, unblinds })
     }
 
@@ -515,6 +881,7 @@ impl EsploraClient {
         history_txs_heights_plus_tip: &HashSet<Height>,
         height_blockhash: &HashMap<Height, BlockHash>,
         height_timestamp: &HashMap<Height, Timestamp>,
+        block_signers: &[Script],
         store: &Store,
     ) -> Result<Vec<(Height, Timestamp)>, Error> {
         let mut result = vec![];
@@ -528,10 +895,16 @@ impl EsploraClient {
             .cloned()
             .collect();
         if !heights_to_download.is_empty() {
-            for h in self
+            let headers = self
                 .get_headers(&heights_to_download, height_blockhash)
-                .await?
-            {
+                .await?;
+            verify_headers(
+                &self.network.checkpoints(),
+                block_signers,
+                &headers,
+                height_blockhash,
+            )?;
+            for h in headers {
                 result.push((h.height, h.time))
             }
 
@@ -547,7 +920,11 @@ impl EsploraClient {
         Ok(result)
     }
 
-    pub(crate) fn capabilities(&self) -> HashSet<Capability> {
+    /// The capabilities used by the client
+    ///
+    /// [`Capability::Waterfalls`] is dropped if the server turns out not to support the
+    /// "waterfalls" endpoints, and the following scans use the standard esplora endpoints.
+    pub fn capabilities(&self) -> HashSet<Capability> {
         if self.waterfalls {
             vec![Capability::Waterfalls].into_iter().collect()
         } else {
@@ -562,6 +939,18 @@ pub struct EsploraClientBuilder {
     waterfalls: bool,
     network: ElementsNetwork,
     timeout: Option<u8>,
+    #[cfg(not(target_arch = "wasm32"))]
+    connect_timeout: Option<u8>,
+    concurrency: usize,
+    retry: RetryPolicy,
+    #[cfg(not(target_arch = "wasm32"))]
+    tls_pins: Vec<crate::TlsPin>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<reqwest::Proxy>,
+    headers: HeaderMap,
+    client: Option<reqwest::Client>,
+    #[cfg(not(target_arch = "wasm32"))]
+    cache_dir: Option<std::path::PathBuf>,
 }
 
 impl EsploraClientBuilder {
@@ -572,6 +961,18 @@ impl EsploraClientBuilder {
             waterfalls: false,
             network,
             timeout: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            connect_timeout: None,
+            concurrency: 1,
+            retry: RetryPolicy::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            tls_pins: vec![],
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
+            headers: HeaderMap::new(),
+            client: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            cache_dir: None,
         }
     }
 
@@ -592,11 +993,116 @@ impl EsploraClientBuilder {
         self
     }
 
+    /// Set a timeout in seconds to connect to the server, requests may last longer
+    ///
+    /// The [`EsploraClientBuilder::timeout()`] applies to the whole request, including the
+    /// connection.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, timeout: u8) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how the requests are retried when the server rate limits them (HTTP status 429),
+    /// is temporarily unavailable (503) or, if [`RetryPolicy::transient`], doesn't answer in time
+    ///
+    /// By default requests are retried 7 times, waiting from 1 second up to 64 seconds, and
+    /// requests failing for timeouts or connection errors are not retried.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set the maximum number of requests in flight, 1 by default
+    ///
+    /// Script histories, transactions and headers are requested concurrently, which makes the
+    /// scan of wallets with many scripts and transactions much faster. Public servers may rate
+    /// limit clients doing too many concurrent requests.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Accept only server certificates matching at least one of the given pins
+    ///
+    /// The certificate must also be valid for the server domain and plain HTTP requests are
+    /// refused.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls_pins(mut self, pins: Vec<crate::TlsPin>) -> Self {
+        self.tls_pins = pins;
+        self
+    }
+
+    /// Send the requests through the proxy at `url`, for instance to connect through Tor
+    ///
+    /// HTTP proxies, such as the Tor `HTTPTunnelPort`, are always supported. SOCKS5 proxies, such
+    /// as `socks5h://127.0.0.1:9050` which resolves the host names (including `.onion` ones)
+    /// through the proxy, require the `socks` feature of `reqwest`, which applications can enable
+    /// by depending on it; otherwise an error is returned.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, url: &str) -> Result<Self, Error> {
+        self.proxy = Some(reqwest::Proxy::all(url)?);
+        Ok(self)
+    }
+
+    /// Add a header to every request, for instance an API key for servers requiring
+    /// authentication
+    ///
+    /// Returns an error if the name or the value are not valid for an HTTP header.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, Error> {
+        let name =
+            HeaderName::from_str(name).map_err(|_| Error::InvalidHttpHeader(name.to_string()))?;
+        let mut value =
+            HeaderValue::from_str(value).map_err(|_| Error::InvalidHttpHeader(name.to_string()))?;
+        value.set_sensitive(true);
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Authenticate every request with the given bearer token
+    pub fn bearer_auth(self, token: &str) -> Result<Self, Error> {
+        self.header("Authorization", &format!("Bearer {token}"))
+    }
+
+    /// Use the given client to make the requests, for instance configured with client
+    /// certificates or custom root certificates
+    ///
+    /// The other options setting up the client, like [`EsploraClientBuilder::timeout()`], are
+    /// ignored, while the headers are added to the requests.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Cache the immutable responses of the server in the directory `dir`, so that wallets
+    /// opened again don't download the same transactions and headers
+    ///
+    /// Raw transactions and block headers are cached, after checking they match the requested
+    /// txid or block hash, thus the directory can be shared by clients of different servers.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cache_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
     /// Consume the builder and build a new [`EsploraClient`]
     pub fn build(self) -> EsploraClient {
-        let client = match self.timeout {
-            Some(timeout) => client_with_timeout(timeout),
-            None => reqwest::Client::new(),
+        let client = match (self.client.clone(), self.timeout) {
+            (Some(client), _) => client,
+            #[cfg(not(target_arch = "wasm32"))]
+            _ if !self.tls_pins.is_empty()
+                || self.proxy.is_some()
+                || self.connect_timeout.is_some() =>
+            {
+                client_with_options(
+                    &self.tls_pins,
+                    self.timeout,
+                    self.connect_timeout,
+                    self.proxy.clone(),
+                )
+            }
+            (None, Some(timeout)) => client_with_timeout(timeout),
+            (None, None) => reqwest::Client::new(),
         };
         EsploraClient {
             client,
@@ -605,8 +1111,16 @@ impl EsploraClientBuilder {
             broadcast_url: format!("{}/tx", self.base_url),
             waterfalls: self.waterfalls,
             waterfalls_server_recipient: None,
+            waterfalls_encrypted_descriptors: HashMap::new(),
             waterfalls_avoid_encryption: false,
             network: self.network,
+            concurrency: self.concurrency,
+            retry: self.retry,
+            headers: self.headers,
+            #[cfg(not(target_arch = "wasm32"))]
+            cache: self.cache_dir.map(EsploraCache::new),
+            #[cfg(target_arch = "wasm32")]
+            cache: None,
         }
     }
 }
@@ -619,16 +1133,71 @@ fn client_with_timeout(timeout: u8) -> reqwest::Client {
         .expect("Failed to create client") // TODO: handle error but note that this is equivalent to the new() which panics
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn client_with_options(
+    pins: &[crate::TlsPin],
+    timeout: Option<u8>,
+    connect_timeout: Option<u8>,
+    proxy: Option<reqwest::Proxy>,
+) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if !pins.is_empty() {
+        let mut config =
+            crate::clients::tls::pinned_client_config(pins, true).expect("web PKI roots are valid");
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        builder = builder.use_preconfigured_tls(config).https_only(true);
+    }
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(std::time::Duration::from_secs(timeout as u64));
+    }
+    if let Some(timeout) = connect_timeout {
+        builder = builder.connect_timeout(std::time::Duration::from_secs(timeout as u64));
+    }
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy);
+    }
+    builder.build().expect("Failed to create client")
+}
+
 #[cfg(target_arch = "wasm32")]
 fn client_with_timeout(timeout: u8) -> reqwest::Client {
     // https://github.com/seanmonstar/reqwest/issues/1135
     reqwest::Client::new()
 }
 
-async fn get_with_retry(client: &reqwest::Client, url: &str) -> Result<Response, Error> {
+async fn get_with_retry(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    retry: &RetryPolicy,
+    url: &str,
+) -> Result<Response, Error> {
+    send_with_retry(retry, url, || client.get(url).headers(headers.clone())).await
+}
+
+/// Send the request built by `request`, retrying with backoff if the server is rate limiting
+/// (HTTP status 429) or temporarily unavailable (503), or if the request fails for transient
+/// reasons and [`RetryPolicy::transient`] is set
+///
+/// The `Retry-After` header of the server, if any, is honored up to
+/// [`RetryPolicy::max_backoff`].
+async fn send_with_retry(
+    retry: &RetryPolicy,
+    url: &str,
+    request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<Response, Error> {
     let mut attempt = 0;
     loop {
-        let response = client.get(url).send().await?;
+        let response = match request().send().await {
+            Ok(response) => response,
+            Err(e) if retry.transient && is_transient(&e) && attempt < retry.max_retries as u32 => {
+                let wait = retry.wait(attempt, None);
+                log::debug!("{url} failed with {e}, waiting {wait:?}");
+                async_sleep(wait.as_millis() as i32).await;
+                attempt += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         let level = if response.status() == 200 {
             log::Level::Trace
@@ -646,15 +1215,15 @@ async fn get_with_retry(client: &reqwest::Client, url: &str) -> Result<Response,
         // 429 Too many requests
         // 503 Service Temporarily Unavailable
         if response.status() == 429 || response.status() == 503 {
-            if attempt > 6 {
-                log::warn!("{url} tried 6 times, failing");
+            if attempt >= retry.max_retries as u32 {
+                log::warn!("{url} tried {attempt} times, failing");
                 return Err(Error::Generic("Too many retry".to_string()));
             }
-            let secs = 1 << attempt;
+            let wait = retry.wait(attempt, retry_after(&response));
 
-            log::debug!("{url} waiting {secs}");
+            log::debug!("{url} waiting {wait:?}");
 
-            async_sleep(secs * 1000).await;
+            async_sleep(wait.as_millis() as i32).await;
             attempt += 1;
         } else {
             return Ok(response);
@@ -662,6 +1231,29 @@ async fn get_with_retry(client: &reqwest::Client, url: &str) -> Result<Response,
     }
 }
 
+/// The wait requested by the server with the `Retry-After` header, if expressed in seconds
+fn retry_after(response: &Response) -> Option<std::time::Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let secs: u64 = value.to_str().ok()?.trim().parse().ok()?;
+    Some(std::time::Duration::from_secs(secs))
+}
+
+/// Whether the HTTP status means the server doesn't have the "waterfalls" endpoints
+fn waterfalls_unsupported(status: u16) -> bool {
+    // 404 Not Found
+    // 501 Not Implemented
+    status == 404 || status == 501
+}
+
+/// Whether the request failed for a reason that may not happen again, like a timeout
+fn is_transient(e: &reqwest::Error) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    if e.is_connect() {
+        return true;
+    }
+    e.is_timeout()
+}
+
 // based on https://users.rust-lang.org/t/rust-wasm-async-sleeping-for-100-milli-seconds-goes-up-to-1-minute/81177
 // TODO remove/handle/justify unwraps
 #[cfg(target_arch = "wasm32")]
@@ -705,6 +1297,58 @@ struct Status {
     block_hash: Option<BlockHash>,
 }
 
+impl Status {
+    fn confirmed(&self) -> bool {
+        self.block_height.is_some()
+    }
+}
+
+/// The number of confirmed transactions returned by esplora in a page of a script history
+const ESPLORA_CHAIN_PAGE_SIZE: usize = 25;
+
+/// The confirmed wallet transactions in the store, grouped by the scripts they spend or fund
+fn known_script_txs(store: &Store) -> HashMap<Script, HashMap<Txid, Height>> {
+    let all_txs = &store.cache.all_txs;
+    let mut known: HashMap<Script, HashMap<Txid, Height>> = HashMap::new();
+    for (txid, height) in store.cache.heights.iter() {
+        let (Some(height), Some(tx)) = (height, all_txs.get(txid)) else {
+            continue;
+        };
+        let prevouts = tx.input.iter().filter_map(|input| {
+            let prev = all_txs.get(&input.previous_output.txid)?;
+            prev.output.get(input.previous_output.vout as usize)
+        });
+        for output in tx.output.iter().chain(prevouts) {
+            known
+                .entry(output.script_pubkey.clone())
+                .or_default()
+                .insert(*txid, *height);
+        }
+    }
+    known
+}
+
+/// The height of the oldest transaction in `txs` confirmed at the same height it is `known` at
+fn oldest_known_height(txs: &[EsploraTx], known: &HashMap<Txid, Height>) -> Option<Height> {
+    txs.iter()
+        .filter_map(|tx| {
+            let height = *known.get(&tx.txid)?;
+            (tx.status.block_height == Some(height as i32)).then_some(height)
+        })
+        .min()
+}
+
+/// The hash of the script used by esplora (and electrum) to identify it, hex encoded
+fn script_hash(script: &Script) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+    hash.to_hex()
+}
+
+/// The maximum number of scripts per descriptor returned in a "waterfalls" page, the first index
+/// of a page is `page * WATERFALLS_PAGE_SIZE`
+const WATERFALLS_PAGE_SIZE: u32 = 1000;
+
 /// The result of a "waterfalls" descriptor endpoint call
 #[derive(Deserialize)]
 struct WaterfallsResult {
@@ -734,17 +1378,53 @@ fn encrypt(plaintext: &str, recipient: Recipient) -> Result<String, Error> {
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, str::FromStr};
+    use std::{collections::HashMap, str::FromStr, time::Duration};
 
-    use crate::{clients::asyncr::async_sleep, ElementsNetwork};
+    use crate::{clients::asyncr::async_sleep, ElementsNetwork, RetryPolicy};
 
     use super::EsploraClient;
     use elements::{encode::Decodable, BlockHash};
+    use reqwest::header::HeaderMap;
+
+    /// Start a mock esplora server answering every request with `route`, returning its url
+    ///
+    /// `route` receives the path and the whole request, it returns the status, optionally
+    /// followed by headers, and the body.
+    fn mock_esplora<S, B>(route: impl Fn(&str, &str) -> (S, B) + Send + 'static) -> String
+    where
+        S: AsRef<str>,
+        B: AsRef<[u8]>,
+    {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 8192];
+                let n = stream.read(&mut buf).unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                let (status, body) = route(path, &request);
+                let (status, body) = (status.as_ref(), body.as_ref());
+                let head = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        url
+    }
 
     async fn get_block(base_url: &str, hash: BlockHash) -> elements::Block {
         let url = format!("{}/block/{}/raw", base_url, hash);
         let client = reqwest::Client::new();
-        let response = super::get_with_retry(&client, &url).await.unwrap();
+        let response = super::get_with_retry(&client, &HeaderMap::new(), &Default::default(), &url)
+            .await
+            .unwrap();
         elements::Block::consensus_decode(&response.bytes().await.unwrap()[..]).unwrap()
     }
 
@@ -763,6 +1443,461 @@ mod tests {
         async_sleep(1).await;
     }
 
+    #[tokio::test]
+    async fn test_esplora_proxy() {
+        let builder = || {
+            super::EsploraClientBuilder::new(
+                "http://example.com/liquid/api",
+                ElementsNetwork::Liquid,
+            )
+        };
+        assert!(builder().proxy("not a url").is_err());
+
+        // nothing listens on the proxy port, the request would succeed without the proxy
+        let mut client = builder().proxy("http://127.0.0.1:1").unwrap().build();
+        assert!(client.tip().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_esplora_headers() {
+        let builder = || {
+            super::EsploraClientBuilder::new(
+                "http://example.com/liquid/api",
+                ElementsNetwork::Liquid,
+            )
+        };
+        assert!(builder().header("not a name", "value").is_err());
+        assert!(builder().header("X-Api-Key", "not\na value").is_err());
+
+        // a server answering only to authenticated requests
+        let url = mock_esplora(|_, request| {
+            let request = request.to_lowercase();
+            let authorized = request.contains("authorization: bearer token")
+                && request.contains("x-api-key: key");
+            if authorized {
+                ("200 OK", "{\"1\": 0.1}")
+            } else {
+                ("401 Unauthorized", "")
+            }
+        });
+
+        let client = super::EsploraClientBuilder::new(&url, ElementsNetwork::Liquid).build();
+        assert!(client.fee_estimates().await.is_err());
+
+        let client = super::EsploraClientBuilder::new(&url, ElementsNetwork::Liquid)
+            .client(reqwest::Client::new())
+            .bearer_auth("token")
+            .unwrap()
+            .header("X-Api-Key", "key")
+            .unwrap()
+            .build();
+        assert_eq!(client.fee_estimates().await.unwrap()[&1], 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_waterfalls_pagination() {
+        use crate::{Chain, Wollet, WolletDescriptor};
+
+        let descriptor: WolletDescriptor =
+            lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let wollet =
+            Wollet::without_persist(ElementsNetwork::LiquidTestnet, descriptor.clone()).unwrap();
+        let descs: Vec<String> = descriptor
+            .as_single_descriptors()
+            .unwrap()
+            .into_iter()
+            .map(|d| d.descriptor.to_string())
+            .collect();
+
+        // the external chain has a full first page and a transaction in the second one
+        let tx = |i: u8| format!(r#"[{{"txid":"{:064x}","height":0,"block_hash":null}}]"#, i);
+        let url = mock_esplora(move |path, _| {
+            let (page, external, internal) = if path.contains("page=1") {
+                let mut external = vec!["[]".to_string(); 20];
+                external[5] = tx(2);
+                (1, external, vec![])
+            } else {
+                let mut external = vec!["[]".to_string(); 1000];
+                external[0] = tx(1);
+                let mut internal = vec!["[]".to_string(); 20];
+                internal[3] = tx(3);
+                (0, external, internal)
+            };
+            let body = format!(
+                r#"{{"txs_seen":{{"{}":[{}],"{}":[{}]}},"page":{page},"tip":null}}"#,
+                descs[0],
+                external.join(","),
+                descs[1],
+                internal.join(","),
+            );
+            ("200 OK", body)
+        });
+
+        let mut client = super::EsploraClientBuilder::new(&url, ElementsNetwork::LiquidTestnet)
+            .waterfalls(true)
+            .build();
+        client.avoid_encryption();
+        let data = client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap();
+        assert_eq!(data.last_unused[Chain::External], 1006);
+        assert_eq!(data.last_unused[Chain::Internal], 4);
+        assert_eq!(data.txid_height.len(), 3);
+        assert_eq!(data.scripts.len(), 1020 + 20);
+    }
+
+    #[tokio::test]
+    async fn test_waterfalls_unsupported() {
+        use crate::{Capability, Error, Wollet, WolletDescriptor};
+
+        // a plain esplora server
+        let url = mock_esplora(|_, _| ("404 Not Found", ""));
+
+        let descriptor: WolletDescriptor =
+            lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let wollet =
+            Wollet::without_persist(ElementsNetwork::LiquidTestnet, descriptor.clone()).unwrap();
+        for avoid_encryption in [true, false] {
+            let mut client = super::EsploraClientBuilder::new(&url, ElementsNetwork::LiquidTestnet)
+                .waterfalls(true)
+                .build();
+            client.waterfalls_avoid_encryption = avoid_encryption;
+            assert!(client.capabilities().contains(&Capability::Waterfalls));
+            let err = client
+                .get_history_waterfalls(&descriptor, &wollet)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::WaterfallsUnsupported(_)));
+            assert!(client.capabilities().is_empty());
+        }
+
+        // a waterfalls server returning an invalid response is not a plain esplora server
+        let url = mock_esplora(|_, _| ("200 OK", "not json"));
+        let mut client = super::EsploraClientBuilder::new(&url, ElementsNetwork::LiquidTestnet)
+            .waterfalls(true)
+            .build();
+        client.avoid_encryption();
+        let err = client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::JsonFrom(_)), "{err:?}");
+        assert!(client.capabilities().contains(&Capability::Waterfalls));
+    }
+
+    #[tokio::test]
+    async fn test_waterfalls_recipient_rotation() {
+        use crate::{Error, Wollet, WolletDescriptor};
+        use age::x25519::Identity;
+        use base64::Engine;
+        use std::sync::{Arc, Mutex};
+
+        let old_identity = Identity::generate();
+        let identity = Arc::new(Mutex::new(Identity::generate()));
+        let descriptors = Arc::new(Mutex::new(vec![]));
+        let (server_identity, server_descriptors) = (identity.clone(), descriptors.clone());
+        let url = mock_esplora(move |path, _| {
+            let identity = server_identity.lock().unwrap().clone();
+            if path == "/v1/server_recipient" {
+                return ("200 OK", identity.to_public().to_string());
+            }
+            let query = path.split_once('?').unwrap().1;
+            let (_, desc) = url::form_urlencoded::parse(query.as_bytes())
+                .find(|(k, _)| k == "descriptor")
+                .unwrap();
+            server_descriptors.lock().unwrap().push(desc.to_string());
+            let ciphertext = base64::prelude::BASE64_STANDARD_NO_PAD
+                .decode(desc.as_bytes())
+                .unwrap();
+            match age::decrypt(&identity, &ciphertext) {
+                Ok(_) => ("200 OK", r#"{"txs_seen":{},"page":0}"#.to_string()),
+                Err(_) => ("400 Bad Request", "Invalid descriptor".to_string()),
+            }
+        });
+
+        let descriptor: WolletDescriptor =
+            lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let wollet =
+            Wollet::without_persist(ElementsNetwork::LiquidTestnet, descriptor.clone()).unwrap();
+        let mut client = super::EsploraClientBuilder::new(&url, ElementsNetwork::LiquidTestnet)
+            .waterfalls(true)
+            .build();
+
+        // the ciphertext is reused by the following scans
+        client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap();
+        client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap();
+        let sent = descriptors.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0], sent[1]);
+
+        // a recipient set manually is replaced when the server can't decrypt the descriptor
+        client.set_waterfalls_server_recipient(old_identity.to_public());
+        client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap();
+        let server_recipient = identity.lock().unwrap().to_public().to_string();
+        let recipient = client.waterfalls_server_recipient().await.unwrap();
+        assert_eq!(recipient.to_string(), server_recipient);
+        assert_eq!(descriptors.lock().unwrap().len(), 4);
+
+        // the server rotates its key
+        *identity.lock().unwrap() = Identity::generate();
+        client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap();
+        let server_recipient = identity.lock().unwrap().to_public().to_string();
+        let recipient = client.waterfalls_server_recipient().await.unwrap();
+        assert_eq!(recipient.to_string(), server_recipient);
+        let sent = descriptors.lock().unwrap().clone();
+        assert_eq!(sent.len(), 6);
+        assert_ne!(sent[4], sent[5]);
+
+        // a request rejected for other reasons is not retried
+        client.set_waterfalls_server_recipient(identity.lock().unwrap().to_public());
+        client.waterfalls_encrypted_descriptors.clear();
+        client.waterfalls_encrypted_descriptors.insert(
+            descriptor.bitcoin_descriptor_without_key_origin(),
+            "AA".into(),
+        );
+        let err = client
+            .get_history_waterfalls(&descriptor, &wollet)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::WaterfallsRequestRejected(_)));
+        assert_eq!(descriptors.lock().unwrap().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_esplora_cache_dir() {
+        use elements::{encode::serialize, hashes::Hash, hex::ToHex};
+        use std::sync::{Arc, Mutex};
+
+        let update =
+            crate::Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        let tx = update.new_txs.txs[0].1.clone();
+        let txid = tx.txid();
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        let block_hash = header.block_hash();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let (tx_bytes, header_hex) = (serialize(&tx), serialize(&header).to_hex());
+        let server_requests = requests.clone();
+        let url = mock_esplora(move |path, _| {
+            server_requests.lock().unwrap().push(path.to_string());
+            let body = if path == format!("/block/{block_hash}/header") {
+                header_hex.as_bytes().to_vec()
+            } else {
+                // the transaction, also if not matching the requested txid
+                tx_bytes.clone()
+            };
+            ("200 OK", body)
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let client = || {
+            super::EsploraClientBuilder::new(&url, ElementsNetwork::Liquid)
+                .cache_dir(dir.path())
+                .build()
+        };
+        let height_blockhash = HashMap::from([(2_963_520, block_hash)]);
+        for _ in 0..2 {
+            // a client of a wallet opened again
+            let client = client();
+            assert_eq!(
+                client.get_transactions(&[txid]).await.unwrap(),
+                vec![tx.clone()]
+            );
+            let headers = client
+                .get_headers(&[2_963_520], &height_blockhash)
+                .await
+                .unwrap();
+            assert_eq!(headers, vec![header.clone()]);
+        }
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        // responses not matching the request are not cached
+        let other = elements::Txid::all_zeros();
+        for _ in 0..2 {
+            client().get_transaction(other).await.unwrap();
+        }
+        assert_eq!(requests.lock().unwrap().len(), 4);
+
+        // without the cache every request reaches the server
+        let client = EsploraClient::new(ElementsNetwork::Liquid, &url);
+        client.get_transaction(txid).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_tx_status() {
+        use crate::{clients::asyncr::AsyncWollet, Error, TxStatus, Wollet, WolletDescriptor};
+        use elements::{encode::serialize, hashes::Hash, hex::ToHex, Txid};
+
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        let confirmed = Txid::from_byte_array([1; 32]);
+        let unconfirmed = Txid::from_byte_array([2; 32]);
+        let tip_hash = header.block_hash();
+        let header_hex = serialize(&header).to_hex();
+        let url = mock_esplora(move |path, _| {
+            if path == format!("/tx/{confirmed}/status") {
+                let body = r#"{"confirmed":true,"block_height":2963511}"#;
+                ("200 OK", body.to_string())
+            } else if path == format!("/tx/{unconfirmed}/status") {
+                ("200 OK", r#"{"confirmed":false}"#.to_string())
+            } else if path == "/blocks/tip/hash" {
+                ("200 OK", tip_hash.to_string())
+            } else if path == format!("/block/{tip_hash}/header") {
+                ("200 OK", header_hex.clone())
+            } else {
+                ("404 Not Found", "Transaction not found".to_string())
+            }
+        });
+
+        let client = EsploraClient::new(ElementsNetwork::Liquid, &url);
+        assert_eq!(
+            client.tx_status(&confirmed).await.unwrap(),
+            TxStatus::Confirmed { height: 2963511 }
+        );
+        assert_eq!(
+            client.tx_status(&unconfirmed).await.unwrap(),
+            TxStatus::Unconfirmed
+        );
+        let not_found = Txid::all_zeros();
+        assert_eq!(
+            client.tx_status(&not_found).await.unwrap(),
+            TxStatus::NotFound
+        );
+
+        let descriptor: WolletDescriptor =
+            lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let wollet = Wollet::without_persist(ElementsNetwork::Liquid, descriptor).unwrap();
+        let mut wollet = AsyncWollet::new(wollet, client);
+        let status = wollet
+            .wait_for_confirmation(&confirmed, 10, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(status, TxStatus::Confirmed { height: 2963511 });
+        wollet
+            .wait_for_confirmation(&unconfirmed, 0, Duration::ZERO)
+            .await
+            .unwrap();
+        let err = wollet
+            .wait_for_confirmation(&confirmed, 11, Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ConfirmationTimeout(txid) if txid == confirmed));
+    }
+
+    #[tokio::test]
+    async fn test_get_with_retry() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // a server rate limiting the first request and unavailable for the second one
+        let requests = AtomicUsize::new(0);
+        let url = mock_esplora(move |_, _| {
+            let status = match requests.fetch_add(1, Ordering::Relaxed) % 3 {
+                0 => "429 Too Many Requests\r\nRetry-After: 60",
+                1 => "503 Service Unavailable",
+                _ => "200 OK",
+            };
+            (status, "")
+        });
+        let url = format!("{url}/");
+
+        let client = reqwest::Client::new();
+        let retry = |max_retries| RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            jitter: false,
+            transient: false,
+        };
+        let response = super::get_with_retry(&client, &HeaderMap::new(), &retry(2), &url)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let err = super::get_with_retry(&client, &HeaderMap::new(), &retry(1), &url)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Too many retry");
+    }
+
+    #[tokio::test]
+    async fn test_script_history_pagination() {
+        use elements::Txid;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // a script with 2 unconfirmed and 30 confirmed transactions
+        let tx = |i: u8, height: Option<u32>| {
+            let txid = format!("{:064x}", i);
+            match height {
+                Some(h) => format!(
+                    r#"{{"txid":"{txid}","status":{{"confirmed":true,"block_height":{h},"block_hash":"{:064x}"}}}}"#,
+                    h
+                ),
+                None => format!(r#"{{"txid":"{txid}","status":{{"confirmed":false}}}}"#),
+            }
+        };
+        let chain_requests = Arc::new(AtomicUsize::new(0));
+        let server_chain_requests = chain_requests.clone();
+        let url = mock_esplora(move |path, _| {
+            let txs: Vec<String> = if path.contains("/txs/chain/") {
+                server_chain_requests.fetch_add(1, Ordering::Relaxed);
+                assert!(path.ends_with(&format!("{:064x}", 27u8)));
+                (28..33).map(|i| tx(i, Some(1000 - i as u32))).collect()
+            } else {
+                assert!(path.ends_with("/txs"));
+                (1..3)
+                    .map(|i| tx(i, None))
+                    .chain((3..28).map(|i| tx(i, Some(1000 - i as u32))))
+                    .collect()
+            };
+            ("200 OK", format!("[{}]", txs.join(",")))
+        });
+
+        let client = EsploraClient::new(ElementsNetwork::default_regtest(), &url);
+        let script =
+            elements::Script::from_str("001414fe45f2c2a2b7c00d0940d694a3b6af6c9bf165").unwrap();
+        let history = client.get_scripts_history(&[&script]).await.unwrap();
+        assert_eq!(history[0].len(), 32);
+        assert_eq!(history[0].iter().filter(|h| h.height > 0).count(), 30);
+        assert_eq!(chain_requests.load(Ordering::Relaxed), 1);
+
+        // a known transaction at a different height doesn't stop the pagination
+        let txid = |i: u8| Txid::from_str(&format!("{:064x}", i)).unwrap();
+        let mut known = HashMap::new();
+        known.insert(script.clone(), HashMap::from([(txid(10), 1)]));
+        let history = client
+            .get_scripts_history_known(&[&script], &known)
+            .await
+            .unwrap();
+        assert_eq!(history[0].len(), 32);
+        assert_eq!(chain_requests.load(Ordering::Relaxed), 2);
+
+        // the history older than a known transaction is taken from the known ones
+        let known_txs = [10, 28, 29, 30, 31, 32].map(|i| (txid(i), 1000 - i as u32));
+        known.insert(script.clone(), HashMap::from(known_txs));
+        let history = client
+            .get_scripts_history_known(&[&script], &known)
+            .await
+            .unwrap();
+        assert_eq!(history[0].len(), 32);
+        assert_eq!(history[0].iter().filter(|h| h.height > 0).count(), 30);
+        assert_eq!(chain_requests.load(Ordering::Relaxed), 2);
+        let from_known = history[0].iter().filter(|h| h.block_hash.is_none());
+        assert_eq!(from_known.filter(|h| h.height > 0).count(), 5);
+    }
+
     #[ignore]
     #[tokio::test]
     async fn esplora_wasm_testnet() {
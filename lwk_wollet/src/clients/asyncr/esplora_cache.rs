@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::PathBuf;
+
+use elements::encode::{deserialize, serialize, Decodable, Encodable};
+use elements::hashes::{sha256, Hash};
+use elements::hex::ToHex;
+
+/// A disk cache of the immutable responses of an esplora server, see
+/// [`super::EsploraClientBuilder::cache_dir()`]
+///
+/// The responses are keyed by the path of the resource, such as `tx/<txid>/raw`, and stored
+/// consensus encoded. Callers must verify the responses before inserting them, since the cache is
+/// shared by the servers used with the same directory.
+#[derive(Debug, Clone)]
+pub(crate) struct EsploraCache {
+    dir: PathBuf,
+}
+
+impl EsploraCache {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(sha256::Hash::hash(key.as_bytes()).to_hex())
+    }
+
+    /// The cached value for `key`, if any and not corrupted
+    pub(crate) fn get<T: Decodable>(&self, key: &str) -> Option<T> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        deserialize(&bytes).ok()
+    }
+
+    /// Cache `value` for `key`, a failure only causes the value to be downloaded again
+    pub(crate) fn insert<T: Encodable>(&self, key: &str, value: &T) {
+        if let Err(e) = self.try_insert(key, value) {
+            log::warn!("cannot cache {key}: {e}");
+        }
+    }
+
+    fn try_insert<T: Encodable>(&self, key: &str, value: &T) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        // concurrent readers never see a partially written file
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serialize(value))?;
+        fs::rename(tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::BlockHeader;
+
+    use super::EsploraCache;
+
+    #[test]
+    fn test_esplora_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = EsploraCache::new(dir.path().join("cache"));
+        let header = lwk_test_util::liquid_block_header_2_963_520();
+        let key = format!("block/{}/header", header.block_hash());
+        assert!(cache.get::<BlockHeader>(&key).is_none());
+
+        cache.insert(&key, &header);
+        assert_eq!(cache.get::<BlockHeader>(&key), Some(header.clone()));
+        assert!(cache.get::<BlockHeader>("other").is_none());
+
+        // corrupted values are ignored
+        std::fs::write(cache.path(&key), [1, 2, 3]).unwrap();
+        assert!(cache.get::<BlockHeader>(&key).is_none());
+
+        // another client using the same directory
+        let other = EsploraCache::new(dir.path().join("cache"));
+        other.insert(&key, &header);
+        assert_eq!(cache.get::<BlockHeader>(&key), Some(header));
+    }
+}
@@ -0,0 +1,325 @@
+//! Push notifications from the WebSocket interface of esplora servers running the mempool
+//! backend, such as `wss://liquid.network/api/v1/ws`
+//!
+//! Browsers can't open raw TCP connections, so Electrum subscriptions are not available in WASM
+//! environments. The WebSocket interface notifies new blocks and the transactions of the tracked
+//! addresses, so that the wallet can be scanned only when something changed.
+
+use std::collections::HashMap;
+
+use elements::{Address, BlockHash, Txid};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{Chain, Error, Wollet};
+
+/// A notification received from the esplora WebSocket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EsploraWsNotification {
+    /// A new block was found
+    Block {
+        /// The height of the block
+        height: u32,
+
+        /// The hash of the block
+        block_hash: BlockHash,
+    },
+
+    /// Transactions involving a tracked address entered the mempool, were confirmed or were
+    /// removed from the mempool
+    AddressActivity {
+        /// The tracked address, as given to the server
+        address: String,
+
+        /// The transactions involving the address
+        txids: Vec<Txid>,
+    },
+}
+
+/// The unconfidential addresses of the wallet to track: the used ones and the following
+/// [`crate::Config::gap_limit()`] ones of each chain
+///
+/// The server sees which addresses belong to the wallet, as with the esplora scan.
+pub fn wollet_addresses(wollet: &Wollet) -> Result<Vec<Address>, Error> {
+    let gap_limit = wollet.config().gap_limit();
+    let mut addresses = vec![];
+    for chain in [Chain::External, Chain::Internal] {
+        for index in 0..wollet.last_unused_index(chain) + gap_limit {
+            let address = match chain {
+                Chain::External => wollet.address(Some(index))?,
+                Chain::Internal => wollet.change(Some(index))?,
+            };
+            let address = address.address().to_unconfidential();
+            // without a multipath descriptor both chains derive the same addresses
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+    Ok(addresses)
+}
+
+/// The message asking the server to notify the new blocks
+///
+/// With these messages and [`parse_notifications()`] applications can use the notifications
+/// with any WebSocket implementation, `EsploraWsClient` is available only in WASM.
+pub fn want_blocks_message() -> String {
+    json!({"action": "want", "data": ["blocks"]}).to_string()
+}
+
+/// The message asking the server to notify the transactions of the given addresses
+///
+/// It replaces the addresses previously tracked.
+pub fn track_addresses_message(addresses: &[Address]) -> String {
+    let addresses: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+    json!({ "track-addresses": addresses }).to_string()
+}
+
+/// Parse a message of the server, ignoring the fields not related to the notifications
+pub fn parse_notifications(text: &str) -> Result<Vec<EsploraWsNotification>, Error> {
+    let message: WsMessage = serde_json::from_str(text)?;
+    let mut notifications = vec![];
+    if let Some(block) = message.block {
+        notifications.push(EsploraWsNotification::Block {
+            height: block.height,
+            block_hash: block.id,
+        });
+    }
+    if let Some(addresses) = message.multi_address_transactions {
+        let mut addresses: Vec<_> = addresses.into_iter().collect();
+        addresses.sort_by(|a, b| a.0.cmp(&b.0));
+        for (address, txs) in addresses {
+            let txids: Vec<Txid> = txs
+                .mempool
+                .iter()
+                .chain(txs.confirmed.iter())
+                .chain(txs.removed.iter())
+                .map(|tx| tx.txid)
+                .collect();
+            if !txids.is_empty() {
+                notifications.push(EsploraWsNotification::AddressActivity { address, txids });
+            }
+        }
+    }
+    Ok(notifications)
+}
+
+#[derive(Deserialize)]
+struct WsMessage {
+    block: Option<WsBlock>,
+    #[serde(rename = "multi-address-transactions")]
+    multi_address_transactions: Option<HashMap<String, WsAddressTxs>>,
+}
+
+#[derive(Deserialize)]
+struct WsBlock {
+    id: BlockHash,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct WsAddressTxs {
+    #[serde(default)]
+    mempool: Vec<WsTx>,
+    #[serde(default)]
+    confirmed: Vec<WsTx>,
+    #[serde(default)]
+    removed: Vec<WsTx>,
+}
+
+#[derive(Deserialize)]
+struct WsTx {
+    txid: Txid,
+}
+
+#[cfg(target_arch = "wasm32")]
+pub use wasm::EsploraWsClient;
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use std::collections::VecDeque;
+
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    use web_sys::{Event, MessageEvent, WebSocket};
+
+    use super::{
+        parse_notifications, track_addresses_message, want_blocks_message, wollet_addresses,
+        EsploraWsNotification,
+    };
+    use crate::{Error, Wollet};
+
+    enum WsEvent {
+        Open,
+        Message(String),
+        Closed,
+    }
+
+    /// A client receiving the notifications of the esplora WebSocket, see the [module
+    /// documentation](super)
+    ///
+    /// A typical loop waits for [`EsploraWsClient::next_notification()`] and then scans the
+    /// wallet with the [`crate::asyncr::EsploraClient`].
+    pub struct EsploraWsClient {
+        ws: WebSocket,
+        receiver: mpsc::UnboundedReceiver<WsEvent>,
+
+        /// Notifications parsed from a message and not yet returned
+        pending: VecDeque<EsploraWsNotification>,
+
+        _on_open: Closure<dyn FnMut(Event)>,
+        _on_message: Closure<dyn FnMut(MessageEvent)>,
+        _on_close: Closure<dyn FnMut(Event)>,
+    }
+
+    impl EsploraWsClient {
+        /// Connect to the WebSocket at `url`, for instance `wss://liquid.network/api/v1/ws`
+        pub async fn connect(url: &str) -> Result<Self, Error> {
+            let ws = WebSocket::new(url).map_err(js_error)?;
+            let (sender, receiver) = mpsc::unbounded();
+
+            let open_sender = sender.clone();
+            let on_open = Closure::<dyn FnMut(Event)>::new(move |_| {
+                let _ = open_sender.unbounded_send(WsEvent::Open);
+            });
+            ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+            let message_sender = sender.clone();
+            let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+                if let Some(text) = e.data().as_string() {
+                    let _ = message_sender.unbounded_send(WsEvent::Message(text));
+                }
+            });
+            ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+            let on_close = Closure::<dyn FnMut(Event)>::new(move |_| {
+                let _ = sender.unbounded_send(WsEvent::Closed);
+            });
+            ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+            let mut client = Self {
+                ws,
+                receiver,
+                pending: VecDeque::new(),
+                _on_open: on_open,
+                _on_message: on_message,
+                _on_close: on_close,
+            };
+            match client.receiver.next().await {
+                Some(WsEvent::Open) => Ok(client),
+                _ => Err(closed()),
+            }
+        }
+
+        /// Ask to be notified of the new blocks
+        pub fn subscribe_blocks(&self) -> Result<(), Error> {
+            self.send(&want_blocks_message())
+        }
+
+        /// Ask to be notified of the transactions of the wallet, see [`super::wollet_addresses()`]
+        ///
+        /// Call it again after a scan finding new transactions, so that the new addresses are
+        /// tracked.
+        pub fn track_wollet(&self, wollet: &Wollet) -> Result<(), Error> {
+            self.send(&track_addresses_message(&wollet_addresses(wollet)?))
+        }
+
+        /// Wait for the next notification
+        ///
+        /// A message of the server may contain more notifications, they are returned one at a
+        /// time before reading the following message. Returns an error if the connection was
+        /// closed.
+        pub async fn next_notification(&mut self) -> Result<EsploraWsNotification, Error> {
+            loop {
+                if let Some(n) = self.pending.pop_front() {
+                    return Ok(n);
+                }
+                match self.receiver.next().await {
+                    Some(WsEvent::Message(text)) => {
+                        self.pending.extend(parse_notifications(&text)?);
+                    }
+                    Some(WsEvent::Open) => (),
+                    Some(WsEvent::Closed) | None => return Err(closed()),
+                }
+            }
+        }
+
+        fn send(&self, message: &str) -> Result<(), Error> {
+            self.ws.send_with_str(message).map_err(js_error)
+        }
+    }
+
+    impl Drop for EsploraWsClient {
+        fn drop(&mut self) {
+            self.ws.set_onopen(None);
+            self.ws.set_onmessage(None);
+            self.ws.set_onclose(None);
+            let _ = self.ws.close();
+        }
+    }
+
+    fn js_error(e: wasm_bindgen::JsValue) -> Error {
+        Error::Generic(format!("{e:?}"))
+    }
+
+    fn closed() -> Error {
+        Error::Generic("WebSocket closed".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::{hashes::Hash, BlockHash, Txid};
+
+    use super::{parse_notifications, track_addresses_message, want_blocks_message};
+    use super::{wollet_addresses, EsploraWsNotification};
+    use crate::{ElementsNetwork, Wollet, WolletDescriptor};
+
+    #[test]
+    fn test_esplora_ws_messages() {
+        assert_eq!(
+            want_blocks_message(),
+            r#"{"action":"want","data":["blocks"]}"#
+        );
+
+        let descriptor: WolletDescriptor =
+            lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, descriptor).unwrap();
+        let addresses = wollet_addresses(&wollet).unwrap();
+        assert_eq!(addresses.len(), 40);
+        assert!(addresses.iter().all(|a| a.blinding_pubkey.is_none()));
+        let message = track_addresses_message(&addresses[..1]);
+        assert_eq!(
+            message,
+            format!(r#"{{"track-addresses":["{}"]}}"#, addresses[0])
+        );
+
+        let hash = BlockHash::all_zeros();
+        let block =
+            format!(r#"{{"block":{{"id":"{hash}","height":10,"tx_count":1}},"mempoolInfo":{{}}}}"#);
+        assert_eq!(
+            parse_notifications(&block).unwrap(),
+            vec![EsploraWsNotification::Block {
+                height: 10,
+                block_hash: hash
+            }]
+        );
+
+        let txid = Txid::all_zeros();
+        let activity = format!(
+            r#"{{"multi-address-transactions":{{"addr":{{"mempool":[{{"txid":"{txid}","fee":100}}],"confirmed":[],"removed":[]}},"empty":{{}}}}}}"#
+        );
+        assert_eq!(
+            parse_notifications(&activity).unwrap(),
+            vec![EsploraWsNotification::AddressActivity {
+                address: "addr".to_string(),
+                txids: vec![txid]
+            }]
+        );
+
+        assert!(parse_notifications(r#"{"pong":true}"#).unwrap().is_empty());
+        assert!(parse_notifications("not json").is_err());
+    }
+}
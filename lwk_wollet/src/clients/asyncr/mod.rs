@@ -1,7 +1,17 @@
 //! Asyncronous clients to fetch data from the Blockchain. Suitable to be used in WASM environments like in the browser.
 
 mod esplora;
+mod esplora_cache;
+mod esplora_ws;
+mod wollet;
 
 pub use esplora::async_sleep;
 pub use esplora::EsploraClient;
 pub use esplora::EsploraClientBuilder;
+#[cfg(target_arch = "wasm32")]
+pub use esplora_ws::EsploraWsClient;
+pub use esplora_ws::{
+    parse_notifications, track_addresses_message, want_blocks_message, wollet_addresses,
+    EsploraWsNotification,
+};
+pub use wollet::AsyncWollet;
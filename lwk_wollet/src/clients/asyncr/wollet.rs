@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use elements::{BlockHeader, Transaction, Txid};
+
+use crate::clients::{TxStatus, CONFIRMATION_POLL_INTERVAL};
+use crate::{Error, Wollet};
+
+use super::{async_sleep, EsploraClient};
+
+/// A [`Wollet`] synced with an async [`EsploraClient`]
+///
+/// Async applications should use this instead of the blocking clients, which run their own
+/// runtime and cannot be used from within another one.
+pub struct AsyncWollet {
+    wollet: Wollet,
+    client: EsploraClient,
+}
+
+impl AsyncWollet {
+    /// Create an async wallet syncing `wollet` with `client`
+    pub fn new(wollet: Wollet, client: EsploraClient) -> Self {
+        Self { wollet, client }
+    }
+
+    /// The inner wallet, to get balances, transactions and to create transactions
+    pub fn wollet(&self) -> &Wollet {
+        &self.wollet
+    }
+
+    /// The inner wallet, for instance to apply updates obtained elsewhere
+    pub fn wollet_mut(&mut self) -> &mut Wollet {
+        &mut self.wollet
+    }
+
+    /// The client used to sync the wallet
+    pub fn client(&mut self) -> &mut EsploraClient {
+        &mut self.client
+    }
+
+    /// Consume this and return the inner wallet and client
+    pub fn into_parts(self) -> (Wollet, EsploraClient) {
+        (self.wollet, self.client)
+    }
+
+    /// Scan the blockchain and apply the update to the wallet
+    ///
+    /// Returns whether the wallet changed. See [`EsploraClient::full_scan()`].
+    pub async fn full_scan(&mut self) -> Result<bool, Error> {
+        self.full_scan_to_index(0).await
+    }
+
+    /// Like [`AsyncWollet::full_scan()`] but scans at least up to the given derivation index,
+    /// see [`EsploraClient::full_scan_to_index()`]
+    pub async fn full_scan_to_index(&mut self, index: u32) -> Result<bool, Error> {
+        let mut changed = false;
+        // with a memory budget some transactions may be deferred to the next scans
+        while let Some(update) = self.client.full_scan_to_index(&self.wollet, index).await? {
+            let new_txs = !update.new_txs.txs.is_empty();
+            self.wollet.apply_update(update)?;
+            changed = true;
+            if !new_txs || self.wollet.config().memory_budget().is_none() {
+                break;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Scan the wallet for the given transaction and apply the update to the wallet
+    ///
+    /// Returns whether the wallet changed. See [`EsploraClient::scan_txid()`].
+    pub async fn scan_txid(&mut self, txid: Txid, max_index: u32) -> Result<bool, Error> {
+        match self.client.scan_txid(&self.wollet, txid, max_index).await? {
+            Some(update) => {
+                self.wollet.apply_update(update)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Broadcast a transaction
+    pub async fn broadcast(&self, tx: &Transaction) -> Result<Txid, Error> {
+        self.client.broadcast(tx).await
+    }
+
+    /// Wait until the transaction has at least `confirmations`, checking it every few seconds
+    ///
+    /// Async version of [`crate::blocking::BlockchainBackend::wait_for_confirmation()`], the
+    /// `timeout` doesn't include the time spent in the requests. The wallet is not scanned.
+    pub async fn wait_for_confirmation(
+        &mut self,
+        txid: &Txid,
+        confirmations: u32,
+        timeout: Duration,
+    ) -> Result<TxStatus, Error> {
+        let mut waited = Duration::ZERO;
+        loop {
+            let status = self.client.tx_status(txid).await?;
+            let tip_height = match status {
+                TxStatus::Confirmed { .. } => self.client.tip().await?.height,
+                _ => 0,
+            };
+            if status.is_confirmed(confirmations, tip_height) {
+                return Ok(status);
+            }
+            if waited >= timeout {
+                return Err(Error::ConfirmationTimeout(*txid));
+            }
+            let wait = (timeout - waited).min(CONFIRMATION_POLL_INTERVAL);
+            async_sleep(wait.as_millis() as i32).await;
+            waited += wait;
+        }
+    }
+
+    /// Get the header of the last block of the blockchain
+    ///
+    /// Differently from [`Wollet::tip()`], it's fetched from the server.
+    pub async fn tip(&mut self) -> Result<BlockHeader, Error> {
+        self.client.tip().await
+    }
+}
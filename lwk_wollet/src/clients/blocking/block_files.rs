@@ -0,0 +1,420 @@
+use crate::{clients::History, store::Height, ElementsNetwork, Error};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use elements::{encode::Decodable, Block, BlockHash, BlockHeader, Script, Transaction, Txid};
+
+use super::script_index::ScriptIndex;
+use super::BlockchainBackend;
+
+/// A [`BlockchainBackend`] reading the block files of a local Elements node
+///
+/// The client parses the `blkNNNNN.dat` files in the `blocks` directory of the node data
+/// directory, so wallets can be scanned without any indexing server and without RPC access to
+/// the node. Like the [`crate::ElementsRpcClient`], the histories of the scripts are kept in
+/// memory and the following scans only read the blocks appended to the files in the meantime.
+///
+/// The node mempool is not visible, so unconfirmed transactions are not returned and
+/// transactions cannot be broadcast: use another backend or the node RPC to broadcast.
+///
+/// The files are read while the node is running, so blocks written partially are read by the
+/// following scans. The files written by pruned nodes may lack the blocks preceding
+/// [`BlockFilesClient::with_scan_from()`], in this case the scan fails.
+pub struct BlockFilesClient {
+    blocks_dir: PathBuf,
+    network: ElementsNetwork,
+    scan_from: Height,
+    state: Mutex<BlockFiles>,
+}
+
+impl BlockFilesClient {
+    /// Create a client reading the block files in `blocks_dir`, for instance
+    /// `~/.elements/liquidv1/blocks`
+    pub fn new(network: ElementsNetwork, blocks_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            blocks_dir: blocks_dir.into(),
+            network,
+            scan_from: 0,
+            state: Mutex::new(BlockFiles::default()),
+        }
+    }
+
+    /// Scan the blockchain from the given height, instead of from the genesis block
+    ///
+    /// Transactions confirmed before `height` are not returned when used as a
+    /// [`BlockchainBackend`], use the height of the block preceding the wallet creation.
+    pub fn with_scan_from(mut self, height: Height) -> Self {
+        self.scan_from = height;
+        self
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, BlockFiles> {
+        // the state is consistent also if a scan panicked, at worst some blocks are read again
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn blk_path(&self, file: u32) -> PathBuf {
+        self.blocks_dir.join(format!("blk{file:05}.dat"))
+    }
+
+    /// Read the headers of the blocks appended to the files since the last call
+    fn read_new_blocks(&self, state: &mut BlockFiles) -> Result<(), Error> {
+        loop {
+            let (file, start) = state.next;
+            let path = self.blk_path(file);
+            let f = match File::open(&path) {
+                Ok(f) => f,
+                Err(e) if e.kind() == ErrorKind::NotFound && file == 0 => {
+                    return Err(Error::Generic(format!("{} not found", path.display())))
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let len = f.metadata()?.len();
+            let mut reader = BufReader::new(f);
+            reader.seek(SeekFrom::Start(start))?;
+            let mut offset = start;
+            while offset + 8 <= len {
+                let mut record = [0u8; 8];
+                reader.read_exact(&mut record)?;
+                let magic = <[u8; 4]>::try_from(&record[..4]).expect("4 bytes");
+                if magic == [0u8; 4] {
+                    // space allocated by the node but not written yet
+                    break;
+                }
+                if *state.magic.get_or_insert(magic) != magic {
+                    return Err(Error::Generic(format!(
+                        "Unexpected block file magic in {} at offset {offset}",
+                        path.display()
+                    )));
+                }
+                let size = u32::from_le_bytes(record[4..].try_into().expect("4 bytes")) as u64;
+                if offset + 8 + size > len {
+                    // the node is writing the block
+                    break;
+                }
+                let header = BlockHeader::consensus_decode(&mut reader)?;
+                // blocks preceding the scan start are never part of the indexed chain
+                if header.height >= self.scan_from {
+                    let location = BlockLocation {
+                        prev_blockhash: header.prev_blockhash,
+                        height: header.height,
+                        file,
+                        offset: offset + 8,
+                    };
+                    state.blocks.insert(header.block_hash(), location);
+                }
+                offset += 8 + size;
+                reader.seek(SeekFrom::Start(offset))?;
+            }
+            state.next.1 = offset;
+
+            // the node appends the blocks to the last file only
+            if !self.blk_path(file + 1).exists() {
+                return Ok(());
+            }
+            state.next = (file + 1, 0);
+        }
+    }
+
+    /// Update the best chain with the blocks read, the one with the highest block
+    fn update_chain(&self, state: &mut BlockFiles) -> Result<(), Error> {
+        let tip = state
+            .blocks
+            .iter()
+            // on ties keep the current chain
+            .max_by_key(|(hash, l)| (l.height, state.chain.get(&l.height) == Some(*hash)))
+            .map(|(hash, _)| *hash);
+        let mut hash = match tip {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+
+        // walk back until the current chain or the scan start is reached
+        let mut segment = vec![];
+        loop {
+            let location = state.blocks.get(&hash).ok_or_else(|| {
+                Error::Generic(format!(
+                    "Block {hash} is missing from the block files, the node may be pruned"
+                ))
+            })?;
+            let height = location.height;
+            if state.chain.get(&height) == Some(&hash) {
+                break;
+            }
+            segment.push((height, hash));
+            if height <= self.scan_from {
+                break;
+            }
+            hash = location.prev_blockhash;
+        }
+        let fork_height = match segment.last() {
+            Some((height, _)) => *height,
+            None => return Ok(()),
+        };
+        if fork_height == 0 {
+            if let Some(genesis_hash) = self.network.genesis_hash() {
+                if genesis_hash != hash {
+                    return Err(Error::Generic(format!(
+                        "The block files are not of network {:?}",
+                        self.network
+                    )));
+                }
+            }
+        }
+
+        state.chain.split_off(&fork_height);
+        state.chain.extend(segment);
+        if let Some((height, _)) = state.index.tip {
+            if height >= fork_height {
+                log::info!("reorg detected at height {fork_height}, scanning again");
+                state.index = ScriptIndex::default();
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the new blocks and index the ones of the best chain not indexed yet
+    fn sync(&self) -> Result<(), Error> {
+        let mut state = self.lock_state();
+        self.read_new_blocks(&mut state)?;
+        self.update_chain(&mut state)?;
+        let start = state.index.tip.map_or(self.scan_from, |(h, _)| h + 1);
+        let to_index: Vec<_> = state.chain.range(start..).map(|(h, b)| (*h, *b)).collect();
+        for (height, hash) in to_index {
+            let block = self.block(&state, &hash)?;
+            state.index.index_block(height, &block);
+        }
+        Ok(())
+    }
+
+    fn block(&self, state: &BlockFiles, hash: &BlockHash) -> Result<Block, Error> {
+        let location = state.location(hash)?;
+        read_block(&self.blk_path(location.file), location.offset)
+    }
+
+    fn header(&self, state: &BlockFiles, hash: &BlockHash) -> Result<BlockHeader, Error> {
+        let location = state.location(hash)?;
+        read_header(&self.blk_path(location.file), location.offset)
+    }
+}
+
+impl BlockchainBackend for BlockFilesClient {
+    fn tip(&mut self) -> Result<BlockHeader, Error> {
+        self.sync()?;
+        let state = self.lock_state();
+        let (_, hash) = state
+            .chain
+            .last_key_value()
+            .ok_or_else(|| Error::Generic("No blocks in the block files".into()))?;
+        self.header(&state, hash)
+    }
+
+    fn broadcast(&self, _tx: &Transaction) -> Result<Txid, Error> {
+        Err(Error::Generic(
+            "Cannot broadcast transactions with the block files".into(),
+        ))
+    }
+
+    fn get_transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, Error> {
+        let state = self.lock_state();
+        let mut blocks: HashMap<BlockHash, Block> = HashMap::new();
+        let mut txs = vec![];
+        for txid in txids {
+            let hash = state.index.tx_blocks.get(txid).ok_or_else(|| {
+                Error::Generic(format!("Transaction {txid} not found in the block files"))
+            })?;
+            if !blocks.contains_key(hash) {
+                blocks.insert(*hash, self.block(&state, hash)?);
+            }
+            let tx = blocks[hash]
+                .txdata
+                .iter()
+                .find(|tx| tx.txid() == *txid)
+                .ok_or_else(|| Error::Generic(format!("Transaction {txid} not in block {hash}")))?;
+            txs.push(tx.clone());
+        }
+        Ok(txs)
+    }
+
+    fn get_headers(
+        &self,
+        heights: &[Height],
+        height_blockhash: &HashMap<Height, BlockHash>,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        let state = self.lock_state();
+        heights
+            .iter()
+            .map(|height| {
+                let hash = height_blockhash
+                    .get(height)
+                    .or_else(|| state.chain.get(height))
+                    .ok_or_else(|| {
+                        Error::Generic(format!("No block at height {height} in the block files"))
+                    })?;
+                self.header(&state, hash)
+            })
+            .collect()
+    }
+
+    fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
+        self.sync()?;
+        let state = self.lock_state();
+        Ok(scripts
+            .iter()
+            .map(|script| state.index.history(script))
+            .collect())
+    }
+}
+
+/// The blocks read from the block files and the histories of the best chain
+#[derive(Default)]
+struct BlockFiles {
+    /// The network magic preceding every block in the files
+    magic: Option<[u8; 4]>,
+
+    /// The file number and the offset where the next block will be read
+    next: (u32, u64),
+
+    /// The blocks read from the files from the scan start, including the ones not in the best
+    /// chain
+    blocks: HashMap<BlockHash, BlockLocation>,
+
+    /// The hashes of the blocks in the best chain, from the scan start
+    chain: BTreeMap<Height, BlockHash>,
+
+    index: ScriptIndex,
+}
+
+impl BlockFiles {
+    fn location(&self, hash: &BlockHash) -> Result<&BlockLocation, Error> {
+        self.blocks
+            .get(hash)
+            .ok_or_else(|| Error::Generic(format!("Block {hash} not found in the block files")))
+    }
+}
+
+/// Where a block is in the block files and how it links to the chain
+///
+/// Headers are read again from the files when needed, to keep the memory used small.
+struct BlockLocation {
+    prev_blockhash: BlockHash,
+
+    height: Height,
+
+    file: u32,
+
+    /// The offset of the serialized block, after the magic and the size
+    offset: u64,
+}
+
+fn read_block(path: &Path, offset: u64) -> Result<Block, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    reader.seek(SeekFrom::Start(offset))?;
+    Ok(Block::consensus_decode(&mut reader)?)
+}
+
+fn read_header(path: &Path, offset: u64) -> Result<BlockHeader, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    reader.seek(SeekFrom::Start(offset))?;
+    Ok(BlockHeader::consensus_decode(&mut reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::Path;
+
+    use elements::{encode::serialize, hashes::Hash, Block, BlockHash, BlockHeader, Transaction};
+
+    use super::BlockFilesClient;
+    use crate::{clients::blocking::BlockchainBackend, ElementsNetwork, Update};
+
+    fn block(prev: &Block, time: u32, txdata: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                prev_blockhash: prev.block_hash(),
+                height: prev.header.height + 1,
+                time,
+                ..prev.header.clone()
+            },
+            txdata,
+        }
+    }
+
+    fn append(dir: &Path, file: u32, blocks: &[&Block]) {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("blk{file:05}.dat")))
+            .unwrap();
+        for block in blocks {
+            let bytes = serialize(*block);
+            f.write_all(&[0xfa, 0xbf, 0xb5, 0xda]).unwrap();
+            f.write_all(&(bytes.len() as u32).to_le_bytes()).unwrap();
+            f.write_all(&bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_block_files() {
+        let update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        let tx = update.new_txs.txs[0].1.clone();
+        let script = tx.output[0].script_pubkey.clone();
+
+        let mut genesis = Block {
+            header: lwk_test_util::liquid_block_header_2_963_520(),
+            txdata: vec![],
+        };
+        genesis.header.prev_blockhash = BlockHash::all_zeros();
+        genesis.header.height = 0;
+        let block1 = block(&genesis, 1, vec![tx.clone()]);
+        let block2 = block(&block1, 2, vec![]);
+        // a fork not including the transaction
+        let fork1 = block(&genesis, 3, vec![]);
+
+        let dir = tempfile::tempdir().unwrap();
+        append(dir.path(), 0, &[&genesis, &block1]);
+        let mut client = BlockFilesClient::new(ElementsNetwork::default_regtest(), dir.path());
+
+        assert_eq!(client.tip().unwrap(), block1.header);
+        let history = client.get_scripts_history(&[&script]).unwrap();
+        assert_eq!(history[0].len(), 1);
+        assert_eq!(history[0][0].txid, tx.txid());
+        assert_eq!(history[0][0].height, 1);
+        assert_eq!(client.get_transactions(&[tx.txid()]).unwrap(), vec![tx]);
+        let headers = client.get_headers(&[0, 1], &HashMap::new()).unwrap();
+        assert_eq!(headers, vec![genesis.header.clone(), block1.header.clone()]);
+
+        // a reorg to a longer chain in the following file, with the blocks out of order
+        let fork2 = block(&fork1, 4, vec![]);
+        append(dir.path(), 1, &[&fork2, &fork1]);
+        assert_eq!(client.tip().unwrap(), fork2.header);
+        assert!(client.get_scripts_history(&[&script]).unwrap()[0].is_empty());
+
+        // the transaction is confirmed again in the longest chain
+        let block3 = block(&block2, 5, vec![]);
+        append(dir.path(), 1, &[&block2, &block3]);
+        assert_eq!(client.tip().unwrap(), block3.header);
+        let history = client.get_scripts_history(&[&script]).unwrap();
+        assert_eq!(history[0][0].height, 1);
+
+        // scanning from a later height
+        let mut client =
+            BlockFilesClient::new(ElementsNetwork::default_regtest(), dir.path()).with_scan_from(2);
+        assert!(client.get_scripts_history(&[&script]).unwrap()[0].is_empty());
+        assert_eq!(client.tip().unwrap(), block3.header);
+        // only the blocks from the scan start are kept: fork2, block2 and block3
+        assert_eq!(client.lock_state().blocks.len(), 3);
+        assert!(client.get_headers(&[1], &HashMap::new()).is_err());
+
+        // the block files of another network
+        let client = BlockFilesClient::new(ElementsNetwork::Liquid, dir.path());
+        assert!(client.get_scripts_history(&[&script]).is_err());
+    }
+}
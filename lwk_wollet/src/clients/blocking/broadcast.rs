@@ -0,0 +1,121 @@
+//! Broadcast a transaction through more backends at the same time
+
+use elements::{Transaction, Txid};
+
+use crate::Error;
+
+use super::BlockchainBackend;
+
+/// A backend that can broadcast transactions
+///
+/// Implemented by every [`BlockchainBackend`], it can be used as a trait object to broadcast
+/// through backends of different types with [`broadcast_all()`].
+pub trait Broadcaster {
+    /// Broadcast the transaction, returning its id
+    fn broadcast_tx(&self, tx: &Transaction) -> Result<Txid, Error>;
+}
+
+impl<B: BlockchainBackend> Broadcaster for B {
+    fn broadcast_tx(&self, tx: &Transaction) -> Result<Txid, Error> {
+        self.broadcast(tx)
+    }
+}
+
+/// The result of the broadcast through a backend, see [`broadcast_all()`]
+#[derive(Debug)]
+pub struct BackendBroadcast {
+    /// The name given to the backend
+    pub name: String,
+
+    /// The id of the transaction, or why the backend refused it
+    pub result: Result<Txid, Error>,
+}
+
+/// The results of [`broadcast_all()`], one for every backend in the same order
+#[derive(Debug)]
+pub struct BroadcastResults(pub Vec<BackendBroadcast>);
+
+impl BroadcastResults {
+    /// The id of the transaction, if at least a backend accepted it
+    pub fn txid(&self) -> Option<Txid> {
+        self.0.iter().find_map(|b| b.result.as_ref().ok().cloned())
+    }
+
+    /// Whether every backend accepted the transaction
+    pub fn all_succeeded(&self) -> bool {
+        self.0.iter().all(|b| b.result.is_ok())
+    }
+
+    /// The backends that refused the transaction
+    pub fn failed(&self) -> impl Iterator<Item = &BackendBroadcast> {
+        self.0.iter().filter(|b| b.result.is_err())
+    }
+}
+
+/// Broadcast `tx` through all the given named backends at the same time
+///
+/// Submitting the transaction to more backends, for instance an Electrum server, an Esplora
+/// server and an own node, improves its propagation and doesn't depend on a single server which
+/// could censor it. The transaction is broadcast if [`BroadcastResults::txid()`] is some.
+pub fn broadcast_all(
+    tx: &Transaction,
+    backends: &[(&str, &(dyn Broadcaster + Sync))],
+) -> BroadcastResults {
+    let results = std::thread::scope(|scope| {
+        let handles: Vec<_> = backends
+            .iter()
+            .map(|(name, backend)| (name, scope.spawn(|| backend.broadcast_tx(tx))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|(name, handle)| BackendBroadcast {
+                name: name.to_string(),
+                result: handle
+                    .join()
+                    .unwrap_or_else(|_| Err(Error::Generic("broadcast panicked".to_string()))),
+            })
+            .collect()
+    });
+    BroadcastResults(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::{Transaction, Txid};
+
+    use super::{broadcast_all, Broadcaster};
+    use crate::Error;
+
+    struct Accepting;
+    struct Refusing;
+
+    impl Broadcaster for Accepting {
+        fn broadcast_tx(&self, tx: &Transaction) -> Result<Txid, Error> {
+            Ok(tx.txid())
+        }
+    }
+
+    impl Broadcaster for Refusing {
+        fn broadcast_tx(&self, _tx: &Transaction) -> Result<Txid, Error> {
+            Err(Error::Generic("censored".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_broadcast_all() {
+        let tx = lwk_test_util::liquid_block_1().txdata.remove(0);
+
+        let results = broadcast_all(&tx, &[("electrum", &Accepting), ("esplora", &Refusing)]);
+        assert_eq!(results.txid(), Some(tx.txid()));
+        assert!(!results.all_succeeded());
+        let failed: Vec<_> = results.failed().map(|b| b.name.as_str()).collect();
+        assert_eq!(failed, vec!["esplora"]);
+        assert_eq!(results.0[0].name, "electrum");
+
+        let results = broadcast_all(&tx, &[("esplora", &Refusing)]);
+        assert_eq!(results.txid(), None);
+
+        let results = broadcast_all(&tx, &[("a", &Accepting), ("b", &Accepting)]);
+        assert!(results.all_succeeded());
+    }
+}
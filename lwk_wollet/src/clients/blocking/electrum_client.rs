@@ -1,28 +1,146 @@
 use crate::clients::check_witnesses_non_empty;
+use crate::clients::tls::{pinned_client_config, TlsPin};
+use crate::clients::{RetryPolicy, TxStatus};
+use crate::drift::DriftMonitor;
 use crate::store::Height;
 use crate::Error;
 use crate::History;
+use crate::Wollet;
 
-use electrum_client::ScriptStatus;
-use electrum_client::{Client, ConfigBuilder, ElectrumApi, GetHistoryRes};
+use electrum_client::raw_client::{ElectrumSslStream, RawClient};
+use electrum_client::socks::Socks5Stream;
+use electrum_client::{Client, ConfigBuilder, ElectrumApi, GetHistoryRes, Socks5Config};
+use electrum_client::{RawHeaderNotification, ScriptStatus};
 use elements::encode::deserialize as elements_deserialize;
 use elements::encode::serialize as elements_serialize;
+use elements::hex::ToHex;
 use elements::Address;
 use elements::{bitcoin, BlockHash, BlockHeader, Script, Transaction, Txid};
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::net::IpAddr;
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::ops::ControlFlow;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 
 use super::BlockchainBackend;
 
 /// A client to issue TCP requests to an electrum server.
 pub struct ElectrumClient {
-    client: Client,
+    connection: Mutex<ServerConnection>,
 
     tip: BlockHeader,
 
-    script_status: HashMap<Script, ScriptStatus>,
+    /// Last known status of the subscribed scripts
+    script_status: HashMap<Script, Option<ScriptStatus>>,
+
+    /// The [`ServerConnection::generation`] `tip` and `script_status` refer to
+    generation: u64,
+
+    options: ElectrumOptions,
+}
+
+/// The connection to the current server and the servers to fail over to
+///
+/// It is behind a mutex so that requests made with a shared reference can fail over too.
+struct ServerConnection {
+    client: Connection,
+
+    /// The tip of the current server when the client connected to it
+    tip: BlockHeader,
+
+    /// The servers the client can connect to, the first one for clients created with a single url
+    servers: Vec<ElectrumServerHealth>,
+
+    /// Index in `servers` of the server the client is connected to
+    current: usize,
+
+    /// The tips of the servers, to skip the ones drifting when failing over
+    drift: Option<DriftMonitor>,
+
+    /// Incremented at every reconnection
+    generation: u64,
+}
+
+/// The health of an electrum server used by an [`ElectrumClient`]
+#[derive(Debug, Clone)]
+pub struct ElectrumServerHealth {
+    /// The url of the server
+    pub url: ElectrumUrl,
+
+    /// The number of failures since the last successful connection to the server
+    pub failures: u32,
+
+    /// The server is not tried again before this instant
+    pub retry_after: Option<Instant>,
+}
+
+impl ElectrumServerHealth {
+    fn new(url: ElectrumUrl) -> Self {
+        Self {
+            url,
+            failures: 0,
+            retry_after: None,
+        }
+    }
+
+    fn failed(&mut self, now: Instant, backoff: Option<&RetryPolicy>) {
+        self.failures = self.failures.saturating_add(1);
+        let backoff = match backoff {
+            Some(backoff) => backoff.wait(self.failures - 1, None),
+            None => reconnect_backoff(self.failures),
+        };
+        self.retry_after = Some(now + backoff);
+    }
+
+    fn succeeded(&mut self) {
+        self.failures = 0;
+        self.retry_after = None;
+    }
+
+    fn available(&self, now: Instant) -> bool {
+        self.retry_after.map_or(true, |t| t <= now)
+    }
+}
+
+/// The time to wait before reconnecting to a server that failed `failures` times in a row,
+/// doubling from 1 second up to 5 minutes
+fn reconnect_backoff(failures: u32) -> Duration {
+    let secs = 1u64 << failures.saturating_sub(1).min(16);
+    Duration::from_secs(secs.min(300))
+}
+
+/// The indexes of the `servers` to try, in order, when the `current` one fails
+///
+/// The current server is tried last, regardless of its backoff. The other servers are skipped
+/// while in backoff or if excluded by `drift`.
+fn failover_candidates(
+    servers: &[ElectrumServerHealth],
+    current: usize,
+    now: Instant,
+    drift: Option<&DriftMonitor>,
+) -> Vec<usize> {
+    let len = servers.len();
+    (1..=len)
+        .map(|i| (current + i) % len)
+        .filter(|&i| {
+            let server = &servers[i];
+            i == current
+                || (server.available(now)
+                    && !drift.is_some_and(|d| d.is_excluded(&server.url.to_string())))
+        })
+        .collect()
+}
+
+/// The host of a `host:port` string, without the brackets of IPv6 addresses
+fn host(host_port: &str) -> &str {
+    let host = host_port
+        .rsplit_once(':')
+        .map_or(host_port, |(host, _port)| host);
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
 }
 
 /// An electrum url parsable from string in the following form: `tcp://example.com:50001` or `ssl://example.com:50002`
@@ -93,7 +211,16 @@ impl ElectrumUrl {
             Ok(ElectrumUrl::Plaintext(host_port.into()))
         }
     }
+    /// Build the underlying electrum client
+    ///
+    /// Certificate pinning is not supported by this client, an error is returned if
+    /// [`ElectrumOptions::tls_pins`] is not empty.
     pub fn build_client(&self, options: &ElectrumOptions) -> Result<Client, Error> {
+        if !options.tls_pins.is_empty() {
+            return Err(Error::Generic(
+                "Certificate pinning requires ElectrumClient::with_options".to_string(),
+            ));
+        }
         let builder = ConfigBuilder::new();
         let (url, builder) = match self {
             ElectrumUrl::Tls(url, validate) => {
@@ -101,9 +228,89 @@ impl ElectrumUrl {
             }
             ElectrumUrl::Plaintext(url) => (format!("tcp://{}", url), builder),
         };
-        let builder = builder.timeout(options.timeout);
+        let mut builder = builder
+            .timeout(options.timeout)
+            .socks5(options.socks5.as_ref().map(Socks5Config::new));
+        if let Some(retries) = options.retries {
+            builder = builder.retry(retries);
+        }
         Ok(Client::from_config(&url, builder.build())?)
     }
+
+    fn connect(&self, options: &ElectrumOptions) -> Result<Connection, Error> {
+        if options.tls_pins.is_empty() {
+            return Ok(Connection::Client(self.build_client(options)?));
+        }
+        let (host_port, validate_domain) = match self {
+            ElectrumUrl::Tls(host_port, validate) => (host_port, *validate),
+            ElectrumUrl::Plaintext(_) => return Err(Error::TlsPinWithoutTls),
+        };
+        let config = pinned_client_config(&options.tls_pins, validate_domain)?;
+        let server_name = rustls::pki_types::ServerName::try_from(host(host_port).to_string())
+            .map_err(|e| Error::Generic(e.to_string()))?;
+
+        let timeout = options.timeout.map(|t| Duration::from_secs(t as u64));
+        let stream = match (&options.socks5, timeout) {
+            (Some(proxy), timeout) => {
+                Socks5Stream::connect(proxy.as_str(), host_port.as_str(), timeout)?.into_inner()
+            }
+            (None, Some(timeout)) => {
+                let addr = host_port
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| Error::Generic(format!("Cannot resolve {host_port}")))?;
+                TcpStream::connect_timeout(&addr, timeout)?
+            }
+            (None, None) => TcpStream::connect(host_port.as_str())?,
+        };
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        let connection = rustls::ClientConnection::new(Arc::new(config), server_name)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        let stream: ElectrumSslStream = rustls::StreamOwned::new(connection, stream);
+        Ok(Connection::Pinned(RawClient::from(stream)))
+    }
+}
+
+/// The connection to the electrum server
+///
+/// Pinned connections use a raw client with a custom TLS configuration, they are not
+/// re-established if dropped by the server.
+enum Connection {
+    Client(Client),
+    Pinned(RawClient<ElectrumSslStream>),
+}
+
+macro_rules! forward {
+    ($($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        impl Connection {
+            $(
+                fn $name(&self, $($arg: $ty),*) -> Result<$ret, electrum_client::Error> {
+                    match self {
+                        Connection::Client(c) => c.$name($($arg),*),
+                        Connection::Pinned(c) => c.$name($($arg),*),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+forward! {
+    block_headers_subscribe_raw() -> RawHeaderNotification;
+    block_headers_pop_raw() -> Option<RawHeaderNotification>;
+    ping() -> ();
+    script_subscribe(script: &bitcoin::Script) -> Option<ScriptStatus>;
+    script_pop(script: &bitcoin::Script) -> Option<ScriptStatus>;
+    script_get_history(script: &bitcoin::Script) -> Vec<GetHistoryRes>;
+    batch_script_subscribe(scripts: &[&bitcoin::Script]) -> Vec<Option<ScriptStatus>>;
+    batch_script_get_history(scripts: &[&bitcoin::Script]) -> Vec<Vec<GetHistoryRes>>;
+    batch_transaction_get_raw(txids: &[bitcoin::Txid]) -> Vec<Vec<u8>>;
+    transaction_get_raw(txid: &bitcoin::Txid) -> Vec<u8>;
+    batch_block_header_raw(heights: &[u32]) -> Vec<Vec<u8>>;
+    transaction_broadcast_raw(raw_tx: &[u8]) -> bitcoin::Txid;
+    estimate_fee(number: usize) -> f64;
 }
 
 impl Debug for ElectrumClient {
@@ -114,10 +321,55 @@ impl Debug for ElectrumClient {
     }
 }
 
+/// The default maximum number of requests in an electrum batch, below the default limits of the
+/// common server implementations
+pub const ELECTRUM_BATCH_SIZE: usize = 300;
+
+/// Call `f` on the chunks of `items` with at most `size` elements, concatenating the results
+fn in_batches<T, R, E>(
+    items: &[T],
+    size: usize,
+    mut f: impl FnMut(&[T]) -> Result<Vec<R>, E>,
+) -> Result<Vec<R>, E> {
+    let mut result = Vec::with_capacity(items.len());
+    for chunk in items.chunks(size.max(1)) {
+        result.extend(f(chunk)?);
+    }
+    Ok(result)
+}
+
 /// Options for the [`ElectrumClient::with_options()`] method.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ElectrumOptions {
+    /// The timeout in seconds to connect and to read responses from the server
     pub timeout: Option<u8>,
+
+    /// The number of times requests are retried reconnecting to the same server, 1 if not set
+    ///
+    /// Connections with [`ElectrumOptions::tls_pins`] are never retried.
+    pub retries: Option<u8>,
+
+    /// The wait before connecting again to a failed server of an
+    /// [`ElectrumClient::with_failover()`], from 1 second up to 5 minutes if not set
+    ///
+    /// The wait doubles at every consecutive failure of the server, the
+    /// [`RetryPolicy::max_retries`] of the policy is not used.
+    pub failover_backoff: Option<RetryPolicy>,
+
+    /// If not empty, the server certificate must match at least one of these pins
+    pub tls_pins: Vec<TlsPin>,
+
+    /// The maximum number of requests sent in a single batch, [`ELECTRUM_BATCH_SIZE`] if not set
+    ///
+    /// Script histories, statuses, transactions and headers are requested in batches, saving a
+    /// round trip for every request. Servers limit the size of the batches they accept, bigger
+    /// batches are split.
+    pub batch_size: Option<usize>,
+
+    /// If set, connect through the SOCKS5 proxy at this address, eg. `127.0.0.1:9050` for Tor
+    ///
+    /// The server host name is resolved by the proxy, so that `.onion` servers can be used.
+    pub socks5: Option<String>,
 }
 
 impl ElectrumClient {
@@ -128,15 +380,142 @@ impl ElectrumClient {
 
     /// Creates an Electrum client specifying non default options like timeout
     pub fn with_options(url: &ElectrumUrl, options: ElectrumOptions) -> Result<Self, Error> {
-        let client = url.build_client(&options)?;
-        let header = client.block_headers_subscribe_raw()?;
-        let tip: BlockHeader = elements_deserialize(&header.header)?;
-
-        Ok(Self {
-            client,
-            tip,
-            script_status: HashMap::new(),
-        })
+        Self::with_failover(std::slice::from_ref(url), options)
+    }
+
+    /// Creates an Electrum client failing over between the given servers
+    ///
+    /// The client connects to the first reachable server in `urls`. When a request to the
+    /// current server times out or the connection is dropped, the client reconnects to the next
+    /// available server and sends the request again. Servers that failed are not tried again for
+    /// a time doubling at every consecutive failure, see [`ElectrumClient::servers()`].
+    pub fn with_failover(urls: &[ElectrumUrl], options: ElectrumOptions) -> Result<Self, Error> {
+        let mut servers: Vec<ElectrumServerHealth> = urls
+            .iter()
+            .cloned()
+            .map(ElectrumServerHealth::new)
+            .collect();
+        let mut last_err = Error::Generic("No electrum server given".to_string());
+        for current in 0..servers.len() {
+            match connect_and_subscribe(&servers[current].url, &options) {
+                Ok((client, tip)) => {
+                    let connection = ServerConnection {
+                        client,
+                        tip: tip.clone(),
+                        servers,
+                        current,
+                        drift: None,
+                        generation: 0,
+                    };
+                    return Ok(Self {
+                        connection: Mutex::new(connection),
+                        tip,
+                        script_status: HashMap::new(),
+                        generation: 0,
+                        options,
+                    });
+                }
+                Err(e) => {
+                    servers[current].failed(Instant::now(), options.failover_backoff.as_ref());
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Skip the servers with drift anomalies when failing over
+    ///
+    /// The tip of every server the client connects to is recorded in `monitor`, with the server
+    /// url as backend name. Servers excluded by [`DriftMonitor::is_excluded()`] are not used
+    /// unless the current server is the only one left.
+    pub fn with_drift_monitor(mut self, mut monitor: DriftMonitor) -> Self {
+        let connection = self.connection_mut();
+        let url = connection.servers[connection.current].url.to_string();
+        monitor.observe(&url, &connection.tip, &[]);
+        connection.drift = Some(monitor);
+        self
+    }
+
+    /// The drift monitor set with [`ElectrumClient::with_drift_monitor()`]
+    pub fn drift_monitor(&self) -> Option<DriftMonitor> {
+        self.connection().drift.clone()
+    }
+
+    /// The health of the servers this client can connect to
+    pub fn servers(&self) -> Vec<ElectrumServerHealth> {
+        self.connection().servers.clone()
+    }
+
+    fn batch_size(&self) -> usize {
+        self.options.batch_size.unwrap_or(ELECTRUM_BATCH_SIZE)
+    }
+
+    /// The url of the server the client is connected to
+    pub fn current_url(&self) -> ElectrumUrl {
+        let connection = self.connection();
+        connection.servers[connection.current].url.clone()
+    }
+
+    fn connection(&self) -> MutexGuard<'_, ServerConnection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The connection, after updating the tip and forgetting the subscriptions if the client
+    /// reconnected in a request made with a shared reference
+    fn connection_mut(&mut self) -> &mut ServerConnection {
+        let connection = self
+            .connection
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if connection.generation != self.generation {
+            self.generation = connection.generation;
+            self.tip = connection.tip.clone();
+            self.script_status.clear();
+        }
+        connection
+    }
+
+    fn client(&mut self) -> &Connection {
+        &self.connection_mut().client
+    }
+
+    /// Call `f` with the connection, failing over and calling it again if the server failed
+    fn call<T>(
+        &self,
+        f: impl Fn(&Connection) -> Result<T, electrum_client::Error>,
+    ) -> Result<T, Error> {
+        let mut connection = self.connection();
+        match f(&connection.client) {
+            Err(e) if is_server_failure(&e) => {
+                connection.reconnect(e.into(), &self.options)?;
+                Ok(f(&connection.client)?)
+            }
+            r => Ok(r?),
+        }
+    }
+
+    /// Ping the server to keep the connection alive, reconnecting if it was dropped
+    ///
+    /// Servers close idle connections, long lived clients should call this periodically, for
+    /// instance every minute, so that the following requests don't fail or wait for a new
+    /// connection.
+    pub fn keepalive(&mut self) -> Result<(), Error> {
+        match self.client().ping() {
+            Ok(()) => Ok(()),
+            Err(e) => self.reconnect(e.into()),
+        }
+    }
+
+    /// Mark the current server as failed and connect to the next available one, or again to the
+    /// current one if no other is available
+    fn reconnect(&mut self, err: Error) -> Result<(), Error> {
+        let options = self.options.clone();
+        self.connection_mut().reconnect(err, &options)?;
+        self.connection_mut();
+        Ok(())
     }
 
     /// Return the status of an address as defined by the electrum protocol
@@ -148,30 +527,175 @@ impl ElectrumClient {
         let elements_script = address.script_pubkey();
         let bitcoin_script = bitcoin::ScriptBuf::from(elements_script.to_bytes());
 
-        let val = match self.client.script_subscribe(&bitcoin_script) {
-            Ok(val) => val,
+        match self.client().script_subscribe(&bitcoin_script) {
+            Ok(val) => {
+                self.script_status.insert(elements_script.clone(), val);
+            }
             Err(electrum_client::Error::AlreadySubscribed(_)) => {
-                self.client.script_get_history(&bitcoin_script)?; // it seems it must be called, otherwise the server don't update the status
-                self.client.script_pop(&bitcoin_script)?
+                self.client().script_get_history(&bitcoin_script)?; // it seems it must be called, otherwise the server don't update the status
+                if let Some(val) = self.client().script_pop(&bitcoin_script)? {
+                    self.script_status
+                        .insert(elements_script.clone(), Some(val));
+                }
             }
             Err(e) => return Err(e.into()),
         };
 
-        if let Some(val) = val {
-            self.script_status.insert(elements_script.clone(), val);
-        }
-        Ok(self.script_status.get(&elements_script).cloned())
+        Ok(self.script_status.get(&elements_script).cloned().flatten())
     }
 
     /// Ping the Electrum server
     pub fn ping(&self) -> Result<(), Error> {
-        Ok(self.client.ping()?)
+        Ok(self.connection().client.ping()?)
+    }
+
+    /// Keep the wallet up to date, applying updates as notifications arrive from the server
+    ///
+    /// The wallet is fully scanned once, subscribing to its scripts and to new blocks. Then the
+    /// server is pinged every `interval` to receive the notifications and the wallet is scanned
+    /// again only if a script status changed or a new block arrived.
+    ///
+    /// If the connection is dropped the client reconnects and scans the wallet again. After every
+    /// applied update `on_update` is called with the up to date wallet, watching stops when it
+    /// returns [`ControlFlow::Break`] or in case of errors.
+    pub fn watch<F>(
+        &mut self,
+        wollet: &mut Wollet,
+        interval: Duration,
+        mut on_update: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Wollet) -> ControlFlow<()>,
+    {
+        let mut changed = true;
+        loop {
+            if changed {
+                if let Some(update) = self.full_scan(wollet)? {
+                    wollet.apply_update(update)?;
+                    if on_update(wollet).is_break() {
+                        return Ok(());
+                    }
+                }
+            }
+            std::thread::sleep(interval);
+            changed = match self.pop_notifications() {
+                Ok(changed) => changed,
+                Err(e) => {
+                    self.reconnect(e)?;
+                    true
+                }
+            };
+        }
+    }
+
+    /// Process the notifications received from the server, returning true if any
+    fn pop_notifications(&mut self) -> Result<bool, Error> {
+        let generation = self.generation;
+        // Notifications are read from the connection only while waiting for a response
+        self.client().ping()?;
+        // a request with a shared reference reconnected and subscriptions are lost
+        let mut changed = generation != self.generation;
+        while let Some(header) = self.client().block_headers_pop_raw()? {
+            self.tip = elements_deserialize(&header.header)?;
+            changed = true;
+        }
+        let scripts: Vec<Script> = self.script_status.keys().cloned().collect();
+        for script in scripts {
+            let bitcoin_script = bitcoin::Script::from_bytes(script.as_bytes());
+            while let Some(status) = self.client().script_pop(bitcoin_script)? {
+                self.script_status.insert(script.clone(), Some(status));
+                changed = true;
+            }
+        }
+        Ok(changed)
     }
 }
-impl BlockchainBackend for ElectrumClient {
-    fn tip(&mut self) -> Result<BlockHeader, Error> {
+
+impl ServerConnection {
+    /// Mark the current server as failed and connect to the next available one, or again to the
+    /// current one if no other is available
+    ///
+    /// Subscriptions don't persist across connections, so scripts are subscribed again at the
+    /// next scan.
+    fn reconnect(&mut self, err: Error, options: &ElectrumOptions) -> Result<(), Error> {
+        let now = Instant::now();
+        self.servers[self.current].failed(now, options.failover_backoff.as_ref());
+        let mut last_err = err;
+        let candidates = failover_candidates(&self.servers, self.current, now, self.drift.as_ref());
+        for i in candidates {
+            match connect_and_subscribe(&self.servers[i].url, options) {
+                Ok((client, tip)) => {
+                    if let Some(drift) = self.drift.as_mut() {
+                        let name = self.servers[i].url.to_string();
+                        drift.observe(&name, &tip, &[]);
+                        if i != self.current && drift.is_excluded(&name) {
+                            continue;
+                        }
+                    }
+                    log::info!(
+                        "electrum server {} failed, connected to {}",
+                        self.servers[self.current].url,
+                        self.servers[i].url
+                    );
+                    self.servers[i].succeeded();
+                    self.client = client;
+                    self.tip = tip;
+                    self.current = i;
+                    self.generation += 1;
+                    return Ok(());
+                }
+                Err(e) => {
+                    if i != self.current {
+                        self.servers[i].failed(now, options.failover_backoff.as_ref());
+                    }
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+/// Whether the error is caused by the server or the connection, rather than by the request
+fn is_server_failure(err: &electrum_client::Error) -> bool {
+    !matches!(
+        err,
+        electrum_client::Error::Protocol(_)
+            | electrum_client::Error::AlreadySubscribed(_)
+            | electrum_client::Error::NotSubscribed(_)
+    )
+}
+
+/// Whether the protocol error is the server reporting an unknown transaction
+///
+/// Electrs replies "tx not found", servers backed by elementsd relay its "No such mempool or
+/// blockchain transaction".
+fn is_not_found(value: &serde_json::Value) -> bool {
+    let message = match value {
+        serde_json::Value::String(message) => message,
+        value => match value.get("message").and_then(|m| m.as_str()) {
+            Some(message) => message,
+            None => return false,
+        },
+    };
+    let message = message.to_lowercase();
+    message.contains("not found") || message.contains("no such")
+}
+
+fn connect_and_subscribe(
+    url: &ElectrumUrl,
+    options: &ElectrumOptions,
+) -> Result<(Connection, BlockHeader), Error> {
+    let client = url.connect(options)?;
+    let header = client.block_headers_subscribe_raw()?;
+    let tip: BlockHeader = elements_deserialize(&header.header)?;
+    Ok((client, tip))
+}
+
+impl ElectrumClient {
+    fn tip_inner(&mut self) -> Result<BlockHeader, Error> {
         let mut popped_header = None;
-        while let Some(header) = self.client.block_headers_pop_raw()? {
+        while let Some(header) = self.client().block_headers_pop_raw()? {
             popped_header = Some(header)
         }
 
@@ -185,9 +709,15 @@ impl BlockchainBackend for ElectrumClient {
                 // It might be that the client has reconnected and subscriptions don't persist
                 // across connections. Calling `client.ping()` won't help here because the
                 // successful retry will prevent us knowing about the reconnect.
-                if let Ok(header) = self.client.block_headers_subscribe_raw() {
-                    let tip: BlockHeader = elements_deserialize(&header.header)?;
-                    self.tip = tip;
+                let failover = self.connection_mut().servers.len() > 1;
+                match self.client().block_headers_subscribe_raw() {
+                    Ok(header) => {
+                        let tip: BlockHeader = elements_deserialize(&header.header)?;
+                        self.tip = tip;
+                    }
+                    // with more servers an unresponsive one triggers the fail over
+                    Err(e) if failover => return Err(e.into()),
+                    Err(_) => {}
                 }
             }
         }
@@ -195,15 +725,84 @@ impl BlockchainBackend for ElectrumClient {
         Ok(self.tip.clone())
     }
 
+    fn get_scripts_status_inner(
+        &mut self,
+        scripts: &[&Script],
+    ) -> Result<Vec<Option<String>>, Error> {
+        // forget the subscriptions if a request with a shared reference reconnected
+        self.connection_mut();
+        let (subscribed, new): (Vec<&Script>, Vec<&Script>) = scripts
+            .iter()
+            .partition(|s| self.script_status.contains_key(**s));
+
+        if !subscribed.is_empty() {
+            // process the notifications received in the meantime
+            self.client().ping()?;
+            for script in subscribed {
+                let bitcoin_script = bitcoin::Script::from_bytes(script.as_bytes());
+                while let Some(val) = self.client().script_pop(bitcoin_script)? {
+                    self.script_status.insert(script.clone(), Some(val));
+                }
+            }
+        }
+        if !new.is_empty() {
+            let bitcoin_scripts: Vec<&bitcoin::Script> = new
+                .iter()
+                .map(|s| bitcoin::Script::from_bytes(s.as_bytes()))
+                .collect();
+            let batch_size = self.batch_size();
+            let client = self.client();
+            let statuses = in_batches(&bitcoin_scripts, batch_size, |chunk| {
+                client.batch_script_subscribe(chunk)
+            })?;
+            for (script, status) in new.into_iter().zip(statuses) {
+                self.script_status.insert(script.clone(), status);
+            }
+        }
+
+        Ok(scripts
+            .iter()
+            .map(|s| {
+                self.script_status
+                    .get(*s)
+                    .cloned()
+                    .flatten()
+                    .map(|status| status[..].to_hex())
+            })
+            .collect())
+    }
+}
+
+impl BlockchainBackend for ElectrumClient {
+    /// In case of errors reconnect, failing over to the next server if the client has more
+    fn tip(&mut self) -> Result<BlockHeader, Error> {
+        match self.tip_inner() {
+            Err(e) => {
+                self.reconnect(e)?;
+                Ok(self.tip.clone())
+            }
+            r => r,
+        }
+    }
+
     fn broadcast(&self, tx: &Transaction) -> Result<Txid, Error> {
         check_witnesses_non_empty(tx)?; // We don't support legacy outputs, thus we always have the witness (or forget to sign/finalize)
 
-        let txid = self
-            .client
-            .transaction_broadcast_raw(&elements_serialize(tx))?;
+        let raw_tx = elements_serialize(tx);
+        let txid = self.call(|client| client.transaction_broadcast_raw(&raw_tx))?;
         Ok(Txid::from_raw_hash(txid.to_raw_hash()))
     }
 
+    fn estimate_fee(&self, target: u16) -> Result<f32, Error> {
+        // in BTC/kvb, negative if the server has not enough data
+        let fee_rate = self.call(|client| client.estimate_fee(target as usize))?;
+        if fee_rate < 0.0 {
+            return Err(Error::FeeEstimateUnavailable(target));
+        }
+        Ok((fee_rate * 100_000_000.0) as f32)
+    }
+
+    /// In case of errors reconnect, failing over to the next server if the client has more
     fn get_transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, Error> {
         let txids: Vec<bitcoin::Txid> = txids
             .iter()
@@ -211,35 +810,81 @@ impl BlockchainBackend for ElectrumClient {
             .collect();
 
         let mut result = vec![];
-        for tx in self.client.batch_transaction_get_raw(&txids)? {
+        let txs = self.call(|client| {
+            in_batches(&txids, self.batch_size(), |chunk| {
+                client.batch_transaction_get_raw(chunk)
+            })
+        })?;
+        for tx in txs {
             let tx: Transaction = elements::encode::deserialize(&tx)?;
             result.push(tx);
         }
         Ok(result)
     }
 
+    /// Unknown transactions are reported by the server with "not found" protocol errors, other
+    /// protocol errors are returned
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
+        let txid_bitcoin = bitcoin::Txid::from_raw_hash(txid.to_raw_hash());
+        let tx = match self.call(|client| client.transaction_get_raw(&txid_bitcoin)) {
+            Ok(tx) => tx,
+            Err(Error::ClientError(electrum_client::Error::Protocol(ref value)))
+                if is_not_found(value) =>
+            {
+                return Ok(TxStatus::NotFound)
+            }
+            Err(e) => return Err(e),
+        };
+        let tx: Transaction = elements::encode::deserialize(&tx)?;
+        self.tx_status_of(&tx)
+    }
+
+    /// In case of errors reconnect, failing over to the next server if the client has more
     fn get_headers(
         &self,
         heights: &[Height],
         _: &HashMap<Height, BlockHash>,
     ) -> Result<Vec<BlockHeader>, Error> {
         let mut result = vec![];
-        for header in self.client.batch_block_header_raw(heights)? {
+        let headers = self.call(|client| {
+            in_batches(heights, self.batch_size(), |chunk| {
+                client.batch_block_header_raw(chunk)
+            })
+        })?;
+        for header in headers {
             let header: BlockHeader = elements::encode::deserialize(&header)?;
             result.push(header);
         }
         Ok(result)
     }
 
+    /// In case of errors reconnect, failing over to the next server if the client has more
+    fn get_scripts_status(
+        &mut self,
+        scripts: &[&Script],
+    ) -> Result<Option<Vec<Option<String>>>, Error> {
+        match self.get_scripts_status_inner(scripts) {
+            Err(e) => {
+                self.reconnect(e)?;
+                Ok(Some(self.get_scripts_status_inner(scripts)?))
+            }
+            r => r.map(Some),
+        }
+    }
+
+    /// In case of errors reconnect, failing over to the next server if the client has more
     fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
         let scripts: Vec<&bitcoin::Script> = scripts
             .iter()
             .map(|t| bitcoin::Script::from_bytes(t.as_bytes()))
             .collect();
 
-        Ok(self
-            .client
-            .batch_script_get_history(&scripts)?
+        let histories = self.call(|client| {
+            in_batches(&scripts, self.batch_size(), |chunk| {
+                client.batch_script_get_history(chunk)
+            })
+        })?;
+        Ok(histories
             .into_iter()
             .map(|e| e.into_iter().map(Into::into).collect())
             .collect())
@@ -284,7 +929,13 @@ pub enum UrlError {
 
 #[cfg(test)]
 mod tests {
-    use super::{ElectrumUrl, UrlError};
+    use super::{
+        failover_candidates, host, in_batches, is_not_found, is_server_failure, reconnect_backoff,
+        ElectrumClient, ElectrumOptions, ElectrumServerHealth, ElectrumUrl, UrlError,
+    };
+    use crate::drift::DriftMonitor;
+    use crate::{Error, RetryPolicy, TlsPin};
+    use std::time::{Duration, Instant};
 
     fn check_url(url: &str, url_no_scheme: &str, tls: bool, validate_domain: bool) {
         let electrum_url: ElectrumUrl = url.parse().unwrap();
@@ -358,4 +1009,154 @@ mod tests {
             .to_string();
         assert_eq!(err, "Don't specify the scheme in the url");
     }
+
+    #[test]
+    fn test_electrum_tls_pins() {
+        let pins = TlsPin::from_certificate(include_bytes!("../../../tests/data/tls_cert.der"))
+            .unwrap()
+            .to_vec();
+        let options = || ElectrumOptions {
+            tls_pins: pins.clone(),
+            ..Default::default()
+        };
+
+        let url: ElectrumUrl = "tcp://127.0.0.1:50001".parse().unwrap();
+        let err = ElectrumClient::with_options(&url, options()).unwrap_err();
+        assert!(matches!(err, Error::TlsPinWithoutTls));
+
+        let url: ElectrumUrl = "ssl://example.com:50002".parse().unwrap();
+        assert!(url.build_client(&options()).is_err());
+
+        assert_eq!(host("example.com:50002"), "example.com");
+        assert_eq!(host("127.0.0.1:50002"), "127.0.0.1");
+        assert_eq!(host("[::1]:50002"), "::1");
+        assert!(rustls::pki_types::ServerName::try_from(host("[::1]:50002")).is_ok());
+    }
+
+    #[test]
+    fn test_electrum_socks5() {
+        // nothing listens on the proxy port
+        let options = || ElectrumOptions {
+            socks5: Some("127.0.0.1:1".to_string()),
+            ..Default::default()
+        };
+        let url: ElectrumUrl = "tcp://example.onion:50001".parse().unwrap();
+        assert!(ElectrumClient::with_options(&url, options()).is_err());
+
+        let pins = TlsPin::from_certificate(include_bytes!("../../../tests/data/tls_cert.der"))
+            .unwrap()
+            .to_vec();
+        let options = ElectrumOptions {
+            tls_pins: pins,
+            ..options()
+        };
+        let url: ElectrumUrl = "ssl://example.onion:50002".parse().unwrap();
+        assert!(ElectrumClient::with_options(&url, options).is_err());
+    }
+
+    #[test]
+    fn test_electrum_failover() {
+        assert_eq!(reconnect_backoff(1), Duration::from_secs(1));
+        assert_eq!(reconnect_backoff(2), Duration::from_secs(2));
+        assert_eq!(reconnect_backoff(5), Duration::from_secs(16));
+        assert_eq!(reconnect_backoff(100), Duration::from_secs(300));
+
+        let url: ElectrumUrl = "tcp://127.0.0.1:1".parse().unwrap();
+        let mut health = ElectrumServerHealth::new(url.clone());
+        let now = Instant::now();
+        assert!(health.available(now));
+        health.failed(now, None);
+        health.failed(now, None);
+        assert_eq!(health.failures, 2);
+        assert!(!health.available(now));
+        assert!(health.available(now + Duration::from_secs(2)));
+        let backoff = RetryPolicy {
+            initial_backoff: Duration::from_secs(10),
+            jitter: false,
+            ..Default::default()
+        };
+        health.failed(now, Some(&backoff));
+        assert!(!health.available(now + Duration::from_secs(39)));
+        assert!(health.available(now + Duration::from_secs(40)));
+        health.succeeded();
+        assert_eq!(health.failures, 0);
+        assert!(health.available(now));
+
+        let err = ElectrumClient::with_failover(&[], ElectrumOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "No electrum server given");
+
+        let timeout = std::io::Error::from(std::io::ErrorKind::TimedOut);
+        assert!(is_server_failure(&electrum_client::Error::IOError(timeout)));
+        let not_found = electrum_client::Error::Protocol(serde_json::json!("not found"));
+        assert!(!is_server_failure(&not_found));
+
+        assert!(is_not_found(&serde_json::json!("tx not found")));
+        let no_such =
+            serde_json::json!({"code": -5, "message": "No such mempool or blockchain transaction"});
+        assert!(is_not_found(&no_such));
+        let invalid = serde_json::json!({"code": 1, "message": "invalid tx hash"});
+        assert!(!is_not_found(&invalid));
+        assert!(!is_not_found(&serde_json::json!(null)));
+
+        // nothing listens on these ports
+        let urls = [url, "tcp://127.0.0.1:2".parse().unwrap()];
+        assert!(ElectrumClient::with_failover(&urls, ElectrumOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_electrum_failover_drift() {
+        let urls: Vec<ElectrumUrl> = [
+            "tcp://127.0.0.1:1",
+            "tcp://127.0.0.1:2",
+            "tcp://127.0.0.1:3",
+        ]
+        .iter()
+        .map(|u| u.parse().unwrap())
+        .collect();
+        let servers: Vec<_> = urls
+            .iter()
+            .cloned()
+            .map(ElectrumServerHealth::new)
+            .collect();
+        let now = Instant::now();
+        assert_eq!(failover_candidates(&servers, 0, now, None), vec![1, 2, 0]);
+        assert_eq!(failover_candidates(&servers, 2, now, None), vec![0, 1, 2]);
+
+        // the second server is lagging behind the others
+        let mut header = lwk_test_util::liquid_block_header_2_963_520();
+        let mut monitor = DriftMonitor::new();
+        monitor.observe(&urls[0].to_string(), &header, &[]);
+        monitor.observe(&urls[2].to_string(), &header, &[]);
+        header.height -= 10;
+        monitor.observe(&urls[1].to_string(), &header, &[]);
+        assert!(monitor.is_excluded(&urls[1].to_string()));
+        assert_eq!(
+            failover_candidates(&servers, 0, now, Some(&monitor)),
+            vec![2, 0]
+        );
+
+        // the current server is tried last even if excluded
+        assert_eq!(
+            failover_candidates(&servers, 1, now, Some(&monitor)),
+            vec![2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_in_batches() {
+        let items: Vec<u32> = (0..10).collect();
+        let mut calls = 0;
+        let result = in_batches(&items, 3, |chunk| {
+            calls += 1;
+            assert!(chunk.len() <= 3);
+            Ok::<_, ()>(chunk.iter().map(|i| i * 2).collect())
+        })
+        .unwrap();
+        assert_eq!(calls, 4);
+        assert_eq!(result, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+
+        let empty: Vec<u32> = in_batches(&[], 3, |_: &[u32]| Err(())).unwrap();
+        assert!(empty.is_empty());
+        assert!(in_batches(&items, 0, |_| Err::<Vec<u32>, _>(())).is_err());
+    }
 }
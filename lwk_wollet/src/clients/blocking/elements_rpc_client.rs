@@ -1,21 +1,46 @@
-use crate::{clients::try_unblind, Chain, ElementsNetwork, Error, WalletTxOut, WolletDescriptor};
+use crate::{
+    clients::{try_unblind, History},
+    payment_code::{PaymentCodeReceived, PaymentCodeScanner},
+    store::Height,
+    Chain, ElementsNetwork, Error, MempoolAccept, WalletTxOut, WolletDescriptor,
+};
 
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 use bitcoincore_rpc::{Auth, Client, RpcApi};
 use elements::{
-    encode::deserialize, hex::FromHex, Address, OutPoint, Script, Transaction, TxOut, Txid,
+    encode::{deserialize, serialize_hex, Decodable},
+    hex::FromHex,
+    Address, Block, BlockHash, BlockHeader, OutPoint, Script, Transaction, TxOut, Txid,
 };
+use serde_json::Value;
+
+use super::script_index::ScriptIndex;
+use super::BlockchainBackend;
 
 /// A client to issue RPCs to a Elements node
+///
+/// The client is also a [`BlockchainBackend`], so wallets can be scanned with an own Elements
+/// node instead of an Electrum or Esplora server. Elements doesn't index transactions by script,
+/// so the client downloads the blocks from [`ElementsRpcClient::with_scan_from()`] on and keeps
+/// the histories of the scripts in memory, together with the mempool transactions; blocks
+/// already scanned are not downloaded again by the following scans. Transactions are fetched by
+/// block hash, so the node doesn't need `-txindex`.
+///
+/// Since the blocks are downloaded anyway, the client can also look for payments to a reusable
+/// payment code, see [`ElementsRpcClient::with_payment_code_scanner()`].
 pub struct ElementsRpcClient {
     inner: Client,
-    #[allow(unused)]
     network: ElementsNetwork,
     #[allow(unused)]
     auth: Auth,
     #[allow(unused)]
     url: String,
+    scan_from: Height,
+    index: Mutex<ScriptIndex>,
+    payment_code_scanner: Option<PaymentCodeScanner>,
 }
 
 impl ElementsRpcClient {
@@ -27,6 +52,9 @@ impl ElementsRpcClient {
             network,
             auth,
             url: url.to_string(),
+            scan_from: 0,
+            index: Mutex::new(ScriptIndex::default()),
+            payment_code_scanner: None,
         })
     }
 
@@ -41,6 +69,43 @@ impl ElementsRpcClient {
         Self::new(network, url, auth)
     }
 
+    /// Create a new Elements RPC client authenticating with the node cookie file
+    pub fn new_from_cookie(
+        network: ElementsNetwork,
+        url: &str,
+        cookie_file: impl Into<PathBuf>,
+    ) -> Result<Self, Error> {
+        Self::new(network, url, Auth::CookieFile(cookie_file.into()))
+    }
+
+    /// Scan the blockchain from the given height, instead of from the genesis block
+    ///
+    /// Transactions confirmed before `height` are not returned when used as a
+    /// [`BlockchainBackend`], use the height of the block preceding the wallet creation.
+    pub fn with_scan_from(mut self, height: Height) -> Self {
+        self.scan_from = height;
+        self
+    }
+
+    /// Scan the downloaded blocks also for outputs paying the payment code of `scanner`
+    ///
+    /// Such outputs can't be derived from the wallet descriptor, so they are not part of the
+    /// [`crate::Update`] returned by `full_scan`, get them with
+    /// [`ElementsRpcClient::payment_code_received()`] after the scan.
+    pub fn with_payment_code_scanner(mut self, scanner: PaymentCodeScanner) -> Self {
+        self.payment_code_scanner = Some(scanner);
+        self
+    }
+
+    /// The confirmed outputs paying the payment code found by the scans so far, with the height
+    /// of the block confirming them
+    ///
+    /// Outputs are returned also if spent, they are found only when the blocks are scanned,
+    /// that is by `full_scan`.
+    pub fn payment_code_received(&self) -> Vec<(Height, PaymentCodeReceived)> {
+        self.lock_index().payment_code_received.clone()
+    }
+
     /// Get the blockchain height
     pub fn height(&self) -> Result<u64, Error> {
         self.inner
@@ -50,28 +115,94 @@ impl ElementsRpcClient {
     }
 
     fn get_txout(&self, outpoint: &OutPoint, height: u32) -> Result<TxOut, Error> {
-        let blockhash = self
-            .inner
-            .call::<serde_json::Value>("getblockhash", &[height.into()])?;
+        let blockhash = self.block_hash(height)?;
+        let tx = self.get_raw_transaction(&outpoint.txid, Some(blockhash))?;
+        let txout = tx
+            .output
+            .get(outpoint.vout as usize)
+            .ok_or_else(|| Error::ElementsRpcUnexpectedReturn("getrawtransaction".into()))?
+            .clone();
+        Ok(txout)
+    }
 
-        let method = "getrawtransaction";
-        let txid = outpoint.txid.to_string();
-        let r = self
-            .inner
-            .call::<serde_json::Value>(method, &[txid.into(), false.into(), blockhash])?;
+    /// Call a method returning a consensus encoded object in hex
+    fn call_hex<T: Decodable>(&self, method: &str, params: &[Value]) -> Result<T, Error> {
+        let r = self.inner.call::<Value>(method, params)?;
         let hex = r
             .as_str()
             .ok_or_else(|| Error::ElementsRpcUnexpectedReturn(method.into()))?;
         let bytes = Vec::<u8>::from_hex(hex)
             .map_err(|_| Error::ElementsRpcUnexpectedReturn(method.into()))?;
-        let tx: Transaction = deserialize(&bytes[..])
-            .map_err(|_| Error::ElementsRpcUnexpectedReturn(method.into()))?;
-        let txout = tx
-            .output
-            .get(outpoint.vout as usize)
-            .ok_or_else(|| Error::ElementsRpcUnexpectedReturn(method.into()))?
-            .clone();
-        Ok(txout)
+        deserialize(&bytes[..]).map_err(|_| Error::ElementsRpcUnexpectedReturn(method.into()))
+    }
+
+    fn block_hash(&self, height: Height) -> Result<BlockHash, Error> {
+        Ok(self.inner.call("getblockhash", &[height.into()])?)
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block, Error> {
+        self.call_hex("getblock", &[hash.to_string().into(), 0.into()])
+    }
+
+    fn block_header(&self, hash: &BlockHash) -> Result<BlockHeader, Error> {
+        self.call_hex("getblockheader", &[hash.to_string().into(), false.into()])
+    }
+
+    /// Get a transaction, without `-txindex` the block containing it must be given unless it's in
+    /// the mempool
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        blockhash: Option<BlockHash>,
+    ) -> Result<Transaction, Error> {
+        let mut params = vec![txid.to_string().into(), false.into()];
+        if let Some(blockhash) = blockhash {
+            params.push(blockhash.to_string().into());
+        }
+        self.call_hex("getrawtransaction", &params)
+    }
+
+    fn lock_index(&self) -> MutexGuard<'_, ScriptIndex> {
+        // the index is consistent also if a scan panicked, at worst some blocks are scanned again
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Scan the blocks and the mempool transactions not scanned yet
+    fn sync_index(&self) -> Result<(), Error> {
+        let mut index = self.lock_index();
+        if let Some((height, hash)) = index.tip {
+            if self.block_hash(height)? != hash {
+                log::info!("reorg detected at height {height}, scanning again");
+                *index = ScriptIndex::default();
+            }
+        }
+        let height = self.height()? as Height;
+        let start = index.tip.map_or(self.scan_from, |(h, _)| h + 1);
+        for height in start..=height {
+            let hash = self.block_hash(height)?;
+            let block = self.block(&hash)?;
+            index.index_block(height, &block);
+            if let Some(scanner) = self.payment_code_scanner.as_ref() {
+                let received = scanner.scan_block(&block)?;
+                index
+                    .payment_code_received
+                    .extend(received.into_iter().map(|r| (height, r)));
+            }
+        }
+
+        let txids: HashSet<Txid> = self.inner.call("getrawmempool", &[])?;
+        let mut mempool = std::mem::take(&mut index.mempool);
+        mempool.retain(|txid, _| txids.contains(txid));
+        for txid in txids {
+            if let Entry::Vacant(entry) = mempool.entry(txid) {
+                // the transaction could have been confirmed or evicted in the meantime
+                if let Ok(tx) = self.get_raw_transaction(&txid, None) {
+                    entry.insert(tx);
+                }
+            }
+        }
+        index.set_mempool(mempool);
+        Ok(())
     }
 
     /// Get the confirmed utxos for a descriptor
@@ -115,7 +246,13 @@ impl ElementsRpcClient {
                 .get(&u.script_pubkey)
                 .ok_or_else(|| Error::ElementsRpcUnexpectedReturn("scantxoutset".into()))?;
             let txout = self.get_txout(&outpoint, u.height)?;
-            let unblinded = try_unblind(txout, desc)?;
+            let unblinded = match try_unblind(txout, desc) {
+                Ok(unblinded) => unblinded,
+                Err(e) => {
+                    log::info!("{outpoint} cannot unblind, ignoring: {e}");
+                    continue;
+                }
+            };
             let address =
                 Address::from_script(&u.script_pubkey, None, self.network.address_params())
                     .expect("used descriptors have addresses"); // TODO: get blinding key
@@ -134,6 +271,78 @@ impl ElementsRpcClient {
     }
 }
 
+impl BlockchainBackend for ElementsRpcClient {
+    fn tip(&mut self) -> Result<BlockHeader, Error> {
+        let hash: BlockHash = self.inner.call("getbestblockhash", &[])?;
+        self.block_header(&hash)
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, Error> {
+        Ok(self
+            .inner
+            .call("sendrawtransaction", &[serialize_hex(tx).into()])?)
+    }
+
+    fn test_broadcast(&self, tx: &Transaction) -> Result<Option<MempoolAccept>, Error> {
+        let rawtxs: Value = vec![serialize_hex(tx)].into();
+        let r: Vec<Value> = self.inner.call("testmempoolaccept", &[rawtxs])?;
+        let r = r
+            .first()
+            .ok_or_else(|| Error::Generic("empty testmempoolaccept result".to_string()))?;
+        Ok(Some(MempoolAccept {
+            allowed: r.get("allowed").and_then(Value::as_bool).unwrap_or(false),
+            reject_reason: r
+                .get("reject-reason")
+                .and_then(Value::as_str)
+                .map(ToString::to_string),
+        }))
+    }
+
+    fn get_transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, Error> {
+        let index = self.lock_index();
+        txids
+            .iter()
+            .map(|txid| match index.mempool.get(txid) {
+                Some(tx) => Ok(tx.clone()),
+                None => self.get_raw_transaction(txid, index.tx_blocks.get(txid).cloned()),
+            })
+            .collect()
+    }
+
+    fn get_headers(
+        &self,
+        heights: &[Height],
+        height_blockhash: &HashMap<Height, BlockHash>,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        heights
+            .iter()
+            .map(|height| {
+                let hash = match height_blockhash.get(height) {
+                    Some(hash) => *hash,
+                    None => self.block_hash(*height)?,
+                };
+                self.block_header(&hash)
+            })
+            .collect()
+    }
+
+    fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
+        self.sync_index()?;
+        let index = self.lock_index();
+        Ok(scripts.iter().map(|script| index.history(script)).collect())
+    }
+
+    fn estimate_fee(&self, target: u16) -> Result<f32, Error> {
+        let r: Value = self.inner.call("estimatesmartfee", &[target.into()])?;
+        // the fee rate is in BTC/kvB, missing if the node doesn't have enough data
+        let fee_rate = r
+            .get("feerate")
+            .and_then(Value::as_f64)
+            .ok_or(Error::FeeEstimateUnavailable(target))?;
+        Ok((fee_rate * 100_000_000.0) as f32)
+    }
+}
+
 #[derive(serde::Serialize)]
 struct ScanObject {
     desc: String,
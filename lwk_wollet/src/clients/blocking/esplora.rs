@@ -4,7 +4,7 @@ use std::collections::{HashMap, HashSet};
 use tokio::runtime::Runtime;
 
 use crate::{
-    clients::{asyncr, Capability, Data, History},
+    clients::{asyncr, Capability, Data, History, TxStatus},
     store::Height,
     wollet::WolletState,
     ElementsNetwork, Error, WolletDescriptor,
@@ -15,6 +15,9 @@ use super::BlockchainBackend;
 #[derive(Debug)]
 /// A blockchain backend implementation based on the
 /// [esplora HTTP API](https://github.com/blockstream/esplora/blob/master/API.md)
+///
+/// It runs the async client on its own runtime, async applications should use
+/// [`asyncr::EsploraClient`] or [`asyncr::AsyncWollet`] instead.
 pub struct EsploraClient {
     rt: Runtime,
     client: asyncr::EsploraClient,
@@ -27,6 +30,15 @@ impl EsploraClient {
             client: asyncr::EsploraClient::new(network, url),
         })
     }
+
+    /// Create a new Esplora client with the options of the given builder, for instance to set
+    /// [`asyncr::EsploraClientBuilder::tls_pins()`]
+    pub fn from_builder(builder: asyncr::EsploraClientBuilder) -> Result<Self, Error> {
+        Ok(Self {
+            rt: Runtime::new()?,
+            client: builder.build(),
+        })
+    }
 }
 
 /// "Waterfalls" methods
@@ -78,12 +90,20 @@ impl BlockchainBackend for EsploraClient {
     }
 
     // examples:
-    // https://blockstream.info/liquidtestnet/api/address/tex1qntw9m0j2e93n84x975t47ddhgkzx3x8lhfv2nj/txs
     // https://blockstream.info/liquidtestnet/api/scripthash/b50a2a798d876db54acfa0d8dfdc49154ea8defed37b225ec4c9ec7415358ba3/txs
+    // https://blockstream.info/liquidtestnet/api/scripthash/b50a2a798d876db54acfa0d8dfdc49154ea8defed37b225ec4c9ec7415358ba3/txs/chain/<last_seen_txid>
     fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
         self.rt.block_on(self.client.get_scripts_history(scripts))
     }
 
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
+        self.rt.block_on(self.client.tx_status(txid))
+    }
+
+    fn estimate_fee(&self, target: u16) -> Result<f32, Error> {
+        self.rt.block_on(self.client.estimate_fee(target))
+    }
+
     fn capabilities(&self) -> HashSet<Capability> {
         self.client.capabilities()
     }
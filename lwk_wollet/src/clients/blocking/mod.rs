@@ -1,18 +1,26 @@
 //! Blocking clients to fetch data from the Blockchain.
 
 use crate::{
-    clients::try_unblind,
+    checkpoints::{chain_anchor, verify_chain, verify_headers, verify_tip},
+    clients::{gap_reached, missing_prevouts, try_unblind, wallet_output_index, TxBudget},
     store::{Height, Timestamp, BATCH_SIZE},
     update::{DownloadTxResult, Update},
     wollet::WolletState,
-    BlindingPublicKey, Chain, Error, WolletDescriptor,
+    BlindingPublicKey, Chain, Error, MempoolAccept, WolletDescriptor,
 };
 use elements::{bitcoin::bip32::ChildNumber, OutPoint, Script};
 use elements::{BlockHash, BlockHeader, Transaction, Txid};
 use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
+mod block_files;
+mod broadcast;
 #[cfg(feature = "esplora")]
 mod esplora;
+mod script_index;
+
+pub use block_files::BlockFilesClient;
+pub use broadcast::{broadcast_all, BackendBroadcast, BroadcastResults, Broadcaster};
 
 #[cfg(feature = "esplora")]
 pub use esplora::EsploraClient;
@@ -20,7 +28,10 @@ pub use esplora::EsploraClient;
 #[cfg(feature = "elements_rpc")]
 pub use elements_rpc_client::ElementsRpcClient;
 
-use super::{Capability, Data, History, LastUnused};
+use super::{
+    tx_status_from_history, tx_status_script, Capability, Data, History, LastUnused,
+    ScriptCheckpoint, TxStatus, CONFIRMATION_POLL_INTERVAL,
+};
 
 #[cfg(feature = "electrum")]
 pub(crate) mod electrum_client;
@@ -51,6 +62,124 @@ pub trait BlockchainBackend {
     /// Get the transactions involved in a list of scripts
     fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error>;
 
+    /// Get the status of a list of scripts as defined by the electrum protocol, in hex
+    ///
+    /// The status of a script without history is `None`. Returns `None` if the backend doesn't
+    /// support script statuses, in this case the history of every script is always downloaded.
+    fn get_scripts_status(
+        &mut self,
+        _scripts: &[&Script],
+    ) -> Result<Option<Vec<Option<String>>>, Error> {
+        Ok(None)
+    }
+
+    /// Get the history of a list of scripts, reusing the checkpoints of the scripts whose status
+    /// didn't change and collecting the new checkpoints in `checkpoints`
+    fn get_scripts_history_checkpointed<S: WolletState>(
+        &mut self,
+        scripts: &[&Script],
+        state: &S,
+        checkpoints: &mut Vec<(Script, ScriptCheckpoint)>,
+    ) -> Result<Vec<Vec<History>>, Error> {
+        let statuses = match self.get_scripts_status(scripts)? {
+            Some(statuses) => statuses,
+            None => return self.get_scripts_history(scripts),
+        };
+        let mut result = vec![vec![]; scripts.len()];
+        let mut to_download = vec![];
+        for (i, status) in statuses.into_iter().enumerate() {
+            // A script without status has no history
+            if let Some(status) = status {
+                match state.checkpoint(scripts[i]) {
+                    Some(checkpoint) if checkpoint.status == status => {
+                        result[i] = checkpoint.history
+                    }
+                    _ => to_download.push((i, status)),
+                }
+            }
+        }
+        let to_download_scripts: Vec<_> = to_download.iter().map(|(i, _)| scripts[*i]).collect();
+        let histories = self.get_scripts_history(&to_download_scripts)?;
+        for ((i, status), history) in to_download.into_iter().zip(histories) {
+            checkpoints.push((
+                scripts[i].clone(),
+                ScriptCheckpoint {
+                    status,
+                    history: history.clone(),
+                },
+            ));
+            result[i] = history;
+        }
+        Ok(result)
+    }
+
+    /// Estimate the fee rate in sats/kvb for a transaction to be confirmed within `target` blocks
+    ///
+    /// Backends without fee estimates return [`Error::FeeEstimateUnavailable`].
+    fn estimate_fee(&self, target: u16) -> Result<f32, Error> {
+        Err(Error::FeeEstimateUnavailable(target))
+    }
+
+    /// Check whether `tx` would be accepted by the mempool, without broadcasting it
+    ///
+    /// Backends not supporting the check return `None`.
+    fn test_broadcast(&self, _tx: &Transaction) -> Result<Option<MempoolAccept>, Error> {
+        Ok(None)
+    }
+
+    /// Get the confirmation status of a transaction
+    ///
+    /// By default the transaction is looked up in the history of one of its output scripts,
+    /// backends failing to get the transaction return an error rather than
+    /// [`TxStatus::NotFound`].
+    fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
+        let tx = match self.get_transactions(&[*txid])?.pop() {
+            Some(tx) => tx,
+            None => return Ok(TxStatus::NotFound),
+        };
+        self.tx_status_of(&tx)
+    }
+
+    /// Get the confirmation status of `tx`, already known to the backend
+    fn tx_status_of(&self, tx: &Transaction) -> Result<TxStatus, Error> {
+        let script = match tx_status_script(tx) {
+            Some(script) => script,
+            None => return Ok(TxStatus::Unconfirmed),
+        };
+        let history = self.get_scripts_history(&[script])?;
+        let history = history.first().map(Vec::as_slice).unwrap_or_default();
+        Ok(tx_status_from_history(&tx.txid(), history))
+    }
+
+    /// Wait until the transaction has at least `confirmations`, checking it every few seconds
+    ///
+    /// With 0 confirmations it waits until the transaction is in the mempool. Returns the status
+    /// of the transaction or [`Error::ConfirmationTimeout`] if it's not confirmed within
+    /// `timeout`.
+    fn wait_for_confirmation(
+        &mut self,
+        txid: &Txid,
+        confirmations: u32,
+        timeout: Duration,
+    ) -> Result<TxStatus, Error> {
+        let start = Instant::now();
+        loop {
+            let status = self.tx_status(txid)?;
+            let tip_height = match status {
+                TxStatus::Confirmed { .. } => self.tip()?.height,
+                _ => 0,
+            };
+            if status.is_confirmed(confirmations, tip_height) {
+                return Ok(status);
+            }
+            let remaining = timeout.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                return Err(Error::ConfirmationTimeout(*txid));
+            }
+            std::thread::sleep(remaining.min(CONFIRMATION_POLL_INTERVAL));
+        }
+    }
+
     /// Return the set of [`Capability`] supported by this backend
     fn capabilities(&self) -> HashSet<Capability> {
         HashSet::new()
@@ -67,13 +196,15 @@ pub trait BlockchainBackend {
 
         for descriptor in descriptor.as_single_descriptors()? {
             let mut batch_count = 0;
+            let mut last_used = None;
             let chain: Chain = (&descriptor).try_into().unwrap_or(Chain::External);
             let index = index.max(last_unused[chain]);
             loop {
                 let batch = state.get_script_batch(batch_count, &descriptor)?;
 
                 let s: Vec<_> = batch.value.iter().map(|e| &e.0).collect();
-                let result: Vec<Vec<History>> = self.get_scripts_history(&s)?;
+                let result: Vec<Vec<History>> =
+                    self.get_scripts_history_checkpointed(&s, state, &mut data.checkpoints)?;
                 if !batch.cached {
                     data.scripts.extend(batch.value);
                 }
@@ -84,6 +215,7 @@ pub trait BlockchainBackend {
                     .map(|(i, _)| i as u32)
                     .max();
                 if let Some(max) = max {
+                    last_used = Some(max + batch_count * BATCH_SIZE);
                     match chain {
                         Chain::External => {
                             data.last_unused.external = 1 + max + batch_count * BATCH_SIZE
@@ -96,10 +228,6 @@ pub trait BlockchainBackend {
 
                 let flattened: Vec<History> = result.into_iter().flatten().collect();
 
-                if flattened.is_empty() && index <= 1 + batch_count * BATCH_SIZE {
-                    break;
-                }
-
                 for el in flattened {
                     // el.height = -1 means unconfirmed with unconfirmed parents
                     // el.height =  0 means unconfirmed with confirmed parents
@@ -117,6 +245,14 @@ pub trait BlockchainBackend {
                 }
 
                 batch_count += 1;
+                if gap_reached(
+                    batch_count * BATCH_SIZE,
+                    last_used,
+                    state.gap_limit(),
+                    index,
+                ) {
+                    break;
+                }
             }
         }
         Ok(data)
@@ -163,20 +299,13 @@ pub trait BlockchainBackend {
     ) -> Result<Option<Update>, Error> {
         let descriptor = state.descriptor();
 
-        let Data {
-            txid_height,
-            scripts,
-            last_unused,
-            height_blockhash,
-            height_timestamp: _height_timestamp,
-            tip: _,
-        } = if self.capabilities().contains(&Capability::Waterfalls) {
+        let mut data = if self.capabilities().contains(&Capability::Waterfalls) {
             if index != 0 {
                 return Err(Error::UsingWaterfallsWithNonZeroIndex);
             }
             match self.get_history_waterfalls(&descriptor, state) {
                 Ok(d) => d,
-                Err(Error::UsingWaterfallsWithElip151) => {
+                Err(Error::UsingWaterfallsWithElip151 | Error::WaterfallsUnsupported(_)) => {
                     self.get_history(&descriptor, state, index, state.last_unused())?
                 }
                 Err(e) => return Err(e),
@@ -184,18 +313,45 @@ pub trait BlockchainBackend {
         } else {
             self.get_history(&descriptor, state, index, state.last_unused())?
         };
+        if let Some(birthday) = state.birthday() {
+            data.skip_before(birthday);
+        }
+        let Data {
+            txid_height,
+            scripts,
+            last_unused,
+            height_blockhash,
+            height_timestamp,
+            tip: _,
+            checkpoints,
+        } = data;
 
         let tip = self.tip()?;
+        let block_checkpoints = state.block_checkpoints();
+        verify_tip(&block_checkpoints, &state.block_signers(), &tip)?;
+        if let Some(anchor) = chain_anchor(&block_checkpoints, state.tip(), &tip) {
+            let heights: Vec<Height> = (anchor.0 + 1..tip.height).collect();
+            let headers = self.get_headers(&heights, &HashMap::new())?;
+            verify_chain(anchor, &headers, &tip)?;
+        }
 
         let history_txs_id: HashSet<Txid> = txid_height.keys().cloned().collect();
         let new_txs = self.download_txs(&history_txs_id, &scripts, state, &descriptor)?;
+        let downloaded: HashSet<Txid> = new_txs.txs.iter().map(|(txid, _)| *txid).collect();
         let history_txs_heights_plus_tip: HashSet<Height> = txid_height
             .values()
             .filter_map(|e| *e)
             .chain(std::iter::once(tip.height))
             .collect();
-        let timestamps =
-            self.download_headers(&history_txs_heights_plus_tip, &height_blockhash, state)?;
+        // the header downloaded at the tip height must be the tip
+        let mut height_blockhash = height_blockhash;
+        height_blockhash.insert(tip.height, tip.block_hash());
+        let timestamps = self.download_headers(
+            &history_txs_heights_plus_tip,
+            &height_blockhash,
+            &height_timestamp,
+            state,
+        )?;
 
         let store_last_unused_external = state.last_unused()[Chain::External];
         let store_last_unused_internal = state.last_unused()[Chain::Internal];
@@ -207,13 +363,17 @@ pub trait BlockchainBackend {
             || last_unused_changed
             || !scripts.is_empty()
             || !timestamps.is_empty()
+            || !checkpoints.is_empty()
             || state.tip() != (tip.height, tip.block_hash());
 
         if changed {
             log::debug!("something changed: !new_txs.txs.is_empty():{} last_unused_changed:{} !scripts.is_empty():{} !timestamps.is_empty():{}", !new_txs.txs.is_empty(), last_unused_changed, !scripts.is_empty(), !timestamps.is_empty() );
 
+            // transactions deferred by the memory budget are added by the next scans
+            let txs_in_db = state.txs();
             let txid_height_new: Vec<_> = txid_height
                 .iter()
+                .filter(|(k, _)| txs_in_db.contains(*k) || downloaded.contains(*k))
                 .filter(|(k, v)| match state.heights().get(*k) {
                     Some(e) => e != *v,
                     None => true,
@@ -244,6 +404,7 @@ pub trait BlockchainBackend {
                 timestamps,
                 scripts_with_blinding_pubkey,
                 tip,
+                checkpoints,
             };
             Ok(Some(update))
         } else {
@@ -251,6 +412,29 @@ pub trait BlockchainBackend {
         }
     }
 
+    /// Scan the wallet for the given transaction, even if it pays scripts beyond the gap limit
+    ///
+    /// Useful when a user knows the txid of a deposit that [`BlockchainBackend::full_scan()`]
+    /// didn't find. The transaction is downloaded and its outputs are matched against the wallet
+    /// scripts, deriving them up to `max_index`. The wallet is then scanned up to the index of the
+    /// matching script, so that the transaction is downloaded and unblinded together with the
+    /// other transactions of the skipped scripts.
+    ///
+    /// Returns [`Error::TxNotInWallet`] if no output pays the wallet.
+    fn scan_txid<S: WolletState>(
+        &mut self,
+        state: &S,
+        txid: Txid,
+        max_index: u32,
+    ) -> Result<Option<Update>, Error> {
+        let tx = self
+            .get_transactions(&[txid])?
+            .pop()
+            .ok_or_else(|| Error::Generic(format!("Transaction {txid} not found")))?;
+        let index = wallet_output_index(&tx, state, max_index)?;
+        self.full_scan_to_index(state, index + 1)
+    }
+
     /// Download and unblind the transactions
     fn download_txs<S: WolletState>(
         &self,
@@ -265,7 +449,21 @@ pub trait BlockchainBackend {
         let mut txs_in_db = state.txs().clone();
         let txs_to_download: Vec<Txid> = history_txs_id.difference(&txs_in_db).cloned().collect();
 
-        let txs_downloaded = self.get_transactions(&txs_to_download)?;
+        let mut budget = TxBudget::new(state.memory_budget());
+        let mut txs_downloaded = vec![];
+        for chunk in txs_to_download.chunks(budget.batch_size()) {
+            if budget.exhausted() {
+                log::info!(
+                    "memory budget reached, {} transactions deferred to the next scan",
+                    txs_to_download.len() - txs_downloaded.len()
+                );
+                break;
+            }
+            for tx in self.get_transactions(chunk)? {
+                budget.consume(&tx);
+                txs_downloaded.push(tx);
+            }
+        }
 
         for tx in txs_downloaded.into_iter() {
             let txid = tx.txid();
@@ -292,31 +490,249 @@ pub trait BlockchainBackend {
             txs.push((txid, tx));
         }
 
+        if state.verify_proofs() {
+            let prevouts = missing_prevouts(txs.iter().map(|(_, tx)| tx), &txs_in_db);
+            for tx in self.get_transactions(&prevouts)? {
+                txs.push((tx.txid(), tx));
+            }
+        }
+
         Ok(DownloadTxResult { txs, unblinds })
     }
 
-    /// Download the headers if not available in the store
+    /// The timestamps of the blocks at the given heights not yet known by the wallet, taken from
+    /// the history response if available or from the downloaded headers otherwise
+    ///
+    /// The headers are verified with the checkpoints of the wallet state and `height_blockhash`.
     fn download_headers<S: WolletState>(
         &self,
         history_txs_heights_plus_tip: &HashSet<Height>,
         height_blockhash: &HashMap<Height, BlockHash>,
+        height_timestamp: &HashMap<Height, Timestamp>,
         state: &S,
     ) -> Result<Vec<(Height, Timestamp)>, Error> {
         let mut result = vec![];
-        let heights_in_db: HashSet<Height> =
-            state.heights().iter().filter_map(|(_, h)| *h).collect();
         let heights_to_download: Vec<Height> = history_txs_heights_plus_tip
-            .difference(&heights_in_db)
+            .iter()
+            .filter(|h| state.timestamp(**h).is_none() && !height_timestamp.contains_key(h))
             .cloned()
             .collect();
         if !heights_to_download.is_empty() {
-            for h in self.get_headers(&heights_to_download, height_blockhash)? {
+            let headers = self.get_headers(&heights_to_download, height_blockhash)?;
+            verify_headers(
+                &state.block_checkpoints(),
+                &state.block_signers(),
+                &headers,
+                height_blockhash,
+            )?;
+            for h in headers {
                 result.push((h.height, h.time))
             }
 
             log::debug!("{} headers_downloaded", heights_to_download.len());
         }
 
+        let heights_to_insert = height_timestamp
+            .iter()
+            .filter(|(h, _)| state.timestamp(**h).is_none())
+            .map(|(h, t)| (*h, *t));
+        result.extend(heights_to_insert);
+
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+    use std::str::FromStr;
+
+    use elements::hashes::Hash;
+    use elements::{BlockHash, BlockHeader, Script, Transaction, Txid};
+
+    use super::BlockchainBackend;
+    use crate::clients::{History, ScriptCheckpoint};
+    use crate::store::Height;
+    use crate::{ElementsNetwork, Error, FsPersister, NoPersist, Update, Wollet, WolletDescriptor};
+
+    /// A backend serving statuses and histories from memory
+    struct MockBackend {
+        statuses: Option<HashMap<Script, String>>,
+        histories: HashMap<Script, Vec<History>>,
+        downloaded: RefCell<HashSet<Script>>,
+        downloaded_headers: RefCell<Vec<Height>>,
+    }
+
+    impl BlockchainBackend for MockBackend {
+        fn tip(&mut self) -> Result<BlockHeader, Error> {
+            unimplemented!()
+        }
+        fn broadcast(&self, _tx: &Transaction) -> Result<Txid, Error> {
+            unimplemented!()
+        }
+        fn get_transactions(&self, _txids: &[Txid]) -> Result<Vec<Transaction>, Error> {
+            unimplemented!()
+        }
+        fn get_headers(
+            &self,
+            heights: &[Height],
+            _height_blockhash: &HashMap<Height, BlockHash>,
+        ) -> Result<Vec<BlockHeader>, Error> {
+            self.downloaded_headers.borrow_mut().extend(heights);
+            Ok(heights
+                .iter()
+                .map(|h| {
+                    let mut header = lwk_test_util::liquid_block_header_2_963_520();
+                    header.height = *h;
+                    header.time = *h * 10;
+                    header
+                })
+                .collect())
+        }
+        fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
+            let mut downloaded = self.downloaded.borrow_mut();
+            Ok(scripts
+                .iter()
+                .map(|s| {
+                    downloaded.insert((*s).clone());
+                    self.histories.get(*s).cloned().unwrap_or_default()
+                })
+                .collect())
+        }
+        fn get_scripts_status(
+            &mut self,
+            scripts: &[&Script],
+        ) -> Result<Option<Vec<Option<String>>>, Error> {
+            Ok(self
+                .statuses
+                .as_ref()
+                .map(|statuses| scripts.iter().map(|s| statuses.get(*s).cloned()).collect()))
+        }
+    }
+
+    fn history(i: u8) -> Vec<History> {
+        vec![History {
+            txid: Txid::from_byte_array([i; 32]),
+            height: i as i32,
+            block_hash: None,
+            block_timestamp: None,
+        }]
+    }
+
+    fn descriptor() -> WolletDescriptor {
+        let desc = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        WolletDescriptor::from_str(desc).unwrap()
+    }
+
+    #[test]
+    fn test_scripts_history_checkpointed() {
+        let [a, b, c] = [1u8, 2, 3].map(|i| Script::from(vec![i]));
+        let mut wollet = Wollet::new(
+            ElementsNetwork::LiquidTestnet,
+            NoPersist::new(),
+            descriptor(),
+        )
+        .unwrap();
+        let checkpoint = ScriptCheckpoint {
+            status: "aa".to_string(),
+            history: history(1),
+        };
+        wollet.sync_checkpoints.insert(a.clone(), checkpoint);
+
+        let mut backend = MockBackend {
+            statuses: Some(
+                [(a.clone(), "aa".to_string()), (c.clone(), "cc".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            histories: [(a.clone(), history(9)), (c.clone(), history(3))]
+                .into_iter()
+                .collect(),
+            downloaded: RefCell::new(HashSet::new()),
+            downloaded_headers: RefCell::new(vec![]),
+        };
+        let scripts = [&a, &b, &c];
+        let mut checkpoints = vec![];
+        let result = backend
+            .get_scripts_history_checkpointed(&scripts, &wollet, &mut checkpoints)
+            .unwrap();
+
+        // unchanged scripts use the checkpoint, scripts without status have no history
+        assert_eq!(result, vec![history(1), vec![], history(3)]);
+        assert_eq!(
+            *backend.downloaded.borrow(),
+            [c.clone()].into_iter().collect()
+        );
+        assert_eq!(checkpoints.len(), 1);
+        assert_eq!(checkpoints[0].0, c);
+        assert_eq!(checkpoints[0].1.status, "cc");
+
+        // without status support every history is downloaded
+        backend.statuses = None;
+        let mut checkpoints = vec![];
+        let result = backend
+            .get_scripts_history_checkpointed(&scripts, &wollet, &mut checkpoints)
+            .unwrap();
+        assert_eq!(result, vec![history(9), vec![], history(3)]);
+        assert!(checkpoints.is_empty());
+    }
+
+    #[test]
+    fn test_download_headers() {
+        let mut wollet = Wollet::new(
+            ElementsNetwork::LiquidTestnet,
+            NoPersist::new(),
+            descriptor(),
+        )
+        .unwrap();
+        std::sync::Arc::make_mut(&mut wollet.store.cache)
+            .timestamps
+            .insert(10, 100);
+        let backend = MockBackend {
+            statuses: None,
+            histories: HashMap::new(),
+            downloaded: RefCell::new(HashSet::new()),
+            downloaded_headers: RefCell::new(vec![]),
+        };
+
+        // known timestamps and the ones in the history response are not downloaded
+        let heights: HashSet<Height> = [10, 11, 12].into_iter().collect();
+        let height_timestamp: HashMap<Height, u32> = [(11, 111)].into_iter().collect();
+        let mut timestamps = backend
+            .download_headers(&heights, &HashMap::new(), &height_timestamp, &wollet)
+            .unwrap();
+        timestamps.sort();
+        assert_eq!(timestamps, vec![(11, 111), (12, 120)]);
+        assert_eq!(*backend.downloaded_headers.borrow(), vec![12]);
+    }
+
+    #[test]
+    fn test_sync_checkpoints_persisted() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let desc = descriptor();
+        let new_wollet = || {
+            let persister = FsPersister::new(&tempdir, network, &desc).unwrap();
+            Wollet::new(network, persister, desc.clone()).unwrap()
+        };
+        let script = Script::from(vec![1u8]);
+        let checkpoint = ScriptCheckpoint {
+            status: "aa".to_string(),
+            history: history(1),
+        };
+
+        let mut wollet = new_wollet();
+        let mut update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        update.wollet_status = wollet.status();
+        update.checkpoints = vec![(script.clone(), checkpoint.clone())];
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.sync_checkpoints.get(&script), Some(&checkpoint));
+
+        let mut wollet = new_wollet();
+        assert_eq!(wollet.sync_checkpoints.get(&script), Some(&checkpoint));
+
+        wollet.clear_sync_checkpoints().unwrap();
+        assert!(new_wollet().sync_checkpoints.is_empty());
+    }
+}
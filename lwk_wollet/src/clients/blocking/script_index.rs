@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+use elements::{Block, BlockHash, OutPoint, Script, Transaction, Txid};
+
+use crate::{clients::History, payment_code::PaymentCodeReceived, store::Height};
+
+/// The histories of the scripts involved in the scanned blocks and in the mempool
+#[derive(Default)]
+pub(crate) struct ScriptIndex {
+    /// The height and hash of the last scanned block
+    pub(crate) tip: Option<(Height, BlockHash)>,
+
+    /// The confirmed histories of the scripts
+    histories: HashMap<Script, Vec<History>>,
+
+    /// The scripts of the scanned outputs, to find the scripts involved by the spending
+    /// transactions
+    outputs: HashMap<OutPoint, Script>,
+
+    /// The blocks containing the scanned transactions, to fetch them without `-txindex`
+    pub(crate) tx_blocks: HashMap<Txid, BlockHash>,
+
+    /// The transactions in the mempool
+    #[cfg_attr(not(feature = "elements_rpc"), allow(dead_code))]
+    pub(crate) mempool: HashMap<Txid, Transaction>,
+
+    /// The unconfirmed histories of the scripts, computed from `mempool`
+    mempool_histories: HashMap<Script, Vec<History>>,
+
+    /// The outputs paying a payment code found in the scanned blocks
+    #[cfg_attr(not(feature = "elements_rpc"), allow(dead_code))]
+    pub(crate) payment_code_received: Vec<(Height, PaymentCodeReceived)>,
+}
+
+impl ScriptIndex {
+    pub(crate) fn index_block(&mut self, height: Height, block: &Block) {
+        let block_hash = block.block_hash();
+        for tx in block.txdata.iter() {
+            let history = History {
+                txid: tx.txid(),
+                height: height as i32,
+                block_hash: Some(block_hash),
+                block_timestamp: Some(block.header.time),
+            };
+            let scripts = involved_scripts(tx, |o| self.outputs.get(o).cloned());
+            for script in scripts {
+                self.histories
+                    .entry(script)
+                    .or_default()
+                    .push(history.clone());
+            }
+            for (vout, output) in tx.output.iter().enumerate() {
+                if !output.is_fee() && !output.script_pubkey.is_empty() {
+                    let outpoint = OutPoint::new(history.txid, vout as u32);
+                    self.outputs.insert(outpoint, output.script_pubkey.clone());
+                }
+            }
+            self.tx_blocks.insert(history.txid, block_hash);
+        }
+        self.tip = Some((height, block_hash));
+    }
+
+    /// Used only by the elements node backend, the block files don't include the mempool
+    #[cfg_attr(not(feature = "elements_rpc"), allow(dead_code))]
+    pub(crate) fn set_mempool(&mut self, mempool: HashMap<Txid, Transaction>) {
+        let mut outputs = HashMap::new();
+        for (txid, tx) in mempool.iter() {
+            for (vout, output) in tx.output.iter().enumerate() {
+                outputs.insert(
+                    OutPoint::new(*txid, vout as u32),
+                    output.script_pubkey.clone(),
+                );
+            }
+        }
+        self.mempool_histories.clear();
+        for (txid, tx) in mempool.iter() {
+            let history = History {
+                txid: *txid,
+                height: 0,
+                block_hash: None,
+                block_timestamp: None,
+            };
+            let scripts = involved_scripts(tx, |o| {
+                self.outputs.get(o).or_else(|| outputs.get(o)).cloned()
+            });
+            for script in scripts {
+                self.mempool_histories
+                    .entry(script)
+                    .or_default()
+                    .push(history.clone());
+            }
+        }
+        self.mempool = mempool;
+    }
+
+    pub(crate) fn history(&self, script: &Script) -> Vec<History> {
+        let confirmed = self.histories.get(script).into_iter().flatten();
+        let unconfirmed = self.mempool_histories.get(script).into_iter().flatten();
+        confirmed.chain(unconfirmed).cloned().collect()
+    }
+}
+
+/// The scripts of the outputs created and spent by `tx`, `prevout_script` returns the script of a
+/// spent output if known
+fn involved_scripts(
+    tx: &Transaction,
+    prevout_script: impl Fn(&OutPoint) -> Option<Script>,
+) -> HashSet<Script> {
+    let spent = tx
+        .input
+        .iter()
+        .filter(|i| !i.is_pegin() && !i.is_coinbase())
+        .filter_map(|i| prevout_script(&i.previous_output));
+    let created = tx
+        .output
+        .iter()
+        .filter(|o| !o.is_fee() && !o.script_pubkey.is_empty())
+        .map(|o| o.script_pubkey.clone());
+    spent.chain(created).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use elements::{Block, OutPoint};
+
+    use super::ScriptIndex;
+    use crate::Update;
+
+    #[test]
+    fn test_script_index() {
+        let update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        let tx = update.new_txs.txs[0].1.clone();
+        let txid = tx.txid();
+        let block = Block {
+            header: lwk_test_util::liquid_block_header_2_963_520(),
+            txdata: vec![tx.clone()],
+        };
+        let mut index = ScriptIndex::default();
+        index.index_block(10, &block);
+        assert_eq!(index.tip, Some((10, block.block_hash())));
+        assert_eq!(index.tx_blocks.get(&txid), Some(&block.block_hash()));
+
+        let script = &tx.output[0].script_pubkey;
+        let history = index.history(script);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].txid, txid);
+        assert_eq!(history[0].height, 10);
+        assert_eq!(history[0].block_timestamp, Some(block.header.time));
+
+        // an unconfirmed transaction spending the first output
+        let mut spending = tx.clone();
+        spending.input[0].previous_output = OutPoint::new(txid, 0);
+        spending.output.clear();
+        let spending_txid = spending.txid();
+        index.set_mempool(HashMap::from([(spending_txid, spending)]));
+        let history = index.history(script);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].txid, spending_txid);
+        assert_eq!(history[1].height, 0);
+        assert_eq!(history[1].block_hash, None);
+
+        // the transaction is no more in the mempool
+        index.set_mempool(HashMap::new());
+        assert_eq!(index.history(script).len(), 1);
+    }
+}
@@ -1,20 +1,22 @@
 //! Clients to fetch data from the Blockchain.
 
 use crate::{
-    store::{Height, Timestamp},
+    store::{Height, Timestamp, BATCH_SIZE},
+    wollet::WolletState,
     BlindingPublicKey, Chain, Error, WolletDescriptor, EC,
 };
 use elements::{
     bitcoin::bip32::ChildNumber,
     confidential::{Asset, Nonce, Value},
-    Script, TxOut, TxOutSecrets,
+    Script, Transaction, TxOut, TxOutSecrets,
 };
 use elements::{BlockHash, Txid};
 use lwk_common::derive_blinding_key;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ops::{Index, IndexMut},
+    time::Duration,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -22,6 +24,9 @@ pub mod blocking;
 
 pub mod asyncr;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "tls"))]
+pub mod tls;
+
 /// Last unused derivation index for each chain.
 /// In other words the next index to be used when creating a new internal or external address.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -59,6 +64,23 @@ pub struct Data {
     pub height_blockhash: HashMap<Height, BlockHash>,
     pub height_timestamp: HashMap<Height, Timestamp>,
     pub tip: Option<BlockHash>,
+
+    /// Checkpoints of the scripts whose history has been downloaded
+    pub checkpoints: Vec<(Script, ScriptCheckpoint)>,
+}
+
+impl Data {
+    /// Ignore the transactions confirmed before the given height
+    ///
+    /// Scripts keep being considered used, so that addresses are not reused.
+    pub(crate) fn skip_before(&mut self, birthday: Height) {
+        self.txid_height
+            .retain(|_, height| height.map_or(true, |h| h >= birthday));
+        self.height_blockhash
+            .retain(|height, _| *height >= birthday);
+        self.height_timestamp
+            .retain(|height, _| *height >= birthday);
+    }
 }
 
 /// Capabilities that can be supported by a [`blocking::BlockchainBackend`]
@@ -68,7 +90,7 @@ pub enum Capability {
     Waterfalls,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 /// Position of a transaction involving a certain script
 pub struct History {
     /// Transaction ID
@@ -87,7 +109,134 @@ pub struct History {
     pub block_timestamp: Option<Timestamp>,
 }
 
+/// The confirmation status of a transaction according to a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxStatus {
+    /// The transaction is in a block at the given height
+    Confirmed {
+        /// The height of the block including the transaction
+        height: Height,
+    },
+
+    /// The transaction is in the mempool
+    Unconfirmed,
+
+    /// The backend doesn't know the transaction
+    NotFound,
+}
+
+impl TxStatus {
+    /// The number of confirmations of the transaction when the tip is at `tip_height`
+    pub fn confirmations(&self, tip_height: Height) -> u32 {
+        match self {
+            TxStatus::Confirmed { height } => (tip_height + 1).saturating_sub(*height),
+            TxStatus::Unconfirmed | TxStatus::NotFound => 0,
+        }
+    }
+
+    /// Whether the transaction has at least `confirmations` when the tip is at `tip_height`
+    ///
+    /// With 0 confirmations, a transaction in the mempool is enough.
+    pub fn is_confirmed(&self, confirmations: u32, tip_height: Height) -> bool {
+        match self {
+            TxStatus::NotFound => false,
+            _ => self.confirmations(tip_height) >= confirmations,
+        }
+    }
+}
+
+/// How often the confirmation of a transaction is checked while waiting for it
+pub(crate) const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The history of a script together with the status the script had when it was downloaded
+///
+/// Backends supporting script statuses (such as Electrum) don't download again the history of
+/// scripts whose status didn't change since the checkpoint, see
+/// [`crate::Wollet::clear_sync_checkpoints()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptCheckpoint {
+    /// The status of the script as defined by the electrum protocol, in hex
+    pub status: String,
+
+    /// The history of the script
+    pub history: Vec<History>,
+}
+
+/// How a client retries the requests failing for transient reasons, such as a server rate
+/// limiting the requests or, if [`RetryPolicy::transient`], a timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt
+    pub max_retries: u8,
+
+    /// The wait before the first retry, doubling at every following retry
+    pub initial_backoff: Duration,
+
+    /// The maximum wait between two retries
+    pub max_backoff: Duration,
+
+    /// Wait a random time between half and the whole backoff, so that clients rate limited at
+    /// the same time don't retry all together
+    pub jitter: bool,
+
+    /// Retry also the requests failing with timeouts or connection errors
+    ///
+    /// False by default, an unreachable server would make every request wait for all the
+    /// retries before failing.
+    pub transient: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 7,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(64),
+            jitter: true,
+            transient: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn no_retry() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// The wait before the retry number `retry`, starting from 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1u32 << retry.min(31))
+            .min(self.max_backoff)
+    }
+
+    /// The wait before the retry number `retry`, starting from 0, with jitter if enabled
+    ///
+    /// If the server asked to wait for `retry_after`, the wait is at least that, capped at
+    /// [`RetryPolicy::max_backoff`].
+    pub fn wait(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self.backoff(retry);
+        let wait = if self.jitter {
+            let half = backoff / 2;
+            half + half.mul_f64(rand::random::<f64>())
+        } else {
+            backoff
+        };
+        match retry_after {
+            Some(retry_after) => wait.max(retry_after.min(self.max_backoff)),
+            None => wait,
+        }
+    }
+}
+
 pub fn try_unblind(output: TxOut, descriptor: &WolletDescriptor) -> Result<TxOutSecrets, Error> {
+    if !descriptor.can_unblind() {
+        return Err(Error::BlindingBareUnsupported);
+    }
     match (output.asset, output.value, output.nonce) {
         (Asset::Confidential(_), Value::Confidential(_), Nonce::Confidential(_)) => {
             let receiver_sk = derive_blinding_key(descriptor.as_ref(), &output.script_pubkey)
@@ -102,6 +251,120 @@ pub fn try_unblind(output: TxOut, descriptor: &WolletDescriptor) -> Result<TxOut
     }
 }
 
+/// Whether the scan of a chain can stop
+///
+/// `scanned` scripts have been scanned, the last used one is at `last_used`. The scan stops when
+/// there are at least `gap_limit` consecutive unused scripts and the given `index` is reached.
+pub(crate) fn gap_reached(
+    scanned: u32,
+    last_used: Option<u32>,
+    gap_limit: u32,
+    index: u32,
+) -> bool {
+    let unused = scanned - last_used.map_or(0, |l| l + 1);
+    unused >= gap_limit.max(1) && index <= 1 + scanned.saturating_sub(BATCH_SIZE)
+}
+
+/// A rough estimate of the size of a confidential transaction, used to size the download requests
+const ESTIMATED_TX_SIZE: usize = 10_000;
+
+/// Accounts the memory used by the transactions downloaded during a scan
+///
+/// Downloaded transactions are held in the [`crate::Update`] until it's applied, see
+/// [`crate::Config::with_memory_budget()`].
+pub(crate) struct TxBudget {
+    budget: Option<usize>,
+    used: usize,
+}
+
+impl TxBudget {
+    pub(crate) fn new(budget: Option<usize>) -> Self {
+        Self { budget, used: 0 }
+    }
+
+    /// The number of transactions to request at once
+    pub(crate) fn batch_size(&self) -> usize {
+        self.budget
+            .map_or(usize::MAX, |b| (b / ESTIMATED_TX_SIZE).max(1))
+    }
+
+    /// Whether no more transactions should be downloaded in this scan
+    pub(crate) fn exhausted(&self) -> bool {
+        self.budget.is_some_and(|b| self.used >= b)
+    }
+
+    pub(crate) fn consume(&mut self, tx: &Transaction) {
+        self.used += tx.size();
+    }
+}
+
+/// The previous transactions of `txs` that are not in `known`, needed to verify the proofs
+pub(crate) fn missing_prevouts<'a>(
+    txs: impl Iterator<Item = &'a Transaction>,
+    known: &HashSet<Txid>,
+) -> Vec<Txid> {
+    let mut missing = HashSet::new();
+    for tx in txs {
+        for input in tx.input.iter() {
+            let txid = input.previous_output.txid;
+            if !input.is_pegin() && !input.is_coinbase() && !known.contains(&txid) {
+                missing.insert(txid);
+            }
+        }
+    }
+    missing.into_iter().collect()
+}
+
+/// A script of `tx` whose history contains it, the first output not paying the fee
+pub(crate) fn tx_status_script(tx: &Transaction) -> Option<&Script> {
+    tx.output
+        .iter()
+        .find(|o| !o.is_fee() && !o.script_pubkey.is_empty())
+        .map(|o| &o.script_pubkey)
+}
+
+/// The status of the transaction `txid` found in `history`
+///
+/// A transaction known by the backend but missing from the history is considered unconfirmed.
+pub(crate) fn tx_status_from_history(txid: &Txid, history: &[History]) -> TxStatus {
+    match history.iter().find(|h| h.txid == *txid) {
+        Some(h) if h.height > 0 => TxStatus::Confirmed {
+            height: h.height as Height,
+        },
+        _ => TxStatus::Unconfirmed,
+    }
+}
+
+/// The highest derivation index of the wallet scripts receiving an output of `tx`
+///
+/// The scripts already derived by the wallet are checked first, then the scripts of both chains
+/// are derived up to `max_index`, so that outputs beyond the gap limit can be found.
+pub(crate) fn wallet_output_index<S: WolletState>(
+    tx: &Transaction,
+    state: &S,
+    max_index: u32,
+) -> Result<u32, Error> {
+    let scripts: HashSet<&Script> = tx.output.iter().map(|o| &o.script_pubkey).collect();
+    let paths = state.paths();
+    let known = scripts
+        .iter()
+        .filter_map(|s| paths.get(*s))
+        .map(|(_, child)| u32::from(*child))
+        .max();
+    if let Some(index) = known {
+        return Ok(index);
+    }
+    let descriptor = state.descriptor();
+    for index in 0..=max_index {
+        for chain in [Chain::External, Chain::Internal] {
+            if scripts.contains(&descriptor.script_pubkey(chain, index)?) {
+                return Ok(index);
+            }
+        }
+    }
+    Err(Error::TxNotInWallet(tx.txid(), max_index))
+}
+
 pub(crate) fn check_witnesses_non_empty(tx: &elements::Transaction) -> Result<(), Error> {
     if tx.input.iter().any(|e| e.witness.is_empty()) {
         return Err(Error::EmptyWitness);
@@ -111,6 +374,139 @@ pub(crate) fn check_witnesses_non_empty(tx: &elements::Transaction) -> Result<()
 
 #[cfg(test)]
 mod tests {
+    use super::{Data, RetryPolicy};
+    use elements::hashes::Hash;
+    use elements::{BlockHash, Txid};
+    use std::time::Duration;
+
+    #[test]
+    fn test_gap_reached() {
+        use super::gap_reached;
+        // with the default gap limit, stop at the first batch without history
+        assert!(!gap_reached(20, Some(0), 20, 0));
+        assert!(!gap_reached(20, Some(19), 20, 0));
+        assert!(gap_reached(20, None, 20, 0));
+        assert!(gap_reached(40, Some(19), 20, 0));
+
+        assert!(gap_reached(20, Some(9), 10, 0));
+        assert!(!gap_reached(40, Some(9), 50, 0));
+        assert!(gap_reached(80, Some(9), 50, 0));
+
+        // scan at least up to the given index
+        assert!(!gap_reached(20, None, 20, 30));
+        assert!(gap_reached(40, None, 20, 21));
+    }
+
+    #[test]
+    fn test_tx_budget() {
+        use super::TxBudget;
+        let tx = lwk_test_util::liquid_block_1().txdata.remove(0);
+
+        let mut unlimited = TxBudget::new(None);
+        assert_eq!(unlimited.batch_size(), usize::MAX);
+        unlimited.consume(&tx);
+        assert!(!unlimited.exhausted());
+
+        // At least one transaction is always requested
+        assert_eq!(TxBudget::new(Some(1)).batch_size(), 1);
+        assert_eq!(TxBudget::new(Some(100_000)).batch_size(), 10);
+
+        let mut budget = TxBudget::new(Some(tx.size() * 2));
+        budget.consume(&tx);
+        assert!(!budget.exhausted());
+        budget.consume(&tx);
+        assert!(budget.exhausted());
+    }
+
+    #[test]
+    fn test_wallet_output_index() {
+        use super::wallet_output_index;
+        use crate::wollet::tests::test_wollet_with_many_transactions;
+        use crate::{Chain, Error};
+        let wollet = test_wollet_with_many_transactions();
+        let mut tx = wollet.transactions().unwrap()[0].tx.clone();
+        let descriptor = wollet.wollet_descriptor();
+
+        // Paying a script beyond the derived ones
+        tx.output[0].script_pubkey = descriptor.script_pubkey(Chain::Internal, 500).unwrap();
+        for output in tx.output.iter_mut().skip(1) {
+            output.script_pubkey = elements::Script::new();
+        }
+        assert!(matches!(
+            wallet_output_index(&tx, &wollet, 10),
+            Err(Error::TxNotInWallet(_, 10))
+        ));
+        assert_eq!(wallet_output_index(&tx, &wollet, 600).unwrap(), 500);
+
+        // Known scripts are found without deriving
+        tx.output[1].script_pubkey = descriptor.script_pubkey(Chain::External, 0).unwrap();
+        tx.output[0].script_pubkey = elements::Script::new();
+        assert_eq!(wallet_output_index(&tx, &wollet, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_skip_before() {
+        let txid = |i: u8| Txid::from_byte_array([i; 32]);
+        let mut data = Data::default();
+        data.txid_height.insert(txid(1), Some(10));
+        data.txid_height.insert(txid(2), Some(20));
+        data.txid_height.insert(txid(3), None);
+        data.height_blockhash.insert(10, BlockHash::all_zeros());
+        data.height_blockhash.insert(20, BlockHash::all_zeros());
+        data.height_timestamp.insert(10, 1);
+        data.last_unused.external = 2;
+
+        data.skip_before(20);
+        assert_eq!(data.txid_height.len(), 2);
+        assert!(!data.txid_height.contains_key(&txid(1)));
+        assert_eq!(data.height_blockhash.len(), 1);
+        assert!(data.height_timestamp.is_empty());
+        assert_eq!(data.last_unused.external, 2);
+    }
+
+    #[test]
+    fn test_tx_status() {
+        use super::TxStatus;
+        let confirmed = TxStatus::Confirmed { height: 100 };
+        assert_eq!(confirmed.confirmations(100), 1);
+        assert_eq!(confirmed.confirmations(105), 6);
+        assert_eq!(confirmed.confirmations(99), 0);
+        assert!(confirmed.is_confirmed(6, 105));
+        assert!(!confirmed.is_confirmed(7, 105));
+
+        assert_eq!(TxStatus::Unconfirmed.confirmations(100), 0);
+        assert!(TxStatus::Unconfirmed.is_confirmed(0, 100));
+        assert!(!TxStatus::Unconfirmed.is_confirmed(1, 100));
+        assert!(!TxStatus::NotFound.is_confirmed(0, 100));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(6), Duration::from_secs(64));
+        assert_eq!(policy.backoff(7), Duration::from_secs(64));
+        assert_eq!(policy.backoff(100), Duration::from_secs(64));
+
+        for _ in 0..10 {
+            let wait = policy.wait(3, None);
+            assert!(wait >= Duration::from_secs(4) && wait <= Duration::from_secs(8));
+        }
+        let wait = policy.wait(0, Some(Duration::from_secs(30)));
+        assert_eq!(wait, Duration::from_secs(30));
+        let wait = policy.wait(0, Some(Duration::from_secs(3600)));
+        assert_eq!(wait, Duration::from_secs(64));
+
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.wait(3, None), Duration::from_secs(8));
+        assert_eq!(RetryPolicy::no_retry().max_retries, 0);
+        assert!(!RetryPolicy::default().transient);
+    }
+
     /*
     use std::time::Instant;
 
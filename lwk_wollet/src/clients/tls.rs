@@ -0,0 +1,250 @@
+//! Certificate pinning for TLS connections to Electrum and Esplora servers
+//!
+//! Pinning makes the connection fail unless the server presents one of the expected
+//! certificates, protecting against compromised certificate authorities or other MITM.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use elements::bitcoin::hashes::{sha256, Hash};
+use elements::hex::{FromHex, ToHex};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::Error;
+
+/// A pin of the certificate presented by a server over TLS
+///
+/// Parsed from and displayed as `cert-sha256:<hex>` or `spki-sha256:<hex>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TlsPin {
+    /// The SHA256 of the DER encoded server certificate
+    Certificate(sha256::Hash),
+
+    /// The SHA256 of the DER encoded subject public key info of the server certificate
+    ///
+    /// Differently from [`TlsPin::Certificate`] it remains valid when the certificate is renewed
+    /// with the same key.
+    PublicKey(sha256::Hash),
+}
+
+impl TlsPin {
+    /// Create the pins matching the given DER encoded certificate
+    pub fn from_certificate(der: &[u8]) -> Result<[TlsPin; 2], Error> {
+        let der = CertificateDer::from(der);
+        let spki = spki(&der)?;
+        Ok([
+            TlsPin::Certificate(sha256::Hash::hash(der.as_ref())),
+            TlsPin::PublicKey(sha256::Hash::hash(&spki)),
+        ])
+    }
+
+    fn matches(&self, der: &CertificateDer) -> bool {
+        match self {
+            TlsPin::Certificate(hash) => *hash == sha256::Hash::hash(der.as_ref()),
+            TlsPin::PublicKey(hash) => {
+                spki(der).is_ok_and(|spki| *hash == sha256::Hash::hash(&spki))
+            }
+        }
+    }
+}
+
+impl FromStr for TlsPin {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, hex) = s
+            .split_once(':')
+            .ok_or_else(|| Error::InvalidTlsPin(s.to_string()))?;
+        let bytes = <[u8; 32]>::from_hex(hex).map_err(|_| Error::InvalidTlsPin(s.to_string()))?;
+        let hash = sha256::Hash::from_byte_array(bytes);
+        match kind {
+            "cert-sha256" => Ok(TlsPin::Certificate(hash)),
+            "spki-sha256" => Ok(TlsPin::PublicKey(hash)),
+            _ => Err(Error::InvalidTlsPin(s.to_string())),
+        }
+    }
+}
+
+impl Display for TlsPin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsPin::Certificate(hash) => write!(f, "cert-sha256:{}", hash[..].to_hex()),
+            TlsPin::PublicKey(hash) => write!(f, "spki-sha256:{}", hash[..].to_hex()),
+        }
+    }
+}
+
+fn spki(der: &CertificateDer) -> Result<Vec<u8>, Error> {
+    let cert = webpki::EndEntityCert::try_from(der)
+        .map_err(|e| Error::Generic(format!("Invalid certificate: {e}")))?;
+    Ok(cert.subject_public_key_info().as_ref().to_vec())
+}
+
+/// Accept only certificates matching at least one of the pins
+///
+/// If `inner` is given the certificate must also be valid for the server name.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<TlsPin>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(inner) = self.inner.as_ref() {
+            inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        if self.pins.iter().any(|pin| pin.matches(end_entity)) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "Server certificate doesn't match any pin".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// A TLS configuration accepting only server certificates matching one of the `pins`
+///
+/// If `validate_domain` is true the certificate must also be valid for the server domain
+/// according to the usual web PKI roots.
+pub(crate) fn pinned_client_config(
+    pins: &[TlsPin],
+    validate_domain: bool,
+) -> Result<ClientConfig, Error> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let inner = if validate_domain {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let verifier =
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|e| Error::Generic(e.to_string()))?;
+        Some(verifier)
+    } else {
+        None
+    };
+    let verifier = PinnedVerifier {
+        inner,
+        pins: pins.to_vec(),
+        provider: provider.clone(),
+    };
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| Error::Generic(e.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustls::client::danger::ServerCertVerifier;
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+
+    use super::{PinnedVerifier, TlsPin};
+
+    #[test]
+    fn test_tls_pin_roundtrip() {
+        let hex = "8e1b5a9b1e2c7e1e5f1d6c9a3b8f2d4e6a0c1b3d5f7e9a1c3e5b7d9f1a3c5e7b";
+        for s in [format!("cert-sha256:{hex}"), format!("spki-sha256:{hex}")] {
+            let pin: TlsPin = s.parse().unwrap();
+            assert_eq!(pin.to_string(), s);
+        }
+        assert!(matches!(
+            format!("cert-sha256:{hex}").parse().unwrap(),
+            TlsPin::Certificate(_)
+        ));
+
+        for invalid in [
+            hex.to_string(),
+            format!("sha1:{hex}"),
+            "cert-sha256:00".to_string(),
+            format!("spki-sha256:{hex}zz"),
+        ] {
+            assert!(invalid.parse::<TlsPin>().is_err(), "{invalid}");
+        }
+        assert!(TlsPin::from_certificate(&[0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn test_pinned_verifier() {
+        let cert = include_bytes!("../../tests/data/tls_cert.der");
+        let other = include_bytes!("../../tests/data/tls_other_cert.der");
+        let [cert_pin, spki_pin] = TlsPin::from_certificate(cert).unwrap();
+        let [other_pin, _] = TlsPin::from_certificate(other).unwrap();
+        assert_ne!(cert_pin, other_pin);
+
+        let verify = |pins: Vec<TlsPin>| {
+            let verifier = PinnedVerifier {
+                inner: None,
+                pins,
+                provider: Arc::new(rustls::crypto::ring::default_provider()),
+            };
+            verifier
+                .verify_server_cert(
+                    &CertificateDer::from(&cert[..]),
+                    &[],
+                    &ServerName::try_from("example.com").unwrap(),
+                    &[],
+                    UnixTime::now(),
+                )
+                .is_ok()
+        };
+        assert!(verify(vec![cert_pin]));
+        assert!(verify(vec![spki_pin]));
+        assert!(verify(vec![other_pin, spki_pin]));
+        assert!(!verify(vec![other_pin]));
+        assert!(!verify(vec![]));
+    }
+}
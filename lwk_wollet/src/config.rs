@@ -1,13 +1,21 @@
 use serde::{Deserialize, Serialize};
 
-use crate::elements::{AddressParams, AssetId};
+use crate::elements::{AddressParams, AssetId, BlockHash, Script, Transaction};
 use crate::error::Error;
+use crate::store::{Height, BATCH_SIZE};
+use crate::ConfirmationPolicy;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 const LIQUID_POLICY_ASSET_STR: &str =
     "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
 const LIQUID_TESTNET_POLICY_ASSET_STR: &str =
     "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49";
+const LIQUID_GENESIS_HASH_STR: &str =
+    "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003";
+const LIQUID_TESTNET_GENESIS_HASH_STR: &str =
+    "a771da8e52ee6ad581ed1e9a99825e5b3b7992225534eaa2ae23244fe26ab1c1";
 
 /// The network of the elements blockchain.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Copy, Hash)]
@@ -30,6 +38,22 @@ impl ElementsNetwork {
         }
     }
 
+    /// Return the hash of the genesis block of this network
+    ///
+    /// Returns `None` for regtest networks since the genesis block depends on the node
+    /// configuration.
+    pub fn genesis_hash(&self) -> Option<BlockHash> {
+        match self {
+            ElementsNetwork::Liquid => {
+                Some(BlockHash::from_str(LIQUID_GENESIS_HASH_STR).expect("can't fail on const"))
+            }
+            ElementsNetwork::LiquidTestnet => Some(
+                BlockHash::from_str(LIQUID_TESTNET_GENESIS_HASH_STR).expect("can't fail on const"),
+            ),
+            ElementsNetwork::ElementsRegtest { .. } => None,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             ElementsNetwork::Liquid => "liquid",
@@ -38,6 +62,22 @@ impl ElementsNetwork {
         }
     }
 
+    /// A name identifying this network, used to namespace data persisted on disk
+    ///
+    /// Unlike [`ElementsNetwork::as_str()`], regtest networks with different policy assets have
+    /// different namespaces, so that wallets of different chains don't share the same data. The
+    /// default regtest network keeps the `liquid-regtest` namespace.
+    pub fn namespace(&self) -> String {
+        match self {
+            ElementsNetwork::ElementsRegtest { policy_asset }
+                if *self != ElementsNetwork::default_regtest() =>
+            {
+                format!("{}-{}", self.as_str(), &policy_asset.to_string()[..16])
+            }
+            _ => self.as_str().to_string(),
+        }
+    }
+
     pub fn address_params(&self) -> &'static AddressParams {
         match self {
             ElementsNetwork::Liquid => &AddressParams::LIQUID,
@@ -82,24 +122,232 @@ impl ElementsNetwork {
     }
 }
 
-#[derive(Debug, Clone, Hash)]
+/// The network parameters used by a [`crate::Wollet`]
+///
+/// By default they are the ones of the [`ElementsNetwork`], but some can be overridden to
+/// support forks of Elements, for instance with custom address prefixes.
+#[derive(Debug, Clone)]
 pub struct Config {
     network: ElementsNetwork,
+    address_params: Option<&'static AddressParams>,
+    genesis_hash: Option<BlockHash>,
+    verify_proofs: bool,
+    birthday: Option<Height>,
+    gap_limit: u32,
+    memory_budget: Option<usize>,
+    confirmation_policy: Option<ConfirmationPolicy>,
+    strict_blinding: bool,
+    explicit_allowed: HashSet<Script>,
+    block_signers: Vec<Script>,
+}
+
+impl Hash for Config {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.network.hash(state);
+        // hashed only if overridden, so that the wallet status doesn't change for default configs
+        if let Some(params) = self.address_params {
+            params.hash(state);
+        }
+        if let Some(genesis_hash) = self.genesis_hash {
+            genesis_hash.hash(state);
+        }
+        if let Some(birthday) = self.birthday {
+            birthday.hash(state);
+        }
+    }
 }
 
 impl Config {
     pub fn new(network: ElementsNetwork) -> Result<Self, Error> {
-        Ok(Config { network })
+        Ok(Config {
+            network,
+            address_params: None,
+            genesis_hash: None,
+            verify_proofs: false,
+            birthday: None,
+            gap_limit: BATCH_SIZE,
+            memory_budget: None,
+            confirmation_policy: None,
+            strict_blinding: false,
+            explicit_allowed: HashSet::new(),
+            block_signers: vec![],
+        })
+    }
+
+    /// Override the address parameters of the network
+    ///
+    /// Note: the transaction builder validates string addresses with the parameters of the
+    /// network, use [`crate::TxBuilder::add_validated_recipient()`] for addresses with custom
+    /// parameters.
+    pub fn with_address_params(mut self, address_params: &'static AddressParams) -> Self {
+        self.address_params = Some(address_params);
+        self
+    }
+
+    /// Override the hash of the genesis block
+    ///
+    /// Needed for regtest networks to compute taproot (BIP341) sighashes.
+    pub fn with_genesis_hash(mut self, genesis_hash: BlockHash) -> Self {
+        self.genesis_hash = Some(genesis_hash);
+        self
+    }
+
+    /// Verify the rangeproofs and surjection proofs of the wallet transactions during sync
+    ///
+    /// When enabled, scans also download the previous transactions of the wallet transactions
+    /// and [`crate::WalletTx::verified`] reports the outcome of the verification, instead of
+    /// relying on the outputs being unblindable only.
+    pub fn with_verify_proofs(mut self, verify_proofs: bool) -> Self {
+        self.verify_proofs = verify_proofs;
+        self
+    }
+
+    /// Set the wallet birthday, the height of the first block that could contain wallet transactions
+    ///
+    /// Scans ignore the transactions confirmed before this height, so they don't have to be
+    /// downloaded, which makes the first scan of newly created descriptors much faster.
+    /// Funds received before the birthday won't be visible to the wallet.
+    pub fn with_birthday(mut self, height: Height) -> Self {
+        self.birthday = Some(height);
+        self
+    }
+
+    /// The wallet birthday height, if set
+    pub fn birthday(&self) -> Option<Height> {
+        self.birthday
+    }
+
+    /// Set the number of consecutive unused scripts after which scans stop, 20 by default
+    ///
+    /// Wallets restored from software giving out many addresses without receiving funds on them
+    /// may need a bigger gap limit to find all their funds, see
+    /// [`crate::Wollet::gap_limit_warning()`].
+    pub fn with_gap_limit(mut self, gap_limit: u32) -> Self {
+        self.gap_limit = gap_limit.max(1);
+        self
+    }
+
+    /// The number of consecutive unused scripts after which scans stop
+    pub fn gap_limit(&self) -> u32 {
+        self.gap_limit
+    }
+
+    /// Limit the memory, in bytes, used by the transactions downloaded during a scan
+    ///
+    /// The transactions downloaded by a scan are held in the returned [`crate::Update`] until
+    /// it's applied. With a budget they are requested in batches sized accordingly and, once the
+    /// budget is reached, the remaining ones are deferred: the update contains only the
+    /// transactions downloaded and the following scans download the others. Scan and apply until
+    /// the update has no new transactions to complete the sync.
+    ///
+    /// The budget is a soft limit: it can be exceeded by the last batch downloaded and by the
+    /// previous transactions downloaded to verify the proofs (see
+    /// [`Config::with_verify_proofs()`]). At least one transaction is downloaded per scan. The
+    /// memory used by the wallet itself, which keeps all its transactions, is not accounted.
+    ///
+    /// By default there is no limit.
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// The memory budget for the transactions downloaded during a scan, if set
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// Set the confirmations after which the wallet transactions are final
+    ///
+    /// The policy is evaluated when updates are applied: the listeners of the wallet receive a
+    /// [`crate::WolletEvent::Final`] event when a transaction reaches the required
+    /// confirmations, see [`crate::Wollet::add_listener()`].
+    pub fn with_confirmation_policy(mut self, policy: ConfirmationPolicy) -> Self {
+        self.confirmation_policy = Some(policy);
+        self
     }
 
+    /// The confirmation policy, if set
+    pub fn confirmation_policy(&self) -> Option<&ConfirmationPolicy> {
+        self.confirmation_policy.as_ref()
+    }
+
+    /// Require every output, except the fee, to be blinded
+    ///
+    /// The transaction builder and [`crate::Wollet::finalize()`] fail with
+    /// [`Error::ExplicitOutput`] instead of creating explicit outputs, for instance to
+    /// unconfidential addresses or burns, unless their script is allowed with
+    /// [`Config::with_explicit_output_allowed()`].
+    pub fn with_strict_blinding(mut self, strict: bool) -> Self {
+        self.strict_blinding = strict;
+        self
+    }
+
+    /// Allow explicit outputs with `script_pubkey` with [`Config::with_strict_blinding()`]
+    pub fn with_explicit_output_allowed(mut self, script_pubkey: Script) -> Self {
+        self.explicit_allowed.insert(script_pubkey);
+        self
+    }
+
+    /// Verify that the headers downloaded by the scans are signed by one of `scripts`, adding them
+    /// to the ones already set
+    ///
+    /// Block signatures are not verified by default, since a change of the signing script, such
+    /// as a federation update, would make the scans fail until it's added. Pass
+    /// [`ElementsNetwork::signblockscripts()`] to verify the known ones, and the new scripts as
+    /// they're adopted.
+    pub fn with_block_signers(mut self, scripts: impl IntoIterator<Item = Script>) -> Self {
+        self.block_signers.extend(scripts);
+        self
+    }
+
+    /// The scripts that must sign the headers downloaded by the scans, not verified if empty
+    pub fn block_signers(&self) -> &[Script] {
+        &self.block_signers
+    }
+
+    /// Whether every output, except the fee, is required to be blinded
+    pub fn strict_blinding(&self) -> bool {
+        self.strict_blinding
+    }
+
+    /// Check that `tx` respects [`Config::with_strict_blinding()`], if set
+    pub fn check_blinding(&self, tx: &Transaction) -> Result<(), Error> {
+        if !self.strict_blinding {
+            return Ok(());
+        }
+        for (vout, output) in tx.output.iter().enumerate() {
+            let blinded = output.value.is_confidential() && output.asset.is_confidential();
+            if !blinded
+                && !output.is_fee()
+                && !self.explicit_allowed.contains(&output.script_pubkey)
+            {
+                return Err(Error::ExplicitOutput(vout as u32));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the proofs of the wallet transactions are verified during sync
+    pub fn verify_proofs(&self) -> bool {
+        self.verify_proofs
+    }
+
+    /// The address parameters, the overridden ones if set or the ones of the network
     pub fn address_params(&self) -> &'static AddressParams {
-        self.network.address_params()
+        self.address_params
+            .unwrap_or_else(|| self.network.address_params())
     }
 
     pub fn policy_asset(&self) -> AssetId {
         self.network.policy_asset()
     }
 
+    /// The hash of the genesis block, the overridden one if set or
+    /// [`ElementsNetwork::genesis_hash()`]
+    pub fn genesis_hash(&self) -> Option<BlockHash> {
+        self.genesis_hash.or_else(|| self.network.genesis_hash())
+    }
+
     pub fn network(&self) -> ElementsNetwork {
         self.network
     }
@@ -113,6 +361,7 @@ mod test {
     };
 
     use super::Config;
+    use crate::elements::AddressParams;
 
     #[test]
     fn test_config_hash() {
@@ -121,4 +370,71 @@ mod test {
         config.hash(&mut hasher);
         assert_eq!(13646096770106105413, hasher.finish());
     }
+
+    #[test]
+    fn test_config_address_params() {
+        let config = Config::new(crate::ElementsNetwork::Liquid).unwrap();
+        assert_eq!(config.address_params(), &AddressParams::LIQUID);
+        assert_eq!(
+            config.genesis_hash().unwrap().to_string(),
+            "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003"
+        );
+
+        let custom = config.clone().with_address_params(&AddressParams::ELEMENTS);
+        assert_eq!(custom.address_params(), &AddressParams::ELEMENTS);
+        assert_eq!(custom.policy_asset(), config.policy_asset());
+
+        let hash = |c: &Config| {
+            let mut hasher = DefaultHasher::new();
+            c.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_ne!(hash(&config), hash(&custom));
+
+        let network = crate::ElementsNetwork::default_regtest();
+        assert!(network.genesis_hash().is_none());
+
+        let regtest = Config::new(network).unwrap();
+        assert!(regtest.genesis_hash().is_none());
+        let genesis_hash = config.genesis_hash().unwrap();
+        let regtest = regtest.with_genesis_hash(genesis_hash);
+        assert_eq!(regtest.genesis_hash(), Some(genesis_hash));
+    }
+
+    #[test]
+    fn test_config_block_signers() {
+        let network = crate::ElementsNetwork::Liquid;
+        let config = Config::new(network).unwrap();
+        assert!(config.block_signers().is_empty());
+
+        let new_federation = crate::elements::Script::from(vec![0x51]);
+        let verified = config
+            .clone()
+            .with_block_signers(network.signblockscripts())
+            .with_block_signers([new_federation.clone()]);
+        assert_eq!(verified.block_signers().len(), 2);
+        assert_eq!(verified.block_signers()[1], new_federation);
+
+        // the wallet status doesn't depend on the verification
+        let hash = |c: &Config| {
+            let mut hasher = DefaultHasher::new();
+            c.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&config), hash(&verified));
+    }
+
+    #[test]
+    fn test_network_namespace() {
+        use crate::ElementsNetwork;
+        assert_eq!(ElementsNetwork::Liquid.namespace(), "liquid");
+        assert_eq!(ElementsNetwork::LiquidTestnet.namespace(), "liquid-testnet");
+        assert_eq!(
+            ElementsNetwork::default_regtest().namespace(),
+            "liquid-regtest"
+        );
+        let policy_asset = ElementsNetwork::Liquid.policy_asset();
+        let other = ElementsNetwork::ElementsRegtest { policy_asset };
+        assert_eq!(other.namespace(), "liquid-regtest-6f0279e9ed041c3d");
+    }
 }
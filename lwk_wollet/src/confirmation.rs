@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+
+use elements::{AssetId, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::store::Height;
+use crate::{Error, WalletTx, Wollet, WolletEvent};
+
+/// The confirmations required by a [`ConfirmationPolicy`] for amounts of at least `min_amount`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ConfirmationRule {
+    asset: Option<AssetId>,
+    min_amount: u64,
+    confirmations: u32,
+}
+
+/// The number of confirmations after which incoming transactions are considered final,
+/// depending on the assets and the amounts received
+///
+/// For instance, to require 2 confirmations for payments under 0.01 L-BTC and 10 above:
+///
+/// ```
+/// # use lwk_wollet::{ConfirmationPolicy, ElementsNetwork};
+/// let lbtc = ElementsNetwork::Liquid.policy_asset();
+/// let policy = ConfirmationPolicy::new(2).with_asset_rule(lbtc, 1_000_000, 10);
+/// assert_eq!(policy.required(&lbtc, 999_999), 2);
+/// assert_eq!(policy.required(&lbtc, 1_000_000), 10);
+/// ```
+///
+/// Set it with [`crate::Config::with_confirmation_policy()`] to get
+/// [`crate::WolletEvent::Final`] events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfirmationPolicy {
+    default: u32,
+    rules: Vec<ConfirmationRule>,
+}
+
+impl Default for ConfirmationPolicy {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl ConfirmationPolicy {
+    /// A policy requiring `confirmations` for any asset and amount, at least 1
+    pub fn new(confirmations: u32) -> Self {
+        Self {
+            default: confirmations.max(1),
+            rules: vec![],
+        }
+    }
+
+    /// Require `confirmations` for amounts of at least `min_amount` of any asset
+    pub fn with_rule(mut self, min_amount: u64, confirmations: u32) -> Self {
+        self.rules.push(ConfirmationRule {
+            asset: None,
+            min_amount,
+            confirmations,
+        });
+        self
+    }
+
+    /// Require `confirmations` for amounts of at least `min_amount` of `asset`
+    pub fn with_asset_rule(mut self, asset: AssetId, min_amount: u64, confirmations: u32) -> Self {
+        self.rules.push(ConfirmationRule {
+            asset: Some(asset),
+            min_amount,
+            confirmations,
+        });
+        self
+    }
+
+    /// The confirmations required to receive `amount` of `asset`
+    ///
+    /// If more rules apply, the greatest number of confirmations is required.
+    pub fn required(&self, asset: &AssetId, amount: u64) -> u32 {
+        self.rules
+            .iter()
+            .filter(|r| r.asset.map_or(true, |a| a == *asset) && amount >= r.min_amount)
+            .map(|r| r.confirmations)
+            .fold(self.default, u32::max)
+    }
+
+    /// The confirmations required by a transaction, considering the amounts it receives
+    pub fn required_for_balance(&self, balance: &BTreeMap<AssetId, i64>) -> u32 {
+        balance
+            .iter()
+            .filter(|(_, v)| **v > 0)
+            .map(|(asset, v)| self.required(asset, *v as u64))
+            .fold(self.default, u32::max)
+    }
+
+    /// Whether a wallet transaction is final with the blockchain at `tip_height`
+    pub fn is_final(&self, tx: &WalletTx, tip_height: Height) -> bool {
+        confirmations(tx.height, tip_height) >= self.required_for_balance(&tx.balance)
+    }
+}
+
+/// The number of confirmations of a transaction confirmed at `height`
+pub(crate) fn confirmations(height: Option<Height>, tip_height: Height) -> u32 {
+    match height {
+        Some(h) if h <= tip_height => tip_height - h + 1,
+        _ => 0,
+    }
+}
+
+/// The [`WolletEvent::Final`] events of the transactions that became final with the last update,
+/// given the heights and the tip height before it
+pub(crate) fn final_events(
+    wollet: &Wollet,
+    policy: &ConfirmationPolicy,
+    heights_before: &HashMap<Txid, Option<Height>>,
+    tip_before: Height,
+) -> Result<Vec<WolletEvent>, Error> {
+    let tip_height = wollet.tip().height();
+    let mut events = vec![];
+    for tx in wollet.transactions()? {
+        let required = policy.required_for_balance(&tx.balance);
+        let confirmations_after = confirmations(tx.height, tip_height);
+        let height_before = heights_before.get(&tx.txid).cloned().flatten();
+        if confirmations_after >= required && confirmations(height_before, tip_before) < required {
+            events.push(WolletEvent::Final {
+                txid: tx.txid,
+                confirmations: confirmations_after,
+            });
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use elements::AssetId;
+
+    use super::{confirmations, ConfirmationPolicy};
+
+    #[test]
+    fn test_confirmation_policy() {
+        let lbtc = AssetId::from_slice(&[1; 32]).unwrap();
+        let usdt = AssetId::from_slice(&[2; 32]).unwrap();
+        let policy = ConfirmationPolicy::new(2)
+            .with_asset_rule(lbtc, 1_000_000, 10)
+            .with_rule(5_000_000, 6);
+        assert_eq!(policy.required(&lbtc, 0), 2);
+        assert_eq!(policy.required(&lbtc, 1_000_000), 10);
+        assert_eq!(policy.required(&lbtc, 5_000_000), 10);
+        assert_eq!(policy.required(&usdt, 1_000_000), 2);
+        assert_eq!(policy.required(&usdt, 5_000_000), 6);
+
+        let balance: BTreeMap<_, _> = [(lbtc, -2_000_000), (usdt, 5_000_000)].into();
+        assert_eq!(policy.required_for_balance(&balance), 6);
+        assert_eq!(policy.required_for_balance(&BTreeMap::new()), 2);
+        assert_eq!(ConfirmationPolicy::new(0).required(&lbtc, 0), 1);
+
+        assert_eq!(confirmations(None, 10), 0);
+        assert_eq!(confirmations(Some(10), 10), 1);
+        assert_eq!(confirmations(Some(5), 10), 6);
+        assert_eq!(confirmations(Some(11), 10), 0);
+    }
+}
@@ -15,6 +15,7 @@ use elements_miniscript::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
 use crate::EC;
 
 sha256t_hash_newtype! {
@@ -46,9 +47,6 @@ impl TryFrom<ConfidentialDescriptor<DescriptorPublicKey>> for WolletDescriptor {
     type Error = crate::error::Error;
 
     fn try_from(desc: ConfidentialDescriptor<DescriptorPublicKey>) -> Result<Self, Self::Error> {
-        if let Key::Bare(_) = &desc.key {
-            return Err(Self::Error::BlindingBareUnsupported);
-        }
         if let Key::View(DescriptorSecretKey::MultiXPrv(_)) = &desc.key {
             return Err(Self::Error::BlindingViewMultiUnsupported);
         }
@@ -83,8 +81,8 @@ impl TryFrom<ConfidentialDescriptor<DescriptorPublicKey>> for WolletDescriptor {
             }
         }
         match desc.descriptor.desc_type().segwit_version() {
-            Some(WitnessVersion::V0) => Ok(WolletDescriptor(desc)),
-            _ => Err(Self::Error::UnsupportedDescriptorNonV0),
+            Some(WitnessVersion::V0) | Some(WitnessVersion::V1) => Ok(WolletDescriptor(desc)),
+            _ => Err(Self::Error::UnsupportedDescriptorWitnessVersion),
         }
     }
 }
@@ -93,10 +91,37 @@ impl FromStr for WolletDescriptor {
     type Err = crate::error::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        ConfidentialDescriptor::<DescriptorPublicKey>::from_str(s)?.try_into()
+        match view_key_wif_to_hex(s)? {
+            Some(s) => ConfidentialDescriptor::<DescriptorPublicKey>::from_str(&s)?.try_into(),
+            None => ConfidentialDescriptor::<DescriptorPublicKey>::from_str(s)?.try_into(),
+        }
     }
 }
 
+/// If the view key is a single key in WIF format, as exported by some wallets, convert it to hex
+///
+/// The descriptor parser supports single view keys only in hex format.
+/// The checksum, if present, is verified and recomputed for the converted descriptor.
+fn view_key_wif_to_hex(s: &str) -> Result<Option<String>, Error> {
+    let s = s.trim();
+    let Some((key, rest)) = s.strip_prefix("ct(").and_then(|r| r.split_once(',')) else {
+        return Ok(None);
+    };
+    let Ok(key) = bitcoin::PrivateKey::from_wif(key) else {
+        return Ok(None);
+    };
+    if s.contains('#') {
+        verify_descriptor_checksum(s)?;
+    }
+    let desc = format!(
+        "ct({},{}",
+        key.inner.display_secret(),
+        remove_checksum_if_any(rest)
+    );
+    let checksum = descriptor_checksum(&desc)?;
+    Ok(Some(format!("{desc}#{checksum}")))
+}
+
 /// The chain can be either External or Internal.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub enum Chain {
@@ -195,6 +220,13 @@ impl WolletDescriptor {
         }
     }
 
+    /// Return wether the descriptor is multipath (`<0;1>`), having separate external and internal chains
+    ///
+    /// If it's not multipath, change addresses are derived from the external chain.
+    pub fn is_multipath(&self) -> bool {
+        self.0.descriptor.is_multipath()
+    }
+
     /// Strip key origin information from the bitcoin descriptor and return it without checksum
     pub fn bitcoin_descriptor_without_key_origin(&self) -> String {
         let desc = self.0.descriptor.to_string();
@@ -216,7 +248,7 @@ impl WolletDescriptor {
 
     /// return the single descriptor if not multipath, if multipath returns the internal or the
     /// external descriptor accordint to `int_or_ext`
-    fn inner_descriptor_if_available(&self, ext_int: Chain) -> WolletDescriptor {
+    pub(crate) fn inner_descriptor_if_available(&self, ext_int: Chain) -> WolletDescriptor {
         let mut descriptors = self
             .0
             .descriptor
@@ -264,6 +296,16 @@ impl WolletDescriptor {
         Aes256GcmSiv::new(key)
     }
 
+    /// Encrypt `plaintext` with the [`WolletDescriptor::cipher()`], as the persisted data
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        crate::update::encrypt(plaintext.to_vec(), &self.cipher())
+    }
+
+    /// Decrypt data encrypted with [`WolletDescriptor::encrypt()`]
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        crate::update::decrypt(ciphertext, &self.cipher())
+    }
+
     pub fn address(
         &self,
         index: u32,
@@ -285,6 +327,17 @@ impl WolletDescriptor {
             .address(&crate::EC, params)?)
     }
 
+    /// Prove that the address at the given chain and index derives from this descriptor, without
+    /// revealing the descriptor, see [`crate::address_proof`]
+    pub fn address_proof(
+        &self,
+        chain: Chain,
+        index: u32,
+        params: &'static AddressParams,
+    ) -> Result<crate::address_proof::AddressProof, Error> {
+        crate::address_proof::AddressProof::new(self, chain, index, params)
+    }
+
     /// Get a scriptpubkey
     pub fn script_pubkey(&self, ext_int: Chain, index: u32) -> Result<Script, crate::error::Error> {
         let v = self.0.descriptor.clone().into_single_descriptors()?;
@@ -295,6 +348,18 @@ impl WolletDescriptor {
         Ok(d.at_derivation_index(index)?.script_pubkey())
     }
 
+    /// Iterate lazily over the scripts of the given chain, with their derivation index and address
+    ///
+    /// Useful for external indexers which need to know the wallet scripts in advance. The
+    /// iteration ends at the last non-hardened derivation index.
+    pub fn script_iter(&self, chain: Chain, params: &'static AddressParams) -> ScriptIter {
+        ScriptIter {
+            descriptor: self.inner_descriptor_if_available(chain),
+            params,
+            index: 0,
+        }
+    }
+
     /// Get a definite descriptor
     pub fn definite_descriptor(
         &self,
@@ -326,6 +391,15 @@ impl WolletDescriptor {
         }
     }
 
+    /// Whether the wallet can unblind the outputs it receives
+    ///
+    /// Descriptors with a bare blinding public key (`ct(<pubkey>,...)`) can derive confidential
+    /// addresses and be watched, but they cannot unblind amounts and assets since the private
+    /// blinding key is not available.
+    pub fn can_unblind(&self) -> bool {
+        !matches!(self.0.key, Key::Bare(_))
+    }
+
     /// Returns true if all the xpubs in the descriptors are for mainnet
     pub fn is_mainnet(&self) -> bool {
         self.descriptor().for_each_key(|k| match k {
@@ -397,12 +471,80 @@ fn remove_checksum_if_any(s: &str) -> String {
         .expect("even if '#' is not there we always have one element")
 }
 
+/// Compute the checksum of a descriptor, ignoring the existing checksum if any
+pub fn descriptor_checksum(desc: &str) -> Result<String, Error> {
+    let s = remove_checksum_if_any(desc.trim());
+    Ok(elements_miniscript::descriptor::checksum::desc_checksum(
+        &s,
+    )?)
+}
+
+/// Verify that the descriptor has a checksum and that it is correct
+pub fn verify_descriptor_checksum(desc: &str) -> Result<(), Error> {
+    let desc = desc.trim();
+    let found = desc
+        .split_once('#')
+        .map(|(_, c)| c)
+        .ok_or(Error::MissingDescriptorChecksum)?;
+    let expected = descriptor_checksum(desc)?;
+    if expected != found {
+        return Err(Error::InvalidDescriptorChecksum {
+            expected,
+            found: found.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Normalize a descriptor, adding the checksum if missing
+///
+/// If the checksum is present it must be correct.
+pub fn normalize_descriptor(desc: &str) -> Result<String, Error> {
+    let desc = desc.trim();
+    match verify_descriptor_checksum(desc) {
+        Ok(()) => Ok(desc.to_string()),
+        Err(Error::MissingDescriptorChecksum) => {
+            Ok(format!("{}#{}", desc, descriptor_checksum(desc)?))
+        }
+        Err(e) => Err(e),
+    }
+}
+
 fn to_bitcoin_descriptor(s: &str) -> String {
     let s = remove_checksum_if_any(&s[2..]);
     let c = elements_miniscript::descriptor::checksum::desc_checksum(&s).unwrap_or("".into());
     format!("{}#{}", s, c)
 }
 
+/// An iterator over the scripts of a [`WolletDescriptor`] chain, see [`WolletDescriptor::script_iter()`]
+pub struct ScriptIter {
+    descriptor: WolletDescriptor,
+    params: &'static AddressParams,
+    index: u32,
+}
+
+impl Iterator for ScriptIter {
+    type Item = (u32, Script, Address);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.index;
+        let address = self
+            .descriptor
+            .0
+            .at_derivation_index(index)
+            .ok()?
+            .address(&EC, self.params)
+            .ok()?;
+        self.index = index.checked_add(1)?;
+        Some((index, address.script_pubkey(), address))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.index = self.index.checked_add(u32::try_from(n).ok()?)?;
+        self.next()
+    }
+}
+
 impl AsRef<ConfidentialDescriptor<DescriptorPublicKey>> for WolletDescriptor {
     fn as_ref(&self) -> &ConfidentialDescriptor<DescriptorPublicKey> {
         &self.0
@@ -420,7 +562,10 @@ mod test {
     use elements::bitcoin;
     use elements_miniscript::{BtcDescriptor, BtcMiniscript, BtcSegwitv0};
 
-    use crate::{descriptor::remove_checksum_if_any, Chain, WolletDescriptor, EC};
+    use crate::{
+        descriptor::remove_checksum_if_any, descriptor_checksum, normalize_descriptor,
+        verify_descriptor_checksum, Chain, Error, WolletDescriptor, EC,
+    };
 
     #[test]
     fn test_wollet_hash() {
@@ -604,6 +749,22 @@ mod test {
         assert_eq!(a, s);
     }
 
+    #[test]
+    fn test_taproot_desc() {
+        let xpub = "tpubDC2Q4xK4XH72GM7MowNuajyWVbigRLBWKswyP5T88hpPwu5nGqJWnda8zhJEFt71av73Hm8mUMMFSz9acNVzz8b1UbdSHCDXKTbSv5eEytu";
+        let d = format!("ct(elip151,eltr({xpub}/<0;1>/*))");
+        let d = WolletDescriptor::from_str(&d).unwrap();
+        let params = &elements::AddressParams::ELEMENTS;
+
+        let a = d.address(1, params).unwrap();
+        assert!(a.to_string().starts_with("el1p"));
+        assert!(a.script_pubkey().is_v1_p2tr());
+        assert_eq!(
+            a.script_pubkey(),
+            d.script_pubkey(Chain::External, 1).unwrap()
+        );
+    }
+
     #[test]
     fn get_pegin_address() {
         let d: BtcDescriptor<bitcoin::PublicKey> =
@@ -625,4 +786,142 @@ mod test {
         let pegin_address_api = desc.pegin_address(0, bitcoin::Network::Testnet, d).unwrap();
         assert_eq!(pegin_address_api.to_string(), expected);
     }
+
+    #[test]
+    fn test_descriptor_checksum() {
+        let with = "ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*))#cch6wrnp";
+        let without = with.split('#').next().unwrap();
+
+        assert_eq!(descriptor_checksum(with).unwrap(), "cch6wrnp");
+        assert_eq!(descriptor_checksum(without).unwrap(), "cch6wrnp");
+
+        verify_descriptor_checksum(with).unwrap();
+        assert!(matches!(
+            verify_descriptor_checksum(without),
+            Err(Error::MissingDescriptorChecksum)
+        ));
+        let wrong = format!("{without}#aaaaaaaa");
+        assert!(matches!(
+            verify_descriptor_checksum(&wrong),
+            Err(Error::InvalidDescriptorChecksum { .. })
+        ));
+
+        assert_eq!(normalize_descriptor(without).unwrap(), with);
+        assert_eq!(normalize_descriptor(&format!(" {with}\n")).unwrap(), with);
+        assert!(normalize_descriptor(&wrong).is_err());
+        let desc: WolletDescriptor = normalize_descriptor(without).unwrap().parse().unwrap();
+        assert_eq!(desc.to_string(), with);
+    }
+
+    #[test]
+    fn test_view_single_wollet_descriptor() {
+        let view_key = "1111111111111111111111111111111111111111111111111111111111111111";
+        let sk = elements::secp256k1_zkp::SecretKey::from_str(view_key).unwrap();
+        let wif = bitcoin::PrivateKey::new(sk, bitcoin::NetworkKind::Test).to_wif();
+        let xpub = "tpubDD7tXK8KeQ3YY83yWq755fHY2JW8Ha8Q765tknUM5rSvjPcGWfUppDFMpQ1ScziKfW3ZNtZvAD7M3u7bSs7HofjTD3KP3YxPK7X6hwV8Rk2";
+
+        let desc_hex: WolletDescriptor = format!("ct({view_key},elwpkh({xpub}/<0;1>/*))")
+            .parse()
+            .unwrap();
+        let desc_wif: WolletDescriptor =
+            format!("ct({wif},elwpkh({xpub}/<0;1>/*))").parse().unwrap();
+        assert_eq!(desc_hex.to_string(), desc_wif.to_string());
+
+        // checksum of the WIF descriptor is verified
+        let wif_str = format!("ct({wif},elwpkh({xpub}/<0;1>/*))");
+        let checksum = descriptor_checksum(&wif_str).unwrap();
+        let desc: WolletDescriptor = format!("{wif_str}#{checksum}").parse().unwrap();
+        assert_eq!(desc.to_string(), desc_hex.to_string());
+        let err = format!("{wif_str}#aaaaaaaa").parse::<WolletDescriptor>();
+        assert!(matches!(err, Err(Error::InvalidDescriptorChecksum { .. })));
+
+        // derive and unblind
+        let params = &elements::AddressParams::ELEMENTS;
+        let address = desc_wif.address(0, params).unwrap();
+        let asset = elements::AssetId::from_slice(&[1; 32]).unwrap();
+        let secrets = elements::TxOutSecrets::new(
+            asset,
+            elements::confidential::AssetBlindingFactor::zero(),
+            1000,
+            elements::confidential::ValueBlindingFactor::zero(),
+        );
+        let (txout, _, _, _) = elements::TxOut::new_not_last_confidential(
+            &mut rand::thread_rng(),
+            &EC,
+            1000,
+            address,
+            asset,
+            &[secrets],
+        )
+        .unwrap();
+        let unblinded = crate::clients::try_unblind(txout, &desc_wif).unwrap();
+        assert_eq!(unblinded.value, 1000);
+        assert_eq!(unblinded.asset, asset);
+    }
+
+    #[test]
+    fn test_bare_blinding_key() {
+        let bare_key = "0337cceec0beea0232ebe14cba0197a9fbd45fcf2ec946749de920e71434c2b904";
+        let xpub = "tpubDD7tXK8KeQ3YY83yWq755fHY2JW8Ha8Q765tknUM5rSvjPcGWfUppDFMpQ1ScziKfW3ZNtZvAD7M3u7bSs7HofjTD3KP3YxPK7X6hwV8Rk2";
+        let desc: WolletDescriptor = format!("ct({bare_key},elwpkh({xpub}/<0;1>/*))")
+            .parse()
+            .unwrap();
+        assert!(!desc.can_unblind());
+
+        let params = &elements::AddressParams::ELEMENTS;
+        let address = desc.address(0, params).unwrap();
+        assert!(address.is_blinded());
+        assert_ne!(
+            address.blinding_pubkey,
+            desc.address(1, params).unwrap().blinding_pubkey
+        );
+
+        let asset = elements::AssetId::from_slice(&[1; 32]).unwrap();
+        let secrets = elements::TxOutSecrets::new(
+            asset,
+            elements::confidential::AssetBlindingFactor::zero(),
+            1000,
+            elements::confidential::ValueBlindingFactor::zero(),
+        );
+        let (txout, _, _, _) = elements::TxOut::new_not_last_confidential(
+            &mut rand::thread_rng(),
+            &EC,
+            1000,
+            address,
+            asset,
+            &[secrets],
+        )
+        .unwrap();
+        let err = crate::clients::try_unblind(txout, &desc).unwrap_err();
+        assert!(matches!(err, Error::BlindingBareUnsupported));
+
+        let desc: WolletDescriptor = format!("ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh({xpub}/<0;1>/*))")
+            .parse()
+            .unwrap();
+        assert!(desc.can_unblind());
+    }
+
+    #[test]
+    fn test_script_iter() {
+        let desc_str = "ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*))";
+        let desc: WolletDescriptor = desc_str.parse().unwrap();
+        let params = &elements::AddressParams::LIQUID_TESTNET;
+
+        for (chain, n) in [(Chain::External, 0), (Chain::Internal, 7)] {
+            let (index, script, address) = desc.script_iter(chain, params).nth(n).unwrap();
+            assert_eq!(index, n as u32);
+            assert_eq!(script, desc.script_pubkey(chain, index).unwrap());
+            assert_eq!(address, desc.inner_address(index, params, chain).unwrap());
+        }
+        let items: Vec<_> = desc.script_iter(Chain::External, params).take(3).collect();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2].0, 2);
+        assert_ne!(items[0].1, items[1].1);
+
+        // Ends at the last non-hardened index
+        let mut iter = desc.script_iter(Chain::External, params);
+        let last = (1 << 31) - 1;
+        assert_eq!(iter.nth(last).unwrap().0, last as u32);
+        assert!(iter.next().is_none());
+    }
 }
@@ -0,0 +1,375 @@
+//! Track the blockchain tip reported by each backend
+//!
+//! Applications configured with more backends can detect servers lagging behind or following a
+//! different chain: a [`DriftMonitor`] records the tip reported by every backend, and reports as
+//! anomalous the backends behind the highest one (the leader) by more than
+//! [`DriftMonitor::with_max_lag()`] blocks, or whose tip is not in the chain of the leader.
+//! Backends with anomalies should not be used until they recover, see
+//! [`DriftMonitor::is_excluded()`]; an electrum client with failover skips them when given a
+//! monitor with `ElectrumClient::with_drift_monitor()`.
+//!
+//! The tips are persisted with [`Persister::put_value()`], so that anomalies are known at
+//! startup, before polling the backends again.
+
+use std::collections::{BTreeMap, HashMap};
+
+use elements::{BlockHash, BlockHeader};
+use serde::{Deserialize, Serialize};
+
+use crate::store::{Height, Timestamp};
+use crate::{Error, Persister};
+
+/// The default number of blocks a backend can be behind the leader
+pub const DEFAULT_MAX_LAG: u32 = 2;
+
+/// Key used to persist the tips with [`Persister::put_value()`]
+const DRIFT_KEY: &str = "backend_drift";
+
+/// The tip reported by a backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackendTip {
+    /// The height of the tip
+    pub height: Height,
+
+    /// The hash of the tip
+    pub block_hash: BlockHash,
+
+    /// The timestamp of the tip block
+    pub timestamp: Timestamp,
+}
+
+/// A backend that should not be used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DriftAnomaly {
+    /// The backend is behind the leader by more than the maximum lag
+    Lagging {
+        /// The backend name
+        backend: String,
+        /// The height of the backend tip
+        height: Height,
+        /// The height of the leader tip
+        best_height: Height,
+    },
+
+    /// The backend tip is not in the chain of the leader
+    DifferentChain {
+        /// The backend name
+        backend: String,
+        /// The height of the backend tip
+        height: Height,
+        /// The hash of the backend tip
+        block_hash: BlockHash,
+        /// The hash of the block at `height` in the chain of the leader
+        expected: BlockHash,
+    },
+}
+
+impl DriftAnomaly {
+    /// The name of the anomalous backend
+    pub fn backend(&self) -> &str {
+        match self {
+            DriftAnomaly::Lagging { backend, .. } => backend,
+            DriftAnomaly::DifferentChain { backend, .. } => backend,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct DriftState {
+    tips: BTreeMap<String, BackendTip>,
+    leader: Option<String>,
+
+    /// The blocks of the leader chain at the heights of the other backends tips
+    best_chain: BTreeMap<Height, BlockHash>,
+}
+
+/// Track the tips reported by the backends, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct DriftMonitor {
+    max_lag: u32,
+    state: DriftState,
+}
+
+impl Default for DriftMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DriftMonitor {
+    /// Create a monitor without tips
+    pub fn new() -> Self {
+        Self {
+            max_lag: DEFAULT_MAX_LAG,
+            state: DriftState::default(),
+        }
+    }
+
+    /// Create a monitor with the tips previously persisted in `persister`, if any
+    pub fn load(persister: &dyn Persister) -> Result<Self, Error> {
+        let state = match persister.get_value(DRIFT_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => DriftState::default(),
+        };
+        Ok(Self {
+            max_lag: DEFAULT_MAX_LAG,
+            state,
+        })
+    }
+
+    /// Persist the tips in `persister`
+    pub fn save(&self, persister: &dyn Persister) -> Result<(), Error> {
+        let bytes = serde_json::to_vec(&self.state)?;
+        persister.put_value(DRIFT_KEY, &bytes)?;
+        Ok(())
+    }
+
+    /// Consider anomalous the backends behind the leader by more than `blocks`
+    pub fn with_max_lag(mut self, blocks: u32) -> Self {
+        self.max_lag = blocks;
+        self
+    }
+
+    /// The last tip reported by each backend
+    pub fn tips(&self) -> &BTreeMap<String, BackendTip> {
+        &self.state.tips
+    }
+
+    /// The backend with the highest tip
+    pub fn leader(&self) -> Option<&str> {
+        self.state.leader.as_deref()
+    }
+
+    /// The current anomalies
+    pub fn anomalies(&self) -> Vec<DriftAnomaly> {
+        let Some(best_height) = self.leader_tip().map(|t| t.height) else {
+            return vec![];
+        };
+        let mut anomalies = vec![];
+        for (backend, tip) in self.state.tips.iter() {
+            if Some(backend) == self.state.leader.as_ref() {
+                continue;
+            }
+            match self.state.best_chain.get(&tip.height) {
+                Some(expected) if *expected != tip.block_hash => {
+                    anomalies.push(DriftAnomaly::DifferentChain {
+                        backend: backend.clone(),
+                        height: tip.height,
+                        block_hash: tip.block_hash,
+                        expected: *expected,
+                    })
+                }
+                _ if best_height.saturating_sub(tip.height) > self.max_lag => {
+                    anomalies.push(DriftAnomaly::Lagging {
+                        backend: backend.clone(),
+                        height: tip.height,
+                        best_height,
+                    })
+                }
+                _ => {}
+            }
+        }
+        anomalies
+    }
+
+    /// Whether `backend` has an anomaly and should not be used
+    pub fn is_excluded(&self, backend: &str) -> bool {
+        self.anomalies().iter().any(|a| a.backend() == backend)
+    }
+
+    /// The heights of the headers to fetch from `backend` and pass to
+    /// [`DriftMonitor::observe()`], with `tip_height` the height of its tip
+    ///
+    /// If `backend` is going to be the leader, the blocks of its chain are needed to check that
+    /// the other backends are on the same chain.
+    pub fn heights_to_check(&self, backend: &str, tip_height: Height) -> Vec<Height> {
+        let is_leader = match self.leader_tip() {
+            None => true,
+            Some(leader) => {
+                self.state.leader.as_deref() == Some(backend) || tip_height > leader.height
+            }
+        };
+        if !is_leader {
+            return vec![];
+        }
+        let mut heights: Vec<_> = self
+            .state
+            .tips
+            .iter()
+            .filter(|(name, tip)| name.as_str() != backend && tip.height < tip_height)
+            .map(|(_, tip)| tip.height)
+            .collect();
+        heights.sort();
+        heights.dedup();
+        heights
+    }
+
+    /// Record the `tip` reported by `backend`, returning the anomalies that were not there before
+    ///
+    /// `headers` are the headers at [`DriftMonitor::heights_to_check()`] fetched from `backend`.
+    pub fn observe(
+        &mut self,
+        backend: &str,
+        tip: &BlockHeader,
+        headers: &[BlockHeader],
+    ) -> Vec<DriftAnomaly> {
+        let before = self.anomalies();
+        self.state.tips.insert(
+            backend.to_string(),
+            BackendTip {
+                height: tip.height,
+                block_hash: tip.block_hash(),
+                timestamp: tip.time,
+            },
+        );
+
+        // the incumbent leader keeps the lead on ties
+        let mut leader = self
+            .state
+            .leader
+            .clone()
+            .filter(|l| self.state.tips.contains_key(l));
+        for (name, t) in self.state.tips.iter() {
+            let best = leader.as_ref().and_then(|l| self.state.tips.get(l));
+            if best.map_or(true, |b| t.height > b.height) {
+                leader = Some(name.clone());
+            }
+        }
+        if leader != self.state.leader || leader.as_deref() == Some(backend) {
+            self.state.best_chain.clear();
+        }
+        self.state.leader = leader;
+        if let Some(leader_tip) = self.leader_tip().cloned() {
+            self.state
+                .best_chain
+                .insert(leader_tip.height, leader_tip.block_hash);
+        }
+        if self.state.leader.as_deref() == Some(backend) {
+            for header in headers {
+                self.state
+                    .best_chain
+                    .insert(header.height, header.block_hash());
+            }
+        }
+
+        let after = self.anomalies();
+        for anomaly in after.iter().filter(|a| !before.contains(a)) {
+            log::warn!("backend drift detected: {anomaly:?}");
+        }
+        after.into_iter().filter(|a| !before.contains(a)).collect()
+    }
+
+    /// Fetch the tip from `client`, record it as the tip of `backend` and persist the tips,
+    /// see [`DriftMonitor::observe()`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll<B: crate::clients::blocking::BlockchainBackend>(
+        &mut self,
+        backend: &str,
+        client: &mut B,
+        persister: &dyn Persister,
+    ) -> Result<Vec<DriftAnomaly>, Error> {
+        let tip = client.tip()?;
+        let heights = self.heights_to_check(backend, tip.height);
+        let headers = client.get_headers(&heights, &HashMap::new())?;
+        let anomalies = self.observe(backend, &tip, &headers);
+        self.save(persister)?;
+        Ok(anomalies)
+    }
+
+    /// Async version of [`DriftMonitor::poll()`]
+    #[cfg(feature = "esplora")]
+    pub async fn poll_async(
+        &mut self,
+        backend: &str,
+        client: &mut crate::asyncr::EsploraClient,
+        persister: &dyn Persister,
+    ) -> Result<Vec<DriftAnomaly>, Error> {
+        let tip = client.tip().await?;
+        let heights = self.heights_to_check(backend, tip.height);
+        let headers = client.get_headers(&heights, &HashMap::new()).await?;
+        let anomalies = self.observe(backend, &tip, &headers);
+        self.save(persister)?;
+        Ok(anomalies)
+    }
+
+    fn leader_tip(&self) -> Option<&BackendTip> {
+        self.state
+            .leader
+            .as_ref()
+            .and_then(|l| self.state.tips.get(l))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::hashes::Hash;
+    use elements::{BlockHash, BlockHeader};
+
+    use super::{DriftAnomaly, DriftMonitor};
+    use crate::FsPersister;
+
+    fn header(height: u32, fork: u8) -> BlockHeader {
+        let mut header = lwk_test_util::liquid_block_header_2_963_520();
+        header.height = height;
+        header.prev_blockhash = BlockHash::from_byte_array([fork; 32]);
+        header
+    }
+
+    #[test]
+    fn test_drift_monitor() {
+        let mut monitor = DriftMonitor::new().with_max_lag(1);
+        assert!(monitor.observe("a", &header(10, 0), &[]).is_empty());
+        assert_eq!(monitor.leader(), Some("a"));
+
+        // b is behind by one block, then by two
+        assert_eq!(monitor.heights_to_check("b", 9), Vec::<u32>::new());
+        assert!(monitor.observe("b", &header(9, 0), &[]).is_empty());
+        assert_eq!(
+            monitor.observe("a", &header(11, 0), &[header(9, 0)]).len(),
+            1
+        );
+        assert_eq!(
+            monitor.anomalies(),
+            vec![DriftAnomaly::Lagging {
+                backend: "b".into(),
+                height: 9,
+                best_height: 11
+            }]
+        );
+        assert!(monitor.is_excluded("b"));
+
+        // b catches up on a different chain
+        let forked = header(11, 1);
+        let anomalies = monitor.observe("b", &forked, &[]);
+        assert_eq!(
+            anomalies,
+            vec![DriftAnomaly::DifferentChain {
+                backend: "b".into(),
+                height: 11,
+                block_hash: forked.block_hash(),
+                expected: header(11, 0).block_hash(),
+            }]
+        );
+
+        // c becomes the leader on the chain of a, then a is behind
+        assert_eq!(monitor.heights_to_check("c", 14), vec![11]);
+        let anomalies = monitor.observe("c", &header(14, 0), &[header(11, 0)]);
+        assert_eq!(monitor.leader(), Some("c"));
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].backend(), "a");
+        assert!(monitor.is_excluded("b"));
+        assert!(!monitor.is_excluded("c"));
+
+        // the tips are persisted
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = crate::ElementsNetwork::LiquidTestnet;
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let persister = FsPersister::new(&tempdir, network, &desc).unwrap();
+        monitor.save(persister.as_ref()).unwrap();
+        let loaded = DriftMonitor::load(persister.as_ref())
+            .unwrap()
+            .with_max_lag(1);
+        assert_eq!(loaded.tips(), monitor.tips());
+        assert_eq!(loaded.anomalies(), monitor.anomalies());
+    }
+}
@@ -1,4 +1,4 @@
-use elements::OutPoint;
+use elements::{BlockHash, OutPoint, Txid};
 
 /// Error type for the whole crate.
 #[derive(thiserror::Error, Debug)]
@@ -9,7 +9,7 @@ pub enum Error {
     #[error("Aes {0}")]
     Aes(String),
 
-    #[error("Blinding bare key unsupported")]
+    #[error("Cannot unblind with a bare blinding key")]
     BlindingBareUnsupported,
 
     #[error("Blinding view key with wildcard unsupported")]
@@ -156,6 +156,15 @@ pub enum Error {
     #[error("Invalid issuer pubkey")]
     InvalidIssuerPubkey,
 
+    #[error("Only wsh(multi(...)) and wsh(sortedmulti(...)) descriptors are supported")]
+    UnsupportedNonMultisigDescriptor,
+
+    #[error("Migration incomplete: {0} UTXOs left on the old descriptor")]
+    MigrationIncomplete(usize),
+
+    #[error("Invalid asset icon of {0} bytes, must be non empty and at most 100kB")]
+    InvalidAssetIcon(usize),
+
     #[error("Descriptor without wildcard not supported")]
     UnsupportedDescriptorWithoutWildcard,
 
@@ -164,8 +173,14 @@ pub enum Error {
     )]
     UnsupportedMultipathDescriptor,
 
-    #[error("Descriptor with segwit not v0 is not supported")]
-    UnsupportedDescriptorNonV0, // TODO add non supported descriptor type as field or split it further: UnsupportedDescriptorPreSegwit, UnsupportedDescriptorTaproot, UnsupportedDescriptorUnknownSegwitVersion
+    #[error("Descriptor with segwit not v0 or v1 is not supported")]
+    UnsupportedDescriptorWitnessVersion, // TODO add non supported descriptor type as field or split it further: UnsupportedDescriptorPreSegwit, UnsupportedDescriptorUnknownSegwitVersion
+
+    #[error("Descriptor checksum is missing")]
+    MissingDescriptorChecksum,
+
+    #[error("Invalid descriptor checksum: expected {expected} found {found}")]
+    InvalidDescriptorChecksum { expected: String, found: String },
 
     #[error("Missing PSET")]
     MissingPset,
@@ -203,9 +218,18 @@ pub enum Error {
     #[error("At the moment waterfalls does not support scanning up to index (index should be 0)")]
     UsingWaterfallsWithNonZeroIndex,
 
+    #[error("The server doesn't support waterfalls: {0}")]
+    WaterfallsUnsupported(String),
+
+    #[error("The waterfalls server rejected the request: {0}")]
+    WaterfallsRequestRejected(String),
+
     #[error("Cannot encrypt")]
     CannotEncrypt,
 
+    #[error("Cannot decrypt")]
+    CannotDecrypt,
+
     #[error("Cannot parse server recipient key")]
     CannotParseRecipientKey,
 
@@ -216,14 +240,97 @@ pub enum Error {
     #[error("Manual coin selection is not allowed when assets are involved (this limitation will be removed in the future)")]
     ManualCoinSelectionOnlyLbtc,
 
+    #[error("Paying fees with {0} is not supported for this transaction")]
+    UnsupportedFeeAsset(crate::elements::AssetId),
+
     #[error("Missing wallet UTXO {0}")]
     MissingWalletUtxo(OutPoint),
 
     #[error("Transaction has empty witness, did you forget to sign and finalize?")]
     EmptyWitness,
 
+    #[error(transparent)]
+    Sighash(#[from] elements_miniscript::psbt::SighashError),
+
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[error("Cannot finalize any input: {0:?}")]
+    CannotFinalize(Vec<(usize, crate::FinalizeFailure)>),
+
     #[error(transparent)]
     LiquidexError(#[from] crate::liquidex::LiquidexError),
+
+    #[error(transparent)]
+    SwapError(#[from] crate::swap::SwapError),
+
+    #[error("All the wallets in a group must be on the same network")]
+    WolletGroupNetworkMismatch,
+
+    #[error("Descriptor {0} is already in the group")]
+    WolletGroupDuplicateDescriptor(String),
+
+    #[error("Expected a descriptor without blinding key")]
+    UnexpectedConfidentialDescriptor,
+
+    #[error("Invalid TLS pin `{0}`, expected `cert-sha256:<hex>` or `spki-sha256:<hex>`")]
+    InvalidTlsPin(String),
+
+    #[error("Cannot pin the certificate of a connection without TLS")]
+    TlsPinWithoutTls,
+
+    #[error("Invalid HTTP header `{0}`")]
+    InvalidHttpHeader(String),
+
+    #[error("Transaction {0} has no output to the wallet scripts up to index {1}")]
+    TxNotInWallet(Txid, u32),
+
+    #[error("Fee estimate for a confirmation within {0} blocks is not available")]
+    FeeEstimateUnavailable(u16),
+
+    #[error("Invalid address proof: {0}")]
+    InvalidAddressProof(String),
+
+    #[error("Output {0} doesn't pay the wallet or its secrets don't match the commitments")]
+    InvalidUnblindedOutput(u32),
+
+    #[error("Transaction {0} doesn't involve the wallet")]
+    TxNotInvolvingWallet(Txid),
+
+    #[error("Transaction {0} didn't reach the required confirmations in time")]
+    ConfirmationTimeout(Txid),
+
+    #[error("Store version {0} is not supported, upgrade to read it")]
+    UnsupportedStoreVersion(u8),
+
+    #[error("Output {0} is not blinded, but strict blinding is required")]
+    ExplicitOutput(u32),
+
+    #[error("The persisted cache doesn't match its checksum")]
+    CacheChecksumMismatch,
+
+    #[error("Block {height} is {found}, but the checkpoint is {expected}")]
+    CheckpointMismatch {
+        height: u32,
+        expected: BlockHash,
+        found: BlockHash,
+    },
+
+    #[error("The tip at height {tip} is before the checkpoint at height {checkpoint}")]
+    TipBeforeCheckpoint { tip: u32, checkpoint: u32 },
+
+    #[error("The header at height {0} doesn't connect to the other blocks")]
+    HeadersNotConnected(u32),
+
+    #[error("Block {height} is {found}, but the history returned by the server has {expected}")]
+    BlockHashMismatch {
+        height: u32,
+        expected: BlockHash,
+        found: BlockHash,
+    },
+
+    #[error("The header at height {0} is not signed by the block signers of the network")]
+    InvalidBlockSignature(u32),
 }
 
 // cannot derive automatically with this error because of trait bound
@@ -0,0 +1,264 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, PoisonError};
+
+use elements::{BlockHash, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::store::Height;
+
+/// A change of the wallet caused by [`crate::Wollet::apply_update()`]
+///
+/// Events are serialized with a `type` field holding the snake case name of the variant, for
+/// instance `{"type":"unconfirmed","txid":"..."}`, so that they can be passed to other languages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WolletEvent {
+    /// A transaction involving the wallet has been found
+    NewTransaction {
+        /// The transaction id
+        txid: Txid,
+        /// The confirmation height, `None` if unconfirmed
+        height: Option<Height>,
+    },
+
+    /// A known transaction has been confirmed, or confirmed in a different block after a reorg
+    Confirmed {
+        /// The transaction id
+        txid: Txid,
+        /// The confirmation height
+        height: Height,
+    },
+
+    /// A confirmed transaction went back to the mempool after a reorg
+    Unconfirmed {
+        /// The transaction id
+        txid: Txid,
+    },
+
+    /// A known transaction is no longer in the blockchain nor in the mempool, for instance
+    /// because it has been replaced
+    Removed {
+        /// The transaction id
+        txid: Txid,
+    },
+
+    /// Two wallet transactions spend the same output, see [`crate::Wollet::double_spent()`]
+    DoubleSpendDetected {
+        /// The transaction not expected to be included in the blockchain
+        losing: Txid,
+        /// The transaction expected to be included in the blockchain
+        winning: Txid,
+    },
+
+    /// A transaction reached the confirmations required by the
+    /// [`crate::ConfirmationPolicy`] of the wallet
+    Final {
+        /// The transaction id
+        txid: Txid,
+        /// The confirmations of the transaction
+        confirmations: u32,
+    },
+
+    /// The blockchain tip changed
+    NewTip {
+        /// The height of the new tip
+        height: Height,
+        /// The hash of the new tip
+        block_hash: BlockHash,
+    },
+}
+
+/// Receive the [`WolletEvent`]s of a wallet, see [`crate::Wollet::add_listener()`]
+///
+/// Events are emitted synchronously while applying the update, implementors should not block.
+pub trait WolletListener {
+    /// Called for every event, in the order they happened
+    fn on_event(&self, event: &WolletEvent);
+}
+
+/// Forward the events to a channel
+impl WolletListener for Sender<WolletEvent> {
+    fn on_event(&self, event: &WolletEvent) {
+        // the receiver may have been dropped, there is nothing to notify
+        let _ = self.send(event.clone());
+    }
+}
+
+/// A [`WolletListener`] queueing the events until they are polled with
+/// [`EventQueue::drain_events()`]
+///
+/// Useful when the events can't be pushed to the caller, for instance across FFI boundaries. If
+/// the events are not drained, the oldest ones are discarded once `capacity` events are queued.
+#[derive(Debug)]
+pub struct EventQueue {
+    events: Mutex<VecDeque<WolletEvent>>,
+    capacity: usize,
+}
+
+impl EventQueue {
+    /// Create a queue holding at most `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// Remove and return the queued events, in the order they happened
+    pub fn drain_events(&self) -> Vec<WolletEvent> {
+        let mut events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        events.drain(..).collect()
+    }
+}
+
+impl WolletListener for EventQueue {
+    fn on_event(&self, event: &WolletEvent) {
+        let mut events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.capacity == 0 {
+            return;
+        }
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+    }
+}
+
+/// The events caused by an update, comparing the heights and the tip of the wallet before and
+/// after it's applied
+pub(crate) fn events(
+    heights_before: &HashMap<Txid, Option<Height>>,
+    tip_before: (Height, BlockHash),
+    txid_height_new: &[(Txid, Option<Height>)],
+    txid_height_delete: &[Txid],
+    tip: (Height, BlockHash),
+) -> Vec<WolletEvent> {
+    let mut events = vec![];
+    for txid in txid_height_delete {
+        if heights_before.contains_key(txid) {
+            events.push(WolletEvent::Removed { txid: *txid });
+        }
+    }
+    for (txid, height) in txid_height_new {
+        let txid = *txid;
+        match (heights_before.get(&txid), height) {
+            (None, height) => events.push(WolletEvent::NewTransaction {
+                txid,
+                height: *height,
+            }),
+            (Some(before), Some(height)) if *before != Some(*height) => {
+                events.push(WolletEvent::Confirmed {
+                    txid,
+                    height: *height,
+                })
+            }
+            (Some(Some(_)), None) => events.push(WolletEvent::Unconfirmed { txid }),
+            _ => {}
+        }
+    }
+    if tip != tip_before {
+        events.push(WolletEvent::NewTip {
+            height: tip.0,
+            block_hash: tip.1,
+        });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use elements::hashes::Hash;
+    use elements::{BlockHash, Txid};
+
+    use super::{events, EventQueue, WolletEvent, WolletListener};
+
+    #[test]
+    fn test_events() {
+        let txid = |i: u8| Txid::from_byte_array([i; 32]);
+        let tip = BlockHash::from_byte_array([1; 32]);
+        let heights: HashMap<_, _> = [
+            (txid(1), None),
+            (txid(2), Some(10)),
+            (txid(3), Some(10)),
+            (txid(4), None),
+        ]
+        .into_iter()
+        .collect();
+        let new = [
+            (txid(1), Some(11)),
+            (txid(2), None),
+            (txid(3), Some(10)),
+            (txid(5), None),
+        ];
+        let result = events(&heights, (10, tip), &new, &[txid(4), txid(6)], (10, tip));
+        assert_eq!(
+            result,
+            vec![
+                WolletEvent::Removed { txid: txid(4) },
+                WolletEvent::Confirmed {
+                    txid: txid(1),
+                    height: 11
+                },
+                WolletEvent::Unconfirmed { txid: txid(2) },
+                WolletEvent::NewTransaction {
+                    txid: txid(5),
+                    height: None
+                },
+            ]
+        );
+
+        let new_tip = BlockHash::from_byte_array([2; 32]);
+        let result = events(&heights, (10, tip), &[], &[], (11, new_tip));
+        assert_eq!(
+            result,
+            vec![WolletEvent::NewTip {
+                height: 11,
+                block_hash: new_tip
+            }]
+        );
+    }
+
+    #[test]
+    fn test_event_queue() {
+        let txid = |i: u8| Txid::from_byte_array([i; 32]);
+        let queue = EventQueue::new(2);
+        assert!(queue.drain_events().is_empty());
+        for i in 0..3 {
+            queue.on_event(&WolletEvent::Unconfirmed { txid: txid(i) });
+        }
+        // the oldest event has been discarded
+        assert_eq!(
+            queue.drain_events(),
+            vec![
+                WolletEvent::Unconfirmed { txid: txid(1) },
+                WolletEvent::Unconfirmed { txid: txid(2) },
+            ]
+        );
+        assert!(queue.drain_events().is_empty());
+    }
+
+    #[test]
+    fn test_event_serde() {
+        let event = WolletEvent::Confirmed {
+            txid: Txid::from_byte_array([1; 32]),
+            height: 10,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"confirmed","txid":"0101010101010101010101010101010101010101010101010101010101010101","height":10}"#
+        );
+        assert_eq!(serde_json::from_str::<WolletEvent>(&json).unwrap(), event);
+
+        let event = WolletEvent::NewTransaction {
+            txid: Txid::from_byte_array([1; 32]),
+            height: None,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with(r#"{"type":"new_transaction","#));
+        assert_eq!(serde_json::from_str::<WolletEvent>(&json).unwrap(), event);
+    }
+}
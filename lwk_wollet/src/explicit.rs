@@ -0,0 +1,214 @@
+//! Watch explicit outputs of non-confidential descriptors
+//!
+//! An [`ExplicitWollet`] tracks the explicit (unblinded) outputs sent to the scripts of a
+//! descriptor without blinding key, for instance to monitor federation or fee scripts. It never
+//! unblinds anything and it's kept separated from [`Wollet`], so that confidential and explicit
+//! funds cannot be mixed by mistake.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use elements::bitcoin::bip32::ChildNumber;
+use elements::{Address, AssetId, BlockHash, Script, Txid};
+use elements_miniscript::confidential::Key;
+use elements_miniscript::{ConfidentialDescriptor, Descriptor, DescriptorPublicKey};
+
+use crate::clients::{LastUnused, ScriptCheckpoint};
+use crate::store::{Height, ScriptBatch, Timestamp};
+use crate::wollet::WolletState;
+use crate::{
+    BlindingPublicKey, Chain, Config, Error, ExternalUtxo, Persister, Update, Wollet,
+    WolletDescriptor,
+};
+
+/// A NUMS point used as blinding key of the inner wallet, which never unblinds anything
+///
+/// It's the point `H` of BIP341, nobody knows its discrete logarithm.
+const NUMS_KEY: &str = "0250929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+/// A watch-only wallet tracking the explicit outputs of a non-confidential descriptor
+pub struct ExplicitWollet {
+    inner: Wollet,
+    descriptor: Descriptor<DescriptorPublicKey>,
+}
+
+impl ExplicitWollet {
+    /// Create a new explicit wallet from a descriptor without blinding key, eg. `elwpkh(...)`
+    ///
+    /// Confidential descriptors are rejected, use [`Wollet`] for them.
+    pub fn new(
+        config: Config,
+        persister: Arc<dyn Persister + Send + Sync>,
+        descriptor: &str,
+    ) -> Result<Self, Error> {
+        if descriptor.trim().starts_with("ct(") {
+            return Err(Error::UnexpectedConfidentialDescriptor);
+        }
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(descriptor)?;
+        let key = Key::Bare(DescriptorPublicKey::from_str(NUMS_KEY).expect("static"));
+        let wollet_descriptor: WolletDescriptor = ConfidentialDescriptor {
+            key,
+            descriptor: descriptor.clone(),
+        }
+        .try_into()?;
+        let inner = Wollet::with_config(config, persister, wollet_descriptor)?;
+        Ok(Self { inner, descriptor })
+    }
+
+    /// The descriptor of the wallet
+    pub fn descriptor(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.descriptor
+    }
+
+    /// The unconfidential address at the given derivation index
+    pub fn address(&self, index: u32) -> Result<Address, Error> {
+        Ok(self.inner.address_at(index)?.address().to_unconfidential())
+    }
+
+    /// Apply an update obtained by scanning this wallet
+    pub fn apply_update(&mut self, update: Update) -> Result<(), Error> {
+        self.inner.apply_update(update)
+    }
+
+    /// The ids and heights of the transactions involving the wallet scripts
+    pub fn txids(&self) -> Vec<(Txid, Option<Height>)> {
+        self.inner.txids()
+    }
+
+    /// The unspent explicit outputs
+    pub fn utxos(&self) -> Result<Vec<ExternalUtxo>, Error> {
+        self.inner.explicit_utxos()
+    }
+
+    /// The balance of the unspent explicit outputs
+    pub fn balance(&self) -> Result<BTreeMap<AssetId, u64>, Error> {
+        let mut balance = BTreeMap::new();
+        for utxo in self.utxos()? {
+            *balance.entry(utxo.unblinded.asset).or_default() += utxo.unblinded.value;
+        }
+        Ok(balance)
+    }
+
+    /// The wallet status, see [`Wollet::status()`]
+    pub fn status(&self) -> u64 {
+        self.inner.status()
+    }
+}
+
+impl WolletState for ExplicitWollet {
+    fn get_script_batch(
+        &self,
+        batch: u32,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+    ) -> Result<ScriptBatch, Error> {
+        self.inner.get_script_batch(batch, descriptor)
+    }
+
+    fn get_or_derive(
+        &self,
+        ext_int: Chain,
+        child: ChildNumber,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+    ) -> Result<(Script, BlindingPublicKey, bool), Error> {
+        self.inner.get_or_derive(ext_int, child, descriptor)
+    }
+
+    fn heights(&self) -> &HashMap<Txid, Option<Height>> {
+        self.inner.heights()
+    }
+
+    fn paths(&self) -> &HashMap<Script, (Chain, ChildNumber)> {
+        self.inner.paths()
+    }
+
+    fn txs(&self) -> HashSet<Txid> {
+        self.inner.txs()
+    }
+
+    fn tip(&self) -> (Height, BlockHash) {
+        WolletState::tip(&self.inner)
+    }
+
+    fn last_unused(&self) -> LastUnused {
+        self.inner.last_unused()
+    }
+
+    fn descriptor(&self) -> WolletDescriptor {
+        WolletState::descriptor(&self.inner)
+    }
+
+    fn wollet_status(&self) -> u64 {
+        self.inner.wollet_status()
+    }
+
+    fn birthday(&self) -> Option<Height> {
+        self.inner.birthday()
+    }
+
+    fn checkpoint(&self, script: &Script) -> Option<ScriptCheckpoint> {
+        self.inner.checkpoint(script)
+    }
+
+    fn timestamp(&self, height: Height) -> Option<Timestamp> {
+        self.inner.timestamp(height)
+    }
+
+    fn gap_limit(&self) -> u32 {
+        self.inner.gap_limit()
+    }
+
+    fn memory_budget(&self) -> Option<usize> {
+        self.inner.memory_budget()
+    }
+
+    fn block_checkpoints(&self) -> Vec<(Height, BlockHash)> {
+        self.inner.block_checkpoints()
+    }
+
+    fn block_signers(&self) -> Vec<Script> {
+        self.inner.block_signers()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::ExplicitWollet;
+    use crate::{Config, ElementsNetwork, Error, NoPersist, WolletDescriptor};
+
+    const XPUB: &str = "[73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M";
+
+    #[test]
+    fn test_explicit_wollet() {
+        let config = Config::new(ElementsNetwork::LiquidTestnet).unwrap();
+        let desc = format!("elwpkh({XPUB}/<0;1>/*)");
+        let wollet = ExplicitWollet::new(config.clone(), NoPersist::new(), &desc).unwrap();
+        assert_eq!(
+            wollet.descriptor().to_string().split('#').next(),
+            Some(&desc[..])
+        );
+
+        // Same scripts of the confidential descriptor, but unconfidential addresses
+        let ct = format!(
+            "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),{desc})"
+        );
+        let ct = WolletDescriptor::from_str(&ct).unwrap();
+        let address = wollet.address(3).unwrap();
+        assert!(!address.is_blinded());
+        assert_eq!(
+            address,
+            ct.address(3, config.address_params())
+                .unwrap()
+                .to_unconfidential()
+        );
+
+        assert!(wollet.txids().is_empty());
+        assert!(wollet.utxos().unwrap().is_empty());
+        assert!(wollet.balance().unwrap().is_empty());
+
+        let err = ExplicitWollet::new(config, NoPersist::new(), &ct.to_string());
+        assert!(matches!(err, Err(Error::UnexpectedConfidentialDescriptor)));
+    }
+}
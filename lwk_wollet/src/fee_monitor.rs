@@ -0,0 +1,193 @@
+//! Monitor the fee rates estimated by a backend
+//!
+//! Services batching their withdrawals can wait for cheap periods: a [`FeeMonitor`] polls the
+//! fee estimate for a confirmation target and emits a [`FeeEvent`] every time the fee rate
+//! crosses one of the configured thresholds.
+
+use std::collections::VecDeque;
+
+use crate::Error;
+
+/// The default number of fee rates kept by a [`FeeMonitor`]
+pub const DEFAULT_HISTORY_LEN: usize = 144;
+
+/// A fee rate crossing a threshold of a [`FeeMonitor`], rates are in sats/kvb
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeeEvent {
+    /// The fee rate went from below to equal or above the threshold
+    Above {
+        /// The crossed threshold
+        threshold: f32,
+        /// The new fee rate
+        fee_rate: f32,
+    },
+
+    /// The fee rate went from equal or above to below the threshold
+    Below {
+        /// The crossed threshold
+        threshold: f32,
+        /// The new fee rate
+        fee_rate: f32,
+    },
+}
+
+/// Track the fee rate estimated for a confirmation target
+#[derive(Debug, Clone)]
+pub struct FeeMonitor {
+    target: u16,
+    thresholds: Vec<f32>,
+    history: VecDeque<f32>,
+    history_len: usize,
+}
+
+impl FeeMonitor {
+    /// Create a monitor for the fee rate of transactions to be confirmed within `target` blocks
+    pub fn new(target: u16) -> Self {
+        Self {
+            target,
+            thresholds: vec![],
+            history: VecDeque::new(),
+            history_len: DEFAULT_HISTORY_LEN,
+        }
+    }
+
+    /// Emit events when the fee rate crosses `threshold`, in sats/kvb
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.thresholds.push(threshold);
+        self.thresholds.sort_by(f32::total_cmp);
+        self
+    }
+
+    /// Keep the last `len` fee rates, at least 1
+    pub fn with_history_len(mut self, len: usize) -> Self {
+        self.history_len = len.max(1);
+        self.history.truncate(self.history_len);
+        self
+    }
+
+    /// The confirmation target in blocks
+    pub fn target(&self) -> u16 {
+        self.target
+    }
+
+    /// The fee rates observed, from the oldest to the most recent
+    pub fn history(&self) -> impl Iterator<Item = f32> + '_ {
+        self.history.iter().rev().copied()
+    }
+
+    /// The last fee rate observed
+    pub fn last(&self) -> Option<f32> {
+        self.history.front().copied()
+    }
+
+    /// The lowest fee rate in the history
+    pub fn min(&self) -> Option<f32> {
+        self.history().min_by(f32::total_cmp)
+    }
+
+    /// The average fee rate in the history
+    pub fn average(&self) -> Option<f32> {
+        let len = self.history.len();
+        (len > 0).then(|| self.history.iter().sum::<f32>() / len as f32)
+    }
+
+    /// Record a new fee rate, returning the crossed thresholds
+    ///
+    /// The first fee rate recorded doesn't emit events.
+    pub fn observe(&mut self, fee_rate: f32) -> Vec<FeeEvent> {
+        let events = match self.last() {
+            None => vec![],
+            Some(last) => self
+                .thresholds
+                .iter()
+                .filter_map(|threshold| {
+                    let threshold = *threshold;
+                    if last < threshold && fee_rate >= threshold {
+                        Some(FeeEvent::Above {
+                            threshold,
+                            fee_rate,
+                        })
+                    } else if last >= threshold && fee_rate < threshold {
+                        Some(FeeEvent::Below {
+                            threshold,
+                            fee_rate,
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+        };
+        self.history.push_front(fee_rate);
+        self.history.truncate(self.history_len);
+        events
+    }
+
+    /// Fetch the fee estimate from the backend and record it, see [`FeeMonitor::observe()`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll<B: crate::clients::blocking::BlockchainBackend>(
+        &mut self,
+        client: &B,
+    ) -> Result<Vec<FeeEvent>, Error> {
+        let fee_rate = client.estimate_fee(self.target)?;
+        Ok(self.observe(fee_rate))
+    }
+
+    /// Async version of [`FeeMonitor::poll()`]
+    #[cfg(feature = "esplora")]
+    pub async fn poll_async(
+        &mut self,
+        client: &crate::asyncr::EsploraClient,
+    ) -> Result<Vec<FeeEvent>, Error> {
+        let fee_rate = client.estimate_fee(self.target).await?;
+        Ok(self.observe(fee_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeeEvent, FeeMonitor};
+
+    #[test]
+    fn test_fee_monitor() {
+        let mut monitor = FeeMonitor::new(2)
+            .with_threshold(500.0)
+            .with_threshold(100.0)
+            .with_history_len(3);
+        assert!(monitor.observe(200.0).is_empty());
+        assert!(monitor.observe(300.0).is_empty());
+
+        assert_eq!(
+            monitor.observe(600.0),
+            vec![FeeEvent::Above {
+                threshold: 500.0,
+                fee_rate: 600.0
+            }]
+        );
+        assert_eq!(
+            monitor.observe(50.0),
+            vec![
+                FeeEvent::Below {
+                    threshold: 100.0,
+                    fee_rate: 50.0
+                },
+                FeeEvent::Below {
+                    threshold: 500.0,
+                    fee_rate: 50.0
+                },
+            ]
+        );
+        assert_eq!(
+            monitor.observe(100.0),
+            vec![FeeEvent::Above {
+                threshold: 100.0,
+                fee_rate: 100.0
+            }]
+        );
+
+        assert_eq!(monitor.history().collect::<Vec<_>>(), [600.0, 50.0, 100.0]);
+        assert_eq!(monitor.last(), Some(100.0));
+        assert_eq!(monitor.min(), Some(50.0));
+        assert_eq!(monitor.average(), Some(250.0));
+    }
+}
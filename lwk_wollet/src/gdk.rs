@@ -0,0 +1,321 @@
+//! Conversions between LWK types and the JSON models used by Blockstream's GDK
+//!
+//! These adapters allow applications migrating from GDK (eg. Green) to run both stacks side by
+//! side, exchanging transactions, addressees and PSETs in the format GDK expects.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use elements::pset::PartiallySignedTransaction;
+use serde::{Deserialize, Serialize};
+
+use crate::{Chain, Error, LabelType, Labels, UnvalidatedRecipient, WalletTx, WalletTxOut};
+
+/// An addressee as used in GDK `create_transaction`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GdkAddressee {
+    /// The address to send to
+    pub address: String,
+
+    /// The amount to send in satoshi
+    pub satoshi: u64,
+
+    /// The asset to send, if missing the policy asset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<String>,
+}
+
+impl From<&UnvalidatedRecipient> for GdkAddressee {
+    fn from(r: &UnvalidatedRecipient) -> Self {
+        Self {
+            address: r.address.clone(),
+            satoshi: r.satoshi,
+            asset_id: (!r.asset.is_empty()).then(|| r.asset.clone()),
+        }
+    }
+}
+
+impl From<&GdkAddressee> for UnvalidatedRecipient {
+    fn from(a: &GdkAddressee) -> Self {
+        Self {
+            satoshi: a.satoshi,
+            address: a.address.clone(),
+            asset: a.asset_id.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A transaction input or output as returned by GDK `get_transactions`
+///
+/// Only inputs and outputs belonging to the wallet are unblinded, the others have
+/// `is_relevant` set to false and no amounts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GdkTxInOut {
+    /// The index of the input or output in the transaction
+    pub pt_idx: u32,
+
+    /// Whether the input or output belongs to the wallet
+    pub is_relevant: bool,
+
+    /// The confidential address, if the input or output belongs to the wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// The unblinded amount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub satoshi: Option<u64>,
+
+    /// The unblinded asset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<String>,
+
+    /// Whether the address is on the internal (change) chain
+    #[serde(default)]
+    pub is_internal: bool,
+
+    /// The derivation index of the address
+    #[serde(default)]
+    pub pointer: u32,
+}
+
+impl GdkTxInOut {
+    fn new(pt_idx: usize, txout: &Option<WalletTxOut>) -> Self {
+        match txout {
+            Some(txout) => Self {
+                pt_idx: pt_idx as u32,
+                is_relevant: true,
+                address: Some(txout.address.to_string()),
+                satoshi: Some(txout.unblinded.value),
+                asset_id: Some(txout.unblinded.asset.to_string()),
+                is_internal: txout.ext_int == Chain::Internal,
+                pointer: txout.wildcard_index,
+            },
+            None => Self {
+                pt_idx: pt_idx as u32,
+                is_relevant: false,
+                address: None,
+                satoshi: None,
+                asset_id: None,
+                is_internal: false,
+                pointer: 0,
+            },
+        }
+    }
+}
+
+/// A transaction as returned by GDK `get_transactions`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GdkTransaction {
+    /// The transaction id
+    pub txhash: String,
+
+    /// The height of the block containing the transaction, 0 if unconfirmed
+    pub block_height: u32,
+
+    /// The block timestamp in microseconds, 0 if unconfirmed
+    pub created_at_ts: u64,
+
+    /// The fee paid in satoshi
+    pub fee: u64,
+
+    /// The fee rate in satoshi per 1000 virtual bytes
+    pub fee_rate: u64,
+
+    /// One of "incoming", "outgoing", "redeposit" or "mixed"
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// The net balance change for every asset involved
+    pub satoshi: BTreeMap<String, i64>,
+
+    /// The transaction memo, the label of the transaction, see [`GdkTransaction::with_labels()`]
+    #[serde(default)]
+    pub memo: String,
+
+    /// The transaction weight
+    pub transaction_weight: u64,
+
+    /// The transaction virtual size
+    pub transaction_vsize: u64,
+
+    /// The transaction inputs
+    pub inputs: Vec<GdkTxInOut>,
+
+    /// The transaction outputs
+    pub outputs: Vec<GdkTxInOut>,
+}
+
+/// Map the LWK transaction type to the GDK one
+fn gdk_type(type_: &str) -> &'static str {
+    match type_ {
+        "incoming" => "incoming",
+        "outgoing" | "burn" | "issuance" | "reissuance" => "outgoing",
+        "redeposit" => "redeposit",
+        _ => "mixed",
+    }
+}
+
+impl GdkTransaction {
+    /// Convert the transaction with its label as memo
+    ///
+    /// Pass the [`crate::Wollet::labels()`] so the memos set by the user are kept in the export.
+    pub fn with_labels(tx: &WalletTx, labels: &Labels) -> Self {
+        let memo = labels
+            .get(LabelType::Tx, &tx.txid.to_string())
+            .and_then(|label| label.label.clone())
+            .unwrap_or_default();
+        Self {
+            memo,
+            ..Self::from(tx)
+        }
+    }
+}
+
+/// The transaction without memo, use [`GdkTransaction::with_labels()`] to include it
+impl From<&WalletTx> for GdkTransaction {
+    fn from(tx: &WalletTx) -> Self {
+        let weight = tx.tx.weight() as u64;
+        let vsize = weight.div_ceil(4);
+        Self {
+            txhash: tx.txid.to_string(),
+            block_height: tx.height.unwrap_or(0),
+            created_at_ts: tx.timestamp.map(|t| t as u64 * 1_000_000).unwrap_or(0),
+            fee: tx.fee,
+            fee_rate: (tx.fee * 1000).checked_div(vsize).unwrap_or(0),
+            type_: gdk_type(&tx.type_).to_string(),
+            satoshi: tx
+                .balance
+                .iter()
+                .map(|(asset, value)| (asset.to_string(), *value))
+                .collect(),
+            memo: String::new(),
+            transaction_weight: weight,
+            transaction_vsize: vsize,
+            inputs: tx
+                .inputs
+                .iter()
+                .enumerate()
+                .map(|(i, txout)| GdkTxInOut::new(i, txout))
+                .collect(),
+            outputs: tx
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(i, txout)| GdkTxInOut::new(i, txout))
+                .collect(),
+        }
+    }
+}
+
+/// A PSET as exchanged with GDK `psbt_sign` and `psbt_get_details`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GdkPsbt {
+    /// The base64 encoded PSET
+    pub psbt: String,
+}
+
+impl From<&PartiallySignedTransaction> for GdkPsbt {
+    fn from(pset: &PartiallySignedTransaction) -> Self {
+        Self {
+            psbt: pset.to_string(),
+        }
+    }
+}
+
+impl TryFrom<&GdkPsbt> for PartiallySignedTransaction {
+    type Error = Error;
+
+    fn try_from(value: &GdkPsbt) -> Result<Self, Self::Error> {
+        Ok(PartiallySignedTransaction::from_str(&value.psbt)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::pset::PartiallySignedTransaction;
+
+    use super::{GdkAddressee, GdkPsbt, GdkTransaction};
+    use crate::{Label, Labels, UnvalidatedRecipient, WalletTx};
+
+    #[test]
+    fn test_gdk_addressee() {
+        let json = r#"{"address":"el1qq...","satoshi":1000}"#;
+        let a: GdkAddressee = serde_json::from_str(json).unwrap();
+        let r = UnvalidatedRecipient::from(&a);
+        assert_eq!(r.asset, "");
+        assert_eq!(r.satoshi, 1000);
+        assert_eq!(GdkAddressee::from(&r), a);
+        assert_eq!(serde_json::to_string(&a).unwrap(), json);
+
+        let asset = "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49";
+        let r = UnvalidatedRecipient {
+            satoshi: 1,
+            address: "el1qq...".to_string(),
+            asset: asset.to_string(),
+        };
+        let a = GdkAddressee::from(&r);
+        assert_eq!(a.asset_id.as_deref(), Some(asset));
+    }
+
+    #[test]
+    fn test_gdk_transaction() {
+        let json_str = include_str!("../tests/data/wallet_tx.json");
+        let wallet_tx: WalletTx = serde_json::from_str(json_str).unwrap();
+        let asset = "38fca2d939696061a8f76d4e6b5eecd54e3b4221c846f24a6b279e79952850a5";
+
+        let gdk = GdkTransaction::from(&wallet_tx);
+        assert_eq!(
+            gdk.txhash,
+            "c6e3187f028942973ad27224ca79baa8382e90ad686e927fc29896e8a2edf3f3"
+        );
+        assert_eq!(gdk.type_, "incoming");
+        assert_eq!(gdk.block_height, 1306469);
+        assert_eq!(gdk.created_at_ts, 1_710_527_583_000_000);
+        assert_eq!(gdk.fee, 376);
+        assert!(gdk.fee_rate > 0);
+        assert_eq!(gdk.satoshi.get(asset), Some(&5000));
+        assert_eq!(gdk.inputs.len(), 2);
+        assert_eq!(gdk.outputs.len(), 4);
+        assert!(gdk.outputs[0].is_relevant);
+        assert_eq!(gdk.outputs[0].satoshi, Some(5000));
+        assert_eq!(gdk.outputs[0].asset_id.as_deref(), Some(asset));
+        assert!(!gdk.outputs[1].is_relevant);
+        assert_eq!(gdk.outputs[1].pt_idx, 1);
+
+        let value = serde_json::to_value(&gdk).unwrap();
+        assert_eq!(value["type"], "incoming");
+        let back: GdkTransaction = serde_json::from_value(value).unwrap();
+        assert_eq!(back, gdk);
+
+        assert_eq!(gdk.memo, "");
+        let mut labels = Labels::default();
+        let gdk = GdkTransaction::with_labels(&wallet_tx, &labels);
+        assert_eq!(gdk.memo, "");
+        labels.insert(Label::tx(&wallet_tx.txid, "rent"));
+        let gdk = GdkTransaction::with_labels(&wallet_tx, &labels);
+        assert_eq!(gdk.memo, "rent");
+        assert_eq!(gdk.txhash, GdkTransaction::from(&wallet_tx).txhash);
+
+        let mut burn = wallet_tx;
+        burn.type_ = "burn".to_string();
+        assert_eq!(GdkTransaction::from(&burn).type_, "outgoing");
+    }
+
+    #[test]
+    fn test_gdk_psbt() {
+        let json_str = include_str!("../tests/data/wallet_tx.json");
+        let wallet_tx: WalletTx = serde_json::from_str(json_str).unwrap();
+        let pset = PartiallySignedTransaction::from_tx(wallet_tx.tx);
+
+        let gdk = GdkPsbt::from(&pset);
+        let json = serde_json::to_string(&gdk).unwrap();
+        let gdk: GdkPsbt = serde_json::from_str(&json).unwrap();
+        let back = PartiallySignedTransaction::try_from(&gdk).unwrap();
+        assert_eq!(back, pset);
+
+        let invalid = GdkPsbt {
+            psbt: "invalid".to_string(),
+        };
+        assert!(PartiallySignedTransaction::try_from(&invalid).is_err());
+    }
+}
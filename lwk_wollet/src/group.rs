@@ -0,0 +1,307 @@
+//! Aggregate several wallets as if they were one
+//!
+//! A [`WolletGroup`] tracks several descriptors of the same network (for instance a legacy and a
+//! segwit wallet, or several accounts), exposing merged balances, UTXOs and transaction history.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use elements::{AssetId, Txid};
+
+use crate::model::{Balances, WalletTx, WalletTxOut};
+use crate::wollet::{tx_type, Wollet};
+use crate::{ElementsNetwork, Error};
+
+/// A group of [`Wollet`]s with merged balances, UTXOs and transactions
+pub struct WolletGroup {
+    network: ElementsNetwork,
+    wollets: Vec<Wollet>,
+}
+
+impl WolletGroup {
+    /// Create a group from the given wallets
+    ///
+    /// All the wallets must be on the same network and have different descriptors.
+    pub fn new(network: ElementsNetwork, wollets: Vec<Wollet>) -> Result<Self, Error> {
+        let mut group = Self {
+            network,
+            wollets: vec![],
+        };
+        for wollet in wollets {
+            group.add(wollet)?;
+        }
+        Ok(group)
+    }
+
+    /// Add a wallet to the group
+    pub fn add(&mut self, wollet: Wollet) -> Result<(), Error> {
+        if wollet.network() != self.network {
+            return Err(Error::WolletGroupNetworkMismatch);
+        }
+        let descriptor = wollet.wollet_descriptor();
+        if self
+            .wollets
+            .iter()
+            .any(|w| w.wollet_descriptor().to_string() == descriptor.to_string())
+        {
+            return Err(Error::WolletGroupDuplicateDescriptor(
+                descriptor.to_string(),
+            ));
+        }
+        self.wollets.push(wollet);
+        Ok(())
+    }
+
+    /// Remove the wallet at the given position, if any
+    pub fn remove(&mut self, index: usize) -> Option<Wollet> {
+        (index < self.wollets.len()).then(|| self.wollets.remove(index))
+    }
+
+    /// The network of the group
+    pub fn network(&self) -> ElementsNetwork {
+        self.network
+    }
+
+    /// The wallets in the group
+    pub fn wollets(&self) -> &[Wollet] {
+        &self.wollets
+    }
+
+    /// The wallets in the group, for instance to apply updates fetched separately
+    pub fn wollets_mut(&mut self) -> &mut [Wollet] {
+        &mut self.wollets
+    }
+
+    /// Get the merged balance of all the wallets
+    pub fn balance(&self) -> Result<BTreeMap<AssetId, u64>, Error> {
+        let mut result = BTreeMap::new();
+        for wollet in self.wollets.iter() {
+            add_balance(&mut result, &wollet.balance()?);
+        }
+        Ok(result)
+    }
+
+    /// Get the merged balances of all the wallets, see [`Wollet::balances()`]
+    pub fn balances(&self) -> Result<Balances, Error> {
+        let mut result = Balances::default();
+        for wollet in self.wollets.iter() {
+            let balances = wollet.balances()?;
+            add_balance(&mut result.confirmed, &balances.confirmed);
+            add_balance(&mut result.trusted_pending, &balances.trusted_pending);
+            add_balance(&mut result.untrusted_pending, &balances.untrusted_pending);
+        }
+        Ok(result)
+    }
+
+    /// Get the unspent outputs of all the wallets
+    pub fn utxos(&self) -> Result<Vec<WalletTxOut>, Error> {
+        let mut result = vec![];
+        for wollet in self.wollets.iter() {
+            result.extend(wollet.utxos()?);
+        }
+        Ok(result)
+    }
+
+    /// Get the transactions of all the wallets
+    ///
+    /// Transactions involving more than one wallet of the group are returned once, with the
+    /// balances, inputs and outputs of all the wallets merged.
+    pub fn transactions(&self) -> Result<Vec<WalletTx>, Error> {
+        let mut txs: HashMap<Txid, WalletTx> = HashMap::new();
+        let mut merged = HashSet::new();
+        for wollet in self.wollets.iter() {
+            for tx in wollet.transactions()? {
+                match txs.get_mut(&tx.txid) {
+                    Some(existing) => {
+                        merge_tx(existing, tx);
+                        merged.insert(existing.txid);
+                    }
+                    None => {
+                        txs.insert(tx.txid, tx);
+                    }
+                }
+            }
+        }
+        let policy_asset = self.network.policy_asset();
+        for txid in merged {
+            if let Some(tx) = txs.get_mut(&txid) {
+                tx.type_ = tx_type(&tx.tx, &policy_asset, &tx.balance, tx.fee);
+            }
+        }
+        let mut txs: Vec<_> = txs.into_values().collect();
+        txs.sort_by(|a, b| {
+            let height_cmp = b
+                .height
+                .unwrap_or(u32::MAX)
+                .cmp(&a.height.unwrap_or(u32::MAX));
+            match height_cmp {
+                Ordering::Equal => b.txid.cmp(&a.txid),
+                h => h,
+            }
+        });
+        Ok(txs)
+    }
+
+    /// Scan the blockchain for all the wallets in the group, using the same client
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn full_scan<B: crate::clients::blocking::BlockchainBackend>(
+        &mut self,
+        client: &mut B,
+    ) -> Result<(), Error> {
+        for wollet in self.wollets.iter_mut() {
+            if let Some(update) = client.full_scan(wollet)? {
+                wollet.apply_update(update)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Scan the blockchain for all the wallets in the group, using the same async client
+    #[cfg(feature = "esplora")]
+    pub async fn full_scan_async(
+        &mut self,
+        client: &mut crate::asyncr::EsploraClient,
+    ) -> Result<(), Error> {
+        for wollet in self.wollets.iter_mut() {
+            if let Some(update) = client.full_scan(wollet).await? {
+                wollet.apply_update(update)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn add_balance(acc: &mut BTreeMap<AssetId, u64>, balance: &BTreeMap<AssetId, u64>) {
+    for (asset, value) in balance {
+        *acc.entry(*asset).or_default() += value;
+    }
+}
+
+fn merge_tx(existing: &mut WalletTx, other: WalletTx) {
+    for (asset, value) in other.balance {
+        *existing.balance.entry(asset).or_default() += value;
+    }
+    for (a, b) in existing.inputs.iter_mut().zip(other.inputs) {
+        if a.is_none() {
+            *a = b;
+        }
+    }
+    for (a, b) in existing.outputs.iter_mut().zip(other.outputs) {
+        if a.is_none() {
+            *a = b;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::AssetId;
+
+    use super::merge_tx;
+    use super::WolletGroup;
+    use crate::wollet::tests::test_wollet_with_many_transactions;
+    use crate::{ElementsNetwork, Error, NoPersist, Wollet, WolletDescriptor};
+
+    fn other_wollet() -> Wollet {
+        let desc = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc = WolletDescriptor::from_str(desc).unwrap();
+        Wollet::new(ElementsNetwork::LiquidTestnet, NoPersist::new(), desc).unwrap()
+    }
+
+    #[test]
+    fn test_wollet_group() {
+        let w1 = test_wollet_with_many_transactions();
+        let w2 = other_wollet();
+        let network = ElementsNetwork::LiquidTestnet;
+        let policy_asset = network.policy_asset();
+
+        let b1 = w1.balance().unwrap();
+        let b2 = w2.balance().unwrap();
+        let utxos = w1.utxos().unwrap().len() + w2.utxos().unwrap().len();
+        let txs = w1.transactions().unwrap().len() + w2.transactions().unwrap().len();
+
+        let group = WolletGroup::new(network, vec![w1, w2]).unwrap();
+        let balance = group.balance().unwrap();
+        let sum = |b: &std::collections::BTreeMap<AssetId, u64>| {
+            b.get(&policy_asset).cloned().unwrap_or(0)
+        };
+        assert_eq!(sum(&balance), sum(&b1) + sum(&b2));
+        assert_eq!(group.utxos().unwrap().len(), utxos);
+        let balances = group.balances().unwrap();
+        let total: u64 = balances.confirmed.get(&policy_asset).cloned().unwrap_or(0)
+            + balances
+                .trusted_pending
+                .get(&policy_asset)
+                .cloned()
+                .unwrap_or(0)
+            + balances
+                .untrusted_pending
+                .get(&policy_asset)
+                .cloned()
+                .unwrap_or(0);
+        assert_eq!(total, sum(&balance));
+
+        let merged = group.transactions().unwrap();
+        // the wallets don't share transactions
+        assert_eq!(merged.len(), txs);
+        let expected: Vec<_> = group.wollets()[0]
+            .transactions()
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.txid, t.type_))
+            .collect();
+        let merged_ids: Vec<_> = merged.iter().map(|t| (t.txid, t.type_.clone())).collect();
+        assert_eq!(merged_ids, expected);
+        let heights: Vec<_> = merged
+            .iter()
+            .map(|t| t.height.unwrap_or(u32::MAX))
+            .collect();
+        assert!(heights.windows(2).all(|w| w[0] >= w[1]));
+
+        let err = WolletGroup::new(network, vec![other_wollet(), other_wollet()]);
+        assert!(matches!(err, Err(Error::WolletGroupDuplicateDescriptor(_))));
+    }
+
+    #[test]
+    fn test_wollet_group_network_mismatch() {
+        let mut group = WolletGroup::new(ElementsNetwork::Liquid, vec![]).unwrap();
+        assert!(matches!(
+            group.add(other_wollet()),
+            Err(Error::WolletGroupNetworkMismatch)
+        ));
+        assert!(group.remove(0).is_none());
+    }
+
+    #[test]
+    fn test_merge_tx() {
+        let wollet = test_wollet_with_many_transactions();
+        let tx = wollet
+            .transactions()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.outputs.iter().filter(|o| o.is_some()).count() > 1)
+            .unwrap();
+
+        // Split the wallet outputs between two fictional wallets
+        let mut first = tx.clone();
+        let mut second = tx.clone();
+        let idx = first.outputs.iter().position(|o| o.is_some()).unwrap();
+        for (i, o) in first.outputs.iter_mut().enumerate() {
+            if i != idx {
+                *o = None;
+            }
+        }
+        second.outputs[idx] = None;
+        second.inputs = vec![None; tx.inputs.len()];
+        let value = tx.outputs[idx].as_ref().unwrap().unblinded;
+        first.balance = [(value.asset, value.value as i64)].into();
+        *second.balance.entry(value.asset).or_default() -= value.value as i64;
+
+        merge_tx(&mut first, second);
+        assert_eq!(first.balance, tx.balance);
+        assert_eq!(first.inputs, tx.inputs);
+        assert_eq!(first.outputs, tx.outputs);
+    }
+}
@@ -0,0 +1,202 @@
+//! Labels of wallet items, importable and exportable in the
+//! [BIP-329](https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki) format.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::elements::{Address, OutPoint, Txid};
+use crate::Error;
+
+/// Key used to persist the labels with [`crate::Persister::put_value()`]
+pub(crate) const LABELS_KEY: &str = "labels";
+
+/// The type of the item referenced by a [`Label`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType {
+    /// A transaction, referenced by txid
+    Tx,
+
+    /// An address, referenced by its string representation
+    Addr,
+
+    /// A public key, referenced by its hex representation
+    Pubkey,
+
+    /// A transaction input, referenced by the outpoint it spends ("txid:vout")
+    Input,
+
+    /// A transaction output, referenced by its outpoint ("txid:vout")
+    Output,
+
+    /// An extended public key
+    Xpub,
+}
+
+/// A label as defined in BIP-329
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// The type of the referenced item
+    #[serde(rename = "type")]
+    pub type_: LabelType,
+
+    /// The reference to the item, its format depends on the type
+    #[serde(rename = "ref")]
+    pub ref_: String,
+
+    /// The label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// The descriptor (without checksum) of the wallet the item belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+
+    /// Whether the output can be spent, meaningful only for outputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+impl Label {
+    /// Create a label with only the mandatory fields and the label text
+    pub fn new(type_: LabelType, ref_: String, label: String) -> Self {
+        Self {
+            type_,
+            ref_,
+            label: Some(label),
+            origin: None,
+            spendable: None,
+        }
+    }
+
+    /// Create a label for a transaction
+    pub fn tx(txid: &Txid, label: &str) -> Self {
+        Self::new(LabelType::Tx, txid.to_string(), label.to_string())
+    }
+
+    /// Create a label for an address
+    pub fn address(address: &Address, label: &str) -> Self {
+        Self::new(LabelType::Addr, address.to_string(), label.to_string())
+    }
+
+    /// Create a label for a transaction output
+    pub fn output(outpoint: &OutPoint, label: &str) -> Self {
+        Self::new(LabelType::Output, outpoint.to_string(), label.to_string())
+    }
+}
+
+/// A collection of labels, at most one for every referenced item
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels {
+    map: BTreeMap<(LabelType, String), Label>,
+}
+
+impl Labels {
+    /// Get the label of the item of type `type_` referenced by `ref_`
+    pub fn get(&self, type_: LabelType, ref_: &str) -> Option<&Label> {
+        self.map.get(&(type_, ref_.to_string()))
+    }
+
+    /// Insert a label, returning the previous one of the same item if any
+    pub fn insert(&mut self, label: Label) -> Option<Label> {
+        self.map.insert((label.type_, label.ref_.clone()), label)
+    }
+
+    /// Remove the label of the item of type `type_` referenced by `ref_`
+    pub fn remove(&mut self, type_: LabelType, ref_: &str) -> Option<Label> {
+        self.map.remove(&(type_, ref_.to_string()))
+    }
+
+    /// Iterate over the labels, ordered by type and reference
+    pub fn iter(&self) -> impl Iterator<Item = &Label> {
+        self.map.values()
+    }
+
+    /// Number of labels
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether there are no labels
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Export the labels in the BIP-329 format: a JSON object per line
+    pub fn export_bip329(&self) -> Result<String, Error> {
+        let mut result = String::new();
+        for label in self.iter() {
+            result.push_str(&serde_json::to_string(label)?);
+            result.push('\n');
+        }
+        Ok(result)
+    }
+
+    /// Import labels in the BIP-329 format, overwriting existing labels of the same items
+    ///
+    /// Returns the number of imported labels. Empty lines are ignored.
+    pub fn import_bip329(&mut self, jsonl: &str) -> Result<usize, Error> {
+        let labels = jsonl
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Label>, _>>()?;
+        let imported = labels.len();
+        for label in labels {
+            self.insert(label);
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_bip329() {
+        // From BIP-329 test vectors
+        let jsonl = r#"{ "type": "tx", "ref": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd", "label": "Transaction", "origin": "wpkh([d34db33f/84'/0'/0'])" }
+{ "type": "addr", "ref": "bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c", "label": "Address" }
+
+{ "type": "output", "ref": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1", "label": "Output", "spendable": false }
+"#;
+        let mut labels = Labels::default();
+        assert_eq!(labels.import_bip329(jsonl).unwrap(), 3);
+        assert_eq!(labels.len(), 3);
+
+        let output = labels
+            .get(
+                LabelType::Output,
+                "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1",
+            )
+            .unwrap();
+        assert_eq!(output.label.as_deref(), Some("Output"));
+        assert_eq!(output.spendable, Some(false));
+
+        let exported = labels.export_bip329().unwrap();
+        assert_eq!(exported.lines().count(), 3);
+        let mut back = Labels::default();
+        back.import_bip329(&exported).unwrap();
+        assert_eq!(labels, back);
+
+        // Importing again overwrites
+        let relabel =
+            r#"{"type":"addr","ref":"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c","label":"New"}"#;
+        assert_eq!(labels.import_bip329(relabel).unwrap(), 1);
+        assert_eq!(labels.len(), 3);
+        let addr = labels
+            .get(
+                LabelType::Addr,
+                "bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c",
+            )
+            .unwrap();
+        assert_eq!(addr.label.as_deref(), Some("New"));
+
+        assert!(labels
+            .import_bip329(r#"{"type":"unknown","ref":"x"}"#)
+            .is_err());
+    }
+}
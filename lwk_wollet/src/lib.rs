@@ -11,7 +11,8 @@
 //! With a wallet you can:
 //! * Generate addresses via [`Wollet::address()`].
 //! * Pass it to a blockchain backend ([`ElectrumClient`], [`blocking::EsploraClient`]) to retrieve wallet history via the [`blocking::BlockchainBackend::full_scan()`] trait.
-//!   Or asyncronously via the [`asyncr::EsploraClient::full_scan()`] method. The convenience method [`full_scan_with_electrum_client()`] is also provided.
+//!   Or asyncronously via the [`asyncr::EsploraClient::full_scan()`] method. The convenience method [`full_scan_with_electrum_client()`] is also provided,
+//!   while async applications can keep a wallet synced with [`asyncr::AsyncWollet`].
 //! * Create transactions, inclunding issuances, reissuances and burn via the [`TxBuilder`].
 //! * Analyze a partially signed transaction with respect to the wallet via [`Wollet::get_details()`].
 //!
@@ -88,52 +89,91 @@
 //! # }
 //! ```
 
+pub mod address_proof;
 #[cfg(feature = "amp2")]
 pub mod amp2;
+mod checkpoints;
 pub mod clients;
 mod config;
+mod confirmation;
 mod descriptor;
 mod domain;
+pub mod drift;
 mod error;
+mod event;
+mod explicit;
+pub mod fee_monitor;
+pub mod gdk;
+mod group;
+mod labels;
 mod liquidex;
+mod mempool_accept;
 mod model;
+pub mod payment_code;
 pub mod pegin;
 mod persister;
 mod pset_create;
+mod pset_store;
 pub mod registry;
+pub mod rotation;
 mod store;
+pub mod support;
+pub mod swap;
 mod tx_builder;
 mod update;
 mod util;
 mod wollet;
+mod zero_conf;
 
-pub use crate::clients::{Capability, History};
-pub use crate::config::ElementsNetwork;
-pub use crate::descriptor::{Chain, WolletDescriptor};
+pub use crate::clients::{Capability, History, RetryPolicy, ScriptCheckpoint, TxStatus};
+pub use crate::config::{Config, ElementsNetwork};
+pub use crate::confirmation::ConfirmationPolicy;
+pub use crate::descriptor::{
+    descriptor_checksum, normalize_descriptor, verify_descriptor_checksum, Chain, ScriptIter,
+    WolletDescriptor,
+};
 pub use crate::error::Error;
+pub use crate::event::{EventQueue, WolletEvent, WolletListener};
+pub use crate::explicit::ExplicitWollet;
+pub use crate::group::WolletGroup;
+pub use crate::labels::{Label, LabelType, Labels};
 pub use crate::liquidex::LiquidexProposal;
+pub use crate::mempool_accept::MempoolAccept;
 pub use crate::model::{
-    AddressResult, ExternalUtxo, IssuanceDetails, Recipient, UnvalidatedRecipient, WalletTx,
-    WalletTxOut,
+    AddressResult, Balances, ExternalUtxo, FinalizeFailure, FinalizeReport, InputSighash,
+    IssuanceDetails, Recipient, SighashKind, TxDirection, TxFilter, UnvalidatedRecipient,
+    UtxoFilter, WalletTx, WalletTxOut, WolletStats,
 };
 pub use crate::pegin::fed_peg_script;
-pub use crate::persister::{FsPersister, NoPersist, PersistError, Persister};
-pub use crate::registry::{asset_ids, issuance_ids, Contract, Entity};
-pub use crate::tx_builder::{TxBuilder, WolletTxBuilder};
-pub use crate::update::{DownloadTxResult, Update};
+pub use crate::persister::{
+    FsPersister, MemoryPersister, NoPersist, PersistError, Persister, STORE_VERSION,
+};
+pub use crate::pset_store::{PsetStatus, StoredPset};
+pub use crate::registry::{asset_ids, issuance_ids, AssetIcon, Contract, Entity};
+pub use crate::tx_builder::{ChangeScript, CoinSelection, TxBuilder, WolletTxBuilder};
+pub use crate::update::{DownloadTxResult, Update, UPDATE_VERSION};
 pub use crate::util::EC;
-pub use crate::wollet::{Tip, Wollet};
+pub use crate::wollet::{
+    finalize_partial_with_genesis, finalize_with_genesis, Tip, Wollet, WolletConciseState,
+    WolletSnapshot,
+};
+pub use crate::zero_conf::ZeroConfRisk;
 
 #[cfg(feature = "electrum")]
 pub use crate::wollet::full_scan_to_index_with_electrum_client;
 #[cfg(feature = "electrum")]
 pub use crate::wollet::full_scan_with_electrum_client;
 #[cfg(feature = "electrum")]
-pub use clients::blocking::electrum_client::{ElectrumClient, ElectrumOptions, ElectrumUrl};
+pub use clients::blocking::electrum_client::{
+    ElectrumClient, ElectrumOptions, ElectrumServerHealth, ElectrumUrl, ELECTRUM_BATCH_SIZE,
+};
 
 #[cfg(feature = "esplora")]
 pub use age;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "tls"))]
+pub use crate::clients::tls::TlsPin;
+
 pub use crate::clients::asyncr;
 
 #[cfg(feature = "electrum")]
@@ -142,6 +182,9 @@ pub use crate::clients::blocking;
 #[cfg(feature = "elements_rpc")]
 pub use clients::blocking::ElementsRpcClient;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use clients::blocking::BlockFilesClient;
+
 #[cfg(feature = "elements_rpc")]
 pub use bitcoincore_rpc;
 
@@ -0,0 +1,163 @@
+//! Checks on whether a transaction would be accepted if broadcast
+
+use elements::Transaction;
+use serde::{Deserialize, Serialize};
+
+use crate::clients::check_witnesses_non_empty;
+use crate::{Error, Wollet};
+
+/// The minimum fee rate, in sats/kvb, relayed by Elements nodes with the default options
+const MIN_RELAY_FEE: u64 = 100;
+
+/// The maximum weight of a standard transaction
+const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+
+/// Whether a transaction would be accepted by the mempool, like the result of the
+/// `testmempoolaccept` RPC
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MempoolAccept {
+    /// Whether the transaction would be accepted
+    pub allowed: bool,
+
+    /// Why the transaction would be rejected, using the reject reasons of the node when
+    /// possible, such as `min relay fee not met`
+    pub reject_reason: Option<String>,
+}
+
+impl MempoolAccept {
+    pub(crate) fn allowed() -> Self {
+        Self {
+            allowed: true,
+            reject_reason: None,
+        }
+    }
+
+    pub(crate) fn rejected(reason: &str) -> Self {
+        Self {
+            allowed: false,
+            reject_reason: Some(reason.to_string()),
+        }
+    }
+}
+
+impl Wollet {
+    /// Check whether `tx` would be accepted if broadcast, without broadcasting it
+    ///
+    /// Only local checks are done: the transaction must be finalized, pay at least the minimum
+    /// relay fee, have a standard weight, not be already known and not spend outputs already
+    /// spent by other wallet transactions. Signatures and inputs not owned by the wallet are not
+    /// verified, use [`Wollet::test_broadcast_with()`] to check also with the backend.
+    pub fn test_broadcast(&self, tx: &Transaction) -> Result<MempoolAccept, Error> {
+        if tx.input.is_empty() {
+            return Ok(MempoolAccept::rejected("bad-txns-vin-empty"));
+        }
+        if tx.output.is_empty() {
+            return Ok(MempoolAccept::rejected("bad-txns-vout-empty"));
+        }
+        if check_witnesses_non_empty(tx).is_err() {
+            return Ok(MempoolAccept::rejected("missing-witness"));
+        }
+        if tx.weight() > MAX_STANDARD_TX_WEIGHT {
+            return Ok(MempoolAccept::rejected("tx-size"));
+        }
+        let fee_rate = tx.fee_in(self.policy_asset()) * 1000 / tx.discount_vsize().max(1) as u64;
+        if fee_rate < MIN_RELAY_FEE {
+            return Ok(MempoolAccept::rejected("min relay fee not met"));
+        }
+        if self.store.cache.heights.contains_key(&tx.txid()) {
+            return Ok(MempoolAccept::rejected("txn-already-known"));
+        }
+        let txos = self.txos_map()?;
+        let spent = tx
+            .input
+            .iter()
+            .filter_map(|i| txos.get(&i.previous_output))
+            .any(|txo| txo.is_spent);
+        if spent {
+            return Ok(MempoolAccept::rejected("bad-txns-inputs-missingorspent"));
+        }
+        Ok(MempoolAccept::allowed())
+    }
+
+    /// Check whether `tx` would be accepted if broadcast, without broadcasting it
+    ///
+    /// After the checks of [`Wollet::test_broadcast()`], the transaction is checked by the
+    /// backend if it supports it, for instance an [`crate::ElementsRpcClient`] uses the
+    /// `testmempoolaccept` RPC, which verifies also the signatures.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn test_broadcast_with<B: crate::clients::blocking::BlockchainBackend>(
+        &self,
+        tx: &Transaction,
+        backend: &B,
+    ) -> Result<MempoolAccept, Error> {
+        let local = self.test_broadcast(tx)?;
+        if !local.allowed {
+            return Ok(local);
+        }
+        Ok(backend.test_broadcast(tx)?.unwrap_or(local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::confidential::Value;
+
+    use super::MempoolAccept;
+    use crate::wollet::tests::test_wollet_with_many_transactions;
+
+    #[test]
+    fn test_test_broadcast() {
+        let wollet = test_wollet_with_many_transactions();
+        let txs = wollet.transactions().unwrap();
+        let sent = txs
+            .iter()
+            .find(|tx| tx.inputs.iter().all(|i| i.is_some()))
+            .unwrap();
+        let rejected = MempoolAccept::rejected;
+
+        let mut tx = sent.tx.clone();
+        assert_eq!(
+            wollet.test_broadcast(&tx).unwrap(),
+            rejected("txn-already-known")
+        );
+
+        // the wallet inputs are already spent
+        tx.lock_time = elements::LockTime::from_height(1).unwrap();
+        assert_eq!(
+            wollet.test_broadcast(&tx).unwrap(),
+            rejected("bad-txns-inputs-missingorspent")
+        );
+
+        // spend an unspent output instead
+        let utxo = wollet.utxos().unwrap().remove(0);
+        tx.input.truncate(1);
+        tx.input[0].previous_output = utxo.outpoint;
+        assert_eq!(
+            wollet.test_broadcast(&tx).unwrap(),
+            MempoolAccept::allowed()
+        );
+
+        let mut no_witness = tx.clone();
+        no_witness.input[0].witness = Default::default();
+        assert_eq!(
+            wollet.test_broadcast(&no_witness).unwrap(),
+            rejected("missing-witness")
+        );
+
+        let mut no_fee = tx.clone();
+        for output in no_fee.output.iter_mut().filter(|o| o.is_fee()) {
+            output.value = Value::Explicit(0);
+        }
+        assert_eq!(
+            wollet.test_broadcast(&no_fee).unwrap(),
+            rejected("min relay fee not met")
+        );
+
+        let mut no_inputs = tx;
+        no_inputs.input.clear();
+        assert_eq!(
+            wollet.test_broadcast(&no_inputs).unwrap(),
+            rejected("bad-txns-vin-empty")
+        );
+    }
+}
@@ -1,8 +1,12 @@
+use crate::bitcoin::bip32::KeySource;
 use crate::descriptor::Chain;
-use crate::elements::{Address, AssetId, OutPoint, Script, Transaction, TxOutSecrets, Txid};
+use crate::elements::{
+    Address, AssetId, EcdsaSighashType, OutPoint, SchnorrSighashType, Script, Transaction,
+    TxOutSecrets, Txid,
+};
 use crate::pset_create::validate_address;
-use crate::secp256k1::PublicKey;
-use crate::store::Timestamp;
+use crate::secp256k1::{Message, PublicKey};
+use crate::store::{Height, Timestamp};
 use crate::{ElementsNetwork, Error};
 use elements::bitcoin;
 
@@ -52,9 +56,27 @@ pub struct WalletTx {
     pub balance: BTreeMap<AssetId, i64>,
     pub fee: u64,
     pub type_: String,
+
+    /// The timestamp of the block confirming the transaction, in seconds since the Unix epoch
+    ///
+    /// The timestamps are downloaded and persisted by the scans, `None` if unconfirmed.
     pub timestamp: Option<Timestamp>,
+
     pub inputs: Vec<Option<WalletTxOut>>,
     pub outputs: Vec<Option<WalletTxOut>>,
+
+    /// Whether the rangeproofs and surjection proofs of the transaction are valid and the wallet
+    /// outputs commitments match their unblinded values
+    ///
+    /// `None` if the verification is disabled (see [`crate::Config::with_verify_proofs()`]) or
+    /// the previous outputs of the transaction are not available.
+    #[serde(default)]
+    pub verified: Option<bool>,
+
+    /// The risk of accepting the transaction before it's confirmed, `None` if the transaction is
+    /// confirmed or it doesn't increase the wallet balance
+    #[serde(default)]
+    pub zero_conf_risk: Option<crate::ZeroConfRisk>,
 }
 
 /// A recipient of a transaction.
@@ -220,6 +242,347 @@ impl BitcoinAddressResult {
     }
 }
 
+/// Filter for [`crate::Wollet::utxos_with()`]
+///
+/// By default it matches the same UTXOs returned by [`crate::Wollet::utxos()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtxoFilter {
+    asset: Option<AssetId>,
+    min_value: u64,
+    min_confirmations: u32,
+    exclude_spent_by_unconfirmed: bool,
+    heights: HeightRange,
+    page: Page,
+}
+
+impl Default for UtxoFilter {
+    fn default() -> Self {
+        Self {
+            asset: None,
+            min_value: 0,
+            min_confirmations: 0,
+            exclude_spent_by_unconfirmed: true,
+            heights: HeightRange::default(),
+            page: Page::default(),
+        }
+    }
+}
+
+impl UtxoFilter {
+    /// Match only UTXOs of the given asset
+    pub fn asset(mut self, asset: AssetId) -> Self {
+        self.asset = Some(asset);
+        self
+    }
+
+    /// Match only UTXOs with value greater or equal than `min_value`
+    pub fn min_value(mut self, min_value: u64) -> Self {
+        self.min_value = min_value;
+        self
+    }
+
+    /// Match only UTXOs with at least `min_confirmations` confirmations
+    pub fn min_confirmations(mut self, min_confirmations: u32) -> Self {
+        self.min_confirmations = min_confirmations;
+        self
+    }
+
+    /// Whether to exclude the outputs spent by unconfirmed transactions, `true` by default
+    ///
+    /// If `false` such outputs are returned with [`WalletTxOut::is_spent`] set, this is useful
+    /// to know which outputs will be available again if the spending transactions are dropped.
+    pub fn exclude_spent_by_unconfirmed(mut self, exclude: bool) -> Self {
+        self.exclude_spent_by_unconfirmed = exclude;
+        self
+    }
+
+    /// Match only UTXOs created at or after `height`, unconfirmed UTXOs are included
+    pub fn min_height(mut self, height: Height) -> Self {
+        self.heights.min = Some(height);
+        self
+    }
+
+    /// Match only UTXOs confirmed at or before `height`, unconfirmed UTXOs are excluded
+    pub fn max_height(mut self, height: Height) -> Self {
+        self.heights.max = Some(height);
+        self
+    }
+
+    /// Skip the first `offset` matching UTXOs
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.page.offset = offset;
+        self
+    }
+
+    /// Return at most `limit` UTXOs
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.page.limit = Some(limit);
+        self
+    }
+
+    pub(crate) fn matches(&self, utxo: &WalletTxOut, confirmations: u32) -> bool {
+        let asset_matches = match self.asset {
+            Some(asset) => asset == utxo.unblinded.asset,
+            None => true,
+        };
+        asset_matches
+            && utxo.unblinded.value >= self.min_value
+            && confirmations >= self.min_confirmations
+            && self.heights.contains(utxo.height)
+    }
+
+    pub(crate) fn page(&self) -> &Page {
+        &self.page
+    }
+
+    pub(crate) fn excludes_spent_by_unconfirmed(&self) -> bool {
+        self.exclude_spent_by_unconfirmed
+    }
+}
+
+/// The heights at which transactions or UTXOs are matched by a filter, unconfirmed ones are
+/// considered above any height
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct HeightRange {
+    min: Option<Height>,
+    max: Option<Height>,
+}
+
+impl HeightRange {
+    fn contains(&self, height: Option<Height>) -> bool {
+        match height {
+            Some(h) => {
+                self.min.map_or(true, |min| h >= min) && self.max.map_or(true, |max| h <= max)
+            }
+            None => self.max.is_none(),
+        }
+    }
+}
+
+/// A page of the results of a query
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Page {
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    /// The items of this page
+    pub(crate) fn apply<T>(&self, items: impl Iterator<Item = T>) -> impl Iterator<Item = T> {
+        items
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+    }
+}
+
+/// The direction of a wallet transaction, see [`TxFilter::direction()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxDirection {
+    /// The wallet balance doesn't decrease for any asset
+    Incoming,
+
+    /// The wallet balance decreases for some asset, including the fee paid by the wallet
+    Outgoing,
+}
+
+/// Filter for [`crate::Wollet::transactions_with()`]
+///
+/// By default it matches the same transactions returned by [`crate::Wollet::transactions()`],
+/// in the same order: large wallets can return them a page at a time with
+/// [`TxFilter::offset()`] and [`TxFilter::limit()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxFilter {
+    asset: Option<AssetId>,
+    direction: Option<TxDirection>,
+    heights: HeightRange,
+    page: Page,
+}
+
+impl TxFilter {
+    /// Match only transactions changing the wallet balance of the given asset
+    pub fn asset(mut self, asset: AssetId) -> Self {
+        self.asset = Some(asset);
+        self
+    }
+
+    /// Match only transactions in the given direction
+    pub fn direction(mut self, direction: TxDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Match only transactions confirmed at or after `height`, unconfirmed transactions are
+    /// included
+    pub fn min_height(mut self, height: Height) -> Self {
+        self.heights.min = Some(height);
+        self
+    }
+
+    /// Match only transactions confirmed at or before `height`, unconfirmed transactions are
+    /// excluded
+    pub fn max_height(mut self, height: Height) -> Self {
+        self.heights.max = Some(height);
+        self
+    }
+
+    /// Skip the first `offset` matching transactions
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.page.offset = offset;
+        self
+    }
+
+    /// Return at most `limit` transactions
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.page.limit = Some(limit);
+        self
+    }
+
+    pub(crate) fn matches_height(&self, height: Option<Height>) -> bool {
+        self.heights.contains(height)
+    }
+
+    pub(crate) fn matches_balance(&self, balance: &BTreeMap<AssetId, i64>) -> bool {
+        let asset_matches = match self.asset {
+            Some(asset) => balance.get(&asset).map_or(false, |v| *v != 0),
+            None => true,
+        };
+        let outgoing = balance.values().any(|v| *v < 0);
+        let direction_matches = match self.direction {
+            Some(TxDirection::Incoming) => !outgoing,
+            Some(TxDirection::Outgoing) => outgoing,
+            None => true,
+        };
+        asset_matches && direction_matches
+    }
+
+    pub(crate) fn page(&self) -> &Page {
+        &self.page
+    }
+}
+
+/// Value returned from [`crate::Wollet::balances()`], containing the wallet balance split
+/// according to how much the funds can be trusted
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Balances {
+    /// Balance of the confirmed UTXOs
+    pub confirmed: BTreeMap<AssetId, u64>,
+
+    /// Balance of the unconfirmed UTXOs created by transactions spending only wallet inputs,
+    /// for instance the change of an outgoing transaction
+    pub trusted_pending: BTreeMap<AssetId, u64>,
+
+    /// Balance of the unconfirmed UTXOs created by transactions spending inputs not owned by
+    /// the wallet, for instance an incoming payment
+    pub untrusted_pending: BTreeMap<AssetId, u64>,
+}
+
+impl Balances {
+    /// The balance that can be considered safe to spend: confirmed plus trusted pending
+    pub fn trusted(&self) -> BTreeMap<AssetId, u64> {
+        let mut r = self.confirmed.clone();
+        for (asset, value) in self.trusted_pending.iter() {
+            *r.entry(*asset).or_default() += value;
+        }
+        r
+    }
+}
+
+/// Value returned from [`crate::Wollet::stats()`], a summary of the wallet on-chain footprint
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WolletStats {
+    /// Number of wallet transactions, as returned by [`crate::Wollet::transactions()`]
+    pub txs: usize,
+
+    /// Number of the wallet transactions not yet confirmed
+    pub unconfirmed_txs: usize,
+
+    /// Number of unspent outputs, as returned by [`crate::Wollet::utxos()`]
+    pub utxos: usize,
+
+    /// Number of wallet addresses that received at least an output
+    pub used_addresses: usize,
+
+    /// Number of assets with a positive balance
+    pub assets: usize,
+
+    /// Total fees paid, in satoshi of the policy asset, by the transactions spending wallet
+    /// inputs
+    pub fees_paid: u64,
+
+    /// Height of the first confirmed wallet transaction
+    pub first_height: Option<u32>,
+
+    /// Height of the last confirmed wallet transaction
+    pub last_height: Option<u32>,
+
+    /// Number of cached transactions, including the ones not involving the wallet but needed to
+    /// verify its transactions
+    pub cached_txs: usize,
+
+    /// Size in bytes of the cached transactions
+    pub cache_size: usize,
+}
+
+/// The reason why a PSET input could not be finalized, see [`FinalizeReport`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum FinalizeFailure {
+    /// The input doesn't have enough signatures to satisfy its script
+    MissingSignature,
+
+    /// The input script is not supported by the finalizer
+    UnsupportedScript,
+
+    /// Any other reason, described in the string
+    Other(String),
+}
+
+/// Value returned from [`crate::Wollet::finalize_partial()`], reporting which inputs have been
+/// finalized
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FinalizeReport {
+    /// Indexes of the finalized inputs, including the ones already finalized before the call
+    pub finalized: Vec<usize>,
+
+    /// Indexes of the inputs that could not be finalized, with the reason
+    pub not_finalized: Vec<(usize, FinalizeFailure)>,
+}
+
+impl FinalizeReport {
+    /// Whether all the inputs have been finalized
+    pub fn is_complete(&self) -> bool {
+        self.not_finalized.is_empty()
+    }
+}
+
+/// The kind of signature required by a PSET input, see [`InputSighash`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SighashKind {
+    /// Segwit v0 input, to be signed with ECDSA
+    Ecdsa(EcdsaSighashType),
+
+    /// Taproot input, to be signed with Schnorr (BIP340)
+    Schnorr(SchnorrSighashType),
+}
+
+/// The message that must be signed for a PSET input, returned by
+/// [`crate::Wollet::input_sighashes()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSighash {
+    /// Index of the input in the PSET
+    pub index: usize,
+
+    /// The message to sign
+    pub message: Message,
+
+    /// The signature algorithm and the sighash flag to use
+    pub kind: SighashKind,
+
+    /// The keys expected to sign the input, with their key origin
+    ///
+    /// For taproot inputs the keys are x-only, here they are returned with even parity.
+    pub keys: Vec<(bitcoin::PublicKey, KeySource)>,
+}
+
 /// Value returned from [`crate::Wollet::issuance()`] containing details about an issuance
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IssuanceDetails {
@@ -0,0 +1,395 @@
+//! Reusable payment codes (experimental)
+//!
+//! A payment code is a static identifier, made of a scan public key and a spend public key, that
+//! can be published (for example as a donation address) without incurring address reuse.
+//! The scheme is inspired by [silent payments](https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki)
+//! but adapted to the Elements confidential addresses:
+//!
+//! * The sender chooses an ephemeral key `e` and computes the shared secret `S = e·B_scan`.
+//! * The `k`-th output is locked to `B_spend + H_tweak(S, k)·G` (p2wpkh) and blinded with the
+//!   blinding key `H_blind(S, k)`.
+//! * The ephemeral public key `E = e·G` is published in an `OP_RETURN` output of the same
+//!   transaction, so that the receiver can compute `S = b_scan·E` and find (and unblind) the
+//!   outputs belonging to it.
+//!
+//! The receiver scans transactions with a [`PaymentCodeScanner`], which needs only the scan
+//! secret key, while spending the found outputs requires the spend secret key tweaked with
+//! [`PaymentCodeReceived::spend_tweak`].
+//!
+//! Payments can't be found by the script based scan of Electrum and Esplora servers, every
+//! transaction must be scanned. The `ElementsRpcClient` does it while syncing the wallet if
+//! configured with `with_payment_code_scanner`, with the other backends blocks must be fetched and
+//! passed to [`PaymentCodeScanner::scan_block`] by the caller.
+//!
+//! This is an exploration: the format is not standardized and could change.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use elements::hashes::{sha256t_hash_newtype, Hash};
+use elements::hex::ToHex;
+use elements::opcodes::all::OP_RETURN;
+use elements::script::{Builder, Instruction};
+use elements::{bitcoin, Address, AddressParams, Block, OutPoint, Script, Transaction};
+use elements::{TxOut, TxOutSecrets};
+
+use crate::secp256k1::{PublicKey, Scalar, SecretKey};
+use crate::{Error, EC};
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct PaymentCodeTweakTag = hash_str("LWK-PaymentCode-Tweak/1.0");
+
+    /// A tagged hash to derive the tweak of the spend public key
+    #[hash_newtype(forward)]
+    pub struct PaymentCodeTweakHash(_);
+}
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct PaymentCodeBlindingTag = hash_str("LWK-PaymentCode-Blinding/1.0");
+
+    /// A tagged hash to derive the blinding key of the outputs
+    #[hash_newtype(forward)]
+    pub struct PaymentCodeBlindingHash(_);
+}
+
+/// Prefix of the string representation of a [`PaymentCode`]
+const PAYMENT_CODE_PREFIX: &str = "lwkpc";
+
+/// Version of the payment code scheme
+const PAYMENT_CODE_VERSION: u8 = 0;
+
+/// Marker prefixing the ephemeral public key in the notification `OP_RETURN` output
+const NOTIFICATION_MARKER: &[u8] = b"LWKPC";
+
+/// Maximum number of outputs to the same payment code searched in a single transaction
+const MAX_OUTPUTS_PER_TX: u32 = 100;
+
+/// A reusable payment code, consisting of a scan and a spend public keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaymentCode {
+    scan: PublicKey,
+    spend: PublicKey,
+}
+
+/// A one-time destination derived from a [`PaymentCode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentCodeOutput {
+    /// The confidential address to send the funds to
+    pub address: Address,
+
+    /// The script of the output that must be included in the same transaction so that the
+    /// receiver can find the payment
+    pub notification_script: Script,
+}
+
+/// An output found by [`PaymentCodeScanner`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentCodeReceived {
+    /// The outpoint of the received output
+    pub outpoint: OutPoint,
+
+    /// The script pubkey of the received output
+    pub script_pubkey: Script,
+
+    /// The unblinded values of the received output
+    pub unblinded: TxOutSecrets,
+
+    /// The index of this output among the ones paying the same code in the transaction
+    pub k: u32,
+
+    /// The tweak to add to the spend secret key to obtain the key able to spend this output
+    pub spend_tweak: SecretKey,
+}
+
+impl PaymentCode {
+    /// Create a payment code from its scan and spend public keys
+    pub fn new(scan: PublicKey, spend: PublicKey) -> Self {
+        Self { scan, spend }
+    }
+
+    /// The scan public key
+    pub fn scan_pubkey(&self) -> PublicKey {
+        self.scan
+    }
+
+    /// The spend public key
+    pub fn spend_pubkey(&self) -> PublicKey {
+        self.spend
+    }
+
+    /// Derive the `k`-th one-time destination of this payment code using the sender chosen
+    /// `ephemeral` secret key.
+    ///
+    /// All the outputs paying this code in the same transaction must use the same ephemeral key,
+    /// with `k` starting from 0 and increasing by one for every output.
+    /// The `notification_script` must be included only once per transaction.
+    pub fn payment(
+        &self,
+        ephemeral: &SecretKey,
+        k: u32,
+        params: &'static AddressParams,
+    ) -> Result<PaymentCodeOutput, Error> {
+        let shared = self.scan.mul_tweak(&EC, &Scalar::from(*ephemeral))?;
+        let (spend_pubkey, blinding_pubkey) = derive_output_keys(&shared, &self.spend, k)?;
+        let address = Address::p2wpkh(
+            &bitcoin::PublicKey::new(spend_pubkey),
+            Some(blinding_pubkey),
+            params,
+        );
+        Ok(PaymentCodeOutput {
+            address,
+            notification_script: notification_script(&ephemeral.public_key(&EC)),
+        })
+    }
+}
+
+impl Display for PaymentCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut bytes = Vec::with_capacity(67);
+        bytes.push(PAYMENT_CODE_VERSION);
+        bytes.extend(self.scan.serialize());
+        bytes.extend(self.spend.serialize());
+        write!(f, "{}{}", PAYMENT_CODE_PREFIX, bytes.to_hex())
+    }
+}
+
+impl FromStr for PaymentCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix(PAYMENT_CODE_PREFIX)
+            .ok_or_else(|| Error::Generic("Payment code must start with 'lwkpc'".into()))?;
+        let bytes: Vec<u8> = elements::hex::FromHex::from_hex(hex)?;
+        if bytes.len() != 67 {
+            return Err(Error::Generic("Invalid payment code length".into()));
+        }
+        if bytes[0] != PAYMENT_CODE_VERSION {
+            return Err(Error::InvalidVersion);
+        }
+        Ok(Self {
+            scan: PublicKey::from_slice(&bytes[1..34])?,
+            spend: PublicKey::from_slice(&bytes[34..67])?,
+        })
+    }
+}
+
+/// Scans transactions looking for outputs paying a [`PaymentCode`]
+pub struct PaymentCodeScanner {
+    scan_key: SecretKey,
+    spend: PublicKey,
+}
+
+impl PaymentCodeScanner {
+    /// Create a scanner from the scan secret key and the spend public key
+    pub fn new(scan_key: SecretKey, spend: PublicKey) -> Self {
+        Self { scan_key, spend }
+    }
+
+    /// The payment code to share with senders
+    pub fn payment_code(&self) -> PaymentCode {
+        PaymentCode::new(self.scan_key.public_key(&EC), self.spend)
+    }
+
+    /// Return the outputs of the transaction paying our payment code
+    pub fn scan_tx(&self, tx: &Transaction) -> Result<Vec<PaymentCodeReceived>, Error> {
+        let mut result = vec![];
+        let txid = tx.txid();
+        for ephemeral in tx.output.iter().filter_map(ephemeral_pubkey) {
+            let shared = ephemeral.mul_tweak(&EC, &Scalar::from(self.scan_key))?;
+            for k in 0..MAX_OUTPUTS_PER_TX {
+                let tweak = tweak_hash(&shared, k)?;
+                let spend_pubkey = self.spend.add_exp_tweak(&EC, &Scalar::from(tweak))?;
+                let script_pubkey = p2wpkh_script(&spend_pubkey);
+                let found = tx
+                    .output
+                    .iter()
+                    .enumerate()
+                    .find(|(_, o)| o.script_pubkey == script_pubkey);
+                let Some((vout, output)) = found else {
+                    break;
+                };
+                let blinding_key = blinding_hash(&shared, k)?;
+                let unblinded = output.unblind(&EC, blinding_key)?;
+                result.push(PaymentCodeReceived {
+                    outpoint: OutPoint::new(txid, vout as u32),
+                    script_pubkey,
+                    unblinded,
+                    k,
+                    spend_tweak: tweak,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Return the outputs of all the transactions in the block paying our payment code
+    ///
+    /// Since outputs paying a payment code cannot be known in advance, they can't be found by
+    /// the script based scan of the blockchain backends, thus the receiver must process every
+    /// block (or every transaction with a notification output).
+    pub fn scan_block(&self, block: &Block) -> Result<Vec<PaymentCodeReceived>, Error> {
+        let mut result = vec![];
+        for tx in block.txdata.iter() {
+            result.extend(self.scan_tx(tx)?);
+        }
+        Ok(result)
+    }
+}
+
+fn derive_output_keys(
+    shared: &PublicKey,
+    spend: &PublicKey,
+    k: u32,
+) -> Result<(PublicKey, PublicKey), Error> {
+    let tweak = tweak_hash(shared, k)?;
+    let spend_pubkey = spend.add_exp_tweak(&EC, &Scalar::from(tweak))?;
+    let blinding_pubkey = blinding_hash(shared, k)?.public_key(&EC);
+    Ok((spend_pubkey, blinding_pubkey))
+}
+
+fn tagged_input(shared: &PublicKey, k: u32) -> Vec<u8> {
+    let mut engine = Vec::with_capacity(37);
+    engine.extend(shared.serialize());
+    engine.extend(k.to_be_bytes());
+    engine
+}
+
+fn tweak_hash(shared: &PublicKey, k: u32) -> Result<SecretKey, Error> {
+    let hash = PaymentCodeTweakHash::hash(&tagged_input(shared, k));
+    Ok(SecretKey::from_slice(hash.as_byte_array())?)
+}
+
+fn blinding_hash(shared: &PublicKey, k: u32) -> Result<SecretKey, Error> {
+    let hash = PaymentCodeBlindingHash::hash(&tagged_input(shared, k));
+    Ok(SecretKey::from_slice(hash.as_byte_array())?)
+}
+
+fn p2wpkh_script(pubkey: &PublicKey) -> Script {
+    Address::p2wpkh(
+        &bitcoin::PublicKey::new(*pubkey),
+        None,
+        &AddressParams::ELEMENTS, // the params doesn't matter, we use only the script pubkey
+    )
+    .script_pubkey()
+}
+
+fn notification_script(ephemeral: &PublicKey) -> Script {
+    let mut data = NOTIFICATION_MARKER.to_vec();
+    data.extend(ephemeral.serialize());
+    Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_slice(&data)
+        .into_script()
+}
+
+/// Return the ephemeral public key if the output is a payment code notification
+fn ephemeral_pubkey(output: &TxOut) -> Option<PublicKey> {
+    let mut instructions = output.script_pubkey.instructions();
+    match instructions.next()? {
+        Ok(Instruction::Op(op)) if op == OP_RETURN => {}
+        _ => return None,
+    }
+    match instructions.next()? {
+        Ok(Instruction::PushBytes(data)) => {
+            let key = data.strip_prefix(NOTIFICATION_MARKER)?;
+            PublicKey::from_slice(key).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::confidential::{Asset, AssetBlindingFactor, Nonce, Value, ValueBlindingFactor};
+    use elements::{AssetId, TxOutWitness};
+
+    use super::*;
+
+    fn scanner() -> PaymentCodeScanner {
+        let scan_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let spend_key = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        PaymentCodeScanner::new(scan_key, spend_key.public_key(&EC))
+    }
+
+    #[test]
+    fn test_payment_code_roundtrip() {
+        let code = scanner().payment_code();
+        let s = code.to_string();
+        assert!(s.starts_with("lwkpc00"));
+        assert_eq!(s.parse::<PaymentCode>().unwrap(), code);
+        assert!(s[1..].parse::<PaymentCode>().is_err());
+        assert!(s[..s.len() - 2].parse::<PaymentCode>().is_err());
+    }
+
+    #[test]
+    fn test_payment_code_scan() {
+        let scanner = scanner();
+        let code = scanner.payment_code();
+        let ephemeral = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let params = &AddressParams::ELEMENTS;
+        let first = code.payment(&ephemeral, 0, params).unwrap();
+        let second = code.payment(&ephemeral, 1, params).unwrap();
+        assert_ne!(first.address, second.address);
+        assert_eq!(first.notification_script, second.notification_script);
+
+        let mut rng = rand::thread_rng();
+        let asset = AssetId::default();
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let spent = [TxOutSecrets::new(
+            asset,
+            AssetBlindingFactor::zero(),
+            3000,
+            ValueBlindingFactor::zero(),
+        )];
+        for (value, address) in [(1000, &first.address), (2000, &second.address)] {
+            let (txout, _, _, _) = TxOut::new_not_last_confidential(
+                &mut rng,
+                &EC,
+                value,
+                address.clone(),
+                asset,
+                &spent,
+            )
+            .unwrap();
+            tx.output.push(txout);
+        }
+        tx.output.push(TxOut {
+            asset: Asset::Explicit(asset),
+            value: Value::Explicit(0),
+            nonce: Nonce::Null,
+            script_pubkey: first.notification_script.clone(),
+            witness: TxOutWitness::default(),
+        });
+
+        let received = scanner.scan_tx(&tx).unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].unblinded.value, 1000);
+        assert_eq!(received[1].unblinded.value, 2000);
+        assert_eq!(received[1].k, 1);
+
+        // The tweaked spend key controls the output
+        let spend_key = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let key = spend_key
+            .add_tweak(&Scalar::from(received[0].spend_tweak))
+            .unwrap();
+        assert_eq!(
+            p2wpkh_script(&key.public_key(&EC)),
+            tx.output[0].script_pubkey
+        );
+
+        // Another scanner doesn't find anything
+        let other = PaymentCodeScanner::new(
+            SecretKey::from_slice(&[4u8; 32]).unwrap(),
+            code.spend_pubkey(),
+        );
+        assert!(other.scan_tx(&tx).unwrap().is_empty());
+    }
+}
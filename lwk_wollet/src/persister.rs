@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
     fs,
     ops::Add,
@@ -7,8 +8,11 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use aes_gcm_siv::aead::generic_array::GenericArray;
+use aes_gcm_siv::{Aes256GcmSiv, KeyInit};
 use elements::{bitcoin::hashes::Hash, hashes::sha256t_hash_newtype};
 
+use crate::update::{decrypt, encrypt};
 use crate::{ElementsNetwork, Error, Update, WolletDescriptor};
 
 /// Error type for the [`Persister`] trait.
@@ -20,6 +24,10 @@ pub enum PersistError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    /// The persisted data cannot be decrypted or decoded
+    #[error("Corrupted data: {0}")]
+    Corrupted(String),
+
     #[error("{0}")]
     Other(String),
 }
@@ -27,8 +35,50 @@ pub enum PersistError {
 /// Trait for persisting updates.
 ///
 /// A persister writing on the file system is provided via [`FsPersister`],
-/// otherwise a user can use [`NoPersist`] to avoid persistence.
+/// otherwise a user can use [`NoPersist`] to avoid persistence, or [`MemoryPersister`] to keep
+/// the data only in memory.
 /// Other persisters can be implemented by the user (even across ffi boundaries).
+///
+/// The wallet state is rebuilt at startup by applying the updates in order, so a storage only
+/// needs to append updates and to read them by position. For instance with a key-value store:
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use std::sync::{Arc, Mutex};
+/// # use lwk_wollet::{ElementsNetwork, PersistError, Persister, Update, Wollet, WolletDescriptor};
+/// #[derive(Default)]
+/// struct KvPersister(Mutex<HashMap<String, Vec<u8>>>);
+///
+/// impl Persister for KvPersister {
+///     fn get(&self, index: usize) -> Result<Option<Update>, PersistError> {
+///         match self.get_value(&format!("update_{index}"))? {
+///             Some(bytes) => Ok(Some(Update::deserialize(&bytes)?)),
+///             None => Ok(None),
+///         }
+///     }
+///
+///     fn push(&self, update: Update) -> Result<(), PersistError> {
+///         let index = self.0.lock().unwrap().keys().filter(|k| k.starts_with("update_")).count();
+///         self.put_value(&format!("update_{index}"), &update.serialize()?)
+///     }
+///
+///     fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>, PersistError> {
+///         Ok(self.0.lock().unwrap().get(key).cloned())
+///     }
+///
+///     fn put_value(&self, key: &str, value: &[u8]) -> Result<(), PersistError> {
+///         self.0.lock().unwrap().insert(key.to_string(), value.to_vec());
+///         Ok(())
+///     }
+/// }
+///
+/// let desc: WolletDescriptor = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+/// let persister = Arc::new(KvPersister::default());
+/// let wollet = Wollet::new(ElementsNetwork::LiquidTestnet, persister, desc).unwrap();
+/// ```
+///
+/// The data is not encrypted by the wallet: implementations storing it on untrusted media should
+/// encrypt it, as [`FsPersister`] does.
 pub trait Persister {
     /// Return ith elements inserted
     fn get(&self, index: usize) -> Result<Option<Update>, PersistError>;
@@ -37,6 +87,29 @@ pub trait Persister {
     ///
     /// Implementors are encouraged to coalesce consequent updates with `update.only_tip() == true`
     fn push(&self, update: Update) -> Result<(), PersistError>;
+
+    /// Return the value previously stored with [`Persister::put_value()`] under `key`
+    ///
+    /// Used for wallet data not contained in updates, like labels.
+    /// The default implementation doesn't persist anything.
+    fn get_value(&self, _key: &str) -> Result<Option<Vec<u8>>, PersistError> {
+        Ok(None)
+    }
+
+    /// Persist `value` under `key`, replacing the previous value if any
+    ///
+    /// [`FsPersister`] accepts only keys made of ASCII alphanumeric characters, `_` and `-`.
+    fn put_value(&self, _key: &str, _value: &[u8]) -> Result<(), PersistError> {
+        Ok(())
+    }
+
+    /// Remove all the updates, keeping the values
+    ///
+    /// Used to rebuild the wallet cache when the updates cannot be loaded. The default
+    /// implementation fails, and the wallet returns the loading error instead.
+    fn clear(&self) -> Result<(), PersistError> {
+        Err(PersistError::Other("clear not supported".to_string()))
+    }
 }
 
 sha256t_hash_newtype! {
@@ -48,6 +121,18 @@ sha256t_hash_newtype! {
     pub struct DirectoryIdHash(_);
 }
 
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct PasswordSaltTag = hash_str("LWK-FS-Password-Salt/1.0");
+
+    /// A tagged hash of the descriptor used as salt to derive the key from a password
+    #[hash_newtype(forward)]
+    pub struct PasswordSaltHash(_);
+}
+
+/// The scrypt cost parameter used by [`FsPersister::with_password()`], 2^15 iterations
+const SCRYPT_LOG_N: u8 = 15;
+
 /// Implementation of a [`Persister`] which persist nothing.
 pub struct NoPersist {}
 
@@ -65,6 +150,10 @@ impl Persister for NoPersist {
     fn push(&self, _update: Update) -> Result<(), PersistError> {
         Ok(())
     }
+
+    fn clear(&self) -> Result<(), PersistError> {
+        Ok(())
+    }
 }
 
 struct FsPersisterInner {
@@ -74,8 +163,8 @@ struct FsPersisterInner {
     /// Next free position to write an update
     next: Counter,
 
-    /// used to create the cipher to encrypt data
-    desc: WolletDescriptor,
+    /// used to encrypt data, derived from the descriptor by default
+    cipher: Aes256GcmSiv,
 }
 
 /// A file system persister that writes encrypted incremental updates
@@ -86,15 +175,59 @@ pub struct FsPersister {
 impl FsPersister {
     /// Creates a persister of updates. While being written they are encrypted using a key derived
     /// from the given descriptor.
-    /// From the given path create a network subdirectory with
-    /// another subdirectory which name is one-way derived from the descriptor
+    /// From the given path create a network subdirectory (see [`ElementsNetwork::namespace()`])
+    /// with another subdirectory which name is one-way derived from the descriptor
     pub fn new<P: AsRef<Path>>(
         path: P,
         network: ElementsNetwork,
         desc: &WolletDescriptor,
+    ) -> Result<Arc<Self>, Error> {
+        Self::with_cipher(path, network, desc, desc.cipher())
+    }
+
+    /// Like [`FsPersister::new()`], but the data is encrypted with the given key instead of a key
+    /// derived from the descriptor
+    ///
+    /// With the default key anyone having the descriptor, which is often stored together with the
+    /// wallet data, can read the wallet transactions and amounts. The cache of a wallet must be
+    /// always opened with the same key, otherwise this fails with [`Error::CannotDecrypt`]; to
+    /// change the key delete the cache and scan again.
+    pub fn with_key<P: AsRef<Path>>(
+        path: P,
+        network: ElementsNetwork,
+        desc: &WolletDescriptor,
+        key: [u8; 32],
+    ) -> Result<Arc<Self>, Error> {
+        let cipher = Aes256GcmSiv::new(GenericArray::from_slice(&key));
+        Self::with_cipher(path, network, desc, cipher)
+    }
+
+    /// Like [`FsPersister::with_key()`], with the key derived from the given password
+    ///
+    /// The key is derived with scrypt, salted with the descriptor.
+    pub fn with_password<P: AsRef<Path>>(
+        path: P,
+        network: ElementsNetwork,
+        desc: &WolletDescriptor,
+        password: &str,
+    ) -> Result<Arc<Self>, Error> {
+        let salt = PasswordSaltHash::hash(desc.to_string().as_bytes());
+        let params = scrypt::Params::new(SCRYPT_LOG_N, 8, 1, 32)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), salt.as_ref(), &params, &mut key)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        Self::with_key(path, network, desc, key)
+    }
+
+    fn with_cipher<P: AsRef<Path>>(
+        path: P,
+        network: ElementsNetwork,
+        desc: &WolletDescriptor,
+        cipher: Aes256GcmSiv,
     ) -> Result<Arc<Self>, Error> {
         let mut path = path.as_ref().to_path_buf();
-        path.push(network.as_str());
+        path.push(network.namespace());
         path.push("enc_cache");
         path.push(DirectoryIdHash::hash(desc.to_string().as_bytes()).to_string());
         if path.is_file() {
@@ -116,13 +249,78 @@ impl FsPersister {
             }
         }
 
-        Ok(Arc::new(Self {
-            inner: Mutex::new(FsPersisterInner {
-                path,
-                next,
-                desc: desc.clone(),
-            }),
-        }))
+        let inner = FsPersisterInner { path, next, cipher };
+        // fail early if the data has been encrypted with a different key, with versioned stores
+        // the version value is checked instead, so that a corrupted update can be rebuilt
+        if inner.next.0 > 0 && !inner.value_path(STORE_VERSION_KEY)?.exists() {
+            let bytes = fs::read(inner.path(&Counter::from(0)))?;
+            decrypt(&bytes, &inner.cipher).map_err(|_| Error::CannotDecrypt)?;
+        }
+
+        let persister = Self {
+            inner: Mutex::new(inner),
+        };
+        persister.migrate(desc)?;
+        Ok(Arc::new(persister))
+    }
+
+    /// Bring the directory to [`STORE_VERSION`]
+    ///
+    /// Updates serialized with previous versions are rewritten with [`crate::UPDATE_VERSION`].
+    /// If an update can't be read, it's removed with the following ones: the wallet loads the
+    /// previous updates and the next scan downloads the missing data again.
+    fn migrate(&self, desc: &WolletDescriptor) -> Result<(), Error> {
+        let version_bytes = {
+            let inner = self.inner.lock().map_err(to_other)?;
+            let path = inner.value_path(STORE_VERSION_KEY)?;
+            if path.exists() {
+                let bytes = fs::read(path)?;
+                // the version is the first value written, failing to decrypt it means a different key
+                Some(decrypt(&bytes, &inner.cipher).map_err(|_| Error::CannotDecrypt)?)
+            } else {
+                None
+            }
+        };
+        let version = match version_bytes {
+            Some(bytes) => match bytes[..] {
+                [version] => version,
+                _ => return Err(Error::Generic("Invalid store version".to_string())),
+            },
+            None => 0,
+        };
+        if version > STORE_VERSION {
+            return Err(Error::UnsupportedStoreVersion(version));
+        }
+        if version == STORE_VERSION {
+            return Ok(());
+        }
+
+        {
+            let mut inner = self.inner.lock().map_err(to_other)?;
+            for index in 0..inner.next.0 {
+                let upgraded = inner.get(index).map_err(Error::from).and_then(|update| {
+                    let mut update = update.expect("index < next");
+                    update.upgrade(desc)?;
+                    Ok(update)
+                });
+                match upgraded {
+                    Ok(update) => {
+                        let ciphertext = encrypt(update.serialize()?, &inner.cipher)?;
+                        fs::write(inner.path(&Counter::from(index)), ciphertext)?;
+                    }
+                    Err(e) => {
+                        log::warn!("removing updates from {index}, cannot read them: {e}");
+                        for i in index..inner.next.0 {
+                            fs::remove_file(inner.path(&Counter::from(i)))?;
+                        }
+                        inner.next = Counter::from(index);
+                        break;
+                    }
+                }
+            }
+        }
+        self.put_value(STORE_VERSION_KEY, &[STORE_VERSION])?;
+        Ok(())
     }
 }
 
@@ -133,6 +331,24 @@ impl FsPersisterInner {
         path
     }
 
+    /// Values are stored in a subdirectory so that they are not confused with updates
+    ///
+    /// The key is used as file name, so it must not contain path separators or dots to avoid
+    /// writing outside of the directory.
+    fn value_path(&self, key: &str) -> Result<PathBuf, PersistError> {
+        let valid = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(PersistError::Other(format!("Invalid key: {key:?}")));
+        }
+        let mut path = self.path.clone();
+        path.push(VALUES_DIR);
+        path.push(key);
+        Ok(path)
+    }
+
     fn last(&self) -> Result<Option<Update>, PersistError> {
         if self.next.0 == 0 {
             return Ok(None);
@@ -147,8 +363,9 @@ impl FsPersisterInner {
             let bytes = fs::read(path)?;
 
             Ok(Some(
-                Update::deserialize_decrypted(&bytes, &self.desc)
-                    .map_err(|e| PersistError::Other(e.to_string()))?,
+                decrypt(&bytes, &self.cipher)
+                    .and_then(|plaintext| Ok(Update::deserialize(&plaintext)?))
+                    .map_err(|e| PersistError::Corrupted(e.to_string()))?,
             ))
         } else {
             Ok(None)
@@ -180,16 +397,59 @@ impl Persister for FsPersister {
             }
         }
         let path = inner.path(&inner.next);
-        let ciphertext = update
-            .serialize_encrypted(&inner.desc)
+        let ciphertext = encrypt(update.serialize()?, &inner.cipher)
             .map_err(|e| PersistError::Other(e.to_string()))?;
 
         fs::write(path, ciphertext)?;
         inner.next = inner.next.clone() + 1;
         Ok(())
     }
+
+    fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>, PersistError> {
+        let inner = self.inner.lock().map_err(to_other)?;
+        let path = inner.value_path(key)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(path)?;
+        let plaintext =
+            decrypt(&bytes, &inner.cipher).map_err(|e| PersistError::Other(e.to_string()))?;
+        Ok(Some(plaintext))
+    }
+
+    fn put_value(&self, key: &str, value: &[u8]) -> Result<(), PersistError> {
+        let inner = self.inner.lock().map_err(to_other)?;
+        let path = inner.value_path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let ciphertext = encrypt(value.to_vec(), &inner.cipher)
+            .map_err(|e| PersistError::Other(e.to_string()))?;
+        fs::write(path, ciphertext)?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), PersistError> {
+        let mut inner = self.inner.lock().map_err(to_other)?;
+        for index in 0..inner.next.0 {
+            fs::remove_file(inner.path(&Counter::from(index)))?;
+        }
+        inner.next = Counter::default();
+        Ok(())
+    }
 }
 
+const VALUES_DIR: &str = "values";
+
+/// The version of the layout of the [`FsPersister`] directory
+///
+/// Version 0 has no version value and updates of any version, in version 1 the updates have
+/// been upgraded to [`crate::UPDATE_VERSION`].
+pub const STORE_VERSION: u8 = 1;
+
+/// Key used to persist the [`STORE_VERSION`] with [`Persister::put_value()`]
+const STORE_VERSION_KEY: &str = "store_version";
+
 const PERSISTED_FILE_NAME_LENGTH: usize = 12;
 
 /// Encapsulate an usize so that its to/from string representation are coherent
@@ -235,34 +495,59 @@ impl Add<usize> for Counter {
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::{
-        str::FromStr,
-        sync::{Arc, Mutex},
-    };
+/// Implementation of a [`Persister`] keeping the data in memory.
+///
+/// The data is lost when the persister is dropped, but unlike [`NoPersist`] a wallet can be
+/// recreated from the same persister without syncing again, and the values written with
+/// [`Persister::put_value()`], such as the labels, are kept.
+#[derive(Default)]
+pub struct MemoryPersister {
+    updates: Mutex<Vec<Update>>,
+    values: Mutex<HashMap<String, Vec<u8>>>,
+}
 
-    use crate::{ElementsNetwork, FsPersister, PersistError, Update, WolletDescriptor};
+impl MemoryPersister {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+}
 
-    use super::{Counter, NoPersist, Persister};
+impl Persister for MemoryPersister {
+    fn get(&self, index: usize) -> Result<Option<Update>, PersistError> {
+        let updates = self.updates.lock().map_err(to_other)?;
+        Ok(updates.get(index).cloned())
+    }
 
-    struct MemoryPersister(Mutex<Vec<Update>>);
-    impl MemoryPersister {
-        pub fn new() -> Arc<Self> {
-            Arc::new(Self(Mutex::new(vec![])))
-        }
+    fn push(&self, update: Update) -> Result<(), PersistError> {
+        self.updates.lock().map_err(to_other)?.push(update);
+        Ok(())
     }
-    impl Persister for MemoryPersister {
-        fn get(&self, index: usize) -> Result<Option<Update>, PersistError> {
-            Ok(self.0.lock().unwrap().get(index).cloned())
-        }
 
-        fn push(&self, update: crate::Update) -> Result<(), PersistError> {
-            self.0.lock().unwrap().push(update);
-            Ok(())
-        }
+    fn get_value(&self, key: &str) -> Result<Option<Vec<u8>>, PersistError> {
+        let values = self.values.lock().map_err(to_other)?;
+        Ok(values.get(key).cloned())
     }
 
+    fn put_value(&self, key: &str, value: &[u8]) -> Result<(), PersistError> {
+        let mut values = self.values.lock().map_err(to_other)?;
+        values.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), PersistError> {
+        self.updates.lock().map_err(to_other)?.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{str::FromStr, sync::Arc};
+
+    use crate::{ElementsNetwork, Error, FsPersister, Update, Wollet, WolletDescriptor};
+
+    use super::{Counter, MemoryPersister, NoPersist, Persister, STORE_VERSION, STORE_VERSION_KEY};
+
     fn inner_test_persister(persister: Arc<dyn Persister>, first_time: bool) {
         if first_time {
             assert_eq!(persister.get(0).unwrap(), None);
@@ -297,6 +582,152 @@ mod test {
     fn test_memory_persister() {
         let persister = MemoryPersister::new();
         inner_test_persister(persister, true);
+
+        let persister = MemoryPersister::new();
+        assert_eq!(persister.get_value("key").unwrap(), None);
+        persister.put_value("key", b"value").unwrap();
+        assert_eq!(persister.get_value("key").unwrap().unwrap(), b"value");
+
+        // a wallet recreated from the same persister has the same state
+        let network = ElementsNetwork::LiquidTestnet;
+        let desc = wollet_descriptor_test_vector();
+        let mut wollet = Wollet::new(network, persister.clone(), desc.clone()).unwrap();
+        let mut update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        update.wollet_status = wollet.status();
+        wollet.apply_update(update).unwrap();
+        let recreated = Wollet::new(network, persister, desc.clone()).unwrap();
+        assert_eq!(recreated.status(), wollet.status());
+        assert_ne!(
+            Wollet::in_memory(network, desc).unwrap().status(),
+            wollet.status()
+        );
+    }
+
+    #[test]
+    fn test_fs_persister_migration() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        assert_eq!(update.version, 0);
+
+        // a directory written before versioning the store
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        persister.push(update.clone()).unwrap();
+        persister.push(update.clone()).unwrap();
+        let (version_path, update_path) = {
+            let inner = persister.inner.lock().unwrap();
+            (
+                inner.value_path(STORE_VERSION_KEY).unwrap(),
+                inner.path(&Counter::from(1)),
+            )
+        };
+        std::fs::remove_file(&version_path).unwrap();
+        let garbage = crate::update::encrypt(b"garbage".to_vec(), &desc.cipher()).unwrap();
+        std::fs::write(update_path, garbage).unwrap();
+
+        // the first update is upgraded, the unreadable one is removed
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let upgraded = persister.get(0).unwrap().unwrap();
+        assert_eq!(upgraded.version, crate::UPDATE_VERSION);
+        assert_eq!(upgraded.txid_height_new, update.txid_height_new);
+        assert!(persister.get(1).unwrap().is_none());
+        assert_eq!(
+            persister.get_value(STORE_VERSION_KEY).unwrap(),
+            Some(vec![STORE_VERSION])
+        );
+
+        // a directory written by a future version is not touched
+        persister
+            .put_value(STORE_VERSION_KEY, &[STORE_VERSION + 1])
+            .unwrap();
+        assert!(matches!(
+            FsPersister::new(&tempdir, n, &desc),
+            Err(Error::UnsupportedStoreVersion(v)) if v == STORE_VERSION + 1
+        ));
+
+        // failing to read the version is not a decryption error
+        std::fs::remove_file(&version_path).unwrap();
+        std::fs::create_dir(&version_path).unwrap();
+        assert!(matches!(
+            FsPersister::new(&tempdir, n, &desc),
+            Err(Error::StdIOError(_))
+        ));
+    }
+
+    #[test]
+    fn test_fs_persister_rebuild() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let mut update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        let txid = update.new_txs.txs[0].0;
+        let update_path = |persister: &FsPersister| persister.inner.lock().unwrap().path(&0.into());
+
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let mut wollet = Wollet::new(n, persister.clone(), desc.clone()).unwrap();
+        update.wollet_status = wollet.status();
+        wollet.apply_update(update.clone()).unwrap();
+        wollet.set_label(crate::Label::tx(&txid, "rent")).unwrap();
+        let status = wollet.status();
+
+        // a missing update is detected by the checksum, the cache is rebuilt keeping the labels
+        let corrupted = std::fs::read(update_path(&persister)).unwrap();
+        std::fs::remove_file(update_path(&persister)).unwrap();
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let mut wollet = Wollet::new(n, persister.clone(), desc.clone()).unwrap();
+        assert!(wollet.never_scanned());
+        assert_eq!(wollet.labels().len(), 1);
+
+        update.wollet_status = wollet.status();
+        wollet.apply_update(update.clone()).unwrap();
+        assert_eq!(wollet.status(), status);
+        let mut wollet = Wollet::new(n, persister.clone(), desc.clone()).unwrap();
+        assert_eq!(wollet.status(), status);
+
+        // the checksum covers the tip updates written over the previous one
+        let mut tip_update = Update {
+            new_txs: Default::default(),
+            txid_height_new: vec![],
+            txid_height_delete: vec![],
+            timestamps: vec![],
+            scripts_with_blinding_pubkey: vec![],
+            ..update
+        };
+        assert!(tip_update.only_tip());
+        for _ in 0..2 {
+            tip_update.tip.height += 1;
+            tip_update.wollet_status = wollet.status();
+            wollet.apply_update(tip_update.clone()).unwrap();
+        }
+        assert_eq!(persister.get(2).unwrap(), None);
+        let tip = wollet.tip().height();
+        let wollet = Wollet::new(n, persister.clone(), desc.clone()).unwrap();
+        assert_eq!(wollet.tip().height(), tip);
+
+        // the checksum doesn't depend on the wallet configuration
+        let config = crate::Config::new(n).unwrap().with_gap_limit(30);
+        let wollet = Wollet::with_config(config, persister.clone(), desc.clone()).unwrap();
+        assert_eq!(wollet.tip().height(), tip);
+
+        // io errors are returned, the cache is not removed
+        let bytes = std::fs::read(update_path(&persister)).unwrap();
+        std::fs::remove_file(update_path(&persister)).unwrap();
+        std::fs::create_dir(update_path(&persister)).unwrap();
+        assert!(Wollet::new(n, persister.clone(), desc.clone()).is_err());
+        std::fs::remove_dir(update_path(&persister)).unwrap();
+        std::fs::write(update_path(&persister), bytes).unwrap();
+        let wollet = Wollet::new(n, persister.clone(), desc.clone()).unwrap();
+        assert_eq!(wollet.tip().height(), tip);
+
+        // an update that can't be decrypted
+        let mut corrupted = corrupted;
+        corrupted[20] ^= 1;
+        std::fs::write(update_path(&persister), corrupted).unwrap();
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let wollet = Wollet::new(n, persister.clone(), desc.clone()).unwrap();
+        assert!(wollet.never_scanned());
+        assert!(persister.get(0).unwrap().is_none());
     }
 
     #[test]
@@ -319,6 +750,93 @@ mod test {
         inner_test_persister(persister, false);
     }
 
+    #[test]
+    fn test_fs_persister_with_key() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let persister = FsPersister::with_password(&tempdir, n, &desc, "password").unwrap();
+        inner_test_persister(persister, true);
+        let persister = FsPersister::with_password(&tempdir, n, &desc, "password").unwrap();
+        inner_test_persister(persister, false);
+
+        // The descriptor is not enough to read the data
+        assert!(matches!(
+            FsPersister::new(&tempdir, n, &desc),
+            Err(Error::CannotDecrypt)
+        ));
+        assert!(matches!(
+            FsPersister::with_key(&tempdir, n, &desc, [1; 32]),
+            Err(Error::CannotDecrypt)
+        ));
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let persister = FsPersister::with_key(&tempdir, n, &desc, [1; 32]).unwrap();
+        persister.put_value("key", b"value").unwrap();
+        inner_test_persister(persister, true);
+        let persister = FsPersister::with_key(&tempdir, n, &desc, [1; 32]).unwrap();
+        assert_eq!(persister.get_value("key").unwrap().unwrap(), b"value");
+    }
+
+    #[test]
+    fn test_fs_persister_values() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        assert_eq!(persister.get_value("key").unwrap(), None);
+        persister.put_value("key", b"value").unwrap();
+        persister.put_value("key", b"value2").unwrap();
+        assert_eq!(persister.get_value("key").unwrap().unwrap(), b"value2");
+
+        // keys can't point outside the values directory
+        for key in ["", "../key", "/tmp/key", "a/b", ".."] {
+            assert!(persister.put_value(key, b"value").is_err(), "{key}");
+            assert!(persister.get_value(key).is_err(), "{key}");
+        }
+
+        // values don't interfere with updates
+        inner_test_persister(persister, true);
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        assert_eq!(persister.get_value("key").unwrap().unwrap(), b"value2");
+        inner_test_persister(persister, false);
+
+        assert_eq!(NoPersist::new().get_value("key").unwrap(), None);
+    }
+
+    #[test]
+    fn test_fs_persister_network_namespace() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let policy_asset = ElementsNetwork::Liquid.policy_asset();
+        let networks = [
+            ElementsNetwork::Liquid,
+            ElementsNetwork::LiquidTestnet,
+            ElementsNetwork::default_regtest(),
+            ElementsNetwork::ElementsRegtest { policy_asset },
+        ];
+        let persisters: Vec<_> = networks
+            .iter()
+            .map(|n| FsPersister::new(&tempdir, *n, &desc).unwrap())
+            .collect();
+        let update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        persisters[0].push(update.clone()).unwrap();
+        persisters[0].put_value("key", b"value").unwrap();
+
+        // the same descriptor on other networks doesn't see the data
+        for persister in persisters.iter().skip(1) {
+            assert_eq!(persister.get(0).unwrap(), None);
+            assert_eq!(persister.get_value("key").unwrap(), None);
+        }
+        let persister = FsPersister::new(&tempdir, networks[0], &desc).unwrap();
+        assert_eq!(persister.get(0).unwrap(), Some(update));
+        assert!(tempdir.path().join("liquid-regtest").is_dir());
+        assert!(tempdir
+            .path()
+            .join("liquid-regtest-6f0279e9ed041c3d")
+            .is_dir());
+    }
+
     #[test]
     fn test_counter() {
         let c = Counter::default();
@@ -5,10 +5,11 @@ use crate::elements::pset::{Input, Output, PartiallySignedTransaction};
 use crate::elements::{Address, AssetId, OutPoint, Transaction, TxOut, TxOutSecrets, Txid};
 use crate::error::Error;
 use crate::hashes::Hash;
-use crate::model::{Recipient, WalletTxOut};
+use crate::model::{Recipient, UtxoFilter, WalletTxOut};
 use crate::registry::Contract;
 use crate::wollet::Wollet;
 use crate::ElementsNetwork;
+use crate::EC;
 use elements::pset::elip100::AssetMetadata;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -23,11 +24,7 @@ pub enum IssuanceRequest {
 
 impl Wollet {
     pub(crate) fn asset_utxos(&self, asset: &AssetId) -> Result<Vec<WalletTxOut>, Error> {
-        Ok(self
-            .utxos()?
-            .into_iter()
-            .filter(|utxo| &utxo.unblinded.asset == asset)
-            .collect())
+        self.utxos_with(&UtxoFilter::default().asset(*asset))
     }
 
     fn get_tx(&self, txid: &Txid) -> Result<Transaction, Error> {
@@ -88,7 +85,6 @@ impl Wollet {
         input.witness_utxo = Some(txout);
         // Needed by ledger
         let mut rng = rand::thread_rng();
-        let secp = elements::secp256k1_zkp::Secp256k1::new();
         use elements::secp256k1_zkp::{RangeProof, SurjectionProof};
         use elements::{BlindAssetProofs, BlindValueProofs};
 
@@ -96,7 +92,7 @@ impl Wollet {
         input.blind_asset_proof = Some(Box::new(
             SurjectionProof::blind_asset_proof(
                 &mut rng,
-                &secp,
+                &EC,
                 utxo.unblinded.asset,
                 utxo.unblinded.asset_bf,
             )
@@ -106,7 +102,7 @@ impl Wollet {
         input.blind_value_proof = Some(Box::new(
             RangeProof::blind_value_proof(
                 &mut rng,
-                &secp,
+                &EC,
                 utxo.unblinded.value,
                 value_comm,
                 asset_gen,
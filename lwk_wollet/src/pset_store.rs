@@ -0,0 +1,199 @@
+//! PSETs in flight, stored with the wallet so that signing sessions survive restarts
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::elements::pset::PartiallySignedTransaction;
+use crate::elements::Txid;
+use crate::{Error, Wollet};
+
+/// Key used to persist the PSETs with [`crate::Persister::put_value()`]
+const PSETS_KEY: &str = "psets";
+
+/// The progress of a stored PSET
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PsetStatus {
+    /// No input has signatures
+    Created,
+
+    /// Some inputs have signatures, but not all of them are finalized
+    PartiallySigned,
+
+    /// All the inputs are finalized
+    Finalized,
+
+    /// The transaction is known by the wallet, in the mempool or in the blockchain
+    Broadcast,
+}
+
+/// A PSET stored with [`Wollet::save_pset()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredPset {
+    /// The id of the unsigned transaction, which doesn't change while signing
+    pub txid: Txid,
+
+    /// The progress of the PSET
+    pub status: PsetStatus,
+
+    /// The PSET
+    pub pset: PartiallySignedTransaction,
+}
+
+impl Wollet {
+    /// Store `pset`, returning the id of its unsigned transaction
+    ///
+    /// If a PSET of the same transaction is already stored, the signatures of the two are
+    /// combined, so that PSETs signed on different devices can be saved as they come back.
+    /// PSETs are stored with [`crate::Persister::put_value()`], with [`crate::NoPersist`] they
+    /// are discarded.
+    pub fn save_pset(&self, pset: &PartiallySignedTransaction) -> Result<Txid, Error> {
+        let txid = pset.extract_tx()?.txid();
+        let mut psets = self.load_psets()?;
+        match psets.get_mut(&txid) {
+            Some(stored) => stored.merge(pset.clone())?,
+            None => {
+                psets.insert(txid, pset.clone());
+            }
+        }
+        self.persist_psets(&psets)?;
+        Ok(txid)
+    }
+
+    /// The stored PSETs, ordered by transaction id
+    pub fn psets(&self) -> Result<Vec<StoredPset>, Error> {
+        Ok(self
+            .load_psets()?
+            .into_iter()
+            .map(|(txid, pset)| self.stored_pset(txid, pset))
+            .collect())
+    }
+
+    /// The stored PSET of the transaction `txid`, to resume signing it
+    pub fn pset(&self, txid: &Txid) -> Result<Option<StoredPset>, Error> {
+        Ok(self
+            .load_psets()?
+            .remove(txid)
+            .map(|pset| self.stored_pset(*txid, pset)))
+    }
+
+    /// Remove the stored PSET of the transaction `txid`, returning it if it was stored
+    pub fn remove_pset(&self, txid: &Txid) -> Result<Option<PartiallySignedTransaction>, Error> {
+        let mut psets = self.load_psets()?;
+        let removed = psets.remove(txid);
+        if removed.is_some() {
+            self.persist_psets(&psets)?;
+        }
+        Ok(removed)
+    }
+
+    fn stored_pset(&self, txid: Txid, pset: PartiallySignedTransaction) -> StoredPset {
+        let status = if self.store.cache.all_txs.contains_key(&txid) {
+            PsetStatus::Broadcast
+        } else if pset
+            .inputs()
+            .iter()
+            .all(|i| i.final_script_witness.is_some() || i.final_script_sig.is_some())
+        {
+            PsetStatus::Finalized
+        } else if pset.inputs().iter().any(|i| {
+            !i.partial_sigs.is_empty() || i.tap_key_sig.is_some() || !i.tap_script_sigs.is_empty()
+        }) {
+            PsetStatus::PartiallySigned
+        } else {
+            PsetStatus::Created
+        };
+        StoredPset { txid, status, pset }
+    }
+
+    fn load_psets(&self) -> Result<BTreeMap<Txid, PartiallySignedTransaction>, Error> {
+        let bytes = match self.persister.get_value(PSETS_KEY)? {
+            Some(bytes) => bytes,
+            None => return Ok(BTreeMap::new()),
+        };
+        let encoded: BTreeMap<Txid, String> = serde_json::from_slice(&bytes)?;
+        encoded
+            .into_iter()
+            .map(|(txid, base64)| Ok((txid, PartiallySignedTransaction::from_str(&base64)?)))
+            .collect()
+    }
+
+    fn persist_psets(
+        &self,
+        psets: &BTreeMap<Txid, PartiallySignedTransaction>,
+    ) -> Result<(), Error> {
+        let encoded: BTreeMap<_, _> = psets
+            .iter()
+            .map(|(txid, pset)| (txid, pset.to_string()))
+            .collect();
+        let bytes = serde_json::to_vec(&encoded)?;
+        self.persister.put_value(PSETS_KEY, &bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::pset::PartiallySignedTransaction;
+
+    use super::PsetStatus;
+    use crate::{ElementsNetwork, MemoryPersister, Update, Wollet, WolletDescriptor};
+
+    #[test]
+    fn test_pset_store() {
+        let desc = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc = WolletDescriptor::from_str(desc).unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let persister = MemoryPersister::new();
+        let mut wollet = Wollet::new(network, persister.clone(), desc.clone()).unwrap();
+        let mut update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        let tx = update.new_txs.txs[0].1.clone();
+
+        // a finalized PSET of a transaction the wallet doesn't know yet
+        let finalized = PartiallySignedTransaction::from_tx(tx.clone());
+        assert!(wollet.psets().unwrap().is_empty());
+        let finalized_txid = wollet.save_pset(&finalized).unwrap();
+        assert_eq!(finalized_txid, tx.txid());
+        let stored = wollet.pset(&finalized_txid).unwrap().unwrap();
+        assert_eq!(stored.status, PsetStatus::Finalized);
+
+        let mut pset = finalized.clone();
+        for input in pset.inputs_mut() {
+            input.final_script_witness = None;
+            input.final_script_sig = None;
+        }
+        let txid = wollet.save_pset(&pset).unwrap();
+        assert_eq!(
+            wollet.pset(&txid).unwrap().unwrap().status,
+            PsetStatus::Created
+        );
+
+        // signatures from another device are combined
+        let mut signed = pset.clone();
+        let key = elements::bitcoin::PublicKey::from_str(
+            "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        )
+        .unwrap();
+        signed.inputs_mut()[0].partial_sigs.insert(key, vec![0x30]);
+        assert_eq!(wollet.save_pset(&signed).unwrap(), txid);
+        let stored = wollet.pset(&txid).unwrap().unwrap();
+        assert_eq!(stored.status, PsetStatus::PartiallySigned);
+        assert_eq!(stored.pset.inputs()[0].partial_sigs.len(), 1);
+
+        // the PSETs survive restarts, and are marked as broadcast once the wallet knows the tx
+        update.wollet_status = wollet.status();
+        wollet.apply_update(update).unwrap();
+        let wollet = Wollet::new(network, persister, desc).unwrap();
+        assert_eq!(wollet.psets().unwrap().len(), 2);
+        let stored = wollet.pset(&finalized_txid).unwrap().unwrap();
+        assert_eq!(stored.status, PsetStatus::Broadcast);
+
+        assert!(wollet.remove_pset(&txid).unwrap().is_some());
+        assert!(wollet.remove_pset(&txid).unwrap().is_none());
+        assert!(wollet.remove_pset(&finalized_txid).unwrap().is_some());
+        assert!(wollet.psets().unwrap().is_empty());
+    }
+}
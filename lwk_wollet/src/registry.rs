@@ -6,7 +6,7 @@ use crate::elements::hashes::{sha256, Hash};
 use crate::elements::{AssetId, ContractHash, OutPoint};
 use crate::error::Error;
 use crate::util::{serde_from_hex, serde_to_hex, verify_pubkey};
-use crate::ElementsNetwork;
+use crate::{ElementsNetwork, Persister};
 use elements::{Transaction, Txid};
 use once_cell::sync::Lazy;
 use regex_lite::Regex;
@@ -99,6 +99,42 @@ impl FromStr for Contract {
     }
 }
 
+/// The maximum size in bytes of an asset icon
+pub const MAX_ICON_SIZE: usize = 100_000;
+
+/// The icon of an asset, a PNG image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetIcon {
+    bytes: Vec<u8>,
+    hash: sha256::Hash,
+}
+
+impl AssetIcon {
+    /// Create an icon from its bytes, which must be non empty and at most [`MAX_ICON_SIZE`]
+    pub fn new(bytes: Vec<u8>) -> Result<Self, Error> {
+        if bytes.is_empty() || bytes.len() > MAX_ICON_SIZE {
+            return Err(Error::InvalidAssetIcon(bytes.len()));
+        }
+        let hash = sha256::Hash::hash(&bytes);
+        Ok(Self { bytes, hash })
+    }
+
+    /// The bytes of the image
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The SHA256 of the image, to detect icon changes without comparing the bytes
+    pub fn hash(&self) -> sha256::Hash {
+        self.hash
+    }
+}
+
+/// The key used to cache the icon of the asset in a [`Persister`]
+fn icon_key(asset_id: AssetId) -> String {
+    format!("registry_icon_{asset_id}")
+}
+
 pub struct Registry {
     client: reqwest::Client,
     base_url: String,
@@ -156,6 +192,46 @@ impl Registry {
         Ok((data.contract, tx))
     }
 
+    /// Fetch the icon of the asset, failing if it's bigger than [`MAX_ICON_SIZE`]
+    pub async fn fetch_icon(&self, asset_id: AssetId) -> Result<AssetIcon, Error> {
+        let url = format!("{}/icons/{}.png", self.base_url, asset_id);
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Generic(format!(
+                "Failed to fetch icon of {asset_id} from registry: {status}"
+            )));
+        }
+        if let Some(len) = response.content_length() {
+            if len as usize > MAX_ICON_SIZE {
+                return Err(Error::InvalidAssetIcon(len as usize));
+            }
+        }
+        AssetIcon::new(response.bytes().await?.to_vec())
+    }
+
+    /// Like [`Registry::fetch_icon()`], but the icon is taken from the `persister` if previously
+    /// cached there, otherwise it's fetched and cached
+    ///
+    /// Icons rarely change, to refresh a cached icon call [`Registry::fetch_icon()`] and compare
+    /// the [`AssetIcon::hash()`].
+    pub async fn fetch_icon_cached(
+        &self,
+        asset_id: AssetId,
+        persister: &dyn Persister,
+    ) -> Result<AssetIcon, Error> {
+        let key = icon_key(asset_id);
+        if let Some(bytes) = persister.get_value(&key)? {
+            match AssetIcon::new(bytes) {
+                Ok(icon) => return Ok(icon),
+                Err(e) => log::warn!("ignoring cached icon of {asset_id}: {e}"),
+            }
+        }
+        let icon = self.fetch_icon(asset_id).await?;
+        persister.put_value(&key, icon.bytes())?;
+        Ok(icon)
+    }
+
     pub async fn post(&self, data: &RegistryPost) -> Result<(), Error> {
         let response = self.client.post(&self.base_url).json(&data).send().await?;
         let status = response.status();
@@ -184,7 +260,7 @@ pub mod blocking {
     use elements::{AssetId, Transaction};
     use tokio::runtime::Runtime;
 
-    use crate::{ElementsNetwork, Error};
+    use crate::{ElementsNetwork, Error, Persister};
 
     use super::RegistryPost;
 
@@ -220,6 +296,19 @@ pub mod blocking {
             self.rt.block_on(self.inner.fetch_with_tx(asset_id, client))
         }
 
+        pub fn fetch_icon(&self, asset_id: AssetId) -> Result<super::AssetIcon, Error> {
+            self.rt.block_on(self.inner.fetch_icon(asset_id))
+        }
+
+        pub fn fetch_icon_cached(
+            &self,
+            asset_id: AssetId,
+            persister: &dyn Persister,
+        ) -> Result<super::AssetIcon, Error> {
+            self.rt
+                .block_on(self.inner.fetch_icon_cached(asset_id, persister))
+        }
+
         pub fn post(&self, data: &RegistryPost) -> Result<(), Error> {
             self.rt.block_on(self.inner.post(data))
         }
@@ -297,6 +386,52 @@ mod tests {
         assert_eq!(registry_data.contract.ticker, "USDt");
     }
 
+    #[test]
+    fn test_asset_icon() {
+        let bytes = vec![1u8; MAX_ICON_SIZE];
+        let icon = AssetIcon::new(bytes.clone()).unwrap();
+        assert_eq!(icon.bytes(), &bytes[..]);
+        assert_eq!(icon.hash(), sha256::Hash::hash(&bytes));
+        assert!(matches!(
+            AssetIcon::new(vec![1u8; MAX_ICON_SIZE + 1]),
+            Err(Error::InvalidAssetIcon(n)) if n == MAX_ICON_SIZE + 1
+        ));
+        assert!(matches!(
+            AssetIcon::new(vec![]),
+            Err(Error::InvalidAssetIcon(0))
+        ));
+    }
+
+    #[test]
+    fn test_asset_icon_cached() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc: crate::WolletDescriptor = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let persister =
+            crate::FsPersister::new(&tempdir, ElementsNetwork::LiquidTestnet, &desc).unwrap();
+        let asset_id = ElementsNetwork::LiquidTestnet.policy_asset();
+
+        // Nothing is listening here, the icon must come from the cache
+        let registry = blocking::Registry::new("http://127.0.0.1:1").unwrap();
+        assert!(registry.fetch_icon_cached(asset_id, &*persister).is_err());
+
+        persister
+            .put_value(&icon_key(asset_id), b"\x89PNG")
+            .unwrap();
+        let icon = registry.fetch_icon_cached(asset_id, &*persister).unwrap();
+        assert_eq!(icon.bytes(), b"\x89PNG");
+    }
+
+    #[ignore = "require internet connection"]
+    #[test]
+    fn test_registry_fetch_icon() {
+        let tether_asset_id =
+            AssetId::from_str("ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2")
+                .unwrap();
+        let registry = blocking::Registry::default_for_network(ElementsNetwork::Liquid).unwrap();
+        let icon = registry.fetch_icon(tether_asset_id).unwrap();
+        assert!(icon.bytes().starts_with(b"\x89PNG"));
+    }
+
     #[test]
     fn test_registry() {
         let contract_string = "{\"entity\":{\"domain\":\"tether.to\"},\"issuer_pubkey\":\"0337cceec0beea0232ebe14cba0197a9fbd45fcf2ec946749de920e71434c2b904\",\"name\":\"Tether USD\",\"precision\":8,\"ticker\":\"USDt\",\"version\":0}";
@@ -0,0 +1,228 @@
+//! Move the funds of a multisig wallet to a descriptor with a different set of signers
+//!
+//! Adding or removing a cosigner, or changing the threshold, changes all the scripts of the
+//! wallet, thus all the funds must be sent to the new descriptor. The migration is:
+//! 1. [`analyze()`] the wallet and the proposed descriptor, checking the signers changes;
+//! 2. sign with the old signers and broadcast the [`Rotation::migration_pset()`];
+//! 3. once synced, check with [`verify_migrated()`] that no funds are left on the old descriptor.
+
+use std::collections::BTreeMap;
+
+use elements::pset::PartiallySignedTransaction;
+use elements::AssetId;
+use elements_miniscript::descriptor::{DescriptorPublicKey, WshInner};
+use elements_miniscript::miniscript::decode::Terminal;
+use elements_miniscript::Descriptor;
+
+use crate::{Error, WalletTxOut, Wollet, WolletDescriptor};
+
+/// The changes of the signers between two multisig descriptors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignersDiff {
+    /// The signers only in the new descriptor
+    pub added: Vec<DescriptorPublicKey>,
+
+    /// The signers only in the old descriptor
+    pub removed: Vec<DescriptorPublicKey>,
+
+    /// The signers in both descriptors
+    pub kept: Vec<DescriptorPublicKey>,
+
+    /// The number of signatures required by the old descriptor
+    pub old_threshold: usize,
+
+    /// The number of signatures required by the new descriptor
+    pub new_threshold: usize,
+}
+
+/// The analysis of the migration of a wallet to a new descriptor, see [`analyze()`]
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    new_descriptor: WolletDescriptor,
+    signers: SignersDiff,
+    utxos: Vec<WalletTxOut>,
+}
+
+impl Rotation {
+    /// The changes of the signers
+    pub fn signers(&self) -> &SignersDiff {
+        &self.signers
+    }
+
+    /// The unspent outputs that must be moved to the new descriptor
+    pub fn utxos(&self) -> &[WalletTxOut] {
+        &self.utxos
+    }
+
+    /// The amounts to be moved for each asset, fees excluded
+    pub fn balance(&self) -> BTreeMap<AssetId, u64> {
+        let mut balance = BTreeMap::new();
+        for utxo in self.utxos.iter() {
+            *balance.entry(utxo.unblinded.asset).or_default() += utxo.unblinded.value;
+        }
+        balance
+    }
+
+    /// Whether there are funds to move
+    pub fn needs_migration(&self) -> bool {
+        !self.utxos.is_empty()
+    }
+
+    /// Create the transaction sending all the funds of `old` to the new descriptor
+    ///
+    /// Every asset is sent to a different address of the new descriptor, the policy asset pays
+    /// the fee. The transaction must be signed by the signers of the old descriptor.
+    pub fn migration_pset(
+        &self,
+        old: &Wollet,
+        fee_rate: Option<f32>,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        if !self.needs_migration() {
+            return Err(Error::Generic("No funds to migrate".into()));
+        }
+        let params = old.config().address_params();
+        let policy_asset = old.policy_asset();
+        let mut builder = old.tx_builder().fee_rate(fee_rate);
+        let mut index = 0;
+        for (asset, value) in self.balance() {
+            if asset == policy_asset {
+                continue;
+            }
+            index += 1;
+            let address = self.new_descriptor.address(index, params)?;
+            builder = builder.add_recipient(&address, value, asset)?;
+        }
+        let address = self.new_descriptor.address(0, params)?;
+        builder.drain_lbtc_wallet().drain_lbtc_to(address).finish()
+    }
+}
+
+/// Analyze the migration of the funds of the multisig wallet `old` to the `new` descriptor
+///
+/// Both descriptors must be `wsh(multi(...))` or `wsh(sortedmulti(...))`.
+pub fn analyze(old: &Wollet, new: &WolletDescriptor) -> Result<Rotation, Error> {
+    let old_descriptor = old.wollet_descriptor();
+    if old_descriptor.is_mainnet() != new.is_mainnet() {
+        return Err(Error::Generic(
+            "Descriptors are for different networks".into(),
+        ));
+    }
+    let (old_threshold, old_keys) = multisig(old_descriptor.descriptor())?;
+    let (new_threshold, new_keys) = multisig(new.descriptor())?;
+    let signers = SignersDiff {
+        added: new_keys
+            .iter()
+            .filter(|k| !old_keys.contains(k))
+            .cloned()
+            .collect(),
+        removed: old_keys
+            .iter()
+            .filter(|k| !new_keys.contains(k))
+            .cloned()
+            .collect(),
+        kept: old_keys
+            .iter()
+            .filter(|k| new_keys.contains(k))
+            .cloned()
+            .collect(),
+        old_threshold,
+        new_threshold,
+    };
+    // With the same descriptor the scripts and the blinding keys don't change
+    let utxos = if old_descriptor.to_string() == new.to_string() {
+        vec![]
+    } else {
+        old.utxos()?
+    };
+    Ok(Rotation {
+        new_descriptor: new.clone(),
+        signers,
+        utxos,
+    })
+}
+
+/// Check that no funds are left on the old descriptor once the migration is completed
+///
+/// The wallet must be synced after the migration transaction has been broadcast.
+pub fn verify_migrated(old: &Wollet) -> Result<(), Error> {
+    let left = old.utxos()?.len();
+    if left > 0 {
+        return Err(Error::MigrationIncomplete(left));
+    }
+    Ok(())
+}
+
+fn multisig(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+) -> Result<(usize, Vec<DescriptorPublicKey>), Error> {
+    if let Descriptor::Wsh(wsh) = descriptor {
+        match wsh.as_inner() {
+            WshInner::SortedMulti(multi) => return Ok((multi.k, multi.pks.clone())),
+            WshInner::Ms(ms) => {
+                if let Terminal::Multi(k, pks) = &ms.node {
+                    return Ok((*k, pks.clone()));
+                }
+            }
+        }
+    }
+    Err(Error::UnsupportedNonMultisigDescriptor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{analyze, verify_migrated};
+    use crate::wollet::tests::test_wollet_with_many_transactions;
+    use crate::{ElementsNetwork, Error, NoPersist, Wollet, WolletDescriptor};
+
+    const VIEW_KEY: &str = "a45210d9afc904e522bd17a433518d75c6a00cc09ced714b7ec211abdebcb783";
+    const A: &str = "[e6b7814d/87'/1'/0']tpubDDmvBugC5YMK3UDKjcym7ED8Vfv8aLiX83Tcbecc783VFPEDqBigmzF52uFMyh89bXaf7jAporM1LcoaMcLdKeV4m7ixNAchpMQCL569Ldv/<0;1>/*";
+    const B: &str = "[a5a0841e/87'/1'/0']tpubDDZCCwQJyHksYEfUHb59Mr4ZCo1ndMt4Ys8rXF7RLhmfttU9AYybscFyCmWRVQUxffjGYQe8dtmGchA91PhLUCkH3H7D7Nx1CJLrv5W9tTs/<0;1>/*";
+    const C: &str = "[996febb0/87'/1'/0']tpubDDR1DaDYEKDCkuZ6eU6orfUZshJDDZNjauQsKeK6SkwqPSnRYRPGuYM5MnCqJo88Az8YX6a9oP45W4fenTyS9kLg1TG3LJBbY1jS36r893V/<0;1>/*";
+
+    fn desc(s: &str) -> WolletDescriptor {
+        WolletDescriptor::from_str(&format!("ct({VIEW_KEY},{s})")).unwrap()
+    }
+
+    #[test]
+    fn test_rotation_analysis() {
+        let old = desc(&format!("elwsh(multi(2,{A},{B}))"));
+        let wollet = Wollet::new(ElementsNetwork::LiquidTestnet, NoPersist::new(), old).unwrap();
+
+        let new = desc(&format!("elwsh(sortedmulti(2,{A},{C}))"));
+        let rotation = analyze(&wollet, &new).unwrap();
+        let signers = rotation.signers();
+        assert_eq!(signers.added.len(), 1);
+        assert_eq!(signers.added[0].to_string(), C);
+        assert_eq!(signers.removed[0].to_string(), B);
+        assert_eq!(signers.kept[0].to_string(), A);
+        assert_eq!((signers.old_threshold, signers.new_threshold), (2, 2));
+
+        // No funds to move
+        assert!(!rotation.needs_migration());
+        assert!(rotation.migration_pset(&wollet, None).is_err());
+        verify_migrated(&wollet).unwrap();
+
+        let singlesig = desc(&format!("elwpkh({A})"));
+        assert!(matches!(
+            analyze(&wollet, &singlesig),
+            Err(Error::UnsupportedNonMultisigDescriptor)
+        ));
+    }
+
+    #[test]
+    fn test_rotation_utxos() {
+        let wollet = test_wollet_with_many_transactions();
+        assert!(matches!(
+            analyze(&wollet, &wollet.wollet_descriptor()),
+            Err(Error::UnsupportedNonMultisigDescriptor)
+        ));
+        let utxos = wollet.utxos().unwrap().len();
+        assert!(utxos > 0);
+        assert!(matches!(
+            verify_migrated(&wollet),
+            Err(Error::MigrationIncomplete(n)) if n == utxos
+        ));
+    }
+}
@@ -6,6 +6,7 @@ use elements::bitcoin::bip32::ChildNumber;
 use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
 use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 
 pub const BATCH_SIZE: u32 = 20;
 pub type Height = u32;
@@ -40,6 +41,9 @@ pub struct RawCache {
 
     /// last unused index for internal addresses (changes) for current descriptor
     pub last_unused_internal: AtomicU32,
+
+    /// outcome of the proofs verification of wallet txs, only if enabled in the config
+    pub verified: HashMap<Txid, bool>,
 }
 
 impl Default for RawCache {
@@ -54,6 +58,24 @@ impl Default for RawCache {
             last_unused_internal: 0.into(),
             last_unused_external: 0.into(),
             timestamps: HashMap::default(),
+            verified: HashMap::default(),
+        }
+    }
+}
+
+impl Clone for RawCache {
+    fn clone(&self) -> Self {
+        Self {
+            all_txs: self.all_txs.clone(),
+            paths: self.paths.clone(),
+            scripts: self.scripts.clone(),
+            heights: self.heights.clone(),
+            unblinded: self.unblinded.clone(),
+            tip: self.tip,
+            timestamps: self.timestamps.clone(),
+            last_unused_external: self.last_unused_external.load(Ordering::Relaxed).into(),
+            last_unused_internal: self.last_unused_internal.load(Ordering::Relaxed).into(),
+            verified: self.verified.clone(),
         }
     }
 }
@@ -97,9 +119,10 @@ impl std::hash::Hash for RawCache {
     }
 }
 
-#[derive(Default, Hash)]
+#[derive(Default, Hash, Clone)]
 pub struct Store {
-    pub cache: RawCache,
+    /// Shared with the snapshots of the wallet, mutations clone it if a snapshot is alive
+    pub cache: Arc<RawCache>,
 }
 
 #[derive(Default, Debug)]
@@ -174,6 +197,7 @@ mod tests {
         convert::TryInto,
         hash::{Hash, Hasher},
         str::FromStr,
+        sync::Arc,
     };
     use tempfile::TempDir;
 
@@ -217,8 +241,7 @@ mod tests {
         store.hash(&mut hasher);
         assert_eq!(11565483422739161174, hasher.finish());
 
-        store
-            .cache
+        Arc::make_mut(&mut store.cache)
             .heights
             .insert(<Txid as elements::hashes::Hash>::all_zeros(), None);
         let mut hasher = DefaultHasher::new();
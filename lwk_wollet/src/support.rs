@@ -0,0 +1,238 @@
+//! Diagnostics to attach to bug reports
+//!
+//! A [`SupportBundle`] gathers the information useful to triage an issue: versions, network,
+//! backend capabilities, the consistency of the wallet cache, the wallet statistics and the recent
+//! logs of the application. It doesn't contain the descriptor, key fingerprints, addresses,
+//! transaction ids and heights or amounts, the wallet is identified by its
+//! [`crate::WolletDescriptor::dwid()`] and identifiers in the logs are redacted, see [`redact()`].
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use regex_lite::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::store::Height;
+use crate::{Capability, ElementsNetwork, Error, Wollet, WolletStats};
+
+/// The maximum number of log lines kept in a bundle
+pub const MAX_LOG_LINES: usize = 200;
+
+/// The consistency of the wallet cache
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheReport {
+    /// The status of the wallet, see [`Wollet::status()`]
+    pub status: u64,
+
+    /// Wallet transactions whose content is not cached
+    pub missing_txs: usize,
+
+    /// Heights of the wallet transactions without the block timestamp
+    pub missing_timestamps: usize,
+
+    /// Transactions whose proofs failed the verification
+    pub unverified_txs: usize,
+
+    /// Unblinded outputs of transactions not cached
+    pub orphan_unblinded: usize,
+}
+
+impl CacheReport {
+    /// Whether no inconsistency was found
+    pub fn is_consistent(&self) -> bool {
+        self.missing_txs == 0
+            && self.missing_timestamps == 0
+            && self.unverified_txs == 0
+            && self.orphan_unblinded == 0
+    }
+}
+
+/// Redacted diagnostics of a wallet, see the [module documentation](self)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SupportBundle {
+    /// The version of this library
+    pub lwk_version: String,
+
+    /// The network of the wallet
+    pub network: String,
+
+    /// The wallet identifier, see [`crate::WolletDescriptor::dwid()`]
+    pub wallet_id: String,
+
+    /// The type of the descriptor, such as `Wpkh` or `WshSortedMulti`
+    pub descriptor_type: String,
+
+    /// Whether the descriptor has separate chains for external and change addresses
+    pub multipath: bool,
+
+    /// The capabilities of the backend used to scan the wallet
+    pub backend_capabilities: Vec<String>,
+
+    /// The height of the wallet tip
+    pub tip_height: Height,
+
+    /// The number of updates applied to the wallet and persisted
+    pub updates: usize,
+
+    /// The consistency of the wallet cache
+    pub cache: CacheReport,
+
+    /// The wallet statistics as of the last sync, without the fees paid and the heights of the
+    /// first and last transactions
+    pub stats: WolletStats,
+
+    /// The last [`MAX_LOG_LINES`] lines of the application logs, redacted
+    pub logs: Vec<String>,
+}
+
+impl SupportBundle {
+    /// Add the capabilities of the backend used to scan the wallet
+    pub fn with_backend_capabilities(mut self, capabilities: &HashSet<Capability>) -> Self {
+        let mut capabilities: Vec<String> = capabilities.iter().map(|c| format!("{c:?}")).collect();
+        capabilities.sort();
+        self.backend_capabilities = capabilities;
+        self
+    }
+
+    /// Add the last [`MAX_LOG_LINES`] of `logs`, redacting them with [`redact()`]
+    pub fn with_logs<S: AsRef<str>>(mut self, logs: &[S]) -> Self {
+        let skip = logs.len().saturating_sub(MAX_LOG_LINES);
+        self.logs = logs[skip..].iter().map(|l| redact(l.as_ref())).collect();
+        self
+    }
+}
+
+impl std::fmt::Display for SupportBundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let json = serde_json::to_string_pretty(self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{json}")
+    }
+}
+
+/// Replace the long alphanumeric words of `line`, such as addresses, transaction ids, keys and
+/// descriptors, and the words of 8 hex characters, such as key fingerprints, with `<redacted>`
+pub fn redact(line: &str) -> String {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"[A-Za-z0-9]{26,}|\b[0-9A-Fa-f]{8}\b").expect("static"));
+    re.replace_all(line, "<redacted>").into_owned()
+}
+
+impl Wollet {
+    /// Gather redacted diagnostics of the wallet to attach to bug reports
+    ///
+    /// Applications can add the capabilities of their backend and their logs with
+    /// [`SupportBundle::with_backend_capabilities()`] and [`SupportBundle::with_logs()`].
+    pub fn support_bundle(&self) -> Result<SupportBundle, Error> {
+        let network = match self.network() {
+            ElementsNetwork::Liquid => lwk_common::Network::Liquid,
+            ElementsNetwork::LiquidTestnet => lwk_common::Network::TestnetLiquid,
+            ElementsNetwork::ElementsRegtest { .. } => lwk_common::Network::LocaltestLiquid,
+        };
+        let descriptor = self.wollet_descriptor();
+        let updates = self.updates().map_err(|e| Error::Generic(e.to_string()))?;
+        Ok(SupportBundle {
+            lwk_version: env!("CARGO_PKG_VERSION").to_string(),
+            network: self.network().as_str().to_string(),
+            wallet_id: descriptor.dwid(network)?,
+            descriptor_type: format!("{:?}", self.descriptor().descriptor.desc_type()),
+            multipath: descriptor.is_multipath(),
+            backend_capabilities: vec![],
+            tip_height: self.tip().height(),
+            updates: updates.len(),
+            cache: self.cache_report(),
+            stats: WolletStats {
+                fees_paid: 0,
+                first_height: None,
+                last_height: None,
+                ..self.stats()?
+            },
+            logs: vec![],
+        })
+    }
+
+    fn cache_report(&self) -> CacheReport {
+        let cache = &self.store.cache;
+        CacheReport {
+            status: self.status(),
+            missing_txs: cache
+                .heights
+                .keys()
+                .filter(|txid| !cache.all_txs.contains_key(*txid))
+                .count(),
+            missing_timestamps: cache
+                .heights
+                .values()
+                .flatten()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .filter(|h| !cache.timestamps.contains_key(*h))
+                .count(),
+            unverified_txs: cache.verified.values().filter(|v| !**v).count(),
+            orphan_unblinded: cache
+                .unblinded
+                .keys()
+                .filter(|o| !cache.all_txs.contains_key(&o.txid))
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, MAX_LOG_LINES};
+    use crate::wollet::tests::test_wollet_with_many_transactions;
+    use crate::Capability;
+
+    #[test]
+    fn test_redact() {
+        let txid = "0471d2f856b3fdbc4397af272bee1660b77aaf9a4aeb86fdd96110ce00f2b158";
+        assert_eq!(
+            redact(&format!("downloaded tx {txid} at height 10")),
+            "downloaded tx <redacted> at height 10"
+        );
+        let address = "tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn";
+        assert_eq!(redact(address), "<redacted>");
+        assert_eq!(redact("scan completed in 2s"), "scan completed in 2s");
+        assert_eq!(
+            redact("signing with [73c5da0a/84h/1h/0h]"),
+            "signing with [<redacted>/84h/1h/0h]"
+        );
+    }
+
+    #[test]
+    fn test_support_bundle() {
+        let wollet = test_wollet_with_many_transactions();
+        let logs: Vec<String> = (0..MAX_LOG_LINES + 10)
+            .map(|i| format!("line {i}"))
+            .collect();
+        let bundle = wollet
+            .support_bundle()
+            .unwrap()
+            .with_backend_capabilities(&[Capability::Waterfalls].into_iter().collect())
+            .with_logs(&logs);
+        assert_eq!(bundle.network, "liquid-testnet");
+        assert_eq!(bundle.descriptor_type, "Wpkh");
+        assert_eq!(bundle.backend_capabilities, vec!["Waterfalls"]);
+        assert_eq!(bundle.tip_height, wollet.tip().height());
+        let stats = wollet.stats().unwrap();
+        assert!(stats.fees_paid > 0 && stats.first_height.is_some());
+        assert_eq!(bundle.stats.txs, stats.txs);
+        assert_eq!(bundle.stats.fees_paid, 0);
+        assert_eq!(bundle.stats.first_height, None);
+        assert_eq!(bundle.stats.last_height, None);
+        assert!(bundle.cache.is_consistent());
+        assert_eq!(bundle.logs.len(), MAX_LOG_LINES);
+        assert_eq!(bundle.logs[0], "line 10");
+
+        let s = bundle.to_string();
+        let descriptor = wollet.wollet_descriptor().to_string();
+        let xpub = descriptor
+            .split(['(', ')', ']', '/'])
+            .find(|p| p.len() > 100);
+        assert!(!s.contains(xpub.unwrap()));
+        let fingerprint = &descriptor[descriptor.find('[').unwrap() + 1..][..8];
+        assert!(!s.contains(fingerprint));
+        let txid = wollet.transactions().unwrap()[0].txid.to_string();
+        assert!(!s.contains(&txid));
+    }
+}
@@ -0,0 +1,466 @@
+//! Helpers for Boltz-style submarine swaps (Liquid→Lightning) and reverse submarine swaps
+//! (Lightning→Liquid)
+//!
+//! The swap funds are locked in a P2WSH output whose script can be spent either by the claimer
+//! revealing the preimage of the Lightning payment hash or by the refunder after a timeout.
+//!
+//! In a submarine swap the user locks funds and the swap provider claims them, while the user can
+//! refund after the timeout. In a reverse submarine swap the swap provider locks the funds and the
+//! user claims them revealing the preimage.
+//!
+//! This module builds the lockup script and address, and creates the claim and refund
+//! transactions as blinded PSETs which can be finalized with [`SwapScript::finalize_claim`] or
+//! [`SwapScript::finalize_refund`].
+
+use std::collections::HashMap;
+
+use elements::{
+    confidential::{Asset, AssetBlindingFactor, Value, ValueBlindingFactor},
+    hashes::{hash160, Hash},
+    locktime::Height,
+    opcodes::all::{
+        OP_CHECKSIG, OP_CLTV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160,
+        OP_IF, OP_SIZE,
+    },
+    pset::{Input, Output, PartiallySignedTransaction},
+    script::Builder,
+    secp256k1_zkp::{Message, PublicKey, SecretKey},
+    sighash::SighashCache,
+    Address, AddressParams, EcdsaSighashType, OutPoint, Script, Sequence, Transaction, TxOut,
+    TxOutSecrets, TxOutWitness,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, EC};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SwapError {
+    #[error("The lockup transaction doesn't contain an output to the swap script")]
+    MissingLockupOutput,
+
+    #[error("The lockup amount {amount} is not enough to pay the fee {fee}")]
+    AmountTooLow { amount: u64, fee: u64 },
+
+    #[error("The preimage doesn't match the swap preimage hash")]
+    InvalidPreimage,
+
+    #[error("The secret key doesn't match the swap {0} public key")]
+    InvalidSecretKey(&'static str),
+
+    #[error("Cannot spend a confidential lockup output to an unconfidential address")]
+    UnconfidentialDestination,
+
+    #[error("Invalid timeout {0}, must be a block height")]
+    InvalidTimeout(u32),
+
+    #[error("The PSET is not a swap {0} transaction")]
+    InvalidPset(&'static str),
+}
+
+/// The kind of swap
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapKind {
+    /// Liquid→Lightning, the user locks the funds and can refund them after the timeout
+    Submarine,
+
+    /// Lightning→Liquid, the user claims the funds locked by the swap provider
+    ReverseSubmarine,
+}
+
+/// The parameters of a swap, defining the lockup script
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SwapScript {
+    /// The kind of swap
+    pub kind: SwapKind,
+
+    /// The HASH160 of the preimage
+    pub preimage_hash: hash160::Hash,
+
+    /// The key that can spend the output revealing the preimage
+    pub claim_pubkey: PublicKey,
+
+    /// The key that can spend the output after the timeout
+    pub refund_pubkey: PublicKey,
+
+    /// The block height after which the refund path is enabled
+    pub timeout: u32,
+
+    /// The private blinding key of the lockup address, if confidential
+    pub blinding_key: Option<SecretKey>,
+}
+
+impl SwapScript {
+    /// The witness script of the lockup output
+    pub fn witness_script(&self) -> Script {
+        let builder = match self.kind {
+            SwapKind::Submarine => Builder::new()
+                .push_opcode(OP_HASH160)
+                .push_slice(self.preimage_hash.as_byte_array())
+                .push_opcode(OP_EQUAL)
+                .push_opcode(OP_IF)
+                .push_slice(&self.claim_pubkey.serialize())
+                .push_opcode(OP_ELSE),
+            SwapKind::ReverseSubmarine => Builder::new()
+                .push_opcode(OP_SIZE)
+                .push_int(32)
+                .push_opcode(OP_EQUAL)
+                .push_opcode(OP_IF)
+                .push_opcode(OP_HASH160)
+                .push_slice(self.preimage_hash.as_byte_array())
+                .push_opcode(OP_EQUALVERIFY)
+                .push_slice(&self.claim_pubkey.serialize())
+                .push_opcode(OP_ELSE)
+                .push_opcode(OP_DROP),
+        };
+        builder
+            .push_int(self.timeout as i64)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_slice(&self.refund_pubkey.serialize())
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// The script pubkey of the lockup output
+    pub fn script_pubkey(&self) -> Script {
+        self.witness_script().to_v0_p2wsh()
+    }
+
+    /// The lockup address, confidential if the blinding key is set
+    pub fn address(&self, params: &'static AddressParams) -> Address {
+        let blinder = self
+            .blinding_key
+            .map(|sk| PublicKey::from_secret_key(&EC, &sk));
+        Address::p2wsh(&self.witness_script(), blinder, params)
+    }
+
+    /// Find the lockup output in the given transaction and unblind it
+    pub fn lockup_output(
+        &self,
+        lockup_tx: &Transaction,
+    ) -> Result<(OutPoint, TxOut, TxOutSecrets), Error> {
+        let script_pubkey = self.script_pubkey();
+        let (vout, txout) = lockup_tx
+            .output
+            .iter()
+            .enumerate()
+            .find(|(_, o)| o.script_pubkey == script_pubkey)
+            .ok_or(SwapError::MissingLockupOutput)?;
+        let secrets = match (txout.asset, txout.value, self.blinding_key) {
+            (Asset::Explicit(asset), Value::Explicit(value), _) => TxOutSecrets::new(
+                asset,
+                AssetBlindingFactor::zero(),
+                value,
+                ValueBlindingFactor::zero(),
+            ),
+            (_, _, Some(blinding_key)) => txout.unblind(&EC, blinding_key)?,
+            _ => return Err(SwapError::MissingLockupOutput.into()),
+        };
+        Ok((
+            OutPoint::new(lockup_tx.txid(), vout as u32),
+            txout.clone(),
+            secrets,
+        ))
+    }
+
+    /// Create the PSET claiming the lockup output to `destination` revealing the preimage
+    ///
+    /// The PSET is blinded and must be finalized with [`SwapScript::finalize_claim`].
+    pub fn claim_pset(
+        &self,
+        lockup_tx: &Transaction,
+        destination: &Address,
+        fee: u64,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        self.spending_pset(lockup_tx, destination, fee, None)
+    }
+
+    /// Create the PSET refunding the lockup output to `destination` after the timeout
+    ///
+    /// The PSET is blinded and must be finalized with [`SwapScript::finalize_refund`].
+    pub fn refund_pset(
+        &self,
+        lockup_tx: &Transaction,
+        destination: &Address,
+        fee: u64,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        self.spending_pset(lockup_tx, destination, fee, Some(self.timeout))
+    }
+
+    fn spending_pset(
+        &self,
+        lockup_tx: &Transaction,
+        destination: &Address,
+        fee: u64,
+        timeout: Option<u32>,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        let (outpoint, txout, secrets) = self.lockup_output(lockup_tx)?;
+        if secrets.value <= fee {
+            return Err(SwapError::AmountTooLow {
+                amount: secrets.value,
+                fee,
+            }
+            .into());
+        }
+        let confidential_input = txout.value.is_confidential();
+        if confidential_input && destination.blinding_pubkey.is_none() {
+            return Err(SwapError::UnconfidentialDestination.into());
+        }
+
+        let mut pset = PartiallySignedTransaction::new_v2();
+
+        let mut input = Input::from_prevout(outpoint);
+        input.witness_utxo = Some(TxOut {
+            witness: TxOutWitness::default(),
+            ..txout
+        });
+        input.witness_script = Some(self.witness_script());
+        input.amount = Some(secrets.value);
+        input.asset = Some(secrets.asset);
+        if let Some(timeout) = timeout {
+            input.sequence = Some(Sequence::ENABLE_LOCKTIME_NO_RBF);
+            input.required_height_locktime = Some(
+                Height::from_consensus(timeout).map_err(|_| SwapError::InvalidTimeout(timeout))?,
+            );
+        }
+        pset.add_input(input);
+
+        pset.add_output(Output {
+            script_pubkey: destination.script_pubkey(),
+            amount: Some(secrets.value - fee),
+            asset: Some(secrets.asset),
+            blinding_key: destination.blinding_pubkey.map(convert_pubkey),
+            blinder_index: Some(0),
+            ..Default::default()
+        });
+        pset.add_output(Output::new_explicit(
+            Script::default(),
+            fee,
+            secrets.asset,
+            None,
+        ));
+
+        if destination.blinding_pubkey.is_some() {
+            let inp_txout_sec = HashMap::from([(0, secrets)]);
+            pset.blind_last(&mut rand::thread_rng(), &EC, &inp_txout_sec)?;
+        }
+        Ok(pset)
+    }
+
+    /// Sign and finalize the claim PSET, returning the transaction ready to be broadcast
+    pub fn finalize_claim(
+        &self,
+        pset: &PartiallySignedTransaction,
+        claim_key: &SecretKey,
+        preimage: &[u8; 32],
+    ) -> Result<Transaction, Error> {
+        if hash160::Hash::hash(preimage) != self.preimage_hash {
+            return Err(SwapError::InvalidPreimage.into());
+        }
+        if PublicKey::from_secret_key(&EC, claim_key) != self.claim_pubkey {
+            return Err(SwapError::InvalidSecretKey("claim").into());
+        }
+        self.finalize(pset, claim_key, preimage.to_vec(), "claim")
+    }
+
+    /// Sign and finalize the refund PSET, returning the transaction ready to be broadcast
+    ///
+    /// The transaction can be included in a block only after the swap timeout.
+    pub fn finalize_refund(
+        &self,
+        pset: &PartiallySignedTransaction,
+        refund_key: &SecretKey,
+    ) -> Result<Transaction, Error> {
+        if PublicKey::from_secret_key(&EC, refund_key) != self.refund_pubkey {
+            return Err(SwapError::InvalidSecretKey("refund").into());
+        }
+        self.finalize(pset, refund_key, vec![], "refund")
+    }
+
+    fn finalize(
+        &self,
+        pset: &PartiallySignedTransaction,
+        key: &SecretKey,
+        unlock: Vec<u8>,
+        path: &'static str,
+    ) -> Result<Transaction, Error> {
+        let witness_script = self.witness_script();
+        let [input] = pset.inputs() else {
+            return Err(SwapError::InvalidPset(path).into());
+        };
+        let value = match &input.witness_utxo {
+            Some(txout) if txout.script_pubkey == witness_script.to_v0_p2wsh() => txout.value,
+            _ => return Err(SwapError::InvalidPset(path).into()),
+        };
+
+        let mut tx = pset.extract_tx()?;
+        let sighash = SighashCache::new(&tx).segwitv0_sighash(
+            0,
+            &witness_script,
+            value,
+            EcdsaSighashType::All,
+        );
+        let message = Message::from_digest(sighash.to_byte_array());
+        let signature = EC.sign_ecdsa_low_r(&message, key);
+        let mut sig = signature.serialize_der().to_vec();
+        sig.push(EcdsaSighashType::All as u8);
+
+        tx.input[0].witness.script_witness = vec![sig, unlock, witness_script.to_bytes()];
+        Ok(tx)
+    }
+}
+
+fn convert_pubkey(pk: PublicKey) -> elements::bitcoin::PublicKey {
+    elements::bitcoin::PublicKey::new(pk)
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::{
+        confidential::{AssetBlindingFactor, ValueBlindingFactor},
+        hashes::{hash160, Hash},
+        secp256k1_zkp::{Message, PublicKey, SecretKey},
+        sighash::SighashCache,
+        Address, AddressParams, AssetId, EcdsaSighashType, LockTime, Transaction, TxOut,
+        TxOutSecrets,
+    };
+
+    use super::{SwapKind, SwapScript};
+    use crate::{Error, EC};
+
+    fn key(i: u8) -> (SecretKey, PublicKey) {
+        let sk = SecretKey::from_slice(&[i; 32]).unwrap();
+        (sk, PublicKey::from_secret_key(&EC, &sk))
+    }
+
+    fn swap(kind: SwapKind, preimage: &[u8; 32]) -> SwapScript {
+        SwapScript {
+            kind,
+            preimage_hash: hash160::Hash::hash(preimage),
+            claim_pubkey: key(1).1,
+            refund_pubkey: key(2).1,
+            timeout: 1_000,
+            blinding_key: Some(key(3).0),
+        }
+    }
+
+    fn lockup_tx(swap: &SwapScript, asset: AssetId, value: u64) -> Transaction {
+        let address = swap.address(&AddressParams::ELEMENTS);
+        let input = TxOutSecrets::new(
+            asset,
+            AssetBlindingFactor::zero(),
+            value,
+            ValueBlindingFactor::zero(),
+        );
+        let (txout, _, _, _) = TxOut::new_not_last_confidential(
+            &mut rand::thread_rng(),
+            &EC,
+            value,
+            address,
+            asset,
+            &[input],
+        )
+        .unwrap();
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![txout],
+        }
+    }
+
+    fn destination() -> Address {
+        let (_, pk) = key(4);
+        let (_, blinder) = key(5);
+        Address::p2wpkh(
+            &elements::bitcoin::PublicKey::new(pk),
+            Some(blinder),
+            &AddressParams::ELEMENTS,
+        )
+    }
+
+    fn verify(swap: &SwapScript, lockup: &Transaction, tx: &Transaction, pk: &PublicKey) {
+        let prevout = &lockup.output[0];
+        tx.verify_tx_amt_proofs(&EC, &[prevout.clone()]).unwrap();
+        let witness = &tx.input[0].witness.script_witness;
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[2], swap.witness_script().to_bytes());
+        let sighash = SighashCache::new(tx).segwitv0_sighash(
+            0,
+            &swap.witness_script(),
+            prevout.value,
+            EcdsaSighashType::All,
+        );
+        let message = Message::from_digest(sighash.to_byte_array());
+        let sig = &witness[0];
+        let sig =
+            elements::secp256k1_zkp::ecdsa::Signature::from_der(&sig[..sig.len() - 1]).unwrap();
+        EC.verify_ecdsa(&message, &sig, pk).unwrap();
+    }
+
+    #[test]
+    fn test_swap_script() {
+        let preimage = [7u8; 32];
+        let submarine = swap(SwapKind::Submarine, &preimage);
+        let reverse = swap(SwapKind::ReverseSubmarine, &preimage);
+        assert_ne!(submarine.script_pubkey(), reverse.script_pubkey());
+        assert!(submarine.script_pubkey().is_v0_p2wsh());
+        assert!(submarine.address(&AddressParams::LIQUID).is_blinded());
+
+        let asm = reverse.witness_script().asm();
+        assert!(asm.starts_with("OP_SIZE OP_PUSHBYTES_1 20 OP_EQUAL OP_IF OP_HASH160"));
+        assert!(asm.ends_with("OP_ENDIF OP_CHECKSIG"));
+        let asm = submarine.witness_script().asm();
+        assert!(asm.starts_with("OP_HASH160"));
+        assert!(asm.contains("OP_CLTV OP_DROP"));
+    }
+
+    #[test]
+    fn test_swap_claim() {
+        let preimage = [7u8; 32];
+        let swap = swap(SwapKind::ReverseSubmarine, &preimage);
+        let asset = AssetId::from_slice(&[9; 32]).unwrap();
+        let lockup = lockup_tx(&swap, asset, 10_000);
+
+        let pset = swap.claim_pset(&lockup, &destination(), 200).unwrap();
+        assert_eq!(pset.outputs()[1].amount, Some(200));
+
+        let err = swap.finalize_claim(&pset, &key(1).0, &[8u8; 32]);
+        assert!(matches!(err, Err(Error::SwapError(_))));
+        let err = swap.finalize_claim(&pset, &key(2).0, &preimage);
+        assert!(matches!(err, Err(Error::SwapError(_))));
+
+        let tx = swap.finalize_claim(&pset, &key(1).0, &preimage).unwrap();
+        assert_eq!(tx.input[0].witness.script_witness[1], preimage.to_vec());
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+        verify(&swap, &lockup, &tx, &key(1).1);
+
+        let err = swap.claim_pset(&lockup, &destination(), 10_000);
+        assert!(matches!(err, Err(Error::SwapError(_))));
+        let unconfidential = destination().to_unconfidential();
+        let err = swap.claim_pset(&lockup, &unconfidential, 200);
+        assert!(matches!(err, Err(Error::SwapError(_))));
+    }
+
+    #[test]
+    fn test_swap_refund() {
+        let preimage = [7u8; 32];
+        let swap = swap(SwapKind::Submarine, &preimage);
+        let asset = AssetId::from_slice(&[9; 32]).unwrap();
+        let lockup = lockup_tx(&swap, asset, 10_000);
+
+        let pset = swap.refund_pset(&lockup, &destination(), 200).unwrap();
+        let tx = swap.finalize_refund(&pset, &key(2).0).unwrap();
+        assert!(tx.input[0].witness.script_witness[1].is_empty());
+        assert_eq!(tx.lock_time, LockTime::from_height(1_000).unwrap());
+        assert!(tx.input[0].sequence.enables_absolute_lock_time());
+        verify(&swap, &lockup, &tx, &key(2).1);
+
+        let other = SwapScript {
+            timeout: 1_001,
+            ..swap.clone()
+        };
+        assert!(other.finalize_refund(&pset, &key(2).0).is_err());
+    }
+}
@@ -7,16 +7,190 @@ use elements::{
     secp256k1_zkp::{self, ZERO_TWEAK},
     Address, AssetId, EcdsaSighashType, OutPoint, Script, Transaction,
 };
-use rand::thread_rng;
+use rand::{seq::SliceRandom, thread_rng, Rng};
 
 use crate::{
     hashes::Hash,
     liquidex::{self, LiquidexError},
-    model::{ExternalUtxo, IssuanceDetails, Recipient},
+    model::{ExternalUtxo, IssuanceDetails, Recipient, WalletTxOut},
     pset_create::{validate_address, IssuanceRequest},
-    Contract, ElementsNetwork, Error, LiquidexProposal, UnvalidatedRecipient, Wollet, EC,
+    Contract, ElementsNetwork, Error, LiquidexProposal, UnvalidatedRecipient, Wollet,
+    WolletDescriptor, EC,
 };
 
+/// How the wallet UTXOs are selected by [`TxBuilder::finish()`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoinSelection {
+    /// Assets UTXOs are selected by value, descending, until the amount is covered.
+    /// All the fee asset UTXOs are selected.
+    #[default]
+    Deterministic,
+
+    /// UTXOs are drawn at random until the amount, and for the fee asset the fee, is covered
+    ///
+    /// The UTXOs on the same address are drawn together, so that an address is not linked to
+    /// more transactions than needed. Repeated payments don't spend the same inputs pattern,
+    /// at the cost of possibly more inputs than the deterministic selection.
+    RandomDraw,
+}
+
+/// Where [`TxBuilder::finish()`] sends the change
+///
+/// By default the change goes to the internal chain of the wallet descriptor. Sending it to a
+/// descriptor with a different script type helps migrating the funds to a new descriptor (for
+/// instance from wrapped to native segwit), or spending from wallets of different script types
+/// without revealing which output is the change.
+///
+/// The indexes are the first internal indexes to use for the given descriptors, which are
+/// incremented for every change output; usually the index returned by
+/// [`Wollet::change()`] of the wallet of that descriptor.
+#[derive(Debug, Clone, Default)]
+pub enum ChangeScript {
+    /// Send the change to the wallet descriptor
+    #[default]
+    Wallet,
+
+    /// Send the change to the given descriptor
+    Fixed(WolletDescriptor, u32),
+
+    /// Send the change to the descriptor with the same script type of the input with the largest
+    /// value, choosing among the wallet descriptor and the given ones
+    ///
+    /// If no descriptor matches, the change is sent to the wallet descriptor.
+    MatchLargestInput(Vec<(WolletDescriptor, u32)>),
+}
+
+impl ChangeScript {
+    /// Send the outputs at `change_outputs`, created with the wallet change addresses, to the
+    /// descriptor chosen by this policy
+    fn apply(
+        &self,
+        wollet: &Wollet,
+        pset: &mut PartiallySignedTransaction,
+        inp_txout_sec: &HashMap<usize, elements::TxOutSecrets>,
+        change_outputs: &[usize],
+    ) -> Result<(), Error> {
+        let network = wollet.network();
+        let (descriptor, mut index) = match self {
+            ChangeScript::Wallet => return Ok(()),
+            ChangeScript::Fixed(descriptor, index) => (descriptor, *index),
+            ChangeScript::MatchLargestInput(descriptors) => {
+                // ties are broken by the input position, so that the choice is deterministic
+                let largest = inp_txout_sec
+                    .iter()
+                    .max_by_key(|(idx, secrets)| (secrets.value, std::cmp::Reverse(**idx)))
+                    .and_then(|(idx, _)| pset.inputs()[*idx].witness_utxo.as_ref())
+                    .map(|txout| ScriptType::new(&txout.script_pubkey));
+                let Some(largest) = largest else {
+                    return Ok(());
+                };
+                if ScriptType::of_descriptor(&wollet.wollet_descriptor(), network)? == largest {
+                    return Ok(());
+                }
+                let mut matching = None;
+                for (descriptor, index) in descriptors {
+                    if ScriptType::of_descriptor(descriptor, network)? == largest {
+                        matching = Some((descriptor, *index));
+                        break;
+                    }
+                }
+                match matching {
+                    Some(matching) => matching,
+                    None => return Ok(()),
+                }
+            }
+        };
+        for vout in change_outputs {
+            let address = descriptor.change(index, network.address_params())?;
+            index += 1;
+            let output = &mut pset.outputs_mut()[*vout];
+            output.script_pubkey = address.script_pubkey();
+            output.blinding_key = address
+                .blinding_pubkey
+                .map(elements::bitcoin::PublicKey::new);
+        }
+        Ok(())
+    }
+}
+
+/// The type of an output script, to match change and inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Other,
+}
+
+impl ScriptType {
+    fn new(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_v0_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_v0_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_v1_p2tr() {
+            ScriptType::P2tr
+        } else {
+            ScriptType::Other
+        }
+    }
+
+    fn of_descriptor(
+        descriptor: &WolletDescriptor,
+        network: ElementsNetwork,
+    ) -> Result<Self, Error> {
+        let address = descriptor.change(0, network.address_params())?;
+        Ok(Self::new(&address.script_pubkey()))
+    }
+}
+
+/// Upper bounds of the vsize of the transaction parts, to estimate the fee while drawing UTXOs
+const MAX_TX_OVERHEAD_VSIZE: usize = 100;
+const MAX_INPUT_VSIZE: usize = 50;
+const MAX_OUTPUT_VSIZE: usize = 1_300;
+
+/// The outputs that may be added after selecting the fee asset UTXOs: change, fee and
+/// (re)issuance outputs
+const MAX_LATER_OUTPUTS: usize = 4;
+
+impl CoinSelection {
+    /// The groups of UTXOs to select in order, all the UTXOs of a group must be selected
+    fn groups<R: Rng>(&self, utxos: Vec<WalletTxOut>, rng: &mut R) -> Vec<Vec<WalletTxOut>> {
+        match self {
+            CoinSelection::Deterministic => utxos.into_iter().map(|u| vec![u]).collect(),
+            CoinSelection::RandomDraw => {
+                let mut groups: Vec<Vec<WalletTxOut>> = vec![];
+                for utxo in utxos {
+                    match groups
+                        .iter_mut()
+                        .find(|g| g[0].script_pubkey == utxo.script_pubkey)
+                    {
+                        Some(group) => group.push(utxo),
+                        None => groups.push(vec![utxo]),
+                    }
+                }
+                groups.shuffle(rng);
+                groups
+            }
+        }
+    }
+}
+
+/// An upper bound of the fee of a transaction with the given inputs and outputs
+fn max_fee(fee_rate: f32, n_inputs: usize, inp_weight: usize, n_outputs: usize) -> u64 {
+    let vsize = MAX_TX_OVERHEAD_VSIZE
+        + n_inputs * MAX_INPUT_VSIZE
+        + inp_weight.div_ceil(4)
+        + n_outputs * MAX_OUTPUT_VSIZE;
+    (vsize as f32 * fee_rate / 1000.0).ceil() as u64
+}
+
 pub fn extract_issuances(tx: &Transaction) -> Vec<IssuanceDetails> {
     let mut r = vec![];
     for (vin, txin) in tx.input.iter().enumerate() {
@@ -97,10 +271,14 @@ pub struct TxBuilder {
     external_utxos: Vec<ExternalUtxo>,
 
     selected_utxos: Option<Vec<OutPoint>>,
+    coin_selection: CoinSelection,
+    change_script: ChangeScript,
 
     // LiquiDEX fields
     is_liquidex_make: bool,
     liquidex_proposals: Vec<LiquidexProposal>,
+
+    fee_asset: Option<AssetId>,
 }
 
 impl TxBuilder {
@@ -116,8 +294,11 @@ impl TxBuilder {
             drain_to: None,
             external_utxos: vec![],
             selected_utxos: None,
+            coin_selection: CoinSelection::default(),
+            change_script: ChangeScript::default(),
             is_liquidex_make: false,
             liquidex_proposals: vec![],
+            fee_asset: None,
         }
     }
 
@@ -125,6 +306,12 @@ impl TxBuilder {
         self.network
     }
 
+    /// The asset used to pay the fees, the network policy asset if not set
+    fn get_fee_asset(&self) -> AssetId {
+        self.fee_asset
+            .unwrap_or_else(|| self.network().policy_asset())
+    }
+
     /// Add recipient to the internal list
     pub fn add_recipient(
         self,
@@ -189,6 +376,18 @@ impl TxBuilder {
         self
     }
 
+    /// Set the asset used to pay the fees, by default the network policy asset is used
+    ///
+    /// The fee asset is also the one used for the change of the fee inputs and the one drained
+    /// with [`TxBuilder::drain_lbtc_wallet()`] and [`TxBuilder::drain_lbtc_to()`].
+    ///
+    /// Note: Liquid nodes relay only transactions paying fees in the policy asset, use a
+    /// different asset only with networks accepting it.
+    pub fn fee_asset(mut self, asset: AssetId) -> Self {
+        self.fee_asset = Some(asset);
+        self
+    }
+
     /// Use ELIP200 discounted fees for Confidential Transactions
     ///
     /// Note: if ELIP200 was not activated by miners and nodes relaying transactions, using
@@ -322,6 +521,22 @@ impl TxBuilder {
         self
     }
 
+    /// Set how the wallet UTXOs are selected, see [`CoinSelection`]
+    ///
+    /// Ignored if the UTXOs are set with [`TxBuilder::set_wallet_utxos()`].
+    pub fn coin_selection(mut self, coin_selection: CoinSelection) -> Self {
+        self.coin_selection = coin_selection;
+        self
+    }
+
+    /// Set where the change is sent, see [`ChangeScript`]
+    ///
+    /// Ignored for the L-BTC change if [`TxBuilder::drain_lbtc_to()`] is set.
+    pub fn change_script(mut self, change_script: ChangeScript) -> Self {
+        self.change_script = change_script;
+        self
+    }
+
     /// Set data to create a PSET from which you
     /// can create a LiquiDEX proposal
     pub fn liquidex_make(
@@ -488,6 +703,7 @@ impl TxBuilder {
         let mut input_domain = vec![];
         let mut last_unused_internal = wollet.change(None)?.index();
         let mut last_unused_external = wollet.address(None)?.index();
+        let mut change_outputs = vec![];
         let mut rng = thread_rng();
 
         let [input] = pset.inputs() else {
@@ -560,6 +776,7 @@ impl TxBuilder {
                             &mut last_unused_internal,
                         )?;
                         wollet.add_output(&mut pset, &addressee)?;
+                        change_outputs.push(pset.n_outputs() - 1);
                     }
                     break;
                 }
@@ -610,6 +827,9 @@ impl TxBuilder {
             &mut last_unused_internal,
         )?;
         wollet.add_output(&mut pset, &addressee)?;
+        change_outputs.push(pset.n_outputs() - 1);
+        self.change_script
+            .apply(wollet, &mut pset, &inp_txout_sec, &change_outputs)?;
         let fee_output =
             Output::new_explicit(Script::default(), temp_fee, wollet.policy_asset(), None);
         pset.add_output(fee_output);
@@ -679,7 +899,27 @@ impl TxBuilder {
     }
 
     /// Finish building the transaction
+    ///
+    /// Fails if the transaction doesn't respect [`crate::Config::with_strict_blinding()`].
     pub fn finish(self, wollet: &Wollet) -> Result<PartiallySignedTransaction, Error> {
+        let pset = self.finish_unchecked(wollet)?;
+        if wollet.config.strict_blinding() {
+            wollet.config.check_blinding(&pset.extract_tx()?)?;
+        }
+        Ok(pset)
+    }
+
+    fn finish_unchecked(self, wollet: &Wollet) -> Result<PartiallySignedTransaction, Error> {
+        let fee_asset = self.get_fee_asset();
+        let policy_asset = self.network().policy_asset();
+        if fee_asset != policy_asset
+            && (self.is_liquidex_make
+                || !self.liquidex_proposals.is_empty()
+                || !self.external_utxos.is_empty())
+        {
+            // LiquiDEX and external utxos assume fees are paid with the policy asset
+            return Err(Error::UnsupportedFeeAsset(fee_asset));
+        }
         if self.is_liquidex_make {
             return self.finish_liquidex_make(wollet);
         } else if !self.liquidex_proposals.is_empty() {
@@ -690,14 +930,15 @@ impl TxBuilder {
         let mut inp_txout_sec = HashMap::new();
         let mut last_unused_internal = wollet.change(None)?.index();
         let mut last_unused_external = wollet.address(None)?.index();
+        let mut change_outputs = vec![];
 
         let mut inp_weight = 0;
+        let mut rng = thread_rng();
 
-        let policy_asset = self.network().policy_asset();
         let (addressees_lbtc, addressees_asset): (Vec<_>, Vec<_>) = self
             .recipients
             .into_iter()
-            .partition(|a| a.asset == policy_asset);
+            .partition(|a| a.asset == fee_asset);
 
         // Assets inputs and outputs
         let assets: HashSet<_> = addressees_asset.iter().map(|a| a.asset).collect();
@@ -711,9 +952,12 @@ impl TxBuilder {
                 wollet.add_output(&mut pset, addressee)?;
                 satoshi_out += addressee.satoshi;
             }
-            for utxo in wollet.asset_utxos(&asset)? {
-                wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
-                satoshi_in += utxo.unblinded.value;
+            let utxos = wollet.asset_utxos(&asset)?;
+            for group in self.coin_selection.groups(utxos, &mut rng) {
+                for utxo in group {
+                    wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
+                    satoshi_in += utxo.unblinded.value;
+                }
                 if satoshi_in >= satoshi_out {
                     if satoshi_in > satoshi_out {
                         let satoshi_change = satoshi_in - satoshi_out;
@@ -723,6 +967,7 @@ impl TxBuilder {
                             &mut last_unused_internal,
                         )?;
                         wollet.add_output(&mut pset, &addressee)?;
+                        change_outputs.push(pset.n_outputs() - 1);
                     }
                     break;
                 }
@@ -736,8 +981,8 @@ impl TxBuilder {
             }
         }
 
-        // L-BTC inputs and outputs
-        // Fee and L-BTC change after (re)issuance
+        // Fee asset (L-BTC unless set otherwise) inputs and outputs
+        // Fee and fee asset change after (re)issuance
         let mut satoshi_out = 0;
         let mut satoshi_in = 0;
         for addressee in addressees_lbtc {
@@ -747,7 +992,7 @@ impl TxBuilder {
 
         // Add all external L-BTC utxos
         for utxo in &self.external_utxos {
-            if utxo.unblinded.asset != policy_asset {
+            if utxo.unblinded.asset != fee_asset {
                 continue;
             }
             add_external_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, utxo);
@@ -760,16 +1005,30 @@ impl TxBuilder {
 
                 for coin in coins {
                     let utxo = utxos.get(&coin).ok_or(Error::MissingWalletUtxo(coin))?;
-                    if utxo.unblinded.asset != policy_asset {
+                    if utxo.unblinded.asset != fee_asset {
                         return Err(Error::ManualCoinSelectionOnlyLbtc);
                     }
                     wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, utxo)?;
                     satoshi_in += utxo.unblinded.value;
                 }
             }
+            None if self.coin_selection == CoinSelection::RandomDraw && !self.drain_lbtc => {
+                let utxos = wollet.asset_utxos(&fee_asset)?;
+                for group in self.coin_selection.groups(utxos, &mut rng) {
+                    for utxo in group {
+                        wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
+                        satoshi_in += utxo.unblinded.value;
+                    }
+                    let n_outputs = pset.n_outputs() + MAX_LATER_OUTPUTS;
+                    let fee = max_fee(self.fee_rate, pset.n_inputs(), inp_weight, n_outputs);
+                    if satoshi_in > satoshi_out + fee {
+                        break;
+                    }
+                }
+            }
             None => {
-                // FIXME: For implementation simplicity now we always add all L-BTC inputs
-                for utxo in wollet.asset_utxos(&wollet.policy_asset())? {
+                // FIXME: For implementation simplicity now we always add all fee asset inputs
+                for utxo in wollet.asset_utxos(&fee_asset)? {
                     wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
                     satoshi_in += utxo.unblinded.value;
                 }
@@ -786,7 +1045,7 @@ impl TxBuilder {
                 address_token,
                 contract,
             ) => {
-                // At least a L-BTC input for the fee was added.
+                // At least a fee asset input was added.
                 let idx = 0;
                 let (asset, token) =
                     wollet.set_issuance(&mut pset, idx, satoshi_asset, satoshi_token, contract)?;
@@ -856,6 +1115,7 @@ impl TxBuilder {
                                 &mut last_unused_internal,
                             )?;
                             wollet.add_output(&mut pset, &addressee)?;
+                            change_outputs.push(pset.n_outputs() - 1);
 
                             (idx, utxo_token.unblinded.asset_bf)
                         }
@@ -888,27 +1148,24 @@ impl TxBuilder {
         if satoshi_in <= (satoshi_out + temp_fee) {
             return Err(Error::InsufficientFunds {
                 missing_sats: (satoshi_out + temp_fee + 1) - satoshi_in, // +1 to ensure we have more than just equal
-                asset_id: wollet.policy_asset(),
+                asset_id: fee_asset,
                 is_token: false,
             });
         }
         let satoshi_change = satoshi_in - satoshi_out - temp_fee;
         let addressee = if let Some(address) = self.drain_to {
-            Recipient::from_address(satoshi_change, &address, wollet.policy_asset())
+            Recipient::from_address(satoshi_change, &address, fee_asset)
         } else {
-            wollet.addressee_change(
-                satoshi_change,
-                wollet.policy_asset(),
-                &mut last_unused_internal,
-            )?
+            change_outputs.push(pset.n_outputs());
+            wollet.addressee_change(satoshi_change, fee_asset, &mut last_unused_internal)?
         };
         wollet.add_output(&mut pset, &addressee)?;
-        let fee_output =
-            Output::new_explicit(Script::default(), temp_fee, wollet.policy_asset(), None);
+        self.change_script
+            .apply(wollet, &mut pset, &inp_txout_sec, &change_outputs)?;
+        let fee_output = Output::new_explicit(Script::default(), temp_fee, fee_asset, None);
         pset.add_output(fee_output);
 
         let weight = {
-            let mut rng = thread_rng();
             let mut temp_pset = pset.clone();
             temp_pset.blind_last(&mut rng, &EC, &inp_txout_sec)?;
             let tx_weight = {
@@ -927,7 +1184,7 @@ impl TxBuilder {
         if satoshi_in <= (satoshi_out + fee) {
             return Err(Error::InsufficientFunds {
                 missing_sats: (satoshi_out + fee + 1) - satoshi_in, // +1 to ensure we have more than just equal
-                asset_id: wollet.policy_asset(),
+                asset_id: fee_asset,
                 is_token: false,
             });
         }
@@ -943,7 +1200,6 @@ impl TxBuilder {
         // TODO inputs/outputs(except fee) randomization, not trivial because of blinder_index on inputs
 
         // Blind the transaction
-        let mut rng = thread_rng();
         pset.blind_last(&mut rng, &EC, &inp_txout_sec)?;
 
         // Add details to the pset from our descriptor, like bip32derivation and keyorigin
@@ -1041,6 +1297,14 @@ impl<'a> WolletTxBuilder<'a> {
         }
     }
 
+    /// Wrapper of [`TxBuilder::fee_asset()`]
+    pub fn fee_asset(self, asset: AssetId) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.fee_asset(asset),
+        }
+    }
+
     /// Wrapper of [`TxBuilder::enable_ct_discount()`]
     pub fn enable_ct_discount(self) -> Self {
         Self {
@@ -1128,6 +1392,22 @@ impl<'a> WolletTxBuilder<'a> {
         }
     }
 
+    /// Wrapper of [`TxBuilder::coin_selection()`]
+    pub fn coin_selection(self, coin_selection: CoinSelection) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.coin_selection(coin_selection),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::change_script()`]
+    pub fn change_script(self, change_script: ChangeScript) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.change_script(change_script),
+        }
+    }
+
     /// Wrapper of [`TxBuilder::liquidex_make()`]
     pub fn liquidex_make(
         self,
@@ -1153,6 +1433,8 @@ impl<'a> WolletTxBuilder<'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use elements::encode::Decodable;
 
     use super::*;
@@ -1183,4 +1465,163 @@ mod tests {
         assert_eq!(issuance.asset_amount, Some(1000000000));
         assert_eq!(issuance.token_amount, Some(1));
     }
+
+    #[test]
+    fn test_change_script() {
+        let wollet = crate::wollet::tests::test_wollet_with_many_transactions();
+        let params = wollet.network().address_params();
+        let address = wollet.address(None).unwrap().address().clone();
+        let change = wollet.change(None).unwrap();
+        // the same keys, wrapped in p2sh
+        let wrapped: WolletDescriptor = wollet
+            .wollet_descriptor()
+            .to_string()
+            .split('#')
+            .next()
+            .unwrap()
+            .replace("elwpkh(", "elsh(wpkh(")
+            .replace("/*))", "/*)))")
+            .parse()
+            .unwrap();
+        let change_scripts = |change_script: ChangeScript| {
+            let pset = wollet
+                .tx_builder()
+                .add_lbtc_recipient(&address, 1_000)
+                .unwrap()
+                .change_script(change_script)
+                .finish()
+                .unwrap();
+            let tx = pset.extract_tx().unwrap();
+            tx.output
+                .iter()
+                .map(|o| o.script_pubkey.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let scripts = change_scripts(ChangeScript::Wallet);
+        assert!(scripts.contains(&change.address().script_pubkey()));
+
+        let scripts = change_scripts(ChangeScript::Fixed(wrapped.clone(), 5));
+        assert!(!scripts.contains(&change.address().script_pubkey()));
+        let wrapped_change = wrapped.change(5, params).unwrap().script_pubkey();
+        assert!(wrapped_change.is_p2sh());
+        assert!(scripts.contains(&wrapped_change));
+
+        // all the inputs are native segwit
+        let scripts = change_scripts(ChangeScript::MatchLargestInput(vec![(wrapped, 5)]));
+        assert!(scripts.contains(&change.address().script_pubkey()));
+        assert!(!scripts.contains(&wrapped_change));
+    }
+
+    #[test]
+    fn test_fee_asset() {
+        let wollet = crate::wollet::tests::test_wollet_with_many_transactions();
+        let policy_asset = wollet.policy_asset();
+        let fee_asset =
+            AssetId::from_str("38fca2d939696061a8f76d4e6b5eecd54e3b4221c846f24a6b279e79952850a5")
+                .unwrap();
+        let balance = wollet.balance().unwrap();
+        assert_eq!(balance.get(&fee_asset), Some(&9876));
+        let address = wollet.address(None).unwrap().address().clone();
+
+        let pset = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .fee_asset(fee_asset)
+            .finish()
+            .unwrap();
+        let tx = pset.extract_tx().unwrap();
+        let fees: Vec<_> = tx.output.iter().filter(|o| o.is_fee()).collect();
+        assert_eq!(fees.len(), 1);
+        assert_eq!(fees[0].asset.explicit(), Some(fee_asset));
+        let fee = fees[0].value.explicit().unwrap();
+        assert!(fee > 0 && fee < 9876);
+        assert_eq!(tx.fee_in(fee_asset), fee);
+        assert_eq!(tx.fee_in(policy_asset), 0);
+
+        // sending L-BTC to ourselves only the balance of the fee asset changes
+        let details = wollet.get_details(&pset).unwrap();
+        assert_eq!(details.balance.fee, fee);
+        assert_eq!(details.balance.balances.get(&policy_asset), None);
+        assert_eq!(
+            details.balance.balances.get(&fee_asset),
+            Some(&-(fee as i64))
+        );
+
+        // not enough funds to pay the fee
+        let token =
+            AssetId::from_str("39ee0a62f96c5b5bd28266769ab4d7df28777ed2988f3818fffe48c4c5ba0f84")
+                .unwrap();
+        let err = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .fee_asset(token)
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::InsufficientFunds { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn test_coin_selection_groups() {
+        use rand::SeedableRng;
+
+        let wollet = crate::wollet::tests::test_wollet_with_many_transactions();
+        let mut utxos = wollet.asset_utxos(&wollet.policy_asset()).unwrap();
+        assert!(utxos.len() > 1);
+        // another utxo on the address of the first one
+        let mut same_address = utxos[0].clone();
+        same_address.outpoint.vout += 100;
+        utxos.push(same_address.clone());
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+        let groups = CoinSelection::Deterministic.groups(utxos.clone(), &mut rng);
+        assert_eq!(groups.len(), utxos.len());
+        assert!(groups
+            .iter()
+            .zip(utxos.iter())
+            .all(|(g, u)| g == &vec![u.clone()]));
+
+        let groups = CoinSelection::RandomDraw.groups(utxos.clone(), &mut rng);
+        assert_eq!(groups.len(), utxos.len() - 1);
+        assert_eq!(groups.iter().map(|g| g.len()).sum::<usize>(), utxos.len());
+        let group = groups.iter().find(|g| g.contains(&same_address)).unwrap();
+        assert_eq!(group, &vec![utxos[0].clone(), same_address]);
+        let scripts: HashSet<_> = groups.iter().map(|g| &g[0].script_pubkey).collect();
+        assert_eq!(scripts.len(), groups.len());
+        for group in groups.iter() {
+            assert!(group
+                .iter()
+                .all(|u| u.script_pubkey == group[0].script_pubkey));
+        }
+    }
+
+    #[test]
+    fn test_coin_selection_max_fee() {
+        assert_eq!(max_fee(1000.0, 0, 0, 0), MAX_TX_OVERHEAD_VSIZE as u64);
+        assert_eq!(max_fee(100.0, 1, 272, 2), 282);
+        assert!(max_fee(100.0, 2, 272, 2) > max_fee(100.0, 1, 272, 2));
+        assert!(max_fee(100.0, 1, 272, 3) > max_fee(100.0, 1, 272, 2));
+        assert!(max_fee(200.0, 1, 272, 2) > max_fee(100.0, 1, 272, 2));
+
+        // the random draw stops once the amount and the fee are covered
+        let wollet = crate::wollet::tests::test_wollet_with_many_transactions();
+        let address = wollet.address(None).unwrap().address().clone();
+        let n_inputs = |coin_selection| {
+            let pset = wollet
+                .tx_builder()
+                .add_lbtc_recipient(&address, 1_000)
+                .unwrap()
+                .coin_selection(coin_selection)
+                .finish()
+                .unwrap();
+            pset.n_inputs()
+        };
+        let all = wollet.asset_utxos(&wollet.policy_asset()).unwrap().len();
+        assert_eq!(all, 2);
+        assert_eq!(n_inputs(CoinSelection::Deterministic), all);
+        // the smaller utxo doesn't cover the amount, the larger one does
+        assert!((0..64).any(|_| n_inputs(CoinSelection::RandomDraw) == 1));
+    }
 }
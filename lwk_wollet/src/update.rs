@@ -1,20 +1,22 @@
+use crate::clients::ScriptCheckpoint;
 use crate::descriptor::Chain;
 use crate::elements::{OutPoint, Script, Transaction, TxOutSecrets, Txid};
 use crate::error::Error;
 use crate::store::{Height, Timestamp};
-use crate::wollet::WolletState;
+use crate::wollet::{tx_proofs_verified, WolletState};
 use crate::EC;
 use crate::{BlindingPublicKey, Wollet, WolletDescriptor};
 use aes_gcm_siv::aead::generic_array::GenericArray;
-use aes_gcm_siv::aead::AeadMutInPlace;
+use aes_gcm_siv::aead::AeadInPlace;
+use aes_gcm_siv::Aes256GcmSiv;
 use base64::prelude::*;
 use elements::bitcoin::bip32::ChildNumber;
 use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
 use elements::encode::{Decodable, Encodable};
 use elements::{BlockHeader, TxInWitness, TxOutWitness};
 use rand::{thread_rng, Rng};
-use std::collections::HashMap;
-use std::sync::atomic;
+use std::collections::{HashMap, HashSet};
+use std::sync::{atomic, Arc};
 
 /// Transactions downloaded and unblinded
 #[derive(Default, Clone, PartialEq, Eq, Debug)]
@@ -69,6 +71,12 @@ pub struct Update {
     /// The blinding pubkey is optional for backward compatibility reasons
     pub scripts_with_blinding_pubkey: Vec<(Chain, ChildNumber, Script, Option<BlindingPublicKey>)>,
     pub tip: BlockHeader,
+
+    /// The new sync checkpoints of the scripts
+    ///
+    /// They are not serialized, when the update is applied they are persisted separately with
+    /// [`crate::Persister::put_value()`].
+    pub checkpoints: Vec<(Script, ScriptCheckpoint)>,
 }
 
 impl Update {
@@ -77,10 +85,31 @@ impl Update {
             && self.txid_height_new.is_empty()
             && self.txid_height_delete.is_empty()
             && self.scripts_with_blinding_pubkey.is_empty()
+            && self.checkpoints.is_empty()
     }
     pub fn prune(&mut self, wallet: &Wollet) {
         self.new_txs.prune(&wallet.store.cache.paths);
     }
+
+    /// Upgrade an update deserialized from a previous version to [`UPDATE_VERSION`]
+    ///
+    /// The blinding public keys missing in updates before version 2 are derived from `desc`.
+    pub fn upgrade(&mut self, desc: &WolletDescriptor) -> Result<(), Error> {
+        if self.version == UPDATE_VERSION {
+            return Ok(());
+        }
+        let scripts = std::mem::take(&mut self.scripts_with_blinding_pubkey);
+        self.scripts_with_blinding_pubkey =
+            compute_blinding_pubkey_if_missing(scripts, desc.clone())?
+                .into_iter()
+                .map(|(chain, child, script, blinding_pubkey)| {
+                    (chain, child, script, Some(blinding_pubkey))
+                })
+                .collect();
+        self.version = UPDATE_VERSION;
+        Ok(())
+    }
+
     pub fn serialize(&self) -> Result<Vec<u8>, elements::encode::Error> {
         let mut vec = vec![];
         self.consensus_encode(&mut vec)?;
@@ -91,20 +120,7 @@ impl Update {
     }
 
     pub fn serialize_encrypted(&self, desc: &WolletDescriptor) -> Result<Vec<u8>, Error> {
-        let mut plaintext = self.serialize()?;
-
-        let mut nonce_bytes = [0u8; 12];
-        thread_rng().fill(&mut nonce_bytes);
-        let nonce = GenericArray::from_slice(&nonce_bytes);
-
-        desc.cipher().encrypt_in_place(nonce, b"", &mut plaintext)?;
-        let ciphertext = plaintext;
-
-        let mut result = Vec::with_capacity(ciphertext.len() + 12);
-        result.extend(nonce.as_slice());
-        result.extend(&ciphertext);
-
-        Ok(result)
+        encrypt(self.serialize()?, &desc.cipher())
     }
 
     pub fn serialize_encrypted_base64(&self, desc: &WolletDescriptor) -> Result<String, Error> {
@@ -113,16 +129,7 @@ impl Update {
     }
 
     pub fn deserialize_decrypted(bytes: &[u8], desc: &WolletDescriptor) -> Result<Update, Error> {
-        let nonce_bytes = &bytes[..12];
-        let mut ciphertext = bytes[12..].to_vec();
-
-        let nonce = GenericArray::from_slice(nonce_bytes);
-
-        desc.cipher()
-            .decrypt_in_place(nonce, b"", &mut ciphertext)?;
-        let plaintext = ciphertext;
-
-        Ok(Update::deserialize(&plaintext)?)
+        Ok(Update::deserialize(&decrypt(bytes, &desc.cipher())?)?)
     }
 
     pub fn deserialize_decrypted_base64(
@@ -136,17 +143,71 @@ impl Update {
     }
 }
 
+/// Encrypt `plaintext` with the given cipher, prepending the random nonce
+///
+/// The cipher is usually the one derived from the descriptor, see [`WolletDescriptor::cipher()`].
+pub(crate) fn encrypt(mut plaintext: Vec<u8>, cipher: &Aes256GcmSiv) -> Result<Vec<u8>, Error> {
+    let mut nonce_bytes = [0u8; 12];
+    thread_rng().fill(&mut nonce_bytes);
+    let nonce = GenericArray::from_slice(&nonce_bytes);
+
+    cipher.encrypt_in_place(nonce, b"", &mut plaintext)?;
+    let ciphertext = plaintext;
+
+    let mut result = Vec::with_capacity(ciphertext.len() + 12);
+    result.extend(nonce.as_slice());
+    result.extend(&ciphertext);
+
+    Ok(result)
+}
+
+/// Decrypt data encrypted with [`encrypt()`]
+pub(crate) fn decrypt(bytes: &[u8], cipher: &Aes256GcmSiv) -> Result<Vec<u8>, Error> {
+    if bytes.len() < 12 {
+        return Err(Error::CannotDecrypt);
+    }
+    let nonce_bytes = &bytes[..12];
+    let mut ciphertext = bytes[12..].to_vec();
+
+    let nonce = GenericArray::from_slice(nonce_bytes);
+
+    cipher.decrypt_in_place(nonce, b"", &mut ciphertext)?;
+    let plaintext = ciphertext;
+
+    Ok(plaintext)
+}
+
+/// How an update is applied to the wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ApplyMode {
+    /// A new update, persisted
+    Persist,
+
+    /// A new update, not persisted
+    NoPersist,
+
+    /// An update already persisted, replayed while loading the wallet
+    ///
+    /// The checks on the resulting state are done once after all the updates are replayed.
+    Replay,
+}
+
 impl Wollet {
     pub fn apply_update(&mut self, update: Update) -> Result<(), Error> {
-        self.apply_update_inner(update, true)
+        self.apply_update_inner(update, ApplyMode::Persist)
     }
 
     pub fn apply_update_no_persist(&mut self, update: Update) -> Result<(), Error> {
-        self.apply_update_inner(update, false)
+        self.apply_update_inner(update, ApplyMode::NoPersist)
     }
 
-    fn apply_update_inner(&mut self, update: Update, do_persist: bool) -> Result<(), Error> {
+    pub(crate) fn apply_update_inner(
+        &mut self,
+        update: Update,
+        mode: ApplyMode,
+    ) -> Result<(), Error> {
         // TODO should accept &Update
+        let do_persist = mode == ApplyMode::Persist;
 
         if update.wollet_status != 0 {
             // wollet status 0 means the update has been created before saving the status (v0) and we can't check
@@ -158,6 +219,7 @@ impl Wollet {
             }
         }
         let descriptor = self.wollet_descriptor();
+        let verify_proofs = self.config.verify_proofs();
         let store = &mut self.store;
         let Update {
             version: _,
@@ -168,6 +230,7 @@ impl Wollet {
             timestamps,
             scripts_with_blinding_pubkey,
             tip,
+            checkpoints,
         } = update.clone();
 
         let scripts_with_blinding_pubkey =
@@ -181,34 +244,60 @@ impl Wollet {
             });
         }
 
-        store.cache.tip = (tip.height, tip.block_hash());
-        store.cache.unblinded.extend(new_txs.unblinds);
-        store.cache.all_txs.extend(new_txs.txs);
-        store
-            .cache
-            .heights
-            .retain(|k, _| !txid_height_delete.contains(k));
-        store.cache.heights.extend(txid_height_new.clone());
-        store.cache.timestamps.extend(timestamps);
-        store.cache.scripts.extend(
+        let final_before = match self.config.confirmation_policy() {
+            Some(_) if !self.listeners.is_empty() => {
+                Some((store.cache.heights.clone(), store.cache.tip.0))
+            }
+            _ => None,
+        };
+        let mut events = if self.listeners.is_empty() {
+            vec![]
+        } else {
+            crate::event::events(
+                &store.cache.heights,
+                store.cache.tip,
+                &txid_height_new,
+                &txid_height_delete,
+                (tip.height, tip.block_hash()),
+            )
+        };
+
+        let cache = Arc::make_mut(&mut store.cache);
+        cache.tip = (tip.height, tip.block_hash());
+        cache.unblinded.extend(new_txs.unblinds);
+        let downloaded: HashSet<Txid> = new_txs.txs.iter().map(|(txid, _)| *txid).collect();
+        cache.all_txs.extend(new_txs.txs);
+        cache.heights.retain(|k, _| !txid_height_delete.contains(k));
+        cache.heights.extend(txid_height_new.clone());
+        cache.timestamps.extend(timestamps);
+        cache.scripts.extend(
             scripts_with_blinding_pubkey
                 .clone()
                 .into_iter()
                 .map(|(a, b, c, d)| ((a, b), (c, d))),
         );
-        store.cache.paths.extend(
+        cache.paths.extend(
             scripts_with_blinding_pubkey
                 .clone()
                 .into_iter()
                 .map(|(a, b, c, _d)| (c, (a, b))),
         );
+        if verify_proofs {
+            for (txid, _) in txid_height_new.iter() {
+                if let Some(tx) = cache.all_txs.get(txid) {
+                    match tx_proofs_verified(*txid, tx, &cache.all_txs, &cache.unblinded) {
+                        Some(verified) => cache.verified.insert(*txid, verified),
+                        None => cache.verified.remove(txid),
+                    };
+                }
+            }
+        }
         let mut last_used_internal = None;
         let mut last_used_external = None;
         for (txid, _) in txid_height_new {
-            if let Some(tx) = store.cache.all_txs.get(&txid) {
+            if let Some(tx) = cache.all_txs.get(&txid) {
                 for (vout, output) in tx.output.iter().enumerate() {
-                    if !store
-                        .cache
+                    if !cache
                         .unblinded
                         .contains_key(&OutPoint::new(txid, vout as u32))
                     {
@@ -216,7 +305,7 @@ impl Wollet {
                         continue;
                     }
                     if let Some((ext_int, ChildNumber::Normal { index })) =
-                        store.cache.paths.get(&output.script_pubkey)
+                        cache.paths.get(&output.script_pubkey)
                     {
                         match ext_int {
                             Chain::External => match last_used_external {
@@ -235,20 +324,46 @@ impl Wollet {
             }
         }
         if let Some(last_used_external) = last_used_external {
-            store
-                .cache
+            cache
                 .last_unused_external
                 .store(last_used_external + 1, atomic::Ordering::Relaxed);
         }
         if let Some(last_used_internal) = last_used_internal {
-            store
-                .cache
+            cache
                 .last_unused_internal
                 .store(last_used_internal + 1, atomic::Ordering::Relaxed);
         }
 
+        if !self.rescan.is_empty() {
+            self.rescan
+                .retain(|txid| !downloaded.contains(txid) && !txid_height_delete.contains(txid));
+        }
         if do_persist {
             self.persister.push(update)?;
+            self.checksum_pushed_update()?;
+            self.persist_cache_checksum()?;
+        }
+        if !checkpoints.is_empty() {
+            self.sync_checkpoints.extend(checkpoints);
+            if do_persist {
+                self.persist_sync_checkpoints()?;
+            }
+        }
+        if mode != ApplyMode::Replay {
+            self.warn_gap_limit();
+            events.extend(self.update_double_spent());
+        }
+        if let (Some(policy), Some((heights_before, tip_before))) =
+            (self.config.confirmation_policy(), final_before)
+        {
+            let final_events =
+                crate::confirmation::final_events(self, policy, &heights_before, tip_before)?;
+            events.extend(final_events);
+        }
+        for event in events.iter() {
+            for listener in self.listeners.iter() {
+                listener.on_event(event);
+            }
         }
 
         Ok(())
@@ -390,6 +505,9 @@ impl Decodable for EncodableTxOutSecrets {
 }
 
 const UPDATE_MAGIC_BYTES: [u8; 4] = [0x89, 0x61, 0xb8, 0xc8];
+
+/// The latest version of the [`Update`] serialization
+pub const UPDATE_VERSION: u8 = 2;
 impl Encodable for Update {
     fn consensus_encode<W: std::io::Write>(
         &self,
@@ -466,7 +584,7 @@ impl Decodable for Update {
         }
 
         let version = u8::consensus_decode(&mut d)?;
-        if version > 2 {
+        if version > UPDATE_VERSION {
             return Err(elements::encode::Error::ParseFailed("Unsupported version"));
         }
         let wollet_status = if version >= 1 {
@@ -543,6 +661,7 @@ impl Decodable for Update {
             timestamps,
             scripts_with_blinding_pubkey,
             tip,
+            checkpoints: vec![],
         })
     }
 }
@@ -584,6 +703,7 @@ mod test {
             scripts_with_blinding_pubkey: Default::default(),
             tip,
             wollet_status: 1,
+            checkpoints: vec![],
         };
         assert!(update.only_tip());
         update
@@ -638,6 +758,7 @@ mod test {
             scripts_with_blinding_pubkey,
             tip,
             wollet_status: 1,
+            checkpoints: vec![],
         };
 
         let mut vec = vec![];
@@ -1,18 +1,9 @@
 use crate::elements::bitcoin::secp256k1::PublicKey;
 use crate::elements::hex::{FromHex, ToHex};
 use crate::error::Error;
-use crate::secp256k1;
-use rand::thread_rng;
 use serde::Deserialize;
 
-/// The secp256k1 context, created once and reused across the crate since creating it is expensive.
-pub static EC: once_cell::sync::Lazy<secp256k1::Secp256k1<secp256k1::All>> =
-    once_cell::sync::Lazy::new(|| {
-        let mut ctx = secp256k1::Secp256k1::new();
-        let mut rng = thread_rng();
-        ctx.randomize(&mut rng);
-        ctx
-    });
+pub use lwk_common::EC;
 
 /// Deserializes a hex string to a `Vec<u8>`.
 pub fn serde_from_hex<'de, D>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error>
@@ -1,49 +1,144 @@
 use crate::bitcoin::bip32::Fingerprint;
-use crate::clients::LastUnused;
+use crate::clients::{try_unblind, LastUnused, ScriptCheckpoint};
 use crate::config::{Config, ElementsNetwork};
 use crate::descriptor::Chain;
 use crate::elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
 use crate::elements::pset::PartiallySignedTransaction;
-use crate::elements::secp256k1_zkp::ZERO_TWEAK;
-use crate::elements::{AssetId, BlockHash, OutPoint, Script, Transaction, TxOutSecrets, Txid};
+use crate::elements::schnorr::SchnorrSig;
+use crate::elements::secp256k1_zkp::{Generator, PedersenCommitment, ZERO_TWEAK};
+use crate::elements::sighash::SighashCache;
+use crate::elements::{
+    AssetId, BlockHash, EcdsaSighashType, OutPoint, SchnorrSighashType, Script, Transaction, TxOut,
+    TxOutSecrets, Txid,
+};
 use crate::error::Error;
-use crate::hashes::Hash;
+use crate::event::WolletListener;
+use crate::hashes::{sha256, Hash, HashEngine};
+use crate::labels::{Label, LabelType, Labels, LABELS_KEY};
 use crate::model::{
-    AddressResult, BitcoinAddressResult, ExternalUtxo, IssuanceDetails, WalletTx, WalletTxOut,
+    AddressResult, Balances, BitcoinAddressResult, ExternalUtxo, FinalizeFailure, FinalizeReport,
+    InputSighash, IssuanceDetails, SighashKind, TxFilter, UtxoFilter, WalletTx, WalletTxOut,
+    WolletStats,
 };
 use crate::persister::PersistError;
+use crate::secp256k1::{ecdsa, Message, Parity};
 use crate::store::{Height, ScriptBatch, Store, Timestamp, BATCH_SIZE};
 use crate::tx_builder::{extract_issuances, WolletTxBuilder};
+use crate::update::ApplyMode;
 use crate::util::EC;
-use crate::{BlindingPublicKey, FsPersister, NoPersist, Persister, Update, WolletDescriptor};
-use elements::bitcoin::bip32::ChildNumber;
+use crate::{
+    BlindingPublicKey, FsPersister, MemoryPersister, NoPersist, Persister, Update, WolletDescriptor,
+};
+use elements::bitcoin::bip32::{ChildNumber, KeySource};
 use elements::{bitcoin, Address, AddressParams};
-use elements_miniscript::psbt::PsbtExt;
+use elements_miniscript::elementssig_to_rawsig;
+use elements_miniscript::psbt::{PsbtExt, PsbtSighashMsg, SighashError};
 use elements_miniscript::{BtcDescriptor, ForEachKey};
 use elements_miniscript::{
     ConfidentialDescriptor, DefiniteDescriptorKey, Descriptor, DescriptorPublicKey,
 };
 use fxhash::FxHasher;
-use lwk_common::{burn_script, pset_balance, pset_issuances, pset_signatures, PsetDetails};
+use lwk_common::{
+    burn_script, pset_balance, pset_issuances, pset_signatures, resolve_key_source, PsetDetails,
+};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::hash::Hasher;
 use std::path::Path;
 use std::sync::{atomic, Arc};
 
+/// Key used to persist the index marked as used with [`Wollet::mark_used()`]
+const MARKED_USED_KEY: &str = "marked_used";
+const SYNC_CHECKPOINTS_KEY: &str = "sync_checkpoints";
+/// Key used to persist the [`Wollet::status()`] after the last persisted update
+const CACHE_CHECKSUM_KEY: &str = "cache_checksum";
+
 /// A watch-only wallet defined by a CT descriptor.
 pub struct Wollet {
     pub(crate) config: Config,
     pub(crate) store: Store,
     pub(crate) persister: Arc<dyn Persister + Send + Sync>,
     descriptor: WolletDescriptor,
+    labels: Labels,
+    /// Highest external index explicitly marked as used with [`Wollet::mark_used()`]
+    marked_used: Option<u32>,
+    /// Last known status and history of the scripts, see [`ScriptCheckpoint`]
+    pub(crate) sync_checkpoints: HashMap<Script, ScriptCheckpoint>,
+    /// Transactions to download again in the next scan, see [`Wollet::rescan_from()`]
+    pub(crate) rescan: HashSet<Txid>,
+    /// Transactions double spent by another wallet transaction, see [`Wollet::double_spent()`]
+    pub(crate) double_spent: HashMap<Txid, Txid>,
+    /// Notified when updates are applied, see [`Wollet::add_listener()`]
+    pub(crate) listeners: Vec<Arc<dyn WolletListener + Send + Sync>>,
+    /// The fee rate estimate in sats/kvb, see [`Wollet::set_fee_estimate()`]
+    pub(crate) fee_estimate: Option<f32>,
+    /// The checksum of the updates in the persister
+    updates_checksum: UpdatesChecksum,
     // cached value
     max_weight_to_satisfy: usize,
 }
 
+/// Whether the cache can't be loaded because the persisted updates are not the ones written
+fn is_corrupted_cache(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::CacheChecksumMismatch
+            | Error::PersistError(PersistError::Encoding(_) | PersistError::Corrupted(_))
+    )
+}
+
+/// A checksum of the content of the persisted updates, chaining the hashes of their serialization
+#[derive(Debug, Clone)]
+struct UpdatesChecksum {
+    /// The number of persisted updates
+    len: usize,
+
+    /// The checksum of the persisted updates but the last one, which persisters may overwrite
+    before_last: sha256::Hash,
+
+    /// The checksum of all the persisted updates
+    checksum: sha256::Hash,
+}
+
+impl UpdatesChecksum {
+    fn new() -> Self {
+        Self {
+            len: 0,
+            before_last: sha256::Hash::all_zeros(),
+            checksum: sha256::Hash::all_zeros(),
+        }
+    }
+
+    fn push(&mut self, update: &Update) -> Result<(), Error> {
+        self.before_last = self.checksum;
+        self.checksum = Self::chain(&self.checksum, update)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn replace_last(&mut self, update: &Update) -> Result<(), Error> {
+        self.checksum = Self::chain(&self.before_last, update)?;
+        Ok(())
+    }
+
+    fn chain(checksum: &sha256::Hash, update: &Update) -> Result<sha256::Hash, Error> {
+        let mut engine = sha256::Hash::engine();
+        engine.input(checksum.as_byte_array());
+        engine.input(&update.serialize()?);
+        Ok(sha256::Hash::from_engine(engine))
+    }
+}
+
 /// A coincise state of the wallet, in particular having only transactions ids instead of full
 /// transactions and missing other things not strictly needed for a scan.
 /// By using this instead of a borrow of the wallet we can release locks
+///
+/// It can be serialized, so that the scan can happen on a different machine than the wallet:
+/// the online machine scans with the state received and returns the resulting [`Update`], which
+/// is applied with [`Wollet::apply_update()`] by the (possibly offline) wallet.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "ConciseStateData", into = "ConciseStateData")]
 pub struct WolletConciseState {
     wollet_status: u64,
     descriptor: WolletDescriptor,
@@ -51,8 +146,91 @@ pub struct WolletConciseState {
     paths: HashMap<Script, (Chain, ChildNumber)>,
     scripts: HashMap<(Chain, ChildNumber), (Script, BlindingPublicKey)>,
     heights: HashMap<Txid, Option<Height>>,
+    timestamps: HashMap<Height, Timestamp>,
     tip: (Height, BlockHash),
     last_unused: LastUnused,
+    sync_checkpoints: HashMap<Script, ScriptCheckpoint>,
+    verify_proofs: bool,
+    birthday: Option<Height>,
+    gap_limit: u32,
+    memory_budget: Option<usize>,
+}
+
+/// The serialized form of [`WolletConciseState`]
+#[derive(Serialize, Deserialize)]
+struct ConciseStateData {
+    wollet_status: u64,
+    descriptor: String,
+    txs: Vec<Txid>,
+    scripts: Vec<(Chain, ChildNumber, Script, BlindingPublicKey)>,
+    heights: Vec<(Txid, Option<Height>)>,
+    #[serde(default)]
+    timestamps: Vec<(Height, Timestamp)>,
+    tip: (Height, BlockHash),
+    last_unused: (u32, u32),
+    sync_checkpoints: Vec<(Script, ScriptCheckpoint)>,
+    verify_proofs: bool,
+    birthday: Option<Height>,
+    gap_limit: u32,
+    memory_budget: Option<usize>,
+}
+
+impl From<WolletConciseState> for ConciseStateData {
+    fn from(state: WolletConciseState) -> Self {
+        Self {
+            wollet_status: state.wollet_status,
+            descriptor: state.descriptor.to_string(),
+            txs: state.txs.into_iter().collect(),
+            scripts: state
+                .scripts
+                .into_iter()
+                .map(|((chain, child), (script, pubkey))| (chain, child, script, pubkey))
+                .collect(),
+            heights: state.heights.into_iter().collect(),
+            timestamps: state.timestamps.into_iter().collect(),
+            tip: state.tip,
+            last_unused: (state.last_unused.external, state.last_unused.internal),
+            sync_checkpoints: state.sync_checkpoints.into_iter().collect(),
+            verify_proofs: state.verify_proofs,
+            birthday: state.birthday,
+            gap_limit: state.gap_limit,
+            memory_budget: state.memory_budget,
+        }
+    }
+}
+
+impl TryFrom<ConciseStateData> for WolletConciseState {
+    type Error = Error;
+
+    fn try_from(data: ConciseStateData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            wollet_status: data.wollet_status,
+            descriptor: data.descriptor.parse()?,
+            txs: data.txs.into_iter().collect(),
+            paths: data
+                .scripts
+                .iter()
+                .map(|(chain, child, script, _)| (script.clone(), (*chain, *child)))
+                .collect(),
+            scripts: data
+                .scripts
+                .into_iter()
+                .map(|(chain, child, script, pubkey)| ((chain, child), (script, pubkey)))
+                .collect(),
+            heights: data.heights.into_iter().collect(),
+            timestamps: data.timestamps.into_iter().collect(),
+            tip: data.tip,
+            last_unused: LastUnused {
+                external: data.last_unused.0,
+                internal: data.last_unused.1,
+            },
+            sync_checkpoints: data.sync_checkpoints.into_iter().collect(),
+            verify_proofs: data.verify_proofs,
+            birthday: data.birthday,
+            gap_limit: data.gap_limit.max(1),
+            memory_budget: data.memory_budget,
+        })
+    }
 }
 
 pub trait WolletState {
@@ -74,6 +252,47 @@ pub trait WolletState {
     fn last_unused(&self) -> LastUnused; // TODO change to &LastUnused when possible
     fn descriptor(&self) -> WolletDescriptor;
     fn wollet_status(&self) -> u64;
+
+    /// Whether the scan should download the previous transactions to verify the proofs
+    fn verify_proofs(&self) -> bool {
+        false
+    }
+
+    /// The height before which transactions are ignored by the scan
+    fn birthday(&self) -> Option<Height> {
+        None
+    }
+
+    /// The last known status and history of the given script
+    fn checkpoint(&self, _script: &Script) -> Option<ScriptCheckpoint> {
+        None
+    }
+
+    /// The timestamp of the block at `height`, if known
+    ///
+    /// The scan downloads the headers of the blocks containing wallet transactions whose
+    /// timestamp is not known.
+    fn timestamp(&self, height: Height) -> Option<Timestamp>;
+
+    /// The number of consecutive unused scripts after which the scan stops
+    fn gap_limit(&self) -> u32 {
+        BATCH_SIZE
+    }
+
+    /// The memory budget for the transactions downloaded during a scan
+    fn memory_budget(&self) -> Option<usize> {
+        None
+    }
+
+    /// Known blocks that the headers downloaded by the scan must match
+    fn block_checkpoints(&self) -> Vec<(Height, BlockHash)> {
+        vec![]
+    }
+
+    /// The scripts that must sign the headers downloaded by the scan, not verified if empty
+    fn block_signers(&self) -> Vec<Script> {
+        vec![]
+    }
 }
 
 impl WolletState for WolletConciseState {
@@ -148,6 +367,30 @@ impl WolletState for WolletConciseState {
     fn wollet_status(&self) -> u64 {
         self.wollet_status
     }
+
+    fn verify_proofs(&self) -> bool {
+        self.verify_proofs
+    }
+
+    fn birthday(&self) -> Option<Height> {
+        self.birthday
+    }
+
+    fn checkpoint(&self, script: &Script) -> Option<ScriptCheckpoint> {
+        self.sync_checkpoints.get(script).cloned()
+    }
+
+    fn timestamp(&self, height: Height) -> Option<Timestamp> {
+        self.timestamps.get(&height).cloned()
+    }
+
+    fn gap_limit(&self) -> u32 {
+        self.gap_limit
+    }
+
+    fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
 }
 
 impl std::fmt::Debug for Wollet {
@@ -183,7 +426,13 @@ impl WolletState for Wollet {
     }
 
     fn txs(&self) -> HashSet<Txid> {
-        self.store.cache.all_txs.keys().cloned().collect()
+        self.store
+            .cache
+            .all_txs
+            .keys()
+            .filter(|txid| !self.rescan.contains(*txid))
+            .cloned()
+            .collect()
     }
 
     fn tip(&self) -> (Height, BlockHash) {
@@ -213,6 +462,38 @@ impl WolletState for Wollet {
     fn wollet_status(&self) -> u64 {
         self.status()
     }
+
+    fn verify_proofs(&self) -> bool {
+        self.config.verify_proofs()
+    }
+
+    fn birthday(&self) -> Option<Height> {
+        self.config.birthday()
+    }
+
+    fn checkpoint(&self, script: &Script) -> Option<ScriptCheckpoint> {
+        self.sync_checkpoints.get(script).cloned()
+    }
+
+    fn timestamp(&self, height: Height) -> Option<Timestamp> {
+        self.store.cache.timestamps.get(&height).cloned()
+    }
+
+    fn gap_limit(&self) -> u32 {
+        self.config.gap_limit()
+    }
+
+    fn memory_budget(&self) -> Option<usize> {
+        self.config.memory_budget()
+    }
+
+    fn block_checkpoints(&self) -> Vec<(Height, BlockHash)> {
+        self.network().checkpoints()
+    }
+
+    fn block_signers(&self) -> Vec<Script> {
+        self.config.block_signers().to_vec()
+    }
 }
 
 impl std::hash::Hash for Wollet {
@@ -230,28 +511,117 @@ impl Wollet {
         persister: Arc<dyn Persister + Send + Sync>,
         descriptor: WolletDescriptor,
     ) -> Result<Self, Error> {
-        let config = Config::new(network)?;
+        Self::with_config(Config::new(network)?, persister, descriptor)
+    }
 
+    /// Create a new wallet with the given network parameters, see [`Config`]
+    ///
+    /// If the persisted updates can't be decoded or don't match their checksum they are removed,
+    /// and the next scan downloads the data again. Other errors of the persister are returned.
+    pub fn with_config(
+        config: Config,
+        persister: Arc<dyn Persister + Send + Sync>,
+        descriptor: WolletDescriptor,
+    ) -> Result<Self, Error> {
         let store = Store::default();
         let max_weight_to_satisfy = descriptor
             .definite_descriptor(Chain::External, 0)?
             .max_weight_to_satisfy()?;
+        let mut labels = Labels::default();
+        if let Some(bytes) = persister.get_value(LABELS_KEY)? {
+            let jsonl = String::from_utf8(bytes).map_err(|e| Error::Generic(e.to_string()))?;
+            labels.import_bip329(&jsonl)?;
+        }
+        let marked_used = match persister.get_value(MARKED_USED_KEY)? {
+            Some(bytes) => {
+                Some(u32::from_le_bytes(bytes.try_into().map_err(|_| {
+                    Error::Generic("Invalid marked used index".to_string())
+                })?))
+            }
+            None => None,
+        };
+        let sync_checkpoints = match persister.get_value(SYNC_CHECKPOINTS_KEY)? {
+            Some(bytes) => {
+                let checkpoints: Vec<(Script, ScriptCheckpoint)> = serde_json::from_slice(&bytes)?;
+                checkpoints.into_iter().collect()
+            }
+            None => HashMap::new(),
+        };
         let mut wollet = Wollet {
             store,
             config,
             descriptor,
             persister,
+            labels,
+            marked_used,
+            sync_checkpoints,
+            rescan: HashSet::new(),
+            double_spent: HashMap::new(),
+            listeners: vec![],
+            fee_estimate: None,
+            updates_checksum: UpdatesChecksum::new(),
             max_weight_to_satisfy,
         };
 
+        match wollet.load_updates() {
+            Ok(()) => {}
+            // the cache can be downloaded again, unlike the values like the labels
+            Err(e) if is_corrupted_cache(&e) && wollet.persister.clear().is_ok() => {
+                log::warn!(
+                    "cannot load the wallet cache, it will be rebuilt by the next scan: {e}"
+                );
+                wollet.store = Store::default();
+                wollet.rescan.clear();
+                wollet.double_spent.clear();
+                wollet.updates_checksum = UpdatesChecksum::new();
+                wollet.clear_sync_checkpoints()?;
+                wollet.persist_cache_checksum()?;
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(wollet)
+    }
+
+    /// Apply the persisted updates, checking their checksum
+    fn load_updates(&mut self) -> Result<(), Error> {
         for i in 0.. {
-            match wollet.persister.get(i)? {
-                Some(update) => wollet.apply_update_no_persist(update)?,
+            match self.persister.get(i)? {
+                Some(update) => {
+                    self.updates_checksum.push(&update)?;
+                    self.apply_update_inner(update, ApplyMode::Replay)?;
+                }
                 None => break,
             }
         }
+        self.warn_gap_limit();
+        self.update_double_spent();
+        match self.persister.get_value(CACHE_CHECKSUM_KEY)? {
+            Some(bytes) if bytes[..] != self.updates_checksum.checksum[..] => {
+                Err(Error::CacheChecksumMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
 
-        Ok(wollet)
+    /// Add to the checksum the update just pushed, which the persister may have written over the
+    /// last one
+    pub(crate) fn checksum_pushed_update(&mut self) -> Result<(), Error> {
+        let checksum = &mut self.updates_checksum;
+        if let Some(update) = self.persister.get(checksum.len)? {
+            checksum.push(&update)?;
+        } else if let Some(last) = checksum.len.checked_sub(1) {
+            if let Some(update) = self.persister.get(last)? {
+                checksum.replace_last(&update)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn persist_cache_checksum(&self) -> Result<(), Error> {
+        self.persister
+            .put_value(CACHE_CHECKSUM_KEY, &self.updates_checksum.checksum[..])?;
+        Ok(())
     }
 
     /// Max weight to satisfy for inputs belonging to this wallet
@@ -264,15 +634,21 @@ impl Wollet {
         WolletConciseState {
             wollet_status: self.status(),
             descriptor: self.wollet_descriptor(),
-            txs: cache.all_txs.keys().cloned().collect(),
+            txs: WolletState::txs(self),
             paths: cache.paths.clone(),
             scripts: cache.scripts.clone(),
             heights: cache.heights.clone(),
+            timestamps: cache.timestamps.clone(),
             tip: cache.tip,
             last_unused: LastUnused {
                 internal: cache.last_unused_internal.load(atomic::Ordering::Relaxed),
                 external: cache.last_unused_external.load(atomic::Ordering::Relaxed),
             },
+            sync_checkpoints: self.sync_checkpoints.clone(),
+            verify_proofs: self.config.verify_proofs(),
+            birthday: self.config.birthday(),
+            gap_limit: self.config.gap_limit(),
+            memory_budget: self.config.memory_budget(),
         }
     }
 
@@ -297,6 +673,18 @@ impl Wollet {
         Self::new(network, Arc::new(NoPersist {}), descriptor)
     }
 
+    /// Create a new wallet keeping its data in memory, see [`MemoryPersister`]
+    ///
+    /// Useful for ephemeral wallets or environments where writing on the file system is not
+    /// possible. To recreate the wallet without syncing again, create it with [`Wollet::new()`]
+    /// passing the same [`MemoryPersister`].
+    pub fn in_memory(
+        network: ElementsNetwork,
+        descriptor: WolletDescriptor,
+    ) -> Result<Self, Error> {
+        Self::new(network, MemoryPersister::new(), descriptor)
+    }
+
     /// Get the network policy asset
     pub fn policy_asset(&self) -> AssetId {
         self.config.policy_asset()
@@ -312,6 +700,47 @@ impl Wollet {
         self.config.network()
     }
 
+    /// Get the labels of the wallet items
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    /// Set a label, replacing the existing one of the same item, and persist it
+    pub fn set_label(&mut self, label: Label) -> Result<(), Error> {
+        self.labels.insert(label);
+        self.persist_labels()
+    }
+
+    /// Remove the label of the item of type `type_` referenced by `ref_`, and persist the change
+    pub fn remove_label(&mut self, type_: LabelType, ref_: &str) -> Result<Option<Label>, Error> {
+        let removed = self.labels.remove(type_, ref_);
+        self.persist_labels()?;
+        Ok(removed)
+    }
+
+    /// Import labels in the BIP-329 format, see [`Labels::import_bip329()`]
+    pub fn import_labels(&mut self, jsonl: &str) -> Result<usize, Error> {
+        let imported = self.labels.import_bip329(jsonl)?;
+        self.persist_labels()?;
+        Ok(imported)
+    }
+
+    /// Export labels in the BIP-329 format, see [`Labels::export_bip329()`]
+    pub fn export_labels(&self) -> Result<String, Error> {
+        self.labels.export_bip329()
+    }
+
+    fn persist_labels(&self) -> Result<(), Error> {
+        let jsonl = self.labels.export_bip329()?;
+        self.persister.put_value(LABELS_KEY, jsonl.as_bytes())?;
+        Ok(())
+    }
+
+    /// Get the network parameters used by the wallet
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Get a reference of the wallet descriptor
     pub fn descriptor(&self) -> &ConfidentialDescriptor<DescriptorPublicKey> {
         self.descriptor.as_ref()
@@ -346,6 +775,282 @@ impl Wollet {
         Ok(AddressResult::new(address, index))
     }
 
+    /// Get the wallet address at the given `index`
+    ///
+    /// The wallet state is not modified, see [`Wollet::mark_used()`].
+    pub fn address_at(&self, index: u32) -> Result<AddressResult, Error> {
+        self.address(Some(index))
+    }
+
+    /// Get the next wallet address that would be returned by `address(None)`
+    ///
+    /// The wallet state is not modified, so calling this repeatedly returns the same address,
+    /// until the address receives funds or it's marked as used with [`Wollet::mark_used()`].
+    pub fn peek_next_address(&self) -> Result<AddressResult, Error> {
+        self.address(None)
+    }
+
+    /// Mark the external address at the given `index` (and all the previous ones) as used
+    ///
+    /// Subsequent calls to `address(None)` return addresses at higher indexes,
+    /// even if the address didn't receive funds yet. This is persisted.
+    pub fn mark_used(&mut self, index: u32) -> Result<(), Error> {
+        if let Some(marked) = self.marked_used {
+            if index <= marked {
+                return Ok(());
+            }
+        }
+        self.persister
+            .put_value(MARKED_USED_KEY, &index.to_le_bytes())?;
+        self.marked_used = Some(index);
+        Ok(())
+    }
+
+    /// Remove the sync checkpoints, so that the next scan downloads the history of every script
+    ///
+    /// With backends supporting script statuses, scans download only the history of scripts
+    /// whose status changed since the last scan. This forces a full rescan, for instance if the
+    /// server has been changed or it's suspected to have missed some notifications.
+    pub fn clear_sync_checkpoints(&mut self) -> Result<(), Error> {
+        self.sync_checkpoints.clear();
+        self.persist_sync_checkpoints()
+    }
+
+    /// Notify `listener` of the [`crate::WolletEvent`]s caused by the updates applied from now on
+    ///
+    /// Applications can react to new transactions, confirmations and new blocks without comparing
+    /// the wallet state before and after every sync. A [`std::sync::mpsc::Sender`] can be used
+    /// to receive the events on a channel.
+    pub fn add_listener(&mut self, listener: Arc<dyn WolletListener + Send + Sync>) {
+        self.listeners.push(listener);
+    }
+
+    /// Download and unblind again in the next scan the wallet transactions confirmed at or after
+    /// `height`, and the unconfirmed ones
+    ///
+    /// Useful to recover from a corrupted cache, or to unblind again outputs after importing
+    /// blinding data, without deleting the whole wallet data. The cached transactions are used
+    /// until replaced by the next scan, which also removes the ones not existing anymore. The sync
+    /// checkpoints are cleared, see [`Wollet::clear_sync_checkpoints()`].
+    ///
+    /// Returns the number of transactions to download again.
+    pub fn rescan_from(&mut self, height: Height) -> Result<usize, Error> {
+        self.rescan = self
+            .store
+            .cache
+            .heights
+            .iter()
+            .filter(|(_, h)| h.map_or(true, |h| h >= height))
+            .map(|(txid, _)| *txid)
+            .collect();
+        self.clear_sync_checkpoints()?;
+        Ok(self.rescan.len())
+    }
+
+    /// Insert a transaction known out of band, for instance received from a counterparty before
+    /// it's broadcast, so that balances and coin selection account for it immediately
+    ///
+    /// The transaction is considered unconfirmed. The outputs paying the wallet scripts are
+    /// unblinded with the wallet blinding keys, `unblinded` provides the secrets of the outputs,
+    /// by index, which cannot be unblinded otherwise; they must match the output commitments.
+    ///
+    /// The transaction is not persisted: the next scans confirm it, or remove it if the
+    /// blockchain backend doesn't know it.
+    pub fn insert_tx(
+        &mut self,
+        tx: Transaction,
+        unblinded: Vec<(u32, TxOutSecrets)>,
+    ) -> Result<(), Error> {
+        let txid = tx.txid();
+        let descriptor = self.wollet_descriptor();
+        let unblinded: HashMap<u32, TxOutSecrets> = unblinded.into_iter().collect();
+        // scripts given out but not yet synced are not in the cache
+        let mut derived = None;
+        let mut new_scripts = vec![];
+        let mut new_unblinded = vec![];
+        for (vout, output) in tx.output.iter().enumerate() {
+            let vout = vout as u32;
+            let script = &output.script_pubkey;
+            let path = match self.store.cache.paths.get(script) {
+                Some(path) => Some(*path),
+                None => {
+                    let derived = match derived.as_mut() {
+                        Some(derived) => derived,
+                        None => derived.insert(self.derive_up_to_gap()?),
+                    };
+                    derived
+                        .remove(script)
+                        .map(|(chain, child, blinding_pubkey)| {
+                            new_scripts.push((chain, child, script.clone(), blinding_pubkey));
+                            (chain, child)
+                        })
+                }
+            };
+            let secrets = match unblinded.get(&vout) {
+                Some(secrets) => {
+                    if path.is_none() || !secrets_match(output, secrets) {
+                        return Err(Error::InvalidUnblindedOutput(vout));
+                    }
+                    *secrets
+                }
+                None if path.is_some() => match try_unblind(output.clone(), &descriptor) {
+                    Ok(secrets) => secrets,
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            new_unblinded.push((OutPoint::new(txid, vout), secrets, path));
+        }
+        let cache = &self.store.cache;
+        let spends_wallet = tx
+            .input
+            .iter()
+            .any(|i| cache.unblinded.contains_key(&i.previous_output));
+        if new_unblinded.is_empty() && !spends_wallet {
+            return Err(Error::TxNotInvolvingWallet(txid));
+        }
+        let new_tx = !cache.heights.contains_key(&txid);
+
+        let verify_proofs = self.config.verify_proofs();
+        let cache = Arc::make_mut(&mut self.store.cache);
+        for (chain, child, script, blinding_pubkey) in new_scripts {
+            cache.paths.insert(script.clone(), (chain, child));
+            cache
+                .scripts
+                .insert((chain, child), (script, blinding_pubkey));
+        }
+        for (outpoint, secrets, path) in new_unblinded {
+            cache.unblinded.insert(outpoint, secrets);
+            if let Some((chain, ChildNumber::Normal { index })) = path {
+                let last_unused = match chain {
+                    Chain::External => &cache.last_unused_external,
+                    Chain::Internal => &cache.last_unused_internal,
+                };
+                last_unused.fetch_max(index + 1, atomic::Ordering::Relaxed);
+            }
+        }
+        if verify_proofs {
+            match tx_proofs_verified(txid, &tx, &cache.all_txs, &cache.unblinded) {
+                Some(verified) => cache.verified.insert(txid, verified),
+                None => cache.verified.remove(&txid),
+            };
+        }
+        cache.all_txs.insert(txid, tx);
+        cache.heights.entry(txid).or_insert(None);
+
+        let mut events = vec![];
+        if new_tx {
+            events.push(crate::WolletEvent::NewTransaction { txid, height: None });
+        }
+        events.extend(self.update_double_spent());
+        for event in events.iter() {
+            for listener in self.listeners.iter() {
+                listener.on_event(event);
+            }
+        }
+        Ok(())
+    }
+
+    /// The wallet transactions spending an output also spent by another wallet transaction,
+    /// mapped to the transaction which is expected to be included in the blockchain
+    ///
+    /// A confirmed transaction wins over an unconfirmed one, between unconfirmed transactions the
+    /// one paying the highest fee wins, as it's expected to replace the other. The losing
+    /// transactions are reported until the scans remove them, listeners receive a
+    /// [`crate::WolletEvent::DoubleSpendDetected`] when a double spend is found.
+    pub fn double_spent(&self) -> &HashMap<Txid, Txid> {
+        &self.double_spent
+    }
+
+    /// Update the double spent transactions, returning the events of the new ones
+    pub(crate) fn update_double_spent(&mut self) -> Vec<crate::WolletEvent> {
+        let double_spent = self.find_double_spends();
+        let mut events = vec![];
+        for (losing, winning) in double_spent.iter() {
+            if self.double_spent.get(losing) != Some(winning) {
+                log::warn!("transaction {losing} is double spent by {winning}");
+                events.push(crate::WolletEvent::DoubleSpendDetected {
+                    losing: *losing,
+                    winning: *winning,
+                });
+            }
+        }
+        self.double_spent = double_spent;
+        events
+    }
+
+    /// Find the double spends among the wallet transactions, see [`Wollet::double_spent()`]
+    fn find_double_spends(&self) -> HashMap<Txid, Txid> {
+        let cache = &self.store.cache;
+        let policy_asset = self.policy_asset();
+        let mut spending: HashMap<OutPoint, Txid> = HashMap::new();
+        let mut result = HashMap::new();
+        let mut txids: Vec<_> = cache.heights.keys().collect();
+        txids.sort(); // deterministic results with multiple conflicts
+        for txid in txids {
+            let Some(tx) = cache.all_txs.get(txid) else {
+                continue;
+            };
+            for input in tx
+                .input
+                .iter()
+                .filter(|i| !i.is_pegin() && !i.is_coinbase())
+            {
+                let Some(other) = spending.insert(input.previous_output, *txid) else {
+                    continue;
+                };
+                if other == *txid {
+                    continue;
+                }
+                let key = |t: &Txid| {
+                    let confirmed = cache.heights.get(t).cloned().flatten().is_some();
+                    let fee = cache.all_txs.get(t).map_or(0, |tx| tx.fee_in(policy_asset));
+                    (confirmed, fee)
+                };
+                let (winning, losing) = if key(&other) >= key(txid) {
+                    (other, *txid)
+                } else {
+                    (*txid, other)
+                };
+                spending.insert(input.previous_output, winning);
+                result.insert(losing, winning);
+            }
+        }
+        result
+    }
+
+    /// Derive the scripts not in the cache up to the gap limit after the last unused index
+    fn derive_up_to_gap(
+        &self,
+    ) -> Result<HashMap<Script, (Chain, ChildNumber, BlindingPublicKey)>, Error> {
+        let mut derived = HashMap::new();
+        let last_unused = self.last_unused();
+        let chains = if self.descriptor().descriptor.is_multipath() {
+            vec![Chain::External, Chain::Internal]
+        } else {
+            vec![Chain::External]
+        };
+        for chain in chains {
+            let descriptor = self.descriptor.inner_descriptor_if_available(chain);
+            for index in 0..last_unused[chain] + self.config.gap_limit() {
+                let child = ChildNumber::from_normal_idx(index)?;
+                let (script, blinding_pubkey, cached) =
+                    self.get_or_derive(chain, child, descriptor.as_ref())?;
+                if !cached {
+                    derived.insert(script, (chain, child, blinding_pubkey));
+                }
+            }
+        }
+        Ok(derived)
+    }
+
+    pub(crate) fn persist_sync_checkpoints(&self) -> Result<(), Error> {
+        let checkpoints: Vec<_> = self.sync_checkpoints.iter().collect();
+        let bytes = serde_json::to_vec(&checkpoints)?;
+        self.persister.put_value(SYNC_CHECKPOINTS_KEY, &bytes)?;
+        Ok(())
+    }
+
     /// Get a wallet pegin address
     ///
     /// A pegin address is a bitcoin address, funds sent to this address are
@@ -374,14 +1079,69 @@ impl Wollet {
     fn unwrap_or_last_unused(&self, index: Option<u32>) -> u32 {
         match index {
             Some(i) => i,
-            None => self
+            None => {
+                let last_unused = self
+                    .store
+                    .cache
+                    .last_unused_external
+                    .load(atomic::Ordering::Relaxed);
+                match self.marked_used {
+                    Some(marked) => last_unused.max(marked + 1),
+                    None => last_unused,
+                }
+            }
+        }
+    }
+
+    /// The next index to be used for the given chain
+    ///
+    /// For non multipath descriptors both chains derive the same addresses,
+    /// but the indexes are tracked separately.
+    pub fn last_unused_index(&self, chain: Chain) -> u32 {
+        match chain {
+            Chain::External => self.unwrap_or_last_unused(None),
+            Chain::Internal => self
                 .store
                 .cache
-                .last_unused_external
+                .last_unused_internal
                 .load(atomic::Ordering::Relaxed),
         }
     }
 
+    /// Whether some funds may lie beyond the configured gap limit
+    ///
+    /// Returns the largest run of unused scripts found between used scripts of the same chain,
+    /// counting the unused scripts before the first used one, if it's at least half of
+    /// [`Config::gap_limit()`]. In this case the wallet usage is sparse and a scan with a bigger
+    /// gap limit (see [`Config::with_gap_limit()`]) may find more transactions.
+    pub fn gap_limit_warning(&self) -> Option<u32> {
+        let cache = &self.store.cache;
+        let mut used: HashMap<Chain, BTreeSet<u32>> = HashMap::new();
+        for txid in cache.heights.keys() {
+            if let Some(tx) = cache.all_txs.get(txid) {
+                for output in tx.output.iter() {
+                    if let Some((chain, ChildNumber::Normal { index })) =
+                        cache.paths.get(&output.script_pubkey)
+                    {
+                        used.entry(*chain).or_default().insert(*index);
+                    }
+                }
+            }
+        }
+        let largest = used.values().map(largest_gap).max()?;
+        (largest > 0 && largest * 2 >= self.config.gap_limit()).then_some(largest)
+    }
+
+    /// Log a warning if [`Wollet::gap_limit_warning()`] returns a gap
+    pub(crate) fn warn_gap_limit(&self) {
+        if let Some(gap) = self.gap_limit_warning() {
+            log::warn!(
+                "found {gap} consecutive unused scripts, with a gap limit of {} some funds may not be found",
+                self.config.gap_limit()
+            );
+        }
+    }
+
     /// Get a wallet change address
     ///
     /// If a specific descriptor is given for change addresses  it's used to derive this address
@@ -390,14 +1150,7 @@ impl Wollet {
     /// If Some return the address at the given index,
     /// otherwise the last unused address.
     pub fn change(&self, index: Option<u32>) -> Result<AddressResult, Error> {
-        let index = match index {
-            Some(i) => i,
-            None => self
-                .store
-                .cache
-                .last_unused_internal
-                .load(atomic::Ordering::Relaxed),
-        };
+        let index = index.unwrap_or_else(|| self.last_unused_index(Chain::Internal));
 
         let address = self
             .descriptor
@@ -446,7 +1199,7 @@ impl Wollet {
                         let address = Address::from_script(
                             &output.script_pubkey,
                             blinding_pubkey,
-                            self.network().address_params(),
+                            self.config.address_params(),
                         )?;
                         return Some(WalletTxOut {
                             outpoint,
@@ -470,10 +1223,50 @@ impl Wollet {
     /// Get the wallet UTXOs
     pub fn utxos(&self) -> Result<Vec<WalletTxOut>, Error> {
         let mut utxos = self.utxos_inner()?;
-        utxos.sort_by(|a, b| b.unblinded.value.cmp(&a.unblinded.value));
+        utxos.sort_by(|a, b| {
+            b.unblinded
+                .value
+                .cmp(&a.unblinded.value)
+                .then_with(|| a.outpoint.cmp(&b.outpoint))
+        });
         Ok(utxos)
     }
 
+    /// Get the wallet UTXOs matching the given filter
+    ///
+    /// UTXOs are sorted by value, descending, as in [`Wollet::utxos()`].
+    pub fn utxos_with(&self, filter: &UtxoFilter) -> Result<Vec<WalletTxOut>, Error> {
+        let spent_by_confirmed: HashSet<OutPoint> = if filter.excludes_spent_by_unconfirmed() {
+            HashSet::new()
+        } else {
+            self.store
+                .cache
+                .heights
+                .iter()
+                .filter(|(_, height)| height.is_some())
+                .filter_map(|(txid, _)| self.store.cache.all_txs.get(txid))
+                .flat_map(|tx| tx.input.iter().map(|i| i.previous_output))
+                .collect()
+        };
+        let mut utxos: Vec<_> = self
+            .txos_inner()?
+            .into_iter()
+            .filter(|txo| {
+                !txo.is_spent
+                    || (!filter.excludes_spent_by_unconfirmed()
+                        && !spent_by_confirmed.contains(&txo.outpoint))
+            })
+            .filter(|txo| filter.matches(txo, self.confirmations(txo.height)))
+            .collect();
+        utxos.sort_by(|a, b| {
+            b.unblinded
+                .value
+                .cmp(&a.unblinded.value)
+                .then_with(|| a.outpoint.cmp(&b.outpoint))
+        });
+        Ok(filter.page().apply(utxos.into_iter()).collect())
+    }
+
     /// Get the wallet outputs, including spent ones
     pub fn txos(&self) -> Result<Vec<WalletTxOut>, Error> {
         self.txos_inner()
@@ -527,17 +1320,61 @@ impl Wollet {
         Ok(utxos)
     }
 
-    pub(crate) fn balance_from_utxos(
-        &self,
-        utxos: &[WalletTxOut],
-    ) -> Result<BTreeMap<AssetId, u64>, Error> {
-        let mut r = BTreeMap::new();
-        r.entry(self.policy_asset()).or_insert(0);
-        for u in utxos.iter() {
-            *r.entry(u.unblinded.asset).or_default() += u.unblinded.value;
-        }
-        Ok(r)
-    }
+    /// Get the ids and heights of all the transactions involving the wallet scripts
+    ///
+    /// Differently from [`Wollet::transactions()`], this includes the transactions the wallet
+    /// cannot unblind, for instance if the descriptor has a bare blinding key
+    /// (see [`WolletDescriptor::can_unblind()`]).
+    pub fn txids(&self) -> Vec<(Txid, Option<Height>)> {
+        let mut txids: Vec<_> = self
+            .store
+            .cache
+            .heights
+            .iter()
+            .map(|(txid, height)| (*txid, *height))
+            .collect();
+        txids.sort_by(|a, b| {
+            let height_cmp = b.1.unwrap_or(u32::MAX).cmp(&a.1.unwrap_or(u32::MAX));
+            match height_cmp {
+                Ordering::Equal => b.0.cmp(&a.0),
+                h => h,
+            }
+        });
+        txids
+    }
+
+    /// Get the confidential outputs sent to the wallet scripts that the wallet could not unblind
+    ///
+    /// If the descriptor has a bare blinding key, these are all the confidential outputs
+    /// received by the wallet.
+    pub fn blinded_outputs(&self) -> Vec<(OutPoint, TxOut)> {
+        let mut outputs = vec![];
+        for (txid, tx) in self.store.cache.all_txs.iter() {
+            for (vout, o) in tx.output.iter().enumerate() {
+                let outpoint = OutPoint::new(*txid, vout as u32);
+                if o.value.is_confidential()
+                    && self.store.cache.paths.contains_key(&o.script_pubkey)
+                    && !self.store.cache.unblinded.contains_key(&outpoint)
+                {
+                    outputs.push((outpoint, o.clone()));
+                }
+            }
+        }
+        outputs.sort_by_key(|(outpoint, _)| *outpoint);
+        outputs
+    }
+
+    pub(crate) fn balance_from_utxos(
+        &self,
+        utxos: &[WalletTxOut],
+    ) -> Result<BTreeMap<AssetId, u64>, Error> {
+        let mut r = BTreeMap::new();
+        r.entry(self.policy_asset()).or_insert(0);
+        for u in utxos.iter() {
+            *r.entry(u.unblinded.asset).or_default() += u.unblinded.value;
+        }
+        Ok(r)
+    }
 
     /// Get the wallet balance
     pub fn balance(&self) -> Result<BTreeMap<AssetId, u64>, Error> {
@@ -545,10 +1382,149 @@ impl Wollet {
         self.balance_from_utxos(&utxos)
     }
 
+    /// Get the wallet balance considering only UTXOs with at least `min_conf` confirmations
+    ///
+    /// A UTXO included in the tip block has 1 confirmation, an unconfirmed one has 0,
+    /// thus `balance_with_confirmations(0)` is equivalent to [`Wollet::balance()`].
+    pub fn balance_with_confirmations(
+        &self,
+        min_conf: u32,
+    ) -> Result<BTreeMap<AssetId, u64>, Error> {
+        let utxos: Vec<_> = self
+            .utxos()?
+            .into_iter()
+            .filter(|u| self.confirmations(u.height) >= min_conf)
+            .collect();
+        self.balance_from_utxos(&utxos)
+    }
+
+    /// Get the wallet balance as it was at the given block `height`
+    ///
+    /// Only confirmed transactions are considered: an output is counted if it has been created
+    /// in a block at or before `height` and it has not been spent in a block at or before `height`.
+    pub fn balance_at(&self, height: Height) -> Result<BTreeMap<AssetId, u64>, Error> {
+        let heights = &self.store.cache.heights;
+        let confirmed_at = |txid: &Txid| matches!(heights.get(txid), Some(Some(h)) if *h <= height);
+        let spent: HashSet<OutPoint> = heights
+            .keys()
+            .filter(|txid| confirmed_at(txid))
+            .filter_map(|txid| self.store.cache.all_txs.get(txid))
+            .flat_map(|tx| tx.input.iter().map(|i| i.previous_output))
+            .collect();
+        let utxos: Vec<_> = self
+            .txos_inner()?
+            .into_iter()
+            .filter(|txo| confirmed_at(&txo.outpoint.txid) && !spent.contains(&txo.outpoint))
+            .collect();
+        self.balance_from_utxos(&utxos)
+    }
+
+    /// Get the wallet balance split in confirmed, trusted pending and untrusted pending
+    ///
+    /// An unconfirmed UTXO is trusted if the transaction creating it spends only wallet inputs,
+    /// as it happens for the change of the transactions sent by this wallet.
+    pub fn balances(&self) -> Result<Balances, Error> {
+        let txos = self.txos_map()?;
+        let mut balances = Balances::default();
+        for map in [
+            &mut balances.confirmed,
+            &mut balances.trusted_pending,
+            &mut balances.untrusted_pending,
+        ] {
+            map.insert(self.policy_asset(), 0);
+        }
+        for utxo in txos.values().filter(|txo| !txo.is_spent) {
+            let map = if utxo.height.is_some() {
+                &mut balances.confirmed
+            } else if self.is_trusted(&utxo.outpoint.txid, &txos) {
+                &mut balances.trusted_pending
+            } else {
+                &mut balances.untrusted_pending
+            };
+            *map.entry(utxo.unblinded.asset).or_default() += utxo.unblinded.value;
+        }
+        Ok(balances)
+    }
+
+    /// Whether all the inputs of the transaction are owned by the wallet
+    fn is_trusted(&self, txid: &Txid, txos: &HashMap<OutPoint, WalletTxOut>) -> bool {
+        match self.store.cache.all_txs.get(txid) {
+            Some(tx) => {
+                !tx.input.is_empty()
+                    && tx
+                        .input
+                        .iter()
+                        .all(|i| !i.is_pegin && txos.contains_key(&i.previous_output))
+            }
+            None => false,
+        }
+    }
+
+    /// Get statistics on the wallet on-chain footprint, for instance for diagnostics
+    pub fn stats(&self) -> Result<WolletStats, Error> {
+        let cache = &self.store.cache;
+        let txos = self.txos_map()?;
+        let policy_asset = self.policy_asset();
+        let mut stats = WolletStats::default();
+        for (txid, height) in cache.heights.iter() {
+            let tx = match cache.all_txs.get(txid) {
+                Some(tx) if !tx_balance(*txid, tx, &txos).is_empty() => tx,
+                _ => continue,
+            };
+            stats.txs += 1;
+            match height {
+                Some(height) => {
+                    stats.first_height =
+                        Some(stats.first_height.map_or(*height, |h| h.min(*height)));
+                    stats.last_height = Some(stats.last_height.map_or(*height, |h| h.max(*height)));
+                }
+                None => stats.unconfirmed_txs += 1,
+            }
+            if tx
+                .input
+                .iter()
+                .any(|i| txos.contains_key(&i.previous_output))
+            {
+                stats.fees_paid += tx.fee_in(policy_asset);
+            }
+        }
+        stats.utxos = self.utxos()?.len();
+        stats.used_addresses = txos
+            .values()
+            .map(|txo| &txo.script_pubkey)
+            .collect::<HashSet<_>>()
+            .len();
+        stats.assets = self.balance()?.values().filter(|v| **v > 0).count();
+        stats.cached_txs = cache.all_txs.len();
+        stats.cache_size = cache.all_txs.values().map(|tx| tx.size()).sum();
+        Ok(stats)
+    }
+
+    /// Number of confirmations of something included at `height` according to the current tip
+    pub(crate) fn confirmations(&self, height: Option<Height>) -> u32 {
+        match height {
+            Some(height) => self.store.cache.tip.0.saturating_sub(height) + 1,
+            None => 0,
+        }
+    }
+
     /// Get the wallet transactions
     pub fn transactions(&self) -> Result<Vec<WalletTx>, Error> {
-        let mut txs = vec![];
-        let mut my_txids: Vec<(&Txid, &Option<u32>)> = self.store.cache.heights.iter().collect();
+        self.transactions_with(&TxFilter::default())
+    }
+
+    /// Get the wallet transactions matching the given filter
+    ///
+    /// Transactions are sorted as in [`Wollet::transactions()`], the details of the transactions
+    /// out of the requested page are not computed.
+    pub fn transactions_with(&self, filter: &TxFilter) -> Result<Vec<WalletTx>, Error> {
+        let mut my_txids: Vec<(&Txid, &Option<u32>)> = self
+            .store
+            .cache
+            .heights
+            .iter()
+            .filter(|(_, height)| filter.matches_height(**height))
+            .collect();
         my_txids.sort_by(|a, b| {
             let height_cmp = b.1.unwrap_or(u32::MAX).cmp(&a.1.unwrap_or(u32::MAX));
             match height_cmp {
@@ -558,40 +1534,31 @@ impl Wollet {
         });
 
         let txos = self.txos_map()?;
-        for (txid, height) in my_txids.iter() {
+        let mut matching = vec![];
+        for (txid, height) in my_txids {
             let tx = self
                 .store
                 .cache
                 .all_txs
-                .get(*txid)
+                .get(txid)
                 .ok_or_else(|| Error::Generic(format!("list_tx no tx {}", txid)))?;
 
-            let balance = tx_balance(**txid, tx, &txos);
+            let balance = tx_balance(*txid, tx, &txos);
             if balance.is_empty() {
                 // Transaction has no output or input that the wollet can unblind,
                 // ignore this transaction
                 continue;
             }
-            let fee = tx_fee(tx);
-            let policy_asset = self.policy_asset();
-            let type_ = tx_type(tx, &policy_asset, &balance, fee);
-            let timestamp = height.and_then(|h| self.store.cache.timestamps.get(&h).cloned());
-            let inputs = tx_inputs(tx, &txos);
-            let outputs = tx_outputs(**txid, tx, &txos);
-            txs.push(WalletTx {
-                tx: tx.clone(),
-                txid: **txid,
-                height: **height,
-                balance,
-                fee,
-                type_,
-                timestamp,
-                inputs,
-                outputs,
-            });
+            if filter.matches_balance(&balance) {
+                matching.push((txid, *height, tx, balance));
+            }
         }
 
-        Ok(txs)
+        Ok(filter
+            .page()
+            .apply(matching.into_iter())
+            .map(|(txid, height, tx, balance)| self.wallet_tx(txid, height, tx, balance, &txos))
+            .collect())
     }
 
     /// Get a wallet transaction
@@ -600,31 +1567,45 @@ impl Wollet {
         let tx = self.store.cache.all_txs.get(txid);
         if let (Some(height), Some(tx)) = (height, tx) {
             let txos = self.txos_map()?;
-
             let balance = tx_balance(*txid, tx, &txos);
-            let fee = tx_fee(tx);
-            let policy_asset = self.policy_asset();
-            let type_ = tx_type(tx, &policy_asset, &balance, fee);
-            let timestamp = height.and_then(|h| self.store.cache.timestamps.get(&h).cloned());
-            let inputs = tx_inputs(tx, &txos);
-            let outputs = tx_outputs(*txid, tx, &txos);
-
-            Ok(Some(WalletTx {
-                tx: tx.clone(),
-                txid: *txid,
-                height: *height,
-                balance,
-                fee,
-                type_,
-                timestamp,
-                inputs,
-                outputs,
-            }))
+            Ok(Some(self.wallet_tx(txid, *height, tx, balance, &txos)))
         } else {
             Ok(None)
         }
     }
 
+    fn wallet_tx(
+        &self,
+        txid: &Txid,
+        height: Option<Height>,
+        tx: &Transaction,
+        balance: BTreeMap<AssetId, i64>,
+        txos: &HashMap<OutPoint, WalletTxOut>,
+    ) -> WalletTx {
+        let fee = tx_fee(tx);
+        let policy_asset = self.policy_asset();
+        let type_ = tx_type(tx, &policy_asset, &balance, fee);
+        let timestamp = height.and_then(|h| self.store.cache.timestamps.get(&h).cloned());
+        let inputs = tx_inputs(tx, txos);
+        let outputs = tx_outputs(*txid, tx, txos);
+        let verified = self.store.cache.verified.get(txid).cloned();
+        let incoming = balance.values().any(|v| *v > 0);
+        let zero_conf_risk = self.zero_conf_risk(txid, tx, height, incoming, fee);
+        WalletTx {
+            tx: tx.clone(),
+            txid: *txid,
+            height,
+            balance,
+            fee,
+            type_,
+            timestamp,
+            inputs,
+            outputs,
+            verified,
+            zero_conf_risk,
+        }
+    }
+
     /// Get the wallet (re)issuances
     pub fn issuances(&self) -> Result<Vec<IssuanceDetails>, Error> {
         let mut r = vec![];
@@ -679,16 +1660,64 @@ impl Wollet {
         self.descriptor.definite_descriptor(ext_int, utxo_index)
     }
 
+    /// The definite descriptor of `script_pubkey` derived at the last step of one of the
+    /// `key_sources`, for scripts not in the wallet cache, like the ones beyond the gap limit
+    fn definite_descriptor_from_keys<'a>(
+        &self,
+        script_pubkey: &Script,
+        key_sources: impl Iterator<Item = &'a KeySource>,
+    ) -> Option<Descriptor<DefiniteDescriptorKey>> {
+        for (_, path) in key_sources {
+            let Some(ChildNumber::Normal { index }) = path.as_ref().last() else {
+                continue;
+            };
+            for chain in [Chain::External, Chain::Internal] {
+                if let Ok(desc) = self.descriptor.definite_descriptor(chain, *index) {
+                    if &desc.script_pubkey() == script_pubkey {
+                        return Some(desc);
+                    }
+                }
+            }
+        }
+        None
+    }
+
     /// Add the PSET details with respect to the wallet
+    ///
+    /// The key sources of PSETs created by other software are expressed relative to the master
+    /// keys of the cosigners using the PSET global xpubs, see [`resolve_key_source()`], and they
+    /// are used to find the wallet scripts not yet in the wallet cache.
     pub fn add_details(&self, pset: &mut PartiallySignedTransaction) -> Result<(), Error> {
         let pset_clone = pset.clone();
+        for input in pset.inputs_mut() {
+            input.bip32_derivation = input
+                .bip32_derivation
+                .iter()
+                .map(|(pk, ks)| (*pk, resolve_key_source(&pset_clone, pk, ks.clone())))
+                .collect();
+        }
+        for output in pset.outputs_mut() {
+            output.bip32_derivation = output
+                .bip32_derivation
+                .iter()
+                .map(|(pk, ks)| (*pk, resolve_key_source(&pset_clone, pk, ks.clone())))
+                .collect();
+        }
+
         for (idx, input) in pset_clone.inputs().iter().enumerate() {
             if let Some(txout) = input.witness_utxo.as_ref() {
                 match self.definite_descriptor(&txout.script_pubkey) {
                     Ok(desc) => {
                         pset.update_input_with_descriptor(idx, &desc)?;
                     }
-                    Err(Error::ScriptNotMine) => (),
+                    Err(Error::ScriptNotMine) => {
+                        let keys = input.bip32_derivation.values();
+                        if let Some(desc) =
+                            self.definite_descriptor_from_keys(&txout.script_pubkey, keys)
+                        {
+                            pset.update_input_with_descriptor(idx, &desc)?;
+                        }
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -699,7 +1728,14 @@ impl Wollet {
                 Ok(desc) => {
                     pset.update_output_with_descriptor(idx, &desc)?;
                 }
-                Err(Error::ScriptNotMine) => (),
+                Err(Error::ScriptNotMine) => {
+                    let keys = output.bip32_derivation.values();
+                    if let Some(desc) =
+                        self.definite_descriptor_from_keys(&output.script_pubkey, keys)
+                    {
+                        pset.update_output_with_descriptor(idx, &desc)?;
+                    }
+                }
                 Err(e) => return Err(e),
             }
         }
@@ -725,6 +1761,113 @@ impl Wollet {
         Ok(())
     }
 
+    /// Compute the messages that must be signed for every input of the PSET
+    ///
+    /// Allows to sign with signers not integrated with LWK, for instance HSMs.
+    /// The PSET details with respect to this wallet are added before computing the messages,
+    /// so the wallet inputs have the keys expected to sign them.
+    /// Taproot messages commit to the genesis block hash, which is unknown for regtest networks,
+    /// in that case the all zeros hash is used.
+    pub fn input_sighashes(
+        &self,
+        pset: &PartiallySignedTransaction,
+    ) -> Result<Vec<InputSighash>, Error> {
+        let mut pset = pset.clone();
+        self.add_details(&mut pset)?;
+        let tx = pset.extract_tx()?;
+        let mut cache = SighashCache::new(&tx);
+        let genesis_hash = self.sighash_genesis_hash();
+
+        let mut result = vec![];
+        for (index, input) in pset.inputs().iter().enumerate() {
+            let (message, kind, keys) =
+                match pset.sighash_msg(index, &mut cache, None, genesis_hash)? {
+                    PsbtSighashMsg::EcdsaSighash(sighash) => {
+                        let hash_ty = input
+                            .sighash_type
+                            .and_then(|h| h.ecdsa_hash_ty())
+                            .unwrap_or(EcdsaSighashType::All);
+                        let keys = input
+                            .bip32_derivation
+                            .iter()
+                            .map(|(k, v)| (*k, v.clone()))
+                            .collect();
+                        (sighash.to_byte_array(), SighashKind::Ecdsa(hash_ty), keys)
+                    }
+                    PsbtSighashMsg::TapSighash(sighash) => {
+                        let hash_ty = input
+                            .sighash_type
+                            .and_then(|h| h.schnorr_hash_ty())
+                            .unwrap_or(SchnorrSighashType::Default);
+                        let keys = input
+                            .tap_key_origins
+                            .iter()
+                            .map(|(k, (_, v))| {
+                                let pk = k.public_key(Parity::Even);
+                                (bitcoin::PublicKey::new(pk), v.clone())
+                            })
+                            .collect();
+                        (sighash.to_byte_array(), SighashKind::Schnorr(hash_ty), keys)
+                    }
+                };
+            result.push(InputSighash {
+                index,
+                message: Message::from_digest(message),
+                kind,
+                keys,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Add a signature computed outside LWK to the PSET input at `input_index`
+    ///
+    /// For segwit v0 inputs `signature` is a DER encoded ECDSA signature, for taproot inputs it's a
+    /// 64 bytes Schnorr signature; in both cases it can be followed by the sighash byte.
+    /// The signature is verified against the message returned by [`Wollet::input_sighashes()`].
+    pub fn add_signature(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        input_index: usize,
+        pubkey: bitcoin::PublicKey,
+        signature: &[u8],
+    ) -> Result<(), Error> {
+        let sighashes = self.input_sighashes(pset)?;
+        let sighash = sighashes
+            .get(input_index)
+            .ok_or(SighashError::IndexOutOfBounds(input_index, sighashes.len()))?;
+        match sighash.kind {
+            SighashKind::Ecdsa(hash_ty) => {
+                let sig = match ecdsa::Signature::from_der(signature) {
+                    Ok(sig) => sig,
+                    Err(_) => {
+                        let (last, der) = signature.split_last().ok_or(Error::InvalidSignature)?;
+                        if *last as u32 != hash_ty.as_u32() {
+                            return Err(Error::InvalidSignature);
+                        }
+                        ecdsa::Signature::from_der(der).map_err(|_| Error::InvalidSignature)?
+                    }
+                };
+                EC.verify_ecdsa(&sighash.message, &sig, &pubkey.inner)
+                    .map_err(|_| Error::InvalidSignature)?;
+                pset.inputs_mut()[input_index]
+                    .partial_sigs
+                    .insert(pubkey, elementssig_to_rawsig(&(sig, hash_ty)));
+            }
+            SighashKind::Schnorr(hash_ty) => {
+                let sig = SchnorrSig::from_slice(signature).map_err(|_| Error::InvalidSignature)?;
+                if sig.hash_ty != hash_ty {
+                    return Err(Error::InvalidSignature);
+                }
+                let (xonly, _) = pubkey.inner.x_only_public_key();
+                EC.verify_schnorr(&sig.sig, &sighash.message, &xonly)
+                    .map_err(|_| Error::InvalidSignature)?;
+                pset.inputs_mut()[input_index].tap_key_sig = Some(sig);
+            }
+        }
+        Ok(())
+    }
+
     /// Get the signers' fingerprints involved in this descriptor
     pub fn signers(&self) -> Vec<Fingerprint> {
         let mut signers = vec![];
@@ -751,19 +1894,28 @@ impl Wollet {
         Ok(res)
     }
 
+    /// Finalize the PSET and extract the transaction, see [`finalize_with_genesis()`]
+    ///
+    /// Fails if the transaction doesn't respect [`Config::with_strict_blinding()`].
     pub fn finalize(&self, pset: &mut PartiallySignedTransaction) -> Result<Transaction, Error> {
-        // genesis_hash is only used for BIP341 (taproot) sighash computation
-        let result = pset.finalize_mut(&EC, BlockHash::all_zeros());
-        if let Err(errors) = result {
-            if !errors.is_empty() && errors.len() == pset.inputs().len() {
-                // Failed to finalize all inputs
-                // TODO: do not use Generic
-                return Err(Error::Generic(format!("{:?}", errors)));
-            }
-            // If some inputs have been finalized ignore the other errors
-        }
+        let tx = finalize_with_genesis(pset, self.sighash_genesis_hash())?;
+        self.config.check_blinding(&tx)?;
+        Ok(tx)
+    }
+
+    /// Finalize the inputs of the PSET that can be finalized, leaving the others untouched,
+    /// see [`finalize_partial_with_genesis()`]
+    pub fn finalize_partial(&self, pset: &mut PartiallySignedTransaction) -> FinalizeReport {
+        finalize_partial_with_genesis(pset, self.sighash_genesis_hash())
+    }
 
-        Ok(pset.extract_tx()?)
+    /// The genesis hash to use in sighash computation
+    ///
+    /// It's used only for BIP341 (taproot) sighashes, if unknown the all zeros hash is returned.
+    fn sighash_genesis_hash(&self) -> BlockHash {
+        self.config
+            .genesis_hash()
+            .unwrap_or_else(BlockHash::all_zeros)
     }
 
     pub fn updates(&self) -> Result<Vec<Update>, PersistError> {
@@ -793,6 +1945,92 @@ impl Wollet {
     pub fn never_scanned(&self) -> bool {
         self.store.cache.tip == (0, BlockHash::all_zeros())
     }
+
+    /// Returns an immutable view of the current wallet state, see [`WolletSnapshot`]
+    pub fn snapshot(&self) -> WolletSnapshot {
+        WolletSnapshot {
+            inner: Wollet {
+                config: self.config.clone(),
+                store: self.store.clone(),
+                persister: Arc::new(NoPersist {}),
+                descriptor: self.descriptor.clone(),
+                labels: Labels::default(),
+                marked_used: self.marked_used,
+                sync_checkpoints: HashMap::new(),
+                rescan: HashSet::new(),
+                double_spent: self.double_spent.clone(),
+                listeners: vec![],
+                fee_estimate: self.fee_estimate,
+                updates_checksum: UpdatesChecksum::new(),
+                max_weight_to_satisfy: self.max_weight_to_satisfy,
+            },
+        }
+    }
+}
+
+/// Finalize the PSET and extract the transaction, without a wallet
+///
+/// `genesis_hash` is the hash of the genesis block of the network, committed by taproot
+/// (BIP341) signatures: finalizing taproot inputs with a different hash produces invalid
+/// transactions. See [`crate::ElementsNetwork::genesis_hash()`].
+///
+/// Fails if no input can be finalized, otherwise the inputs that can't be finalized are ignored.
+pub fn finalize_with_genesis(
+    pset: &mut PartiallySignedTransaction,
+    genesis_hash: BlockHash,
+) -> Result<Transaction, Error> {
+    let report = finalize_partial_with_genesis(pset, genesis_hash);
+    if !pset.inputs().is_empty() && report.finalized.is_empty() {
+        // Failed to finalize all inputs
+        return Err(Error::CannotFinalize(report.not_finalized));
+    }
+    // If some inputs have been finalized ignore the other errors
+
+    Ok(pset.extract_tx()?)
+}
+
+/// Finalize the inputs of the PSET that can be finalized, leaving the others untouched
+///
+/// Inputs that are not finalized keep their partial signatures,
+/// so the PSET can be passed to further signing rounds and finalized later.
+/// The returned report contains the finalized inputs and why the others couldn't be finalized.
+pub fn finalize_partial_with_genesis(
+    pset: &mut PartiallySignedTransaction,
+    genesis_hash: BlockHash,
+) -> FinalizeReport {
+    let mut report = FinalizeReport::default();
+    for index in 0..pset.inputs().len() {
+        let input = &pset.inputs()[index];
+        if input.final_script_witness.is_some() || input.final_script_sig.is_some() {
+            report.finalized.push(index);
+            continue;
+        }
+        match pset.finalize_inp_mut(&EC, index, genesis_hash) {
+            Ok(()) => report.finalized.push(index),
+            Err(e) => report.not_finalized.push((index, finalize_failure(e))),
+        }
+    }
+    report
+}
+
+fn finalize_failure(error: elements_miniscript::psbt::Error) -> FinalizeFailure {
+    use elements_miniscript::psbt::{Error as PsbtError, InputError};
+    match error {
+        PsbtError::InputError(
+            InputError::CouldNotSatisfyTr
+            | InputError::MiniscriptError(elements_miniscript::Error::CouldNotSatisfy),
+            _,
+        ) => FinalizeFailure::MissingSignature,
+        PsbtError::InputError(
+            InputError::MiniscriptError(_)
+            | InputError::MissingWitnessScript
+            | InputError::MissingRedeemScript
+            | InputError::MissingPubkey
+            | InputError::NonStandardSighashType,
+            _,
+        ) => FinalizeFailure::UnsupportedScript,
+        e => FinalizeFailure::Other(e.to_string()),
+    }
 }
 
 fn tx_balance(
@@ -836,9 +2074,13 @@ pub fn full_scan_to_index_with_electrum_client(
 ) -> Result<(), Error> {
     use crate::clients::blocking::BlockchainBackend;
 
-    let update = electrum_client.full_scan_to_index(wollet, index)?;
-    if let Some(update) = update {
-        wollet.apply_update(update)?
+    // with a memory budget some transactions may be deferred to the next scans
+    while let Some(update) = electrum_client.full_scan_to_index(wollet, index)? {
+        let new_txs = !update.new_txs.txs.is_empty();
+        wollet.apply_update(update)?;
+        if !new_txs || wollet.config().memory_budget().is_none() {
+            break;
+        }
     }
 
     Ok(())
@@ -856,7 +2098,7 @@ fn tx_fee(tx: &Transaction) -> u64 {
 ///
 /// Defining clear rules for types is highly arbitrary so here we provide a string that should
 /// define the type, but it might be inaccurate in some cases.
-fn tx_type(
+pub(crate) fn tx_type(
     tx: &Transaction,
     policy_asset: &AssetId,
     balance: &BTreeMap<AssetId, i64>,
@@ -891,6 +2133,82 @@ fn tx_type(
     }
 }
 
+/// The largest number of consecutive unused indexes before or between the given used ones
+fn largest_gap(used: &BTreeSet<u32>) -> u32 {
+    let mut next = 0;
+    let mut largest = 0;
+    for index in used {
+        largest = largest.max(index - next);
+        next = index + 1;
+    }
+    largest
+}
+
+/// Verify the rangeproofs, the surjection proofs and the balance of the transaction, and that
+/// the commitments of the wallet outputs open to their unblinded values.
+///
+/// Returns `None` if the previous outputs are not all available, for instance for peg-ins.
+pub(crate) fn tx_proofs_verified(
+    txid: Txid, // passed to avoid expensive re-computation
+    tx: &Transaction,
+    all_txs: &HashMap<Txid, Transaction>,
+    unblinded: &HashMap<OutPoint, TxOutSecrets>,
+) -> Option<bool> {
+    let mut spent_utxos = Vec::with_capacity(tx.input.len());
+    for input in tx.input.iter() {
+        if input.is_pegin() || input.is_coinbase() {
+            return None;
+        }
+        let prevout = input.previous_output;
+        let utxo = all_txs
+            .get(&prevout.txid)?
+            .output
+            .get(prevout.vout as usize)?;
+        spent_utxos.push(utxo.clone());
+    }
+    if tx.verify_tx_amt_proofs(&EC, &spent_utxos).is_err() {
+        return Some(false);
+    }
+    for (vout, output) in tx.output.iter().enumerate() {
+        if let Some(secrets) = unblinded.get(&OutPoint::new(txid, vout as u32)) {
+            let asset_gen = Generator::new_blinded(
+                &EC,
+                secrets.asset.into_tag(),
+                secrets.asset_bf.into_inner(),
+            );
+            let value_commit = PedersenCommitment::new(
+                &EC,
+                secrets.value,
+                secrets.value_bf.into_inner(),
+                asset_gen,
+            );
+            if output.asset.commitment() != Some(asset_gen)
+                || output.value.commitment() != Some(value_commit)
+            {
+                return Some(false);
+            }
+        }
+    }
+    Some(true)
+}
+
+/// Whether the secrets match the asset and value of the output
+fn secrets_match(output: &TxOut, secrets: &TxOutSecrets) -> bool {
+    let asset_gen =
+        Generator::new_blinded(&EC, secrets.asset.into_tag(), secrets.asset_bf.into_inner());
+    let value_commit =
+        PedersenCommitment::new(&EC, secrets.value, secrets.value_bf.into_inner(), asset_gen);
+    let asset = match output.asset {
+        elements::confidential::Asset::Explicit(asset) => asset == secrets.asset,
+        _ => output.asset.commitment() == Some(asset_gen),
+    };
+    let value = match output.value {
+        elements::confidential::Value::Explicit(value) => value == secrets.value,
+        _ => output.value.commitment() == Some(value_commit),
+    };
+    asset && value
+}
+
 fn tx_inputs(tx: &Transaction, txos: &HashMap<OutPoint, WalletTxOut>) -> Vec<Option<WalletTxOut>> {
     tx.input
         .iter()
@@ -929,6 +2247,58 @@ impl Tip {
     }
 }
 
+/// An immutable view of the state of a [`Wollet`], created with [`Wollet::snapshot()`]
+///
+/// Creating a snapshot is cheap because the wallet data is shared with the wallet,
+/// it is copied only when the wallet is updated while the snapshot is still alive.
+/// The snapshot doesn't borrow the wallet, so it can be read from other threads
+/// while the wallet is syncing, and the returned values are always consistent with each other.
+pub struct WolletSnapshot {
+    inner: Wollet,
+}
+
+impl WolletSnapshot {
+    /// See [`Wollet::status()`]
+    pub fn status(&self) -> u64 {
+        self.inner.status()
+    }
+
+    /// See [`Wollet::tip()`]
+    pub fn tip(&self) -> Tip {
+        self.inner.tip()
+    }
+
+    /// See [`Wollet::balance()`]
+    pub fn balance(&self) -> Result<BTreeMap<AssetId, u64>, Error> {
+        self.inner.balance()
+    }
+
+    /// See [`Wollet::balances()`]
+    pub fn balances(&self) -> Result<Balances, Error> {
+        self.inner.balances()
+    }
+
+    /// See [`Wollet::utxos()`]
+    pub fn utxos(&self) -> Result<Vec<WalletTxOut>, Error> {
+        self.inner.utxos()
+    }
+
+    /// See [`Wollet::txos()`]
+    pub fn txos(&self) -> Result<Vec<WalletTxOut>, Error> {
+        self.inner.txos()
+    }
+
+    /// See [`Wollet::transactions()`]
+    pub fn transactions(&self) -> Result<Vec<WalletTx>, Error> {
+        self.inner.transactions()
+    }
+
+    /// See [`Wollet::transaction()`]
+    pub fn transaction(&self, txid: &Txid) -> Result<Option<WalletTx>, Error> {
+        self.inner.transaction(txid)
+    }
+}
+
 /// Derive script_pubkey and blinding_pubkey from a descriptor at a given derivation index
 pub fn derive_script_and_blinding_key(
     descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
@@ -947,7 +2317,7 @@ pub fn derive_script_and_blinding_key(
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
     use std::str::FromStr;
@@ -956,7 +2326,7 @@ mod tests {
     use crate::elements::bitcoin::bip32::{Xpriv, Xpub};
     use crate::elements::bitcoin::network::Network;
     use crate::elements::AddressParams;
-    use crate::NoPersist;
+    use crate::{NoPersist, TxDirection};
     use elements_miniscript::confidential::bare::tweak_private_key;
     use elements_miniscript::confidential::Key;
     use elements_miniscript::descriptor::checksum::desc_checksum;
@@ -1153,6 +2523,34 @@ mod tests {
         assert_eq!(addr.address().to_string(), lwk_test_util::PEGIN_TEST_ADDR);
     }
 
+    #[test]
+    fn test_multipath_chains() {
+        let wollet = test_wollet_with_many_transactions();
+        assert!(wollet.wollet_descriptor().is_multipath());
+        let external = wollet.last_unused_index(Chain::External);
+        let internal = wollet.last_unused_index(Chain::Internal);
+        assert!(external > 0);
+        assert!(internal > 0);
+        assert_eq!(wollet.address(None).unwrap().index(), external);
+        assert_eq!(wollet.change(None).unwrap().index(), internal);
+        assert_ne!(
+            wollet.address(Some(0)).unwrap().address(),
+            wollet.change(Some(0)).unwrap().address()
+        );
+
+        let desc = lwk_test_util::wollet_descriptor_many_transactions()
+            .split('#')
+            .next()
+            .unwrap()
+            .replace("<0;1>", "0");
+        let wollet = new_wollet(&desc);
+        assert!(!wollet.wollet_descriptor().is_multipath());
+        assert_eq!(
+            wollet.address(Some(0)).unwrap().address(),
+            wollet.change(Some(0)).unwrap().address()
+        );
+    }
+
     #[test]
     fn test_txos_inner() {
         let wollet = test_wollet_with_many_transactions();
@@ -1162,6 +2560,433 @@ mod tests {
         assert_eq!(txos.len(), 132);
     }
 
+    #[test]
+    fn test_balance_with_confirmations() {
+        let wollet = test_wollet_with_many_transactions();
+        let balance = wollet.balance().unwrap();
+        assert_eq!(wollet.balance_with_confirmations(0).unwrap(), balance);
+
+        let tip = wollet.tip().height();
+        assert_eq!(wollet.confirmations(None), 0);
+        assert_eq!(wollet.confirmations(Some(tip)), 1);
+        assert_eq!(wollet.confirmations(Some(tip - 9)), 10);
+
+        let balance = wollet.balance_with_confirmations(u32::MAX).unwrap();
+        assert_eq!(balance.len(), 1);
+        assert_eq!(balance.get(&wollet.policy_asset()), Some(&0));
+    }
+
+    #[test]
+    fn test_stats() {
+        let wollet = test_wollet_with_many_transactions();
+        let stats = wollet.stats().unwrap();
+        let txs = wollet.transactions().unwrap();
+        assert_eq!(stats.txs, txs.len());
+        assert_eq!(
+            stats.unconfirmed_txs,
+            txs.iter().filter(|tx| tx.height.is_none()).count()
+        );
+        assert_eq!(stats.utxos, wollet.utxos().unwrap().len());
+        let heights: Vec<u32> = txs.iter().filter_map(|tx| tx.height).collect();
+        assert_eq!(stats.first_height, heights.iter().min().cloned());
+        assert_eq!(stats.last_height, heights.iter().max().cloned());
+        let fees: u64 = txs
+            .iter()
+            .filter(|tx| tx.inputs.iter().any(|i| i.is_some()))
+            .map(|tx| tx.fee)
+            .sum();
+        assert_eq!(stats.fees_paid, fees);
+        assert!(stats.fees_paid > 0);
+        assert!(stats.used_addresses > 0);
+        assert!(stats.assets > 0);
+        assert!(stats.cached_txs >= stats.txs);
+        assert!(stats.cache_size > 0);
+
+        let empty = Wollet::new(
+            ElementsNetwork::LiquidTestnet,
+            NoPersist::new(),
+            wollet.wollet_descriptor(),
+        )
+        .unwrap();
+        assert_eq!(empty.stats().unwrap(), WolletStats::default());
+    }
+
+    #[test]
+    fn test_utxos_with() {
+        let wollet = test_wollet_with_many_transactions();
+        let utxos = wollet.utxos().unwrap();
+        assert_eq!(wollet.utxos_with(&UtxoFilter::default()).unwrap(), utxos);
+
+        let policy_asset = wollet.policy_asset();
+        let filter = UtxoFilter::default().asset(policy_asset);
+        let lbtc_utxos = wollet.utxos_with(&filter).unwrap();
+        assert!(lbtc_utxos.iter().all(|u| u.unblinded.asset == policy_asset));
+        assert_eq!(lbtc_utxos, wollet.asset_utxos(&policy_asset).unwrap());
+
+        let max = utxos.iter().map(|u| u.unblinded.value).max().unwrap();
+        let filter = UtxoFilter::default().min_value(max);
+        let big_utxos = wollet.utxos_with(&filter).unwrap();
+        assert!(!big_utxos.is_empty());
+        assert!(big_utxos.iter().all(|u| u.unblinded.value == max));
+
+        let filter = UtxoFilter::default().min_confirmations(u32::MAX);
+        assert!(wollet.utxos_with(&filter).unwrap().is_empty());
+
+        let filter = UtxoFilter::default().exclude_spent_by_unconfirmed(false);
+        let with_spent = wollet.utxos_with(&filter).unwrap();
+        assert!(with_spent.len() >= utxos.len());
+        for utxo in utxos.iter() {
+            assert!(with_spent.contains(utxo));
+        }
+
+        let filter = UtxoFilter::default().offset(1).limit(2);
+        assert_eq!(wollet.utxos_with(&filter).unwrap(), utxos[1..3]);
+
+        let height = utxos.iter().filter_map(|u| u.height).min().unwrap();
+        let filter = UtxoFilter::default().max_height(height);
+        let old_utxos = wollet.utxos_with(&filter).unwrap();
+        assert!(!old_utxos.is_empty());
+        assert!(old_utxos.iter().all(|u| u.height == Some(height)));
+        let filter = UtxoFilter::default().min_height(height + 1);
+        assert_eq!(
+            wollet.utxos_with(&filter).unwrap().len(),
+            utxos.len() - old_utxos.len()
+        );
+    }
+
+    #[test]
+    fn test_transactions_with() {
+        let wollet = test_wollet_with_many_transactions();
+        let txs = wollet.transactions().unwrap();
+        assert!(txs.len() > 10);
+        assert_eq!(wollet.transactions_with(&TxFilter::default()).unwrap(), txs);
+
+        // pages follow the order of transactions()
+        let page = |offset| {
+            let filter = TxFilter::default().offset(offset).limit(5);
+            wollet.transactions_with(&filter).unwrap()
+        };
+        assert_eq!(page(0), txs[..5]);
+        assert_eq!(page(5), txs[5..10]);
+        assert!(page(txs.len()).is_empty());
+
+        let incoming = TxFilter::default().direction(TxDirection::Incoming);
+        let outgoing = TxFilter::default().direction(TxDirection::Outgoing);
+        let incoming = wollet.transactions_with(&incoming).unwrap();
+        let outgoing = wollet.transactions_with(&outgoing).unwrap();
+        assert_eq!(incoming.len() + outgoing.len(), txs.len());
+        assert!(incoming
+            .iter()
+            .all(|tx| tx.balance.values().all(|v| *v >= 0)));
+
+        let asset = *txs
+            .iter()
+            .flat_map(|tx| tx.balance.keys())
+            .find(|a| **a != wollet.policy_asset())
+            .unwrap();
+        let filter = TxFilter::default().asset(asset);
+        let asset_txs = wollet.transactions_with(&filter).unwrap();
+        assert!(!asset_txs.is_empty());
+        assert!(asset_txs.iter().all(|tx| tx.balance.contains_key(&asset)));
+
+        let height = txs[txs.len() / 2].height.unwrap();
+        let filter = TxFilter::default().min_height(height).max_height(height);
+        let at_height = wollet.transactions_with(&filter).unwrap();
+        assert!(!at_height.is_empty());
+        assert!(at_height.iter().all(|tx| tx.height == Some(height)));
+    }
+
+    #[test]
+    fn test_labels() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let descriptor: WolletDescriptor = lwk_test_util::wollet_descriptor_many_transactions()
+            .parse()
+            .unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, descriptor.clone(), &tempdir).unwrap();
+        assert!(wollet.labels().is_empty());
+
+        let address = wollet.address(Some(0)).unwrap().address().clone();
+        wollet
+            .set_label(Label::address(&address, "donations"))
+            .unwrap();
+        let txid = <Txid as crate::hashes::Hash>::all_zeros();
+        wollet.set_label(Label::tx(&txid, "rent")).unwrap();
+        wollet.set_label(Label::tx(&txid, "rent paid")).unwrap();
+        assert_eq!(wollet.labels().len(), 2);
+        let exported = wollet.export_labels().unwrap();
+
+        // Labels are persisted
+        let mut wollet = Wollet::with_fs_persist(network, descriptor, &tempdir).unwrap();
+        assert_eq!(wollet.export_labels().unwrap(), exported);
+        let label = wollet
+            .labels()
+            .get(LabelType::Tx, &txid.to_string())
+            .unwrap();
+        assert_eq!(label.label.as_deref(), Some("rent paid"));
+
+        let removed = wollet
+            .remove_label(LabelType::Addr, &address.to_string())
+            .unwrap();
+        assert!(removed.is_some());
+        assert_eq!(wollet.labels().len(), 1);
+        assert_eq!(wollet.import_labels(&exported).unwrap(), 2);
+        assert_eq!(wollet.labels().len(), 2);
+    }
+
+    #[test]
+    fn test_peek_and_mark_used() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let descriptor: WolletDescriptor = lwk_test_util::wollet_descriptor_many_transactions()
+            .parse()
+            .unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, descriptor.clone(), &tempdir).unwrap();
+        let status = wollet.status();
+
+        let next = wollet.peek_next_address().unwrap();
+        assert_eq!(next.index(), 0);
+        assert_eq!(
+            wollet.peek_next_address().unwrap().address(),
+            next.address()
+        );
+        assert_eq!(wollet.address(None).unwrap().address(), next.address());
+        assert_eq!(wollet.address_at(5).unwrap().index(), 5);
+        assert_eq!(
+            wollet.address_at(5).unwrap().address(),
+            wollet.address(Some(5)).unwrap().address()
+        );
+
+        wollet.mark_used(3).unwrap();
+        assert_eq!(wollet.peek_next_address().unwrap().index(), 4);
+        wollet.mark_used(1).unwrap();
+        assert_eq!(wollet.peek_next_address().unwrap().index(), 4);
+        assert_eq!(wollet.status(), status);
+
+        // Marked index is persisted
+        let wollet = Wollet::with_fs_persist(network, descriptor, &tempdir).unwrap();
+        assert_eq!(wollet.peek_next_address().unwrap().index(), 4);
+    }
+
+    #[test]
+    fn test_balance_at() {
+        let wollet = test_wollet_with_many_transactions();
+        let filter = UtxoFilter::default()
+            .min_confirmations(1)
+            .exclude_spent_by_unconfirmed(false);
+        let utxos = wollet.utxos_with(&filter).unwrap();
+        let expected = wollet.balance_from_utxos(&utxos).unwrap();
+        let tip = wollet.tip().height();
+        assert_eq!(wollet.balance_at(tip).unwrap(), expected);
+        assert_eq!(wollet.balance_at(u32::MAX).unwrap(), expected);
+
+        let balance = wollet.balance_at(0).unwrap();
+        assert_eq!(balance.len(), 1);
+        assert_eq!(balance.get(&wollet.policy_asset()), Some(&0));
+
+        // The balance changes only at the heights of the wallet transactions
+        let txs = wollet.transactions().unwrap();
+        let first_height = txs.iter().filter_map(|tx| tx.height).min().unwrap();
+        assert_eq!(wollet.balance_at(first_height - 1).unwrap(), balance);
+        assert_ne!(wollet.balance_at(first_height).unwrap(), balance);
+    }
+
+    #[test]
+    fn test_balances() {
+        let wollet = test_wollet_with_many_transactions();
+        let balances = wollet.balances().unwrap();
+        let policy_asset = wollet.policy_asset();
+        assert!(balances.confirmed.contains_key(&policy_asset));
+        assert!(balances.trusted_pending.contains_key(&policy_asset));
+        assert!(balances.untrusted_pending.contains_key(&policy_asset));
+
+        let mut total = balances.trusted();
+        for (asset, value) in balances.untrusted_pending.iter() {
+            *total.entry(*asset).or_default() += value;
+        }
+        assert_eq!(total, wollet.balance().unwrap());
+        assert_eq!(
+            balances.confirmed,
+            wollet.balance_with_confirmations(1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut wollet = test_wollet_with_many_transactions();
+        let balance = wollet.balance().unwrap();
+        let status = wollet.status();
+        let snapshot = wollet.snapshot();
+        assert!(Arc::ptr_eq(
+            &snapshot.inner.store.cache,
+            &wollet.store.cache
+        ));
+        assert_eq!(snapshot.status(), status);
+        assert_eq!(snapshot.balance().unwrap(), balance);
+        assert_eq!(
+            snapshot.transactions().unwrap().len(),
+            wollet.transactions().unwrap().len()
+        );
+
+        // Mutating the wallet copies the data, leaving the snapshot unchanged
+        Arc::make_mut(&mut wollet.store.cache).heights.clear();
+        assert!(!Arc::ptr_eq(
+            &snapshot.inner.store.cache,
+            &wollet.store.cache
+        ));
+        assert_ne!(wollet.status(), status);
+        assert!(wollet.utxos().unwrap().is_empty());
+        assert_eq!(snapshot.status(), status);
+        assert_eq!(snapshot.balance().unwrap(), balance);
+
+        fn is_send_sync<T: Send + Sync>(_: &T) {}
+        is_send_sync(&snapshot);
+    }
+
+    #[test]
+    fn test_concise_state_serde() {
+        let wollet = test_wollet_with_many_transactions();
+        let state = wollet.state();
+        let json = serde_json::to_string(&state).unwrap();
+        let back: WolletConciseState = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(back.wollet_status(), wollet.status());
+        assert_eq!(
+            WolletState::descriptor(&back).to_string(),
+            wollet.wollet_descriptor().to_string()
+        );
+        assert_eq!(back.heights(), &wollet.store.cache.heights);
+        assert_eq!(back.paths(), &wollet.store.cache.paths);
+        assert_eq!(back.scripts, wollet.store.cache.scripts);
+        assert_eq!(back.txs(), WolletState::txs(&wollet));
+        assert_eq!(back.tip(), WolletState::tip(&wollet));
+        assert_eq!(back.last_unused(), wollet.last_unused());
+        assert_eq!(back.gap_limit(), 20);
+        assert!(!back.verify_proofs());
+        assert_eq!(back.birthday(), None);
+
+        // Scripts are derived or taken from the cache as with the wallet
+        let descriptor = wollet.wollet_descriptor();
+        let descriptor = descriptor.as_single_descriptors().unwrap();
+        for batch in 0..2 {
+            let a = back.get_script_batch(batch, &descriptor[0]).unwrap();
+            let b = wollet.get_script_batch(batch, &descriptor[0]).unwrap();
+            assert_eq!(a.value, b.value);
+            assert_eq!(a.cached, b.cached);
+        }
+    }
+
+    #[test]
+    fn test_rescan_from() {
+        let mut wollet = test_wollet_with_many_transactions();
+        let txs = wollet.transactions().unwrap();
+        let balance = wollet.balance().unwrap();
+        let heights: BTreeSet<_> = txs.iter().filter_map(|tx| tx.height).collect();
+        let height = *heights.iter().nth(heights.len() / 2).unwrap();
+        let expected = txs
+            .iter()
+            .filter(|tx| tx.height.map_or(true, |h| h >= height))
+            .count();
+        assert!(expected > 0 && expected < txs.len());
+
+        // The transactions to download again are not given to the scan
+        assert_eq!(wollet.rescan_from(height).unwrap(), expected);
+        assert_eq!(WolletState::txs(&wollet).len(), txs.len() - expected);
+        assert_eq!(wollet.state().txs().len(), txs.len() - expected);
+        for txid in WolletState::txs(&wollet) {
+            let tx = txs.iter().find(|tx| tx.txid == txid).unwrap();
+            assert!(tx.height.unwrap() < height);
+        }
+        // while the wallet keeps using the cached ones
+        assert_eq!(wollet.transactions().unwrap().len(), txs.len());
+
+        // Downloading the transactions again completes the rescan
+        let mut update =
+            Update::deserialize(&lwk_test_util::update_test_vector_many_transactions()).unwrap();
+        update.wollet_status = wollet.status();
+        wollet.apply_update(update).unwrap();
+        assert!(wollet.rescan.is_empty());
+        assert_eq!(WolletState::txs(&wollet).len(), txs.len());
+        assert_eq!(wollet.balance().unwrap(), balance);
+
+        assert_eq!(wollet.rescan_from(u32::MAX).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_gap_limit_warning() {
+        assert_eq!(largest_gap(&BTreeSet::new()), 0);
+        assert_eq!(largest_gap(&[0, 1, 2].into()), 0);
+        assert_eq!(largest_gap(&[3, 4].into()), 3);
+        assert_eq!(largest_gap(&[0, 5, 30, 31].into()), 24);
+
+        let wollet = test_wollet_with_many_transactions();
+        assert_eq!(wollet.config().gap_limit(), 20);
+        assert_eq!(wollet.gap_limit_warning(), None);
+
+        // The test wallet uses its addresses sequentially
+        let update = lwk_test_util::update_test_vector_many_transactions();
+        let descriptor = lwk_test_util::wollet_descriptor_many_transactions();
+        let descriptor: WolletDescriptor = descriptor.parse().unwrap();
+        let update = Update::deserialize(&update).unwrap();
+        let config = Config::new(ElementsNetwork::LiquidTestnet)
+            .unwrap()
+            .with_gap_limit(0);
+        assert_eq!(config.gap_limit(), 1);
+        let mut wollet = Wollet::with_config(config, Arc::new(NoPersist {}), descriptor).unwrap();
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.gap_limit_warning(), None);
+    }
+
+    #[test]
+    fn test_verify_proofs() {
+        let update = lwk_test_util::update_test_vector_many_transactions();
+        let descriptor = lwk_test_util::wollet_descriptor_many_transactions();
+        let descriptor: WolletDescriptor = descriptor.parse().unwrap();
+        let update = Update::deserialize(&update).unwrap();
+        let config = Config::new(ElementsNetwork::LiquidTestnet)
+            .unwrap()
+            .with_verify_proofs(true);
+        let mut wollet = Wollet::with_config(config, Arc::new(NoPersist {}), descriptor).unwrap();
+        wollet.apply_update(update).unwrap();
+
+        // Disabled by default
+        let txs = test_wollet_with_many_transactions().transactions().unwrap();
+        assert!(txs.iter().all(|tx| tx.verified.is_none()));
+
+        // Txs spending wallet outputs have all the previous outputs available
+        let txs = wollet.transactions().unwrap();
+        assert!(txs.iter().all(|tx| tx.verified != Some(false)));
+        let tx = txs
+            .iter()
+            .find(|tx| tx.inputs.iter().all(|i| i.is_some()))
+            .unwrap();
+        assert_eq!(tx.verified, Some(true));
+        assert_eq!(
+            wollet.transaction(&tx.txid).unwrap().unwrap().verified,
+            Some(true)
+        );
+
+        // Commitments not matching the unblinded values are detected
+        let cache = &wollet.store.cache;
+        let (vout, _) = tx
+            .outputs
+            .iter()
+            .enumerate()
+            .find(|(_, o)| o.is_some())
+            .unwrap();
+        let mut unblinded = cache.unblinded.clone();
+        let secrets = unblinded
+            .get_mut(&OutPoint::new(tx.txid, vout as u32))
+            .unwrap();
+        secrets.value += 1;
+        let verified = tx_proofs_verified(tx.txid, &tx.tx, &cache.all_txs, &unblinded);
+        assert_eq!(verified, Some(false));
+
+        // Missing previous outputs
+        let verified = tx_proofs_verified(tx.txid, &tx.tx, &HashMap::new(), &cache.unblinded);
+        assert_eq!(verified, None);
+    }
+
     #[test]
     fn test_acceptable_performance() {
         let wollet = test_wollet_with_many_transactions();
@@ -1190,6 +3015,129 @@ mod tests {
         assert!(duration < MAX_DURATION);
     }
 
+    #[test]
+    fn test_insert_tx() {
+        let full = test_wollet_with_many_transactions();
+        let tx = full
+            .transactions()
+            .unwrap()
+            .into_iter()
+            .find(|tx| {
+                tx.type_ == "incoming" && tx.outputs.iter().flatten().all(|o| o.wildcard_index < 20)
+            })
+            .unwrap();
+        let mut wollet = Wollet::new(
+            ElementsNetwork::LiquidTestnet,
+            NoPersist::new(),
+            full.wollet_descriptor(),
+        )
+        .unwrap();
+
+        // Wrong secrets for a wallet output
+        let vout = tx.outputs.iter().position(|o| o.is_some()).unwrap() as u32;
+        let mut secrets = tx.outputs[vout as usize].as_ref().unwrap().unblinded;
+        secrets.value += 1;
+        assert!(matches!(
+            wollet.insert_tx(tx.tx.clone(), vec![(vout, secrets)]),
+            Err(Error::InvalidUnblindedOutput(v)) if v == vout
+        ));
+
+        wollet.insert_tx(tx.tx.clone(), vec![]).unwrap();
+        let inserted = wollet.transactions().unwrap();
+        assert_eq!(inserted.len(), 1);
+        assert_eq!(inserted[0].txid, tx.txid);
+        assert_eq!(inserted[0].height, None);
+        assert_eq!(inserted[0].balance, tx.balance);
+        assert_eq!(
+            wollet.utxos().unwrap().len(),
+            tx.outputs.iter().flatten().count()
+        );
+        let max_index = tx.outputs.iter().flatten().map(|o| o.wildcard_index).max();
+        assert!(wollet.address(None).unwrap().index() > max_index.unwrap());
+
+        // A transaction not involving the wallet
+        let other = lwk_test_util::liquid_block_1().txdata.remove(0);
+        assert!(matches!(
+            wollet.insert_tx(other.clone(), vec![]),
+            Err(Error::TxNotInvolvingWallet(txid)) if txid == other.txid()
+        ));
+    }
+
+    #[test]
+    fn test_double_spent() {
+        let mut wollet = test_wollet_with_many_transactions();
+        assert!(wollet.double_spent().is_empty());
+        let tx = wollet
+            .transactions()
+            .unwrap()
+            .into_iter()
+            .find(|tx| tx.height.is_some() && tx.inputs.iter().any(|i| i.is_some()))
+            .unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        wollet.add_listener(Arc::new(sender));
+
+        // An unconfirmed transaction paying more fees than the confirmed one
+        let mut conflicting = tx.tx.clone();
+        let fee = conflicting.output.iter_mut().find(|o| o.is_fee()).unwrap();
+        fee.value = elements::confidential::Value::Explicit(fee.value.explicit().unwrap() + 1);
+        let conflicting_txid = conflicting.txid();
+        wollet.insert_tx(conflicting, vec![]).unwrap();
+
+        let expected: HashMap<_, _> = [(conflicting_txid, tx.txid)].into();
+        assert_eq!(wollet.double_spent(), &expected);
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert!(events.contains(&crate::WolletEvent::DoubleSpendDetected {
+            losing: conflicting_txid,
+            winning: tx.txid,
+        }));
+    }
+
+    #[test]
+    fn test_double_spent_reload() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let descriptor: WolletDescriptor = lwk_test_util::wollet_descriptor_many_transactions()
+            .parse()
+            .unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, descriptor.clone(), &tempdir).unwrap();
+        let update = lwk_test_util::update_test_vector_many_transactions();
+        let update = Update::deserialize(&update).unwrap();
+        let tip = update.tip.clone();
+        wollet.apply_update(update).unwrap();
+        let tx = wollet
+            .transactions()
+            .unwrap()
+            .into_iter()
+            .find(|tx| tx.height.is_some() && tx.inputs.iter().any(|i| i.is_some()))
+            .unwrap();
+
+        let mut conflicting = tx.tx.clone();
+        let fee = conflicting.output.iter_mut().find(|o| o.is_fee()).unwrap();
+        fee.value = elements::confidential::Value::Explicit(fee.value.explicit().unwrap() + 1);
+        let conflicting_txid = conflicting.txid();
+        let update = Update {
+            version: crate::update::UPDATE_VERSION,
+            wollet_status: wollet.wollet_status(),
+            new_txs: crate::update::DownloadTxResult {
+                txs: vec![(conflicting_txid, conflicting)],
+                unblinds: vec![],
+            },
+            txid_height_new: vec![(conflicting_txid, None)],
+            txid_height_delete: vec![],
+            timestamps: vec![],
+            scripts_with_blinding_pubkey: vec![],
+            tip,
+            checkpoints: vec![],
+        };
+        wollet.apply_update(update).unwrap();
+        let expected: HashMap<_, _> = [(conflicting_txid, tx.txid)].into();
+        assert_eq!(wollet.double_spent(), &expected);
+
+        // The double spends are found again once the persisted updates are replayed
+        let wollet = Wollet::with_fs_persist(network, descriptor, &tempdir).unwrap();
+        assert_eq!(wollet.double_spent(), &expected);
+    }
+
     // duplicated from tests/test_wollet.rs
     pub fn test_wollet_with_many_transactions() -> Wollet {
         let update = lwk_test_util::update_test_vector_many_transactions();
@@ -1205,4 +3153,143 @@ mod tests {
         wollet.apply_update(update).unwrap();
         wollet
     }
+
+    #[test]
+    fn test_listener() {
+        let update = lwk_test_util::update_test_vector_many_transactions();
+        let update = Update::deserialize(&update).unwrap();
+        let descriptor = lwk_test_util::wollet_descriptor_many_transactions();
+        let descriptor: WolletDescriptor = descriptor.parse().unwrap();
+        let config = Config::new(ElementsNetwork::LiquidTestnet)
+            .unwrap()
+            .with_confirmation_policy(crate::ConfirmationPolicy::new(1));
+        let mut wollet = Wollet::with_config(config, NoPersist::new(), descriptor).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        wollet.add_listener(Arc::new(sender));
+        wollet.apply_update(update).unwrap();
+
+        let events: Vec<_> = receiver.try_iter().collect();
+        let count = |f: fn(&crate::WolletEvent) -> bool| events.iter().filter(|e| f(e)).count();
+        let txs = wollet.transactions().unwrap();
+        assert_eq!(
+            count(|e| matches!(e, crate::WolletEvent::NewTransaction { .. })),
+            txs.len()
+        );
+        assert_eq!(
+            count(|e| matches!(e, crate::WolletEvent::Final { .. })),
+            txs.iter().filter(|tx| tx.height.is_some()).count()
+        );
+        let (height, block_hash) = wollet.store.cache.tip;
+        assert!(events.contains(&crate::WolletEvent::NewTip { height, block_hash }));
+    }
+
+    #[test]
+    fn test_strict_blinding() {
+        use elements::encode::Decodable;
+        let tx_bytes = include_bytes!(
+            "../tests/data/62ea5d0aa7c9f4339b16a6d8e6ff4437ffb244de658222841c74d335324e4219"
+        );
+        let mut tx = Transaction::consensus_decode(&tx_bytes[..]).unwrap();
+        let config = Config::new(ElementsNetwork::Liquid).unwrap();
+        let strict = config.clone().with_strict_blinding(true);
+        strict.check_blinding(&tx).unwrap();
+
+        let burn = Script::new_op_return(&[]);
+        let mut explicit = tx.output[0].clone();
+        explicit.value = elements::confidential::Value::Explicit(1000);
+        explicit.asset = elements::confidential::Asset::Explicit(config.policy_asset());
+        explicit.script_pubkey = burn.clone();
+        tx.output.push(explicit);
+        let vout = tx.output.len() as u32 - 1;
+        assert!(matches!(strict.check_blinding(&tx), Err(Error::ExplicitOutput(v)) if v == vout));
+        config.check_blinding(&tx).unwrap();
+        let allowed = strict.with_explicit_output_allowed(burn);
+        allowed.check_blinding(&tx).unwrap();
+    }
+
+    #[test]
+    fn test_global_xpubs() {
+        use crate::elements::bitcoin::bip32::DerivationPath;
+        use crate::elements::pset::{Input, Output};
+
+        let desc: WolletDescriptor = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc.clone()).unwrap();
+        let xpub = Xpub::from_str("tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA").unwrap();
+        let origin = (
+            Fingerprint::from_str("759db348").unwrap(),
+            DerivationPath::from_str("m/84'/1'/0'").unwrap(),
+        );
+
+        // a PSET from software setting the key sources relative to the account xpub, spending
+        // and receiving on scripts beyond the ones cached by the wallet
+        let key = |path: &str| {
+            let path = DerivationPath::from_str(path).unwrap();
+            let pk = xpub.derive_pub(&EC, &path).unwrap().public_key;
+            (
+                crate::bitcoin::PublicKey::new(pk),
+                (xpub.fingerprint(), path),
+            )
+        };
+        let mut pset = PartiallySignedTransaction::new_v2();
+        let mut input = Input::from_prevout(OutPoint::default());
+        let spk = desc
+            .definite_descriptor(Chain::External, 100)
+            .unwrap()
+            .script_pubkey();
+        input.witness_utxo = Some(elements::TxOut {
+            script_pubkey: spk,
+            ..Default::default()
+        });
+        let (input_pk, input_ks) = key("m/0/100");
+        input.bip32_derivation.insert(input_pk, input_ks);
+        pset.add_input(input);
+        let mut output = Output {
+            script_pubkey: desc
+                .definite_descriptor(Chain::Internal, 7)
+                .unwrap()
+                .script_pubkey(),
+            ..Default::default()
+        };
+        let (output_pk, output_ks) = key("m/1/7");
+        output.bip32_derivation.insert(output_pk, output_ks);
+        pset.add_output(output);
+        // a key source of another key, still hinting the index of the wallet script
+        let mut output = Output {
+            script_pubkey: desc
+                .definite_descriptor(Chain::External, 5)
+                .unwrap()
+                .script_pubkey(),
+            ..Default::default()
+        };
+        let path = DerivationPath::from_str("m/0/5").unwrap();
+        output
+            .bip32_derivation
+            .insert(input_pk, (Fingerprint::default(), path));
+        pset.add_output(output);
+        pset.global.xpub.insert(xpub, origin.clone());
+
+        let expected = |path: &str| {
+            (
+                origin.0,
+                origin.1.extend(DerivationPath::from_str(path).unwrap()),
+            )
+        };
+        let sigs = pset_signatures(&pset);
+        assert_eq!(
+            sigs[0].missing_signature,
+            vec![(input_pk, expected("m/0/100"))]
+        );
+
+        wollet.add_details(&mut pset).unwrap();
+        assert_eq!(
+            pset.inputs()[0].bip32_derivation[&input_pk],
+            expected("m/0/100")
+        );
+        assert_eq!(
+            pset.outputs()[0].bip32_derivation[&output_pk],
+            expected("m/1/7")
+        );
+        let (pk, _) = key("m/0/5");
+        assert_eq!(pset.outputs()[1].bip32_derivation[&pk], expected("m/0/5"));
+    }
 }
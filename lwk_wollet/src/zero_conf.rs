@@ -0,0 +1,172 @@
+//! Heuristics on the risk of accepting unconfirmed incoming payments
+
+use elements::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::Wollet;
+
+/// The maximum number of unconfirmed ancestors counted, like the mempool ancestors limit
+const MAX_ANCESTORS: u32 = 25;
+
+/// The risk of accepting an unconfirmed incoming transaction before it's confirmed
+///
+/// On Liquid blocks are produced every minute by the federation, so transactions paying enough
+/// fees are usually confirmed in the next block; still an unconfirmed transaction can be replaced
+/// or never confirmed. This is a heuristic on the data known by the wallet, the `score` goes from
+/// 0, no risk signal, to 100, the transaction is double spent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ZeroConfRisk {
+    /// The risk score, from 0 to 100
+    pub score: u8,
+
+    /// The fee rate of the transaction in sats/kvb, computed on the discounted vsize
+    pub fee_rate: u64,
+
+    /// Whether the fee rate is below the estimate set with [`Wollet::set_fee_estimate()`]
+    pub below_fee_estimate: bool,
+
+    /// Whether an input signals replaceability (BIP125)
+    pub replaceable: bool,
+
+    /// Whether the transaction conflicts with another wallet transaction, see
+    /// [`Wollet::double_spent()`]
+    pub double_spent: bool,
+
+    /// The length of the longest chain of unconfirmed wallet transactions the transaction
+    /// spends from
+    pub unconfirmed_ancestors: u32,
+}
+
+impl ZeroConfRisk {
+    fn new(
+        fee_rate: u64,
+        below_fee_estimate: bool,
+        replaceable: bool,
+        double_spent: bool,
+        unconfirmed_ancestors: u32,
+    ) -> Self {
+        let score = if double_spent {
+            100
+        } else {
+            let mut score = 0u32;
+            if replaceable {
+                score += 40;
+            }
+            if below_fee_estimate {
+                score += 30;
+            }
+            score += 10 * unconfirmed_ancestors.min(3);
+            score.min(99)
+        };
+        Self {
+            score: score as u8,
+            fee_rate,
+            below_fee_estimate,
+            replaceable,
+            double_spent,
+            unconfirmed_ancestors,
+        }
+    }
+}
+
+impl Wollet {
+    /// Set the current fee rate estimate, in sats/kvb, used to compute the
+    /// [`crate::WalletTx::zero_conf_risk`] of unconfirmed incoming transactions
+    ///
+    /// The estimate is not persisted, applications should set it after every fee estimation,
+    /// for instance from a [`crate::fee_monitor::FeeMonitor`].
+    pub fn set_fee_estimate(&mut self, fee_rate: f32) {
+        self.fee_estimate = Some(fee_rate);
+    }
+
+    /// The risk of accepting `tx` before it's confirmed, `None` if it's confirmed or it's not
+    /// an incoming transaction
+    pub(crate) fn zero_conf_risk(
+        &self,
+        txid: &Txid,
+        tx: &Transaction,
+        height: Option<u32>,
+        incoming: bool,
+        fee: u64,
+    ) -> Option<ZeroConfRisk> {
+        if height.is_some() || !incoming {
+            return None;
+        }
+        let fee_rate = fee * 1000 / tx.discount_vsize().max(1) as u64;
+        let below_fee_estimate = self
+            .fee_estimate
+            .map_or(false, |estimate| (fee_rate as f32) < estimate);
+        let replaceable = tx.input.iter().any(|i| i.sequence.is_rbf());
+        let double_spent = self.double_spent.contains_key(txid)
+            || self.double_spent.values().any(|winning| winning == txid);
+        let ancestors = self.unconfirmed_ancestors(tx, MAX_ANCESTORS);
+        Some(ZeroConfRisk::new(
+            fee_rate,
+            below_fee_estimate,
+            replaceable,
+            double_spent,
+            ancestors,
+        ))
+    }
+
+    /// The length of the longest chain of unconfirmed wallet transactions `tx` spends from, up
+    /// to `max`
+    fn unconfirmed_ancestors(&self, tx: &Transaction, max: u32) -> u32 {
+        if max == 0 {
+            return 0;
+        }
+        let cache = &self.store.cache;
+        tx.input
+            .iter()
+            .filter(|i| cache.heights.get(&i.previous_output.txid) == Some(&None))
+            .filter_map(|i| cache.all_txs.get(&i.previous_output.txid))
+            .map(|parent| 1 + self.unconfirmed_ancestors(parent, max - 1))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZeroConfRisk;
+    use crate::wollet::tests::test_wollet_with_many_transactions;
+    use crate::{ElementsNetwork, NoPersist, Wollet};
+
+    #[test]
+    fn test_zero_conf_risk() {
+        let full = test_wollet_with_many_transactions();
+        let txs = full.transactions().unwrap();
+        assert!(txs.iter().all(|tx| tx.zero_conf_risk.is_none()));
+        let tx = txs
+            .into_iter()
+            .find(|tx| {
+                tx.type_ == "incoming" && tx.outputs.iter().flatten().all(|o| o.wildcard_index < 20)
+            })
+            .unwrap();
+
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::new(network, NoPersist::new(), full.wollet_descriptor()).unwrap();
+        wollet.insert_tx(tx.tx.clone(), vec![]).unwrap();
+        let wallet_tx = wollet.transaction(&tx.txid).unwrap().unwrap();
+        let risk = wallet_tx.zero_conf_risk.unwrap();
+        assert_eq!(risk.fee_rate, tx.fee * 1000 / tx.tx.discount_vsize() as u64);
+        assert!(!risk.below_fee_estimate);
+        assert!(!risk.double_spent);
+        assert_eq!(risk.unconfirmed_ancestors, 0);
+
+        wollet.set_fee_estimate(risk.fee_rate as f32 + 1.0);
+        let wallet_tx = wollet.transactions().unwrap().remove(0);
+        let risk = wallet_tx.zero_conf_risk.unwrap();
+        assert!(risk.below_fee_estimate);
+        assert!(risk.score >= 30);
+    }
+
+    #[test]
+    fn test_zero_conf_risk_score() {
+        assert_eq!(ZeroConfRisk::new(100, false, false, false, 0).score, 0);
+        assert_eq!(ZeroConfRisk::new(100, false, true, false, 0).score, 40);
+        assert_eq!(ZeroConfRisk::new(10, true, true, false, 1).score, 80);
+        assert_eq!(ZeroConfRisk::new(10, true, true, false, 10).score, 99);
+        assert_eq!(ZeroConfRisk::new(100, false, false, true, 0).score, 100);
+    }
+}
@@ -252,7 +252,6 @@ fn unsupported_descriptor() {
     let view_key = generate_view_key();
     let desc_p2pkh = format!("ct({view_key},elpkh({xpub1}/*))");
     let desc_p2sh = format!("ct({view_key},elsh(multi(2,{xpub1}/*,{xpub2}/*)))",);
-    let desc_p2tr = format!("ct({view_key},eltr({xpub1}/*))");
     let desc_no_wildcard = format!("ct({view_key},elwpkh({xpub1}))");
 
     let desc_multi_path_1 = format!("ct({view_key},elwpkh({xpub1}/<0;1;2>/*))");
@@ -262,9 +261,8 @@ fn unsupported_descriptor() {
     let desc_multi_path_5 = format!("ct({view_key},elwsh(multi(2,{xpub1}/<0;1>/*,{xpub2}/0/*)))");
 
     for (desc, err) in [
-        (desc_p2pkh, Error::UnsupportedDescriptorNonV0),
-        (desc_p2sh, Error::UnsupportedDescriptorNonV0),
-        (desc_p2tr, Error::UnsupportedDescriptorNonV0),
+        (desc_p2pkh, Error::UnsupportedDescriptorWitnessVersion),
+        (desc_p2sh, Error::UnsupportedDescriptorWitnessVersion),
         (
             desc_no_wildcard,
             Error::UnsupportedDescriptorWithoutWildcard,
@@ -278,10 +276,6 @@ fn unsupported_descriptor() {
         new_unsupported_wallet(&desc, err);
     }
 
-    let bare_key = "0337cceec0beea0232ebe14cba0197a9fbd45fcf2ec946749de920e71434c2b904";
-    let desc_bare = format!("ct({},elwpkh({}/*))", bare_key, signer1.xpub());
-    new_unsupported_wallet(&desc_bare, Error::BlindingBareUnsupported);
-
     let xprv = generate_xprv();
     let desc_view_multi = format!("ct({}/<0;1>,elwpkh({}))", xprv, signer1.xpub());
     new_unsupported_wallet(&desc_view_multi, Error::BlindingViewMultiUnsupported);
@@ -290,6 +284,24 @@ fn unsupported_descriptor() {
     new_unsupported_wallet(&desc_view_wildcard, Error::BlindingViewWildcardUnsupported);
 }
 
+#[test]
+fn bare_blinding_key() {
+    let server = setup();
+
+    let signer = generate_signer();
+    let bare_key = "0337cceec0beea0232ebe14cba0197a9fbd45fcf2ec946749de920e71434c2b904";
+    let desc = format!("ct({bare_key},elwpkh({}/*))", signer.xpub());
+
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    assert!(!wallet.wollet.wollet_descriptor().can_unblind());
+
+    // The wallet can be watched, but amounts and assets cannot be unblinded
+    wallet.fund_blinded(&server, 10_000);
+    assert!(wallet.wollet.transactions().unwrap().is_empty());
+    assert!(wallet.wollet.utxos().unwrap().is_empty());
+}
+
 #[test]
 fn address() {
     let server = setup();
@@ -828,6 +840,160 @@ fn multisig_flow() {
 
     // * Multisig Sign: Complete
 }
+
+#[test]
+fn multisig_2of3_flow() {
+    // Multisig with key origins and multipath, signed by software signers only
+    let server = setup();
+    let signer1 = AnySigner::Software(generate_signer());
+    let signer2 = AnySigner::Software(generate_signer());
+    let signer3 = AnySigner::Software(generate_signer());
+    let signers = [&signer1, &signer2, &signer3];
+    let desc = test_wollet::multisig_desc(&signers, 2);
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    let fingerprints: HashSet<_> = signers.iter().map(|s| s.fingerprint().unwrap()).collect();
+    assert_eq!(
+        wallet.wollet.signers().into_iter().collect::<HashSet<_>>(),
+        fingerprints
+    );
+
+    wallet.fund_btc(&server);
+    let asset = wallet.fund_asset(&server);
+
+    let node_address = server.elementsd_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+
+    // Inputs have the witness script and the key origin of every cosigner
+    for input in pset.inputs() {
+        assert!(input.witness_script.is_some());
+        let input_fingerprints: HashSet<_> =
+            input.bip32_derivation.values().map(|(f, _)| *f).collect();
+        assert_eq!(input_fingerprints, fingerprints);
+    }
+    // So does the change output
+    let change = pset
+        .outputs()
+        .iter()
+        .find(|o| !o.bip32_derivation.is_empty())
+        .unwrap();
+    assert!(change.witness_script.is_some());
+    assert_eq!(change.bip32_derivation.len(), 3);
+
+    // Any 2 of 3 signers can sign
+    wallet.sign(&signer1, &mut pset);
+    wallet.sign(&signer3, &mut pset);
+    wallet.send(&mut pset);
+
+    wallet.send_asset(&[&signer2, &signer3], &node_address, &asset, None);
+    wallet.send_btc(&[&signer1, &signer2], None, None);
+}
+
+#[test]
+fn input_sighashes() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({view_key},elwpkh({}/*))", signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.fund_btc(&server);
+
+    let node_address = server.elementsd_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let sighashes = wallet.wollet.input_sighashes(&pset).unwrap();
+    assert_eq!(sighashes.len(), pset.inputs().len());
+
+    // Signatures produced by the signer are valid for the returned messages
+    wallet.sign(&signer, &mut pset);
+    for (sighash, input) in sighashes.iter().zip(pset.inputs()) {
+        assert_eq!(
+            sighash.kind,
+            SighashKind::Ecdsa(elements::EcdsaSighashType::All)
+        );
+        assert_eq!(sighash.keys.len(), 1);
+        assert_eq!(sighash.keys[0].1 .0, signer.fingerprint());
+        let (pk, sig) = input.partial_sigs.iter().next().unwrap();
+        assert_eq!(pk, &sighash.keys[0].0);
+        let sig = lwk_wollet::secp256k1::ecdsa::Signature::from_der(&sig[..sig.len() - 1]).unwrap();
+        lwk_wollet::EC
+            .verify_ecdsa(&sighash.message, &sig, &pk.inner)
+            .unwrap();
+    }
+}
+
+#[test]
+fn add_signature() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({view_key},elwpkh({}/*))", signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.fund_btc(&server);
+
+    let node_address = server.elementsd_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+
+    // Simulate an external signer returning bare signatures
+    let mut signed = pset.clone();
+    wallet.sign(&signer, &mut signed);
+    for (index, input) in signed.inputs().iter().enumerate() {
+        let (pk, sig) = input.partial_sigs.iter().next().unwrap();
+        let der = &sig[..sig.len() - 1];
+
+        // A signature for another input is rejected
+        let other = (index + 1) % signed.inputs().len();
+        if other != index {
+            let err = wallet
+                .wollet
+                .add_signature(&mut pset, other, *pk, der)
+                .unwrap_err();
+            assert!(matches!(err, lwk_wollet::Error::InvalidSignature));
+        }
+
+        if index % 2 == 0 {
+            wallet
+                .wollet
+                .add_signature(&mut pset, index, *pk, der)
+                .unwrap();
+        } else {
+            wallet
+                .wollet
+                .add_signature(&mut pset, index, *pk, sig)
+                .unwrap();
+        }
+        assert_eq!(pset.inputs()[index].partial_sigs, input.partial_sigs);
+    }
+    let err = wallet
+        .wollet
+        .add_signature(
+            &mut pset,
+            signed.inputs().len(),
+            lwk_wollet::bitcoin::PublicKey::new(signer.xpub().public_key),
+            &[],
+        )
+        .unwrap_err();
+    assert!(matches!(err, lwk_wollet::Error::Sighash(_)));
+
+    wallet.send(&mut pset);
+}
+
 #[test]
 fn jade_sign_wollet_pset() {
     let server = setup();
@@ -1607,9 +1773,30 @@ fn test_unblinded_utxo() {
     assert_eq!(w.balance(&policy_asset), 0);
 }
 
+#[test]
+fn test_electrum_failover() {
+    let server = setup();
+    let dead: ElectrumUrl = "tcp://127.0.0.1:1".parse().unwrap();
+    let alive = ElectrumUrl::new(&server.electrs.electrum_url, false, false).unwrap();
+    let urls = [dead.clone(), alive.clone()];
+    let mut client = ElectrumClient::with_failover(&urls, ElectrumOptions::default()).unwrap();
+    assert_eq!(client.current_url(), alive);
+    client.keepalive().unwrap();
+    assert_eq!(client.servers()[0].failures, 1);
+    assert_eq!(client.servers()[1].failures, 0);
+
+    let signer = generate_signer();
+    let desc = format!("ct(elip151,elwpkh({}/*))", signer.xpub());
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.fund_btc(&server);
+}
+
 #[cfg(feature = "elements_rpc")]
 #[test]
 fn test_elements_rpc() {
+    use lwk_wollet::payment_code::PaymentCodeScanner;
+    use lwk_wollet::secp256k1::SecretKey;
+
     let server = setup();
     assert_eq!(server.elementsd_height(), 101);
     let url = server.elements_rpc_url();
@@ -1638,6 +1825,54 @@ fn test_elements_rpc() {
     server.elementsd_generate(1);
     let utxos = elements_rpc_client.confirmed_utxos(&wd, 20).unwrap();
     assert_eq!(utxos.len(), 1);
+
+    // Scan the wallet with the node as backend
+    let mut elements_rpc_client = elements_rpc_client.with_scan_from(100);
+    let mut wollet = Wollet::without_persist(network, wd.clone()).unwrap();
+    let update = elements_rpc_client.full_scan(&wollet).unwrap().unwrap();
+    wollet.apply_update(update).unwrap();
+    assert_eq!(wollet.utxos().unwrap().len(), 1);
+    assert_eq!(wollet.balance().unwrap(), wallet.wollet.balance().unwrap());
+    assert_eq!(wollet.tip().height(), 102);
+
+    // Unconfirmed transactions are seen from the mempool
+    let address = wollet.address(None).unwrap();
+    let txid = server.elementsd_sendtoaddress(address.address(), 10_000, None);
+    let update = elements_rpc_client.full_scan(&wollet).unwrap().unwrap();
+    wollet.apply_update(update).unwrap();
+    let tx = wollet.transaction(&txid).unwrap().unwrap();
+    assert_eq!(tx.height, None);
+    assert_eq!(wollet.utxos().unwrap().len(), 2);
+
+    // The node rejects transactions already in the mempool
+    let r = elements_rpc_client.test_broadcast(&tx.tx).unwrap().unwrap();
+    assert!(!r.allowed);
+    assert!(r.reject_reason.is_some());
+    let r = wollet
+        .test_broadcast_with(&tx.tx, &elements_rpc_client)
+        .unwrap();
+    assert_eq!(r.reject_reason.as_deref(), Some("txn-already-known"));
+
+    // Payments to a payment code are found while scanning
+    let scan_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+    let spend_key = SecretKey::from_slice(&[2u8; 32]).unwrap();
+    let scanner = PaymentCodeScanner::new(scan_key, spend_key.public_key(&EC));
+    let code = scanner.payment_code();
+    let mut elements_rpc_client = elements_rpc_client.with_payment_code_scanner(scanner);
+    let ephemeral = SecretKey::from_slice(&[3u8; 32]).unwrap();
+    let payment = code
+        .payment(&ephemeral, 0, network.address_params())
+        .unwrap();
+    let notification = &payment.notification_script.as_bytes()[2..]; // skip OP_RETURN and push
+    let txid = server.elementsd_sendtoaddress_with_data(&payment.address, 20_000, notification);
+    server.elementsd_generate(1);
+    elements_rpc_client.full_scan(&wollet).unwrap();
+    let received = elements_rpc_client.payment_code_received();
+    assert_eq!(received.len(), 1);
+    let (height, received) = &received[0];
+    assert_eq!(*height, server.elementsd_height() as u32);
+    assert_eq!(received.outpoint.txid, txid);
+    assert_eq!(received.unblinded.value, 20_000);
 }
 
 #[cfg(feature = "esplora")]
@@ -1664,6 +1899,69 @@ fn test_clients() {
     assert_eq!(esplora_waterfalls_client.capabilities().len(), 1);
 }
 
+#[test]
+fn test_tx_status() {
+    let server = setup_with_esplora();
+
+    let electrum_url = ElectrumUrl::new(&server.electrs.electrum_url, false, false).unwrap();
+    let mut electrum_client = ElectrumClient::new(&electrum_url).unwrap();
+    let esplora_url = format!("http://{}", server.electrs.esplora_url.as_ref().unwrap());
+    let mut esplora_client =
+        clients::blocking::EsploraClient::new(&esplora_url, ElementsNetwork::default_regtest())
+            .unwrap();
+
+    let address = server.elementsd_getnewaddress();
+    let txid = server.elementsd_sendtoaddress(&address, 1_000_000, None);
+    let timeout = std::time::Duration::from_secs(60);
+    let unknown = elements::Txid::from_str(&"0".repeat(64)).unwrap();
+
+    for client in [
+        &mut electrum_client as &mut dyn TxStatusBackend,
+        &mut esplora_client,
+    ] {
+        assert_eq!(client.status(&unknown), TxStatus::NotFound);
+        assert_eq!(client.wait(&txid, 0, timeout), TxStatus::Unconfirmed);
+    }
+
+    server.elementsd_generate(1);
+    let height = server.elementsd_height() as u32;
+    for client in [
+        &mut electrum_client as &mut dyn TxStatusBackend,
+        &mut esplora_client,
+    ] {
+        assert_eq!(
+            client.wait(&txid, 1, timeout),
+            TxStatus::Confirmed { height }
+        );
+    }
+}
+
+/// Object safe wrapper of the [`BlockchainBackend`] confirmation methods
+trait TxStatusBackend {
+    fn status(&self, txid: &elements::Txid) -> TxStatus;
+    fn wait(
+        &mut self,
+        txid: &elements::Txid,
+        confirmations: u32,
+        timeout: std::time::Duration,
+    ) -> TxStatus;
+}
+
+impl<B: BlockchainBackend> TxStatusBackend for B {
+    fn status(&self, txid: &elements::Txid) -> TxStatus {
+        self.tx_status(txid).unwrap()
+    }
+    fn wait(
+        &mut self,
+        txid: &elements::Txid,
+        confirmations: u32,
+        timeout: std::time::Duration,
+    ) -> TxStatus {
+        self.wait_for_confirmation(txid, confirmations, timeout)
+            .unwrap()
+    }
+}
+
 fn wait_esplora_tx_update(client: &mut blocking::EsploraClient, wollet: &Wollet) -> Update {
     for _ in 0..50 {
         let update = client.full_scan(wollet).unwrap();
@@ -1995,6 +2293,62 @@ fn test_manual_coin_selection() {
     assert!(matches!(err, Error::ManualCoinSelectionOnlyLbtc));
 }
 
+#[test]
+fn test_random_coin_selection() {
+    let server = setup();
+
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({view_key},elwpkh({}/*))", signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut w = TestWollet::new(client, &desc);
+    let node_address = server.elementsd_getnewaddress();
+    let policy_asset = w.policy_asset();
+
+    // Two UTXOs on the same address and two on different addresses
+    let address = w.address();
+    w.fund(&server, 500_000, Some(address.clone()), None);
+    w.fund(&server, 500_000, Some(address.clone()), None);
+    w.fund(&server, 500_000, None, None);
+    w.fund(&server, 500_000, None, None);
+    let asset = w.fund_asset(&server);
+
+    for _ in 0..5 {
+        let mut pset = w
+            .tx_builder()
+            .add_recipient(&node_address, 10_000, policy_asset)
+            .unwrap()
+            .add_recipient(&node_address, 1, asset)
+            .unwrap()
+            .coin_selection(CoinSelection::RandomDraw)
+            .finish()
+            .unwrap();
+        // The UTXOs on the same address are spent together
+        let spent: Vec<_> = pset
+            .inputs()
+            .iter()
+            .map(|i| OutPoint::new(i.previous_txid, i.previous_output_index))
+            .collect();
+        let same_address: Vec<_> = w
+            .wollet
+            .utxos()
+            .unwrap()
+            .into_iter()
+            .filter(|u| u.address.script_pubkey() == address.script_pubkey())
+            .map(|u| spent.contains(&u.outpoint))
+            .collect();
+        assert_eq!(same_address.len(), 2);
+        assert_eq!(same_address[0], same_address[1]);
+
+        // Not all the L-BTC UTXOs are needed, one group and the asset UTXO are enough
+        assert!(pset.inputs().len() <= 3);
+
+        signer.sign(&mut pset).unwrap();
+        let tx = w.wollet.finalize(&mut pset).unwrap();
+        assert!(server.elementsd_testmempoolaccept(&serialize(&tx).to_hex()));
+    }
+}
+
 #[ignore = "This test connects to liquid testnet"]
 #[test]
 fn test_liquid_testnet() {
@@ -2167,3 +2521,544 @@ fn test_liquidex() {
 
     // TODO: check fees
 }
+
+#[test]
+fn regtest_time_and_height() {
+    use elements::hashes::{hash160, Hash};
+    use lwk_wollet::secp256k1::SecretKey;
+    use lwk_wollet::swap::{SwapKind, SwapScript};
+
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({view_key},elwpkh({}/*))", signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.wait_height(101);
+
+    server.elementsd_generate_to_height(110);
+    assert_eq!(server.elementsd_height(), 110);
+    server.elementsd_generate_to_height(105);
+    assert_eq!(server.elementsd_height(), 110);
+    wallet.wait_height(110);
+
+    let time = server.elementsd_block_time();
+    server.elementsd_generate_with_interval(3, 3600);
+    assert_eq!(server.elementsd_height(), 113);
+    assert_eq!(server.elementsd_block_time(), time + 3 * 3600);
+    wallet.wait_height(113);
+
+    let median_time = server.elementsd_median_time();
+    server.elementsd_advance_median_time(86400);
+    assert!(server.elementsd_median_time() >= median_time + 86400);
+    server.elementsd_setmocktime(0);
+
+    // Lock funds in an output spendable only after a height, with the refund path of a swap
+    let height = server.elementsd_height() as u32;
+    let refund_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+    let swap = SwapScript {
+        kind: SwapKind::Submarine,
+        preimage_hash: hash160::Hash::hash(&[2u8; 32]),
+        claim_pubkey: SecretKey::from_slice(&[3u8; 32]).unwrap().public_key(&EC),
+        refund_pubkey: refund_key.public_key(&EC),
+        timeout: height + 10,
+        blinding_key: Some(SecretKey::from_slice(&[4u8; 32]).unwrap()),
+    };
+    let params = ElementsNetwork::default_regtest().address_params();
+    let txid = server.elementsd_sendtoaddress(&swap.address(params), 100_000, None);
+    server.elementsd_generate(1);
+    let lockup_tx = wallet.client.get_transactions(&[txid]).unwrap().remove(0);
+
+    let address = wallet.address();
+    let pset = swap.refund_pset(&lockup_tx, &address, 1_000).unwrap();
+    let tx = swap.finalize_refund(&pset, &refund_key).unwrap();
+    let tx_hex = serialize(&tx).to_hex();
+
+    // the output can't be spent before the timeout
+    server.elementsd_generate_to_height(swap.timeout as u64 - 1);
+    assert!(!server.elementsd_testmempoolaccept(&tx_hex));
+    server.elementsd_generate_to_height(swap.timeout as u64);
+    assert!(server.elementsd_testmempoolaccept(&tx_hex));
+
+    let txid = server.elementsd_sendrawtransaction(&tx_hex);
+    assert_eq!(txid, tx.txid().to_string());
+    server.elementsd_generate(1);
+    wallet.wait_height(swap.timeout + 1);
+    assert_eq!(wallet.balance(&wallet.policy_asset()), 99_000);
+}
+
+#[test]
+fn finalize_partial() {
+    let server = setup();
+    let signer1 = AnySigner::Software(generate_signer());
+    let signer2 = AnySigner::Software(generate_signer());
+    let desc = test_wollet::multisig_desc(&[&signer1, &signer2], 2);
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.fund_btc(&server);
+
+    let address = server.elementsd_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&address, 10_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let n_inputs = pset.inputs().len();
+
+    // No signatures
+    let report = wallet.wollet.finalize_partial(&mut pset);
+    assert!(report.finalized.is_empty());
+    assert_eq!(report.not_finalized.len(), n_inputs);
+    for (_, failure) in report.not_finalized.iter() {
+        assert_eq!(failure, &FinalizeFailure::MissingSignature);
+    }
+    let err = wallet.wollet.finalize(&mut pset.clone()).unwrap_err();
+    assert!(matches!(err, lwk_wollet::Error::CannotFinalize(_)));
+
+    // One signature out of two, the partial signatures are kept for the next round
+    wallet.sign(&signer1, &mut pset);
+    let report = wallet.wollet.finalize_partial(&mut pset);
+    assert!(!report.is_complete());
+    assert!(pset.inputs().iter().all(|i| i.partial_sigs.len() == 1));
+
+    // All signatures
+    wallet.sign(&signer2, &mut pset);
+    let report = wallet.wollet.finalize_partial(&mut pset);
+    assert!(report.is_complete());
+    assert_eq!(report.finalized, (0..n_inputs).collect::<Vec<_>>());
+
+    // Already finalized inputs are reported as finalized
+    let report = wallet.wollet.finalize_partial(&mut pset);
+    assert!(report.is_complete());
+    let tx = wallet.wollet.finalize(&mut pset).unwrap();
+    assert!(server.elementsd_testmempoolaccept(&serialize(&tx).to_hex()));
+}
+
+#[test]
+fn taproot() {
+    let server = setup();
+    let genesis_hash = server.elementsd_genesis_hash();
+    let view_key = generate_view_key();
+    let signer_without_genesis = generate_signer();
+    let mut signer = signer_without_genesis.clone();
+    signer.set_genesis_hash(genesis_hash);
+    let xpub = signer.xpub();
+    let signer = AnySigner::Software(signer);
+    let other = generate_signer();
+
+    // key path and script path spends
+    let key_path = format!("ct({view_key},eltr({xpub}/*))");
+    let script_path = format!("ct({view_key},eltr({}/*,pk({xpub}/*)))", other.xpub());
+    for desc in [key_path, script_path] {
+        let client = test_client_electrum(&server.electrs.electrum_url);
+        let mut wallet = TestWollet::new(client, &desc);
+
+        // On regtest the genesis hash must be provided to compute taproot sighashes
+        let config = Config::new(ElementsNetwork::default_regtest())
+            .unwrap()
+            .with_genesis_hash(genesis_hash);
+        let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+        wallet.wollet = Wollet::with_config(config, NoPersist::new(), descriptor).unwrap();
+        wallet.sync();
+        wallet.fund_btc(&server);
+
+        let mut pset = wallet
+            .tx_builder()
+            .add_lbtc_recipient(&wallet.address(), 10_000)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(generate_signer().sign(&mut pset).unwrap(), 0);
+        let err = signer_without_genesis.sign(&mut pset).unwrap_err();
+        assert!(matches!(err, SignError::MissingGenesisHash));
+
+        // The PSET can be finalized without the wallet, knowing the genesis hash
+        let mut signed = pset.clone();
+        wallet.sign(&signer, &mut signed);
+        let tx = finalize_with_genesis(&mut signed, genesis_hash).unwrap();
+        assert!(server.elementsd_testmempoolaccept(&serialize(&tx).to_hex()));
+
+        wallet.send_btc(&[&signer], None, None);
+    }
+}
+
+#[test]
+fn verify_proofs() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+
+    let config = Config::new(ElementsNetwork::default_regtest())
+        .unwrap()
+        .with_verify_proofs(true);
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    wallet.wollet = Wollet::with_config(config, NoPersist::new(), descriptor).unwrap();
+    wallet.sync();
+    wallet.fund_btc(&server);
+    let signer = AnySigner::Software(signer);
+    wallet.send_btc(&[&signer], None, None);
+
+    // Both the incoming and the outgoing transactions have their proofs verified
+    let txs = wallet.wollet.transactions().unwrap();
+    assert_eq!(txs.len(), 2);
+    assert!(txs.iter().all(|tx| tx.verified == Some(true)));
+}
+
+#[test]
+fn birthday() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+
+    wallet.fund_btc(&server);
+    server.elementsd_generate(1);
+    let height = wallet.tip().height() + 1;
+    wallet.wait_height(height);
+    server.elementsd_generate(1);
+    wallet.wait_height(height + 1);
+    assert_eq!(wallet.wollet.transactions().unwrap().len(), 1);
+
+    // A wallet with a later birthday doesn't see the funding transaction
+    let config = Config::new(ElementsNetwork::default_regtest())
+        .unwrap()
+        .with_birthday(height + 1);
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    wallet.wollet = Wollet::with_config(config, NoPersist::new(), descriptor).unwrap();
+    wallet.sync();
+    assert!(wallet.wollet.transactions().unwrap().is_empty());
+
+    // Transactions after the birthday are seen, using an address not used before
+    assert_eq!(wallet.address_result(None).index(), 1);
+    wallet.fund_btc(&server);
+    assert_eq!(wallet.wollet.transactions().unwrap().len(), 1);
+}
+
+#[test]
+fn gap_limit() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+
+    // Receive on an address beyond the default gap limit
+    let config = Config::new(ElementsNetwork::default_regtest())
+        .unwrap()
+        .with_gap_limit(40);
+    wallet.wollet = Wollet::with_config(config, NoPersist::new(), descriptor.clone()).unwrap();
+    let address = wallet.address_result(Some(30)).address().clone();
+    wallet.fund(&server, 10_000, Some(address), None);
+    assert_eq!(wallet.wollet.gap_limit_warning(), Some(30));
+
+    // With the default gap limit the funds are not found
+    let config = Config::new(ElementsNetwork::default_regtest()).unwrap();
+    wallet.wollet = Wollet::with_config(config, NoPersist::new(), descriptor).unwrap();
+    wallet.sync();
+    assert!(wallet.wollet.transactions().unwrap().is_empty());
+}
+
+#[test]
+fn electrum_watch() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    let mut wollet =
+        Wollet::without_persist(ElementsNetwork::default_regtest(), descriptor).unwrap();
+    let address = wollet.address(None).unwrap().address().clone();
+    let mut client = test_client_electrum(&server.electrs.electrum_url);
+    let (scanned_tx, scanned_rx) = std::sync::mpsc::channel();
+
+    let watcher = std::thread::spawn(move || {
+        let interval = std::time::Duration::from_millis(200);
+        client
+            .watch(&mut wollet, interval, |w| {
+                let _ = scanned_tx.send(());
+                if w.transactions().unwrap().is_empty() {
+                    std::ops::ControlFlow::Continue(())
+                } else {
+                    std::ops::ControlFlow::Break(())
+                }
+            })
+            .unwrap();
+        wollet
+    });
+
+    // Wait for the first scan, after which the watcher is subscribed to the wallet scripts
+    scanned_rx
+        .recv_timeout(std::time::Duration::from_secs(60))
+        .expect("first scan of the watcher");
+
+    // The watcher stops once it receives the notification for the funding transaction
+    let txid = server.elementsd_sendtoaddress(&address, 10_000, None);
+    let wollet = watcher.join().unwrap();
+    let txs = wollet.transactions().unwrap();
+    assert_eq!(txs.len(), 1);
+    assert_eq!(txs[0].txid, txid);
+}
+
+#[test]
+fn scan_with_concise_state() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.fund_btc(&server);
+
+    // The wallet is offline, the scan happens on another machine using only the wallet state
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    let mut offline =
+        Wollet::without_persist(ElementsNetwork::default_regtest(), descriptor).unwrap();
+    let state = serde_json::to_string(&offline.state()).unwrap();
+
+    let state: WolletConciseState = serde_json::from_str(&state).unwrap();
+    let mut online = test_client_electrum(&server.electrs.electrum_url);
+    let update = online.full_scan(&state).unwrap().unwrap();
+    let update = update.serialize().unwrap();
+
+    offline
+        .apply_update(Update::deserialize(&update).unwrap())
+        .unwrap();
+    assert_eq!(offline.balance().unwrap(), wallet.wollet.balance().unwrap());
+    assert_eq!(offline.transactions().unwrap().len(), 1);
+}
+
+#[test]
+fn explicit_wollet() {
+    let server = setup();
+    let signer = generate_signer();
+    let desc = format!("elwpkh({}/*)", signer.xpub());
+    let mut client = test_client_electrum(&server.electrs.electrum_url);
+    let config = Config::new(ElementsNetwork::default_regtest()).unwrap();
+    let mut wollet = ExplicitWollet::new(config, NoPersist::new(), &desc).unwrap();
+
+    let address = wollet.address(0).unwrap();
+    let satoshi = 10_000;
+    let txid = server.elementsd_sendtoaddress(&address, satoshi, None);
+    for _ in 0..120 {
+        if let Some(update) = client.full_scan(&wollet).unwrap() {
+            wollet.apply_update(update).unwrap();
+        }
+        if wollet.txids().iter().any(|(t, _)| *t == txid) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+
+    let utxos = wollet.utxos().unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(utxos[0].outpoint.txid, txid);
+    let policy_asset = ElementsNetwork::default_regtest().policy_asset();
+    assert_eq!(wollet.balance().unwrap().get(&policy_asset), Some(&satoshi));
+}
+
+#[test]
+fn memory_budget() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    for _ in 0..3 {
+        wallet.fund_btc(&server);
+    }
+
+    // With a tiny budget every scan downloads a single transaction
+    let config = Config::new(ElementsNetwork::default_regtest())
+        .unwrap()
+        .with_memory_budget(1);
+    let mut wollet =
+        Wollet::with_config(config.clone(), NoPersist::new(), descriptor.clone()).unwrap();
+    let mut client = test_client_electrum(&server.electrs.electrum_url);
+    for expected in 1..=3 {
+        let update = client.full_scan(&wollet).unwrap().unwrap();
+        assert_eq!(update.new_txs.txs.len(), 1);
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.transactions().unwrap().len(), expected);
+    }
+    assert!(client.full_scan(&wollet).unwrap().is_none());
+    assert_eq!(wollet.balance().unwrap(), wallet.wollet.balance().unwrap());
+
+    // The helper scans until all the transactions are downloaded
+    let mut wollet = Wollet::with_config(config, NoPersist::new(), descriptor).unwrap();
+    full_scan_with_electrum_client(&mut wollet, &mut client).unwrap();
+    assert_eq!(wollet.balance().unwrap(), wallet.wollet.balance().unwrap());
+}
+
+#[cfg(feature = "esplora")]
+#[tokio::test]
+async fn test_async_wollet() {
+    let server = setup_with_esplora();
+    let url = format!("http://{}", server.electrs.esplora_url.as_ref().unwrap());
+    let network = ElementsNetwork::default_regtest();
+    let client = clients::asyncr::EsploraClient::new(network, &url);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let descriptor = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let descriptor: WolletDescriptor = descriptor.parse().unwrap();
+    let wollet = Wollet::new(network, NoPersist::new(), descriptor).unwrap();
+    let mut wollet = clients::asyncr::AsyncWollet::new(wollet, client);
+
+    assert!(wollet.full_scan().await.unwrap());
+    let tip = wollet.tip().await.unwrap();
+    assert_eq!(wollet.wollet().tip().height(), tip.height);
+
+    let address = wollet.wollet().address(None).unwrap().address().clone();
+    let txid = server.elementsd_sendtoaddress(&address, 10_000, None);
+    for _ in 0..50 {
+        wollet.full_scan().await.unwrap();
+        if wollet.wollet().transaction(&txid).unwrap().is_some() {
+            break;
+        }
+        clients::asyncr::async_sleep(200).await;
+    }
+    let balance = wollet.wollet().balance().unwrap();
+    assert_eq!(balance.get(&network.policy_asset()), Some(&10_000));
+
+    // Send back to the node and broadcast asynchronously
+    let node_address = server.elementsd_getnewaddress();
+    let mut pset = wollet
+        .wollet()
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 1_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    signer.sign(&mut pset).unwrap();
+    let tx = wollet.wollet().finalize(&mut pset).unwrap();
+    assert_eq!(wollet.broadcast(&tx).await.unwrap(), tx.txid());
+}
+
+#[cfg(feature = "esplora")]
+#[tokio::test]
+async fn test_esplora_concurrency() {
+    let server = setup_with_esplora();
+    let url = format!("http://{}", server.electrs.esplora_url.as_ref().unwrap());
+    let network = ElementsNetwork::default_regtest();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let descriptor = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let descriptor: WolletDescriptor = descriptor.parse().unwrap();
+
+    let mut sequential = Wollet::new(network, NoPersist::new(), descriptor.clone()).unwrap();
+    let mut client = clients::asyncr::EsploraClient::new(network, &url);
+    for i in 0..3 {
+        let address = sequential.address(Some(i)).unwrap().address().clone();
+        server.elementsd_sendtoaddress(&address, 10_000, None);
+    }
+    server.elementsd_generate(1);
+    for _ in 0..50 {
+        if let Some(update) = client.full_scan(&sequential).await.unwrap() {
+            sequential.apply_update(update).unwrap();
+        }
+        if sequential.transactions().unwrap().len() == 3 {
+            break;
+        }
+        clients::asyncr::async_sleep(200).await;
+    }
+    assert_eq!(sequential.transactions().unwrap().len(), 3);
+
+    // Concurrent requests give the same result
+    let mut concurrent = Wollet::new(network, NoPersist::new(), descriptor).unwrap();
+    let mut client = clients::asyncr::EsploraClientBuilder::new(&url, network)
+        .concurrency(8)
+        .build();
+    let update = client.full_scan(&concurrent).await.unwrap().unwrap();
+    concurrent.apply_update(update).unwrap();
+    assert_eq!(concurrent.status(), sequential.status());
+    assert_eq!(concurrent.balance().unwrap(), sequential.balance().unwrap());
+}
+
+#[test]
+fn multisig_rotation() {
+    let server = setup();
+    let signer1 = AnySigner::Software(generate_signer());
+    let signer2 = AnySigner::Software(generate_signer());
+    let signer3 = AnySigner::Software(generate_signer());
+    let desc = test_wollet::multisig_desc(&[&signer1, &signer2], 2);
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut wallet = TestWollet::new(client, &desc);
+    wallet.fund_btc(&server);
+    let asset = wallet.fund_asset(&server);
+
+    // Replace the second signer with the third one
+    let new_desc = test_wollet::multisig_desc(&[&signer1, &signer3], 2);
+    let client = test_client_electrum(&server.electrs.electrum_url);
+    let mut new_wallet = TestWollet::new(client, &new_desc);
+    let rotation =
+        rotation::analyze(&wallet.wollet, &new_wallet.wollet.wollet_descriptor()).unwrap();
+    assert_eq!(rotation.signers().added.len(), 1);
+    assert_eq!(rotation.signers().removed.len(), 1);
+    assert_eq!(rotation.signers().kept.len(), 1);
+    assert_eq!(rotation.utxos().len(), 2);
+    assert!(rotation::verify_migrated(&wallet.wollet).is_err());
+
+    // The old signers move the funds to the new descriptor
+    let mut pset = rotation.migration_pset(&wallet.wollet, None).unwrap();
+    wallet.sign(&signer1, &mut pset);
+    wallet.sign(&signer2, &mut pset);
+    wallet.send(&mut pset);
+    rotation::verify_migrated(&wallet.wollet).unwrap();
+
+    new_wallet.sync();
+    assert_eq!(new_wallet.balance(&asset), rotation.balance()[&asset]);
+    assert!(new_wallet.balance(&new_wallet.wollet.policy_asset()) > 0);
+}
+
+#[test]
+fn scan_txid() {
+    let server = setup();
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let descriptor: WolletDescriptor = add_checksum(&desc).parse().unwrap();
+    let network = ElementsNetwork::default_regtest();
+    let mut wollet = Wollet::without_persist(network, descriptor).unwrap();
+    let mut client = test_client_electrum(&server.electrs.electrum_url);
+
+    // A deposit beyond the gap limit is not found by the full scan
+    let address = wollet.address(Some(30)).unwrap().address().clone();
+    let txid = server.elementsd_sendtoaddress(&address, 10_000, None);
+    server.elementsd_generate(1);
+    for _ in 0..120 {
+        if client.get_transactions(&[txid]).is_ok() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+    assert!(matches!(
+        client.scan_txid(&wollet, txid, 10),
+        Err(Error::TxNotInWallet(t, 10)) if t == txid
+    ));
+
+    // Deriving more scripts the deposit is found
+    let mut update = None;
+    for _ in 0..120 {
+        update = client.scan_txid(&wollet, txid, 100).unwrap();
+        if update.as_ref().is_some_and(|u| !u.new_txs.txs.is_empty()) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+    wollet.apply_update(update.unwrap()).unwrap();
+    assert!(wollet.transaction(&txid).unwrap().is_some());
+    assert_eq!(wollet.balance().unwrap()[&network.policy_asset()], 10_000);
+    assert_eq!(wollet.address(None).unwrap().index(), 31);
+
+    // Scanning it again finds nothing new
+    assert!(client.scan_txid(&wollet, txid, 100).unwrap().is_none());
+}
@@ -205,6 +205,17 @@ impl<C: BlockchainBackend> TestWollet<C> {
         assert_eq!(explicit_utxos_after, explicit_utxos_before + 1);
     }
 
+    /// Send to the wallet a confidential output that it cannot unblind, as with a bare blinding key
+    pub fn fund_blinded(&mut self, server: &TestElectrumServer, satoshi: u64) {
+        let blinded_before = self.wollet.blinded_outputs().len();
+
+        let txid = server.elementsd_sendtoaddress(&self.address(), satoshi, None);
+        self.wait_for_tx_outside_list(&txid);
+
+        assert!(self.wollet.txids().iter().any(|(t, _)| *t == txid));
+        assert_eq!(self.wollet.blinded_outputs().len(), blinded_before + 1);
+    }
+
     /// Send 10_000 satoshi to self with default fee rate.
     ///
     /// To specify a custom fee rate pass Some in `fee_rate`